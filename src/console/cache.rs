@@ -0,0 +1,173 @@
+//! # 管理缓存
+//!
+//! `dadk cache stats`输出缓存根目录中各类缓存项的数量和占用的空间。
+//!
+//! `dadk cache prune`回收缓存：`--unreferenced`删除不属于当前配置文件中任何任务的缓存项，
+//! `--max-age`删除超过指定时间没有被访问的缓存项，`--max-size`按最近最少使用的顺序删除缓存项，
+//! 直到缓存的总大小不超过限制。属于设置了`build_once`的任务的缓存项只会因为`--unreferenced`被删除。
+//! 指定`--dry-run`时只输出会被删除的缓存项。规则的细节见`executor::gc`。
+//!
+//! ```bash
+//! dadk cache stats
+//! dadk -c <配置文件目录> cache prune --unreferenced --max-age 30d --max-size 50G --dry-run
+//! ```
+
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use chrono::Utc;
+use clap::{Args, Subcommand};
+use log::{info, warn};
+
+use crate::{
+    executor::{
+        gc::{self, PruneOptions},
+        limits::parse_size,
+    },
+    parser::task::DADKTask,
+};
+
+#[derive(Debug, Subcommand, Clone, PartialEq, Eq)]
+pub enum CacheCommand {
+    /// 回收缓存
+    Prune(PruneArg),
+    /// 输出各类缓存项的数量和占用的空间
+    Stats,
+}
+
+#[derive(Debug, Args, Clone, Default, PartialEq, Eq)]
+pub struct PruneArg {
+    /// 删除超过这个时间没有被访问的缓存项，例如`30d`、`12h`（支持s、m、h、d、w后缀，没有后缀时为秒）
+    #[arg(long, value_parser = parse_duration)]
+    pub max_age: Option<Duration>,
+    /// 缓存的总大小的上限，例如`50G`，超过时按最近最少使用的顺序删除缓存项
+    #[arg(long, value_parser = parse_size)]
+    pub max_size: Option<u64>,
+    /// 删除不属于当前配置文件中任何任务的缓存项
+    #[arg(long)]
+    pub unreferenced: bool,
+    /// 只输出会被删除的缓存项，不删除
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+impl PruneArg {
+    pub fn options(&self) -> PruneOptions {
+        return PruneOptions {
+            max_age: self.max_age,
+            max_size: self.max_size,
+            unreferenced: self.unreferenced,
+        };
+    }
+}
+
+/// # 解析时间长度
+///
+/// 支持秒数，以及`s`、`m`、`h`、`d`、`w`后缀，例如`90m`、`30d`
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let digits = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, unit) = s.split_at(digits);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("invalid duration {:?}, expected e.g. 12h or 30d", s))?;
+    let seconds = match unit.trim() {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => {
+            return Err(format!(
+                "invalid duration unit in {:?}, expected one of s, m, h, d, w",
+                s
+            ))
+        }
+    };
+    return number
+        .checked_mul(seconds)
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("duration {:?} is too large", s));
+}
+
+/// # 输出各类缓存项的数量和占用的空间
+pub fn print_stats(root: &Path) -> Result<(), String> {
+    let entries = gc::scan(root).map_err(|e| format!("Failed to scan cache: {}", e))?;
+    println!("# cache root: {}", root.display());
+    println!("{:<12} {:>8} {:>16}", "category", "entries", "bytes");
+    let (mut count, mut size) = (0, 0);
+    for (category, (n, bytes)) in gc::stats(&entries) {
+        println!("{:<12} {:>8} {:>16}", category.to_string(), n, bytes);
+        count += n;
+        size += bytes;
+    }
+    println!("{:<12} {:>8} {:>16}", "total", count, size);
+    return Ok(());
+}
+
+/// # 回收缓存
+///
+/// `tasks`是当前配置文件中的所有任务，用于确定缓存项是否被引用
+pub fn prune(arg: &PruneArg, root: &Path, tasks: &[(PathBuf, DADKTask)]) -> Result<(), String> {
+    let options = arg.options();
+    if options == PruneOptions::default() {
+        return Err(
+            "nothing to prune, specify --unreferenced, --max-age or --max-size".to_string(),
+        );
+    }
+    let mut entries = gc::scan(root).map_err(|e| format!("Failed to scan cache: {}", e))?;
+    let tasks: Vec<DADKTask> = tasks.iter().map(|(_, t)| t.clone()).collect();
+    gc::resolve_references(&mut entries, &tasks);
+    let plan = gc::plan_prune(&entries, &options, Utc::now());
+
+    let (mut freed, mut removed, mut busy) = (0, 0, 0);
+    for (i, reason) in plan.iter() {
+        let entry = &entries[*i];
+        // 持有缓存项的锁直到删除完毕，正在被其他DADK进程使用的缓存项不删除
+        let _lock = match gc::try_lock_entry(root, entry)
+            .map_err(|e| format!("Failed to lock {}: {}", entry.path.display(), e))?
+        {
+            gc::EntryLock::Busy => {
+                println!(
+                    "skip {}/{}: in use by another DADK process",
+                    entry.category.dir(),
+                    entry.name
+                );
+                busy += 1;
+                continue;
+            }
+            gc::EntryLock::Held(lock) => Some(lock),
+            gc::EntryLock::None => None,
+        };
+        println!(
+            "{} {}/{}: {} bytes, last access {} ({})",
+            if arg.dry_run {
+                "would remove"
+            } else {
+                "remove"
+            },
+            entry.category.dir(),
+            entry.name,
+            entry.size,
+            entry.last_access.format("%Y-%m-%d %H:%M:%S"),
+            reason
+        );
+        if !arg.dry_run {
+            gc::remove_entry(root, entry)
+                .map_err(|e| format!("Failed to remove {}: {}", entry.path.display(), e))?;
+        }
+        freed += entry.size;
+        removed += 1;
+    }
+    if arg.dry_run {
+        println!("# {} entries, {} bytes would be freed", removed, freed);
+    } else {
+        info!("{} cache entries removed, {} bytes freed", removed, freed);
+    }
+    if busy > 0 {
+        warn!("{} cache entries skipped because they are in use", busy);
+    }
+    return Ok(());
+}
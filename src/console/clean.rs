@@ -1,33 +1,276 @@
-use std::{fmt::Display, str::FromStr};
+//! # 清理任务
+//!
+//! 清理分为四个级别，每个级别包含之前的级别：
+//!
+//! - `output`：构建结果（DADK输出目录），任务的`clean_command`属于这个级别
+//! - `src`：下载、解压或者克隆的源码（源码缓存目录）
+//! - `cache`：下载缓存（例如拉取的OCI镜像展开后的文件系统）
+//! - `all`：任务的其他数据（执行记录、之前的输出日志）
+//!
+//! 任务可以在`clean.levels`中为每个级别指定清理命令，指定了命令的级别默认不删除目录。
+//! `clean.timeout`限制每条清理命令的执行时间，`clean.on_failure`指定清理命令失败或者超时时的处理方式：
+//! `abort`停止整个清理过程，`continue`（默认）继续清理其他任务，`ignore`视为清理成功。
+//! 有任务清理失败、且处理方式不是`ignore`时，以非0的退出码退出。
+//!
+//! 默认清理所有任务，`--task`、`--tag`只清理指定的任务（可以指定多次），
+//! `--with-dependents`同时清理直接或者间接依赖于所选任务的任务。开始清理前输出所选的任务。
+//!
+//! `--dry-run`只输出每个任务会执行的清理命令和会删除的路径（带有占用的字节数），不执行命令，也不删除任何文件。
+//! 解析到缓存根目录之外的路径会被标记出来，真正清理时也会被拒绝。
+//!
+//! ```bash
+//! dadk clean --level src
+//! dadk clean --task app-0.1.0 --with-dependents
+//! dadk clean --tag net
+//! dadk clean --level all --dry-run
+//! ```
+
+use std::{collections::BTreeSet, fmt::Display, str::FromStr};
 
 use clap::{Args, Subcommand};
 
+use crate::{
+    executor::clean_plan::CleanPlan, parser::task::DADKTask, utils::suggest::SuggestUtils,
+};
+
+/// 未知的任务名或者标签的错误信息中，最多列出的相近的名称数
+const MAX_CLOSE_MATCHES: usize = 3;
+
 /// 清理缓存的级别
-#[derive(Debug, Args, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Args, Clone, PartialEq, Eq)]
 pub struct CleanArg {
-    #[arg(default_value = "src")]
-    /// 清理缓存的级别
+    /// 清理缓存的级别（与`--level`相同，保留用于兼容旧的用法）
+    #[arg(hide = true, value_name = "LEVEL", conflicts_with = "level")]
+    pub legacy_level: Option<CleanLevel>,
+    /// 清理缓存的级别，每个级别包含之前的级别
     ///
-    /// all：清理所有缓存
+    /// output：清理构建结果（DADK输出目录），任务的clean_command属于这个级别
     ///
-    /// src：在源码目录内运行clean命令
+    /// src：同时清理下载、解压或者克隆的源码
     ///
-    /// target：清理DADK输出目录
+    /// cache：同时清理下载缓存（拉取的OCI镜像等）
     ///
-    /// cache：清理DADK缓存目录（下载的源码、编译好的库等）
-    pub level: CleanLevel,
+    /// all：同时清理任务的执行记录和之前的输出日志
+    #[arg(long)]
+    pub level: Option<CleanLevel>,
+    /// 只清理指定的任务（`任务名`或者`任务名-版本`），可以指定多次
+    #[arg(long = "task", value_name = "NAME[-VERSION]")]
+    pub tasks: Vec<String>,
+    /// 只清理带有指定标签的任务，可以指定多次
+    #[arg(long = "tag", value_name = "TAG")]
+    pub tags: Vec<String>,
+    /// 同时清理直接或者间接依赖于所选任务的任务
+    #[arg(long)]
+    pub with_dependents: bool,
+    /// 只输出会被执行的清理命令和会被删除的文件，不执行命令，也不删除任何文件
+    #[arg(long)]
+    pub dry_run: bool,
 }
 
-#[derive(Debug, Subcommand, Clone, Copy, PartialEq, Eq)]
+impl CleanArg {
+    #[allow(dead_code)]
+    pub fn new(level: CleanLevel) -> Self {
+        return Self {
+            legacy_level: None,
+            level: Some(level),
+            tasks: Vec::new(),
+            tags: Vec::new(),
+            with_dependents: false,
+            dry_run: false,
+        };
+    }
+
+    /// 实际使用的清理级别，默认为`output`
+    pub fn level(&self) -> CleanLevel {
+        return self
+            .level
+            .or(self.legacy_level)
+            .unwrap_or(CleanLevel::Output);
+    }
+
+    /// 是否指定了`--task`或者`--tag`
+    pub fn is_filtered(&self) -> bool {
+        return !self.tasks.is_empty() || !self.tags.is_empty();
+    }
+
+    /// # 按`--task`、`--tag`选择要清理的任务
+    ///
+    /// 没有指定过滤条件时选择所有任务。某个`--task`没有匹配任何任务、或者没有任务带有某个`--tag`时，
+    /// 返回错误并列出相近的名称。
+    ///
+    /// 返回所选任务的`name_version()`，按任务在`tasks`中的顺序排列
+    pub fn select_tasks(&self, tasks: &[DADKTask]) -> Result<Vec<String>, String> {
+        if !self.is_filtered() {
+            return Ok(tasks.iter().map(|t| t.name_version()).collect());
+        }
+
+        let mut selected = vec![false; tasks.len()];
+        for spec in self.tasks.iter() {
+            let mut found = false;
+            for (i, task) in tasks.iter().enumerate() {
+                if Self::task_matches(task, spec) {
+                    selected[i] = true;
+                    found = true;
+                }
+            }
+            if !found {
+                let candidates: Vec<String> = tasks
+                    .iter()
+                    .flat_map(|t| [t.name.clone(), format!("{}-{}", t.name, t.version)])
+                    .collect();
+                return Err(Self::unknown("unknown task", spec, &candidates));
+            }
+        }
+        for tag in self.tags.iter() {
+            let mut found = false;
+            for (i, task) in tasks.iter().enumerate() {
+                if task.tags.contains(tag) {
+                    selected[i] = true;
+                    found = true;
+                }
+            }
+            if !found {
+                let candidates: Vec<String> =
+                    tasks.iter().flat_map(|t| t.tags.iter().cloned()).collect();
+                return Err(Self::unknown("no task has tag", tag, &candidates));
+            }
+        }
+
+        if self.with_dependents {
+            // 沿反向依赖图扩展，直到不再有新的任务被选中
+            let mut pending: Vec<usize> = (0..tasks.len()).filter(|i| selected[*i]).collect();
+            while let Some(i) = pending.pop() {
+                let dep = &tasks[i];
+                for (j, task) in tasks.iter().enumerate() {
+                    if !selected[j]
+                        && task
+                            .depends
+                            .iter()
+                            .any(|d| d.is_task() && d.name == dep.name && d.version == dep.version)
+                    {
+                        selected[j] = true;
+                        pending.push(j);
+                    }
+                }
+            }
+        }
+
+        let mut names = Vec::new();
+        let mut seen = BTreeSet::new();
+        for (task, _) in tasks.iter().zip(selected).filter(|(_, s)| *s) {
+            if seen.insert(task.name_version()) {
+                names.push(task.name_version());
+            }
+        }
+        return Ok(names);
+    }
+
+    /// 任务是否匹配`--task`指定的`任务名`、`任务名-版本`
+    fn task_matches(task: &DADKTask, spec: &str) -> bool {
+        return task.name == spec
+            || format!("{}-{}", task.name, task.version) == spec
+            || task.name_version() == spec;
+    }
+
+    fn unknown(what: &str, input: &str, candidates: &[String]) -> String {
+        let candidates: Vec<&str> = candidates.iter().map(|c| c.as_str()).collect();
+        let matches = SuggestUtils::close_matches(input, &candidates, MAX_CLOSE_MATCHES);
+        if matches.is_empty() {
+            return format!("{} '{}'", what, input);
+        }
+        return format!(
+            "{} '{}', close matches: {}",
+            what,
+            input,
+            matches.join(", ")
+        );
+    }
+}
+
+/// 输出要清理的任务，在删除任何文件之前调用
+pub fn print_selected(level: CleanLevel, tasks: &[String]) {
+    println!("# tasks to clean (level: {}):", level);
+    for task in tasks {
+        println!("  {}", task);
+    }
+    if tasks.is_empty() {
+        println!("  (none)");
+    }
+}
+
+/// # 输出清理计划
+///
+/// 按任务分组输出清理命令和要删除的路径，计划中有异常或者错误时返回错误
+pub fn print_plans(level: CleanLevel, plans: &[CleanPlan]) -> Result<(), String> {
+    println!("# clean plan (level: {}, dry run):", level);
+    let mut total = 0;
+    for plan in plans.iter() {
+        println!("{}: {} bytes", plan.task, plan.total_bytes());
+        if let Some(work_dir) = &plan.work_dir {
+            println!("  in {}:", work_dir.display());
+        }
+        for command in plan.commands.iter() {
+            println!("  run     {}", command);
+        }
+        for removal in plan.removals.iter() {
+            println!(
+                "  remove  {} ({}, {} bytes)",
+                removal.path.display(),
+                removal.level,
+                removal.bytes
+            );
+        }
+        for level in plan.kept.iter() {
+            println!("  keep    {} directories (clean command specified)", level);
+        }
+        if plan.is_empty() && !plan.has_problems() {
+            println!("  nothing to clean");
+        }
+        for anomaly in plan.anomalies.iter() {
+            println!("  refused: {}", anomaly);
+        }
+        for error in plan.errors.iter() {
+            println!("  error: {}", error);
+        }
+        total += plan.total_bytes();
+    }
+    println!("# {} bytes would be freed", total);
+
+    let problems = plans.iter().filter(|p| p.has_problems()).count();
+    if problems > 0 {
+        return Err(format!(
+            "{} tasks have clean paths outside the DADK cache root or errors",
+            problems
+        ));
+    }
+    return Ok(());
+}
+
+#[derive(Debug, Subcommand, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum CleanLevel {
-    /// 清理所有缓存
-    All,
-    /// 在源码目录内运行clean命令
+    /// 清理构建结果（DADK输出目录），任务的clean_command属于这个级别
+    Output,
+    /// 同时清理下载、解压或者克隆的源码
     Src,
-    /// 清理DADK输出目录
-    Target,
-    /// 清理DADK缓存目录（下载的源码、编译好的库等）
+    /// 同时清理下载缓存
     Cache,
+    /// 同时清理任务的执行记录和之前的输出日志
+    All,
+}
+
+impl CleanLevel {
+    /// 所有级别，按从低到高的顺序
+    pub const LEVELS: [CleanLevel; 4] = [
+        CleanLevel::Output,
+        CleanLevel::Src,
+        CleanLevel::Cache,
+        CleanLevel::All,
+    ];
+
+    /// 清理到当前级别时，需要处理的所有级别（从低到高）
+    pub fn included(self) -> impl Iterator<Item = CleanLevel> {
+        return Self::LEVELS.into_iter().filter(move |l| *l <= self);
+    }
 }
 
 impl FromStr for CleanLevel {
@@ -38,7 +281,8 @@ impl FromStr for CleanLevel {
         match s.as_str() {
             "all" => Ok(CleanLevel::All),
             "src" => Ok(CleanLevel::Src),
-            "target" => Ok(CleanLevel::Target),
+            // `target`是`output`的旧名字
+            "output" | "target" => Ok(CleanLevel::Output),
             "cache" => Ok(CleanLevel::Cache),
             _ => Err(format!("Unknown clean level: {}", s)),
         }
@@ -50,7 +294,7 @@ impl Display for CleanLevel {
         match self {
             CleanLevel::All => write!(f, "all"),
             CleanLevel::Src => write!(f, "src"),
-            CleanLevel::Target => write!(f, "target"),
+            CleanLevel::Output => write!(f, "output"),
             CleanLevel::Cache => write!(f, "cache"),
         }
     }
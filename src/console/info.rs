@@ -0,0 +1,123 @@
+//! # 显示任务的有效配置
+//!
+//! 把按目标架构覆盖的配置合并到基础配置之上，输出各任务在目标架构下实际生效的配置，
+//! 以及任务最近一次执行的日志的路径。指定了任务时，只输出该任务，并输出它的传递依赖、安装清单，
+//! 以及执行时设置的环境变量和它们的来源（`dadk`：DADK设置的，`global`：工作区的`global_envs`，
+//! `env_file`：工作区或任务的`env_files`，`task`：任务的`envs`）。
+//!
+//! ```bash
+//! dadk --config-dir <配置文件目录> --target-arch riscv64 info
+//! dadk --config-dir <配置文件目录> info app-0.1.0 --arch riscv64
+//! ```
+
+use std::path::PathBuf;
+
+use clap::Args;
+
+use crate::{
+    executor::{manifest::InstallManifest, output_log::OutputLog, EnvVar},
+    parser::task::{DADKTask, TargetArch},
+};
+
+#[derive(Debug, Args, Clone, PartialEq, Eq)]
+pub struct InfoArg {
+    /// 只显示这个任务（任务名或者`任务名-版本`）
+    pub task: Option<String>,
+
+    /// 显示这个目标架构下的配置和环境变量，默认与`--target-arch`相同
+    #[arg(long, value_parser = super::parse_target_arch)]
+    pub arch: Option<TargetArch>,
+}
+
+/// # 输出目标架构下各任务的有效配置
+///
+/// 不支持该架构的任务会被跳过，每个任务输出一个JSON对象。指定了任务但没有匹配的任务时返回错误。
+/// `envs_of`计算任务执行时设置的环境变量，`deps_of`计算任务的传递依赖，只在指定了任务时调用
+pub fn print_effective_tasks(
+    tasks: &[(PathBuf, DADKTask)],
+    arch: TargetArch,
+    arg: &InfoArg,
+    envs_of: &dyn Fn(&DADKTask) -> Result<Vec<EnvVar>, String>,
+    deps_of: &dyn Fn(&DADKTask) -> Result<Vec<DADKTask>, String>,
+) -> Result<(), String> {
+    let mut tasks: Vec<&(PathBuf, DADKTask)> = tasks
+        .iter()
+        .filter(|(_, task)| task.target_arch.contains(&arch))
+        .filter(|(_, task)| arg.task.as_ref().map_or(true, |t| matches_task(task, t)))
+        .collect();
+    tasks.sort_by_key(|(_, task)| task.name_version());
+    if let (Some(name), true) = (&arg.task, tasks.is_empty()) {
+        return Err(format!("no task named {} for {:?}", name, arch));
+    }
+
+    for (path, task) in tasks {
+        println!("# {}", path.display());
+        if let Some(log) = OutputLog::latest(&task.name_version()) {
+            println!("# log: {}", log.display());
+        }
+        let mut task_for_arch = task.for_arch(arch);
+        task_for_arch.mask_secrets();
+        println!("{}", task_for_arch.to_canonical_json().unwrap());
+        if arg.task.is_some() {
+            print_deps(&deps_of(task)?);
+            print_envs(&envs_of(task)?);
+            print_manifest(task)?;
+        }
+    }
+    return Ok(());
+}
+
+fn matches_task(task: &DADKTask, name: &str) -> bool {
+    return task.name == name || format!("{}-{}", task.name, task.version) == name;
+}
+
+/// 输出任务的传递依赖，被依赖的任务在前，每行一项。按固定架构构建的依赖带有架构
+fn print_deps(deps: &[DADKTask]) {
+    println!("# dependencies ({} tasks):", deps.len());
+    for dep in deps {
+        match dep.pinned_arch {
+            Some(arch) => println!("{}	{}", dep.name_version(), Into::<&str>::into(arch)),
+            None => println!("{}", dep.name_version()),
+        }
+    }
+}
+
+/// 输出任务执行时设置的环境变量，每行一项：来源、`名称=值`。保密的值被替换为`***`
+fn print_envs(envs: &[EnvVar]) {
+    println!("# environment ({} variables):", envs.len());
+    for env in envs {
+        println!("{}\t{}={}", env.origin, env.key, env.display_value());
+    }
+}
+
+/// 输出任务的安装清单，每行一项：类型、权限、大小、sha256、路径
+fn print_manifest(task: &DADKTask) -> Result<(), String> {
+    let manifest = match InstallManifest::load(&task.name_version())? {
+        Some(manifest) => manifest,
+        None => {
+            println!("# not installed");
+            return Ok(());
+        }
+    };
+    println!(
+        "# installed files ({} entries, sysroot: {}):",
+        manifest.entries.len(),
+        manifest.sysroot.display()
+    );
+    for entry in manifest.entries.iter() {
+        let kind = serde_json::to_value(entry.kind).unwrap();
+        let mut line = format!(
+            "{}\t{}\t{}\t{}\t{}",
+            kind.as_str().unwrap_or_default(),
+            entry.mode,
+            entry.size.map_or("-".to_string(), |s| s.to_string()),
+            entry.sha256.as_deref().unwrap_or("-"),
+            entry.path.display()
+        );
+        if let Some(target) = &entry.target {
+            line.push_str(&format!(" -> {}", target.display()));
+        }
+        println!("{}", line);
+    }
+    return Ok(());
+}
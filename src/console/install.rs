@@ -0,0 +1,117 @@
+//! # 安装任务
+//!
+//! 按拓扑序把各任务的构建结果安装到DragonOS sysroot。
+//! 指定`--dry-run`时只输出安装计划（按任务分组的拷贝、符号链接、删除和冲突），不修改sysroot；
+//! 指定`--diff`时只输出与当前安装清单相比的变化。
+//! 计划中有冲突或者错误时，以非0的退出码退出。
+//!
+//! ```bash
+//! dadk -c <配置文件目录> -d <sysroot> install --dry-run
+//! dadk -c <配置文件目录> -d <sysroot> install --diff
+//! ```
+
+use std::path::Path;
+
+use clap::Args;
+
+use crate::executor::{
+    manifest::InstallManifest,
+    plan::{PlannedOp, TaskPlan},
+};
+
+#[derive(Debug, Args, Clone, Default, PartialEq, Eq)]
+pub struct InstallArg {
+    /// 只输出安装计划，不修改sysroot
+    #[arg(long)]
+    pub dry_run: bool,
+    /// 只输出与当前安装清单相比的变化，不修改sysroot
+    #[arg(long)]
+    pub diff: bool,
+}
+
+impl InstallArg {
+    /// 是否只计算安装计划
+    pub fn is_plan_only(&self) -> bool {
+        return self.dry_run || self.diff;
+    }
+}
+
+/// # 输出安装计划
+///
+/// 有冲突或者错误时返回错误
+pub fn print_plans(plans: &[TaskPlan], sysroot: &Path, diff: bool) -> Result<(), String> {
+    println!("# install plan for {}", sysroot.display());
+    let mut changed = false;
+    for plan in plans.iter() {
+        let lines = if diff {
+            diff_lines(plan)?
+        } else {
+            plan_lines(plan)
+        };
+        if lines.is_empty() && !plan.has_problems() {
+            continue;
+        }
+        changed = true;
+        if plan.removed {
+            println!(
+                "{} (removed from the config, `dadk uninstall --prune-removed` deletes):",
+                plan.task
+            );
+        } else {
+            println!("{}:", plan.task);
+        }
+        for line in lines {
+            println!("  {}", line);
+        }
+        for conflict in plan.conflicts.iter() {
+            println!("  conflict: {}", conflict);
+        }
+        for error in plan.errors.iter() {
+            println!("  error: {}", error);
+        }
+    }
+    if !changed {
+        println!("# no changes");
+    }
+
+    let problems = plans.iter().filter(|p| p.has_problems()).count();
+    if problems > 0 {
+        return Err(format!(
+            "{} tasks have install conflicts or errors",
+            problems
+        ));
+    }
+    return Ok(());
+}
+
+fn plan_lines(plan: &TaskPlan) -> Vec<String> {
+    if plan.up_to_date {
+        return vec!["up to date (install_once)".to_string()];
+    }
+    let mut lines: Vec<String> = plan
+        .ops
+        .iter()
+        .map(|op| match op {
+            PlannedOp::Copy { src, dest, .. } => {
+                format!("copy    {} (from {})", dest.display(), src.display())
+            }
+            PlannedOp::Symlink { link, target } => {
+                format!("symlink {} -> {}", link.display(), target.display())
+            }
+            PlannedOp::Remove { path } => format!("remove  {}", path.display()),
+        })
+        .collect();
+    if plan.post_install {
+        lines.push("run post_install hook".to_string());
+    }
+    return lines;
+}
+
+fn diff_lines(plan: &TaskPlan) -> Result<Vec<String>, String> {
+    let current = InstallManifest::load(&plan.task)?;
+    return Ok(plan
+        .diff(current.as_ref())
+        .into_iter()
+        .map(|(change, path)| format!("{} {}", change.symbol(), path.display()))
+        .collect());
+}
@@ -0,0 +1,39 @@
+//! # 列出任务
+//!
+//! 列出配置文件目录中的所有任务，便于用户查看当前工作区中有哪些任务。
+//!
+//! ```bash
+//! dadk --config-dir <配置文件目录> list
+//! ```
+
+use std::path::PathBuf;
+
+use crate::parser::task::{DADKTask, TaskType};
+
+/// # 列出所有任务
+///
+/// 每行输出一个任务：任务名-版本、任务类别、配置文件路径
+pub fn list_tasks(tasks: &[(PathBuf, DADKTask)]) {
+    let mut tasks: Vec<&(PathBuf, DADKTask)> = tasks.iter().collect();
+    tasks.sort_by_key(|(_, task)| task.name_version());
+
+    for (path, task) in tasks {
+        println!(
+            "{}\t{}\t{}",
+            task.name_version(),
+            task_kind(&task.task_type),
+            path.display()
+        );
+    }
+}
+
+/// 获取任务类别的描述
+fn task_kind(task_type: &TaskType) -> &'static str {
+    match task_type {
+        TaskType::BuildFromSource(_) => "source",
+        TaskType::InstallFromPrebuilt(_) => "prebuilt",
+        TaskType::NoSource => "script",
+        TaskType::Meta => "meta package",
+        TaskType::KernelModule(_) => "kernel module",
+    }
+}
@@ -11,18 +11,33 @@
 //! ```
 //!
 
+pub mod cache;
 pub mod clean;
 pub mod elements;
+pub mod info;
+pub mod install;
 pub mod interactive;
+pub mod list;
 pub mod new_config;
+pub mod owns;
+pub mod uninstall;
 
 use std::path::PathBuf;
 
 use clap::{Parser, Subcommand};
 
-use crate::parser::task::TargetArch;
+use crate::{
+    executor::{diagnostics::DiagnosticMatcher, events::EventSink},
+    parser::{
+        resolver::ResolveMode,
+        task::{DocsPolicy, TargetArch},
+    },
+};
 
-use self::clean::CleanArg;
+use self::{
+    cache::CacheCommand, clean::CleanArg, info::InfoArg, install::InstallArg, owns::OwnsArg,
+    uninstall::UninstallArg,
+};
 
 #[derive(Debug, Parser, Clone)]
 #[command(author, version, about)]
@@ -49,6 +64,106 @@ pub struct CommandLineArgs {
     /// 目标架构，可选： ["aarch64", "x86_64", "riscv64", "riscv32"]
     #[arg(long, value_parser = parse_target_arch)]
     pub target_arch: Option<TargetArch>,
+
+    /// 按每个任务的`target_arch`中的所有架构分别执行任务，而不是只执行`--target-arch`的
+    #[arg(long)]
+    pub all_arches: bool,
+
+    /// 任务失败时不立即停止，继续执行不依赖失败任务的其他任务（例如其他架构的同一个任务），最后以非0的退出码退出
+    #[arg(long)]
+    pub no_fail_fast: bool,
+
+    /// 构建时，把实际获取到的源码身份写入锁文件。不指定时，只在锁文件已经存在时按它检查，不写入
+    #[arg(long, default_value_t = false)]
+    pub lock: bool,
+
+    /// 构建时，允许用实际获取到的源码更新锁文件中不一致的记录（同时会写入锁文件）
+    #[arg(long, default_value_t = false)]
+    pub update_lock: bool,
+
+    /// DragonOS内核源码目录，构建内核模块任务时需要
+    #[arg(long, value_parser = parse_check_dir_exists)]
+    pub kernel_src: Option<PathBuf>,
+
+    /// DragonOS内核构建目录，默认与内核源码目录相同
+    #[arg(long, value_parser = parse_check_dir_exists)]
+    pub kernel_build: Option<PathBuf>,
+
+    /// 内核的目标架构，默认与`--target-arch`相同
+    #[arg(long, value_parser = parse_target_arch)]
+    pub kernel_arch: Option<TargetArch>,
+
+    /// 构建时可以使用的总任务数（例如make的`-j`），在并行执行的任务之间平分，默认为CPU核心数
+    #[arg(short, long)]
+    pub jobs: Option<usize>,
+
+    /// 构建配置档（例如debug、release），合并各任务中对应的`profile`配置
+    #[arg(long)]
+    pub profile: Option<String>,
+
+    /// 依赖版本冲突的解析策略：independent（默认，每个依赖方使用满足其要求的最高版本）、
+    /// strict（与independent相同，但是解析出多个版本时报错）或unify（选择满足所有要求的最高版本）
+    #[arg(long, value_parser = parse_resolve_mode)]
+    pub dep_resolve: Option<ResolveMode>,
+
+    /// 在任务日志的文件名中加入时间戳，保留每一次执行的日志（默认覆盖上一次的日志）
+    #[arg(long)]
+    pub log_timestamp: bool,
+
+    /// 任务失败时输出的日志行数，默认为50
+    #[arg(long)]
+    pub log_tail: Option<usize>,
+
+    /// 附加的构建输出警告/错误匹配规则，格式为`<warning|error>=<正则表达式>`，可以指定多次。
+    /// 内置了gcc/clang和rustc的规则
+    #[arg(long = "diagnostic-pattern", value_parser = parse_diagnostic_pattern)]
+    pub diagnostic_patterns: Vec<DiagnosticMatcher>,
+
+    /// 把所有任务的执行结果以JSON格式写入指定的文件
+    #[arg(long)]
+    pub report: Option<PathBuf>,
+
+    /// 在执行时把事件（任务开始、下载进度、阶段完成、任务失败等）以NDJSON格式写入指定的文件，`-`表示标准输出
+    #[arg(long, value_parser = parse_event_sink)]
+    pub events: Option<EventSink>,
+
+    /// 输出设置了`build_once`的任务需要重新构建的原因（例如哪一个`rerun_if_changed`路径改变了）
+    #[arg(long)]
+    pub why_dirty: bool,
+
+    /// 默认在没有网络的沙箱中执行构建命令和构建钩子，任务可以通过`build.sandbox.network`覆盖
+    #[arg(long)]
+    pub sandbox_network: bool,
+
+    /// 用多个线程解压在线压缩包，线程数为每个任务的构建任务数（见`--jobs`）
+    #[arg(long)]
+    pub parallel_extract: bool,
+
+    /// 同时进行的下载（克隆仓库、下载压缩包、拉取镜像）数，与`--jobs`无关，默认为CPU核心数，但不超过8
+    #[arg(long)]
+    pub download_jobs: Option<usize>,
+
+    /// 安装时文档的默认处理方式：keep（保留）、compress（gzip压缩）或strip（删除），任务可以通过`install.docs`覆盖
+    #[arg(long, value_parser = parse_docs_policy)]
+    pub docs: Option<DocsPolicy>,
+
+    /// 安装时默认保留的语言，多个语言用逗号分隔（例如`zh_CN,en`），其他语言的翻译文件不会被安装。
+    /// 任务可以通过`install.locales`覆盖
+    #[arg(long, value_delimiter = ',')]
+    pub keep_locales: Option<Vec<String>>,
+
+    /// 禁止安装到的DragonOS内的路径（包括其下的路径），多个路径用逗号分隔，替换默认的`/,/proc,/sys,/dev`，
+    /// 指定为空时不禁止任何路径。`/`只禁止替换根目录本身，仍然可以把构建结果目录安装到`/`
+    #[arg(long, value_delimiter = ',', value_parser = parse_deny_install_path)]
+    pub deny_install_path: Option<Vec<PathBuf>>,
+
+    /// 安装时忽略`install_once`，重新安装指定的任务（任务名或者`任务名-版本`），可以指定多次
+    #[arg(long = "force-install", value_name = "TASK")]
+    pub force_install: Vec<String>,
+
+    /// 构建或安装前检查所有任务的源（Git仓库、在线压缩包、本地路径）是否可以访问，有不可访问的源时不执行任务
+    #[arg(long)]
+    pub preflight: bool,
 }
 
 /// @brief 检查目录是否存在
@@ -72,19 +187,52 @@ fn parse_target_arch(s: &str) -> Result<TargetArch, String> {
     return Ok(x.unwrap());
 }
 
+fn parse_resolve_mode(s: &str) -> Result<ResolveMode, String> {
+    return ResolveMode::try_from(s);
+}
+
+fn parse_docs_policy(s: &str) -> Result<DocsPolicy, String> {
+    return DocsPolicy::try_from(s);
+}
+
+fn parse_deny_install_path(s: &str) -> Result<PathBuf, String> {
+    let path = PathBuf::from(s.trim());
+    if !path.as_os_str().is_empty() && !path.has_root() {
+        return Err(format!("Path '{}' should be an absolute path", s));
+    }
+    return Ok(path);
+}
+
+fn parse_diagnostic_pattern(s: &str) -> Result<DiagnosticMatcher, String> {
+    return DiagnosticMatcher::parse(s);
+}
+
+fn parse_event_sink(s: &str) -> Result<EventSink, String> {
+    return EventSink::parse(s);
+}
+
 /// @brief 要执行的操作
-#[derive(Debug, Subcommand, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Subcommand, Clone, PartialEq, Eq)]
 pub enum Action {
     /// 构建所有项目
     Build,
     /// 清理缓存
     Clean(CleanArg),
     /// 安装到DragonOS sysroot
-    Install,
-    /// 尚不支持
-    Uninstall,
+    Install(InstallArg),
+    /// 按安装清单从DragonOS sysroot中卸载任务
+    Uninstall(UninstallArg),
     /// 使用交互式命令行创建dadk任务配置文件
     New,
+    /// 列出所有任务
+    List,
+    /// 显示目标架构下各任务合并后的有效配置，指定任务时同时显示任务的安装清单
+    Info(InfoArg),
+    /// 查询安装了某个路径的任务
+    Owns(OwnsArg),
+    /// 管理缓存（统计、回收）
+    #[command(subcommand)]
+    Cache(CacheCommand),
 }
 
 #[allow(dead_code)]
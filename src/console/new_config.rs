@@ -6,7 +6,11 @@ use crate::{
     console::elements::{BoolInput, OptionalChoice, VecInput},
     executor::{
         cache::CacheDir,
-        source::{ArchiveSource, GitSource, LocalSource},
+        oci::ImageReference,
+        source::{
+            ArchiveSource, GitSource, HgSource, LocalSource, LocalSourceKind, OciSource,
+            ReleaseAssetSource, SvnSource,
+        },
     },
     parser::task::{
         BuildConfig, CleanConfig, CodeSource, DADKTask, Dependency, InstallConfig, PrebuiltSource,
@@ -64,7 +68,7 @@ impl NewConfigCommand {
     }
 
     fn write_dadk_config_file(&self, dadk_task: &DADKTask) -> Result<PathBuf, ConsoleError> {
-        let json = serde_json::to_string_pretty(&dadk_task).map_err(|e| {
+        let json = dadk_task.to_canonical_json().map_err(|e| {
             let msg = format!("Failed to serialize DADKTask to json: {:?}", e);
             error!("{}", msg);
             ConsoleError::InvalidInput(msg)
@@ -106,12 +110,22 @@ impl NewConfigCommand {
         debug!("dep: {:?}", dep);
         let build_config: BuildConfig = match &task_type {
             TaskType::InstallFromPrebuilt(_) => BuildConfig::new(Option::Some("".to_string())),
-            TaskType::BuildFromSource(_) => BuildConfigInput::new().input()?,
+            TaskType::BuildFromSource(_) | TaskType::KernelModule(_) | TaskType::NoSource => {
+                BuildConfigInput::new().input()?
+            }
+            TaskType::Meta => BuildConfig::new(None),
         };
         debug!("build_config: {:?}", build_config);
-        let install_config: InstallConfig = InstallConfigInput::new().input()?;
+        // 元包没有安装和清理配置
+        let install_config: InstallConfig = match &task_type {
+            TaskType::Meta => InstallConfig::new(None),
+            _ => InstallConfigInput::new().input()?,
+        };
         debug!("install_config: {:?}", install_config);
-        let clean_config: CleanConfig = CleanConfigInput::new().input()?;
+        let clean_config: CleanConfig = match &task_type {
+            TaskType::Meta => CleanConfig::new(None),
+            _ => CleanConfigInput::new().input()?,
+        };
         debug!("clean_config: {:?}", clean_config);
 
         let task_env: Option<Vec<TaskEnv>> = TaskEnvInput::new().input()?;
@@ -209,6 +223,9 @@ impl InputFunc<TaskType> for TaskTypeInput {
     fn input(&mut self) -> Result<TaskType, ConsoleError> {
         const TASK_TYPE_BUILD_FROM_SOURCE: &str = "src";
         const TASK_TYPE_INSTALL_FROM_PREBUILT: &str = "prebuilt";
+        const TASK_TYPE_NO_SOURCE: &str = "script";
+        const TASK_TYPE_META: &str = "meta";
+        const TASK_TYPE_KERNEL_MODULE: &str = "kmod";
 
         let mut task_type_choose =
             OptionalChoice::new(Some("Please choose the [type] of the task:".to_string()));
@@ -220,6 +237,18 @@ impl InputFunc<TaskType> for TaskTypeInput {
             TASK_TYPE_INSTALL_FROM_PREBUILT.to_string(),
             "Install from prebuilt".to_string(),
         );
+        task_type_choose.add_choice(
+            TASK_TYPE_NO_SOURCE.to_string(),
+            "Run script only (no source)".to_string(),
+        );
+        task_type_choose.add_choice(
+            TASK_TYPE_META.to_string(),
+            "Meta package (only depends on other tasks)".to_string(),
+        );
+        task_type_choose.add_choice(
+            TASK_TYPE_KERNEL_MODULE.to_string(),
+            "DragonOS kernel module".to_string(),
+        );
 
         // 读取用户输入
         let task_type = task_type_choose.choose_until_valid()?;
@@ -233,6 +262,9 @@ impl InputFunc<TaskType> for TaskTypeInput {
             TASK_TYPE_INSTALL_FROM_PREBUILT => {
                 TaskType::InstallFromPrebuilt(PrebuiltSourceInput::new().input()?)
             }
+            TASK_TYPE_NO_SOURCE => TaskType::NoSource,
+            TASK_TYPE_META => TaskType::Meta,
+            TASK_TYPE_KERNEL_MODULE => TaskType::KernelModule(CodeSourceInput::new().input()?),
             _ => {
                 let msg = format!("Invalid task type: {}", task_type);
                 return Err(ConsoleError::InvalidInput(msg));
@@ -258,8 +290,11 @@ impl CodeSourceInput {
 
     pub fn input(&self) -> Result<CodeSource, ConsoleError> {
         const CODE_SOURCE_GIT: &str = "git";
+        const CODE_SOURCE_HG: &str = "hg";
+        const CODE_SOURCE_SVN: &str = "svn";
         const CODE_SOURCE_LOCAL: &str = "local";
         const CODE_SOURCE_ARCHIVE: &str = "archive";
+        const CODE_SOURCE_RELEASE: &str = "release";
 
         let mut code_source_choose = OptionalChoice::new(Some(
             "Please choose the [code source] of the task:".to_string(),
@@ -268,6 +303,14 @@ impl CodeSourceInput {
             CODE_SOURCE_GIT.to_string(),
             "Build from git repository".to_string(),
         );
+        code_source_choose.add_choice(
+            CODE_SOURCE_HG.to_string(),
+            "Build from mercurial (hg) repository".to_string(),
+        );
+        code_source_choose.add_choice(
+            CODE_SOURCE_SVN.to_string(),
+            "Build from subversion (svn) repository".to_string(),
+        );
         code_source_choose.add_choice(
             CODE_SOURCE_LOCAL.to_string(),
             "Build from local directory".to_string(),
@@ -276,6 +319,10 @@ impl CodeSourceInput {
             CODE_SOURCE_ARCHIVE.to_string(),
             "Build from archive file".to_string(),
         );
+        code_source_choose.add_choice(
+            CODE_SOURCE_RELEASE.to_string(),
+            "Build from GitHub/Gitea release asset".to_string(),
+        );
 
         // 读取用户输入
         let code_source: String = code_source_choose.choose_until_valid()?;
@@ -283,10 +330,15 @@ impl CodeSourceInput {
 
         let mut code_source: CodeSource = match code_source.as_str() {
             CODE_SOURCE_GIT => CodeSource::Git(GitSourceInput::new().input_until_valid()?),
+            CODE_SOURCE_HG => CodeSource::Hg(HgSourceInput::new().input_until_valid()?),
+            CODE_SOURCE_SVN => CodeSource::Svn(SvnSourceInput::new().input_until_valid()?),
             CODE_SOURCE_LOCAL => CodeSource::Local(LocalSourceInput::new().input_until_valid()?),
             CODE_SOURCE_ARCHIVE => {
                 CodeSource::Archive(ArchiveSourceInput::new().input_until_valid()?)
             }
+            CODE_SOURCE_RELEASE => {
+                CodeSource::ReleaseAsset(ReleaseAssetSourceInput::new().input_until_valid()?)
+            }
             _ => {
                 let msg = format!("Invalid code source: {}", code_source);
                 return Err(ConsoleError::InvalidInput(msg));
@@ -312,13 +364,20 @@ impl PrebuiltSourceInput {
 
 impl InputFunc<PrebuiltSource> for PrebuiltSourceInput {
     fn input(&mut self) -> Result<PrebuiltSource, ConsoleError> {
+        const PREBUILT_SOURCE_GIT: &str = "git";
         const PREBUILT_SOURCE_LOCAL: &str = "local";
         const PREBUILT_SOURCE_ARCHIVE: &str = "archive";
+        const PREBUILT_SOURCE_OCI: &str = "oci";
+        const PREBUILT_SOURCE_RELEASE: &str = "release";
 
         let mut prebuilt_source_choose = OptionalChoice::new(Some(
             "Please choose the [prebuilt source] of the task:".to_string(),
         ));
 
+        prebuilt_source_choose.add_choice(
+            PREBUILT_SOURCE_GIT.to_string(),
+            "Install from git repository".to_string(),
+        );
         prebuilt_source_choose.add_choice(
             PREBUILT_SOURCE_LOCAL.to_string(),
             "Install from local directory".to_string(),
@@ -327,18 +386,31 @@ impl InputFunc<PrebuiltSource> for PrebuiltSourceInput {
             PREBUILT_SOURCE_ARCHIVE.to_string(),
             "Install from archive file".to_string(),
         );
+        prebuilt_source_choose.add_choice(
+            PREBUILT_SOURCE_OCI.to_string(),
+            "Install from OCI/Docker image".to_string(),
+        );
+        prebuilt_source_choose.add_choice(
+            PREBUILT_SOURCE_RELEASE.to_string(),
+            "Install from GitHub/Gitea release asset".to_string(),
+        );
 
         // 读取用户输入
         let prebuilt_source: String = prebuilt_source_choose.choose_until_valid()?;
         // debug!("prebuilt source: {}", prebuilt_source);
 
         let mut prebuilt_source: PrebuiltSource = match prebuilt_source.as_str() {
+            PREBUILT_SOURCE_GIT => PrebuiltSource::Git(GitSourceInput::new().input_until_valid()?),
             PREBUILT_SOURCE_LOCAL => {
                 PrebuiltSource::Local(LocalSourceInput::new().input_until_valid()?)
             }
             PREBUILT_SOURCE_ARCHIVE => {
                 PrebuiltSource::Archive(ArchiveSourceInput::new().input_until_valid()?)
             }
+            PREBUILT_SOURCE_OCI => PrebuiltSource::Oci(OciSourceInput::new().input_until_valid()?),
+            PREBUILT_SOURCE_RELEASE => {
+                PrebuiltSource::ReleaseAsset(ReleaseAssetSourceInput::new().input_until_valid()?)
+            }
             _ => {
                 let msg = format!("Invalid prebuilt source: {}", prebuilt_source);
                 return Err(ConsoleError::InvalidInput(msg));
@@ -431,6 +503,108 @@ impl GitSourceInput {
     }
 }
 
+#[derive(Debug)]
+struct HgSourceInput;
+
+impl InputFunc<HgSource> for HgSourceInput {
+    fn input(&mut self) -> Result<HgSource, ConsoleError> {
+        let url = Input::new(
+            Some("Please input the [url] of the hg repository:".to_string()),
+            None,
+        )
+        .input()?;
+
+        // 选择分支还是指定的changeset
+        const HG_SOURCE_BRANCH: &str = "branch";
+        const HG_SOURCE_REVISION: &str = "revision";
+
+        let mut hg_source_choose = OptionalChoice::new(Some(
+            "Please choose the [hg source] of the task:".to_string(),
+        ));
+        hg_source_choose.add_choice(HG_SOURCE_BRANCH.to_string(), "branch name".to_string());
+        hg_source_choose.add_choice(
+            HG_SOURCE_REVISION.to_string(),
+            "changeset id or tag".to_string(),
+        );
+
+        let hg_source = hg_source_choose.choose_until_valid()?;
+        let mut hg_source: HgSource = match hg_source.as_str() {
+            HG_SOURCE_BRANCH => {
+                let branch = Input::new(
+                    Some("Please input the [branch name] of the hg repository:".to_string()),
+                    None,
+                )
+                .input()?;
+                HgSource::new(url, Some(branch), None)
+            }
+            HG_SOURCE_REVISION => {
+                let revision = Input::new(
+                    Some("Please input the [revision] of the hg repository:".to_string()),
+                    None,
+                )
+                .input()?;
+                HgSource::new(url, None, Some(revision))
+            }
+            _ => {
+                let msg = format!("Invalid hg source: {}", hg_source);
+                return Err(ConsoleError::InvalidInput(msg));
+            }
+        };
+        hg_source.trim();
+        hg_source
+            .validate()
+            .map_err(|e| ConsoleError::InvalidInput(format!("Invalid hg source: {}", e)))?;
+
+        return Ok(hg_source);
+    }
+}
+
+impl HgSourceInput {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+#[derive(Debug)]
+struct SvnSourceInput;
+
+impl InputFunc<SvnSource> for SvnSourceInput {
+    fn input(&mut self) -> Result<SvnSource, ConsoleError> {
+        let url = Input::new(
+            Some("Please input the [url] of the svn repository:".to_string()),
+            None,
+        )
+        .input()?;
+        let revision = Input::new(
+            Some(
+                "Please input the [revision] number to check out (leave empty for HEAD):"
+                    .to_string(),
+            ),
+            None,
+        )
+        .input()?;
+        let revision = if revision.trim().is_empty() {
+            None
+        } else {
+            Some(revision)
+        };
+
+        let mut svn_source = SvnSource::new(url, revision);
+        svn_source.trim();
+        svn_source
+            .validate()
+            .map_err(|e| ConsoleError::InvalidInput(format!("Invalid svn source: {}", e)))?;
+
+        return Ok(svn_source);
+    }
+}
+
+impl SvnSourceInput {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
 #[derive(Debug)]
 struct LocalSourceInput;
 
@@ -456,7 +630,7 @@ impl InputFunc<LocalSource> for LocalSourceInput {
 
         local_source.trim();
         // 验证输入
-        local_source.validate(None).map_err(|e| {
+        local_source.validate(LocalSourceKind::Any).map_err(|e| {
             ConsoleError::InvalidInput(format!("Invalid local source: {}", e.to_string()))
         })?;
 
@@ -497,6 +671,132 @@ impl InputFunc<ArchiveSource> for ArchiveSourceInput {
     }
 }
 
+#[derive(Debug)]
+struct ReleaseAssetSourceInput;
+
+impl ReleaseAssetSourceInput {
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    fn input_repo(&self) -> Result<String, ConsoleError> {
+        let repo = Input::new(
+            Some("Please input the [repo], 'owner/name' for GitHub or 'https://<host>/owner/name' for Gitea:".to_string()),
+            None,
+        )
+        .input()?;
+        return Ok(repo);
+    }
+
+    fn input_tag(&self) -> Result<String, ConsoleError> {
+        let tag = Input::new(
+            Some("Please input the release [tag] ('latest' for the latest release):".to_string()),
+            None,
+        )
+        .input()?;
+        return Ok(tag);
+    }
+
+    fn input_asset_pattern(&self) -> Result<String, ConsoleError> {
+        let pattern = Input::new(
+            Some(
+                "Please input the [asset pattern], a glob or a regex prefixed with 're:':"
+                    .to_string(),
+            ),
+            None,
+        )
+        .input()?;
+        return Ok(pattern);
+    }
+}
+
+impl InputFunc<ReleaseAssetSource> for ReleaseAssetSourceInput {
+    fn input(&mut self) -> Result<ReleaseAssetSource, ConsoleError> {
+        let repo = self.input_repo()?;
+        let tag = self.input_tag()?;
+        let asset_pattern = self.input_asset_pattern()?;
+        let mut release_source = ReleaseAssetSource::new(repo, tag, asset_pattern);
+
+        release_source.trim();
+        // 验证输入
+        release_source.validate().map_err(|e| {
+            ConsoleError::InvalidInput(format!("Invalid release asset source: {}", e.to_string()))
+        })?;
+
+        return Ok(release_source);
+    }
+}
+
+#[derive(Debug)]
+struct OciSourceInput;
+
+impl OciSourceInput {
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    fn input_image(&self) -> Result<String, ConsoleError> {
+        let image = Input::new(
+            Some(
+                "Please input the [image] name, e.g. ghcr.io/org/app or ghcr.io/org/app:1.0@sha256:<digest>:"
+                    .to_string(),
+            ),
+            None,
+        )
+        .input()?;
+        return Ok(image);
+    }
+
+    fn input_reference(&self) -> Result<String, ConsoleError> {
+        let reference = Input::new(
+            Some("Please input the [tag or digest] of the image:".to_string()),
+            None,
+        )
+        .input()?;
+        return Ok(reference);
+    }
+
+    fn input_paths(&self) -> Result<Option<Vec<PathBuf>>, ConsoleError> {
+        let paths = Input::new(
+            Some("Please input the [paths] to use in the image, separated by ',' (leave blank to use the whole filesystem):".to_string()),
+            None,
+        )
+        .input()?;
+        let paths: Vec<PathBuf> = paths
+            .split(',')
+            .map(|p| p.trim())
+            .filter(|p| !p.is_empty())
+            .map(PathBuf::from)
+            .collect();
+        if paths.is_empty() {
+            return Ok(None);
+        }
+        return Ok(Some(paths));
+    }
+}
+
+impl InputFunc<OciSource> for OciSourceInput {
+    fn input(&mut self) -> Result<OciSource, ConsoleError> {
+        let image = self.input_image()?;
+        // 镜像名中已经包含tag/digest时，不需要再输入
+        let reference = if ImageReference::split_image(image.trim()).1.is_some() {
+            String::new()
+        } else {
+            self.input_reference()?
+        };
+        let paths = self.input_paths()?;
+        let mut oci_source = OciSource::new(image, reference, paths);
+
+        oci_source.trim();
+        // 验证输入
+        oci_source.validate().map_err(|e| {
+            ConsoleError::InvalidInput(format!("Invalid oci source: {}", e.to_string()))
+        })?;
+
+        return Ok(oci_source);
+    }
+}
+
 #[derive(Debug)]
 struct DependencyInput;
 
@@ -0,0 +1,43 @@
+//! # 查询路径由哪个任务安装
+//!
+//! 扫描所有任务的安装清单，输出安装了给定路径的任务。
+//! 路径可以是DragonOS内的绝对路径，也可以是sysroot中的主机路径。
+//!
+//! ```bash
+//! dadk owns /usr/bin/app
+//! ```
+
+use std::path::PathBuf;
+
+use clap::Args;
+
+use crate::executor::manifest::find_owners;
+
+#[derive(Debug, Args, Clone, PartialEq, Eq)]
+pub struct OwnsArg {
+    /// 要查询的路径
+    pub path: PathBuf,
+}
+
+/// # 输出安装了给定路径的任务
+///
+/// 每行输出一个任务：任务名-版本、类型、路径。没有任务安装该路径时返回错误
+pub fn print_owners(arg: &OwnsArg) -> Result<(), String> {
+    let owners = find_owners(&arg.path)?;
+    if owners.is_empty() {
+        return Err(format!(
+            "{} is not installed by any task",
+            arg.path.display()
+        ));
+    }
+    for (task, entry) in owners {
+        let kind = serde_json::to_value(entry.kind).unwrap();
+        println!(
+            "{}\t{}\t{}",
+            task,
+            kind.as_str().unwrap_or_default(),
+            entry.path.display()
+        );
+    }
+    return Ok(());
+}
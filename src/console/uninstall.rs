@@ -0,0 +1,103 @@
+//! # 卸载任务
+//!
+//! 按任务的安装清单删除安装到DragonOS sysroot中的文件，并清除任务的安装状态
+//! （设置了`install_once`的任务下次会重新安装）。
+//!
+//! ```bash
+//! dadk uninstall app-0.1.0                                  # 卸载指定的任务
+//! dadk -c <配置文件目录> -d <sysroot> uninstall --prune-removed  # 卸载已经从配置文件中移除的任务
+//! ```
+//!
+//! 安装后被修改过的文件默认不会被删除，需要指定`--force`。
+
+use std::path::{Path, PathBuf};
+
+use clap::Args;
+use log::{info, warn};
+
+use crate::{
+    executor::{cache::TaskDataDir, manifest::InstallManifest},
+    parser::task::{DADKTask, NAME_VERSION_REPLACE_TABLE},
+};
+
+#[derive(Debug, Args, Clone, PartialEq, Eq)]
+pub struct UninstallArg {
+    /// 要卸载的任务（`任务名-版本`）
+    pub tasks: Vec<String>,
+    /// 卸载安装过、但已经不在配置文件中的任务（需要指定配置文件目录和sysroot）
+    #[arg(long)]
+    pub prune_removed: bool,
+    /// 删除安装后被修改过的文件
+    #[arg(long)]
+    pub force: bool,
+}
+
+/// # 卸载命令行中指定的任务
+///
+/// 任务没有安装清单时返回错误
+pub fn uninstall_tasks(arg: &UninstallArg) -> Result<(), String> {
+    if arg.tasks.is_empty() && !arg.prune_removed {
+        return Err("no task to uninstall, specify tasks or --prune-removed".to_string());
+    }
+    for task in arg.tasks.iter() {
+        let mut name_version = task.clone();
+        for (src, dst) in &NAME_VERSION_REPLACE_TABLE {
+            name_version = name_version.replace(src, dst);
+        }
+        let manifest = InstallManifest::load(&name_version)?
+            .ok_or_else(|| format!("Task {} has no install manifest", task))?;
+        uninstall(&manifest, arg.force)?;
+    }
+    return Ok(());
+}
+
+/// # 卸载已经不在配置文件中的任务
+///
+/// 只处理安装到`sysroot`中的任务
+pub fn prune_removed(
+    arg: &UninstallArg,
+    tasks: &[(PathBuf, DADKTask)],
+    sysroot: &Path,
+) -> Result<(), String> {
+    let removed = removed_tasks(tasks, sysroot)?;
+    if removed.is_empty() {
+        info!("No removed task is installed in {}", sysroot.display());
+    }
+    for manifest in removed.iter() {
+        uninstall(manifest, arg.force)?;
+    }
+    return Ok(());
+}
+
+/// 安装在`sysroot`中、但已经不在配置文件中的任务的安装清单
+pub fn removed_tasks(
+    tasks: &[(PathBuf, DADKTask)],
+    sysroot: &Path,
+) -> Result<Vec<InstallManifest>, String> {
+    return Ok(InstallManifest::load_all()?
+        .into_iter()
+        .filter(|m| m.sysroot == sysroot)
+        .filter(|m| !tasks.iter().any(|(_, t)| t.name_version() == m.task))
+        .collect());
+}
+
+/// 卸载一个任务，删除它的安装清单并清除安装状态
+pub fn uninstall(manifest: &InstallManifest, force: bool) -> Result<(), String> {
+    info!("Uninstalling task: {}", manifest.task);
+    let summary = manifest.uninstall(force)?;
+    for path in summary.kept.iter() {
+        warn!(
+            "Task {}: {} is also installed by another task, keep it",
+            manifest.task,
+            path.display()
+        );
+    }
+    manifest.remove()?;
+    TaskDataDir::clean_install_status_of(&manifest.task).map_err(|e| format!("{:?}", e))?;
+    info!(
+        "Task {} uninstalled, {} paths removed",
+        manifest.task,
+        summary.removed.len()
+    );
+    return Ok(());
+}
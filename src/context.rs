@@ -10,8 +10,18 @@ use log::error;
 use test_base::{test_context::TestContext, BaseTestContext};
 
 use crate::{
-    console::Action, executor::cache::cache_root_init, parser::task::TargetArch,
-    scheduler::task_deque::TASK_DEQUE,
+    console::{cache::CacheCommand, Action},
+    executor::{
+        cache::cache_root_init,
+        diagnostics::DiagnosticMatcher,
+        events::{self, EventEmitter, EventSink},
+        output_log::{OutputLog, OutputLogConfig},
+    },
+    parser::{
+        resolver::ResolveMode,
+        task::{DocsPolicy, InstallConfig, TargetArch},
+    },
+    scheduler::task_deque::{DEFAULT_THREAD_NUM, MAX_THREAD_NUM, TASK_DEQUE},
 };
 
 #[derive(Debug, Builder)]
@@ -32,6 +42,72 @@ pub struct DadkExecuteContext {
     #[builder(default = "crate::DADKTask::default_target_arch()")]
     target_arch: TargetArch,
 
+    /// 是否把获取到的源码身份写入锁文件
+    #[builder(default = "false")]
+    lock: bool,
+
+    /// 是否允许更新锁文件
+    #[builder(default = "false")]
+    update_lock: bool,
+
+    /// DragonOS内核源码目录
+    #[builder(default = "None")]
+    kernel_src: Option<PathBuf>,
+    /// DragonOS内核构建目录，为None时与内核源码目录相同
+    #[builder(default = "None")]
+    kernel_build: Option<PathBuf>,
+    /// 内核的目标架构，为None时与目标架构相同
+    #[builder(default = "None")]
+    kernel_arch: Option<TargetArch>,
+    /// 构建时可以使用的总任务数，为None时使用CPU核心数
+    #[builder(default = "None")]
+    jobs: Option<usize>,
+    /// 生效的构建配置档
+    #[builder(default = "None")]
+    profile: Option<String>,
+    /// 依赖版本冲突的解析策略
+    #[builder(default = "ResolveMode::Independent")]
+    dep_resolve: ResolveMode,
+    /// 任务输出日志的配置
+    #[builder(default = "OutputLogConfig::default()")]
+    output_log: OutputLogConfig,
+    /// 附加的构建输出警告/错误匹配规则
+    #[builder(default = "Vec::new()")]
+    diagnostic_patterns: Vec<DiagnosticMatcher>,
+    /// 是否输出任务需要重新构建的原因
+    #[builder(default = "false")]
+    why_dirty: bool,
+    /// 执行事件的输出位置，为None时不输出事件
+    #[builder(default = "None")]
+    events: Option<EventSink>,
+    /// 是否默认在网络隔离的沙箱中构建
+    #[builder(default = "false")]
+    sandbox_network: bool,
+    /// 是否用多个线程解压在线压缩包
+    #[builder(default = "false")]
+    parallel_extract: bool,
+    /// 同时进行的下载数，为None时使用CPU核心数（有上限）
+    #[builder(default = "None")]
+    download_jobs: Option<usize>,
+    /// 安装时文档的默认处理方式
+    #[builder(default = "DocsPolicy::Keep")]
+    docs: DocsPolicy,
+    /// 安装时默认保留的语言，为None时保留所有语言
+    #[builder(default = "None")]
+    keep_locales: Option<Vec<String>>,
+    /// 禁止安装到的DragonOS内的路径，为None时使用默认的路径
+    #[builder(default = "None")]
+    deny_install_paths: Option<Vec<PathBuf>>,
+    /// 忽略`install_once`、总是重新安装的任务
+    #[builder(default = "Vec::new()")]
+    force_install: Vec<String>,
+    /// 是否按每个任务的`target_arch`中的所有架构分别执行任务
+    #[builder(default = "false")]
+    all_arches: bool,
+    /// 任务失败时是否立即停止，为false时继续执行不依赖失败任务的其他任务
+    #[builder(default = "true")]
+    fail_fast: bool,
+
     #[cfg(test)]
     base_test_context: Option<BaseTestContext>,
 
@@ -44,8 +120,7 @@ impl DadkExecuteContext {
         self.set_self_ref(Arc::downgrade(&self_arc));
 
         // 初始化缓存目录
-        let r: Result<(), crate::executor::ExecutorError> =
-            cache_root_init(self.cache_dir().cloned());
+        let r: Result<(), crate::executor::ExecError> = cache_root_init(self.cache_dir().cloned());
         if r.is_err() {
             error!("Failed to init cache root: {:?}", r.unwrap_err());
             exit(1);
@@ -55,8 +130,42 @@ impl DadkExecuteContext {
             TASK_DEQUE.lock().unwrap().set_thread(thread);
         }
 
-        if self.action() == &Action::New {
-            return;
+        OutputLog::configure(self.output_log);
+        DiagnosticMatcher::configure(self.diagnostic_patterns.clone());
+        crate::executor::set_why_dirty(self.why_dirty);
+        if let Some(sink) = &self.events {
+            match EventEmitter::open(sink) {
+                Ok(emitter) => events::set_emitter(Some(Arc::new(emitter))),
+                Err(e) => {
+                    error!("Failed to open event sink {:?}: {}", sink, e);
+                    exit(1);
+                }
+            }
+        }
+        crate::executor::sandbox::set_network_default(self.sandbox_network);
+        crate::executor::set_force_install(self.force_install.clone());
+        crate::executor::extract::set_jobs(if self.parallel_extract {
+            self.job_budget()
+        } else {
+            1
+        });
+        crate::executor::fetch::set_download_jobs(self.download_jobs.unwrap_or(0));
+        crate::executor::docs::set_defaults(self.docs, self.keep_locales.clone());
+        if let Some(paths) = &self.deny_install_paths {
+            InstallConfig::set_denied_paths(
+                paths
+                    .iter()
+                    .filter(|p| !p.as_os_str().is_empty())
+                    .cloned()
+                    .collect(),
+            );
+        }
+
+        // 查询安装清单、卸载指定的任务、统计缓存只需要缓存根目录
+        match self.action() {
+            Action::New | Action::Owns(_) | Action::Cache(CacheCommand::Stats) => return,
+            Action::Uninstall(arg) if !arg.prune_removed => return,
+            _ => {}
         }
 
         if self.config_dir().is_none() {
@@ -64,6 +173,13 @@ impl DadkExecuteContext {
             exit(1);
         }
 
+        // 列出任务、显示任务配置、回收缓存不需要DragonOS sysroot
+        if self.action() == &Action::List
+            || matches!(self.action(), Action::Info(_) | Action::Cache(_))
+        {
+            return;
+        }
+
         if self.sysroot_dir().is_none() {
             error!(
                 "dragonos sysroot dir is required for action: {:?}",
@@ -86,6 +202,14 @@ impl DadkExecuteContext {
         &self.target_arch
     }
 
+    pub fn all_arches(&self) -> bool {
+        self.all_arches
+    }
+
+    pub fn fail_fast(&self) -> bool {
+        self.fail_fast
+    }
+
     pub fn sysroot_dir(&self) -> Option<&PathBuf> {
         self.sysroot_dir.as_ref()
     }
@@ -105,6 +229,63 @@ impl DadkExecuteContext {
     pub fn cache_dir(&self) -> Option<&PathBuf> {
         self.cache_dir.as_ref()
     }
+
+    pub fn lock(&self) -> bool {
+        self.lock
+    }
+
+    pub fn update_lock(&self) -> bool {
+        self.update_lock
+    }
+
+    pub fn kernel_src(&self) -> Option<&PathBuf> {
+        self.kernel_src.as_ref()
+    }
+
+    /// 内核构建目录，没有指定时与内核源码目录相同
+    pub fn kernel_build(&self) -> Option<&PathBuf> {
+        self.kernel_build.as_ref().or(self.kernel_src.as_ref())
+    }
+
+    /// 内核的目标架构，没有指定时与目标架构相同
+    pub fn kernel_arch(&self) -> &TargetArch {
+        self.kernel_arch.as_ref().unwrap_or(&self.target_arch)
+    }
+
+    pub fn profile(&self) -> Option<&String> {
+        self.profile.as_ref()
+    }
+
+    pub fn dep_resolve(&self) -> ResolveMode {
+        self.dep_resolve
+    }
+
+    /// 构建时可以使用的总任务数，没有指定时为CPU核心数
+    pub fn jobs(&self) -> usize {
+        self.jobs.unwrap_or_else(|| {
+            std::thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1)
+        })
+    }
+
+    /// 同时执行的任务数
+    pub fn parallel_tasks(&self) -> usize {
+        self.thread_num
+            .unwrap_or(DEFAULT_THREAD_NUM)
+            .clamp(1, MAX_THREAD_NUM)
+    }
+
+    /// # 每个任务的构建任务数
+    ///
+    /// 把总任务数平分给同时执行的任务，至少为1
+    pub fn job_budget(&self) -> usize {
+        return Self::split_jobs(self.jobs(), self.parallel_tasks());
+    }
+
+    pub fn split_jobs(jobs: usize, parallel_tasks: usize) -> usize {
+        return (jobs / parallel_tasks.max(1)).max(1);
+    }
 }
 
 #[cfg(test)]
@@ -172,6 +353,51 @@ impl TestContext for DadkExecuteContextTestBuildRiscV64V1 {
     }
 }
 
+#[cfg(test)]
+pub struct DadkExecuteContextTestBuildAllArchesX86_64V1 {
+    context: Arc<DadkExecuteContext>,
+}
+
+#[cfg(test)]
+impl TestContext for DadkExecuteContextTestBuildAllArchesX86_64V1 {
+    fn setup() -> Self {
+        let base_context = BaseTestContext::setup();
+        let context =
+            DadkExecuteContextBuilder::default_test_execute_context_builder(&base_context)
+                .target_arch(TargetArch::X86_64)
+                .config_dir(Some(base_context.config_v1_dir()))
+                .all_arches(true)
+                .fail_fast(false)
+                .build()
+                .expect("Failed to build DadkExecuteContextTestBuildAllArchesX86_64V1");
+        let context = Arc::new(context);
+        context.init(context.clone());
+        DadkExecuteContextTestBuildAllArchesX86_64V1 { context }
+    }
+}
+
+#[cfg(test)]
+pub struct DadkExecuteContextTestBuildKernelModuleX86_64V1 {
+    context: Arc<DadkExecuteContext>,
+}
+
+#[cfg(test)]
+impl TestContext for DadkExecuteContextTestBuildKernelModuleX86_64V1 {
+    fn setup() -> Self {
+        let base_context = BaseTestContext::setup();
+        let context =
+            DadkExecuteContextBuilder::default_test_execute_context_builder(&base_context)
+                .target_arch(TargetArch::X86_64)
+                .config_dir(Some(base_context.config_v1_dir()))
+                .kernel_src(Some(base_context.abs_path("tests/data/fake_kernel_tree")))
+                .build()
+                .expect("Failed to build DadkExecuteContextTestBuildKernelModuleX86_64V1");
+        let context = Arc::new(context);
+        context.init(context.clone());
+        DadkExecuteContextTestBuildKernelModuleX86_64V1 { context }
+    }
+}
+
 macro_rules! impl_for_test_context {
     ($context:ty) => {
         #[cfg(test)]
@@ -198,3 +424,5 @@ macro_rules! impl_for_test_context {
 
 impl_for_test_context!(DadkExecuteContextTestBuildX86_64V1);
 impl_for_test_context!(DadkExecuteContextTestBuildRiscV64V1);
+impl_for_test_context!(DadkExecuteContextTestBuildKernelModuleX86_64V1);
+impl_for_test_context!(DadkExecuteContextTestBuildAllArchesX86_64V1);
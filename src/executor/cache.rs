@@ -1,20 +1,20 @@
 use std::{
-    path::PathBuf,
+    path::{Path, PathBuf},
     sync::{Arc, Once},
 };
 
-use log::info;
+use log::{info, warn};
 
 use crate::{
     parser::{
-        task::{CodeSource, DADKTask, TaskType},
+        task::{CodeSource, DADKTask, TargetArch, TaskType},
         task_log::TaskLog,
     },
     scheduler::SchedEntity,
-    utils::lazy_init::Lazy,
+    utils::{file_lock::FileLock, lazy_init::Lazy},
 };
 
-use super::ExecutorError;
+use super::ExecError;
 
 pub static CACHE_ROOT: Lazy<PathBuf> = Lazy::new();
 
@@ -23,7 +23,7 @@ pub static CACHE_ROOT: Lazy<PathBuf> = Lazy::new();
 /// ## 参数
 ///
 /// - `path` 缓存根目录的路径
-pub fn cache_root_init(path: Option<PathBuf>) -> Result<(), ExecutorError> {
+pub fn cache_root_init(path: Option<PathBuf>) -> Result<(), ExecError> {
     let cache_root: String;
     if path.is_none() {
         // 查询环境变量，是否有设置缓存根目录
@@ -33,11 +33,11 @@ pub fn cache_root_init(path: Option<PathBuf>) -> Result<(), ExecutorError> {
         } else {
             // 如果没有设置环境变量，则使用默认值
             // 默认值为当前目录下的.cache目录
-            let cwd = std::env::current_dir().map_err(|e| ExecutorError::IoError(e.to_string()))?;
+            let cwd = std::env::current_dir().map_err(|e| ExecError::Cache(e.to_string()))?;
             let cwd = cwd.to_str();
 
             if cwd.is_none() {
-                return Err(ExecutorError::IoError(
+                return Err(ExecError::Cache(
                     std::io::Error::new(
                         std::io::ErrorKind::Other,
                         "Current dir is not a valid unicode string",
@@ -52,7 +52,7 @@ pub fn cache_root_init(path: Option<PathBuf>) -> Result<(), ExecutorError> {
     } else {
         // 如果有设置缓存根目录，则使用设置的值
         let path = path.unwrap();
-        let x = path.to_str().ok_or(ExecutorError::IoError(
+        let x = path.to_str().ok_or(ExecError::Cache(
             std::io::Error::new(
                 std::io::ErrorKind::Other,
                 "Cache root dir is not a valid unicode string",
@@ -67,10 +67,10 @@ pub fn cache_root_init(path: Option<PathBuf>) -> Result<(), ExecutorError> {
     // 如果缓存根目录不存在，则创建
     if !cache_root.exists() {
         info!("Cache root dir not exists, create it: {:?}", cache_root);
-        std::fs::create_dir_all(&cache_root).map_err(|e| ExecutorError::IoError(e.to_string()))?;
+        std::fs::create_dir_all(&cache_root).map_err(|e| ExecError::Cache(e.to_string()))?;
     } else if !cache_root.is_dir() {
         // 如果缓存根目录不是目录，则报错
-        return Err(ExecutorError::IoError(
+        return Err(ExecError::Cache(
             std::io::Error::new(
                 std::io::ErrorKind::NotADirectory,
                 format!("Cache root dir is not a directory: {:?}", cache_root),
@@ -89,6 +89,67 @@ pub fn cache_root_init(path: Option<PathBuf>) -> Result<(), ExecutorError> {
     return Ok(());
 }
 
+/// # 任务缓存的锁
+///
+/// 同时持有源码缓存目录的锁和构建缓存、任务数据目录的锁，drop时释放
+#[derive(Debug)]
+pub struct TaskCacheLock {
+    _source: FileLock,
+    _cache: FileLock,
+}
+
+/// # 对任务的缓存加锁
+///
+/// 锁文件位于缓存根目录下的`locks`目录中，与被保护的缓存目录一一对应：
+///
+/// - 构建缓存和任务数据目录（`build/<缓存键>`、`task_data/<缓存键>`）按缓存键加锁
+/// - 源码缓存目录（`source/<name_version>`）在不同配置档、架构之间共享，单独加锁
+///
+/// 总是先获取源码的锁，再获取缓存键的锁，避免死锁。
+/// 其他DADK进程正在使用同一个缓存时，会等待其释放锁。
+pub fn lock_task_cache(task: &DADKTask) -> Result<TaskCacheLock, ExecError> {
+    let acquire = |path: PathBuf| {
+        FileLock::acquire(&path).map_err(|e| {
+            ExecError::Cache(format!("Failed to lock cache {}: {}", path.display(), e))
+        })
+    };
+    let root = CACHE_ROOT.get();
+    let source = acquire(source_lock_path(root, &task.name_version()))?;
+    let cache = acquire(cache_lock_path(root, &task.cache_key()))?;
+    return Ok(TaskCacheLock {
+        _source: source,
+        _cache: cache,
+    });
+}
+
+/// 保护`build/<缓存键>`和`task_data/<缓存键>`的锁文件，`root`为缓存根目录
+pub fn cache_lock_path(root: &Path, cache_key: &str) -> PathBuf {
+    return root.join("locks").join(format!("{}.lock", cache_key));
+}
+
+/// 保护`source/<name_version>`（以及叠加源的各层）的锁文件，`root`为缓存根目录
+pub fn source_lock_path(root: &Path, name_version: &str) -> PathBuf {
+    return root
+        .join("locks")
+        .join("source")
+        .join(format!("{}.lock", name_version));
+}
+
+/// # 缓存键是否属于任务
+///
+/// 缓存键为`<name_version>[@<配置档>][-<架构>]`
+pub fn cache_key_belongs_to(cache_key: &str, name_version: &str) -> bool {
+    let rest = match cache_key.strip_prefix(name_version) {
+        Some(rest) => rest,
+        None => return false,
+    };
+    return rest.is_empty()
+        || rest.starts_with('@')
+        || rest
+            .strip_prefix('-')
+            .is_some_and(|arch| TargetArch::try_from(arch).is_ok());
+}
+
 #[derive(Debug, Clone, Copy)]
 pub enum CacheDirType {
     /// 构建缓存目录
@@ -110,7 +171,7 @@ pub struct CacheDir {
 impl CacheDir {
     pub const DADK_BUILD_CACHE_DIR_ENV_KEY_PREFIX: &'static str = "DADK_BUILD_CACHE_DIR";
     pub const DADK_SOURCE_CACHE_DIR_ENV_KEY_PREFIX: &'static str = "DADK_SOURCE_CACHE_DIR";
-    pub fn new(entity: Arc<SchedEntity>, cache_type: CacheDirType) -> Result<Self, ExecutorError> {
+    pub fn new(entity: Arc<SchedEntity>, cache_type: CacheDirType) -> Result<Self, ExecError> {
         let task = entity.task();
         let path = Self::get_path(&task, cache_type);
 
@@ -128,43 +189,60 @@ impl CacheDir {
     fn get_path(task: &DADKTask, cache_type: CacheDirType) -> PathBuf {
         let cache_root = CACHE_ROOT.get();
         let name_version = task.name_version();
+        // 构建结果和任务数据按构建配置档区分，源码在不同配置档之间共享
+        let cache_key = task.cache_key();
         let cache_dir = match cache_type {
             CacheDirType::Build => {
-                format!("{}/build/{}", cache_root.to_str().unwrap(), name_version)
+                format!("{}/build/{}", cache_root.to_str().unwrap(), cache_key)
             }
             CacheDirType::Source => {
                 format!("{}/source/{}", cache_root.to_str().unwrap(), name_version)
             }
             CacheDirType::TaskData => {
-                format!(
-                    "{}/task_data/{}",
-                    cache_root.to_str().unwrap(),
-                    name_version
-                )
+                format!("{}/task_data/{}", cache_root.to_str().unwrap(), cache_key)
             }
         };
 
         return PathBuf::from(cache_dir);
     }
 
-    pub fn build_dir(entity: Arc<SchedEntity>) -> Result<PathBuf, ExecutorError> {
+    pub fn build_dir(entity: Arc<SchedEntity>) -> Result<PathBuf, ExecError> {
         return Ok(Self::new(entity.clone(), CacheDirType::Build)?.path);
     }
 
-    pub fn source_dir(entity: Arc<SchedEntity>) -> Result<PathBuf, ExecutorError> {
+    pub fn source_dir(entity: Arc<SchedEntity>) -> Result<PathBuf, ExecError> {
         return Ok(Self::new(entity.clone(), CacheDirType::Source)?.path);
     }
 
-    pub fn build_dir_env_key(entity: &Arc<SchedEntity>) -> Result<String, ExecutorError> {
-        let name_version_env = entity.task().name_version_env();
-        return Ok(format!(
+    /// 构建目录的环境变量名，按固定架构构建的依赖带有架构后缀，例如`DADK_BUILD_CACHE_DIR_TOOL_0_1_0_X86_64`
+    pub fn build_dir_env_key(entity: &Arc<SchedEntity>) -> Result<String, ExecError> {
+        let task = entity.task();
+        return Ok(Self::build_dir_env_key_of(
+            &task.name,
+            &task.version,
+            task.pinned_arch,
+        ));
+    }
+
+    /// 按名称、版本和固定的目标架构计算构建目录的环境变量名，用于查找依赖的构建目录
+    pub fn build_dir_env_key_of(
+        name: &str,
+        version: &str,
+        pinned_arch: Option<TargetArch>,
+    ) -> String {
+        let mut key = format!(
             "{}_{}",
             Self::DADK_BUILD_CACHE_DIR_ENV_KEY_PREFIX,
-            name_version_env
-        ));
+            DADKTask::name_version_uppercase(name, version)
+        );
+        if let Some(arch) = pinned_arch {
+            let arch: &str = arch.into();
+            key = format!("{}_{}", key, arch.to_ascii_uppercase());
+        }
+        return key;
     }
 
-    pub fn source_dir_env_key(entity: &Arc<SchedEntity>) -> Result<String, ExecutorError> {
+    pub fn source_dir_env_key(entity: &Arc<SchedEntity>) -> Result<String, ExecError> {
         let name_version_env = entity.task().name_version_env();
         return Ok(format!(
             "{}_{}",
@@ -176,33 +254,42 @@ impl CacheDir {
     pub fn need_source_cache(entity: &Arc<SchedEntity>) -> bool {
         let task_type = &entity.task().task_type;
 
-        if let TaskType::BuildFromSource(cs) = task_type {
-            match cs {
-                CodeSource::Git(_) | CodeSource::Archive(_) => {
+        match task_type {
+            TaskType::BuildFromSource(cs) | TaskType::KernelModule(cs) => match cs {
+                CodeSource::Git(_)
+                | CodeSource::Hg(_)
+                | CodeSource::Svn(_)
+                | CodeSource::Archive(_)
+                | CodeSource::ReleaseAsset(_)
+                | CodeSource::Overlay(_) => {
                     return true;
                 }
                 CodeSource::Local(_) => {
                     return false;
                 }
-            }
-        } else if let TaskType::InstallFromPrebuilt(ps) = task_type {
-            match ps {
+            },
+            TaskType::InstallFromPrebuilt(ps) => match ps {
+                // Git仓库需要先检出到源码缓存目录，再拷贝到构建结果目录
+                crate::parser::task::PrebuiltSource::Git(_) => return true,
                 crate::parser::task::PrebuiltSource::Archive(_) => return false,
                 crate::parser::task::PrebuiltSource::Local(_) => return false,
-            }
+                crate::parser::task::PrebuiltSource::Oci(_) => return false,
+                crate::parser::task::PrebuiltSource::ReleaseAsset(_) => return false,
+            },
+            // 没有源码的任务，使用空的源码缓存目录作为工作目录
+            TaskType::NoSource => return true,
+            TaskType::Meta => return false,
         }
-        unimplemented!("Not fully implemented task type: {:?}", task_type);
     }
 
-    pub fn create(&self) -> Result<(), ExecutorError> {
+    pub fn create(&self) -> Result<(), ExecError> {
         if !self.path.exists() {
             info!("Cache dir not exists, create it: {:?}", self.path);
-            std::fs::create_dir_all(&self.path)
-                .map_err(|e| ExecutorError::IoError(e.to_string()))?;
+            std::fs::create_dir_all(&self.path).map_err(|e| ExecError::Cache(e.to_string()))?;
             info!("Cache dir: [{:?}] created.", self.path);
         } else if !self.path.is_dir() {
             // 如果路径类别不是目录，则报错
-            return Err(ExecutorError::IoError(
+            return Err(ExecError::Cache(
                 std::io::Error::new(
                     std::io::ErrorKind::NotADirectory,
                     format!("Cache dir is not a directory: {:?}", self.path),
@@ -215,11 +302,11 @@ impl CacheDir {
     }
 
     /// 判断缓存目录是否为空
-    pub fn is_empty(&self) -> Result<bool, ExecutorError> {
+    pub fn is_empty(&self) -> Result<bool, ExecError> {
         let x = self
             .path
             .read_dir()
-            .map_err(|e| ExecutorError::IoError(e.to_string()))?;
+            .map_err(|e| ExecError::Cache(e.to_string()))?;
         for _ in x {
             return Ok(false);
         }
@@ -227,14 +314,34 @@ impl CacheDir {
         return Ok(true);
     }
 
+    /// # 获取叠加源中某一层的缓存目录
+    ///
+    /// 每一层被获取到`<源码缓存目录>.layers/<层的序号>`下，然后再叠加到源码缓存目录中
+    pub fn overlay_layer(&self, index: usize) -> Result<CacheDir, ExecError> {
+        let result = Self {
+            entity: self.entity.clone(),
+            path: self.overlay_layers_root().join(index.to_string()),
+            cache_type: self.cache_type,
+        };
+        result.create()?;
+        return Ok(result);
+    }
+
+    /// 叠加源的各层的缓存目录所在的目录
+    pub fn overlay_layers_root(&self) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(".layers");
+        return PathBuf::from(path);
+    }
+
     /// # 递归删除自身目录
     /// 递归删除自身目录，如果目录不存在，则忽略
     ///
     /// 请注意，这会删除整个目录，包括目录下的所有文件和子目录
-    pub fn remove_self_recursive(&self) -> Result<(), ExecutorError> {
+    pub fn remove_self_recursive(&self) -> Result<(), ExecError> {
         let path = &self.path;
         if path.exists() {
-            std::fs::remove_dir_all(path).map_err(|e| ExecutorError::IoError(e.to_string()))?;
+            std::fs::remove_dir_all(path).map_err(|e| ExecError::Cache(e.to_string()))?;
         }
         return Ok(());
     }
@@ -246,29 +353,77 @@ pub struct TaskDataDir {
 }
 
 impl TaskDataDir {
-    const TASK_LOG_FILE_NAME: &'static str = "task_log.toml";
-    pub fn new(entity: Arc<SchedEntity>) -> Result<Self, ExecutorError> {
+    pub const TASK_LOG_FILE_NAME: &'static str = "task_log.toml";
+    pub fn new(entity: Arc<SchedEntity>) -> Result<Self, ExecError> {
         let dir = CacheDir::new(entity.clone(), CacheDirType::TaskData)?;
         return Ok(Self { dir });
     }
 
+    /// 任务数据目录的路径
+    pub fn path(&self) -> &PathBuf {
+        return &self.dir.path;
+    }
+
     /// # 获取任务日志
+    ///
+    /// 任务日志无法解析时（例如旧版本写入的不完整的文件），视为没有任务日志
     pub fn task_log(&self) -> TaskLog {
-        let path = self.dir.path.join(Self::TASK_LOG_FILE_NAME);
+        return Self::read_task_log(&self.dir.path.join(Self::TASK_LOG_FILE_NAME));
+    }
+
+    /// # 获取任务日志，不创建任务数据目录
+    ///
+    /// 用于在执行任务之前预测任务的执行情况
+    pub fn task_log_of(task: &DADKTask) -> TaskLog {
+        let path = CacheDir::get_path(task, CacheDirType::TaskData).join(Self::TASK_LOG_FILE_NAME);
+        return Self::read_task_log(&path);
+    }
+
+    fn read_task_log(path: &Path) -> TaskLog {
         if path.exists() {
-            let content = std::fs::read_to_string(&path).unwrap();
-            let task_log: TaskLog = toml::from_str(&content).unwrap();
-            return task_log;
+            let content = std::fs::read_to_string(path).unwrap_or_default();
+            return toml::from_str(&content).unwrap_or_else(|e| {
+                warn!("Ignoring invalid task log {}: {}", path.display(), e);
+                TaskLog::new()
+            });
         } else {
             return TaskLog::new();
         }
     }
 
     /// # 设置任务日志
-    pub fn save_task_log(&self, task_log: &TaskLog) -> Result<(), ExecutorError> {
+    pub fn save_task_log(&self, task_log: &TaskLog) -> Result<(), ExecError> {
         let path = self.dir.path.join(Self::TASK_LOG_FILE_NAME);
         let content = toml::to_string(task_log).unwrap();
-        std::fs::write(&path, content).map_err(|e| ExecutorError::IoError(e.to_string()))?;
+        std::fs::write(&path, content).map_err(|e| ExecError::Cache(e.to_string()))?;
+        return Ok(());
+    }
+
+    /// # 清除任务在所有构建配置档、架构下的安装状态
+    ///
+    /// 卸载任务后调用，使设置了`install_once`的任务下次会重新安装。任务不需要在配置文件中
+    pub fn clean_install_status_of(name_version: &str) -> Result<(), ExecError> {
+        let dir = CACHE_ROOT.get().join("task_data");
+        let read_dir = match std::fs::read_dir(dir) {
+            Ok(read_dir) => read_dir,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(ExecError::Cache(e.to_string())),
+        };
+        for entry in read_dir {
+            let entry = entry.map_err(|e| ExecError::Cache(e.to_string()))?;
+            let cache_key = entry.file_name().to_string_lossy().to_string();
+            let path = entry.path().join(Self::TASK_LOG_FILE_NAME);
+            if !cache_key_belongs_to(&cache_key, name_version) || !path.exists() {
+                continue;
+            }
+            let content =
+                std::fs::read_to_string(&path).map_err(|e| ExecError::Cache(e.to_string()))?;
+            let mut task_log: TaskLog = toml::from_str(&content)
+                .map_err(|e| ExecError::Cache(format!("{}: {}", path.display(), e)))?;
+            task_log.clean_install_status();
+            let content = toml::to_string(&task_log).unwrap();
+            std::fs::write(&path, content).map_err(|e| ExecError::Cache(e.to_string()))?;
+        }
         return Ok(());
     }
 }
@@ -0,0 +1,103 @@
+//! # 清理计划
+//!
+//! `dadk clean --dry-run`按与清理相同的规则列出每个任务会执行的清理命令、会删除的文件和目录以及它们占用的空间，
+//! 但不执行任何命令，也不删除任何文件。
+//!
+//! 要删除的路径必须位于DADK管理的目录（缓存根目录）之中。由于符号链接等原因解析到缓存根目录之外的路径
+//! 在计划中被标记为异常，真正清理时也会被拒绝（见[`check_managed_path`]）。
+
+use std::path::{Path, PathBuf};
+
+use crate::console::clean::CleanLevel;
+
+use super::cache::CACHE_ROOT;
+
+/// 计划中要删除的一个路径
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedRemoval {
+    /// 路径所属的清理级别
+    pub level: CleanLevel,
+    pub path: PathBuf,
+    /// 占用的字节数
+    pub bytes: u64,
+}
+
+/// # 一个任务的清理计划
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CleanPlan {
+    /// 任务（`name_version`）
+    pub task: String,
+    /// 执行清理命令的目录
+    pub work_dir: Option<PathBuf>,
+    /// 按执行顺序排列的清理命令（已经替换了变量）
+    pub commands: Vec<String>,
+    /// 要删除的路径，按清理级别从低到高排列
+    pub removals: Vec<PlannedRemoval>,
+    /// 指定了清理命令、不删除目录的级别
+    pub kept: Vec<CleanLevel>,
+    /// 解析到DADK管理的目录之外的路径，真正清理时会被拒绝
+    pub anomalies: Vec<String>,
+    /// 计算计划时的错误
+    pub errors: Vec<String>,
+}
+
+impl CleanPlan {
+    pub fn new(task: String) -> Self {
+        return Self {
+            task,
+            ..Default::default()
+        };
+    }
+
+    /// 要删除的路径占用的总字节数
+    pub fn total_bytes(&self) -> u64 {
+        return self.removals.iter().map(|r| r.bytes).sum();
+    }
+
+    /// 既没有清理命令，也没有要删除的路径
+    pub fn is_empty(&self) -> bool {
+        return self.commands.is_empty() && self.removals.is_empty();
+    }
+
+    /// 计划中有异常或者错误
+    pub fn has_problems(&self) -> bool {
+        return !self.anomalies.is_empty() || !self.errors.is_empty();
+    }
+}
+
+/// # 检查要删除的路径是否位于DADK管理的目录之中
+///
+/// 路径的父目录解析符号链接后必须位于缓存根目录之中，并且路径不能是缓存根目录本身。
+/// 路径本身是符号链接时只删除链接，因此不解析最后一个部分。路径不存在时不检查
+pub fn check_managed_path(path: &Path) -> Result<(), String> {
+    let root = CACHE_ROOT.get();
+    if path.symlink_metadata().is_err() {
+        return Ok(());
+    }
+    let outside = || {
+        format!(
+            "{} resolves outside the DADK cache root {}",
+            path.display(),
+            root.display()
+        )
+    };
+    let root = root.canonicalize().map_err(|e| {
+        format!(
+            "failed to resolve the DADK cache root {}: {}",
+            root.display(),
+            e
+        )
+    })?;
+    let (parent, name) = match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => (parent, name),
+        _ => return Err(outside()),
+    };
+    let resolved = parent
+        .canonicalize()
+        .map_err(|e| format!("failed to resolve {}: {}", path.display(), e))?
+        .join(name);
+    if resolved == root || !resolved.starts_with(&root) {
+        return Err(outside());
+    }
+    return Ok(());
+}
@@ -0,0 +1,149 @@
+//! # 构建输出中的警告和错误统计
+//!
+//! 执行器在构建阶段逐行扫描命令的输出，用正则表达式匹配编译器输出的警告和错误，
+//! 统计每个任务的警告数和错误数。内置了gcc/clang和rustc的匹配规则，
+//! 其他工具链可以通过`--diagnostic-pattern`添加规则。
+
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    RwLock,
+};
+
+use regex::Regex;
+
+use super::result::DiagnosticCounts;
+
+lazy_static! {
+    static ref EXTRA_MATCHERS: RwLock<Vec<DiagnosticMatcher>> = RwLock::new(Vec::new());
+}
+
+/// 内置的匹配规则
+const BUILTIN_PATTERNS: [(DiagnosticKind, &str); 4] = [
+    // gcc/clang: `main.c:3:5: warning: unused variable 'x'`
+    (DiagnosticKind::Warning, r"^\S[^:]*:\d+(:\d+)?: warning: "),
+    (
+        DiagnosticKind::Error,
+        r"^\S[^:]*:\d+(:\d+)?: (fatal )?error: ",
+    ),
+    // rustc: `warning: unused variable: `x`` / `error[E0425]: cannot find value`
+    (DiagnosticKind::Warning, r"^warning(\[[\w:-]+\])?: "),
+    (DiagnosticKind::Error, r"^error(\[[\w:-]+\])?: "),
+];
+
+/// 不计数的行：cargo输出的汇总信息，它们是对前面已经计数的警告、错误的重复
+const BUILTIN_IGNORES: [&str; 3] = [
+    r"^warning: .* generated \d+ warnings?",
+    r"^error: could not compile ",
+    r"^error: aborting due to ",
+];
+
+/// # 诊断信息的种类
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiagnosticKind {
+    Warning,
+    Error,
+}
+
+impl TryFrom<&str> for DiagnosticKind {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, String> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "warning" => Ok(DiagnosticKind::Warning),
+            "error" => Ok(DiagnosticKind::Error),
+            _ => Err(format!(
+                "Unknown diagnostic kind: {}, expected warning or error",
+                value
+            )),
+        }
+    }
+}
+
+/// # 诊断信息的匹配规则
+#[derive(Debug, Clone)]
+pub struct DiagnosticMatcher {
+    kind: DiagnosticKind,
+    pattern: Regex,
+}
+
+impl DiagnosticMatcher {
+    pub fn new(kind: DiagnosticKind, pattern: &str) -> Result<Self, String> {
+        let pattern = Regex::new(pattern)
+            .map_err(|e| format!("invalid diagnostic pattern {:?}: {}", pattern, e))?;
+        return Ok(Self { kind, pattern });
+    }
+
+    /// # 解析命令行中的匹配规则
+    ///
+    /// 格式为`<warning|error>=<正则表达式>`，例如`warning=^WARN: `
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let (kind, pattern) = spec.split_once('=').ok_or_else(|| {
+            format!(
+                "invalid diagnostic pattern {:?}, expected <warning|error>=<regex>",
+                spec
+            )
+        })?;
+        return Self::new(DiagnosticKind::try_from(kind)?, pattern);
+    }
+
+    /// 设置全局的附加匹配规则，内置规则总是生效
+    pub fn configure(extra: Vec<DiagnosticMatcher>) {
+        *EXTRA_MATCHERS.write().unwrap() = extra;
+    }
+}
+
+/// # 诊断信息计数器
+///
+/// 可以在多个线程中同时扫描标准输出和标准错误
+#[derive(Debug)]
+pub struct DiagnosticCounter {
+    matchers: Vec<DiagnosticMatcher>,
+    ignores: Vec<Regex>,
+    warnings: AtomicUsize,
+    errors: AtomicUsize,
+}
+
+impl DiagnosticCounter {
+    /// 使用内置规则以及全局配置的附加规则创建计数器
+    pub fn new() -> Self {
+        let mut matchers: Vec<DiagnosticMatcher> = BUILTIN_PATTERNS
+            .iter()
+            .map(|(kind, pattern)| DiagnosticMatcher::new(*kind, pattern).unwrap())
+            .collect();
+        matchers.extend(EXTRA_MATCHERS.read().unwrap().iter().cloned());
+        let ignores = BUILTIN_IGNORES
+            .iter()
+            .map(|pattern| Regex::new(pattern).unwrap())
+            .collect();
+        return Self {
+            matchers,
+            ignores,
+            warnings: AtomicUsize::new(0),
+            errors: AtomicUsize::new(0),
+        };
+    }
+
+    /// # 扫描一行输出
+    ///
+    /// 一行最多计数一次，以第一条匹配的规则为准
+    pub fn scan(&self, line: &str) {
+        let line = line.trim_end();
+        if self.ignores.iter().any(|re| re.is_match(line)) {
+            return;
+        }
+        if let Some(m) = self.matchers.iter().find(|m| m.pattern.is_match(line)) {
+            let counter = match m.kind {
+                DiagnosticKind::Warning => &self.warnings,
+                DiagnosticKind::Error => &self.errors,
+            };
+            counter.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn counts(&self) -> DiagnosticCounts {
+        return DiagnosticCounts {
+            warnings: self.warnings.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+        };
+    }
+}
@@ -0,0 +1,253 @@
+//! # 安装时处理文档和翻译文件
+//!
+//! 任务的`install.docs`（或命令行中的`--docs`）决定安装到标准文档目录
+//! （`/usr/share/doc`、`/usr/share/man`、`/usr/share/info`以及`/usr/local`下对应的目录）中的文件如何处理：
+//! `keep`原样保留，`compress`用gzip压缩（文件名加上`.gz`），`strip`删除。
+//!
+//! 任务的`install.locales`（或命令行中的`--keep-locales`）是保留的语言列表，
+//! `/usr/share/locale`下其他语言的目录中的文件会被删除。
+//!
+//! 这些处理在按`include`、`exclude`拷贝之后进行，只影响本任务安装的文件，构建缓存中的文件保持不变。
+//! 处理后的文件列表会写入安装清单，减少的字节数记录在任务的执行结果中。
+
+use std::{
+    collections::BTreeSet,
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+    sync::RwLock,
+};
+
+use flate2::{write::GzEncoder, Compression};
+use log::debug;
+
+use crate::{
+    parser::task::{DocsPolicy, InstallConfig},
+    utils::file::FileUtils,
+};
+
+/// 标准文档目录（DragonOS内的路径，不带开头的斜杠）
+const DOC_DIRS: [&str; 6] = [
+    "usr/share/doc",
+    "usr/share/man",
+    "usr/share/info",
+    "usr/local/share/doc",
+    "usr/local/share/man",
+    "usr/local/share/info",
+];
+
+/// 翻译文件目录，其中每个子目录对应一种语言
+const LOCALE_DIRS: [&str; 2] = ["usr/share/locale", "usr/local/share/locale"];
+
+/// 已经压缩过的文件的扩展名，`compress`时不会再次压缩
+const COMPRESSED_EXTENSIONS: [&str; 6] = ["gz", "bz2", "xz", "zst", "lz", "Z"];
+
+/// 命令行中指定的默认处理方式（`--docs`、`--keep-locales`），任务中的配置优先
+static DEFAULTS: RwLock<(DocsPolicy, Option<Vec<String>>)> = RwLock::new((DocsPolicy::Keep, None));
+
+pub fn set_defaults(docs: DocsPolicy, locales: Option<Vec<String>>) {
+    *DEFAULTS.write().unwrap() = (docs, locales);
+}
+
+/// # 任务实际使用的文档处理方式和保留的语言
+pub fn effective_policy(install: &InstallConfig) -> (DocsPolicy, Option<Vec<String>>) {
+    let defaults = DEFAULTS.read().unwrap();
+    return (
+        install.docs.unwrap_or(defaults.0),
+        install.locales.clone().or_else(|| defaults.1.clone()),
+    );
+}
+
+/// 任务是否需要处理文档或翻译文件
+pub fn is_enabled(install: &InstallConfig) -> bool {
+    let (docs, locales) = effective_policy(install);
+    return docs != DocsPolicy::Keep || locales.is_some();
+}
+
+/// # 处理安装的文档和翻译文件
+///
+/// ## 参数
+///
+/// * `docs` - 文档的处理方式
+/// * `locales` - 保留的语言，为None时保留所有语言
+/// * `files` - 安装的文件和符号链接，相对于`install_path`
+/// * `install_path` - 安装路径（主机上的路径）
+/// * `sysroot` - DragonOS sysroot在主机上的路径
+///
+/// ## 返回值
+///
+/// 处理后的文件列表（相对于`install_path`，删除的文件不在其中，压缩的文件带有`.gz`后缀），以及减少的字节数
+pub fn apply(
+    docs: DocsPolicy,
+    locales: Option<&[String]>,
+    files: Vec<PathBuf>,
+    install_path: &Path,
+    sysroot: &Path,
+) -> Result<(Vec<PathBuf>, u64), String> {
+    let mut result = Vec::with_capacity(files.len());
+    let mut before = 0u64;
+    let mut after = 0u64;
+    // 被压缩的文件在主机上的路径（压缩前）
+    let mut compressed = BTreeSet::new();
+    // 需要在压缩之后处理的符号链接
+    let mut links = Vec::new();
+    for file in files {
+        let host = install_path.join(&file);
+        let in_dragonos = host.strip_prefix(sysroot).unwrap_or(&host).to_path_buf();
+        let meta = host
+            .symlink_metadata()
+            .map_err(|e| format!("Failed to stat {}: {}", host.display(), e))?;
+        let size = if meta.file_type().is_file() {
+            meta.len()
+        } else {
+            0
+        };
+
+        match action_for(docs, locales, &in_dragonos) {
+            DocsAction::Keep => {}
+            DocsAction::Remove(root) => {
+                debug!("Remove {}", host.display());
+                std::fs::remove_file(&host)
+                    .map_err(|e| format!("Failed to remove {}: {}", host.display(), e))?;
+                remove_empty_parents(&host, &sysroot.join(root));
+                before += size;
+                continue;
+            }
+            DocsAction::Compress if meta.file_type().is_symlink() => {
+                links.push(file);
+                continue;
+            }
+            DocsAction::Compress if meta.file_type().is_file() => {
+                let gz = gz_path(&host);
+                debug!("Compress {}", host.display());
+                gzip_file(&host, &gz)?;
+                before += size;
+                after += std::fs::metadata(&gz).map(|m| m.len()).unwrap_or(0);
+                compressed.insert(FileUtils::normalize_path(&host));
+                result.push(gz_path(&file));
+                continue;
+            }
+            DocsAction::Compress => {}
+        }
+        result.push(file);
+    }
+
+    // 指向被压缩的文件的符号链接，改为指向压缩后的文件
+    for file in links {
+        let host = install_path.join(&file);
+        let target = std::fs::read_link(&host)
+            .map_err(|e| format!("Failed to read link {}: {}", host.display(), e))?;
+        let resolved = if target.is_absolute() {
+            sysroot.join(target.strip_prefix("/").unwrap())
+        } else {
+            host.parent().unwrap().join(&target)
+        };
+        if !compressed.contains(&FileUtils::normalize_path(&resolved)) {
+            result.push(file);
+            continue;
+        }
+        let link = gz_path(&host);
+        std::fs::remove_file(&host)
+            .map_err(|e| format!("Failed to remove {}: {}", host.display(), e))?;
+        std::os::unix::fs::symlink(gz_path(&target), &link)
+            .map_err(|e| format!("Failed to create symlink {}: {}", link.display(), e))?;
+        result.push(gz_path(&file));
+    }
+    result.sort();
+    return Ok((result, before.saturating_sub(after)));
+}
+
+/// # 文档处理方式对一个文件的处理
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocsAction {
+    /// 保持不变
+    Keep,
+    /// 用gzip压缩，文件名加上`.gz`
+    Compress,
+    /// 删除，附带文件所在的文档或翻译文件目录
+    Remove(&'static str),
+}
+
+/// # 文档处理方式和保留的语言对一个安装的文件（DragonOS内的路径，不带开头的斜杠）的处理
+pub fn action_for(docs: DocsPolicy, locales: Option<&[String]>, in_dragonos: &Path) -> DocsAction {
+    if let (Some(keep), Some((root, lang))) = (locales, locale_of(in_dragonos)) {
+        if !keep.iter().any(|k| locale_matches(k.trim(), lang)) {
+            return DocsAction::Remove(root);
+        }
+    }
+    return match (doc_root_of(in_dragonos), docs) {
+        (Some(root), DocsPolicy::Strip) => DocsAction::Remove(root),
+        (Some(_), DocsPolicy::Compress) if !is_compressed(in_dragonos) => DocsAction::Compress,
+        _ => DocsAction::Keep,
+    };
+}
+
+/// 文件所在的文档目录
+fn doc_root_of(in_dragonos: &Path) -> Option<&'static str> {
+    return DOC_DIRS
+        .iter()
+        .find(|dir| in_dragonos.starts_with(dir) && in_dragonos != Path::new(dir))
+        .copied();
+}
+
+/// 文件所在的翻译文件目录，以及文件所属的语言
+fn locale_of(in_dragonos: &Path) -> Option<(&'static str, &str)> {
+    for dir in LOCALE_DIRS.iter() {
+        if let Ok(relative) = in_dragonos.strip_prefix(dir) {
+            let mut components = relative.components();
+            let lang = components.next()?.as_os_str().to_str()?;
+            // 直接放在翻译文件目录中的文件（例如`locale.alias`）不属于任何语言
+            components.next()?;
+            return Some((dir, lang));
+        }
+    }
+    return None;
+}
+
+/// # 判断语言目录是否匹配保留的语言
+///
+/// `zh`匹配`zh`、`zh_CN`、`zh_CN.UTF-8`、`zh@latin`等
+fn locale_matches(keep: &str, lang: &str) -> bool {
+    return match lang.strip_prefix(keep) {
+        Some(rest) => rest.is_empty() || rest.starts_with(['_', '.', '@']),
+        None => false,
+    };
+}
+
+fn is_compressed(path: &Path) -> bool {
+    return path
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| COMPRESSED_EXTENSIONS.contains(&e));
+}
+
+pub fn gz_path(path: &Path) -> PathBuf {
+    let mut s = path.as_os_str().to_owned();
+    s.push(".gz");
+    return PathBuf::from(s);
+}
+
+/// 把`src`压缩为`dst`，保留原文件的权限，成功后删除`src`
+fn gzip_file(src: &Path, dst: &Path) -> Result<(), String> {
+    let err = |e: std::io::Error| format!("Failed to compress {}: {}", src.display(), e);
+    let mut input = File::open(src).map_err(err)?;
+    let output = File::create(dst).map_err(err)?;
+    let mut encoder = GzEncoder::new(BufWriter::new(output), Compression::best());
+    std::io::copy(&mut input, &mut encoder).map_err(err)?;
+    encoder.finish().map_err(err)?;
+    let permissions = std::fs::metadata(src).map_err(err)?.permissions();
+    std::fs::set_permissions(dst, permissions).map_err(err)?;
+    std::fs::remove_file(src).map_err(err)?;
+    return Ok(());
+}
+
+/// 删除文件后，删除`root`以下变为空的目录（不包括`root`）
+fn remove_empty_parents(path: &Path, root: &Path) {
+    let mut dir = path.parent();
+    while let Some(d) = dir {
+        if d == root || !d.starts_with(root) || std::fs::remove_dir(d).is_err() {
+            break;
+        }
+        dir = d.parent();
+    }
+}
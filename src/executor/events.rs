@@ -0,0 +1,222 @@
+//! # 执行事件流
+//!
+//! 指定了`--events <路径>`时，DADK在执行任务时把事件以NDJSON格式（每行一个JSON对象）写入文件，
+//! 路径为`-`时写入标准输出，便于CI等工具读取构建进度。此时日志、构建命令以及git等命令的标准输出
+//! 都改为输出到标准错误，标准输出中只有事件。
+//!
+//! 每个事件都有`event`（事件类型）、`name_version`（任务）和`timestamp`（RFC 3339格式的UTC时间）：
+//!
+//! - `task_started`：开始执行任务，`action`为执行的操作（例如`build`、`install`、`clean`）
+//! - `download_progress`：下载进度，`bytes`为已经下载的字节数，`total`为总字节数（服务器没有返回长度时为`null`）
+//! - `phase_completed`：一个阶段（`source`、`build`、`install`、`clean`）执行完毕，`duration_ms`为阶段的时长
+//! - `task_completed`：任务执行成功
+//! - `task_failed`：任务执行失败，`error`为错误信息，`phase`为失败时所在的阶段
+//!
+//! ```text
+//! {"event":"task_started","action":"build","name_version":"app_0_1_0","timestamp":"2024-05-01T08:00:00.000Z"}
+//! {"event":"phase_completed","phase":"source","duration_ms":12,"name_version":"app_0_1_0","timestamp":"..."}
+//! ```
+
+use std::{
+    fmt::Debug,
+    io::Write,
+    path::PathBuf,
+    process::Stdio,
+    sync::{Arc, Mutex, RwLock},
+};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use lazy_static::lazy_static;
+use log::warn;
+use serde::{Deserialize, Serialize, Serializer};
+
+use super::progress::ProgressReporter;
+
+lazy_static! {
+    // 命令行指定的事件输出，调度器把它设置到每个执行器中
+    static ref EMITTER: RwLock<Option<Arc<EventEmitter>>> = RwLock::new(None);
+}
+
+/// # 事件类型
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum EventKind {
+    TaskStarted {
+        action: String,
+    },
+    DownloadProgress {
+        bytes: u64,
+        total: Option<u64>,
+    },
+    PhaseCompleted {
+        phase: String,
+        duration_ms: u64,
+    },
+    TaskCompleted,
+    TaskFailed {
+        error: String,
+        phase: Option<String>,
+    },
+}
+
+/// # 一个事件
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Event {
+    #[serde(flatten)]
+    pub kind: EventKind,
+    /// 任务的`name_version()`
+    pub name_version: String,
+    #[serde(serialize_with = "serialize_timestamp")]
+    pub timestamp: DateTime<Utc>,
+}
+
+fn serialize_timestamp<S: Serializer>(t: &DateTime<Utc>, s: S) -> Result<S::Ok, S::Error> {
+    return s.serialize_str(&t.to_rfc3339_opts(SecondsFormat::Millis, true));
+}
+
+/// # 事件的输出位置
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EventSink {
+    Stdout,
+    File(PathBuf),
+}
+
+impl EventSink {
+    /// 解析命令行参数，`-`表示标准输出
+    pub fn parse(s: &str) -> Result<Self, String> {
+        return match s.trim() {
+            "" => Err("event sink path is empty".to_string()),
+            "-" => Ok(Self::Stdout),
+            path => Ok(Self::File(PathBuf::from(path))),
+        };
+    }
+}
+
+/// # 事件输出器
+///
+/// 多个执行器共用一个输出器，每个事件一次写入一整行，不同任务的事件不会交错在同一行中
+pub struct EventEmitter {
+    out: Mutex<Box<dyn Write + Send>>,
+    /// 是否输出到标准输出
+    stdout: bool,
+}
+
+impl Debug for EventEmitter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventEmitter").finish_non_exhaustive()
+    }
+}
+
+impl EventEmitter {
+    pub fn new(out: Box<dyn Write + Send>) -> Self {
+        return Self {
+            out: Mutex::new(out),
+            stdout: false,
+        };
+    }
+
+    /// 打开事件的输出位置，文件已经存在时覆盖
+    pub fn open(sink: &EventSink) -> std::io::Result<Self> {
+        return match sink {
+            EventSink::Stdout => Ok(Self {
+                out: Mutex::new(Box::new(std::io::stdout())),
+                stdout: true,
+            }),
+            EventSink::File(path) => Ok(Self::new(Box::new(std::fs::File::create(path)?))),
+        };
+    }
+
+    /// 事件是否输出到标准输出，此时其他输出都不能写入标准输出
+    pub fn writes_stdout(&self) -> bool {
+        return self.stdout;
+    }
+
+    /// # 输出一个事件
+    ///
+    /// 写入失败时只输出警告，不影响任务的执行
+    pub fn emit(&self, name_version: &str, kind: EventKind) {
+        let event = Event {
+            kind,
+            name_version: name_version.to_string(),
+            timestamp: Utc::now(),
+        };
+        let mut line = serde_json::to_string(&event).unwrap();
+        line.push('\n');
+        let mut out = self.out.lock().unwrap();
+        if let Err(e) = out.write_all(line.as_bytes()).and_then(|_| out.flush()) {
+            warn!("Failed to write event of task {}: {}", name_version, e);
+        }
+    }
+}
+
+/// 设置命令行指定的事件输出器（`--events`）
+pub fn set_emitter(emitter: Option<Arc<EventEmitter>>) {
+    *EMITTER.write().unwrap() = emitter;
+}
+
+/// 命令行指定的事件输出器，没有指定时为None
+pub fn emitter() -> Option<Arc<EventEmitter>> {
+    return EMITTER.read().unwrap().clone();
+}
+
+/// # 子进程的标准输出
+///
+/// 事件输出到标准输出（`--events -`）时，把子进程的标准输出重定向到标准错误，
+/// 否则继承DADK的标准输出
+pub fn child_stdout() -> Stdio {
+    if emitter().is_some_and(|e| e.writes_stdout()) {
+        return Stdio::from(std::io::stderr());
+    }
+    return Stdio::inherit();
+}
+
+/// # 把下载进度作为事件输出
+///
+/// 下载进度每增加1%（不知道总字节数时每下载1MiB）输出一次，其他回调转发给原来的进度报告器
+#[derive(Debug)]
+pub struct EventProgressReporter {
+    inner: Arc<dyn ProgressReporter>,
+    emitter: Arc<EventEmitter>,
+    name_version: String,
+    /// 上一次输出的下载进度（百分比或者MiB数）
+    last_step: Mutex<Option<u64>>,
+}
+
+impl EventProgressReporter {
+    pub fn new(
+        inner: Arc<dyn ProgressReporter>,
+        emitter: Arc<EventEmitter>,
+        name_version: String,
+    ) -> Self {
+        return Self {
+            inner,
+            emitter,
+            name_version,
+            last_step: Mutex::new(None),
+        };
+    }
+}
+
+impl ProgressReporter for EventProgressReporter {
+    fn on_stage(&self, name: &str) {
+        *self.last_step.lock().unwrap() = None;
+        self.inner.on_stage(name);
+    }
+
+    fn on_download_progress(&self, bytes: u64, total: Option<u64>) {
+        self.inner.on_download_progress(bytes, total);
+        let step = match total {
+            Some(total) if total > 0 => bytes.min(total) * 100 / total,
+            _ => bytes >> 20,
+        };
+        let mut last = self.last_step.lock().unwrap();
+        if *last == Some(step) {
+            return;
+        }
+        *last = Some(step);
+        self.emitter.emit(
+            &self.name_version,
+            EventKind::DownloadProgress { bytes, total },
+        );
+    }
+}
@@ -0,0 +1,643 @@
+//! # 解压压缩包
+//!
+//! tar.gz、tar.xz（通过`xz -dc`解压缩数据流）和zip压缩包都在进程内解压。
+//! 指定`--parallel-extract`时，写入文件的工作分给多个线程，线程数为每个任务的构建任务数（见`--jobs`）。
+//!
+//! 为了保证多线程解压的结果与按顺序解压相同：
+//! - 目录在读取压缩包的线程中按顺序创建，文件写入之前它的上级目录已经存在
+//! - 同一个路径的所有成员交给同一个线程按顺序写入，后出现的成员覆盖前面的成员
+//! - 符号链接和硬链接在所有文件写入之后，按在压缩包中的顺序创建，写入文件时不会经过压缩包中的符号链接
+//! - 目录的权限在最后设置，只读的目录不会影响其中文件的写入
+//!
+//! 成员名按字面规范化后才使用。成员名是绝对路径、或者规范化后会跳出解压目录（例如`../evil`）的压缩包会被拒绝，
+//! 经过压缩包中的符号链接指向解压目录以外的硬链接也会被拒绝，错误信息中包含有问题的成员名。
+
+use std::{
+    collections::hash_map::DefaultHasher,
+    fs::File,
+    hash::{Hash, Hasher},
+    io::{BufReader, Read, Write},
+    os::unix::fs::PermissionsExt,
+    path::{Component, Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{sync_channel, Receiver, SyncSender},
+    },
+};
+
+use log::{debug, warn};
+use zip::ZipArchive;
+
+/// 解压时写入文件的线程数，1表示在读取压缩包的线程中按顺序写入
+static JOBS: AtomicUsize = AtomicUsize::new(1);
+
+/// 超过这个大小的文件先写入临时文件，再交给写入线程移动到目标位置，避免占用过多内存
+const INLINE_LIMIT: u64 = 4 * 1024 * 1024;
+
+/// 每个写入线程的队列长度
+const QUEUE_DEPTH: usize = 16;
+
+/// 设置解压时写入文件的线程数
+pub fn set_jobs(jobs: usize) {
+    JOBS.store(jobs.max(1), Ordering::Relaxed);
+}
+
+/// 解压时写入文件的线程数
+pub fn jobs() -> usize {
+    return JOBS.load(Ordering::Relaxed);
+}
+
+/// # 把压缩包的成员名转换为相对于解压目录的路径
+///
+/// 按字面规范化成员名：去掉`.`，并用`..`抵消前一级目录。
+/// 成员名是绝对路径、或者规范化后会跳出解压目录时返回错误。返回空路径表示解压目录本身
+pub fn member_path(name: &str) -> Result<PathBuf, String> {
+    let mut path = PathBuf::new();
+    for component in Path::new(name).components() {
+        match component {
+            Component::Normal(c) => path.push(c),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !path.pop() {
+                    return Err(format!(
+                        "archive member {:?} escapes the extraction directory",
+                        name
+                    ));
+                }
+            }
+            Component::RootDir | Component::Prefix(_) => {
+                return Err(format!(
+                    "archive member {:?} is an absolute path, refusing to extract it",
+                    name
+                ));
+            }
+        }
+    }
+    return Ok(path);
+}
+
+/// # 检查解压目录中的路径在解析符号链接之后是否仍然在解压目录中
+///
+/// `path`的上级目录必须存在，`follow_last`为false时不解析最后一级
+fn ensure_inside(dest: &Path, path: &Path, follow_last: bool) -> Result<(), String> {
+    let err = |e: std::io::Error| format!("Failed to resolve {}: {}", path.display(), e);
+    let root = dest.canonicalize().map_err(err)?;
+    let resolved = if follow_last {
+        path.canonicalize().map_err(err)?
+    } else {
+        let parent = path.parent().unwrap_or(dest).canonicalize().map_err(err)?;
+        match path.file_name() {
+            Some(name) => parent.join(name),
+            None => parent,
+        }
+    };
+    if !resolved.starts_with(root) {
+        return Err(format!(
+            "archive member {} resolves to {}, outside the extraction directory",
+            path.strip_prefix(dest).unwrap_or(path).display(),
+            resolved.display()
+        ));
+    }
+    return Ok(());
+}
+
+/// 写入线程的一项工作
+enum Payload {
+    /// 文件内容
+    Data(Vec<u8>),
+    /// 已经写入临时文件的内容
+    Temp(PathBuf),
+}
+
+/// 要写入的文件
+struct FileJob {
+    path: PathBuf,
+    payload: Payload,
+    mode: Option<u32>,
+}
+
+impl FileJob {
+    fn write(self) -> Result<(), String> {
+        let err = |e: std::io::Error| format!("Failed to extract {}: {}", self.path.display(), e);
+        // 与tar相同，先删除已有的文件（或符号链接），不会写入符号链接指向的文件
+        if self
+            .path
+            .symlink_metadata()
+            .is_ok_and(|m| !m.file_type().is_dir())
+        {
+            std::fs::remove_file(&self.path).map_err(err)?;
+        }
+        match &self.payload {
+            Payload::Data(data) => {
+                File::create(&self.path)
+                    .and_then(|mut f| f.write_all(data))
+                    .map_err(err)?;
+            }
+            Payload::Temp(temp) => std::fs::rename(temp, &self.path).map_err(err)?,
+        }
+        if let Some(mode) = self.mode {
+            std::fs::set_permissions(&self.path, std::fs::Permissions::from_mode(mode))
+                .map_err(err)?;
+        }
+        return Ok(());
+    }
+}
+
+/// # 写入文件的线程池
+///
+/// 按路径把文件分给固定的线程，同一个路径的文件按提交的顺序写入
+struct Writers {
+    senders: Vec<SyncSender<FileJob>>,
+}
+
+impl Writers {
+    fn new(jobs: usize) -> (Self, Vec<Receiver<FileJob>>) {
+        let (senders, receivers) = (0..jobs).map(|_| sync_channel(QUEUE_DEPTH)).unzip();
+        return (Self { senders }, receivers);
+    }
+
+    fn submit(&self, job: FileJob) -> Result<(), String> {
+        let mut hasher = DefaultHasher::new();
+        job.path.hash(&mut hasher);
+        let i = (hasher.finish() % self.senders.len() as u64) as usize;
+        // 写入线程出错退出时，错误在线程结束后返回
+        return self.senders[i]
+            .send(job)
+            .map_err(|_| "extraction worker exited".to_string());
+    }
+
+    /// 写入线程：出错后丢弃剩余的工作，返回第一个错误
+    fn run(receiver: Receiver<FileJob>) -> Result<(), String> {
+        let mut result = Ok(());
+        for job in receiver {
+            if result.is_ok() {
+                result = job.write();
+            }
+        }
+        return result;
+    }
+}
+
+/// 所有文件写入之后创建的链接，以及需要设置权限的目录
+#[derive(Default)]
+struct Deferred {
+    /// `(路径, 目标, 是否为硬链接)`，硬链接的目标是相对于解压目录的路径
+    links: Vec<(PathBuf, PathBuf, bool)>,
+    dirs: Vec<(PathBuf, u32)>,
+}
+
+impl Deferred {
+    fn apply(self, dest: &Path) -> Result<(), String> {
+        for (path, target, hard) in self.links {
+            let err = |e: std::io::Error| format!("Failed to extract {}: {}", path.display(), e);
+            // 之前创建的符号链接可能使链接所在的目录指向解压目录以外
+            ensure_inside(dest, &path, false)?;
+            if path
+                .symlink_metadata()
+                .is_ok_and(|m| !m.file_type().is_dir())
+            {
+                std::fs::remove_file(&path).map_err(err)?;
+            }
+            if !hard {
+                std::os::unix::fs::symlink(&target, &path).map_err(err)?;
+                continue;
+            }
+            let source = dest.join(&target);
+            if source.symlink_metadata().is_err() {
+                // 硬链接的目标没有被提取（例如不在指定的子目录中）
+                warn!(
+                    "Skip hard link {} -> {}: target is not extracted",
+                    path.display(),
+                    target.display()
+                );
+                continue;
+            }
+            // 硬链接的目标本身是符号链接时，链接的是符号链接本身
+            ensure_inside(dest, &source, !source.is_symlink()).map_err(|e| {
+                format!(
+                    "hard link {} -> {}: {}",
+                    path.strip_prefix(dest).unwrap_or(&path).display(),
+                    target.display(),
+                    e
+                )
+            })?;
+            std::fs::hard_link(&source, &path).map_err(err)?;
+        }
+        for (dir, mode) in self.dirs.into_iter().rev() {
+            std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(mode))
+                .map_err(|e| format!("Failed to set mode of {}: {}", dir.display(), e))?;
+        }
+        return Ok(());
+    }
+}
+
+/// # 在`jobs`个线程中执行`produce`提交的写入工作
+///
+/// `jobs`为1时在当前线程中直接写入
+fn with_writers(
+    jobs: usize,
+    produce: impl FnOnce(&dyn Fn(FileJob) -> Result<(), String>) -> Result<(), String>,
+) -> Result<(), String> {
+    if jobs <= 1 {
+        return produce(&|job: FileJob| job.write());
+    }
+    let (writers, receivers) = Writers::new(jobs);
+    return std::thread::scope(|s| {
+        let handles: Vec<_> = receivers
+            .into_iter()
+            .map(|r| s.spawn(move || Writers::run(r)))
+            .collect();
+        let produced = produce(&|job| writers.submit(job));
+        drop(writers);
+        let mut written = Ok(());
+        for handle in handles {
+            let r = handle
+                .join()
+                .unwrap_or_else(|_| Err("extraction worker panicked".to_string()));
+            if written.is_ok() {
+                written = r;
+            }
+        }
+        // 写入线程的错误是提交失败的原因，优先返回
+        return written.and(produced);
+    });
+}
+
+/// # 解压tar数据流
+///
+/// ## 参数
+///
+/// * `reader` - 解压缩之后的tar数据
+/// * `dest` - 解压目录
+/// * `jobs` - 写入文件的线程数
+/// * `selects` - 是否提取某个成员（参数是相对于解压目录的路径）
+pub fn extract_tar(
+    reader: impl Read,
+    dest: &Path,
+    jobs: usize,
+    selects: &dyn Fn(&Path) -> bool,
+) -> Result<(), String> {
+    let mut reader = TarReader::new(reader);
+    let mut deferred = Deferred::default();
+    let mut temp_count = 0usize;
+    with_writers(jobs, |submit| {
+        while let Some(entry) = reader.next_entry()? {
+            let relative = member_path(&entry.name)?;
+            if relative.as_os_str().is_empty() || !selects(&relative) {
+                reader.skip_data(entry.size)?;
+                continue;
+            }
+            let path = dest.join(&relative);
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+            }
+            match entry.kind {
+                TarEntryKind::Dir => {
+                    std::fs::create_dir_all(&path).map_err(|e| e.to_string())?;
+                    deferred.dirs.push((path, entry.mode));
+                    reader.skip_data(entry.size)?;
+                }
+                TarEntryKind::Symlink | TarEntryKind::Hardlink => {
+                    let hard = entry.kind == TarEntryKind::Hardlink;
+                    let target = if hard {
+                        member_path(&entry.link_name)?
+                    } else {
+                        PathBuf::from(&entry.link_name)
+                    };
+                    deferred.links.push((path, target, hard));
+                    reader.skip_data(entry.size)?;
+                }
+                TarEntryKind::File => {
+                    let payload = if entry.size > INLINE_LIMIT {
+                        let temp = dest.join(format!(".dadk-extract-{}", temp_count));
+                        temp_count += 1;
+                        let mut file = File::create(&temp).map_err(|e| e.to_string())?;
+                        reader.copy_data(entry.size, &mut file)?;
+                        Payload::Temp(temp)
+                    } else {
+                        let mut data = Vec::with_capacity(entry.size as usize);
+                        reader.copy_data(entry.size, &mut data)?;
+                        Payload::Data(data)
+                    };
+                    submit(FileJob {
+                        path,
+                        payload,
+                        mode: Some(entry.mode),
+                    })?;
+                }
+                TarEntryKind::Other(kind) => {
+                    debug!("Skip tar member {} of type {:?}", entry.name, kind as char);
+                    reader.skip_data(entry.size)?;
+                }
+            }
+        }
+        return reader.finish();
+    })?;
+    return deferred.apply(dest);
+}
+
+/// # 解压zip压缩包
+///
+/// 参数与[`extract_tar`]相同。每个写入线程单独打开压缩包，读取分给它的成员
+pub fn extract_zip(
+    archive_path: &Path,
+    dest: &Path,
+    jobs: usize,
+    selects: &dyn Fn(&Path) -> bool,
+) -> Result<(), String> {
+    let open = || -> Result<ZipArchive<BufReader<File>>, String> {
+        let file = File::open(archive_path).map_err(|e| e.to_string())?;
+        return ZipArchive::new(BufReader::new(file)).map_err(|e| e.to_string());
+    };
+    let mut archive = open()?;
+    let mut deferred = Deferred::default();
+    // 按路径分给写入线程的成员
+    let mut buckets: Vec<Vec<(usize, PathBuf, Option<u32>)>> = vec![Vec::new(); jobs.max(1)];
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i).map_err(|e| e.to_string())?;
+        let relative = member_path(file.name())?;
+        if relative.as_os_str().is_empty() || !selects(&relative) {
+            continue;
+        }
+        let path = dest.join(&relative);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        //设置解压后权限，在Linux中Unzip会丢失权限
+        let mode = file.unix_mode();
+        if file.is_dir() {
+            std::fs::create_dir_all(&path).map_err(|e| e.to_string())?;
+            if let Some(mode) = mode {
+                deferred.dirs.push((path, mode & 0o7777));
+            }
+        } else if mode.is_some_and(|m| m & 0o170000 == 0o120000) {
+            // 符号链接的内容是链接的目标
+            let mut target = String::new();
+            file.read_to_string(&mut target)
+                .map_err(|e| e.to_string())?;
+            deferred.links.push((path, PathBuf::from(target), false));
+        } else {
+            let mut hasher = DefaultHasher::new();
+            path.hash(&mut hasher);
+            let bucket = (hasher.finish() % buckets.len() as u64) as usize;
+            buckets[bucket].push((i, path, mode.map(|m| m & 0o7777)));
+        }
+    }
+
+    let extract_bucket = |archive: &mut ZipArchive<BufReader<File>>,
+                          bucket: &[(usize, PathBuf, Option<u32>)]|
+     -> Result<(), String> {
+        for (i, path, mode) in bucket.iter() {
+            let mut file = archive.by_index(*i).map_err(|e| e.to_string())?;
+            let mut data = Vec::with_capacity(file.size() as usize);
+            file.read_to_end(&mut data).map_err(|e| e.to_string())?;
+            FileJob {
+                path: path.clone(),
+                payload: Payload::Data(data),
+                mode: *mode,
+            }
+            .write()?;
+        }
+        return Ok(());
+    };
+    if buckets.len() == 1 {
+        extract_bucket(&mut archive, &buckets[0])?;
+    } else {
+        std::thread::scope(|s| {
+            let handles: Vec<_> = buckets
+                .iter()
+                .map(|bucket| s.spawn(|| extract_bucket(&mut open()?, bucket)))
+                .collect();
+            let mut result = Ok(());
+            for handle in handles {
+                let r = handle
+                    .join()
+                    .unwrap_or_else(|_| Err("extraction worker panicked".to_string()));
+                if result.is_ok() {
+                    result = r;
+                }
+            }
+            return result;
+        })?;
+    }
+    return deferred.apply(dest);
+}
+
+/// tar成员的类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TarEntryKind {
+    File,
+    Dir,
+    Symlink,
+    Hardlink,
+    /// 其他类型（设备文件、FIFO等），不会被提取
+    Other(u8),
+}
+
+/// tar成员的头部信息
+#[derive(Debug)]
+struct TarEntry {
+    name: String,
+    link_name: String,
+    kind: TarEntryKind,
+    mode: u32,
+    size: u64,
+}
+
+/// # tar格式的读取器
+///
+/// 支持ustar、GNU（长文件名）和pax（`path`、`linkpath`）格式
+struct TarReader<R: Read> {
+    reader: R,
+}
+
+impl<R: Read> TarReader<R> {
+    const BLOCK: u64 = 512;
+
+    fn new(reader: R) -> Self {
+        return Self { reader };
+    }
+
+    /// # 读取结束标记之后的填充数据
+    ///
+    /// tar压缩包的长度按记录（通常为10240字节）对齐，数据来自管道时，
+    /// 不读完会使写入端（例如`xz -dc`）因为管道关闭而失败
+    fn finish(&mut self) -> Result<(), String> {
+        std::io::copy(&mut self.reader, &mut std::io::sink())
+            .map_err(|e| format!("Failed to read tar archive: {}", e))?;
+        return Ok(());
+    }
+
+    /// 读取下一个成员的头部，压缩包结束时返回None
+    fn next_entry(&mut self) -> Result<Option<TarEntry>, String> {
+        let mut long_name = None;
+        let mut long_link = None;
+        loop {
+            let mut header = [0u8; 512];
+            if !self.read_header(&mut header)? {
+                return Ok(None);
+            }
+            let size = Self::parse_number(&header[124..136])?;
+            match header[156] {
+                // GNU长文件名和长链接目标
+                b'L' => long_name = Some(self.read_string(size)?),
+                b'K' => long_link = Some(self.read_string(size)?),
+                // pax扩展头部
+                b'x' => {
+                    let records = self.read_string(size)?;
+                    for (key, value) in Self::parse_pax(&records)? {
+                        match key {
+                            "path" => long_name = Some(value.to_string()),
+                            "linkpath" => long_link = Some(value.to_string()),
+                            _ => {}
+                        }
+                    }
+                }
+                // pax全局头部（例如GitHub生成的压缩包中的提交号）
+                b'g' => self.skip_data(size)?,
+                typeflag => {
+                    let mut name = match long_name.take() {
+                        Some(name) => name,
+                        None => Self::header_name(&header),
+                    };
+                    let link_name = long_link.unwrap_or_else(|| Self::c_string(&header[157..257]));
+                    let mut kind = match typeflag {
+                        b'0' | b'\0' | b'7' => TarEntryKind::File,
+                        b'1' => TarEntryKind::Hardlink,
+                        b'2' => TarEntryKind::Symlink,
+                        b'5' => TarEntryKind::Dir,
+                        b'S' => {
+                            return Err(format!("unsupported sparse tar member {:?}", name));
+                        }
+                        other => TarEntryKind::Other(other),
+                    };
+                    // 旧格式中以`/`结尾的普通文件是目录
+                    if kind == TarEntryKind::File && name.ends_with('/') {
+                        kind = TarEntryKind::Dir;
+                    }
+                    if kind == TarEntryKind::Dir {
+                        name = name.trim_end_matches('/').to_string();
+                    }
+                    return Ok(Some(TarEntry {
+                        name,
+                        link_name,
+                        kind,
+                        mode: Self::parse_number(&header[100..108])? as u32 & 0o7777,
+                        size,
+                    }));
+                }
+            }
+        }
+    }
+
+    /// 读取头部并校验，遇到全为0的结束块或者数据结束时返回false
+    fn read_header(&mut self, header: &mut [u8; 512]) -> Result<bool, String> {
+        let mut read = 0;
+        while read < header.len() {
+            let n = self
+                .reader
+                .read(&mut header[read..])
+                .map_err(|e| format!("Failed to read tar archive: {}", e))?;
+            if n == 0 {
+                if read == 0 {
+                    return Ok(false);
+                }
+                return Err("unexpected end of tar archive".to_string());
+            }
+            read += n;
+        }
+        if header.iter().all(|b| *b == 0) {
+            return Ok(false);
+        }
+        let expected = Self::parse_number(&header[148..156])?;
+        let actual: u64 = header
+            .iter()
+            .enumerate()
+            .map(|(i, b)| if (148..156).contains(&i) { b' ' } else { *b } as u64)
+            .sum();
+        if expected != actual {
+            return Err("invalid tar header checksum, not a tar archive?".to_string());
+        }
+        return Ok(true);
+    }
+
+    /// 把成员的数据写入`writer`，并跳过补齐到块大小的部分
+    fn copy_data(&mut self, size: u64, writer: &mut dyn Write) -> Result<(), String> {
+        let copied = std::io::copy(&mut (&mut self.reader).take(size), writer)
+            .map_err(|e| format!("Failed to read tar archive: {}", e))?;
+        if copied != size {
+            return Err("unexpected end of tar archive".to_string());
+        }
+        return self.skip_padding(size);
+    }
+
+    fn skip_data(&mut self, size: u64) -> Result<(), String> {
+        return self.copy_data(size, &mut std::io::sink());
+    }
+
+    fn skip_padding(&mut self, size: u64) -> Result<(), String> {
+        let padding = (Self::BLOCK - size % Self::BLOCK) % Self::BLOCK;
+        std::io::copy(&mut (&mut self.reader).take(padding), &mut std::io::sink())
+            .map_err(|e| format!("Failed to read tar archive: {}", e))?;
+        return Ok(());
+    }
+
+    fn read_string(&mut self, size: u64) -> Result<String, String> {
+        let mut data = Vec::new();
+        self.copy_data(size, &mut data)?;
+        return Ok(Self::c_string(&data));
+    }
+
+    /// ustar格式的文件名由`prefix`和`name`两部分组成
+    fn header_name(header: &[u8; 512]) -> String {
+        let name = Self::c_string(&header[0..100]);
+        if &header[257..263] != b"ustar\0" {
+            return name;
+        }
+        let prefix = Self::c_string(&header[345..500]);
+        if prefix.is_empty() {
+            return name;
+        }
+        return format!("{}/{}", prefix, name);
+    }
+
+    fn c_string(data: &[u8]) -> String {
+        let end = data.iter().position(|b| *b == 0).unwrap_or(data.len());
+        return String::from_utf8_lossy(&data[..end]).to_string();
+    }
+
+    /// 解析八进制数字，或者GNU的base-256编码（第一个字节的最高位为1）
+    fn parse_number(field: &[u8]) -> Result<u64, String> {
+        if field[0] & 0x80 != 0 {
+            return Ok(field[1..]
+                .iter()
+                .fold((field[0] & 0x7f) as u64, |n, b| (n << 8) | *b as u64));
+        }
+        let s = Self::c_string(field);
+        let s = s.trim_matches(|c: char| c == ' ' || c == '\0');
+        if s.is_empty() {
+            return Ok(0);
+        }
+        return u64::from_str_radix(s, 8).map_err(|_| format!("invalid tar header field {:?}", s));
+    }
+
+    /// 解析pax记录，格式为`<长度> <键>=<值>\n`
+    fn parse_pax(records: &str) -> Result<Vec<(&str, &str)>, String> {
+        let mut result = Vec::new();
+        let mut rest = records;
+        while !rest.is_empty() {
+            let invalid = || "invalid pax extended header".to_string();
+            let (len, _) = rest.split_once(' ').ok_or_else(invalid)?;
+            let len: usize = len.parse().map_err(|_| invalid())?;
+            let record = rest.get(..len).ok_or_else(invalid)?;
+            rest = &rest[len..];
+            let (_, kv) = record.split_once(' ').ok_or_else(invalid)?;
+            let (key, value) = kv
+                .trim_end_matches('\n')
+                .split_once('=')
+                .ok_or_else(invalid)?;
+            result.push((key, value));
+        }
+        return Ok(result);
+    }
+}
@@ -0,0 +1,136 @@
+//! # 限制同时进行的下载
+//!
+//! 获取源文件（克隆仓库、下载压缩包和Release附件、拉取OCI镜像）之前，任务需要先取得一个许可，
+//! 同时进行的获取数不超过`--download-jobs`，以免占满网络带宽或者触发服务器的频率限制。
+//! 这个限制与构建使用的`--jobs`相互独立，默认为CPU核心数，但不超过[`DEFAULT_MAX_DOWNLOAD_JOBS`]。
+//!
+//! 一个任务获取失败时，其他已经开始的下载不会被中断：DADK退出前会等待它们完成（见[`drain`]）。
+
+use std::sync::{Condvar, Mutex};
+
+use crate::parser::task::{CodeSource, DADKTask, PrebuiltSource, TaskType};
+
+/// 没有指定`--download-jobs`时，同时进行的下载数的上限
+pub const DEFAULT_MAX_DOWNLOAD_JOBS: usize = 8;
+
+/// 全局的下载限制
+static LIMITER: FetchLimiter = FetchLimiter::new(0);
+
+/// 设置同时进行的下载数，为0时使用默认值
+pub fn set_download_jobs(jobs: usize) {
+    LIMITER.set_limit(jobs);
+}
+
+/// 默认的同时进行的下载数：CPU核心数，但不超过[`DEFAULT_MAX_DOWNLOAD_JOBS`]
+pub fn default_download_jobs() -> usize {
+    return std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+        .min(DEFAULT_MAX_DOWNLOAD_JOBS);
+}
+
+/// 任务获取源文件时是否需要访问网络（克隆仓库、下载或者拉取镜像）
+pub fn fetches_remote(task: &DADKTask) -> bool {
+    return match &task.task_type {
+        TaskType::BuildFromSource(cs) | TaskType::KernelModule(cs) => {
+            !matches!(cs, CodeSource::Local(_))
+        }
+        TaskType::InstallFromPrebuilt(pb) => !matches!(pb, PrebuiltSource::Local(_)),
+        TaskType::NoSource | TaskType::Meta => false,
+    };
+}
+
+/// 取得一个下载许可，同时进行的下载数达到上限时阻塞
+pub fn acquire() -> FetchPermit<'static> {
+    return LIMITER.acquire();
+}
+
+/// # 等待正在进行的下载完成
+///
+/// 之后不再开始新的下载（[`acquire`]会一直阻塞），用于任务失败、DADK退出之前
+pub fn drain() {
+    LIMITER.drain();
+}
+
+#[derive(Debug)]
+struct LimiterState {
+    /// 同时进行的下载数的上限，为0时使用默认值
+    limit: usize,
+    /// 正在进行的下载数
+    active: usize,
+    /// 是否已经停止开始新的下载
+    draining: bool,
+}
+
+/// # 计数信号量
+///
+/// 限制同时进行的下载数
+#[derive(Debug)]
+pub struct FetchLimiter {
+    state: Mutex<LimiterState>,
+    cond: Condvar,
+}
+
+impl FetchLimiter {
+    /// 创建下载限制，`limit`为0时使用默认值
+    pub const fn new(limit: usize) -> Self {
+        return Self {
+            state: Mutex::new(LimiterState {
+                limit,
+                active: 0,
+                draining: false,
+            }),
+            cond: Condvar::new(),
+        };
+    }
+
+    pub fn set_limit(&self, limit: usize) {
+        self.state.lock().unwrap().limit = limit;
+        self.cond.notify_all();
+    }
+
+    fn effective_limit(state: &LimiterState) -> usize {
+        if state.limit == 0 {
+            return default_download_jobs();
+        }
+        return state.limit;
+    }
+
+    /// 取得一个下载许可，同时进行的下载数达到上限时阻塞
+    pub fn acquire(&self) -> FetchPermit<'_> {
+        let mut state = self
+            .cond
+            .wait_while(self.state.lock().unwrap(), |s| {
+                s.draining || s.active >= Self::effective_limit(s)
+            })
+            .unwrap();
+        state.active += 1;
+        return FetchPermit { limiter: self };
+    }
+
+    /// 停止开始新的下载，并等待正在进行的下载完成
+    pub fn drain(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.draining = true;
+        let _state = self.cond.wait_while(state, |s| s.active > 0).unwrap();
+    }
+
+    fn release(&self) {
+        self.state.lock().unwrap().active -= 1;
+        self.cond.notify_all();
+    }
+}
+
+/// # 下载许可
+///
+/// 被丢弃时归还许可
+#[derive(Debug)]
+pub struct FetchPermit<'a> {
+    limiter: &'a FetchLimiter,
+}
+
+impl Drop for FetchPermit<'_> {
+    fn drop(&mut self) {
+        self.limiter.release();
+    }
+}
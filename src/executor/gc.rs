@@ -0,0 +1,422 @@
+//! # 缓存回收
+//!
+//! 缓存根目录下的以下内容是可以回收的缓存项：
+//!
+//! - `build/<缓存键>`：构建结果
+//! - `source/<name_version>`：下载、解压或者克隆的源码（叠加源的各层在`<name_version>.layers`中）
+//! - `task_data/<缓存键>`：任务的执行记录
+//! - `oci/rootfs/<digest>-<架构>`：OCI镜像展开后的文件系统
+//! - `oci/blobs/<digest>`：下载的OCI镜像层
+//! - `logs/<name_version>[-<时间戳>].log`：任务的输出日志
+//!
+//! 安装清单、缓存锁等其他内容不会被回收。
+//! 构建结果、源码和任务的执行记录正在被其他DADK进程使用（持有对应的缓存锁）时，跳过这些缓存项。
+//!
+//! 执行器使用缓存项时，在`access/<类别>/<缓存项>`中记录最近一次访问的时间，
+//! 没有访问记录的缓存项（例如输出日志）使用它的修改时间。
+//!
+//! `dadk cache prune`按以下顺序选择要删除的缓存项：
+//!
+//! 1. 指定了`--unreferenced`时，删除不属于当前配置文件中任何任务的缓存项
+//!    （无法确定所属任务的OCI镜像层除外）
+//! 2. 属于设置了`build_once`的任务的缓存项不会被删除
+//! 3. 指定了`--max-age`时，删除超过这个时间没有被访问的缓存项
+//! 4. 指定了`--max-size`时，按最近一次访问的时间从早到晚删除缓存项，直到缓存的总大小不超过限制
+
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    parser::{task::DADKTask, task_log::TaskLog},
+    utils::{file::FileUtils, file_lock::FileLock},
+};
+
+use super::cache::{
+    cache_key_belongs_to, cache_lock_path, source_lock_path, TaskDataDir, CACHE_ROOT,
+};
+
+/// 访问记录所在的目录（相对于缓存根目录）
+const ACCESS_DIR: &str = "access";
+
+/// # 缓存项的类别
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CacheCategory {
+    /// 构建结果
+    Build,
+    /// 源码
+    Source,
+    /// 任务的执行记录
+    TaskData,
+    /// OCI镜像展开后的文件系统
+    OciRootfs,
+    /// 下载的OCI镜像层
+    OciBlobs,
+    /// 任务的输出日志
+    Logs,
+}
+
+impl CacheCategory {
+    pub const ALL: [CacheCategory; 6] = [
+        CacheCategory::Build,
+        CacheCategory::Source,
+        CacheCategory::TaskData,
+        CacheCategory::OciRootfs,
+        CacheCategory::OciBlobs,
+        CacheCategory::Logs,
+    ];
+
+    /// 缓存项所在的目录（相对于缓存根目录）
+    pub fn dir(self) -> &'static str {
+        return match self {
+            CacheCategory::Build => "build",
+            CacheCategory::Source => "source",
+            CacheCategory::TaskData => "task_data",
+            CacheCategory::OciRootfs => "oci/rootfs",
+            CacheCategory::OciBlobs => "oci/blobs",
+            CacheCategory::Logs => "logs",
+        };
+    }
+}
+
+impl Display for CacheCategory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return write!(f, "{}", self.dir().replace('/', "_"));
+    }
+}
+
+/// # 缓存项与当前配置文件中的任务的关系
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reference {
+    /// 无法确定所属的任务
+    Unknown,
+    /// 不属于任何任务
+    Unreferenced,
+    /// 属于某个任务
+    Referenced {
+        /// 所属的任务是否设置了`build_once`
+        build_once: bool,
+    },
+}
+
+/// # 缓存项
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheEntry {
+    pub category: CacheCategory,
+    /// 缓存项在类别目录中的名字
+    pub name: String,
+    pub path: PathBuf,
+    /// 占用的空间（字节）
+    pub size: u64,
+    /// 最近一次访问的时间
+    pub last_access: DateTime<Utc>,
+    pub reference: Reference,
+}
+
+impl CacheEntry {
+    /// 属于设置了`build_once`的任务的缓存项，只有不被引用时才会被删除
+    pub fn is_protected(&self) -> bool {
+        return self.reference == Reference::Referenced { build_once: true };
+    }
+}
+
+/// 访问记录
+#[derive(Debug, Serialize, Deserialize)]
+struct AccessRecord {
+    last_access: DateTime<Utc>,
+}
+
+/// 缓存项的访问记录的路径
+fn access_record_path(root: &Path, category: CacheCategory, name: &str) -> PathBuf {
+    return root.join(ACCESS_DIR).join(category.dir()).join(name);
+}
+
+/// # 记录缓存项被访问的时间
+pub fn record_access(
+    root: &Path,
+    category: CacheCategory,
+    name: &str,
+    time: DateTime<Utc>,
+) -> std::io::Result<()> {
+    let path = access_record_path(root, category, name);
+    std::fs::create_dir_all(path.parent().unwrap())?;
+    let content = toml::to_string(&AccessRecord { last_access: time }).unwrap();
+    return std::fs::write(path, content);
+}
+
+/// # 记录缓存根目录中的缓存项现在被访问
+///
+/// 访问记录只用于回收缓存，记录失败时不影响任务的执行
+pub fn touch(category: CacheCategory, name: &str) {
+    if let Err(e) = record_access(CACHE_ROOT.get(), category, name, Utc::now()) {
+        debug!(
+            "Failed to record cache access of {} {}: {}",
+            category, name, e
+        );
+    }
+}
+
+/// 缓存项最近一次被访问的时间，没有访问记录时使用修改时间
+fn last_access(root: &Path, category: CacheCategory, name: &str, path: &Path) -> DateTime<Utc> {
+    let record = std::fs::read_to_string(access_record_path(root, category, name))
+        .ok()
+        .and_then(|content| toml::from_str::<AccessRecord>(&content).ok());
+    if let Some(record) = record {
+        return record.last_access;
+    }
+    return path
+        .symlink_metadata()
+        .and_then(|m| m.modified())
+        .map(DateTime::<Utc>::from)
+        .unwrap_or_default();
+}
+
+/// # 列出缓存根目录中的所有缓存项
+///
+/// 缓存项的引用状态为[`Reference::Unknown`]，需要调用[`resolve_references`]确定
+pub fn scan(root: &Path) -> std::io::Result<Vec<CacheEntry>> {
+    let mut entries = Vec::new();
+    for category in CacheCategory::ALL {
+        let dir = root.join(category.dir());
+        let read_dir = match dir.read_dir() {
+            Ok(read_dir) => read_dir,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        let mut names: Vec<String> = read_dir
+            .map(|e| e.map(|e| e.file_name().to_string_lossy().to_string()))
+            .collect::<std::io::Result<_>>()?;
+        names.sort();
+        for name in names {
+            let path = dir.join(&name);
+            entries.push(CacheEntry {
+                category,
+                size: FileUtils::disk_usage(&path)?,
+                last_access: last_access(root, category, &name, &path),
+                name,
+                path,
+                reference: Reference::Unknown,
+            });
+        }
+    }
+    return Ok(entries);
+}
+
+/// # 按当前配置文件中的任务确定各缓存项的引用状态
+///
+/// OCI镜像展开后的文件系统按任务最近一次拉取的镜像的digest确定，OCI镜像层无法确定所属的任务
+pub fn resolve_references(entries: &mut [CacheEntry], tasks: &[DADKTask]) {
+    // 被引用的镜像digest -> 任务是否设置了build_once
+    let mut digests: BTreeMap<String, bool> = BTreeMap::new();
+    for entry in entries.iter() {
+        if entry.category != CacheCategory::TaskData {
+            continue;
+        }
+        let task = match tasks.iter().find(|t| owns(entry, t)) {
+            Some(task) => task,
+            None => continue,
+        };
+        let task_log = std::fs::read_to_string(entry.path.join(TaskDataDir::TASK_LOG_FILE_NAME))
+            .ok()
+            .and_then(|content| toml::from_str::<TaskLog>(&content).ok());
+        if let Some(digest) = task_log.as_ref().and_then(|l| l.oci_digest()) {
+            let build_once = digests.entry(digest.replace(':', "-")).or_default();
+            *build_once |= task.build_once;
+        }
+    }
+
+    for entry in entries.iter_mut() {
+        entry.reference = match entry.category {
+            CacheCategory::OciBlobs => Reference::Unknown,
+            CacheCategory::OciRootfs => digests
+                .iter()
+                .find(|(digest, _)| entry.name.starts_with(&format!("{}-", digest)))
+                .map_or(Reference::Unreferenced, |(_, build_once)| {
+                    Reference::Referenced {
+                        build_once: *build_once,
+                    }
+                }),
+            _ => {
+                let owners: Vec<&DADKTask> = tasks.iter().filter(|t| owns(entry, t)).collect();
+                if owners.is_empty() {
+                    Reference::Unreferenced
+                } else {
+                    Reference::Referenced {
+                        build_once: owners.iter().any(|t| t.build_once),
+                    }
+                }
+            }
+        };
+    }
+}
+
+/// 缓存项是否属于任务（不包括OCI镜像）
+fn owns(entry: &CacheEntry, task: &DADKTask) -> bool {
+    let name_version = task.name_version();
+    return match entry.category {
+        CacheCategory::Build | CacheCategory::TaskData => {
+            cache_key_belongs_to(&entry.name, &name_version)
+        }
+        CacheCategory::Source => {
+            entry.name.strip_suffix(".layers").unwrap_or(&entry.name) == name_version
+        }
+        CacheCategory::Logs => entry
+            .name
+            .strip_prefix(name_version.as_str())
+            .and_then(|rest| rest.strip_suffix(".log"))
+            .is_some_and(|rest| {
+                rest.is_empty()
+                    || rest
+                        .strip_prefix('-')
+                        .is_some_and(|t| !t.is_empty() && t.chars().all(|c| c.is_ascii_digit()))
+            }),
+        CacheCategory::OciRootfs | CacheCategory::OciBlobs => false,
+    };
+}
+
+/// # 回收缓存的条件
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PruneOptions {
+    /// 删除超过这个时间没有被访问的缓存项
+    pub max_age: Option<Duration>,
+    /// 缓存的总大小的上限（字节）
+    pub max_size: Option<u64>,
+    /// 删除不属于任何任务的缓存项
+    pub unreferenced: bool,
+}
+
+/// # 缓存项被删除的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PruneReason {
+    Unreferenced,
+    Expired,
+    OverBudget,
+}
+
+impl Display for PruneReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PruneReason::Unreferenced => write!(f, "unreferenced"),
+            PruneReason::Expired => write!(f, "expired"),
+            PruneReason::OverBudget => write!(f, "over size budget"),
+        }
+    }
+}
+
+/// # 计算要删除的缓存项
+///
+/// 规则见模块文档。返回要删除的缓存项在`entries`中的序号和原因，按序号排列
+pub fn plan_prune(
+    entries: &[CacheEntry],
+    options: &PruneOptions,
+    now: DateTime<Utc>,
+) -> Vec<(usize, PruneReason)> {
+    let mut removed: BTreeMap<usize, PruneReason> = BTreeMap::new();
+    for (i, entry) in entries.iter().enumerate() {
+        if options.unreferenced && entry.reference == Reference::Unreferenced {
+            removed.insert(i, PruneReason::Unreferenced);
+            continue;
+        }
+        if entry.is_protected() {
+            continue;
+        }
+        let expired = options.max_age.is_some_and(|max_age| {
+            now.signed_duration_since(entry.last_access)
+                .to_std()
+                .is_ok_and(|age| age > max_age)
+        });
+        if expired {
+            removed.insert(i, PruneReason::Expired);
+        }
+    }
+
+    if let Some(max_size) = options.max_size {
+        let mut total: u64 = entries
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !removed.contains_key(i))
+            .map(|(_, e)| e.size)
+            .sum();
+        // 最近最少使用的缓存项先被删除
+        let mut candidates: Vec<usize> = (0..entries.len())
+            .filter(|i| !removed.contains_key(i) && !entries[*i].is_protected())
+            .collect();
+        candidates.sort_by_key(|i| (entries[*i].last_access, *i));
+        for i in candidates {
+            if total <= max_size {
+                break;
+            }
+            total -= entries[i].size;
+            removed.insert(i, PruneReason::OverBudget);
+        }
+    }
+    return removed.into_iter().collect();
+}
+
+/// # 缓存项的锁
+#[derive(Debug)]
+pub enum EntryLock {
+    /// 缓存项没有对应的缓存锁（例如输出日志、OCI镜像）
+    None,
+    /// 已经获取了缓存项的锁，drop时释放
+    Held(FileLock),
+    /// 缓存项正在被其他DADK进程使用
+    Busy,
+}
+
+/// # 尝试获取缓存项的锁，不会阻塞
+///
+/// 构建结果和任务的执行记录使用按缓存键区分的锁，源码（包括叠加源的各层）使用按`name_version`区分的锁，
+/// 与执行任务时获取的锁相同
+pub fn try_lock_entry(root: &Path, entry: &CacheEntry) -> std::io::Result<EntryLock> {
+    let path = match entry.category {
+        CacheCategory::Build | CacheCategory::TaskData => cache_lock_path(root, &entry.name),
+        CacheCategory::Source => source_lock_path(
+            root,
+            entry.name.strip_suffix(".layers").unwrap_or(&entry.name),
+        ),
+        _ => return Ok(EntryLock::None),
+    };
+    return Ok(match FileLock::try_acquire(&path)? {
+        Some(lock) => EntryLock::Held(lock),
+        None => EntryLock::Busy,
+    });
+}
+
+/// # 删除缓存项及其访问记录
+pub fn remove_entry(root: &Path, entry: &CacheEntry) -> std::io::Result<()> {
+    let meta = match entry.path.symlink_metadata() {
+        Ok(meta) => Some(meta),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e),
+    };
+    match meta {
+        Some(meta) if meta.is_dir() => std::fs::remove_dir_all(&entry.path)?,
+        Some(_) => std::fs::remove_file(&entry.path)?,
+        None => {}
+    }
+    match std::fs::remove_file(access_record_path(root, entry.category, &entry.name)) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    return Ok(());
+}
+
+/// # 各类别的缓存项数和占用的空间
+pub fn stats(entries: &[CacheEntry]) -> BTreeMap<CacheCategory, (usize, u64)> {
+    let mut stats: BTreeMap<CacheCategory, (usize, u64)> =
+        CacheCategory::ALL.iter().map(|c| (*c, (0, 0))).collect();
+    for entry in entries {
+        let stat = stats.get_mut(&entry.category).unwrap();
+        stat.0 += 1;
+        stat.1 += entry.size;
+    }
+    return stats;
+}
@@ -0,0 +1,194 @@
+//! # 构建进程的资源限制
+//!
+//! 任务可以在`build`中设置`max_memory`、`cpu_time_limit`和`nice`，
+//! 执行器在构建命令和构建钩子执行前（`pre_exec`）通过`setrlimit`和`setpriority`设置限制，
+//! 构建命令派生的子进程会继承这些限制。
+//!
+//! 注意：`max_memory`限制的是每个进程的虚拟地址空间（`RLIMIT_AS`），
+//! `cpu_time_limit`限制的是每个进程的CPU时间（`RLIMIT_CPU`），而不是整个进程树的总量。
+//! 不支持的平台上输出警告，并在不限制资源的情况下构建。
+
+use std::{fmt::Display, process::Command};
+
+/// 进程被内存限制拒绝分配内存时，常见的输出
+const OOM_MESSAGES: [&str; 6] = [
+    "cannot allocate memory",
+    "out of memory",
+    "memory exhausted",
+    "memory allocation of",
+    "bad_alloc",
+    "memoryerror",
+];
+
+/// # 构建进程的资源限制
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResourceLimits {
+    /// 每个进程的最大虚拟内存（字节）
+    pub max_memory: Option<u64>,
+    /// 每个进程的最大CPU时间（秒）
+    pub cpu_time_limit: Option<u64>,
+    /// 构建进程的nice值
+    pub nice: Option<i32>,
+}
+
+impl ResourceLimits {
+    pub fn is_empty(&self) -> bool {
+        return self.max_memory.is_none() && self.cpu_time_limit.is_none() && self.nice.is_none();
+    }
+
+    /// 当前平台是否支持资源限制
+    pub fn supported() -> bool {
+        return cfg!(unix);
+    }
+
+    /// # 在命令执行前设置资源限制
+    ///
+    /// 设置失败时（例如非root用户设置负的nice值），命令无法启动
+    #[cfg(unix)]
+    pub fn apply(&self, command: &mut Command) {
+        use std::os::unix::process::CommandExt;
+
+        if self.is_empty() {
+            return;
+        }
+        let limits = self.clone();
+        unsafe {
+            command.pre_exec(move || {
+                if let Some(bytes) = limits.max_memory {
+                    set_rlimit(libc::RLIMIT_AS, bytes, bytes)?;
+                }
+                if let Some(secs) = limits.cpu_time_limit {
+                    // 软限制到达时进程收到SIGXCPU，留出1秒再由硬限制强制结束
+                    set_rlimit(libc::RLIMIT_CPU, secs, secs.saturating_add(1))?;
+                }
+                if let Some(nice) = limits.nice {
+                    if libc::setpriority(libc::PRIO_PROCESS, 0, nice) != 0 {
+                        return Err(std::io::Error::last_os_error());
+                    }
+                }
+                return Ok(());
+            });
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub fn apply(&self, _command: &mut Command) {}
+
+    /// # 判断构建命令的失败是否由资源限制导致
+    ///
+    /// ## 参数
+    ///
+    /// * `signal` - 结束进程的信号。shell中的子进程被信号结束时，shell的退出码为`128+信号`，也视为该信号
+    /// * `oom_output` - 命令的输出中是否出现了内存分配失败的信息
+    ///
+    /// ## 返回值
+    ///
+    /// 超出的限制的说明，不是资源限制导致的失败时返回None
+    pub fn exceeded(&self, signal: Option<i32>, oom_output: bool) -> Option<String> {
+        #[cfg(unix)]
+        {
+            if let Some(secs) = self.cpu_time_limit {
+                if signal == Some(libc::SIGXCPU)
+                    || (signal == Some(libc::SIGKILL) && self.max_memory.is_none())
+                {
+                    return Some(format!("exceeded cpu_time_limit ({}s)", secs));
+                }
+            }
+            if let Some(bytes) = self.max_memory {
+                let killed = matches!(
+                    signal,
+                    Some(libc::SIGKILL) | Some(libc::SIGSEGV) | Some(libc::SIGABRT)
+                );
+                if oom_output || killed {
+                    return Some(format!(
+                        "possibly exceeded max_memory ({})",
+                        format_size(bytes)
+                    ));
+                }
+            }
+        }
+        #[cfg(not(unix))]
+        let _ = (signal, oom_output);
+        return None;
+    }
+}
+
+impl Display for ResourceLimits {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(bytes) = self.max_memory {
+            parts.push(format!("max_memory={}", format_size(bytes)));
+        }
+        if let Some(secs) = self.cpu_time_limit {
+            parts.push(format!("cpu_time_limit={}s", secs));
+        }
+        if let Some(nice) = self.nice {
+            parts.push(format!("nice={}", nice));
+        }
+        if parts.is_empty() {
+            return write!(f, "none");
+        }
+        return write!(f, "{}", parts.join(", "));
+    }
+}
+
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+type RlimitResource = libc::__rlimit_resource_t;
+#[cfg(all(unix, not(all(target_os = "linux", target_env = "gnu"))))]
+type RlimitResource = libc::c_int;
+
+#[cfg(unix)]
+fn set_rlimit(resource: RlimitResource, soft: u64, hard: u64) -> std::io::Result<()> {
+    let limit = libc::rlimit {
+        rlim_cur: soft as libc::rlim_t,
+        rlim_max: hard as libc::rlim_t,
+    };
+    if unsafe { libc::setrlimit(resource, &limit) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    return Ok(());
+}
+
+/// 一行输出是否为内存分配失败的信息
+pub fn is_oom_message(line: &str) -> bool {
+    let line = line.to_ascii_lowercase();
+    return OOM_MESSAGES.iter().any(|m| line.contains(m));
+}
+
+/// # 解析内存大小
+///
+/// 支持字节数，以及`K`、`M`、`G`、`T`后缀（以1024为基数，可以带`B`或`iB`），例如`512M`、`2GiB`
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let digits = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, unit) = s.split_at(digits);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("invalid size {:?}, expected e.g. 512M or 2G", s))?;
+    let shift = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 0,
+        "K" | "KB" | "KIB" => 10,
+        "M" | "MB" | "MIB" => 20,
+        "G" | "GB" | "GIB" => 30,
+        "T" | "TB" | "TIB" => 40,
+        _ => {
+            return Err(format!(
+                "invalid size unit in {:?}, expected one of K, M, G, T",
+                s
+            ))
+        }
+    };
+    return number
+        .checked_mul(1u64 << shift)
+        .ok_or_else(|| format!("size {:?} is too large", s));
+}
+
+/// 以最大的整除单位显示内存大小
+fn format_size(bytes: u64) -> String {
+    for (shift, unit) in [(40, "T"), (30, "G"), (20, "M"), (10, "K")] {
+        if bytes != 0 && bytes % (1u64 << shift) == 0 {
+            return format!("{}{}", bytes >> shift, unit);
+        }
+    }
+    return format!("{}B", bytes);
+}
@@ -0,0 +1,199 @@
+//! # 锁文件
+//!
+//! 为了实现可复现的构建，DADK会在构建时把每个任务实际获取到的源码身份记录到锁文件中：
+//!
+//! - Git源：解析后的commit（即使配置文件中指定的是分支）
+//! - Mercurial源：解析后的changeset id
+//! - Subversion源：检出的revision号
+//! - 在线压缩包：下载的压缩包的sha256值
+//! - 本地源：本地目录/文件的路径，以及它的哈希值（不包括版本控制目录和构建目录）
+//! - OCI镜像：镜像的digest
+//! - Release附件：附件所属的tag（即使配置文件中指定的是`latest`）以及附件的sha256值
+//! - 叠加源：按顺序记录每一层的源码身份，任何一层发生变化都会被检测到
+//!
+//! 锁文件只在指定了`--lock`（或者`--update-lock`）时写入。
+//! 当锁文件存在时，如果解析出的源码身份与锁文件中记录的不一致（比如分支被更新了），
+//! 则构建会失败，除非指定了`--update-lock`参数。
+//!
+//! 本地源是工作区的一部分，每次构建都可能被修改，因此只锁定它的路径：
+//! 哈希值改变时只更新记录（例如用于生成SBOM），不会使构建失败。
+
+use std::{collections::BTreeMap, path::PathBuf, sync::Mutex};
+
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use super::ExecError;
+
+lazy_static! {
+    // 全局锁文件，只在构建时被初始化
+    pub static ref LOCK_FILE: Mutex<Option<LockFile>> = Mutex::new(None);
+}
+
+/// 锁文件的文件名（位于DADK任务配置文件所在目录下）
+pub const LOCK_FILE_NAME: &str = "dadk.lock";
+
+/// # 锁文件
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LockFile {
+    /// 锁文件的路径
+    #[serde(skip)]
+    path: PathBuf,
+    /// 是否允许用解析出的源码身份更新锁文件
+    #[serde(skip)]
+    update: bool,
+    /// 每个任务的源码身份，key为任务的`name_version`
+    #[serde(default)]
+    tasks: BTreeMap<String, LockedSource>,
+}
+
+/// # 锁文件中记录的源码身份
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum LockedSource {
+    /// Git仓库的commit
+    Git { url: String, commit: String },
+    /// Mercurial仓库的changeset
+    Hg { url: String, changeset: String },
+    /// Subversion仓库的revision号
+    Svn { url: String, revision: String },
+    /// 在线压缩包的sha256值
+    Archive { url: String, sha256: String },
+    /// 本地目录/文件的哈希值
+    Local { path: PathBuf, hash: String },
+    /// OCI镜像的digest
+    Oci { image: String, digest: String },
+    /// Release附件所属的tag（指定为`latest`时为解析后的tag）以及附件的sha256值
+    #[serde(rename = "release_asset")]
+    ReleaseAsset {
+        repo: String,
+        tag: String,
+        sha256: String,
+    },
+    /// 叠加源中每一层的源码身份
+    Overlay { layers: Vec<LockedSource> },
+}
+
+impl LockedSource {
+    /// # 判断解析出的源码身份是否与锁定的一致
+    ///
+    /// 本地源只比较路径，不比较哈希值
+    pub fn matches(&self, resolved: &LockedSource) -> bool {
+        return match (self, resolved) {
+            (LockedSource::Local { path: a, .. }, LockedSource::Local { path: b, .. }) => a == b,
+            (LockedSource::Overlay { layers: a }, LockedSource::Overlay { layers: b }) => {
+                a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| a.matches(b))
+            }
+            _ => self == resolved,
+        };
+    }
+}
+
+impl LockFile {
+    /// # 加载锁文件
+    ///
+    /// 如果锁文件不存在，则返回一个空的锁文件
+    ///
+    /// ## 参数
+    ///
+    /// * `path` - 锁文件的路径
+    /// * `update` - 是否允许更新锁文件中已有的记录
+    pub fn load(path: PathBuf, update: bool) -> Result<Self, ExecError> {
+        let mut lock = if path.exists() {
+            let content = std::fs::read_to_string(&path).map_err(|e| {
+                ExecError::Validation(format!(
+                    "Failed to read lock file {}: {}",
+                    path.display(),
+                    e
+                ))
+            })?;
+            toml::from_str::<LockFile>(&content).map_err(|e| {
+                ExecError::Validation(format!(
+                    "Failed to parse lock file {}: {}",
+                    path.display(),
+                    e
+                ))
+            })?
+        } else {
+            LockFile::default()
+        };
+        lock.path = path;
+        lock.update = update;
+        return Ok(lock);
+    }
+
+    /// # 保存锁文件
+    pub fn save(&self) -> Result<(), ExecError> {
+        let content = toml::to_string(self)
+            .map_err(|e| ExecError::Validation(format!("Failed to serialize lock file: {}", e)))?;
+        std::fs::write(&self.path, content).map_err(|e| {
+            ExecError::Validation(format!(
+                "Failed to write lock file {}: {}",
+                self.path.display(),
+                e
+            ))
+        })?;
+        return Ok(());
+    }
+
+    /// # 检查任务解析出的源码身份
+    ///
+    /// - 如果锁文件中没有该任务的记录，则记录下来
+    /// - 如果与锁文件中的记录一致，则直接返回（本地源的哈希值改变时更新记录）
+    /// - 如果不一致，且允许更新锁文件，则更新记录，否则返回`ExecError::Validation`
+    pub fn check(&mut self, task: &str, resolved: LockedSource) -> Result<(), ExecError> {
+        match self.tasks.get(task) {
+            Some(locked) if *locked == resolved => {
+                return Ok(());
+            }
+            Some(locked) if locked.matches(&resolved) => {}
+            Some(locked) => {
+                if !self.update {
+                    return Err(ExecError::Validation(format!(
+                        "Task {}: resolved source {:?} differs from the locked one {:?} in {}, run with `--update-lock` to accept it",
+                        task,
+                        resolved,
+                        locked,
+                        self.path.display()
+                    )));
+                }
+                info!("Task {}: updating locked source to {:?}", task, resolved);
+            }
+            None => {}
+        }
+
+        self.tasks.insert(task.to_string(), resolved);
+        return Ok(());
+    }
+
+    pub fn get(&self, task: &str) -> Option<&LockedSource> {
+        self.tasks.get(task)
+    }
+}
+
+/// # 初始化全局锁文件
+pub fn lock_file_init(path: PathBuf, update: bool) -> Result<(), ExecError> {
+    let lock = LockFile::load(path, update)?;
+    *LOCK_FILE.lock().unwrap() = Some(lock);
+    return Ok(());
+}
+
+/// # 保存全局锁文件
+///
+/// 如果全局锁文件没有被初始化，则什么也不做
+pub fn lock_file_save() -> Result<(), ExecError> {
+    if let Some(lock) = LOCK_FILE.lock().unwrap().as_ref() {
+        lock.save()?;
+    }
+    return Ok(());
+}
+
+/// # 使用全局锁文件检查任务解析出的源码身份
+///
+/// 如果全局锁文件没有被初始化，则不进行检查
+pub fn check_locked_source(task: &str, resolved: LockedSource) -> Result<(), ExecError> {
+    if let Some(lock) = LOCK_FILE.lock().unwrap().as_mut() {
+        lock.check(task, resolved)?;
+    }
+    return Ok(());
+}
@@ -0,0 +1,357 @@
+//! # 安装清单
+//!
+//! 每个任务安装成功后，把这次安装创建的文件、目录和符号链接记录到
+//! `<缓存根目录>/manifests/<name_version>.json`中，便于查询某个路径是由哪个任务安装的：
+//!
+//! ```bash
+//! dadk --config-dir <配置文件目录> info <任务名>   # 输出任务的配置和安装清单
+//! dadk owns /usr/bin/app                         # 查询安装了该路径的任务
+//! ```
+//!
+//! 清单中的路径都是DragonOS内的绝对路径。目录记录的是安装的文件所在的各级目录，
+//! 可能同时出现在多个任务的清单中。`install.post_install`钩子创建的文件不会被记录。
+//!
+//! 清单先写入临时文件再重命名，安装失败时保留上一次成功安装的清单。
+//!
+//! 卸载任务时只删除清单中记录的文件和符号链接，以及变为空的、没有被其他任务的清单记录的目录。
+//!
+//! 同一次执行中，两个任务向同一个sysroot安装了同一路径但内容不同的文件（或者指向不同的符号链接）时，
+//! 后安装的任务在拷贝之前失败，除非该路径匹配它的`install.allow_overwrite`并且它依赖先安装的任务。
+//! 内容相同时允许。规则见[`super::plan::PlannedPaths`]。
+
+use std::{
+    collections::BTreeMap,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::utils::hash::HashUtils;
+
+use super::{cache::CACHE_ROOT, ownership::Ownership};
+
+/// # 清单中的一项的类型
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum ManifestEntryKind {
+    Dir,
+    File,
+    Symlink,
+}
+
+/// # 清单中的一项
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ManifestEntry {
+    /// DragonOS内的绝对路径
+    pub path: PathBuf,
+    #[serde(rename = "type")]
+    pub kind: ManifestEntryKind,
+    /// 八进制的权限，例如`"0755"`
+    pub mode: String,
+    /// 文件的大小（字节）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    /// 文件内容的sha256值
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// 符号链接指向的路径
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<PathBuf>,
+    /// `install.files`中设置的属主uid
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uid: Option<u32>,
+    /// `install.files`中设置的属组gid
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gid: Option<u32>,
+}
+
+/// # 任务的安装清单
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct InstallManifest {
+    /// 安装了这些文件的任务（`name_version`）
+    pub task: String,
+    /// 安装时使用的DragonOS sysroot在主机上的路径
+    pub sysroot: PathBuf,
+    /// 按路径排序
+    pub entries: Vec<ManifestEntry>,
+}
+
+impl InstallManifest {
+    /// 清单目录
+    pub fn manifests_dir() -> PathBuf {
+        return CACHE_ROOT.get().join("manifests");
+    }
+
+    /// 任务的清单文件路径
+    pub fn path_for(name_version: &str) -> PathBuf {
+        return Self::manifests_dir().join(format!("{}.json", name_version));
+    }
+
+    /// # 根据安装的路径生成清单
+    ///
+    /// ## 参数
+    ///
+    /// - `installed` - 安装的文件和符号链接在主机上的路径（位于`sysroot`中）
+    /// - `owners` - `install.files`中设置了属主的文件
+    pub fn collect(
+        task: String,
+        sysroot: &Path,
+        installed: &[PathBuf],
+        owners: &BTreeMap<PathBuf, Ownership>,
+    ) -> Result<Self, String> {
+        let mut entries: BTreeMap<PathBuf, ManifestEntry> = BTreeMap::new();
+        for host_path in installed {
+            let relative = host_path.strip_prefix(sysroot).map_err(|_| {
+                format!(
+                    "installed path {} is outside the sysroot",
+                    host_path.display()
+                )
+            })?;
+            // 安装的文件所在的各级目录
+            for dir in relative.ancestors().skip(1) {
+                if dir.as_os_str().is_empty() {
+                    break;
+                }
+                let path = Path::new("/").join(dir);
+                if !entries.contains_key(&path) {
+                    entries.insert(path.clone(), Self::entry(path, &sysroot.join(dir))?);
+                }
+            }
+            let path = Path::new("/").join(relative);
+            let mut entry = Self::entry(path.clone(), host_path)?;
+            if let Some(owner) = owners.get(&path) {
+                entry.uid = owner.uid;
+                entry.gid = owner.gid;
+            }
+            entries.insert(path, entry);
+        }
+        return Ok(Self {
+            task,
+            sysroot: sysroot.to_path_buf(),
+            entries: entries.into_values().collect(),
+        });
+    }
+
+    fn entry(path: PathBuf, host_path: &Path) -> Result<ManifestEntry, String> {
+        let metadata = host_path
+            .symlink_metadata()
+            .map_err(|e| format!("Failed to stat {}: {}", host_path.display(), e))?;
+        let mut entry = ManifestEntry {
+            path,
+            kind: ManifestEntryKind::File,
+            mode: format!("{:04o}", metadata.permissions().mode() & 0o7777),
+            size: None,
+            sha256: None,
+            target: None,
+            uid: None,
+            gid: None,
+        };
+        if metadata.file_type().is_symlink() {
+            entry.kind = ManifestEntryKind::Symlink;
+            entry.target = Some(
+                std::fs::read_link(host_path)
+                    .map_err(|e| format!("Failed to read {}: {}", host_path.display(), e))?,
+            );
+        } else if metadata.is_dir() {
+            entry.kind = ManifestEntryKind::Dir;
+        } else {
+            entry.size = Some(metadata.len());
+            entry.sha256 = Some(
+                HashUtils::sha256_file(host_path)
+                    .map_err(|e| format!("Failed to hash {}: {}", host_path.display(), e))?,
+            );
+        }
+        return Ok(entry);
+    }
+
+    /// # 保存清单
+    ///
+    /// 先写入临时文件再重命名，不会留下不完整的清单
+    pub fn save(&self) -> Result<PathBuf, String> {
+        let path = Self::path_for(&self.task);
+        std::fs::create_dir_all(Self::manifests_dir()).map_err(|e| e.to_string())?;
+        let tmp = path.with_extension("json.tmp");
+        let content = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        std::fs::write(&tmp, content)
+            .map_err(|e| format!("Failed to write {}: {}", tmp.display(), e))?;
+        std::fs::rename(&tmp, &path)
+            .map_err(|e| format!("Failed to rename {}: {}", tmp.display(), e))?;
+        return Ok(path);
+    }
+
+    /// # 读取任务的清单，任务还没有安装过时返回`None`
+    pub fn load(name_version: &str) -> Result<Option<Self>, String> {
+        return Self::load_file(&Self::path_for(name_version));
+    }
+
+    fn load_file(path: &Path) -> Result<Option<Self>, String> {
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+        };
+        return serde_json::from_str(&content)
+            .map(Some)
+            .map_err(|e| format!("Failed to parse {}: {}", path.display(), e));
+    }
+
+    /// # 读取所有任务的清单，按任务名排序
+    pub fn load_all() -> Result<Vec<Self>, String> {
+        let dir = Self::manifests_dir();
+        let read_dir = match std::fs::read_dir(&dir) {
+            Ok(read_dir) => read_dir,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(format!("Failed to read {}: {}", dir.display(), e)),
+        };
+        let mut manifests = Vec::new();
+        for entry in read_dir {
+            let path = entry.map_err(|e| e.to_string())?.path();
+            if path.extension().map_or(false, |ext| ext == "json") {
+                if let Some(manifest) = Self::load_file(&path)? {
+                    manifests.push(manifest);
+                }
+            }
+        }
+        manifests.sort_by(|a, b| a.task.cmp(&b.task));
+        return Ok(manifests);
+    }
+
+    /// # 删除清单文件
+    pub fn remove(&self) -> Result<(), String> {
+        let path = Self::path_for(&self.task);
+        return std::fs::remove_file(&path)
+            .map_err(|e| format!("Failed to remove {}: {}", path.display(), e));
+    }
+
+    /// 清单中的路径在主机上的路径
+    fn host_path(&self, path: &Path) -> PathBuf {
+        return self.sysroot.join(path.strip_prefix("/").unwrap_or(path));
+    }
+
+    /// # 卸载清单中记录的文件
+    ///
+    /// 先检查所有文件：安装后被修改过（内容或者符号链接的目标与清单不一致）的文件，
+    /// 除非指定了`force`，否则报错并且不删除任何文件。已经不存在的文件会被跳过。
+    ///
+    /// 同一sysroot中其他任务的清单也记录了的路径会被保留。目录按从深到浅的顺序，只删除空目录。
+    /// 不会删除清单文件，也不会修改任务的安装状态。
+    pub fn uninstall(&self, force: bool) -> Result<UninstallSummary, String> {
+        let others: Vec<Self> = Self::load_all()?
+            .into_iter()
+            .filter(|m| m.task != self.task && m.sysroot == self.sysroot)
+            .collect();
+        let shared = |path: &Path| others.iter().any(|m| m.find(path).is_some());
+
+        let mut modified = Vec::new();
+        for entry in self.entries.iter() {
+            if entry.kind == ManifestEntryKind::Dir {
+                continue;
+            }
+            let host_path = self.host_path(&entry.path);
+            if host_path.symlink_metadata().is_err() {
+                continue;
+            }
+            if !Self::matches(entry, &host_path)? {
+                modified.push(entry.path.display().to_string());
+            }
+        }
+        if !modified.is_empty() && !force {
+            return Err(format!(
+                "Task {}: files modified since installed: [{}], use --force to remove them anyway",
+                self.task,
+                modified.join(", ")
+            ));
+        }
+
+        let mut summary = UninstallSummary::default();
+        for entry in self.entries.iter().rev() {
+            let host_path = self.host_path(&entry.path);
+            if host_path.symlink_metadata().is_err() {
+                continue;
+            }
+            if shared(&entry.path) {
+                summary.kept.push(entry.path.clone());
+                continue;
+            }
+            let r = if entry.kind == ManifestEntryKind::Dir {
+                let is_empty = std::fs::read_dir(&host_path)
+                    .map_err(|e| format!("Failed to read {}: {}", host_path.display(), e))?
+                    .next()
+                    .is_none();
+                if !is_empty {
+                    continue;
+                }
+                std::fs::remove_dir(&host_path)
+            } else {
+                std::fs::remove_file(&host_path)
+            };
+            r.map_err(|e| format!("Failed to remove {}: {}", host_path.display(), e))?;
+            summary.removed.push(entry.path.clone());
+        }
+        return Ok(summary);
+    }
+
+    /// 路径当前的状态是否与清单中记录的一致
+    fn matches(entry: &ManifestEntry, host_path: &Path) -> Result<bool, String> {
+        let current = Self::entry(entry.path.clone(), host_path)?;
+        if current.kind != entry.kind {
+            return Ok(false);
+        }
+        return Ok(match entry.kind {
+            ManifestEntryKind::File => current.sha256 == entry.sha256,
+            ManifestEntryKind::Symlink => current.target == entry.target,
+            ManifestEntryKind::Dir => true,
+        });
+    }
+
+    /// # 查找清单中的路径
+    ///
+    /// `path`可以是DragonOS内的绝对路径，也可以是清单的sysroot中的主机路径
+    pub fn find(&self, path: &Path) -> Option<&ManifestEntry> {
+        let path = match path.strip_prefix(&self.sysroot) {
+            Ok(relative) => Path::new("/").join(relative),
+            Err(_) => path.to_path_buf(),
+        };
+        let path: PathBuf = path.components().collect();
+        return self.entries.iter().find(|e| e.path == path);
+    }
+}
+
+impl ManifestEntry {
+    /// 用于比较和输出的内容描述：文件的sha256值，或者符号链接的目标
+    pub(crate) fn content(&self) -> String {
+        return match self.kind {
+            ManifestEntryKind::File => {
+                format!("sha256 {}", self.sha256.as_deref().unwrap_or("-"))
+            }
+            ManifestEntryKind::Symlink => format!(
+                "symlink -> {}",
+                self.target.as_deref().unwrap_or(Path::new("")).display()
+            ),
+            ManifestEntryKind::Dir => "directory".to_string(),
+        };
+    }
+}
+
+/// # 卸载的结果
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct UninstallSummary {
+    /// 删除的路径（DragonOS内的绝对路径）
+    pub removed: Vec<PathBuf>,
+    /// 其他任务也安装了，因此保留的路径
+    pub kept: Vec<PathBuf>,
+}
+
+/// # 查询安装了某个路径的任务
+///
+/// 目录可能被多个任务安装，返回所有包含该路径的清单中的项
+pub fn find_owners(path: &Path) -> Result<Vec<(String, ManifestEntry)>, String> {
+    let mut owners = Vec::new();
+    for manifest in InstallManifest::load_all()? {
+        if let Some(entry) = manifest.find(path) {
+            owners.push((manifest.task.clone(), entry.clone()));
+        }
+    }
+    return Ok(owners);
+}
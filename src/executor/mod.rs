@@ -1,9 +1,17 @@
 use std::{
-    collections::BTreeMap,
+    cell::Cell,
+    collections::{BTreeMap, BTreeSet},
     env::Vars,
-    path::PathBuf,
-    process::{Command, Stdio},
-    sync::{Arc, RwLock},
+    fmt::Display,
+    io::Write,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    process::{Child, Command, ExitStatus, Stdio},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLock,
+    },
+    time::{Duration, Instant},
 };
 
 use log::{debug, error, info, warn};
@@ -11,26 +19,126 @@ use log::{debug, error, info, warn};
 use crate::{
     console::{clean::CleanLevel, Action},
     context::DadkExecuteContext,
-    executor::cache::CacheDir,
+    executor::{
+        cache::CacheDir,
+        lock::{check_locked_source, LockedSource},
+    },
     parser::{
-        task::{CodeSource, PrebuiltSource, TaskEnv, TaskType},
-        task_log::{BuildStatus, InstallStatus, TaskLog},
+        dotenv, interpolate,
+        task::{
+            AutotoolsBuild, CargoBuild, CleanFailurePolicy, CmakeBuild, CodeSource, DADKTask,
+            Dependency, DocsPolicy, EnvFile, InstallConfig, InstallFilter, PrebuiltSource,
+            TargetArch, TaskEnv, TaskType, SECRET_MASK,
+        },
+        task_log::{BuildStatus, InstallStamp, InstallStatus, OverlayLayer, TaskLog},
+        workspace::WORKSPACE_CONFIG_FILE_NAME,
     },
     scheduler::{SchedEntities, SchedEntity},
-    utils::file::FileUtils,
+    utils::{file::FileUtils, hash::HashUtils, ignore::IgnoreMatcher},
 };
 
-use self::cache::{CacheDirType, TaskDataDir};
+use self::{
+    cache::{lock_task_cache, CacheDirType, TaskDataDir, CACHE_ROOT},
+    clean_plan::{check_managed_path, CleanPlan, PlannedRemoval},
+    diagnostics::DiagnosticCounter,
+    events::{EventEmitter, EventKind, EventProgressReporter},
+    gc::CacheCategory,
+    limits::{is_oom_message, ResourceLimits},
+    manifest::InstallManifest,
+    oci::OciPuller,
+    output_log::{tee_lines, OutputLog},
+    ownership::{Ownership, OwnershipDb},
+    plan::{check_install_conflicts, PlannedOp, TaskPlan},
+    progress::{NoopProgressReporter, ProgressReporter},
+    result::{PhaseResult, TaskResult},
+    source::GitSource,
+};
 
 pub mod cache;
+pub mod clean_plan;
+pub mod diagnostics;
+pub mod docs;
+pub mod events;
+pub mod extract;
+pub mod fetch;
+pub mod gc;
+pub mod limits;
+pub mod lock;
+pub mod manifest;
+pub mod oci;
+pub mod output_log;
+pub mod ownership;
+pub mod plan;
+pub mod preflight;
+pub mod progress;
+pub mod result;
+pub mod s3;
+pub mod sandbox;
 pub mod source;
+pub mod strip;
+pub mod summary;
+pub mod system_dep;
 pub mod target;
 #[cfg(test)]
 mod tests;
+pub mod verify;
+
+/// 内核源码目录的环境变量名
+pub const KERNEL_SRC_ENV_KEY: &str = "DADK_KERNEL_SRC";
+/// 内核构建目录的环境变量名
+pub const KERNEL_BUILD_ENV_KEY: &str = "DADK_KERNEL_BUILD";
+/// 内核的目标架构的环境变量名
+pub const KERNEL_ARCH_ENV_KEY: &str = "DADK_KERNEL_ARCH";
+/// 每个任务构建时可以使用的任务数的环境变量名
+pub const JOBS_ENV_KEY: &str = "DADK_JOBS";
+/// 生效的构建配置档的环境变量名
+pub const PROFILE_ENV_KEY: &str = "DADK_PROFILE";
+/// 当前目标架构的环境变量名
+pub const TARGET_ARCH_ENV_KEY: &str = "DADK_TARGET_ARCH";
+/// 任务安装到DragonOS内的目录的环境变量名
+pub const INSTALL_PREFIX_ENV_KEY: &str = "DADK_INSTALL_PREFIX";
+/// 任务名的环境变量名
+pub const PKG_NAME_ENV_KEY: &str = "DADK_PKG_NAME";
+/// 任务版本的环境变量名
+pub const PKG_VERSION_ENV_KEY: &str = "DADK_PKG_VERSION";
+/// 任务在全局环境变量名中使用的标识（`name_version_env()`）的环境变量名
+pub const PKG_ID_ENV_KEY: &str = "DADK_PKG_ID";
+/// DragonOS sysroot在主机上的路径的环境变量名，只在安装阶段设置
+pub const SYSROOT_ENV_KEY: &str = "DADK_SYSROOT";
+/// 当前执行阶段（`build`、`install`、`clean`）的环境变量名
+pub const PHASE_ENV_KEY: &str = "DADK_PHASE";
+/// 内核模块默认的安装路径
+const KERNEL_MODULE_INSTALL_PATH: &str = "/lib/modules";
+/// 构建命令失败后，重试之前等待的时间
+const BUILD_RETRY_DELAY: Duration = Duration::from_secs(1);
 
 lazy_static! {
     // 全局环境变量的列表
     pub static ref ENV_LIST: RwLock<EnvMap> = RwLock::new(EnvMap::new());
+    // 忽略安装标记、总是重新安装的任务（`--force-install`）
+    static ref FORCE_INSTALL: RwLock<Vec<String>> = RwLock::new(Vec::new());
+}
+
+/// 是否输出任务需要重新构建的原因
+static WHY_DIRTY: AtomicBool = AtomicBool::new(false);
+
+/// 设置是否输出任务需要重新构建的原因（`--why-dirty`）
+pub fn set_why_dirty(enabled: bool) {
+    WHY_DIRTY.store(enabled, Ordering::Relaxed);
+}
+
+/// 设置忽略`install_once`、总是重新安装的任务（`--force-install`），任务名或者`任务名-版本`
+pub fn set_force_install(tasks: Vec<String>) {
+    *FORCE_INSTALL.write().unwrap() = tasks;
+}
+
+/// 任务是否被`--force-install`选中
+fn force_install(task: &DADKTask) -> bool {
+    return FORCE_INSTALL.read().unwrap().iter().any(|t| {
+        *t == task.name
+            || *t == format!("{}-{}", task.name, task.version)
+            || *t == task.name_version()
+    });
 }
 
 #[derive(Debug, Clone)]
@@ -46,6 +154,34 @@ pub struct Executor {
     task_data_dir: TaskDataDir,
     /// DragonOS sysroot的路径
     dragonos_sysroot: PathBuf,
+    /// 拉取数据时的进度报告器
+    progress: Arc<dyn ProgressReporter>,
+    /// 任务的执行结果
+    result: TaskResult,
+    /// 当前阶段是否命中缓存
+    cache_hit: bool,
+    /// 最后一条执行的命令的退出码
+    last_exit_code: Cell<Option<i32>>,
+    /// 任务的输出日志，执行任务时创建
+    output_log: Option<Arc<OutputLog>>,
+    /// 构建阶段的警告和错误计数器
+    diagnostics: Option<Arc<DiagnosticCounter>>,
+    /// 是否在网络隔离的沙箱中执行命令（只用于构建命令和构建钩子）
+    network_isolated: bool,
+    /// 构建命令和构建钩子的资源限制
+    resource_limits: ResourceLimits,
+    /// 每条命令的超时时间（目前只用于清理命令）
+    command_timeout: Option<Duration>,
+    /// 安装时剥离符号减少的字节数
+    stripped_bytes: Cell<u64>,
+    /// 安装时压缩或删除文档、翻译文件减少的字节数
+    docs_saved_bytes: Cell<u64>,
+    /// 本次安装的安装标记，安装成功后写入任务日志（仅设置了`install_once`的任务）
+    install_stamp: Option<InstallStamp>,
+    /// 执行事件的输出器，没有指定`--events`时为None
+    events: Option<Arc<EventEmitter>>,
+    /// 当前的阶段以及开始的时间
+    current_phase: Cell<Option<(&'static str, Instant)>>,
 }
 
 impl Executor {
@@ -60,12 +196,12 @@ impl Executor {
     /// ## 返回值
     ///
     /// * `Ok(Executor)` - 创建成功
-    /// * `Err(ExecutorError)` - 创建失败
+    /// * `Err(ExecError)` - 创建失败
     pub fn new(
         entity: Arc<SchedEntity>,
         action: Action,
         dragonos_sysroot: PathBuf,
-    ) -> Result<Self, ExecutorError> {
+    ) -> Result<Self, ExecError> {
         let local_envs = EnvMap::new();
         let build_dir = CacheDir::new(entity.clone(), CacheDirType::Build)?;
         let task_data_dir = TaskDataDir::new(entity.clone())?;
@@ -76,6 +212,10 @@ impl Executor {
             None
         };
 
+        let task_result = TaskResult::new(
+            entity.task().name_version(),
+            Self::task_arch(&entity.task()),
+        );
         let result: Executor = Self {
             action,
             entity,
@@ -84,11 +224,71 @@ impl Executor {
             source_dir,
             task_data_dir,
             dragonos_sysroot,
+            progress: Arc::new(NoopProgressReporter),
+            result: task_result,
+            cache_hit: false,
+            last_exit_code: Cell::new(None),
+            output_log: None,
+            diagnostics: None,
+            network_isolated: false,
+            resource_limits: ResourceLimits::default(),
+            command_timeout: None,
+            stripped_bytes: Cell::new(0),
+            docs_saved_bytes: Cell::new(0),
+            install_stamp: None,
+            events: None,
+            current_phase: Cell::new(None),
         };
 
         return Ok(result);
     }
 
+    /// 任务的目标架构
+    fn target_arch(&self) -> TargetArch {
+        return Self::task_arch(&self.entity.task());
+    }
+
+    /// 按固定架构构建的依赖使用该架构，其他任务使用当前的目标架构
+    pub(crate) fn task_arch(task: &DADKTask) -> TargetArch {
+        return task.pinned_arch.unwrap_or_else(Self::current_target_arch);
+    }
+
+    /// 当前的目标架构
+    fn current_target_arch() -> TargetArch {
+        return ENV_LIST
+            .read()
+            .unwrap()
+            .get("ARCH")
+            .and_then(|v| TargetArch::try_from(v.value.as_str()).ok())
+            .unwrap_or_else(DADKTask::default_target_arch);
+    }
+
+    /// # 任务的执行结果
+    ///
+    /// 执行任务之后，记录了执行了哪些阶段、是否命中缓存、时长以及退出码
+    pub fn result(&self) -> &TaskResult {
+        &self.result
+    }
+
+    /// # 设置进度报告器
+    ///
+    /// 默认不报告任何进度
+    pub fn set_progress_reporter(&mut self, progress: Arc<dyn ProgressReporter>) {
+        self.progress = progress;
+    }
+
+    /// # 设置执行事件的输出器
+    ///
+    /// 下载进度也作为事件输出，因此需要在[`Self::set_progress_reporter`]之后调用
+    pub fn set_event_emitter(&mut self, emitter: Arc<EventEmitter>) {
+        self.progress = Arc::new(EventProgressReporter::new(
+            self.progress.clone(),
+            emitter.clone(),
+            self.entity.task().name_version(),
+        ));
+        self.events = Some(emitter);
+    }
+
     /// # 执行任务
     ///
     /// 创建执行器后，调用此方法执行任务。
@@ -98,22 +298,156 @@ impl Executor {
     /// 2. 准备环境变量
     /// 3. 拉取数据（可选）
     /// 4. 执行构建
-    pub fn execute(&mut self) -> Result<(), ExecutorError> {
+    pub fn execute(&mut self) -> Result<(), ExecError> {
         info!("Execute task: {}", self.entity.task().name_version());
 
-        let r = self.do_execute();
-        self.save_task_data(r.clone());
+        // 持有缓存锁直到任务数据保存完毕，避免多个DADK进程同时读写同一个缓存
+        let _cache_lock = lock_task_cache(&self.entity.task())?;
+        if !matches!(self.action, Action::Clean(_)) {
+            self.record_cache_access();
+        }
+        self.output_log = match OutputLog::create(&self.entity.task().name_version()) {
+            Ok(log) => Some(Arc::new(log)),
+            Err(e) => {
+                warn!(
+                    "Failed to create log file for task {}: {}",
+                    self.entity.task().name_version(),
+                    e
+                );
+                None
+            }
+        };
+        self.current_phase.set(None);
+        self.emit(EventKind::TaskStarted {
+            action: self.action_name().to_string(),
+        });
+        // 错误信息中可能包括环境变量的值（例如替换了变量的命令），隐藏保密的值
+        let r = self
+            .do_execute()
+            .map_err(|e| e.map_message(|msg| self.local_envs.mask(&msg)));
+        // 执行成功、但无法保存执行记录时，任务也失败
+        let r = match (self.save_task_data(r.clone()), r) {
+            (Err(e), Ok(())) => Err(e),
+            (Err(e), Err(original)) => {
+                warn!(
+                    "Failed to save the task log of task {}: {}",
+                    self.entity.task().name_version(),
+                    e
+                );
+                Err(original)
+            }
+            (Ok(()), r) => r,
+        }
+        .map_err(|e| e.with_task(&self.entity.task().name_version()));
+        // 清理命令失败、但按`on_failure = "continue"`继续执行时，错误已经记录在结果中
+        self.result.success = r.is_ok() && self.result.error.is_none();
+        if let Err(e) = &r {
+            self.result.error = Some(e.to_string());
+        }
+        if let Err(e) = &r {
+            self.report_failure_log(e);
+        }
+        match &r {
+            Ok(()) => {
+                self.complete_phase();
+                self.emit(EventKind::TaskCompleted);
+            }
+            Err(e) => self.emit(EventKind::TaskFailed {
+                error: e.to_string(),
+                phase: self
+                    .current_phase
+                    .take()
+                    .map(|(phase, _)| phase.to_string()),
+            }),
+        }
         info!("Task {} finished", self.entity.task().name_version());
         return r;
     }
 
+    /// 记录任务的缓存目录被访问，用于按最近一次访问的时间回收缓存
+    fn record_cache_access(&self) {
+        let task = self.entity.task();
+        gc::touch(CacheCategory::Build, &task.cache_key());
+        gc::touch(CacheCategory::TaskData, &task.cache_key());
+        if let Some(source_dir) = &self.source_dir {
+            gc::touch(CacheCategory::Source, &task.name_version());
+            if source_dir.overlay_layers_root().exists() {
+                gc::touch(
+                    CacheCategory::Source,
+                    &format!("{}.layers", task.name_version()),
+                );
+            }
+        }
+    }
+
+    /// # 任务失败时，输出日志的最后若干行以及日志的路径
+    fn report_failure_log(&self, e: &ExecError) {
+        let log = match &self.output_log {
+            Some(log) => log,
+            None => return,
+        };
+        log.message(&format!("task failed: {:?}", e));
+        let n = OutputLog::config().tail_lines;
+        error!(
+            "Last {} lines of the log of task {}:",
+            n,
+            self.entity.task().name_version()
+        );
+        for line in log.tail(n) {
+            error!("{}", line);
+        }
+        error!("Full log: {}", log.path().display());
+    }
+
+    /// 在任务的输出日志中写入阶段标记，上一个阶段执行完毕
+    fn log_phase(&self, phase: &'static str) {
+        self.complete_phase();
+        self.current_phase.set(Some((phase, Instant::now())));
+        if let Some(log) = &self.output_log {
+            log.phase(phase);
+        }
+    }
+
+    /// 当前阶段执行完毕，输出`phase_completed`事件
+    fn complete_phase(&self) {
+        if let Some((phase, start)) = self.current_phase.take() {
+            self.emit(EventKind::PhaseCompleted {
+                phase: phase.to_string(),
+                duration_ms: start.elapsed().as_millis() as u64,
+            });
+        }
+    }
+
+    /// 输出执行事件
+    fn emit(&self, kind: EventKind) {
+        if let Some(events) = &self.events {
+            events.emit(&self.entity.task().name_version(), kind);
+        }
+    }
+
+    /// 执行的操作的名称，用于执行事件
+    fn action_name(&self) -> &'static str {
+        return match self.action {
+            Action::Build => "build",
+            Action::Install(_) => "install",
+            Action::Uninstall(_) => "uninstall",
+            Action::Clean(_) => "clean",
+            _ => "other",
+        };
+    }
+
     /// # 保存任务数据
-    fn save_task_data(&self, r: Result<(), ExecutorError>) {
+    fn save_task_data(&self, r: Result<(), ExecError>) -> Result<(), ExecError> {
         let mut task_log = self.task_data_dir.task_log();
         match self.action {
             Action::Build => {
                 if r.is_ok() {
                     task_log.set_build_status(BuildStatus::Success);
+                    // 构建完成后再计算，构建过程本身修改了这些路径时不会导致下次重新构建
+                    let watched = self.watched_hashes();
+                    task_log.set_rerun_if_changed((!watched.is_empty()).then_some(watched));
+                    task_log.set_built_commit(self.built_commit());
+                    task_log.set_global_envs_hash(self.entity.task().global_envs_hash());
                 } else {
                     task_log.set_build_status(BuildStatus::Failed);
                 }
@@ -121,9 +455,10 @@ impl Executor {
                 task_log.set_build_time_now();
             }
 
-            Action::Install => {
+            Action::Install(_) => {
                 if r.is_ok() {
                     task_log.set_install_status(InstallStatus::Success);
+                    task_log.set_install_stamp(self.install_stamp.clone());
                 } else {
                     task_log.set_install_status(InstallStatus::Failed);
                 }
@@ -137,33 +472,80 @@ impl Executor {
             _ => {}
         }
 
-        self.task_data_dir
-            .save_task_log(&task_log)
-            .expect("Failed to save task log");
+        return self.task_data_dir.save_task_log(&task_log);
     }
 
-    fn do_execute(&mut self) -> Result<(), ExecutorError> {
+    fn do_execute(&mut self) -> Result<(), ExecError> {
+        // 元包没有构建、安装和清理操作，总是被视为最新的
+        if self.entity.task().task_type == TaskType::Meta {
+            info!(
+                "Task {} is a meta package, nothing to do.",
+                self.entity.task().name_version()
+            );
+            return Ok(());
+        }
+
         // 准备本地环境变量
         self.prepare_local_env()?;
+        self.log_composed_envs();
 
+        let start = Instant::now();
         match self.action {
             Action::Build => {
                 // 构建任务
-                self.build()?;
+                self.diagnostics = Some(Arc::new(DiagnosticCounter::new()));
+                let r = self.build();
+                self.result.build = Some(self.finish_phase(start));
+                self.result.diagnostics = self.diagnostics.take().map(|d| d.counts());
+                r?;
             }
-            Action::Install => {
+            Action::Install(_) => {
                 // 把构建结果安装到DragonOS
-                self.install()?;
+                self.log_phase("install");
+                let r = self.install();
+                if self.entity.task().install.strip.is_enabled() && !self.cache_hit {
+                    self.result.stripped_bytes = Some(self.stripped_bytes.get());
+                }
+                if docs::is_enabled(&self.entity.task().install) && !self.cache_hit {
+                    self.result.docs_saved_bytes = Some(self.docs_saved_bytes.get());
+                }
+                self.result.install = Some(self.finish_phase(start));
+                r?;
             }
             Action::Clean(_) => {
                 // 清理构建结果
+                self.log_phase("clean");
                 let r = self.clean();
-                if let Err(e) = r {
-                    error!(
-                        "Failed to clean task {}: {:?}",
-                        self.entity.task().name_version(),
-                        e
-                    );
+                self.result.clean = Some(self.finish_phase(start));
+                self.result.freed_bytes = r.as_ref().ok().copied();
+                match (r, self.entity.task().clean.on_failure) {
+                    (Err(ExecError::NothingToClean(msg)), _) => {
+                        warn!("{msg}");
+                    }
+                    (Err(e), CleanFailurePolicy::Abort) => {
+                        error!(
+                            "Failed to clean task {}, aborting: {:?}",
+                            self.entity.task().name_version(),
+                            e
+                        );
+                        return Err(e);
+                    }
+                    (Err(e), CleanFailurePolicy::Continue) => {
+                        error!(
+                            "Failed to clean task {}, continuing with other tasks: {:?}",
+                            self.entity.task().name_version(),
+                            e
+                        );
+                        self.result.error = Some(e.to_string());
+                    }
+                    (Err(e), CleanFailurePolicy::Ignore) => {
+                        warn!(
+                            "Ignoring clean failure of task {}: {:?}",
+                            self.entity.task().name_version(),
+                            e
+                        );
+                    }
+                    (Ok(_), _) => {}
                 }
             }
             _ => {
@@ -174,26 +556,115 @@ impl Executor {
         return Ok(());
     }
 
+    /// # 结束一个阶段
+    ///
+    /// 生成该阶段的执行结果
+    fn finish_phase(&mut self, start: Instant) -> PhaseResult {
+        if std::mem::take(&mut self.cache_hit) {
+            return PhaseResult::cached();
+        }
+        return PhaseResult::executed(start.elapsed(), self.last_exit_code.take());
+    }
+
     /// # 执行build操作
-    fn build(&mut self) -> Result<(), ExecutorError> {
+    fn build(&mut self) -> Result<(), ExecError> {
         if let Some(status) = self.task_log().build_status() {
             if *status == BuildStatus::Success && self.entity.task().build_once {
-                info!(
-                    "Task {} has been built successfully, skip build.",
-                    self.entity.task().name_version()
-                );
-                return Ok(());
+                let dirty = self.dirty_watched_paths();
+                let commit_changed = self.git_commit_changed()?;
+                let envs_changed = self.global_envs_changed();
+                if dirty.is_empty() && commit_changed.is_none() && envs_changed.is_none() {
+                    info!(
+                        "Task {} has been built successfully, skip build.",
+                        self.entity.task().name_version()
+                    );
+                    self.cache_hit = true;
+                    return Ok(());
+                }
+                for reason in commit_changed.into_iter().chain(envs_changed) {
+                    info!(
+                        "Task {}: {}, rebuild.",
+                        self.entity.task().name_version(),
+                        reason
+                    );
+                }
+                if !dirty.is_empty() {
+                    if WHY_DIRTY.load(Ordering::Relaxed) {
+                        info!(
+                            "Task {} is dirty, watched paths changed: {}",
+                            self.entity.task().name_version(),
+                            dirty.join(", ")
+                        );
+                    } else {
+                        info!(
+                            "Task {}: rerun_if_changed inputs changed, rebuild. Use --why-dirty for details.",
+                            self.entity.task().name_version()
+                        );
+                    }
+                }
             }
         }
 
+        self.check_system_depends()?;
         self.mv_target_to_tmp()?;
 
         // 确认源文件就绪
+        self.log_phase("source");
         self.prepare_input()?;
+        // 检查源码身份是否与锁文件一致
+        self.check_source_lock()?;
+        // 内核模块需要内核源码目录，并且内核的架构需要与任务匹配
+        if let TaskType::KernelModule(_) = self.entity.task().task_type {
+            self.check_kernel_module_env(&ENV_LIST.read().unwrap())?;
+        }
+
+        // 源码已经就绪，之后的构建命令和钩子按配置在沙箱中执行
+        self.network_isolated = self.network_sandbox_enabled()?;
 
-        let command: Option<Command> = self.create_command()?;
-        if let Some(cmd) = command {
-            self.run_command(cmd)?;
+        self.log_phase("build");
+        self.resource_limits = self.build_resource_limits()?;
+        self.prepare_build_work_dir()?;
+        self.run_pre_build()?;
+        if self.entity.task().build.cmake.is_some() {
+            self.write_cmake_toolchain()?;
+        }
+
+        let commands = self.create_commands()?;
+        let total = commands.len();
+        for (i, (raw_cmd, cmd)) in commands.into_iter().enumerate() {
+            if total > 1 {
+                info!(
+                    "Task {}: build step {}/{}: {}",
+                    self.entity.task().name_version(),
+                    i + 1,
+                    total,
+                    raw_cmd
+                );
+            }
+            let (r, attempts) = self.run_build_step(i, cmd)?;
+            r.map_err(|e| match e {
+                ExecError::Build(_) | ExecError::Timeout(_) => e.map_message(|mut msg| {
+                    // 有多条构建命令时，指出是哪一条失败了
+                    if total > 1 {
+                        msg = format!("{} (build step {}/{}: `{}`)", msg, i + 1, total, raw_cmd);
+                    }
+                    if attempts > 1 {
+                        msg = format!("{} (failed after {} attempts)", msg, attempts);
+                    }
+                    msg
+                }),
+                e => e,
+            })?;
+        }
+
+        self.check_max_warnings()?;
+
+        if let Some(cargo) = &self.entity.task().build.cargo {
+            self.stage_cargo_bins(cargo)?;
+        }
+
+        if let Some(post_build) = &self.entity.task().build.post_build {
+            self.run_hook("post_build", &post_build.commands())?;
         }
 
         // 检查构建结果，如果为空，则抛出警告
@@ -206,219 +677,2040 @@ impl Executor {
         return Ok(());
     }
 
-    /// # 执行安装操作，把构建结果安装到DragonOS
-    fn install(&self) -> Result<(), ExecutorError> {
-        if let Some(status) = self.task_log().install_status() {
-            if *status == InstallStatus::Success && self.entity.task().install_once {
-                info!(
-                    "Task {} has been installed successfully, skip install.",
-                    self.entity.task().name_version()
-                );
-                return Ok(());
+    /// # 执行第`index`条构建命令
+    ///
+    /// 命令退出码不为0时，按`retries`等待一小段时间后重新执行，启动命令失败等其他错误不重试。
+    /// 返回最后一次执行的结果以及执行的次数
+    fn run_build_step(
+        &self,
+        index: usize,
+        mut cmd: Command,
+    ) -> Result<(Result<(), ExecError>, u32), ExecError> {
+        let retries = self.entity.task().build.retries.unwrap_or(0);
+        let mut attempts = 1;
+        loop {
+            match self.run_command(cmd) {
+                Err(e @ (ExecError::Build(_) | ExecError::Timeout(_))) if attempts <= retries => {
+                    warn!(
+                        "Task {}: build command failed (attempt {}/{}), retrying in {}s: {}",
+                        self.entity.task().name_version(),
+                        attempts,
+                        retries + 1,
+                        BUILD_RETRY_DELAY.as_secs(),
+                        e.message()
+                    );
+                    std::thread::sleep(BUILD_RETRY_DELAY);
+                    attempts += 1;
+                    // Command执行后不能再次执行，重新创建
+                    cmd = self.create_commands()?.swap_remove(index).1;
+                }
+                r => return Ok((r, attempts)),
             }
         }
+    }
 
-        let binding = self.entity.task();
-        let in_dragonos_path = binding.install.in_dragonos_path.as_ref();
-        // 如果没有指定安装路径，则不执行安装
-        if in_dragonos_path.is_none() {
-            return Ok(());
+    /// # 检查任务的系统依赖
+    ///
+    /// 所有系统依赖都会被检查，缺少的依赖汇总在一个错误中
+    fn check_system_depends(&self) -> Result<(), ExecError> {
+        let task = self.entity.task();
+        let errors: Vec<String> = task
+            .depends
+            .iter()
+            .filter(|d| !d.is_task())
+            .filter_map(|d| system_dep::check(d).err())
+            .collect();
+        if !errors.is_empty() {
+            return Err(ExecError::Validation(format!(
+                "Task {}: {}",
+                task.name_version(),
+                errors.join("; ")
+            )));
         }
-        info!("Installing task: {}", self.entity.task().name_version());
-        let mut in_dragonos_path = in_dragonos_path.unwrap().to_string_lossy().to_string();
+        return Ok(());
+    }
 
-        debug!("in_dragonos_path: {}", in_dragonos_path);
-        // 去除开头的斜杠
-        {
-            let count_leading_slashes = in_dragonos_path.chars().take_while(|c| *c == '/').count();
-            in_dragonos_path = in_dragonos_path[count_leading_slashes..].to_string();
+    /// # 构建时是否开启网络隔离
+    ///
+    /// 任务自己开启了网络隔离但沙箱不可用时报错；
+    /// 只是全局默认开启时，输出警告并在不隔离网络的情况下构建
+    fn network_sandbox_enabled(&self) -> Result<bool, ExecError> {
+        let explicit = self.entity.task().build.sandbox_network();
+        if !explicit.unwrap_or_else(sandbox::network_default) {
+            return Ok(false);
         }
-        // 拼接最终的安装路径
-        let install_path = self.dragonos_sysroot.join(in_dragonos_path);
-        debug!("install_path: {:?}", install_path);
-        // 创建安装路径
-        std::fs::create_dir_all(&install_path).map_err(|e| {
-            ExecutorError::InstallError(format!("Failed to create install path: {}", e.to_string()))
-        })?;
-
-        // 拷贝构建结果到安装路径
-        let build_dir: PathBuf = self.build_dir.path.clone();
-        FileUtils::copy_dir_all(&build_dir, &install_path)
-            .map_err(|e| ExecutorError::InstallError(e))?;
-        info!("Task {} installed.", self.entity.task().name_version());
-
-        // 安装完后，删除临时target文件
-        if let Some(target) = self.entity.target() {
-            target.clean_tmpdadk()?;
+        match sandbox::probe_network() {
+            Ok(_) => {
+                info!(
+                    "Task {}: build commands run without network access",
+                    self.entity.task().name_version()
+                );
+                return Ok(true);
+            }
+            Err(e) if explicit == Some(true) => {
+                return Err(ExecError::Validation(format!(
+                    "Task {} requires sandbox.network, but network sandboxing is unavailable on this host: {}",
+                    self.entity.task().name_version(),
+                    e
+                )));
+            }
+            Err(e) => {
+                warn!(
+                    "Network sandboxing is unavailable on this host, task {} is built with network access: {}",
+                    self.entity.task().name_version(),
+                    e
+                );
+                return Ok(false);
+            }
         }
-
-        return Ok(());
     }
 
-    fn clean(&self) -> Result<(), ExecutorError> {
-        let level = if let Action::Clean(l) = self.action {
-            l.level
-        } else {
-            panic!(
-                "BUG: clean() called with non-clean action. executor details: {:?}",
-                self
+    /// # 构建命令和构建钩子的资源限制
+    ///
+    /// 实际生效的限制写入任务的输出日志；不支持资源限制的平台上输出警告，不限制资源
+    fn build_resource_limits(&self) -> Result<ResourceLimits, ExecError> {
+        let limits = self
+            .entity
+            .task()
+            .build
+            .resource_limits()
+            .map_err(ExecError::Validation)?;
+        if limits.is_empty() {
+            return Ok(limits);
+        }
+        if !ResourceLimits::supported() {
+            warn!(
+                "Resource limits are not supported on this platform, task {} is built without limits ({})",
+                self.entity.task().name_version(),
+                limits
             );
-        };
+            return Ok(ResourceLimits::default());
+        }
         info!(
-            "Cleaning task: {}, level={level}",
-            self.entity.task().name_version()
+            "Task {}: resource limits: {}",
+            self.entity.task().name_version(),
+            limits
         );
+        if let Some(log) = &self.output_log {
+            log.message(&format!("resource limits: {}", limits));
+        }
+        return Ok(limits);
+    }
 
-        let r: Result<(), ExecutorError> = match level {
-            CleanLevel::All => self.clean_all(),
-            CleanLevel::Src => self.clean_src(),
-            CleanLevel::Target => self.clean_target(),
-            CleanLevel::Cache => self.clean_cache(),
+    /// # 检查构建输出中的警告数是否超过`max_warnings`
+    fn check_max_warnings(&self) -> Result<(), ExecError> {
+        let max_warnings = match self.entity.task().build.max_warnings {
+            Some(max_warnings) => max_warnings,
+            None => return Ok(()),
         };
-
-        if let Err(e) = r {
-            error!(
-                "Failed to clean task: {}, error message: {:?}",
+        let counts = self
+            .diagnostics
+            .as_ref()
+            .map(|d| d.counts())
+            .unwrap_or_default();
+        if counts.warnings > max_warnings {
+            let errmsg = format!(
+                "Task {} produced {} warnings, exceeding max_warnings {}",
                 self.entity.task().name_version(),
-                e
+                counts.warnings,
+                max_warnings
             );
-            return Err(e);
+            error!("{errmsg}");
+            return Err(ExecError::Build(errmsg));
         }
-
-        return Ok(());
-    }
-
-    fn clean_all(&self) -> Result<(), ExecutorError> {
-        // 在源文件目录执行清理
-        self.clean_src()?;
-        // 清理构建结果
-        self.clean_target()?;
-        // 清理缓存
-        self.clean_cache()?;
         return Ok(());
     }
 
-    /// 在源文件目录执行清理
-    fn clean_src(&self) -> Result<(), ExecutorError> {
-        let cmd: Option<Command> = self.create_command()?;
-        if cmd.is_none() {
-            // 如果这里没有命令，则认为用户不需要在源文件目录执行清理
+    /// # 准备执行构建命令的工作目录
+    ///
+    /// 如果工作目录不存在，且配置了`create`，则创建它。
+    /// 工作目录会被输出到日志中，并通过`DADK_BUILD_DIR`环境变量传递给构建命令
+    fn prepare_build_work_dir(&mut self) -> Result<(), ExecError> {
+        // 没有构建命令（例如预编译包）时，不需要构建的工作目录
+        if !self.entity.task().build.has_build() {
             return Ok(());
         }
+        let work_dir = self.build_work_dir();
+        if !work_dir.exists() {
+            if self.entity.task().build.create {
+                std::fs::create_dir_all(&work_dir).map_err(|e| {
+                    ExecError::Validation(format!(
+                        "Failed to create build dir {}: {}",
+                        work_dir.display(),
+                        e
+                    ))
+                })?;
+            } else {
+                return Err(ExecError::Validation(format!(
+                    "Build dir {} does not exist, set `create` to create it automatically",
+                    work_dir.display()
+                )));
+            }
+        }
         info!(
-            "{}: Cleaning in source directory: {:?}",
+            "Task {}: building in {}",
             self.entity.task().name_version(),
-            self.src_work_dir()
+            work_dir.display()
         );
-
-        let cmd = cmd.unwrap();
-        self.run_command(cmd)?;
+        self.local_envs.add(EnvVar::new(
+            "DADK_BUILD_DIR".to_string(),
+            work_dir.to_string_lossy().to_string(),
+        ));
         return Ok(());
     }
 
-    /// 清理构建输出目录
-    fn clean_target(&self) -> Result<(), ExecutorError> {
-        info!(
-            "{}: Cleaning build target directory: {:?}",
-            self.entity.task().name_version(),
-            self.build_dir.path
-        );
+    /// # 执行构建前钩子
+    ///
+    /// 默认只在源码身份与上一次成功执行钩子时不同（或者无法确定源码身份）时执行，
+    /// 开启`pre_build_always`时每次构建都执行
+    fn run_pre_build(&self) -> Result<(), ExecError> {
+        let task = self.entity.task();
+        let pre_build = match &task.build.pre_build {
+            Some(pre_build) => pre_build.commands(),
+            None => return Ok(()),
+        };
+        if !task.build.pre_build_always {
+            if let Some(resolved) = self.resolve_source()? {
+                if self.task_log().pre_build_source() == Some(&resolved) {
+                    info!(
+                        "Task {}: source unchanged, skip pre_build hook",
+                        task.name_version()
+                    );
+                    return Ok(());
+                }
+            }
+        }
 
-        return self.build_dir.remove_self_recursive();
-    }
+        self.run_hook("pre_build", &pre_build)?;
 
-    /// 清理下载缓存
-    fn clean_cache(&self) -> Result<(), ExecutorError> {
-        let cache_dir = self.source_dir.as_ref();
-        if cache_dir.is_none() {
-            // 如果没有缓存目录，则认为用户不需要清理缓存
-            return Ok(());
-        }
-        info!(
-            "{}: Cleaning cache directory: {}",
-            self.entity.task().name_version(),
-            self.src_work_dir().display()
-        );
-        return cache_dir.unwrap().remove_self_recursive();
+        // 钩子可能会修改源码目录，因此记录执行钩子之后的源码身份
+        let resolved = self.resolve_source()?;
+        let mut task_log = self.task_log();
+        task_log.set_pre_build_source(resolved);
+        self.task_data_dir.save_task_log(&task_log)?;
+        return Ok(());
     }
 
-    /// 获取源文件的工作目录
-    fn src_work_dir(&self) -> PathBuf {
-        if let Some(local_path) = self.entity.task().source_path() {
-            return local_path;
+    /// # 按顺序执行钩子中的命令
+    ///
+    /// 钩子与构建命令使用相同的工作目录和环境变量，失败时的错误信息中会指出是哪个钩子
+    fn run_hook(&self, hook: &str, commands: &[String]) -> Result<(), ExecError> {
+        let name_version = self.entity.task().name_version();
+        let total = commands.len();
+        for (i, raw_cmd) in commands.iter().enumerate() {
+            info!(
+                "Task {}: {} hook {}/{}: {}",
+                name_version,
+                hook,
+                i + 1,
+                total,
+                raw_cmd
+            );
+            let cmd = self.create_command(raw_cmd)?;
+            self.run_command(cmd).map_err(|e| match e {
+                ExecError::Build(_) | ExecError::Timeout(_) => e.map_message(|msg| {
+                    format!("{} ({} hook {}/{}: `{}`)", msg, hook, i + 1, total, raw_cmd)
+                }),
+                e => e,
+            })?;
         }
-        return self.source_dir.as_ref().unwrap().path.clone();
+        info!("Task {}: {} hook finished", name_version, hook);
+        return Ok(());
     }
 
-    fn task_log(&self) -> TaskLog {
-        return self.task_data_dir.task_log();
+    /// # 计算当前的安装标记
+    fn current_install_stamp(&self) -> Result<InstallStamp, ExecError> {
+        let task = self.entity.task();
+        let output_hash = HashUtils::hash_dir(&self.build_dir.path, &IgnoreMatcher::empty())
+            .map_err(|e| {
+                ExecError::Install(format!(
+                    "Failed to hash build output {}: {}",
+                    self.build_dir.path.display(),
+                    e
+                ))
+            })?;
+        let install_config =
+            serde_json::to_string(&task.install).map_err(|e| ExecError::Install(e.to_string()))?;
+        return Ok(InstallStamp {
+            name_version: task.name_version(),
+            output_hash,
+            install_config_hash: HashUtils::sha256_bytes(install_config.as_bytes()),
+        });
     }
 
-    /// 为任务创建命令
-    fn create_command(&self) -> Result<Option<Command>, ExecutorError> {
-        // 获取命令
-        let raw_cmd = match self.entity.task().task_type {
-            TaskType::BuildFromSource(_) => match self.action {
-                Action::Build => self.entity.task().build.build_command.clone(),
-                Action::Clean(_) => self.entity.task().clean.clean_command.clone(),
-                _ => unimplemented!(
-                    "create_command: Action {:?} not supported yet.",
-                    self.action
-                ),
+    /// # 设置了`install_once`的任务是否可以跳过安装
+    ///
+    /// 上一次安装成功，并且安装标记与当前一致时才跳过。没有安装标记（旧版本安装的任务）时重新安装
+    fn install_once_satisfied(&self, stamp: &InstallStamp) -> bool {
+        let task = self.entity.task();
+        let task_log = self.task_log();
+        if task_log.install_status() != Some(&InstallStatus::Success) {
+            return false;
+        }
+        if force_install(&task) {
+            info!("Task {}: --force-install, reinstall", task.name_version());
+            return false;
+        }
+        let reason = match task_log.install_stamp() {
+            Some(previous) => match stamp.diff(previous) {
+                Some(reason) => reason,
+                None => return true,
             },
+            None => "no install stamp",
+        };
+        info!(
+            "Task {} has install_once set but needs reinstall: {}",
+            task.name_version(),
+            reason
+        );
+        return false;
+    }
 
-            TaskType::InstallFromPrebuilt(_) => match self.action {
-                Action::Build => self.entity.task().build.build_command.clone(),
-                Action::Clean(_) => self.entity.task().clean.clean_command.clone(),
-                _ => unimplemented!(
-                    "create_command: Action {:?} not supported yet.",
-                    self.action
-                ),
-            },
+    /// 执行构建命令的工作目录
+    fn build_work_dir(&self) -> PathBuf {
+        let src_work_dir = self.src_work_dir();
+        return match &self.entity.task().build.build_dir {
+            Some(build_dir) => src_work_dir.join(build_dir),
+            None => src_work_dir,
         };
+    }
 
-        if raw_cmd.is_none() {
-            return Ok(None);
+    /// # 执行安装操作，把构建结果安装到DragonOS
+    fn install(&mut self) -> Result<(), ExecError> {
+        if self.entity.task().install_once {
+            let stamp = self.current_install_stamp()?;
+            if self.install_once_satisfied(&stamp) {
+                info!(
+                    "Task {} has been installed successfully, skip install.",
+                    self.entity.task().name_version()
+                );
+                self.cache_hit = true;
+                return Ok(());
+            }
+            self.install_stamp = Some(stamp);
         }
 
-        let raw_cmd = raw_cmd.unwrap();
-
-        let mut command = Command::new("bash");
-        command.current_dir(self.src_work_dir());
-
-        // 设置参数
-        command.arg("-c");
-        command.arg(raw_cmd);
-
-        // 设置环境变量
-        let env_list = ENV_LIST.read().unwrap();
+        let binding = self.entity.task();
+        let is_kernel_module = matches!(binding.task_type, TaskType::KernelModule(_));
+        let in_dragonos_path = match binding.install.in_dragonos_path.as_ref() {
+            Some(path) => Some(path.clone()),
+            // 内核模块默认安装到/lib/modules
+            None if is_kernel_module => Some(PathBuf::from(KERNEL_MODULE_INSTALL_PATH)),
+            None => None,
+        };
+        // 如果没有指定安装路径，也没有指定安装目录、要安装的文件、符号链接和安装后钩子，则不执行安装
+        if in_dragonos_path.is_none()
+            && binding.install.destinations.is_empty()
+            && binding.install.files.is_empty()
+            && binding.install.symlinks.is_empty()
+            && binding.install.post_install.is_none()
+        {
+            return Ok(());
+        }
+        info!("Installing task: {}", self.entity.task().name_version());
+        // 在拷贝之前检查与本次执行中已经安装的任务是否冲突，有冲突时不修改sysroot
+        let allow_overwrite = binding
+            .install
+            .overwrite_matcher()
+            .map_err(ExecError::Install)?;
+        let mut plan = TaskPlan::new(binding.name_version());
+        self.plan_install_ops(&mut plan)?;
+        check_install_conflicts(
+            &self.dragonos_sysroot,
+            &mut plan,
+            &self.entity,
+            &allow_overwrite,
+        )
+        .map_err(|e| ExecError::Install(format!("Task {}: {}", binding.name_version(), e)))?;
+        let build_dir: PathBuf = self.build_dir.path.clone();
+        // 安装的文件和符号链接，记录到安装清单中
+        let mut installed = Vec::new();
+        if let Some(in_dragonos_path) = in_dragonos_path {
+            installed.extend(self.install_dir(&build_dir, &in_dragonos_path, is_kernel_module)?);
+        }
+        installed.extend(self.install_destinations(&build_dir, is_kernel_module)?);
+        // 按顺序安装`files`中的文件，它们可以覆盖前面拷贝的文件
+        let (files, owners) = self.install_files(&build_dir)?;
+        installed.extend(files);
+        installed.extend(self.install_symlinks()?);
+        // 安装后钩子在拷贝文件、创建符号链接之后执行，通过`DADK_SYSROOT`访问sysroot
+        if let Some(post_install) = &binding.install.post_install {
+            self.run_hook("post_install", &post_install.commands())
+                .map_err(|e| match e {
+                    ExecError::Build(msg) => ExecError::Install(msg),
+                    e => e,
+                })?;
+        }
+        let manifest = InstallManifest::collect(
+            binding.name_version(),
+            &self.dragonos_sysroot,
+            &installed,
+            &owners,
+        )
+        .map_err(|e| {
+            ExecError::Install(format!(
+                "Task {}: failed to collect install manifest: {}",
+                binding.name_version(),
+                e
+            ))
+        })?;
+        let manifest = manifest.save().map_err(|e| {
+            ExecError::Install(format!(
+                "Task {}: failed to write install manifest: {}",
+                binding.name_version(),
+                e
+            ))
+        })?;
+        debug!("Install manifest: {}", manifest.display());
+        info!("Task {} installed.", self.entity.task().name_version());
+
+        // 安装完后，删除临时target文件
+        if let Some(target) = self.entity.target() {
+            target.clean_tmpdadk()?;
+        }
+
+        return Ok(());
+    }
+
+    /// # 计算安装计划
+    ///
+    /// 按与`install()`相同的规则列出要拷贝的文件和要创建的符号链接，不修改sysroot，也不保存任务数据。
+    /// 计算过程中的错误记录在计划的`errors`中
+    pub fn install_plan(&mut self) -> TaskPlan {
+        let mut plan = TaskPlan::new(self.entity.task().name_version());
+        if let Err(e) = self.plan_install(&mut plan) {
+            plan.errors.push(format!("{:?}", e));
+        }
+        return plan;
+    }
+
+    fn plan_install(&mut self, plan: &mut TaskPlan) -> Result<(), ExecError> {
+        if self.entity.task().task_type == TaskType::Meta {
+            return Ok(());
+        }
+        self.prepare_local_env()?;
+        let binding = self.entity.task();
+        if binding.install_once && self.install_once_satisfied(&self.current_install_stamp()?) {
+            plan.up_to_date = true;
+            return Ok(());
+        }
+        if self.task_log().build_status() != Some(&BuildStatus::Success) {
+            return Err(ExecError::Install(format!(
+                "Task {} has not been built successfully, build it before installing",
+                binding.name_version()
+            )));
+        }
+        return self.plan_install_ops(plan);
+    }
+
+    /// # 列出安装时要拷贝的文件和要创建的符号链接
+    ///
+    /// 安装前检查冲突和`--dry-run`都使用这里的结果
+    fn plan_install_ops(&self, plan: &mut TaskPlan) -> Result<(), ExecError> {
+        let binding = self.entity.task();
+        let is_kernel_module = matches!(binding.task_type, TaskType::KernelModule(_));
+        let in_dragonos_path = match binding.install.in_dragonos_path.as_ref() {
+            Some(path) => Some(path.clone()),
+            None if is_kernel_module => Some(PathBuf::from(KERNEL_MODULE_INSTALL_PATH)),
+            None => None,
+        };
+        let build_dir = self.build_dir.path.clone();
+        let filter = binding
+            .install
+            .install_filter()
+            .map_err(ExecError::Install)?;
+        if let Some(in_dragonos_path) = in_dragonos_path {
+            let install_path = self.sysroot_path(&in_dragonos_path, true)?;
+            if is_kernel_module {
+                for module in FileUtils::list_files_recursive(&build_dir)
+                    .map_err(|e| ExecError::Install(e.to_string()))?
+                    .into_iter()
+                    .filter(|f| f.extension().map_or(false, |ext| ext == "ko"))
+                {
+                    let dest = install_path.join(module.file_name().unwrap());
+                    self.plan_file(&module, &dest, true, plan)?;
+                }
+            } else {
+                self.plan_dir(&build_dir, &install_path, &filter, plan)?;
+            }
+        }
+        for (i, dest) in binding.install.destinations.iter().enumerate() {
+            let src = build_dir.join(&dest.src_subdir);
+            if !src.is_dir() {
+                return Err(ExecError::Install(format!(
+                    "Task {}: install.destinations[{}] ({} -> {}): {} is not a directory in the build output",
+                    binding.name_version(),
+                    i,
+                    dest.src_subdir.display(),
+                    dest.in_dragonos_path.display(),
+                    src.display()
+                )));
+            }
+            let install_path = self.sysroot_path(&dest.in_dragonos_path, true)?;
+            self.plan_dir(&src, &install_path, &filter, plan)?;
+        }
+        for (i, entry) in binding.install.files.iter().enumerate() {
+            let src = build_dir.join(&entry.src);
+            if src.symlink_metadata().is_err() {
+                return Err(ExecError::Install(format!(
+                    "Task {}: install.files[{}] ({} -> {}): source {} does not exist in the build output",
+                    binding.name_version(),
+                    i,
+                    entry.src.display(),
+                    entry.dest.display(),
+                    src.display()
+                )));
+            }
+            let dest = self.sysroot_path(&entry.dest, false)?;
+            if src.is_dir() {
+                self.plan_dir(&src, &dest, &filter.exclude_only(), plan)?;
+            } else {
+                self.plan_file(&src, &dest, true, plan)?;
+            }
+        }
+        for symlink in binding.install.symlinks.iter() {
+            let link = self.sysroot_path(&symlink.link, false)?;
+            plan.ops.push(PlannedOp::Symlink {
+                link: self.dragonos_path(&link),
+                target: PathBuf::from(self.interpolate(&symlink.target.to_string_lossy())?),
+            });
+        }
+        plan.post_install = binding.install.post_install.is_some();
+
+        let (policy, locales) = docs::effective_policy(&binding.install);
+        plan.apply_docs(policy, locales.as_deref());
+        return Ok(());
+    }
+
+    /// 把目录中被`filter`选中的文件加入安装计划，与`copy_install_dir`的规则相同
+    fn plan_dir(
+        &self,
+        src: &Path,
+        install_path: &Path,
+        filter: &InstallFilter,
+        plan: &mut TaskPlan,
+    ) -> Result<(), ExecError> {
+        let files = FileUtils::list_files_recursive(src).map_err(|e| {
+            ExecError::Install(format!("Failed to list files in {}: {}", src.display(), e))
+        })?;
+        let follow_symlinks = self.entity.task().install.follow_symlinks;
+        for file in files {
+            let relative = file.strip_prefix(src).unwrap();
+            if !filter.selects(relative) {
+                continue;
+            }
+            self.plan_file(&file, &install_path.join(relative), follow_symlinks, plan)?;
+        }
+        return Ok(());
+    }
+
+    /// 把一个文件（或者跟随的符号链接指向的目录中的文件）加入安装计划，`dest`为sysroot中的路径
+    fn plan_file(
+        &self,
+        src: &Path,
+        dest: &Path,
+        follow_symlinks: bool,
+        plan: &mut TaskPlan,
+    ) -> Result<(), ExecError> {
+        let err = |e: std::io::Error| {
+            ExecError::Install(format!("Failed to read {}: {}", src.display(), e))
+        };
+        if src.is_symlink() && !follow_symlinks {
+            plan.ops.push(PlannedOp::Symlink {
+                link: self.dragonos_path(dest),
+                target: std::fs::read_link(src).map_err(err)?,
+            });
+        } else if src.is_dir() {
+            for file in FileUtils::list_files_recursive(src).map_err(err)? {
+                let relative = file.strip_prefix(src).unwrap();
+                self.plan_file(&file, &dest.join(relative), true, plan)?;
+            }
+        } else {
+            let strip = self.entity.task().install.strip.is_enabled();
+            plan.ops.push(PlannedOp::Copy {
+                src: src.to_path_buf(),
+                dest: self.dragonos_path(dest),
+                sha256: HashUtils::sha256_file(src).map_err(err)?,
+                transformed: strip && strip::is_strippable_elf(src).unwrap_or(false),
+            });
+        }
+        return Ok(());
+    }
+
+    /// sysroot中的路径在DragonOS内对应的绝对路径
+    fn dragonos_path(&self, path: &Path) -> PathBuf {
+        return Path::new("/").join(path.strip_prefix(&self.dragonos_sysroot).unwrap_or(path));
+    }
+
+    /// # DragonOS内的路径在sysroot中对应的路径
+    ///
+    /// 会先替换路径中的`${变量}`，替换后的路径按与配置文件相同的规则校验，
+    /// `into_dir`的含义见[`InstallConfig::validate_dragonos_path`]
+    fn sysroot_path(&self, in_dragonos_path: &Path, into_dir: bool) -> Result<PathBuf, ExecError> {
+        let mut in_dragonos_path = self.interpolate(&in_dragonos_path.to_string_lossy())?;
+        debug!("in_dragonos_path: {}", in_dragonos_path);
+        InstallConfig::validate_dragonos_path(Path::new(&in_dragonos_path), into_dir).map_err(
+            |e| ExecError::Install(format!("Task {}: {}", self.entity.task().name_version(), e)),
+        )?;
+        // 去除开头的斜杠
+        {
+            let count_leading_slashes = in_dragonos_path.chars().take_while(|c| *c == '/').count();
+            in_dragonos_path = in_dragonos_path[count_leading_slashes..].to_string();
+        }
+        return Ok(self.dragonos_sysroot.join(in_dragonos_path));
+    }
+
+    /// # 检查sysroot中的路径在主机上解析符号链接之后仍然在sysroot中
+    ///
+    /// 之前安装的符号链接可能让安装写到sysroot以外，例如指向`/lib`的绝对路径的符号链接，
+    /// 在主机上会解析为主机的`/lib`。只检查路径中已经存在的部分，
+    /// `follow_last`为false时不解析最后一级（安装时会先删除已有的文件或符号链接）
+    fn ensure_in_sysroot(&self, path: &Path, follow_last: bool) -> Result<(), ExecError> {
+        let err = |msg: String| {
+            ExecError::Install(format!(
+                "Task {}: {}",
+                self.entity.task().name_version(),
+                msg
+            ))
+        };
+        let sysroot = self.dragonos_sysroot.canonicalize().map_err(|e| {
+            err(format!(
+                "Failed to resolve sysroot {}: {}",
+                self.dragonos_sysroot.display(),
+                e
+            ))
+        })?;
+        let checked = match path.parent() {
+            Some(parent) if !follow_last => parent,
+            _ => path,
+        };
+        let existing = match checked.ancestors().find(|p| p.symlink_metadata().is_ok()) {
+            Some(existing) => existing,
+            None => return Ok(()),
+        };
+        let resolved = existing.canonicalize().map_err(|e| {
+            err(format!(
+                "Failed to resolve {} in the sysroot: {}",
+                self.dragonos_path(existing).display(),
+                e
+            ))
+        })?;
+        if !resolved.starts_with(&sysroot) {
+            return Err(err(format!(
+                "{} resolves to {} on the host, which is outside the sysroot {}, refusing to install through a symlink that escapes the sysroot",
+                self.dragonos_path(path).display(),
+                resolved.display(),
+                sysroot.display()
+            )));
+        }
+        return Ok(());
+    }
+
+    /// # 检查把`src`目录拷贝到`install_path`时写入的每一个路径都在sysroot中
+    ///
+    /// 拷贝普通文件时会写入已有的符号链接指向的文件，原样重建符号链接时则会替换已有的符号链接
+    fn ensure_copy_in_sysroot(
+        &self,
+        src: &Path,
+        install_path: &Path,
+        follow_symlinks: bool,
+    ) -> Result<(), ExecError> {
+        self.ensure_in_sysroot(install_path, true)?;
+        let files = FileUtils::list_files_recursive(src).map_err(|e| {
+            ExecError::Install(format!("Failed to list files in {}: {}", src.display(), e))
+        })?;
+        for file in files {
+            let replaces_link = !follow_symlinks && file.is_symlink();
+            self.ensure_in_sysroot(
+                &install_path.join(file.strip_prefix(src).unwrap()),
+                !replaces_link,
+            )?;
+        }
+        return Ok(());
+    }
+
+    /// # 把整个构建结果目录拷贝到`in_dragonos_path`
+    ///
+    /// 返回安装的文件和符号链接在sysroot中的路径
+    fn install_dir(
+        &self,
+        build_dir: &Path,
+        in_dragonos_path: &Path,
+        is_kernel_module: bool,
+    ) -> Result<Vec<PathBuf>, ExecError> {
+        let binding = self.entity.task();
+        // 拼接最终的安装路径
+        let install_path = self.sysroot_path(in_dragonos_path, true)?;
+        debug!("install_path: {:?}", install_path);
+        if is_kernel_module {
+            self.ensure_in_sysroot(&install_path, true)?;
+        } else {
+            self.ensure_copy_in_sysroot(build_dir, &install_path, binding.install.follow_symlinks)?;
+        }
+        // 创建安装路径
+        std::fs::create_dir_all(&install_path).map_err(|e| {
+            ExecError::Install(format!("Failed to create install path: {}", e.to_string()))
+        })?;
+
+        // 拷贝构建结果到安装路径
+        if is_kernel_module {
+            return Self::install_kernel_modules(build_dir, &install_path);
+        } else {
+            if !binding.install.follow_symlinks {
+                Self::warn_dangling_symlinks(build_dir);
+            }
+            let files = binding
+                .install
+                .install_filter()
+                .and_then(|filter| {
+                    Self::copy_install_dir(
+                        &filter,
+                        binding.install.follow_symlinks,
+                        build_dir,
+                        &install_path,
+                    )
+                })
+                .and_then(|files| {
+                    // 在剥离符号、压缩文档改变文件内容之前，校验拷贝的文件
+                    verify::verify_dir(
+                        build_dir,
+                        &install_path,
+                        &files,
+                        binding.install.follow_symlinks,
+                    )?;
+                    Ok(files)
+                })
+                .map_err(|e| {
+                    ExecError::Install(format!(
+                        "Failed to install task {}: {}",
+                        binding.name_version(),
+                        e
+                    ))
+                })?;
+            let files = self.apply_docs_policy(&binding.install, files, &install_path)?;
+            self.strip_installed(&binding.install, &files, &install_path)?;
+            Self::apply_install_modes(&binding.install, &files, &install_path)?;
+            return Ok(files.iter().map(|f| install_path.join(f)).collect());
+        }
+    }
+
+    /// # 按顺序把`install.destinations`中的子目录安装到各自的目录
+    fn install_destinations(
+        &self,
+        build_dir: &Path,
+        is_kernel_module: bool,
+    ) -> Result<Vec<PathBuf>, ExecError> {
+        let binding = self.entity.task();
+        let mut installed = Vec::new();
+        for (i, dest) in binding.install.destinations.iter().enumerate() {
+            let src = build_dir.join(&dest.src_subdir);
+            if !src.is_dir() {
+                return Err(ExecError::Install(format!(
+                    "Task {}: install.destinations[{}] ({} -> {}): {} is not a directory in the build output",
+                    binding.name_version(),
+                    i,
+                    dest.src_subdir.display(),
+                    dest.in_dragonos_path.display(),
+                    src.display()
+                )));
+            }
+            installed.extend(self.install_dir(&src, &dest.in_dragonos_path, is_kernel_module)?);
+        }
+        return Ok(installed);
+    }
+
+    /// # 按`include`、`exclude`拷贝目录
+    ///
+    /// 返回拷贝了的文件（相对路径）
+    fn copy_install_dir(
+        filter: &InstallFilter,
+        follow_symlinks: bool,
+        src: &Path,
+        dst: &Path,
+    ) -> Result<Vec<PathBuf>, String> {
+        if filter.is_empty() {
+            FileUtils::copy_dir_with_symlinks(src, dst, follow_symlinks)?;
+            let files = FileUtils::list_files_recursive(src).map_err(|e| e.to_string())?;
+            return Ok(files
+                .iter()
+                .map(|f| f.strip_prefix(src).unwrap().to_path_buf())
+                .collect());
+        }
+        return FileUtils::copy_dir_filtered(src, dst, follow_symlinks, &|relative| {
+            filter.selects(relative)
+        });
+    }
+
+    /// # 按顺序安装`install.files`中的文件
+    ///
+    /// 源文件是目录时，递归地拷贝整个目录，`mode`应用到其中的每个普通文件
+    ///
+    /// 返回安装的文件在sysroot中的路径，以及设置了属主的文件（DragonOS内的路径）
+    fn install_files(
+        &self,
+        build_dir: &Path,
+    ) -> Result<(Vec<PathBuf>, BTreeMap<PathBuf, Ownership>), ExecError> {
+        let binding = self.entity.task();
+        let install = &binding.install;
+        let mut installed_files = Vec::new();
+        let mut owners = BTreeMap::new();
+        if install.files.is_empty() {
+            return Ok((installed_files, owners));
+        }
+        let strip_tool = self.strip_tool(install)?;
+        let can_chown = ownership::can_chown();
+        for (i, entry) in install.files.iter().enumerate() {
+            let err = |msg: String| {
+                ExecError::Install(format!(
+                    "Task {}: install.files[{}] ({} -> {}): {}",
+                    binding.name_version(),
+                    i,
+                    entry.src.display(),
+                    entry.dest.display(),
+                    msg
+                ))
+            };
+            let src = build_dir.join(&entry.src);
+            if src.symlink_metadata().is_err() {
+                return Err(err(format!(
+                    "source {} does not exist in the build output",
+                    src.display()
+                )));
+            }
+            let dest = self.sysroot_path(&entry.dest, false)?;
+            debug!("Install {} -> {}", src.display(), dest.display());
+            if src.is_dir() {
+                self.ensure_copy_in_sysroot(&src, &dest, install.follow_symlinks)?;
+            } else {
+                // 已有的文件或符号链接会先被删除
+                self.ensure_in_sysroot(&dest, false)?;
+            }
+            if let Some(parent) = dest.parent() {
+                std::fs::create_dir_all(parent).map_err(|e| err(e.to_string()))?;
+            }
+            let installed = if src.is_dir() {
+                std::fs::create_dir_all(&dest).map_err(|e| err(e.to_string()))?;
+                let filter = install.install_filter().map_err(err)?.exclude_only();
+                let files = Self::copy_install_dir(&filter, install.follow_symlinks, &src, &dest)
+                    .map_err(err)?;
+                verify::verify_dir(&src, &dest, &files, install.follow_symlinks).map_err(err)?;
+                files
+                    .iter()
+                    .map(|f| dest.join(f))
+                    .filter(|f| f.symlink_metadata().map_or(false, |m| m.is_file()))
+                    .collect()
+            } else {
+                if dest.symlink_metadata().is_ok() {
+                    std::fs::remove_file(&dest).map_err(|e| err(e.to_string()))?;
+                }
+                std::fs::copy(&src, &dest).map_err(|e| err(e.to_string()))?;
+                verify::verify_copy(&src, &dest, true).map_err(err)?;
+                vec![dest]
+            };
+            for file in installed {
+                if let Some(tool) = &strip_tool {
+                    self.strip_file(tool, &file, install)?;
+                }
+                let has_owner = entry.uid.is_some() || entry.gid.is_some();
+                // chown会清除setuid/setgid位，因此先修改属主再设置权限
+                if has_owner && can_chown {
+                    std::os::unix::fs::chown(&file, entry.uid, entry.gid)
+                        .map_err(|e| err(format!("Failed to chown {}: {}", file.display(), e)))?;
+                }
+                if let Some(mode) = entry.mode().map_err(err)? {
+                    std::fs::set_permissions(&file, std::fs::Permissions::from_mode(mode))
+                        .map_err(|e| err(e.to_string()))?;
+                }
+                if has_owner {
+                    let mode = file
+                        .symlink_metadata()
+                        .map_err(|e| err(e.to_string()))?
+                        .permissions()
+                        .mode()
+                        & 0o7777;
+                    let relative = file
+                        .strip_prefix(&self.dragonos_sysroot)
+                        .map_err(|_| err(format!("{} is outside the sysroot", file.display())))?;
+                    owners.insert(
+                        Path::new("/").join(relative),
+                        Ownership {
+                            mode,
+                            uid: entry.uid,
+                            gid: entry.gid,
+                        },
+                    );
+                }
+                installed_files.push(file);
+            }
+        }
+        if !owners.is_empty() && !can_chown {
+            let count = owners.len();
+            let db = OwnershipDb::record(&self.dragonos_sysroot, owners.clone())
+                .map_err(ExecError::Install)?;
+            warn!(
+                "Task {}: not running as root, ownership of {} installed file(s) was recorded in {} instead of applied; the rootfs image builder must apply it, otherwise the files keep the building user's uid/gid",
+                binding.name_version(),
+                count,
+                db.display()
+            );
+        }
+        return Ok((installed_files, owners));
+    }
+
+    /// # 按顺序创建`install.symlinks`中的符号链接
+    ///
+    /// 已经存在指向相同目标的符号链接时跳过。已经存在其他文件或符号链接时安装失败，
+    /// 设置了`force`时替换它（目录不会被替换）
+    ///
+    /// 返回符号链接在sysroot中的路径
+    fn install_symlinks(&self) -> Result<Vec<PathBuf>, ExecError> {
+        let binding = self.entity.task();
+        let mut links = Vec::new();
+        for (i, symlink) in binding.install.symlinks.iter().enumerate() {
+            let err = |msg: String| {
+                ExecError::Install(format!(
+                    "Task {}: install.symlinks[{}] ({} -> {}): {}",
+                    binding.name_version(),
+                    i,
+                    symlink.link.display(),
+                    symlink.target.display(),
+                    msg
+                ))
+            };
+            let link = self.sysroot_path(&symlink.link, false)?;
+            self.ensure_in_sysroot(&link, false)?;
+            let target = PathBuf::from(self.interpolate(&symlink.target.to_string_lossy())?);
+            if let Ok(metadata) = link.symlink_metadata() {
+                if metadata.file_type().is_symlink()
+                    && std::fs::read_link(&link).ok() == Some(target.clone())
+                {
+                    debug!("Symlink {} -> {} exists", link.display(), target.display());
+                    links.push(link);
+                    continue;
+                }
+                if metadata.is_dir() {
+                    return Err(err(format!("{} is a directory", link.display())));
+                }
+                if !symlink.force {
+                    return Err(err(format!(
+                        "{} already exists, set `force: true` to replace it",
+                        link.display()
+                    )));
+                }
+                warn!(
+                    "Task {}: replacing {} with a symlink to {}",
+                    binding.name_version(),
+                    link.display(),
+                    target.display()
+                );
+                std::fs::remove_file(&link).map_err(|e| err(e.to_string()))?;
+            }
+            if let Some(parent) = link.parent() {
+                std::fs::create_dir_all(parent).map_err(|e| err(e.to_string()))?;
+            }
+            debug!("Create symlink {} -> {}", link.display(), target.display());
+            std::os::unix::fs::symlink(&target, &link).map_err(|e| err(e.to_string()))?;
+            links.push(link);
+        }
+        return Ok(links);
+    }
+
+    /// 原样重建符号链接时，失效的符号链接也会被安装，输出警告
+    fn warn_dangling_symlinks(build_dir: &Path) {
+        if let Ok(dangling) = FileUtils::dangling_symlinks(build_dir) {
+            for (link, target) in dangling {
+                warn!(
+                    "Installing dangling symlink {} -> {}",
+                    link.display(),
+                    target.display()
+                );
+            }
+        }
+    }
+
+    /// # 按`install.docs`、`install.locales`处理安装的文档和翻译文件
+    ///
+    /// 返回处理后的文件列表（相对于安装路径），减少的字节数累加到`docs_saved_bytes`中
+    fn apply_docs_policy(
+        &self,
+        install: &InstallConfig,
+        files: Vec<PathBuf>,
+        install_path: &Path,
+    ) -> Result<Vec<PathBuf>, ExecError> {
+        let (policy, locales) = docs::effective_policy(install);
+        if policy == DocsPolicy::Keep && locales.is_none() {
+            return Ok(files);
+        }
+        let (files, saved) = docs::apply(
+            policy,
+            locales.as_deref(),
+            files,
+            install_path,
+            &self.dragonos_sysroot,
+        )
+        .map_err(|e| {
+            ExecError::Install(format!("Task {}: {}", self.entity.task().name_version(), e))
+        })?;
+        self.docs_saved_bytes
+            .set(self.docs_saved_bytes.get() + saved);
+        return Ok(files);
+    }
+
+    /// # 剥离安装的ELF文件中的符号
+    ///
+    /// 只处理从构建结果中安装的ELF可执行文件和共享库（`files`为相对于安装路径的路径），构建缓存中的文件保持不变
+    fn strip_installed(
+        &self,
+        install: &InstallConfig,
+        files: &[PathBuf],
+        install_path: &Path,
+    ) -> Result<(), ExecError> {
+        let tool = match self.strip_tool(install)? {
+            Some(tool) => tool,
+            None => return Ok(()),
+        };
+        for file in files {
+            let installed = install_path.join(file);
+            let is_file = installed
+                .symlink_metadata()
+                .map(|m| m.file_type().is_file())
+                .unwrap_or(false);
+            if !is_file {
+                continue;
+            }
+            self.strip_file(&tool, &installed, install)?;
+        }
+        return Ok(());
+    }
+
+    /// # 安装时使用的strip工具
+    ///
+    /// 没有开启`strip`时返回None。找不到工具时输出警告并返回None，设置了`strict_strip`时报错
+    fn strip_tool(&self, install: &InstallConfig) -> Result<Option<PathBuf>, ExecError> {
+        if !install.strip.is_enabled() {
+            return Ok(None);
+        }
+        let arch = self.target_arch();
+        let configured = {
+            let env_list = ENV_LIST.read().unwrap();
+            self.local_envs
+                .get(strip::STRIP_ENV_KEY)
+                .or_else(|| env_list.get(strip::STRIP_ENV_KEY))
+                .map(|v| v.value.clone())
+        };
+        let tool = strip::find_strip_tool(arch, configured);
+        if tool.is_none() {
+            let msg = format!(
+                "Task {}: no strip tool for {:?} is found",
+                self.entity.task().name_version(),
+                arch
+            );
+            if install.strict_strip {
+                return Err(ExecError::Install(msg));
+            }
+            warn!("{}, binaries are installed unstripped", msg);
+        }
+        return Ok(tool);
+    }
+
+    /// # 剥离一个已安装的文件中的符号
+    ///
+    /// 不是ELF可执行文件或共享库的文件保持不变。剥离失败时输出警告，设置了`strict_strip`时报错。
+    /// 减少的字节数累加到`stripped_bytes`中
+    fn strip_file(
+        &self,
+        tool: &Path,
+        file: &Path,
+        install: &InstallConfig,
+    ) -> Result<(), ExecError> {
+        if !strip::is_strippable_elf(file).unwrap_or(false) {
+            return Ok(());
+        }
+        debug!("Strip {} with {}", file.display(), tool.display());
+        let size = |f: &Path| std::fs::metadata(f).map(|m| m.len()).unwrap_or(0);
+        let before = size(file);
+        let r = match Command::new(tool)
+            .args(install.strip.args())
+            .arg(file)
+            .output()
+        {
+            Ok(output) if output.status.success() => Ok(()),
+            Ok(output) => Err(String::from_utf8_lossy(&output.stderr).trim().to_string()),
+            Err(e) => Err(format!("failed to run {}: {}", tool.display(), e)),
+        };
+        if let Err(e) = r {
+            let msg = format!("Failed to strip {}: {}", file.display(), e);
+            if install.strict_strip {
+                return Err(ExecError::Install(msg));
+            }
+            warn!("{}", msg);
+            return Ok(());
+        }
+        let saved = before.saturating_sub(size(file));
+        self.stripped_bytes.set(self.stripped_bytes.get() + saved);
+        return Ok(());
+    }
+
+    /// # 设置安装的文件的权限
+    ///
+    /// 只处理从构建结果中安装的普通文件（不包括目录和符号链接），不会影响安装目录中其他任务的文件
+    fn apply_install_modes(
+        install: &InstallConfig,
+        files: &[PathBuf],
+        install_path: &Path,
+    ) -> Result<(), ExecError> {
+        let (default_mode, rules) = install.file_modes().map_err(ExecError::Install)?;
+        if default_mode.is_none() && rules.is_empty() {
+            return Ok(());
+        }
+        for relative in files {
+            let installed = install_path.join(relative);
+            let is_file = installed
+                .symlink_metadata()
+                .map(|m| m.file_type().is_file())
+                .unwrap_or(false);
+            if !is_file {
+                continue;
+            }
+            let mode = rules
+                .iter()
+                .find(|(matcher, _)| matcher.is_ignored(relative))
+                .map(|(_, mode)| *mode)
+                .or(default_mode);
+            if let Some(mode) = mode {
+                debug!("Set mode {:o} on {}", mode, installed.display());
+                std::fs::set_permissions(&installed, std::fs::Permissions::from_mode(mode))
+                    .map_err(|e| {
+                        ExecError::Install(format!(
+                            "Failed to set mode {:o} on {}: {}",
+                            mode,
+                            installed.display(),
+                            e
+                        ))
+                    })?;
+            }
+        }
+        return Ok(());
+    }
+
+    /// # 安装内核模块
+    ///
+    /// 把构建结果目录中（包括子目录中）的所有`.ko`文件拷贝到安装路径下
+    fn install_kernel_modules(
+        build_dir: &Path,
+        install_path: &Path,
+    ) -> Result<Vec<PathBuf>, ExecError> {
+        let modules = FileUtils::list_files_recursive(build_dir)
+            .map_err(|e| ExecError::Install(e.to_string()))?
+            .into_iter()
+            .filter(|f| f.extension().map_or(false, |ext| ext == "ko"))
+            .collect::<Vec<_>>();
+        if modules.is_empty() {
+            warn!(
+                "No kernel module (*.ko) found in build result: {}",
+                build_dir.display()
+            );
+        }
+        let mut installed = Vec::new();
+        for module in modules {
+            let dst = install_path.join(module.file_name().unwrap());
+            std::fs::copy(&module, &dst)
+                .map_err(|e| e.to_string())
+                .and_then(|_| verify::verify_copy(&module, &dst, true))
+                .map_err(|e| {
+                    ExecError::Install(format!(
+                        "Failed to install kernel module {}: {}",
+                        module.display(),
+                        e
+                    ))
+                })?;
+            installed.push(dst);
+        }
+        return Ok(installed);
+    }
+
+    /// # 检查构建内核模块所需的环境
+    ///
+    /// - 需要指定内核源码目录（`DADK_KERNEL_SRC`）
+    /// - 内核的目标架构（`DADK_KERNEL_ARCH`）需要在任务支持的目标架构中
+    fn check_kernel_module_env(&self, env_list: &EnvMap) -> Result<(), ExecError> {
+        let task = self.entity.task();
+        if env_list.get(KERNEL_SRC_ENV_KEY).is_none() {
+            return Err(ExecError::Validation(format!(
+                "Task {} is a kernel module, but the kernel source directory is not specified, use `--kernel-src` to specify it",
+                task.name_version()
+            )));
+        }
+        let kernel_arch = env_list
+            .get(KERNEL_ARCH_ENV_KEY)
+            .map(|v| TargetArch::try_from(v.value.as_str()))
+            .transpose()
+            .map_err(ExecError::Validation)?
+            .unwrap_or_else(DADKTask::default_target_arch);
+        if !task.target_arch.contains(&kernel_arch) {
+            return Err(ExecError::Validation(format!(
+                "Task {}: kernel arch {:?} is not in the target arch list {:?} of the task",
+                task.name_version(),
+                kernel_arch,
+                task.target_arch
+            )));
+        }
+        return Ok(());
+    }
+
+    /// # 按清理级别清理任务
+    ///
+    /// 先在源码目录中执行各级别的清理命令，再删除属于各级别、需要由DADK删除的目录，返回删除的字节数
+    fn clean(&mut self) -> Result<u64, ExecError> {
+        let level = self.clean_level();
+        // 用户显式请求清理，但没有任何可以清理的内容时，需要告知用户
+        self.check_clean_target(level)?;
+
+        info!(
+            "Cleaning task: {}, level={level}",
+            self.entity.task().name_version()
+        );
+
+        let clean = self.entity.task().clean.clone();
+        self.command_timeout = clean.timeout.map(Duration::from_secs);
+        let mut r = self.clean_commands();
+        self.command_timeout = None;
+        if let (Err(e), CleanFailurePolicy::Ignore) = (&r, clean.on_failure) {
+            // 忽略清理命令的失败，仍然删除属于各级别的目录
+            warn!(
+                "Task {}: ignoring failed clean command: {:?}",
+                self.entity.task().name_version(),
+                e
+            );
+            r = Ok(());
+        }
+        let r = r.and_then(|_| self.clean_dirs(level));
+        if let Err(e) = &r {
+            error!(
+                "Failed to clean task: {}, error message: {:?}",
+                self.entity.task().name_version(),
+                e
+            );
+        }
+        return r;
+    }
+
+    fn clean_level(&self) -> CleanLevel {
+        if let Action::Clean(arg) = &self.action {
+            return arg.level();
+        }
+        panic!(
+            "BUG: clean() called with non-clean action. executor details: {:?}",
+            self
+        );
+    }
+
+    /// # 计算清理计划
+    ///
+    /// 按与`clean()`相同的规则列出要执行的清理命令和要删除的路径，不执行命令，也不删除任何文件。
+    /// 计算过程中的错误记录在计划的`errors`中
+    pub fn clean_plan(&self) -> CleanPlan {
+        let mut plan = CleanPlan::new(self.entity.task().name_version());
+        if let Err(e) = self.plan_clean(&mut plan) {
+            plan.errors.push(format!("{:?}", e));
+        }
+        return plan;
+    }
+
+    fn plan_clean(&self, plan: &mut CleanPlan) -> Result<(), ExecError> {
+        if self.entity.task().task_type == TaskType::Meta {
+            return Ok(());
+        }
+        let level = self.clean_level();
+        for raw_cmd in self.raw_commands() {
+            plan.commands.push(self.interpolate(&raw_cmd)?);
+        }
+        if !plan.commands.is_empty() {
+            plan.work_dir = Some(self.src_work_dir());
+        }
+        for l in level.included() {
+            if !self.entity.task().clean.remove_dirs(&l.to_string()) {
+                plan.kept.push(l);
+                continue;
+            }
+            for path in self.level_paths(l)? {
+                if path.symlink_metadata().is_err() {
+                    continue;
+                }
+                if let Err(e) = check_managed_path(&path) {
+                    plan.anomalies.push(e);
+                }
+                let bytes =
+                    FileUtils::disk_usage(&path).map_err(|e| ExecError::Clean(e.to_string()))?;
+                plan.removals.push(PlannedRemoval {
+                    level: l,
+                    path,
+                    bytes,
+                });
+            }
+        }
+        return Ok(());
+    }
+
+    /// # 检查在指定的清理级别下，任务是否有可以清理的内容
+    ///
+    /// 如果任务既没有清理命令，也没有DADK管理的产物（构建结果、源码缓存等）需要清理，
+    /// 则返回`ExecError::NothingToClean`，而不是静默地成功。
+    ///
+    /// 解析配置文件时的`validate()`不受影响，`clean_command`仍然是可选的。
+    fn check_clean_target(&self, level: CleanLevel) -> Result<(), ExecError> {
+        let has_clean_command = !self.raw_commands().is_empty();
+        let mut has_artifacts = false;
+        for l in level.included() {
+            if !self.entity.task().clean.remove_dirs(&l.to_string()) {
+                continue;
+            }
+            for path in self.level_paths(l)? {
+                let not_empty = match path.read_dir() {
+                    Ok(mut entries) => entries.next().is_some(),
+                    Err(_) => path.symlink_metadata().is_ok(),
+                };
+                has_artifacts |= not_empty;
+            }
+        }
+
+        if !has_clean_command && !has_artifacts {
+            return Err(ExecError::NothingToClean(format!(
+                "Task {}: nothing to clean (level={level}), no clean command and no DADK-managed artifacts to purge",
+                self.entity.task().name_version()
+            )));
+        }
+
+        return Ok(());
+    }
+
+    /// 在源文件目录执行各级别的清理命令
+    fn clean_commands(&self) -> Result<(), ExecError> {
+        let commands = self.create_commands()?;
+        if commands.is_empty() {
+            // 如果这里没有命令，则认为用户不需要在源文件目录执行清理
+            return Ok(());
+        }
+        info!(
+            "{}: Cleaning in source directory: {:?}",
+            self.entity.task().name_version(),
+            self.src_work_dir()
+        );
+
+        for (_, cmd) in commands {
+            self.run_command(cmd).map_err(|e| match e {
+                ExecError::Build(msg) => ExecError::Clean(msg),
+                e => e,
+            })?;
+        }
+        return Ok(());
+    }
+
+    /// # 删除属于各清理级别的目录
+    ///
+    /// 跳过指定了清理命令、并且没有设置`remove_dirs`的级别，返回删除的字节数，并输出每个级别删除的字节数
+    fn clean_dirs(&self, level: CleanLevel) -> Result<u64, ExecError> {
+        let task = self.entity.task();
+        let mut freed = 0;
+        let mut summary = Vec::new();
+        for l in level.included() {
+            if !task.clean.remove_dirs(&l.to_string()) {
+                summary.push(format!("{}: kept", l));
+                continue;
+            }
+            let mut bytes = 0;
+            for path in self.level_paths(l)? {
+                // 拒绝删除解析到DADK管理的目录之外的路径
+                check_managed_path(&path).map_err(ExecError::Clean)?;
+                bytes +=
+                    FileUtils::disk_usage(&path).map_err(|e| ExecError::Clean(e.to_string()))?;
+                debug!("{}: Removing {}", task.name_version(), path.display());
+                let r = if path.is_dir() && !path.is_symlink() {
+                    std::fs::remove_dir_all(&path)
+                } else {
+                    std::fs::remove_file(&path)
+                };
+                match r {
+                    Ok(_) => {}
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                    Err(e) => {
+                        return Err(ExecError::Clean(format!(
+                            "Failed to remove {}: {}",
+                            path.display(),
+                            e
+                        )))
+                    }
+                }
+            }
+            summary.push(format!("{}: {} bytes", l, bytes));
+            freed += bytes;
+        }
+        info!(
+            "Task {}: {} bytes freed (level={}; {})",
+            task.name_version(),
+            freed,
+            level,
+            summary.join(", ")
+        );
+        return Ok(freed);
+    }
+
+    /// # 属于某个清理级别的路径
+    ///
+    /// - `output`：构建结果目录
+    /// - `src`：源码缓存目录，以及叠加源的各层（本地源码不会被删除）
+    /// - `cache`：任务最近一次拉取的OCI镜像展开后的文件系统
+    /// - `all`：任务数据目录中的文件（执行记录）以及之前的输出日志
+    fn level_paths(&self, level: CleanLevel) -> Result<Vec<PathBuf>, ExecError> {
+        let io_err = |e: std::io::Error| ExecError::Clean(e.to_string());
+        let mut paths = Vec::new();
+        match level {
+            CleanLevel::Output => paths.push(self.build_dir.path.clone()),
+            CleanLevel::Src => {
+                if let Some(source_dir) = &self.source_dir {
+                    paths.push(source_dir.path.clone());
+                    paths.push(source_dir.overlay_layers_root());
+                }
+            }
+            CleanLevel::Cache => {
+                if let Some(digest) = self.task_log().oci_digest() {
+                    paths.extend(OciPuller::cached_rootfs_dirs(digest));
+                }
+            }
+            CleanLevel::All => {
+                for entry in self.task_data_dir.path().read_dir().map_err(io_err)? {
+                    paths.push(entry.map_err(io_err)?.path());
+                }
+                let current = self.output_log.as_ref().map(|l| l.path().to_path_buf());
+                paths.extend(
+                    OutputLog::previous_logs(&self.entity.task().name_version())
+                        .map_err(io_err)?
+                        .into_iter()
+                        .filter(|p| Some(p) != current.as_ref()),
+                );
+            }
+        }
+        paths.sort();
+        return Ok(paths);
+    }
+
+    /// 获取源文件的工作目录
+    fn src_work_dir(&self) -> PathBuf {
+        if let Some(local_path) = self.entity.task().source_path() {
+            return local_path;
+        }
+        return self.source_dir.as_ref().unwrap().path.clone();
+    }
+
+    fn task_log(&self) -> TaskLog {
+        return self.task_data_dir.task_log();
+    }
+
+    /// # 计算`rerun_if_changed`中每个路径的哈希值
+    ///
+    /// 路径相对于任务配置文件所在的目录，不存在的路径的哈希值为空字符串
+    fn watched_hashes(&self) -> BTreeMap<String, String> {
+        return Self::watched_hashes_of(&self.entity);
+    }
+
+    fn watched_hashes_of(entity: &SchedEntity) -> BTreeMap<String, String> {
+        let file_path = entity.file_path();
+        let base = file_path.parent().unwrap_or(Path::new(""));
+        return entity
+            .task()
+            .rerun_if_changed
+            .iter()
+            .map(|watched| {
+                let path = base.join(watched);
+                let hash = match path.symlink_metadata() {
+                    Ok(_) => HashUtils::hash_dir(&path, &IgnoreMatcher::empty())
+                        .unwrap_or_else(|e| format!("error: {}", e)),
+                    Err(_) => String::new(),
+                };
+                (watched.to_string_lossy().to_string(), hash)
+            })
+            .collect();
+    }
+
+    /// 任务的Git源（构建或预编译）
+    fn git_source(&self) -> Option<GitSource> {
+        return match self.entity.task().task_type {
+            TaskType::BuildFromSource(CodeSource::Git(git))
+            | TaskType::KernelModule(CodeSource::Git(git))
+            | TaskType::InstallFromPrebuilt(PrebuiltSource::Git(git)) => Some(git),
+            _ => None,
+        };
+    }
+
+    /// 源码缓存目录中检出的Git提交，不是Git源或者无法解析时为None
+    fn built_commit(&self) -> Option<String> {
+        let git = self.git_source()?;
+        return git.resolved_commit(self.source_dir.as_ref()?).ok();
+    }
+
+    /// # 与上一次构建成功时相比，Git源检出的提交是否改变
+    ///
+    /// 先拉取并检出配置的`revision`或`branch`，因此修改`revision`或分支有新的提交时都会重新构建。
+    /// 返回改变的说明，不是Git源或者提交没有改变时返回None
+    fn git_commit_changed(&self) -> Result<Option<String>, ExecError> {
+        let (git, source_dir) = match (self.git_source(), self.source_dir.as_ref()) {
+            (Some(git), Some(source_dir)) => (git, source_dir),
+            _ => return Ok(None),
+        };
+        git.prepare(source_dir, self.progress.as_ref())
+            .map_err(ExecError::Fetch)?;
+        let commit = git.resolved_commit(source_dir).map_err(ExecError::Fetch)?;
+        return Ok(match self.task_log().built_commit() {
+            Some(old) if *old == commit => None,
+            Some(old) => Some(format!("git commit changed ({} -> {})", old, commit)),
+            None => Some(format!(
+                "git commit of the last build is unknown (now {})",
+                commit
+            )),
+        });
+    }
+
+    /// 与上一次构建成功时相比，继承的全局环境变量是否改变，返回改变的说明
+    fn global_envs_changed(&self) -> Option<String> {
+        let hash = self.entity.task().global_envs_hash();
+        if self.task_log().global_envs_hash() == hash.as_ref() {
+            return None;
+        }
+        return Some("global envs changed".to_string());
+    }
+
+    /// # 与上一次构建成功时相比，内容改变了的`rerun_if_changed`路径
+    ///
+    /// 返回的每一项包括路径以及改变的方式。之前不存在、现在仍然不存在的路径不算改变
+    fn dirty_watched_paths(&self) -> Vec<String> {
+        let recorded = self
+            .task_log()
+            .rerun_if_changed()
+            .cloned()
+            .unwrap_or_default();
+        return self
+            .watched_hashes()
+            .into_iter()
+            .filter_map(|(path, hash)| {
+                let reason = match recorded.get(&path) {
+                    None => "not recorded",
+                    Some(old) if *old == hash => return None,
+                    Some(old) if old.is_empty() => "created",
+                    Some(_) if hash.is_empty() => "removed",
+                    Some(_) => "modified",
+                };
+                Some(format!("{} ({})", path, reason))
+            })
+            .collect();
+    }
+
+    /// 获取任务在当前操作下要执行的命令
+    fn raw_commands(&self) -> Vec<String> {
+        let task = self.entity.task();
+        let build_commands = || {
+            task.build
+                .build_command
+                .as_ref()
+                .map(|c| c.commands())
+                .unwrap_or_default()
+        };
+        // 清理到某个级别时，按从低到高的顺序执行各级别的清理命令
+        let clean_commands = |level: CleanLevel| -> Vec<String> {
+            level
+                .included()
+                .filter_map(|l| task.clean.command(&l.to_string()).cloned())
+                .collect()
+        };
+        return match task.task_type {
+            TaskType::BuildFromSource(_) | TaskType::KernelModule(_) | TaskType::NoSource => {
+                match &self.action {
+                    Action::Build => build_commands(),
+                    Action::Clean(arg) => clean_commands(arg.level()),
+                    _ => unimplemented!(
+                        "create_command: Action {:?} not supported yet.",
+                        self.action
+                    ),
+                }
+            }
+
+            TaskType::InstallFromPrebuilt(_) => match &self.action {
+                Action::Build => build_commands(),
+                Action::Clean(arg) => clean_commands(arg.level()),
+                _ => unimplemented!(
+                    "create_command: Action {:?} not supported yet.",
+                    self.action
+                ),
+            },
+
+            TaskType::Meta => Vec::new(),
+        };
+    }
+
+    /// # 替换配置中的`${变量}`
+    ///
+    /// 支持的变量见[`interpolate::VARIABLES`]
+    fn interpolate(&self, s: &str) -> Result<String, ExecError> {
+        let task = self.entity.task();
+        return self
+            .interpolate_str(s)
+            .map_err(|e| ExecError::Validation(format!("Task {}: {}", task.name_version(), e)));
+    }
+
+    fn interpolate_str(&self, s: &str) -> Result<String, String> {
+        let task = self.entity.task();
+        let lookup = |name: &str| -> Result<String, String> {
+            if let Some(dep) = name.strip_prefix(interpolate::DEP_PREFIX) {
+                return self.dependency_build_dir(dep);
+            }
+            return Ok(match name {
+                "ARCH" => {
+                    let arch: &str = self.target_arch().into();
+                    arch.to_string()
+                }
+                "NAME" => task.name.clone(),
+                "VERSION" => task.version.clone(),
+                "NAME_VERSION" => task.name_version(),
+                "DADK_CACHE_ROOT" => CACHE_ROOT.get().to_string_lossy().to_string(),
+                "DADK_BUILD_DIR" => self.build_work_dir().to_string_lossy().to_string(),
+                "DADK_CURRENT_BUILD_DIR" => self.build_dir.path.to_string_lossy().to_string(),
+                "PROFILE" => task.active_profile.clone().unwrap_or_default(),
+                "DADK_SYSROOT" => self.dragonos_sysroot.to_string_lossy().to_string(),
+                _ => unreachable!("unknown variable {} passed validation", name),
+            });
+        };
+        return interpolate::interpolate(s, &lookup);
+    }
+
+    /// # `${dep:名称}`：`depends`中同名的依赖的构建结果目录
+    ///
+    /// 从全局环境变量中的`DADK_BUILD_CACHE_DIR_*`查找
+    fn dependency_build_dir(&self, name: &str) -> Result<String, String> {
+        let task = self.entity.task();
+        let dep = task
+            .depends
+            .iter()
+            .find(|d| d.is_task() && d.name == name)
+            .ok_or_else(|| {
+                format!(
+                    "${{dep:{}}}: {} is not a dependency of the task",
+                    name, name
+                )
+            })?;
+        return Self::build_dir_of_dependency(dep)
+            .ok_or_else(|| format!("${{dep:{}}}: build directory of {} is unknown", name, name));
+    }
+
+    /// 从全局环境变量中的`DADK_BUILD_CACHE_DIR_*`查找依赖的构建结果目录
+    fn build_dir_of_dependency(dep: &Dependency) -> Option<String> {
+        let key = CacheDir::build_dir_env_key_of(&dep.name, &dep.version, dep.arch);
+        return ENV_LIST.read().unwrap().get(&key).map(|v| v.value.clone());
+    }
+
+    /// # 替换任务环境变量的值中的`${变量}`
+    ///
+    /// 出错时的错误信息中包括环境变量名和定义它的配置文件
+    fn interpolate_env(&self, env: &TaskEnv, origin: EnvOrigin) -> Result<String, ExecError> {
+        return self.interpolate_str(env.value()).map_err(|e| {
+            let defined_in = match origin {
+                EnvOrigin::Global => format!("global_envs in {}", WORKSPACE_CONFIG_FILE_NAME),
+                _ => format!("config file {}", self.entity.file_path().display()),
+            };
+            ExecError::Validation(format!(
+                "Task {}: failed to expand env {} ({}): {}",
+                self.entity.task().name_version(),
+                env.key(),
+                defined_in,
+                e
+            ))
+        });
+    }
+
+    /// 为任务创建命令
+    ///
+    /// 返回每一条命令的原始字符串以及对应的`Command`，构建命令可能有多条，需要按顺序执行
+    fn create_commands(&self) -> Result<Vec<(String, Command)>, ExecError> {
+        let mut commands = Vec::new();
+        for raw_cmd in self.raw_commands() {
+            let raw_cmd = self.interpolate(&raw_cmd)?;
+            let command = self.create_command(&raw_cmd)?;
+            commands.push((raw_cmd, command));
+        }
+        if let (&Action::Build, Some(cargo)) = (&self.action, &self.entity.task().build.cargo) {
+            let args = self.cargo_args(cargo);
+            let mut command = Command::new("cargo");
+            command.args(&args);
+            commands.push((
+                format!("cargo {}", args.join(" ")),
+                self.setup_command(command),
+            ));
+        }
+        if let (&Action::Build, Some(cmake)) = (&self.action, &self.entity.task().build.cmake) {
+            for args in self.cmake_args(cmake) {
+                let mut command = Command::new("cmake");
+                command.args(&args);
+                let mut command = self.setup_command(command);
+                // DESTDIR只影响安装步骤，把结果安装到构建结果目录
+                command.env("DESTDIR", &self.build_dir.path);
+                commands.push((format!("cmake {}", args.join(" ")), command));
+            }
+        }
+        if let (&Action::Build, Some(autotools)) =
+            (&self.action, &self.entity.task().build.autotools)
+        {
+            for (program, display, args) in self.autotools_steps(autotools) {
+                let mut command = Command::new(program);
+                command.args(&args);
+                commands.push((
+                    format!("{} {}", display, args.join(" ")),
+                    self.setup_command(command),
+                ));
+            }
+        }
+        return Ok(commands);
+    }
+
+    /// 为一条命令创建`Command`，在源文件的工作目录中执行，并设置好环境变量
+    fn create_command(&self, raw_cmd: &str) -> Result<Command, ExecError> {
+        let mut command = Command::new("bash");
+
+        // 设置参数
+        command.arg("-c");
+        command.arg(raw_cmd);
+
+        return Ok(self.setup_command(command));
+    }
+
+    /// 设置命令的工作目录和环境变量
+    fn setup_command(&self, mut command: Command) -> Command {
+        // 构建命令在配置的构建工作目录中执行，其他命令在源文件目录中执行
+        if let Action::Build = self.action {
+            command.current_dir(self.build_work_dir());
+        } else {
+            command.current_dir(self.src_work_dir());
+        }
+
+        // 设置环境变量
+        let env_list = ENV_LIST.read().unwrap();
         for (key, value) in env_list.envs.iter() {
             // if key.starts_with("DADK") {
             //     debug!("DADK env found: {}={}", key, value.value);
             // }
             command.env(key, value.value.clone());
         }
-        drop(env_list);
-        for (key, value) in self.local_envs.envs.iter() {
-            debug!("Local env found: {}={}", key, value.value);
-            command.env(key, value.value.clone());
+        drop(env_list);
+        for (key, value) in self.local_envs.envs.iter() {
+            debug!("Local env found: {}={}", key, value.display_value());
+            command.env(key, value.value.clone());
+        }
+
+        return command;
+    }
+
+    /// cargo的target目录，放在任务数据目录下，以便增量构建，并且不会被安装到DragonOS
+    fn cargo_target_dir(&self) -> PathBuf {
+        return self.task_data_dir.path().join("cargo_target");
+    }
+
+    /// 生成`cargo build`的参数
+    fn cargo_args(&self, cargo: &CargoBuild) -> Vec<String> {
+        let mut args = vec![
+            "build".to_string(),
+            "--profile".to_string(),
+            cargo.profile().to_string(),
+            "--target-dir".to_string(),
+            self.cargo_target_dir().to_string_lossy().to_string(),
+        ];
+        if let Some(target) = &self.entity.task().rust_target {
+            args.push("--target".to_string());
+            args.push(target.clone());
+        }
+        if !cargo.features.is_empty() {
+            args.push("--features".to_string());
+            args.push(cargo.features.join(","));
+        }
+        if cargo.no_default_features {
+            args.push("--no-default-features".to_string());
+        }
+        for member in cargo.workspace_members.iter() {
+            args.push("-p".to_string());
+            args.push(member.clone());
+        }
+        for bin in cargo.bins.iter() {
+            args.push("--bin".to_string());
+            args.push(bin.clone());
+        }
+        return args;
+    }
+
+    /// cmake的构建目录，放在任务数据目录下的固定位置，以便增量构建
+    fn cmake_build_dir(&self) -> PathBuf {
+        return self.task_data_dir.path().join("cmake_build");
+    }
+
+    /// 根据目标架构生成的cmake工具链文件的路径
+    fn cmake_toolchain_path(&self) -> PathBuf {
+        return self.task_data_dir.path().join("cmake_toolchain.cmake");
+    }
+
+    /// # 生成cmake工具链文件的内容
+    ///
+    /// 编译器优先使用任务或全局环境变量中的`CC`、`CXX`，
+    /// 否则使用目标架构对应的musl交叉编译工具链。
+    /// 查找库和头文件时只在DragonOS sysroot中查找。
+    fn cmake_toolchain(&self, arch: TargetArch) -> String {
+        let env_list = ENV_LIST.read().unwrap();
+        let compiler = |key: &str, suffix: &str| {
+            return self
+                .local_envs
+                .get(key)
+                .or_else(|| env_list.get(key))
+                .map(|v| v.value.clone())
+                .unwrap_or_else(|| format!("{}-{}", arch.cross_prefix(), suffix));
+        };
+        let processor: &str = arch.into();
+        let lines = [
+            "set(CMAKE_SYSTEM_NAME Linux)".to_string(),
+            format!("set(CMAKE_SYSTEM_PROCESSOR {})", processor),
+            format!("set(CMAKE_C_COMPILER {})", compiler("CC", "gcc")),
+            format!("set(CMAKE_CXX_COMPILER {})", compiler("CXX", "g++")),
+            format!(
+                "set(CMAKE_FIND_ROOT_PATH {})",
+                self.dragonos_sysroot.display()
+            ),
+            "set(CMAKE_FIND_ROOT_PATH_MODE_PROGRAM NEVER)".to_string(),
+            "set(CMAKE_FIND_ROOT_PATH_MODE_LIBRARY ONLY)".to_string(),
+            "set(CMAKE_FIND_ROOT_PATH_MODE_INCLUDE ONLY)".to_string(),
+        ];
+        return lines.join("\n") + "\n";
+    }
+
+    fn write_cmake_toolchain(&self) -> Result<(), ExecError> {
+        let path = self.cmake_toolchain_path();
+        std::fs::write(&path, self.cmake_toolchain(self.target_arch())).map_err(|e| {
+            ExecError::Validation(format!(
+                "Failed to write cmake toolchain file {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+        return Ok(());
+    }
+
+    /// 生成配置、构建、安装三个步骤的cmake参数
+    fn cmake_args(&self, cmake: &CmakeBuild) -> Vec<Vec<String>> {
+        let build_dir = self.cmake_build_dir().to_string_lossy().to_string();
+
+        let mut configure = vec![
+            // 命令在构建工作目录中执行，而构建工作目录可能是相对路径
+            "-S".to_string(),
+            ".".to_string(),
+            "-B".to_string(),
+            build_dir.clone(),
+            format!(
+                "-DCMAKE_TOOLCHAIN_FILE={}",
+                self.cmake_toolchain_path().display()
+            ),
+            format!("-DCMAKE_BUILD_TYPE={}", cmake.build_type()),
+            format!("-DCMAKE_INSTALL_PREFIX={}", cmake.install_prefix()),
+        ];
+        if let Some(generator) = &cmake.generator {
+            configure.push("-G".to_string());
+            configure.push(generator.clone());
+        }
+        for (key, value) in cmake.defines.iter() {
+            configure.push(format!("-D{}={}", key, value));
+        }
+
+        let mut build = vec![
+            "--build".to_string(),
+            build_dir.clone(),
+            "--config".to_string(),
+            cmake.build_type().to_string(),
+        ];
+        if let Some(jobs) = ENV_LIST.read().unwrap().get(JOBS_ENV_KEY) {
+            build.push("--parallel".to_string());
+            build.push(jobs.value.clone());
+        }
+
+        let install = vec![
+            "--install".to_string(),
+            build_dir,
+            "--config".to_string(),
+            cmake.build_type().to_string(),
+        ];
+        return vec![configure, build, install];
+    }
+
+    /// # 生成autotools构建的各个步骤
+    ///
+    /// 返回每一步要执行的程序、在日志中显示的程序名以及参数。
+    /// 源码目录中没有`configure`但有`configure.ac`时，先执行`autoreconf -fi`生成它
+    fn autotools_steps(&self, autotools: &AutotoolsBuild) -> Vec<(PathBuf, String, Vec<String>)> {
+        let work_dir = self.build_work_dir();
+        // 构建工作目录可能是相对路径，而程序的相对路径在设置了工作目录后含义不明确，因此使用绝对路径
+        let configure = std::env::current_dir()
+            .map(|cwd| cwd.join(&work_dir))
+            .unwrap_or(work_dir.clone())
+            .join("configure");
+        let mut steps = Vec::new();
+        if !configure.exists() && work_dir.join("configure.ac").exists() {
+            steps.push((
+                PathBuf::from("autoreconf"),
+                "autoreconf".to_string(),
+                vec!["-fi".to_string()],
+            ));
+        }
+
+        let mut configure_args = vec![
+            format!("--host={}", self.target_arch().cross_prefix()),
+            format!("--prefix={}", AutotoolsBuild::DEFAULT_PREFIX),
+        ];
+        configure_args.extend(autotools.configure_args.iter().cloned());
+        steps.push((configure, "./configure".to_string(), configure_args));
+
+        let mut make_args = Vec::new();
+        if let Some(jobs) = ENV_LIST.read().unwrap().get(JOBS_ENV_KEY) {
+            make_args.push(format!("-j{}", jobs.value));
+        }
+        make_args.extend(autotools.make_targets.iter().cloned());
+        steps.push((PathBuf::from("make"), "make".to_string(), make_args));
+
+        // 安装到构建结果目录，供安装步骤使用
+        let install_args = vec![
+            format!("DESTDIR={}", self.build_dir.path.display()),
+            autotools.install_target().to_string(),
+        ];
+        steps.push((PathBuf::from("make"), "make".to_string(), install_args));
+        return steps;
+    }
+
+    /// cargo生成二进制文件的目录
+    fn cargo_output_dir(&self, cargo: &CargoBuild) -> PathBuf {
+        let mut dir = self.cargo_target_dir();
+        if let Some(target) = &self.entity.task().rust_target {
+            // 使用json文件描述的target时，cargo以文件名（不含扩展名）作为目录名
+            let target = Path::new(target);
+            let name = match target.extension() {
+                Some(ext) if ext == "json" => target.file_stem().unwrap_or(target.as_os_str()),
+                _ => target.as_os_str(),
+            };
+            dir.push(name);
+        }
+        return dir.join(cargo.profile_dir());
+    }
+
+    /// # 把cargo生成的二进制文件拷贝到构建结果目录
+    ///
+    /// 指定了`bins`时只拷贝这些文件，缺少任意一个都会报错；
+    /// 否则拷贝输出目录下所有的可执行文件
+    fn stage_cargo_bins(&self, cargo: &CargoBuild) -> Result<(), ExecError> {
+        let output_dir = self.cargo_output_dir(cargo);
+        let bins: Vec<PathBuf> = if cargo.bins.is_empty() {
+            let entries = output_dir.read_dir().map_err(|e| {
+                ExecError::Build(format!(
+                    "Failed to read cargo output dir {}: {}",
+                    output_dir.display(),
+                    e
+                ))
+            })?;
+            let mut bins = Vec::new();
+            for entry in entries {
+                let path = entry.map_err(|e| ExecError::Build(e.to_string()))?.path();
+                let metadata = path
+                    .metadata()
+                    .map_err(|e| ExecError::Build(e.to_string()))?;
+                if metadata.is_file()
+                    && path.extension().is_none()
+                    && metadata.permissions().mode() & 0o111 != 0
+                {
+                    bins.push(path);
+                }
+            }
+            bins.sort();
+            bins
+        } else {
+            cargo.bins.iter().map(|b| output_dir.join(b)).collect()
+        };
+
+        for bin in bins.iter() {
+            if !bin.is_file() {
+                return Err(ExecError::Build(format!(
+                    "cargo binary {} not found",
+                    bin.display()
+                )));
+            }
+            let dest = self.build_dir.path.join(bin.file_name().unwrap());
+            info!(
+                "Task {}: staging {} to {}",
+                self.entity.task().name_version(),
+                bin.display(),
+                dest.display()
+            );
+            std::fs::copy(bin, &dest).map_err(|e| {
+                ExecError::Build(format!("Failed to copy {}: {}", bin.display(), e))
+            })?;
         }
+        return Ok(());
+    }
 
-        return Ok(Some(command));
+    /// # 当前的执行阶段
+    ///
+    /// 构建（包括构建前钩子）为`build`，安装（包括安装后钩子）为`install`，清理为`clean`。
+    /// 其他操作（例如`dadk info`）按构建阶段准备环境变量
+    fn phase(&self) -> &'static str {
+        return match self.action {
+            Action::Install(_) | Action::Uninstall(_) => "install",
+            Action::Clean(_) => "clean",
+            _ => "build",
+        };
     }
 
     /// # 准备工作线程本地环境变量
-    fn prepare_local_env(&mut self) -> Result<(), ExecutorError> {
+    ///
+    /// 构建、安装、清理的命令以及钩子都使用这里准备的环境变量。
+    /// 环境变量按`phases`过滤，只设置在当前阶段生效的
+    fn prepare_local_env(&mut self) -> Result<(), ExecError> {
+        // 重新准备时从头开始，避免`prepend`/`append`重复组合上一次的结果
+        self.local_envs = EnvMap::new();
         // 设置本地环境变量
         self.prepare_target_env()?;
+        self.prepare_jobs_env(&ENV_LIST.read().unwrap());
 
-        let binding = self.entity.task();
-        let task_envs: Option<&Vec<TaskEnv>> = binding.envs.as_ref();
+        self.prepare_task_info_env()?;
+        self.prepare_dependency_env()?;
 
-        if let Some(task_envs) = task_envs {
-            for tv in task_envs.iter() {
-                self.local_envs
-                    .add(EnvVar::new(tv.key().to_string(), tv.value().to_string()));
+        // 按优先级从低到高设置：工作区的环境变量文件、`global_envs`、任务的环境变量文件、任务的`envs`
+        let binding = self.entity.task();
+        let global_file_envs: Vec<TaskEnv> = self
+            .load_env_files(&binding.global_env_files)?
+            .into_iter()
+            .filter(|env| !binding.unset.contains(&env.key))
+            .collect();
+        let task_file_envs = self.load_env_files(&binding.env_files)?;
+        let layers = [
+            (EnvOrigin::EnvFile, global_file_envs.as_slice()),
+            (EnvOrigin::Global, binding.global_envs.as_slice()),
+            (EnvOrigin::EnvFile, task_file_envs.as_slice()),
+            (EnvOrigin::Task, binding.envs.as_deref().unwrap_or_default()),
+        ];
+        let phase = self.phase();
+        for (origin, envs) in layers {
+            for tv in envs.iter().filter(|tv| tv.applies_in_phase(phase)) {
+                // 环境变量文件中的值原样使用，不替换变量
+                let value = match origin {
+                    EnvOrigin::EnvFile => tv.value().to_string(),
+                    _ => self.interpolate_env(tv, origin)?,
+                };
+                // `prepend`/`append`与继承的值组合，组合出的值包含保密的值时也是保密的
+                let inherited = match tv.op.is_set() {
+                    true => None,
+                    false => self.inherited_env(tv.key()),
+                };
+                let secret = tv.secret || inherited.as_ref().is_some_and(|env| env.secret);
+                let value = tv.compose(value, inherited.as_ref().map(|env| env.value.as_str()));
+                self.local_envs.add(
+                    EnvVar::with_origin(tv.key().to_string(), value, origin).with_secret(secret),
+                );
             }
         }
 
@@ -431,95 +2723,645 @@ impl Executor {
         return Ok(());
     }
 
-    fn prepare_input(&self) -> Result<(), ExecutorError> {
+    /// 继承的环境变量：已经设置的本地环境变量，或者全局环境变量（包括执行DADK时的环境变量）
+    fn inherited_env(&self, key: &str) -> Option<EnvVar> {
+        if let Some(env) = self.local_envs.get(key) {
+            return Some(env.clone());
+        }
+        return ENV_LIST.read().unwrap().get(key).cloned();
+    }
+
+    /// # 在日志中记录组合出的环境变量
+    ///
+    /// 以`prepend`/`append`设置的环境变量的最终值取决于执行时的环境，写在日志的开头以便排查问题
+    fn log_composed_envs(&self) {
+        let log = match &self.output_log {
+            Some(log) => log,
+            None => return,
+        };
+        let task = self.entity.task();
+        let keys: BTreeSet<&str> = task
+            .global_envs
+            .iter()
+            .chain(task.envs.as_deref().unwrap_or_default())
+            .filter(|env| !env.op.is_set() && env.applies_in_phase(self.phase()))
+            .map(|env| env.key())
+            .collect();
+        for key in keys {
+            if let Some(env) = self.local_envs.get(key) {
+                log.message(&format!("env {}={}", key, env.display_value()));
+            }
+        }
+    }
+
+    /// # 任务执行时设置的环境变量
+    ///
+    /// 与执行任务时一样准备本地环境变量，按名称排序返回
+    pub fn effective_envs(&mut self) -> Result<Vec<EnvVar>, ExecError> {
+        self.prepare_local_env()?;
+        return Ok(self.local_envs.envs.values().cloned().collect());
+    }
+
+    /// # 加载环境变量文件
+    ///
+    /// 后面的文件中同名的环境变量覆盖前面的。不存在的文件在`optional`时被忽略，否则返回错误
+    fn load_env_files(&self, files: &[EnvFile]) -> Result<Vec<TaskEnv>, ExecError> {
+        let mut envs = Vec::new();
+        for file in files {
+            if !file.path.exists() {
+                if file.optional {
+                    debug!("Optional env file {} not found", file.path.display());
+                    continue;
+                }
+                return Err(ExecError::Validation(format!(
+                    "Task {}: env file {} does not exist (mark it optional to ignore it)",
+                    self.entity.task().name_version(),
+                    file.path.display()
+                )));
+            }
+            let loaded = dotenv::load(&file.path).map_err(|e| {
+                ExecError::Validation(format!("Task {}: {}", self.entity.task().name_version(), e))
+            })?;
+            envs.extend(loaded.into_iter().map(|env| TaskEnv {
+                secret: file.secret,
+                ..env
+            }));
+        }
+        return Ok(envs);
+    }
+
+    /// # 设置描述任务自身的环境变量
+    ///
+    /// 在任务的`envs`之前设置，任务中声明的同名环境变量会覆盖它们
+    fn prepare_task_info_env(&mut self) -> Result<(), ExecError> {
+        let task = self.entity.task();
+        let arch: &str = self.target_arch().into();
+        let mut envs = vec![
+            (TARGET_ARCH_ENV_KEY, arch.to_string()),
+            (PKG_NAME_ENV_KEY, task.name.clone()),
+            (PKG_VERSION_ENV_KEY, task.version.clone()),
+            (PKG_ID_ENV_KEY, task.name_version_env()),
+            (PHASE_ENV_KEY, self.phase().to_string()),
+        ];
+        if self.phase() == "install" {
+            // 只有安装阶段会修改sysroot
+            envs.push((
+                SYSROOT_ENV_KEY,
+                self.dragonos_sysroot.to_string_lossy().to_string(),
+            ));
+        }
+        if task.pinned_arch.is_some() {
+            // 按固定架构构建的依赖，覆盖全局的ARCH
+            envs.push(("ARCH", arch.to_string()));
+        }
+        let install_prefix = match &task.install.in_dragonos_path {
+            Some(path) => Some(self.interpolate(&path.to_string_lossy())?),
+            None if matches!(task.task_type, TaskType::KernelModule(_)) => {
+                Some(KERNEL_MODULE_INSTALL_PATH.to_string())
+            }
+            None => None,
+        };
+        if let Some(install_prefix) = install_prefix {
+            envs.push((INSTALL_PREFIX_ENV_KEY, install_prefix));
+        }
+        for (key, value) in envs {
+            self.local_envs.add(EnvVar::new(key.to_string(), value));
+        }
+        return Ok(());
+    }
+
+    /// # 导出依赖的构建结果目录
+    ///
+    /// 环境变量名由工作区配置的`dep_envs`和依赖的`env_alias`决定，见[`DADKTask::dependency_env_keys`]。
+    /// 两个依赖的环境变量名相同时返回错误
+    fn prepare_dependency_env(&mut self) -> Result<(), ExecError> {
+        let task = self.entity.task();
+        let keys = task
+            .dependency_env_keys()
+            .map_err(|e| ExecError::Validation(format!("Task {}: {}", task.name_version(), e)))?;
+        for (key, dep) in keys {
+            match Self::build_dir_of_dependency(dep) {
+                Some(value) => self.local_envs.add(EnvVar::new(key, value)),
+                // 依赖没有被调度（例如单独执行任务时），引用它的`${dep:名称}`会报错
+                None => debug!(
+                    "Task {}: {}: dependency {} is not scheduled",
+                    task.name_version(),
+                    key,
+                    dep.name
+                ),
+            }
+        }
+        return Ok(());
+    }
+
+    fn prepare_input(&self) -> Result<(), ExecError> {
+        // 拉取源文件需要访问网络时，限制同时进行的下载数
+        let _permit = fetch::fetches_remote(&self.entity.task()).then(fetch::acquire);
         // 拉取源文件
         let task = self.entity.task();
         match &task.task_type {
-            TaskType::BuildFromSource(cs) => {
+            TaskType::BuildFromSource(cs) | TaskType::KernelModule(cs) => {
                 if self.source_dir.is_none() {
                     return Ok(());
                 }
                 let source_dir = self.source_dir.as_ref().unwrap();
                 match cs {
                     CodeSource::Git(git) => {
-                        git.prepare(source_dir)
-                            .map_err(|e| ExecutorError::PrepareEnvError(e))?;
+                        git.prepare(source_dir, self.progress.as_ref())
+                            .map_err(ExecError::Fetch)?;
+                    }
+                    CodeSource::Hg(hg) => {
+                        hg.prepare(source_dir, self.progress.as_ref())
+                            .map_err(ExecError::Fetch)?;
+                    }
+                    CodeSource::Svn(svn) => {
+                        svn.prepare(source_dir, self.progress.as_ref())
+                            .map_err(ExecError::Fetch)?;
                     }
                     // 本地源文件，不需要拉取
                     CodeSource::Local(_) => return Ok(()),
                     // 在线压缩包，需要下载
                     CodeSource::Archive(archive) => {
-                        archive
-                            .download_unzip(source_dir)
-                            .map_err(|e| ExecutorError::PrepareEnvError(e))?;
+                        let checksum =
+                            archive.download_unzip(source_dir, self.progress.as_ref())?;
+                        self.save_archive_checksum(checksum)?;
+                    }
+                    // Release附件，需要查询Release后下载
+                    CodeSource::ReleaseAsset(release) => {
+                        let resolved =
+                            release.download_unzip(source_dir, self.progress.as_ref())?;
+                        self.save_release_asset(resolved)?;
+                    }
+                    // 叠加源，依次获取每一层后叠加到源码缓存目录
+                    CodeSource::Overlay(layers) => {
+                        self.prepare_overlay(layers, source_dir)?;
                     }
                 }
             }
             TaskType::InstallFromPrebuilt(pb) => {
                 match pb {
+                    // Git仓库，检出后把仓库中的文件直接作为构建结果
+                    PrebuiltSource::Git(git) => {
+                        let source_dir = self.source_dir.as_ref().unwrap();
+                        git.prepare(source_dir, self.progress.as_ref())
+                            .map_err(ExecError::Fetch)?;
+                        self.copy_prebuilt_git_tree(source_dir)?;
+                    }
                     // 本地源文件，不需要拉取
                     PrebuiltSource::Local(local_source) => {
                         let local_path = local_source.path();
                         let target_path = &self.build_dir.path;
                         FileUtils::copy_dir_all(&local_path, &target_path)
-                            .map_err(|e| ExecutorError::TaskFailed(e))?; // let mut cmd = "cp -r ".to_string();
+                            .map_err(ExecError::Fetch)?; // let mut cmd = "cp -r ".to_string();
                         return Ok(());
                     }
                     // 在线压缩包，需要下载
                     PrebuiltSource::Archive(archive) => {
-                        archive
-                            .download_unzip(&self.build_dir)
-                            .map_err(|e| ExecutorError::PrepareEnvError(e))?;
+                        let checksum =
+                            archive.download_unzip(&self.build_dir, self.progress.as_ref())?;
+                        self.save_archive_checksum(checksum)?;
+                    }
+                    // Release附件，需要查询Release后下载
+                    PrebuiltSource::ReleaseAsset(release) => {
+                        let resolved =
+                            release.download_unzip(&self.build_dir, self.progress.as_ref())?;
+                        self.save_release_asset(resolved)?;
+                    }
+                    // OCI镜像，需要拉取
+                    PrebuiltSource::Oci(oci) => {
+                        let arch: &str = self.target_arch().into();
+                        let digest = oci
+                            .prepare(&self.build_dir, arch)
+                            .map_err(ExecError::Fetch)?;
+                        let mut task_log = self.task_log();
+                        task_log.set_oci_digest(digest);
+                        self.task_data_dir.save_task_log(&task_log)?;
                     }
                 }
             }
+            // 没有源码，工作目录在创建执行器时已经创建好了
+            TaskType::NoSource | TaskType::Meta => return Ok(()),
+        }
+
+        return Ok(());
+    }
+
+    /// 把检出的Git仓库（不包括`.git`目录）拷贝到构建结果目录
+    ///
+    /// 构建结果目录会先被清空，以免残留仓库中已经删除的文件
+    fn copy_prebuilt_git_tree(&self, source_dir: &CacheDir) -> Result<(), ExecError> {
+        self.build_dir.remove_self_recursive()?;
+        self.build_dir.create()?;
+        FileUtils::copy_dir_all(&source_dir.path, &self.build_dir.path)
+            .map_err(ExecError::Fetch)?;
+
+        let git_dir = self.build_dir.path.join(".git");
+        if git_dir.is_dir() {
+            std::fs::remove_dir_all(&git_dir).map_err(|e| ExecError::Fetch(e.to_string()))?;
+        } else if git_dir.exists() {
+            // 子模块中的.git是一个文件
+            std::fs::remove_file(&git_dir).map_err(|e| ExecError::Fetch(e.to_string()))?;
+        }
+        return Ok(());
+    }
+
+    /// # 准备叠加源
+    ///
+    /// 依次获取每一层并记录其源码身份，然后清空源码缓存目录，按顺序把各层叠加进去
+    fn prepare_overlay(
+        &self,
+        layers: &[CodeSource],
+        source_dir: &CacheDir,
+    ) -> Result<(), ExecError> {
+        let mut task_log = self.task_log();
+        // 复用缓存时，压缩包不会被重新下载，使用上一次记录的身份
+        let previous = task_log.overlay_layers().cloned().unwrap_or_default();
+
+        let mut layer_paths: Vec<PathBuf> = Vec::new();
+        let mut resolved: Vec<OverlayLayer> = Vec::new();
+        for (i, layer) in layers.iter().enumerate() {
+            let last = previous.get(i).and_then(|l| l.source.as_ref());
+            let (path, locked) = match layer {
+                CodeSource::Local(local) => {
+                    let locked = LockedSource::Local {
+                        path: local.configured_path().clone(),
+                        hash: Self::local_source_hash(local.path(), None)?,
+                    };
+                    (local.path().clone(), Some(locked))
+                }
+                CodeSource::Git(git) => {
+                    let dir = source_dir.overlay_layer(i)?;
+                    git.prepare(&dir, self.progress.as_ref())
+                        .map_err(ExecError::Fetch)?;
+                    let commit = git.resolved_commit(&dir).map_err(ExecError::Fetch)?;
+                    let locked = LockedSource::Git {
+                        url: git.url().to_string(),
+                        commit,
+                    };
+                    (dir.path, Some(locked))
+                }
+                CodeSource::Hg(hg) => {
+                    let dir = source_dir.overlay_layer(i)?;
+                    hg.prepare(&dir, self.progress.as_ref())
+                        .map_err(ExecError::Fetch)?;
+                    let changeset = hg.resolved_changeset(&dir).map_err(ExecError::Fetch)?;
+                    let locked = LockedSource::Hg {
+                        url: hg.url().to_string(),
+                        changeset,
+                    };
+                    (dir.path, Some(locked))
+                }
+                CodeSource::Svn(svn) => {
+                    let dir = source_dir.overlay_layer(i)?;
+                    svn.prepare(&dir, self.progress.as_ref())
+                        .map_err(ExecError::Fetch)?;
+                    let revision = svn.resolved_revision(&dir).map_err(ExecError::Fetch)?;
+                    let locked = LockedSource::Svn {
+                        url: svn.url().to_string(),
+                        revision,
+                    };
+                    (dir.path, Some(locked))
+                }
+                CodeSource::Archive(archive) => {
+                    let dir = source_dir.overlay_layer(i)?;
+                    let checksum = archive.download_unzip(&dir, self.progress.as_ref())?;
+                    let locked = match (checksum, last) {
+                        (Some(sha256), _) => Some(LockedSource::Archive {
+                            url: archive.url().to_string(),
+                            sha256,
+                        }),
+                        (None, Some(l @ LockedSource::Archive { url, .. }))
+                            if url == archive.url() =>
+                        {
+                            Some(l.clone())
+                        }
+                        _ => None,
+                    };
+                    (dir.path, locked)
+                }
+                CodeSource::ReleaseAsset(release) => {
+                    let dir = source_dir.overlay_layer(i)?;
+                    let downloaded = release.download_unzip(&dir, self.progress.as_ref())?;
+                    let locked = match (downloaded, last) {
+                        (Some((tag, sha256)), _) => Some(LockedSource::ReleaseAsset {
+                            repo: release.repo().to_string(),
+                            tag,
+                            sha256,
+                        }),
+                        (None, Some(l @ LockedSource::ReleaseAsset { repo, .. }))
+                            if repo == release.repo() =>
+                        {
+                            Some(l.clone())
+                        }
+                        _ => None,
+                    };
+                    (dir.path, locked)
+                }
+                CodeSource::Overlay(_) => {
+                    return Err(ExecError::Validation(
+                        "nested overlay source is not supported".to_string(),
+                    ));
+                }
+            };
+            layer_paths.push(path);
+            resolved.push(OverlayLayer { source: locked });
+        }
+
+        source_dir.remove_self_recursive()?;
+        source_dir.create()?;
+        FileUtils::overlay_dirs(&layer_paths, &source_dir.path).map_err(ExecError::Fetch)?;
+
+        task_log.set_overlay_layers(Some(resolved));
+        self.task_data_dir.save_task_log(&task_log)?;
+        return Ok(());
+    }
+
+    /// 记录新下载的压缩包的sha256值，以便之后复用缓存时也能得到压缩包的身份
+    fn save_archive_checksum(&self, checksum: Option<String>) -> Result<(), ExecError> {
+        if let Some(checksum) = checksum {
+            let mut task_log = self.task_log();
+            task_log.set_archive_checksum(checksum);
+            self.task_data_dir.save_task_log(&task_log)?;
+        }
+        return Ok(());
+    }
+
+    /// 记录新下载的Release附件所属的tag和sha256值
+    fn save_release_asset(&self, resolved: Option<(String, String)>) -> Result<(), ExecError> {
+        if let Some((tag, checksum)) = resolved {
+            let mut task_log = self.task_log();
+            task_log.set_release_tag(tag);
+            task_log.set_archive_checksum(checksum);
+            self.task_data_dir.save_task_log(&task_log)?;
         }
+        return Ok(());
+    }
 
+    /// # 检查源码身份是否与锁文件中记录的一致
+    ///
+    /// 如果没有初始化全局锁文件，则不进行检查
+    fn check_source_lock(&self) -> Result<(), ExecError> {
+        if let Some(resolved) = self.resolve_source()? {
+            check_locked_source(&self.entity.task().name_version(), resolved)?;
+        }
         return Ok(());
     }
 
-    fn run_command(&self, mut command: Command) -> Result<(), ExecutorError> {
-        let mut child = command
-            .stdin(Stdio::inherit())
-            .spawn()
-            .map_err(|e| ExecutorError::IoError(e.to_string()))?;
+    /// # 解析任务实际获取到的源码身份
+    ///
+    /// 对于没有源码的任务，返回None
+    fn resolve_source(&self) -> Result<Option<LockedSource>, ExecError> {
+        let task = self.entity.task();
+        let locked = match &task.task_type {
+            TaskType::BuildFromSource(CodeSource::Git(git))
+            | TaskType::KernelModule(CodeSource::Git(git))
+            | TaskType::InstallFromPrebuilt(PrebuiltSource::Git(git)) => {
+                let commit = git
+                    .resolved_commit(self.source_dir.as_ref().unwrap())
+                    .map_err(ExecError::Fetch)?;
+                LockedSource::Git {
+                    url: git.url().to_string(),
+                    commit,
+                }
+            }
+            TaskType::BuildFromSource(CodeSource::Hg(hg))
+            | TaskType::KernelModule(CodeSource::Hg(hg)) => {
+                let changeset = hg
+                    .resolved_changeset(self.source_dir.as_ref().unwrap())
+                    .map_err(ExecError::Fetch)?;
+                LockedSource::Hg {
+                    url: hg.url().to_string(),
+                    changeset,
+                }
+            }
+            TaskType::BuildFromSource(CodeSource::Svn(svn))
+            | TaskType::KernelModule(CodeSource::Svn(svn)) => {
+                let revision = svn
+                    .resolved_revision(self.source_dir.as_ref().unwrap())
+                    .map_err(ExecError::Fetch)?;
+                LockedSource::Svn {
+                    url: svn.url().to_string(),
+                    revision,
+                }
+            }
+            TaskType::BuildFromSource(CodeSource::Archive(archive))
+            | TaskType::KernelModule(CodeSource::Archive(archive))
+            | TaskType::InstallFromPrebuilt(PrebuiltSource::Archive(archive)) => {
+                match self.task_log().archive_checksum() {
+                    Some(sha256) => LockedSource::Archive {
+                        url: archive.url().to_string(),
+                        sha256: sha256.clone(),
+                    },
+                    None => {
+                        warn!(
+                            "Task {}: checksum of the cached archive is unknown, clean the cache to lock it",
+                            task.name_version()
+                        );
+                        return Ok(None);
+                    }
+                }
+            }
+            TaskType::BuildFromSource(CodeSource::ReleaseAsset(release))
+            | TaskType::KernelModule(CodeSource::ReleaseAsset(release))
+            | TaskType::InstallFromPrebuilt(PrebuiltSource::ReleaseAsset(release)) => {
+                let task_log = self.task_log();
+                match (task_log.release_tag(), task_log.archive_checksum()) {
+                    (Some(tag), Some(sha256)) => LockedSource::ReleaseAsset {
+                        repo: release.repo().to_string(),
+                        tag: tag.clone(),
+                        sha256: sha256.clone(),
+                    },
+                    _ => {
+                        warn!(
+                            "Task {}: the cached release asset is unknown, clean the cache to lock it",
+                            task.name_version()
+                        );
+                        return Ok(None);
+                    }
+                }
+            }
+            TaskType::InstallFromPrebuilt(PrebuiltSource::Oci(oci)) => {
+                match self.task_log().oci_digest() {
+                    Some(digest) => LockedSource::Oci {
+                        image: oci.image().to_string(),
+                        digest: digest.clone(),
+                    },
+                    None => return Ok(None),
+                }
+            }
+            TaskType::BuildFromSource(CodeSource::Local(local))
+            | TaskType::KernelModule(CodeSource::Local(local))
+            | TaskType::InstallFromPrebuilt(PrebuiltSource::Local(local)) => LockedSource::Local {
+                path: local.configured_path().clone(),
+                hash: Self::local_source_hash(local.path(), task.build.build_dir.as_deref())?,
+            },
+            TaskType::BuildFromSource(CodeSource::Overlay(_))
+            | TaskType::KernelModule(CodeSource::Overlay(_)) => {
+                let layers = self
+                    .task_log()
+                    .overlay_layers()
+                    .cloned()
+                    .unwrap_or_default();
+                // 没有记录时，所有层的身份都未知
+                let unknown: Vec<String> = match layers.is_empty() {
+                    true => vec!["*".to_string()],
+                    false => layers
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, l)| l.source.is_none())
+                        .map(|(i, _)| i.to_string())
+                        .collect(),
+                };
+                if !unknown.is_empty() {
+                    warn!(
+                        "Task {}: layers [{}] of the cached overlay are unknown, clean the cache to lock it",
+                        task.name_version(),
+                        unknown.join(", ")
+                    );
+                    return Ok(None);
+                }
+                LockedSource::Overlay {
+                    layers: layers.into_iter().filter_map(|l| l.source).collect(),
+                }
+            }
+            TaskType::NoSource | TaskType::Meta => return Ok(None),
+        };
+        return Ok(Some(locked));
+    }
+
+    /// # 计算本地源的哈希值
+    ///
+    /// 构建命令在本地源的目录中执行，因此不包括版本控制目录以及构建的工作目录`build_dir`，
+    /// 避免构建产生的文件改变哈希值
+    fn local_source_hash(path: &Path, build_dir: Option<&Path>) -> Result<String, ExecError> {
+        let mut ignore = IgnoreMatcher::new(&[".git", ".hg", ".svn"]).unwrap();
+        if let Some(build_dir) = build_dir {
+            ignore.add_path(build_dir);
+        }
+        return HashUtils::hash_dir(path, &ignore).map_err(|e| {
+            ExecError::Fetch(format!(
+                "Failed to hash local source {}: {}",
+                path.display(),
+                e
+            ))
+        });
+    }
+
+    fn run_command(&self, mut command: Command) -> Result<(), ExecError> {
+        if self.network_isolated {
+            command = sandbox::isolate_network(&command).map_err(|e| {
+                ExecError::Validation(format!("Failed to enter network sandbox: {}", e))
+            })?;
+        }
+        self.resource_limits.apply(&mut command);
+        command.stdin(Stdio::inherit());
+        let log = self.output_log.as_deref();
+        let diagnostics = self.diagnostics.as_deref();
+        let watch_oom = self.resource_limits.max_memory.is_some();
+        if let Some(log) = log {
+            // 命令的调试输出中包括环境变量，隐藏保密的值
+            log.command(&self.local_envs.mask(&format!("{:?}", command)));
+        }
+        if log.is_some() || diagnostics.is_some() || watch_oom {
+            command.stdout(Stdio::piped()).stderr(Stdio::piped());
+        }
+        if self.command_timeout.is_some() {
+            // 超时后结束整个进程组，包括命令启动的子进程
+            std::os::unix::process::CommandExt::process_group(&mut command, 0);
+        }
+        let mut child = command.spawn().map_err(|e| {
+            if self.resource_limits.is_empty() {
+                ExecError::Build(e.to_string())
+            } else {
+                ExecError::Build(format!(
+                    "{} (failed to start with resource limits: {})",
+                    e, self.resource_limits
+                ))
+            }
+        })?;
 
-        // 等待子进程结束
-        let r = child
-            .wait()
-            .map_err(|e| ExecutorError::IoError(e.to_string()));
+        // 等待子进程结束，同时把输出写入日志，统计警告和错误，并留意内存分配失败的信息
+        let oom_seen = AtomicBool::new(false);
+        let oom_seen_ref = &oom_seen;
+        let on_line = move |line: &[u8]| {
+            if let Some(log) = log {
+                log.write_output(line);
+            }
+            if diagnostics.is_none() && !watch_oom {
+                return;
+            }
+            let line = String::from_utf8_lossy(line);
+            if let Some(diagnostics) = diagnostics {
+                diagnostics.scan(&line);
+            }
+            if watch_oom && is_oom_message(&line) {
+                oom_seen_ref.store(true, Ordering::Relaxed);
+            }
+        };
+        let timeout = self.command_timeout;
+        // 事件输出到标准输出时，命令的标准输出改为输出到标准错误，避免与事件混在一起
+        let console: Box<dyn Write + Send> =
+            if self.events.as_ref().is_some_and(|e| e.writes_stdout()) {
+                Box::new(std::io::stderr())
+            } else {
+                Box::new(std::io::stdout())
+            };
+        let r = std::thread::scope(|s| {
+            if let Some(stdout) = child.stdout.take() {
+                s.spawn(move || tee_lines(stdout, console, on_line));
+            }
+            if let Some(stderr) = child.stderr.take() {
+                s.spawn(move || tee_lines(stderr, std::io::stderr(), on_line));
+            }
+            match timeout {
+                Some(timeout) => Self::wait_timeout(&mut child, timeout),
+                None => child.wait().map(|status| (status, false)),
+            }
+        })
+        .map_err(|e| ExecError::Build(e.to_string()));
         debug!("Command finished: {:?}", r);
+        if let Ok((status, _)) = &r {
+            self.last_exit_code.set(status.code());
+        }
+        if let Ok((status, true)) = &r {
+            let errmsg = format!(
+                "Task {} timed out after {}s, killed its process group ({})",
+                self.entity.task().name_version(),
+                timeout.unwrap_or_default().as_secs(),
+                status
+            );
+            error!("{errmsg}");
+            return Err(ExecError::Timeout(errmsg));
+        }
         if r.is_ok() {
-            let r = r.unwrap();
+            let (r, _) = r.unwrap();
             if r.success() {
                 return Ok(());
             } else {
-                // 执行失败，获取最后100行stderr输出
-                let errmsg = format!(
+                // 执行失败，输出的最后若干行会在任务结束时从日志中输出
+                let mut errmsg = format!(
                     "Task {} failed, exit code = {}",
                     self.entity.task().name_version(),
-                    r.code().unwrap()
+                    r.code()
+                        .map_or_else(|| "none".to_string(), |c| c.to_string())
                 );
-                error!("{errmsg}");
-                let command_opt = command.output();
-                if command_opt.is_err() {
-                    return Err(ExecutorError::TaskFailed(
-                        "Failed to get command output".to_string(),
-                    ));
+                let signal = Self::exit_signal(&r);
+                if let Some(exceeded) = self
+                    .resource_limits
+                    .exceeded(signal, oom_seen.load(Ordering::Relaxed))
+                {
+                    errmsg = format!(
+                        "Task {} {} ({})",
+                        self.entity.task().name_version(),
+                        exceeded,
+                        r
+                    );
                 }
-                let command_opt = command_opt.unwrap();
-                let command_output = String::from_utf8_lossy(&command_opt.stderr);
-                let mut last_100_outputs = command_output
-                    .lines()
-                    .rev()
-                    .take(100)
-                    .collect::<Vec<&str>>();
-                last_100_outputs.reverse();
-                error!("Last 100 lines msg of stderr:");
-                for line in last_100_outputs {
-                    error!("{}", line);
+                if self.network_isolated {
+                    errmsg = format!("{} ({})", errmsg, sandbox::NETWORK_SANDBOX_HINT);
                 }
-                return Err(ExecutorError::TaskFailed(errmsg));
+                error!("{errmsg}");
+                return Err(ExecError::Build(errmsg));
             }
         } else {
             let errmsg = format!(
@@ -528,11 +3370,47 @@ impl Executor {
                 r.err().unwrap()
             );
             error!("{errmsg}");
-            return Err(ExecutorError::TaskFailed(errmsg));
+            return Err(ExecError::Build(errmsg));
+        }
+    }
+
+    /// # 等待子进程结束，超时后结束子进程所在的进程组
+    ///
+    /// 子进程需要在单独的进程组中启动。返回子进程的退出状态，以及是否超时
+    fn wait_timeout(child: &mut Child, timeout: Duration) -> std::io::Result<(ExitStatus, bool)> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(status) = child.try_wait()? {
+                return Ok((status, false));
+            }
+            if Instant::now() >= deadline {
+                unsafe {
+                    libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+                }
+                return Ok((child.wait()?, true));
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        }
+    }
+
+    /// # 结束命令的信号
+    ///
+    /// shell中的子进程被信号结束时，shell以`128+信号`退出，同样视为被该信号结束
+    fn exit_signal(status: &std::process::ExitStatus) -> Option<i32> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::ExitStatusExt;
+            if let Some(signal) = status.signal() {
+                return Some(signal);
+            }
         }
+        return status
+            .code()
+            .filter(|c| *c > 128 && *c < 160)
+            .map(|c| c - 128);
     }
 
-    pub fn mv_target_to_tmp(&mut self) -> Result<(), ExecutorError> {
+    pub fn mv_target_to_tmp(&mut self) -> Result<(), ExecError> {
         if let Some(rust_target) = self.entity.task().rust_target.clone() {
             // 将target文件拷贝至 /tmp 下对应的dadk文件的临时target文件中
             self.entity
@@ -544,7 +3422,27 @@ impl Executor {
         return Ok(());
     }
 
-    pub fn prepare_target_env(&mut self) -> Result<(), ExecutorError> {
+    /// # 设置构建任务数相关的环境变量
+    ///
+    /// 如果任务开启了`inject_jobs`，则根据`DADK_JOBS`设置`MAKEFLAGS`和`CARGO_BUILD_JOBS`，
+    /// 任务自己在`envs`中设置的同名环境变量优先
+    fn prepare_jobs_env(&mut self, env_list: &EnvMap) {
+        let jobs = match env_list.get(JOBS_ENV_KEY) {
+            Some(jobs) => jobs.value.clone(),
+            None => return,
+        };
+        let task = self.entity.task();
+        info!("Task {}: job budget {}", task.name_version(), jobs);
+        if !task.build.inject_jobs {
+            return;
+        }
+        self.local_envs
+            .add(EnvVar::new("MAKEFLAGS".to_string(), format!("-j{}", jobs)));
+        self.local_envs
+            .add(EnvVar::new("CARGO_BUILD_JOBS".to_string(), jobs));
+    }
+
+    pub fn prepare_target_env(&mut self) -> Result<(), ExecError> {
         if self.entity.task().rust_target.is_some() {
             // 如果有dadk任务有rust_target字段，需要设置DADK_RUST_TARGET_FILE环境变量，值为临时target文件路径
             self.entity
@@ -573,51 +3471,207 @@ impl EnvMap {
         self.envs.insert(env.key.clone(), env);
     }
 
-    #[allow(dead_code)]
     pub fn get(&self, key: &str) -> Option<&EnvVar> {
         self.envs.get(key)
     }
 
     pub fn add_vars(&mut self, vars: Vars) {
         for (key, value) in vars {
-            self.add(EnvVar::new(key, value));
+            self.add(EnvVar::with_origin(key, value, EnvOrigin::Host));
+        }
+    }
+
+    /// # 把文本中保密的环境变量的值替换为[`SECRET_MASK`]
+    ///
+    /// 用于输出命令和错误信息。先替换较长的值，避免一个值是另一个值的一部分时只替换了一部分
+    pub fn mask(&self, text: &str) -> String {
+        let mut secrets: Vec<&str> = self
+            .envs
+            .values()
+            .filter(|env| env.secret && !env.value.is_empty())
+            .map(|env| env.value.as_str())
+            .collect();
+        secrets.sort_by_key(|value| std::cmp::Reverse(value.len()));
+        let mut text = text.to_string();
+        for value in secrets {
+            text = text.replace(value, SECRET_MASK);
         }
+        return text;
+    }
+}
+
+/// # 环境变量的来源
+#[derive(Debug, PartialEq, PartialOrd, Eq, Ord, Clone, Copy)]
+pub enum EnvOrigin {
+    /// 执行DADK时的环境变量
+    Host,
+    /// DADK设置的环境变量
+    Dadk,
+    /// 工作区配置中的全局环境变量（`global_envs`）
+    Global,
+    /// 环境变量文件（工作区或者任务的`env_files`）
+    EnvFile,
+    /// 任务配置中的环境变量
+    Task,
+}
+
+impl Display for EnvOrigin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            EnvOrigin::Host => "host",
+            EnvOrigin::Dadk => "dadk",
+            EnvOrigin::Global => "global",
+            EnvOrigin::EnvFile => "env_file",
+            EnvOrigin::Task => "task",
+        };
+        write!(f, "{}", s)
     }
 }
 
 /// # 环境变量
-#[derive(Debug, PartialEq, PartialOrd, Eq, Ord, Clone)]
+#[derive(PartialEq, PartialOrd, Eq, Ord, Clone)]
 pub struct EnvVar {
     pub key: String,
     pub value: String,
+    pub origin: EnvOrigin,
+    /// 值是否保密，见[`TaskEnv`]
+    pub secret: bool,
 }
 
 impl EnvVar {
+    /// 创建由DADK设置的环境变量
     pub fn new(key: String, value: String) -> Self {
-        Self { key, value }
+        Self::with_origin(key, value, EnvOrigin::Dadk)
+    }
+
+    pub fn with_origin(key: String, value: String, origin: EnvOrigin) -> Self {
+        Self {
+            key,
+            value,
+            origin,
+            secret: false,
+        }
+    }
+
+    /// 设置值是否保密
+    pub fn with_secret(mut self, secret: bool) -> Self {
+        self.secret = secret;
+        return self;
+    }
+
+    /// 输出时使用的值，保密的值被替换为[`SECRET_MASK`]
+    pub fn display_value(&self) -> &str {
+        if self.secret {
+            return SECRET_MASK;
+        }
+        return &self.value;
     }
 }
 
-/// # 任务执行器错误枚举
-#[allow(dead_code)]
-#[derive(Debug, Clone)]
-pub enum ExecutorError {
-    /// 准备执行环境错误
-    PrepareEnvError(String),
-    IoError(String),
-    /// 构建执行错误
-    TaskFailed(String),
+impl std::fmt::Debug for EnvVar {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EnvVar")
+            .field("key", &self.key)
+            .field("value", &self.display_value())
+            .field("origin", &self.origin)
+            .field("secret", &self.secret)
+            .finish()
+    }
+}
+
+/// # 任务执行错误
+///
+/// 按出错的阶段分类，错误信息中包括出错的任务（见[`ExecError::with_task`]）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExecError {
+    /// 任务的配置、环境变量或者执行环境不满足要求，以及源码身份与锁文件中记录的不一致
+    Validation(String),
+    /// 获取源码或者预编译包失败
+    Fetch(String),
+    /// 解压压缩包失败
+    Extract(String),
+    /// 构建命令（包括钩子）失败
+    Build(String),
     /// 安装错误
-    InstallError(String),
+    Install(String),
     /// 清理错误
-    CleanError(String),
+    Clean(String),
+    /// 命令执行超时
+    Timeout(String),
+    /// 读写缓存目录或者任务数据失败
+    Cache(String),
+    /// 用户显式请求清理，但任务没有可以清理的内容
+    NothingToClean(String),
+}
+
+impl ExecError {
+    /// 错误的类型名称
+    pub fn kind(&self) -> &'static str {
+        return match self {
+            ExecError::Validation(_) => "validation",
+            ExecError::Fetch(_) => "fetch",
+            ExecError::Extract(_) => "extract",
+            ExecError::Build(_) => "build",
+            ExecError::Install(_) => "install",
+            ExecError::Clean(_) => "clean",
+            ExecError::Timeout(_) => "timeout",
+            ExecError::Cache(_) => "cache",
+            ExecError::NothingToClean(_) => "nothing to clean",
+        };
+    }
+
+    pub fn message(&self) -> &str {
+        return match self {
+            ExecError::Validation(msg)
+            | ExecError::Fetch(msg)
+            | ExecError::Extract(msg)
+            | ExecError::Build(msg)
+            | ExecError::Install(msg)
+            | ExecError::Clean(msg)
+            | ExecError::Timeout(msg)
+            | ExecError::Cache(msg)
+            | ExecError::NothingToClean(msg) => msg,
+        };
+    }
+
+    /// 修改错误信息，不改变错误的类型
+    pub fn map_message(self, f: impl FnOnce(String) -> String) -> Self {
+        return match self {
+            ExecError::Validation(msg) => ExecError::Validation(f(msg)),
+            ExecError::Fetch(msg) => ExecError::Fetch(f(msg)),
+            ExecError::Extract(msg) => ExecError::Extract(f(msg)),
+            ExecError::Build(msg) => ExecError::Build(f(msg)),
+            ExecError::Install(msg) => ExecError::Install(f(msg)),
+            ExecError::Clean(msg) => ExecError::Clean(f(msg)),
+            ExecError::Timeout(msg) => ExecError::Timeout(f(msg)),
+            ExecError::Cache(msg) => ExecError::Cache(f(msg)),
+            ExecError::NothingToClean(msg) => ExecError::NothingToClean(f(msg)),
+        };
+    }
+
+    /// # 在错误信息中加上出错的任务
+    ///
+    /// `task`是任务的`name_version`，错误信息中已经包括该任务时不重复添加
+    pub fn with_task(self, task: &str) -> Self {
+        if self.message().contains(task) {
+            return self;
+        }
+        return self.map_message(|msg| format!("Task {}: {}", task, msg));
+    }
 }
 
+impl Display for ExecError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} error: {}", self.kind(), self.message())
+    }
+}
+
+impl std::error::Error for ExecError {}
 /// # 准备全局环境变量
 pub fn prepare_env(
     sched_entities: &SchedEntities,
     execute_ctx: &Arc<DadkExecuteContext>,
-) -> Result<(), ExecutorError> {
+) -> Result<(), ExecError> {
     info!("Preparing environment variables...");
     let env_list = create_global_env_list(sched_entities, execute_ctx)?;
     // 写入全局环境变量列表
@@ -630,7 +3684,7 @@ pub fn prepare_env(
 fn create_global_env_list(
     sched_entities: &SchedEntities,
     execute_ctx: &Arc<DadkExecuteContext>,
-) -> Result<EnvMap, ExecutorError> {
+) -> Result<EnvMap, ExecError> {
     let mut env_list = EnvMap::new();
     let envs: Vars = std::env::vars();
     env_list.add_vars(envs);
@@ -645,6 +3699,22 @@ fn create_global_env_list(
             build_dir_key,
             build_dir.to_str().unwrap().to_string(),
         ));
+        // 只按固定架构构建的依赖，同时导出不带架构后缀的构建目录，便于依赖它的任务引用
+        let task = entity.task();
+        if task.pinned_arch.is_some()
+            && sched_entities
+                .get_by_name_version(&task.name, &task.version)
+                .is_none()
+        {
+            env_list.add(EnvVar::new(
+                format!(
+                    "{}_{}",
+                    CacheDir::DADK_BUILD_CACHE_DIR_ENV_KEY_PREFIX,
+                    task.name_version_env()
+                ),
+                build_dir.to_str().unwrap().to_string(),
+            ));
+        }
 
         // 如果需要源码缓存目录，则导出
         if CacheDir::need_source_cache(entity) {
@@ -661,5 +3731,44 @@ fn create_global_env_list(
     let target_arch = execute_ctx.target_arch();
     env_list.add(EnvVar::new("ARCH".to_string(), (*target_arch).into()));
 
+    // 导出生效的构建配置档
+    if let Some(profile) = execute_ctx.profile() {
+        env_list.add(EnvVar::new(PROFILE_ENV_KEY.to_string(), profile.clone()));
+    }
+
+    // 把总任务数平分给同时执行的任务
+    let job_budget = execute_ctx.job_budget();
+    info!(
+        "Job budget: {} per task ({} jobs / {} parallel tasks)",
+        job_budget,
+        execute_ctx.jobs(),
+        execute_ctx.parallel_tasks()
+    );
+    env_list.add(EnvVar::new(
+        JOBS_ENV_KEY.to_string(),
+        job_budget.to_string(),
+    ));
+
+    // 导出内核源码目录等环境变量，便于构建内核模块
+    if let Some(kernel_src) = execute_ctx.kernel_src() {
+        env_list.add(EnvVar::new(
+            KERNEL_SRC_ENV_KEY.to_string(),
+            kernel_src.to_str().unwrap().to_string(),
+        ));
+        env_list.add(EnvVar::new(
+            KERNEL_BUILD_ENV_KEY.to_string(),
+            execute_ctx
+                .kernel_build()
+                .unwrap()
+                .to_str()
+                .unwrap()
+                .to_string(),
+        ));
+        env_list.add(EnvVar::new(
+            KERNEL_ARCH_ENV_KEY.to_string(),
+            (*execute_ctx.kernel_arch()).into(),
+        ));
+    }
+
     return Ok(env_list);
 }
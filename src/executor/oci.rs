@@ -0,0 +1,716 @@
+//! # OCI镜像
+//!
+//! 从OCI/Docker镜像仓库拉取镜像的manifest和各层，并把各层展开为一个完整的文件系统。
+//!
+//! 下载的镜像层和展开后的文件系统都会缓存在DADK缓存根目录下的`oci`目录中：
+//!
+//! - `oci/blobs/<digest>`：镜像层
+//! - `oci/rootfs/<digest>-<arch>`：展开后的文件系统
+//!
+//! 仓库的认证信息从Docker的配置文件（`$DOCKER_CONFIG/config.json`或`~/.docker/config.json`）中读取。
+
+use std::{
+    fs::File,
+    path::{Component, Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use base64::Engine;
+use log::{debug, info};
+use regex::Regex;
+use reqwest::{
+    blocking::{Client, ClientBuilder, RequestBuilder, Response},
+    header::{ACCEPT, AUTHORIZATION, WWW_AUTHENTICATE},
+    StatusCode,
+};
+use serde::Deserialize;
+
+use crate::utils::{file::FileUtils, hash::HashUtils, stdio::StdioUtils};
+
+use super::{
+    cache::CACHE_ROOT,
+    gc::{self, CacheCategory},
+};
+
+/// manifest请求时可以接受的媒体类型
+const MANIFEST_ACCEPT: &str = "application/vnd.oci.image.index.v1+json, \
+application/vnd.oci.image.manifest.v1+json, \
+application/vnd.docker.distribution.manifest.list.v2+json, \
+application/vnd.docker.distribution.manifest.v2+json";
+
+/// Docker Hub的默认仓库
+const DOCKER_HUB_REGISTRY: &str = "docker.io";
+
+/// # 镜像引用
+///
+/// 由镜像仓库地址、镜像名以及tag/digest组成
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageReference {
+    /// 镜像仓库地址（可能带有端口号）
+    pub registry: String,
+    /// 镜像在仓库中的名字
+    pub repository: String,
+    /// 要拉取的tag或者digest（`sha256:<hex>`）。同时指定了tag和digest时为digest
+    pub reference: String,
+    /// 同时指定了tag和digest（`tag@sha256:<hex>`）时的tag，只用于显示，拉取时以digest为准
+    pub tag: Option<String>,
+}
+
+impl ImageReference {
+    /// # 解析镜像引用
+    ///
+    /// ## 参数
+    ///
+    /// - `image` - 镜像名，例如`alpine`、`ghcr.io/org/app`、`localhost:5000/app`。
+    ///   也可以是带有tag/digest的完整引用，例如`registry/repo:tag@sha256:<hex>`，此时`reference`必须为空
+    /// - `reference` - tag、digest或者`tag@sha256:<hex>`
+    pub fn parse(image: &str, reference: &str) -> Result<Self, String> {
+        if image.is_empty() {
+            return Err("image is empty".to_string());
+        }
+        let (image, embedded) = Self::split_image(image);
+        let reference = match embedded {
+            Some(_) if !reference.is_empty() => {
+                return Err(format!(
+                    "image {:?} already contains a tag or digest, reference {:?} should be empty",
+                    image, reference
+                ))
+            }
+            Some(embedded) => embedded,
+            None => reference.to_string(),
+        };
+        if reference.is_empty() {
+            return Err("reference is empty".to_string());
+        }
+        let image = image.as_str();
+        let reference = reference.as_str();
+
+        let (registry, repository) = match image.split_once('/') {
+            Some((first, rest))
+                if first.contains('.') || first.contains(':') || first == "localhost" =>
+            {
+                (first.to_string(), rest.to_string())
+            }
+            _ => (DOCKER_HUB_REGISTRY.to_string(), image.to_string()),
+        };
+
+        // Docker Hub上的官方镜像位于library命名空间下
+        let repository = if registry == DOCKER_HUB_REGISTRY && !repository.contains('/') {
+            format!("library/{}", repository)
+        } else {
+            repository
+        };
+
+        let component = Regex::new(r"^[a-z0-9]+((\.|_|__|-+)[a-z0-9]+)*$").unwrap();
+        for c in repository.split('/') {
+            if !component.is_match(c) {
+                return Err(format!("invalid image name: {:?}", image));
+            }
+        }
+
+        let (tag, digest) = match reference.split_once('@') {
+            Some((tag, digest)) => (Some(tag), Some(digest)),
+            None if reference.starts_with("sha256:") => (None, Some(reference)),
+            None => (Some(reference), None),
+        };
+        if let Some(tag) = tag {
+            let tag_re = Regex::new(r"^[A-Za-z0-9_][A-Za-z0-9_.-]{0,127}$").unwrap();
+            if !tag_re.is_match(tag) {
+                return Err(format!("invalid tag: {:?}", tag));
+            }
+        }
+        if let Some(digest) = digest {
+            let digest_re = Regex::new(r"^sha256:[a-f0-9]{64}$").unwrap();
+            if !digest_re.is_match(digest) {
+                return Err(format!("invalid digest: {:?}", digest));
+            }
+        }
+
+        return Ok(Self {
+            registry,
+            repository,
+            reference: digest.or(tag).unwrap().to_string(),
+            tag: tag.filter(|_| digest.is_some()).map(|t| t.to_string()),
+        });
+    }
+
+    /// # 把完整的镜像引用拆分为镜像名和tag/digest
+    ///
+    /// 例如`ghcr.io/org/app:1.0@sha256:<hex>`拆分为`ghcr.io/org/app`和`1.0@sha256:<hex>`。
+    /// 仓库地址中的端口号（`localhost:5000/app`）不会被当作tag
+    pub fn split_image(image: &str) -> (String, Option<String>) {
+        let (name, digest) = match image.split_once('@') {
+            Some((name, digest)) => (name, Some(digest)),
+            None => (image, None),
+        };
+        let last_slash = name.rfind('/').map_or(0, |i| i + 1);
+        let (name, tag) = match name[last_slash..].rfind(':') {
+            Some(i) => (&name[..last_slash + i], Some(&name[last_slash + i + 1..])),
+            None => (name, None),
+        };
+        let embedded = match (tag, digest) {
+            (Some(tag), Some(digest)) => Some(format!("{}@{}", tag, digest)),
+            (tag, digest) => tag.or(digest).map(|r| r.to_string()),
+        };
+        return (name.to_string(), embedded);
+    }
+
+    /// 引用是否是固定的digest（否则为tag）
+    pub fn is_digest(&self) -> bool {
+        self.reference.starts_with("sha256:")
+    }
+
+    /// 镜像仓库API的根地址
+    fn api_base(&self) -> String {
+        let host = if self.registry == DOCKER_HUB_REGISTRY {
+            "registry-1.docker.io"
+        } else {
+            self.registry.as_str()
+        };
+
+        // 本地仓库一般不使用https
+        let scheme = if host.starts_with("localhost") || host.starts_with("127.0.0.1") {
+            "http"
+        } else {
+            "https"
+        };
+        return format!("{}://{}/v2/{}", scheme, host, self.repository);
+    }
+}
+
+/// # 镜像拉取器
+///
+/// 拉取镜像，并把展开后的文件系统缓存下来
+pub struct OciPuller {
+    image: ImageReference,
+    /// 镜像的处理器架构（OCI的命名方式，例如`amd64`）
+    arch: String,
+    client: RegistryClient,
+}
+
+impl OciPuller {
+    /// # 创建镜像拉取器
+    ///
+    /// ## 参数
+    ///
+    /// - `image` - 镜像引用
+    /// - `target_arch` - DADK的目标架构，例如`x86_64`
+    pub fn new(image: ImageReference, target_arch: &str) -> Result<Self, String> {
+        let arch = Self::oci_arch(target_arch)?;
+        let client = RegistryClient::new(image.clone())?;
+        return Ok(Self {
+            image,
+            arch,
+            client,
+        });
+    }
+
+    /// 把DADK的目标架构转换为OCI的架构名
+    fn oci_arch(target_arch: &str) -> Result<String, String> {
+        let arch = match target_arch {
+            "x86_64" => "amd64",
+            "aarch64" => "arm64",
+            "riscv64" => "riscv64",
+            _ => {
+                return Err(format!(
+                    "target arch {:?} is not supported by OCI images",
+                    target_arch
+                ))
+            }
+        };
+        return Ok(arch.to_string());
+    }
+
+    /// # 拉取镜像
+    ///
+    /// 如果引用是digest，并且展开后的文件系统已经缓存，则直接使用缓存，不访问网络；
+    /// 如果引用是tag，则会重新解析tag对应的digest，只有在远端digest变化后才会重新拉取。
+    ///
+    /// ## 返回值
+    ///
+    /// - `Ok((digest, rootfs))` - 镜像的digest和展开后的文件系统路径
+    pub fn pull(&mut self) -> Result<(String, PathBuf), String> {
+        if self.image.is_digest() {
+            let rootfs = self.rootfs_dir(&self.image.reference);
+            if rootfs.is_dir() {
+                info!(
+                    "Image {}@{} already cached, skip pulling.",
+                    self.image.repository, self.image.reference
+                );
+                Self::touch(&rootfs);
+                return Ok((self.image.reference.clone(), rootfs));
+            }
+        }
+
+        let (digest, manifest) = self.client.resolve_manifest(&self.image.reference)?;
+        let rootfs = self.rootfs_dir(&digest);
+        if rootfs.is_dir() {
+            info!(
+                "Image {}:{} is up to date ({}), using cache.",
+                self.image.repository, self.image.reference, digest
+            );
+            Self::touch(&rootfs);
+            return Ok((digest, rootfs));
+        }
+
+        let manifest = if manifest.manifests.is_empty() {
+            manifest
+        } else {
+            // 多平台镜像，选择与目标架构对应的manifest
+            let platform_digest = manifest
+                .manifests
+                .iter()
+                .find(|m| {
+                    m.platform
+                        .as_ref()
+                        .map_or(false, |p| p.os == "linux" && p.architecture == self.arch)
+                })
+                .map(|m| m.digest.clone())
+                .ok_or(format!(
+                    "image {} has no manifest for linux/{}",
+                    self.image.repository, self.arch
+                ))?;
+            self.client.resolve_manifest(&platform_digest)?.1
+        };
+
+        info!(
+            "Pulling image {}:{} ({} layers)",
+            self.image.repository,
+            self.image.reference,
+            manifest.layers.len()
+        );
+        let blobs_dir = Self::oci_cache_dir().join("blobs");
+        std::fs::create_dir_all(&blobs_dir).map_err(|e| e.to_string())?;
+
+        let mut layers = Vec::new();
+        for layer in manifest.layers.iter() {
+            let blob = blobs_dir.join(layer.digest.replace(':', "-"));
+            self.client.fetch_blob(&layer.digest, &blob)?;
+            Self::touch(&blob);
+            layers.push(blob);
+        }
+
+        // 先展开到临时目录，成功后再重命名，避免留下不完整的缓存
+        let tmp_rootfs = rootfs.with_extension("tmp");
+        if tmp_rootfs.exists() {
+            std::fs::remove_dir_all(&tmp_rootfs).map_err(|e| e.to_string())?;
+        }
+        std::fs::create_dir_all(&tmp_rootfs).map_err(|e| e.to_string())?;
+        for blob in layers.iter() {
+            Self::extract_layer(blob, &tmp_rootfs)?;
+        }
+        std::fs::rename(&tmp_rootfs, &rootfs).map_err(|e| e.to_string())?;
+        Self::touch(&rootfs);
+
+        return Ok((digest, rootfs));
+    }
+
+    /// # 镜像展开后的文件系统在缓存中的目录
+    ///
+    /// 包括所有架构下的目录，用于清理任务拉取的镜像
+    pub fn cached_rootfs_dirs(digest: &str) -> Vec<PathBuf> {
+        let prefix = format!("{}-", digest.replace(':', "-"));
+        let read_dir = match std::fs::read_dir(Self::oci_cache_dir().join("rootfs")) {
+            Ok(read_dir) => read_dir,
+            Err(_) => return Vec::new(),
+        };
+        let mut dirs: Vec<PathBuf> = read_dir
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_name().to_string_lossy().starts_with(&prefix))
+            .map(|e| e.path())
+            .collect();
+        dirs.sort();
+        return dirs;
+    }
+
+    /// 记录缓存中的镜像文件系统或者镜像层被访问
+    fn touch(path: &Path) {
+        let category = if path.parent().is_some_and(|p| p.ends_with("blobs")) {
+            CacheCategory::OciBlobs
+        } else {
+            CacheCategory::OciRootfs
+        };
+        gc::touch(category, &path.file_name().unwrap().to_string_lossy());
+    }
+
+    fn oci_cache_dir() -> PathBuf {
+        return CACHE_ROOT.get().join("oci");
+    }
+
+    fn rootfs_dir(&self, digest: &str) -> PathBuf {
+        return Self::oci_cache_dir().join("rootfs").join(format!(
+            "{}-{}",
+            digest.replace(':', "-"),
+            self.arch
+        ));
+    }
+
+    /// # 把一个镜像层展开到文件系统中
+    ///
+    /// 镜像层会先被解压到临时目录，处理完whiteout文件后，再合并到文件系统中
+    fn extract_layer(blob: &Path, rootfs: &Path) -> Result<(), String> {
+        let layer_dir = rootfs.with_extension("layer");
+        if layer_dir.exists() {
+            std::fs::remove_dir_all(&layer_dir).map_err(|e| e.to_string())?;
+        }
+        std::fs::create_dir_all(&layer_dir).map_err(|e| e.to_string())?;
+
+        let mut cmd = Command::new("tar");
+        cmd.arg("-xf")
+            .arg(blob)
+            .arg("--no-same-owner")
+            .arg("-C")
+            .arg(&layer_dir);
+        let proc: std::process::Child = cmd
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| e.to_string())?;
+        let output = proc.wait_with_output().map_err(|e| e.to_string())?;
+        if !output.status.success() {
+            return Err(format!(
+                "Failed to extract layer {}, message: {}",
+                blob.display(),
+                StdioUtils::tail_n_str(StdioUtils::stderr_to_lines(&output.stderr), 5)
+            ));
+        }
+
+        Self::apply_layer(&layer_dir, rootfs)?;
+        std::fs::remove_dir_all(&layer_dir).map_err(|e| e.to_string())?;
+        return Ok(());
+    }
+
+    /// # 把已解压的镜像层合并到文件系统中
+    ///
+    /// 会先处理镜像层中的whiteout文件（删除下层中的文件），然后把镜像层中的文件覆盖到文件系统中
+    pub fn apply_layer(layer_dir: &Path, rootfs: &Path) -> Result<(), String> {
+        std::fs::create_dir_all(rootfs).map_err(|e| e.to_string())?;
+        let root = rootfs.canonicalize().map_err(|e| e.to_string())?;
+        Self::apply_whiteouts(layer_dir, &root, Path::new(""))?;
+        return FileUtils::copy_dir_all(layer_dir, rootfs);
+    }
+
+    /// # 处理镜像层中的whiteout文件
+    ///
+    /// `root`为规范化后的文件系统根目录，`relative`为当前处理的目录相对于镜像层根目录的路径。
+    /// 下层中的符号链接不会被跟随：要删除的内容所在的目录规范化后必须仍在`root`之下
+    fn apply_whiteouts(layer_dir: &Path, root: &Path, relative: &Path) -> Result<(), String> {
+        for entry in layer_dir.read_dir().map_err(|e| e.to_string())? {
+            let entry = entry.map_err(|e| e.to_string())?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            let file_type = entry.file_type().map_err(|e| e.to_string())?;
+
+            if name == ".wh..wh..opq" {
+                // 不透明目录：删除下层中该目录下的所有内容
+                if let Some(dir) = Self::lower_dir(root, relative)? {
+                    for old in dir.read_dir().map_err(|e| e.to_string())? {
+                        Self::remove_path(&old.map_err(|e| e.to_string())?.path())?;
+                    }
+                }
+                std::fs::remove_file(entry.path()).map_err(|e| e.to_string())?;
+            } else if let Some(target) = name.strip_prefix(".wh.") {
+                // whiteout文件：删除下层中对应的文件
+                let mut components = Path::new(target).components();
+                if !matches!(
+                    (components.next(), components.next()),
+                    (Some(Component::Normal(_)), None)
+                ) {
+                    return Err(format!(
+                        "Invalid whiteout {}",
+                        relative.join(&name).display()
+                    ));
+                }
+                if let Some(dir) = Self::lower_dir(root, relative)? {
+                    let target = dir.join(target);
+                    debug!("whiteout: {}", target.display());
+                    Self::remove_path(&target)?;
+                }
+                std::fs::remove_file(entry.path()).map_err(|e| e.to_string())?;
+            } else if file_type.is_dir() {
+                Self::apply_whiteouts(&entry.path(), root, &relative.join(&name))?;
+            }
+        }
+        return Ok(());
+    }
+
+    /// # 下层中与镜像层目录对应的目录
+    ///
+    /// 返回规范化后的路径，目录不存在时返回None。
+    /// 路径中的符号链接指向`root`之外时返回错误，避免删除文件系统之外的文件
+    fn lower_dir(root: &Path, relative: &Path) -> Result<Option<PathBuf>, String> {
+        let dir = match root.join(relative).canonicalize() {
+            Ok(dir) => dir,
+            Err(_) => return Ok(None),
+        };
+        if !dir.starts_with(root) {
+            return Err(format!(
+                "Whiteout in {} escapes the rootfs through a symlink",
+                relative.display()
+            ));
+        }
+        if !dir.is_dir() {
+            return Ok(None);
+        }
+        return Ok(Some(dir));
+    }
+
+    fn remove_path(path: &Path) -> Result<(), String> {
+        let metadata = match std::fs::symlink_metadata(path) {
+            Ok(m) => m,
+            Err(_) => return Ok(()),
+        };
+        if metadata.is_dir() {
+            std::fs::remove_dir_all(path).map_err(|e| e.to_string())?;
+        } else {
+            std::fs::remove_file(path).map_err(|e| e.to_string())?;
+        }
+        return Ok(());
+    }
+}
+
+/// # 镜像manifest
+///
+/// 同时兼容镜像索引（多平台镜像）和单平台镜像的manifest
+#[derive(Debug, Clone, Deserialize)]
+struct Manifest {
+    /// 镜像索引中的各个平台的manifest
+    #[serde(default)]
+    manifests: Vec<Descriptor>,
+    /// 镜像的各层
+    #[serde(default)]
+    layers: Vec<Descriptor>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct Descriptor {
+    digest: String,
+    #[serde(default)]
+    platform: Option<Platform>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct Platform {
+    architecture: String,
+    os: String,
+}
+
+/// # 镜像仓库客户端
+///
+/// 实现了OCI Distribution规范中拉取镜像所需的部分，支持Basic和Bearer Token认证
+struct RegistryClient {
+    image: ImageReference,
+    client: Client,
+    /// 用户名和密码
+    credentials: Option<(String, String)>,
+    /// 当前使用的Authorization请求头
+    authorization: Option<String>,
+}
+
+impl RegistryClient {
+    fn new(image: ImageReference) -> Result<Self, String> {
+        let client = ClientBuilder::new()
+            .timeout(std::time::Duration::from_secs(300))
+            .build()
+            .map_err(|e| e.to_string())?;
+        let credentials = Self::load_credentials(&image.registry);
+        return Ok(Self {
+            image,
+            client,
+            credentials,
+            authorization: None,
+        });
+    }
+
+    /// # 解析manifest
+    ///
+    /// ## 返回值
+    ///
+    /// - `Ok((digest, manifest))` - manifest的digest以及manifest本身
+    fn resolve_manifest(&mut self, reference: &str) -> Result<(String, Manifest), String> {
+        let url = format!("{}/manifests/{}", self.image.api_base(), reference);
+        let response = self.get(&url, Some(MANIFEST_ACCEPT))?;
+        let body = response.bytes().map_err(|e| e.to_string())?;
+        let digest = format!("sha256:{}", HashUtils::sha256_bytes(&body));
+        if reference.starts_with("sha256:") && reference != digest {
+            return Err(format!(
+                "manifest digest mismatch, expected {}, got {}",
+                reference, digest
+            ));
+        }
+        let manifest: Manifest = serde_json::from_slice(&body)
+            .map_err(|e| format!("Failed to parse manifest of {}: {}", url, e))?;
+        return Ok((digest, manifest));
+    }
+
+    /// # 下载镜像层
+    ///
+    /// 如果镜像层已经下载过并且校验通过，则不会重复下载
+    fn fetch_blob(&mut self, digest: &str, dest: &Path) -> Result<(), String> {
+        let expected = digest
+            .strip_prefix("sha256:")
+            .ok_or(format!("unsupported digest algorithm: {}", digest))?;
+
+        if dest.is_file() && HashUtils::sha256_file(dest).map_err(|e| e.to_string())? == expected {
+            debug!("blob {} already cached", digest);
+            return Ok(());
+        }
+
+        info!("downloading blob {}", digest);
+        let url = format!("{}/blobs/{}", self.image.api_base(), digest);
+        let mut response = self.get(&url, None)?;
+        let tmp = dest.with_extension("partial");
+        let mut file = File::create(&tmp).map_err(|e| e.to_string())?;
+        response.copy_to(&mut file).map_err(|e| e.to_string())?;
+        drop(file);
+
+        let actual = HashUtils::sha256_file(&tmp).map_err(|e| e.to_string())?;
+        if actual != expected {
+            std::fs::remove_file(&tmp).map_err(|e| e.to_string())?;
+            return Err(format!(
+                "blob digest mismatch, expected {}, got sha256:{}",
+                digest, actual
+            ));
+        }
+        std::fs::rename(&tmp, dest).map_err(|e| e.to_string())?;
+        return Ok(());
+    }
+
+    /// 发送GET请求，如果仓库要求认证，则认证后重试一次
+    fn get(&mut self, url: &str, accept: Option<&str>) -> Result<Response, String> {
+        let response = self
+            .request(url, accept)
+            .send()
+            .map_err(|e| e.to_string())?;
+        let response = if response.status() == StatusCode::UNAUTHORIZED {
+            let challenge = response
+                .headers()
+                .get(WWW_AUTHENTICATE)
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or_default()
+                .to_string();
+            self.authenticate(&challenge)?;
+            self.request(url, accept)
+                .send()
+                .map_err(|e| e.to_string())?
+        } else {
+            response
+        };
+
+        if !response.status().is_success() {
+            return Err(format!(
+                "Failed to get {}, status: {}",
+                url,
+                response.status()
+            ));
+        }
+        return Ok(response);
+    }
+
+    fn request(&self, url: &str, accept: Option<&str>) -> RequestBuilder {
+        let mut request = self.client.get(url);
+        if let Some(accept) = accept {
+            request = request.header(ACCEPT, accept);
+        }
+        if let Some(authorization) = &self.authorization {
+            request = request.header(AUTHORIZATION, authorization);
+        }
+        return request;
+    }
+
+    /// # 根据仓库返回的认证质询进行认证
+    fn authenticate(&mut self, challenge: &str) -> Result<(), String> {
+        if let Some(params) = challenge.strip_prefix("Bearer ") {
+            let params = Self::parse_challenge_params(params);
+            let realm = params
+                .iter()
+                .find(|(k, _)| k == "realm")
+                .map(|(_, v)| v.clone())
+                .ok_or(format!("invalid auth challenge: {:?}", challenge))?;
+            let query: Vec<(String, String)> = params
+                .into_iter()
+                .filter(|(k, _)| k == "service" || k == "scope")
+                .collect();
+
+            let mut request = self.client.get(&realm).query(&query);
+            if let Some((username, password)) = &self.credentials {
+                request = request.basic_auth(username, Some(password));
+            }
+            let response = request.send().map_err(|e| e.to_string())?;
+            if !response.status().is_success() {
+                return Err(format!(
+                    "Failed to get token from {}, status: {}",
+                    realm,
+                    response.status()
+                ));
+            }
+
+            #[derive(Deserialize)]
+            struct TokenResponse {
+                token: Option<String>,
+                access_token: Option<String>,
+            }
+            let token: TokenResponse = response.json().map_err(|e| e.to_string())?;
+            let token = token
+                .token
+                .or(token.access_token)
+                .ok_or(format!("no token returned from {}", realm))?;
+            self.authorization = Some(format!("Bearer {}", token));
+        } else if challenge.starts_with("Basic") {
+            let (username, password) = self.credentials.as_ref().ok_or(format!(
+                "registry {} requires authentication, but no credentials found",
+                self.image.registry
+            ))?;
+            let encoded = base64::engine::general_purpose::STANDARD
+                .encode(format!("{}:{}", username, password));
+            self.authorization = Some(format!("Basic {}", encoded));
+        } else {
+            return Err(format!(
+                "unsupported auth challenge from registry {}: {:?}",
+                self.image.registry, challenge
+            ));
+        }
+        return Ok(());
+    }
+
+    /// 解析`key="value",key="value"`形式的认证参数
+    fn parse_challenge_params(params: &str) -> Vec<(String, String)> {
+        let re = Regex::new(r#"(\w+)="([^"]*)""#).unwrap();
+        return re
+            .captures_iter(params)
+            .map(|c| (c[1].to_string(), c[2].to_string()))
+            .collect();
+    }
+
+    /// # 从Docker配置文件中读取仓库的认证信息
+    fn load_credentials(registry: &str) -> Option<(String, String)> {
+        let config_dir = match std::env::var("DOCKER_CONFIG") {
+            Ok(dir) => PathBuf::from(dir),
+            Err(_) => PathBuf::from(std::env::var("HOME").ok()?).join(".docker"),
+        };
+        let content = std::fs::read_to_string(config_dir.join("config.json")).ok()?;
+        let config: serde_json::Value = serde_json::from_str(&content).ok()?;
+        let auths = config.get("auths")?.as_object()?;
+
+        let keys = if registry == DOCKER_HUB_REGISTRY {
+            vec![
+                "https://index.docker.io/v1/".to_string(),
+                DOCKER_HUB_REGISTRY.to_string(),
+            ]
+        } else {
+            vec![registry.to_string(), format!("https://{}", registry)]
+        };
+        let entry = keys.iter().find_map(|k| auths.get(k))?;
+
+        if let Some(auth) = entry.get("auth").and_then(|a| a.as_str()) {
+            let decoded = base64::engine::general_purpose::STANDARD
+                .decode(auth)
+                .ok()?;
+            let decoded = String::from_utf8(decoded).ok()?;
+            let (username, password) = decoded.split_once(':')?;
+            return Some((username.to_string(), password.to_string()));
+        }
+
+        let username = entry.get("username")?.as_str()?;
+        let password = entry.get("password")?.as_str()?;
+        return Some((username.to_string(), password.to_string()));
+    }
+}
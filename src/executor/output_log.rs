@@ -0,0 +1,195 @@
+//! # 任务输出日志
+//!
+//! 每个任务执行时，其命令的标准输出和标准错误会在输出到终端的同时写入
+//! `<缓存根目录>/logs/<name_version>.log`，日志中带有阶段标记和时间戳。
+//! 任务失败时，在终端中输出日志的最后若干行以及日志的路径，便于在CI中定位问题。
+
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Read, Write},
+    path::{Path, PathBuf},
+    sync::{Mutex, RwLock},
+};
+
+use chrono::Local;
+
+use super::cache::CACHE_ROOT;
+
+lazy_static! {
+    static ref OUTPUT_LOG_CONFIG: RwLock<OutputLogConfig> = RwLock::new(OutputLogConfig::default());
+}
+
+/// # 任务输出日志的配置
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutputLogConfig {
+    /// 是否在日志文件名中加入时间戳（否则每次执行都覆盖上一次的日志）
+    pub timestamped: bool,
+    /// 任务失败时输出的日志行数
+    pub tail_lines: usize,
+}
+
+impl OutputLogConfig {
+    pub const DEFAULT_TAIL_LINES: usize = 50;
+}
+
+impl Default for OutputLogConfig {
+    fn default() -> Self {
+        Self {
+            timestamped: false,
+            tail_lines: Self::DEFAULT_TAIL_LINES,
+        }
+    }
+}
+
+/// # 任务的输出日志文件
+#[derive(Debug)]
+pub struct OutputLog {
+    path: PathBuf,
+    file: Mutex<File>,
+}
+
+impl OutputLog {
+    const TIME_FORMAT: &'static str = "%Y-%m-%d %H:%M:%S";
+
+    /// 设置全局的输出日志配置
+    pub fn configure(config: OutputLogConfig) {
+        *OUTPUT_LOG_CONFIG.write().unwrap() = config;
+    }
+
+    pub fn config() -> OutputLogConfig {
+        return *OUTPUT_LOG_CONFIG.read().unwrap();
+    }
+
+    /// 日志目录
+    pub fn logs_dir() -> PathBuf {
+        return CACHE_ROOT.get().join("logs");
+    }
+
+    /// # 为任务创建日志文件
+    ///
+    /// 没有开启时间戳时，覆盖上一次执行的日志
+    pub fn create(name_version: &str) -> std::io::Result<Self> {
+        let dir = Self::logs_dir();
+        std::fs::create_dir_all(&dir)?;
+        let file_name = if Self::config().timestamped {
+            format!(
+                "{}-{}.log",
+                name_version,
+                Local::now().format("%Y%m%d%H%M%S")
+            )
+        } else {
+            format!("{}.log", name_version)
+        };
+        let path = dir.join(file_name);
+        let file = File::create(&path)?;
+        return Ok(Self {
+            path,
+            file: Mutex::new(file),
+        });
+    }
+
+    /// # 查找任务最近一次执行的日志
+    pub fn latest(name_version: &str) -> Option<PathBuf> {
+        return Self::previous_logs(name_version)
+            .ok()?
+            .into_iter()
+            .filter_map(|path| Some((path.metadata().ok()?.modified().ok()?, path)))
+            .max()
+            .map(|(_, path)| path);
+    }
+
+    /// # 列出任务的所有日志，按路径排序
+    ///
+    /// 日志目录不存在时返回空列表
+    pub fn previous_logs(name_version: &str) -> std::io::Result<Vec<PathBuf>> {
+        let plain = format!("{}.log", name_version);
+        let prefix = format!("{}-", name_version);
+        let entries = match Self::logs_dir().read_dir() {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let mut logs = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            // 时间戳部分只包含数字，避免把名称以本任务名称开头的其他任务的日志当成本任务的
+            let timestamped = name
+                .strip_prefix(&prefix)
+                .and_then(|rest| rest.strip_suffix(".log"))
+                .is_some_and(|ts| !ts.is_empty() && ts.chars().all(|c| c.is_ascii_digit()));
+            if name == plain || timestamped {
+                logs.push(entry.path());
+            }
+        }
+        logs.sort();
+        return Ok(logs);
+    }
+
+    pub fn path(&self) -> &Path {
+        return &self.path;
+    }
+
+    /// 写入阶段标记
+    pub fn phase(&self, phase: &str) {
+        self.write_line(&format!("==== [{}] phase: {} ====", Self::now(), phase));
+    }
+
+    /// 写入即将执行的命令
+    pub fn command(&self, command: &str) {
+        self.write_line(&format!("[{}] $ {}", Self::now(), command));
+    }
+
+    /// 写入一行信息（带时间戳）
+    pub fn message(&self, msg: &str) {
+        self.write_line(&format!("[{}] {}", Self::now(), msg));
+    }
+
+    /// # 写入子进程输出的一行
+    ///
+    /// 持有锁写入整行，多个输出流同时写入时不会在一行内交错
+    pub fn write_output(&self, line: &[u8]) {
+        let mut file = self.file.lock().unwrap();
+        file.write_all(line).ok();
+        if !line.ends_with(b"\n") {
+            file.write_all(b"\n").ok();
+        }
+    }
+
+    /// 读取日志的最后`n`行
+    pub fn tail(&self, n: usize) -> Vec<String> {
+        let content = std::fs::read(&self.path).unwrap_or_default();
+        let content = String::from_utf8_lossy(&content);
+        let lines: Vec<&str> = content.lines().collect();
+        let start = lines.len().saturating_sub(n);
+        return lines[start..].iter().map(|l| l.to_string()).collect();
+    }
+
+    fn write_line(&self, line: &str) {
+        let mut file = self.file.lock().unwrap();
+        writeln!(file, "{}", line).ok();
+    }
+
+    fn now() -> String {
+        return Local::now().format(Self::TIME_FORMAT).to_string();
+    }
+}
+
+/// # 逐行转发子进程的输出
+///
+/// 每读到一行，先原样输出到终端，再交给`on_line`处理（例如写入日志、统计警告）
+pub fn tee_lines<R: Read>(reader: R, mut console: impl Write, mut on_line: impl FnMut(&[u8])) {
+    let mut reader = BufReader::new(reader);
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        match reader.read_until(b'\n', &mut line) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {
+                console.write_all(&line).ok();
+                console.flush().ok();
+                on_line(&line);
+            }
+        }
+    }
+}
@@ -0,0 +1,168 @@
+//! # 安装的文件的属主
+//!
+//! `install.files`中的项可以设置`uid`、`gid`。DADK以root身份运行时，安装后直接`chown`；
+//! 否则（或者设置了环境变量`DADK_RECORD_OWNERSHIP=1`时）不修改属主，改为把期望的属主和权限记录到sysroot旁边的属主数据库中（类似fakeroot的保存文件），
+//! 由构建DragonOS根文件系统镜像的工具在打包时应用。
+//!
+//! 数据库是sysroot目录同级的文本文件`<sysroot>.dadk-ownership`，第一行是版本头，之后每行一项：
+//!
+//! ```text
+//! # dadk-ownership v1
+//! <mode> <uid> <gid> <path>
+//! ```
+//!
+//! - `mode`：八进制的完整权限，包括setuid/setgid/sticky位，例如`4755`
+//! - `uid`、`gid`：十进制数字，`-`表示不修改
+//! - `path`：DragonOS内的绝对路径，放在最后，可以包含空格
+//!
+//! 同一个路径只保留最后一次记录。多个任务并行安装时通过文件锁互斥。
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use crate::utils::file_lock::FileLock;
+
+/// 属主数据库的版本头
+pub const OWNERSHIP_DB_HEADER: &str = "# dadk-ownership v1";
+/// 属主数据库文件名的后缀，追加在sysroot目录名之后
+pub const OWNERSHIP_DB_SUFFIX: &str = ".dadk-ownership";
+/// 设置为`1`时总是记录属主而不直接`chown`的环境变量
+pub const RECORD_OWNERSHIP_ENV_KEY: &str = "DADK_RECORD_OWNERSHIP";
+
+/// # 一个文件期望的属主和权限
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ownership {
+    pub mode: u32,
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+}
+
+/// # 属主数据库
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct OwnershipDb {
+    /// DragonOS内的路径 -> 属主
+    entries: BTreeMap<PathBuf, Ownership>,
+}
+
+impl OwnershipDb {
+    /// # sysroot对应的属主数据库路径
+    pub fn path_for(sysroot: &Path) -> PathBuf {
+        let mut name = sysroot
+            .file_name()
+            .map(|n| n.to_os_string())
+            .unwrap_or_else(|| "sysroot".into());
+        name.push(OWNERSHIP_DB_SUFFIX);
+        return sysroot.with_file_name(name);
+    }
+
+    #[allow(dead_code)]
+    pub fn entries(&self) -> &BTreeMap<PathBuf, Ownership> {
+        return &self.entries;
+    }
+
+    /// # 解析属主数据库
+    pub fn parse(content: &str) -> Result<Self, String> {
+        let mut lines = content.lines();
+        match lines.next() {
+            None => return Ok(Self::default()),
+            Some(header) if header.trim() == OWNERSHIP_DB_HEADER => {}
+            Some(header) => {
+                return Err(format!(
+                    "unsupported ownership database header {:?}, expected {:?}",
+                    header, OWNERSHIP_DB_HEADER
+                ))
+            }
+        }
+        let mut db = Self::default();
+        for (i, line) in lines.enumerate() {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let err = |msg: &str| format!("line {}: {}: {:?}", i + 2, msg, line);
+            let mut fields = line.splitn(4, ' ');
+            let (mode, uid, gid, path) =
+                match (fields.next(), fields.next(), fields.next(), fields.next()) {
+                    (Some(mode), Some(uid), Some(gid), Some(path)) => (mode, uid, gid, path),
+                    _ => return Err(err("expected `<mode> <uid> <gid> <path>`")),
+                };
+            let mode = u32::from_str_radix(mode, 8).map_err(|_| err("invalid mode"))?;
+            let id = |s: &str| -> Result<Option<u32>, String> {
+                if s == "-" {
+                    return Ok(None);
+                }
+                return s.parse().map(Some).map_err(|_| err("invalid uid or gid"));
+            };
+            let path = PathBuf::from(path);
+            if !path.has_root() {
+                return Err(err("path should be absolute"));
+            }
+            db.entries.insert(
+                path,
+                Ownership {
+                    mode,
+                    uid: id(uid)?,
+                    gid: id(gid)?,
+                },
+            );
+        }
+        return Ok(db);
+    }
+
+    /// # 序列化属主数据库
+    pub fn serialize(&self) -> String {
+        let id = |id: Option<u32>| id.map_or("-".to_string(), |id| id.to_string());
+        let mut content = format!("{}\n", OWNERSHIP_DB_HEADER);
+        for (path, ownership) in self.entries.iter() {
+            content.push_str(&format!(
+                "{:04o} {} {} {}\n",
+                ownership.mode,
+                id(ownership.uid),
+                id(ownership.gid),
+                path.display()
+            ));
+        }
+        return content;
+    }
+
+    /// # 读取属主数据库，不存在时返回空数据库
+    pub fn load(path: &Path) -> Result<Self, String> {
+        match std::fs::read_to_string(path) {
+            Ok(content) => {
+                return Self::parse(&content).map_err(|e| format!("{}: {}", path.display(), e))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+        }
+    }
+
+    /// # 把`entries`合并到sysroot的属主数据库中
+    ///
+    /// 返回数据库的路径
+    pub fn record(
+        sysroot: &Path,
+        entries: impl IntoIterator<Item = (PathBuf, Ownership)>,
+    ) -> Result<PathBuf, String> {
+        let path = Self::path_for(sysroot);
+        let mut lock_name = path.as_os_str().to_os_string();
+        lock_name.push(".lock");
+        let _lock = FileLock::acquire(Path::new(&lock_name))
+            .map_err(|e| format!("Failed to lock {}: {}", path.display(), e))?;
+        let mut db = Self::load(&path)?;
+        db.entries.extend(entries);
+        std::fs::write(&path, db.serialize())
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        return Ok(path);
+    }
+}
+
+/// # 当前进程是否可以直接修改文件的属主
+///
+/// 以root身份运行时可以；设置了环境变量`DADK_RECORD_OWNERSHIP=1`时，即使是root也只记录到属主数据库
+pub fn can_chown() -> bool {
+    if std::env::var(RECORD_OWNERSHIP_ENV_KEY).map_or(false, |v| v == "1") {
+        return false;
+    }
+    return unsafe { libc::geteuid() } == 0;
+}
@@ -0,0 +1,340 @@
+//! # 安装计划
+//!
+//! `dadk install --dry-run`按与安装相同的规则（`include`、`exclude`、`destinations`、`files`、
+//! `symlinks`、`install.docs`等）计算每个任务要拷贝的文件和要创建的符号链接，
+//! 检查任务之间的安装路径冲突，但不修改sysroot、安装清单和任务日志。
+//! 实际安装时在拷贝文件之前按同样的规则检查冲突，有冲突的任务不会修改sysroot。
+//! `dadk install --diff`把计划与各任务当前的安装清单比较，只输出变化。
+//!
+//! 已经从配置文件中移除、但仍然安装在sysroot中的任务，计划中列出`dadk uninstall --prune-removed`会删除的文件。
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    parser::task::DocsPolicy,
+    scheduler::SchedEntity,
+    utils::{file::FileUtils, ignore::IgnoreMatcher},
+};
+
+use super::{
+    docs::{self, DocsAction},
+    manifest::{InstallManifest, ManifestEntryKind},
+};
+
+/// # 计划中的一项操作
+///
+/// 路径都是DragonOS内的绝对路径
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlannedOp {
+    /// 从构建结果中拷贝文件
+    Copy {
+        /// 构建结果中的文件（主机上的路径）
+        src: PathBuf,
+        dest: PathBuf,
+        /// 源文件的sha256值
+        sha256: String,
+        /// 安装时文件内容会被改变（剥离符号、压缩文档），安装后的sha256值无法预先计算
+        transformed: bool,
+    },
+    /// 创建符号链接
+    Symlink { link: PathBuf, target: PathBuf },
+    /// 删除文件或符号链接
+    Remove { path: PathBuf },
+}
+
+impl PlannedOp {
+    pub fn path(&self) -> &Path {
+        return match self {
+            PlannedOp::Copy { dest, .. } => dest,
+            PlannedOp::Symlink { link, .. } => link,
+            PlannedOp::Remove { path } => path,
+        };
+    }
+
+    /// 与安装清单中的项相同格式的内容描述，删除操作没有内容
+    fn content(&self) -> Option<String> {
+        return match self {
+            PlannedOp::Copy { sha256, .. } => Some(format!("sha256 {}", sha256)),
+            PlannedOp::Symlink { target, .. } => Some(format!("symlink -> {}", target.display())),
+            PlannedOp::Remove { .. } => None,
+        };
+    }
+}
+
+/// # 与当前安装清单相比的变化
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PlanChange {
+    /// 新安装的路径
+    Added,
+    /// 内容改变的路径
+    Changed,
+    /// 不再由该任务安装的路径
+    Removed,
+}
+
+impl PlanChange {
+    pub fn symbol(&self) -> char {
+        return match self {
+            PlanChange::Added => '+',
+            PlanChange::Changed => '~',
+            PlanChange::Removed => '-',
+        };
+    }
+}
+
+/// # 一个任务的安装计划
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TaskPlan {
+    /// 任务（`name_version`）
+    pub task: String,
+    /// 按执行顺序排列的操作
+    pub ops: Vec<PlannedOp>,
+    /// 安装后是否会执行`post_install`钩子（钩子创建的文件无法预先得知）
+    pub post_install: bool,
+    /// 设置了`install_once`并且已经安装，不会重新安装
+    pub up_to_date: bool,
+    /// 任务已经从配置文件中移除，计划中是卸载它时删除的文件
+    pub removed: bool,
+    /// 与之前的任务的安装路径冲突
+    pub conflicts: Vec<String>,
+    /// 计算计划时的错误（例如配置错误、构建结果中缺少要安装的文件）
+    pub errors: Vec<String>,
+}
+
+impl TaskPlan {
+    pub fn new(task: String) -> Self {
+        return Self {
+            task,
+            ..Default::default()
+        };
+    }
+
+    /// # 卸载已经从配置文件中移除的任务的计划
+    ///
+    /// 删除清单中的文件和符号链接，目录只在变为空时删除，因此不列出
+    pub fn removal(manifest: &InstallManifest) -> Self {
+        let mut plan = Self::new(manifest.task.clone());
+        plan.removed = true;
+        plan.ops = manifest
+            .entries
+            .iter()
+            .filter(|e| e.kind != ManifestEntryKind::Dir)
+            .map(|e| PlannedOp::Remove {
+                path: e.path.clone(),
+            })
+            .collect();
+        return plan;
+    }
+
+    /// 是否有冲突或者错误
+    pub fn has_problems(&self) -> bool {
+        return !self.conflicts.is_empty() || !self.errors.is_empty();
+    }
+
+    /// # 按`install.docs`、`install.locales`调整计划
+    ///
+    /// 与安装时的处理相同：删除的文件从计划中去掉，压缩的文件加上`.gz`后缀，
+    /// 指向被压缩的文件的符号链接改为指向压缩后的文件
+    pub fn apply_docs(&mut self, policy: DocsPolicy, locales: Option<&[String]>) {
+        if policy == DocsPolicy::Keep && locales.is_none() {
+            return;
+        }
+        let action =
+            |path: &Path| docs::action_for(policy, locales, path.strip_prefix("/").unwrap_or(path));
+        let mut compressed = BTreeSet::new();
+        let mut ops = Vec::with_capacity(self.ops.len());
+        for op in std::mem::take(&mut self.ops) {
+            match (action(op.path()), op) {
+                (DocsAction::Remove(_), _) => {}
+                (
+                    DocsAction::Compress,
+                    PlannedOp::Copy {
+                        src, dest, sha256, ..
+                    },
+                ) => {
+                    ops.push(PlannedOp::Copy {
+                        src,
+                        dest: docs::gz_path(&dest),
+                        sha256,
+                        transformed: true,
+                    });
+                    compressed.insert(dest);
+                }
+                (_, op) => ops.push(op),
+            }
+        }
+        for op in ops.iter_mut() {
+            if let PlannedOp::Symlink { link, target } = op {
+                if action(link) != DocsAction::Compress {
+                    continue;
+                }
+                let resolved = FileUtils::normalize_path(&link.parent().unwrap().join(&*target));
+                if compressed.contains(&resolved) {
+                    *link = docs::gz_path(link);
+                    *target = docs::gz_path(target);
+                }
+            }
+        }
+        self.ops = ops;
+    }
+
+    /// # 与任务当前的安装清单比较
+    ///
+    /// 返回有变化的路径，按路径排序。安装时内容会被改变的文件无法预先比较，已经安装时总是视为改变
+    pub fn diff(&self, current: Option<&InstallManifest>) -> Vec<(PlanChange, PathBuf)> {
+        if self.up_to_date {
+            return Vec::new();
+        }
+        let installed: BTreeMap<&Path, String> = current
+            .map(|m| {
+                m.entries
+                    .iter()
+                    .filter(|e| e.kind != ManifestEntryKind::Dir)
+                    .map(|e| (e.path.as_path(), e.content()))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let mut changes = Vec::new();
+        let mut planned = BTreeSet::new();
+        for op in self.ops.iter() {
+            let path = op.path();
+            let change = match (installed.get(path), op) {
+                (Some(_), PlannedOp::Remove { .. }) => Some(PlanChange::Removed),
+                (None, PlannedOp::Remove { .. }) => None,
+                (None, _) => Some(PlanChange::Added),
+                (Some(_), PlannedOp::Copy { transformed, .. }) if *transformed => {
+                    Some(PlanChange::Changed)
+                }
+                (Some(old), op) => {
+                    (op.content().as_ref() != Some(old)).then_some(PlanChange::Changed)
+                }
+            };
+            if !matches!(op, PlannedOp::Remove { .. }) {
+                planned.insert(path);
+            }
+            if let Some(change) = change {
+                changes.push((change, path.to_path_buf()));
+            }
+        }
+        if !self.removed {
+            for path in installed.keys() {
+                if !planned.contains(path) {
+                    changes.push((PlanChange::Removed, path.to_path_buf()));
+                }
+            }
+        }
+        changes.sort_by(|a, b| a.1.cmp(&b.1));
+        changes.dedup();
+        return changes;
+    }
+}
+
+lazy_static! {
+    /// 本次执行中已经安装的路径：sysroot -> 各任务安装的路径
+    static ref INSTALLED_PATHS: Mutex<BTreeMap<PathBuf, PlannedPaths>> = Mutex::new(BTreeMap::new());
+}
+
+/// # 计划中各任务安装的路径
+///
+/// 按任务的安装顺序记录，用于检查冲突，`dadk install --dry-run`与实际安装使用相同的规则：
+///
+/// - 两个任务安装了同一路径，内容相同时允许
+/// - 内容不同时，后安装的任务需要在`install.allow_overwrite`中列出该路径，并且（直接或间接）依赖先安装的任务。
+///   互不依赖的任务可能并行安装，谁覆盖谁是不确定的
+#[derive(Debug, Default)]
+pub struct PlannedPaths {
+    /// DragonOS内的路径 -> (安装它的任务, 内容描述)
+    paths: BTreeMap<PathBuf, (Arc<SchedEntity>, String)>,
+}
+
+impl PlannedPaths {
+    /// # 检查任务的计划与之前的任务的计划是否冲突
+    ///
+    /// 冲突的路径记录到计划的`conflicts`中。有冲突时不记录该任务的路径
+    pub fn check(
+        &mut self,
+        plan: &mut TaskPlan,
+        entity: &Arc<SchedEntity>,
+        allow_overwrite: &IgnoreMatcher,
+    ) {
+        let mut conflicts = Vec::new();
+        let mut paths = Vec::new();
+        for op in plan.ops.iter() {
+            let content = match op.content() {
+                Some(content) => content,
+                None => continue,
+            };
+            let path = op.path();
+            if let Some((writer, prev)) = self.paths.get(path) {
+                let task = writer.task().name_version();
+                if task != plan.task {
+                    // 内容相同时保留先安装的任务
+                    if *prev == content {
+                        continue;
+                    }
+                    if !allow_overwrite.is_ignored(path.strip_prefix("/").unwrap_or(path)) {
+                        conflicts.push(format!(
+                            "{}: {} ({}) and {} ({})",
+                            path.display(),
+                            task,
+                            prev,
+                            plan.task,
+                            content
+                        ));
+                        continue;
+                    }
+                    if !entity.depends_on(writer) {
+                        conflicts.push(format!(
+                            "{}: {} ({}) and {} ({}), neither task depends on the other, \
+                            so the install order is not deterministic",
+                            path.display(),
+                            task,
+                            prev,
+                            plan.task,
+                            content
+                        ));
+                        continue;
+                    }
+                }
+            }
+            paths.push((path.to_path_buf(), content));
+        }
+        if !conflicts.is_empty() {
+            plan.conflicts.extend(conflicts);
+            return;
+        }
+        for (path, content) in paths {
+            self.paths.insert(path, (entity.clone(), content));
+        }
+    }
+}
+
+/// # 在安装之前检查任务的安装计划是否与本次执行中已经安装到同一sysroot的任务冲突
+///
+/// 规则见[`PlannedPaths`]。没有冲突时记录该任务安装的路径；有冲突时返回错误，
+/// 错误信息中包括两个任务、冲突的路径和各自的内容
+pub fn check_install_conflicts(
+    sysroot: &Path,
+    plan: &mut TaskPlan,
+    entity: &Arc<SchedEntity>,
+    allow_overwrite: &IgnoreMatcher,
+) -> Result<(), String> {
+    INSTALLED_PATHS
+        .lock()
+        .unwrap()
+        .entry(sysroot.to_path_buf())
+        .or_default()
+        .check(plan, entity, allow_overwrite);
+    if !plan.conflicts.is_empty() {
+        return Err(format!(
+            "install path conflicts, add the paths to install.allow_overwrite of the later task \
+            and make it depend on the earlier task if this is intended:\n  {}",
+            plan.conflicts.join("\n  ")
+        ));
+    }
+    return Ok(());
+}
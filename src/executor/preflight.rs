@@ -0,0 +1,188 @@
+//! # 源的预检
+//!
+//! 在开始耗时较长的构建之前，检查所有任务的源是否可以访问：
+//!
+//! - Git源：`git ls-remote`，指定了分支时同时检查分支是否存在
+//! - 在线压缩包：HTTP HEAD请求
+//! - 本地源：路径是否存在
+//! - 系统依赖：可执行文件是否存在，版本是否满足要求（见[`super::system_dep`]）
+//!
+//! 预检不会下载任何内容。所有源都会被检查，失败的源汇总到一个报告中，而不是在第一个失败时停止。
+//! 其他类型的源（Release附件、OCI镜像）不做预检。
+//!
+//! 预检是可选的，通过`--preflight`开启。
+
+use std::fmt::Display;
+
+use crate::parser::task::{CodeSource, DADKTask, Dependency, PrebuiltSource, TaskType};
+
+use super::{
+    source::{ArchiveSource, GitSource, HgSource, LocalSource, SvnSource},
+    system_dep,
+};
+
+/// 同时进行的检查数
+const PREFLIGHT_CONCURRENCY: usize = 16;
+
+/// 需要预检的源
+enum SourceCheck<'a> {
+    Git(&'a GitSource),
+    Hg(&'a HgSource),
+    Svn(&'a SvnSource),
+    Archive(&'a ArchiveSource),
+    Local(&'a LocalSource),
+    System(&'a Dependency),
+}
+
+impl SourceCheck<'_> {
+    fn describe(&self) -> String {
+        match self {
+            SourceCheck::Git(git) => match git.branch() {
+                Some(branch) => format!("git {} ({})", git.url(), branch),
+                None => format!("git {}", git.url()),
+            },
+            SourceCheck::Hg(hg) => match hg.branch() {
+                Some(branch) => format!("hg {} ({})", hg.url(), branch),
+                None => format!("hg {}", hg.url()),
+            },
+            SourceCheck::Svn(svn) => match svn.revision() {
+                Some(revision) => format!("svn {} (r{})", svn.url(), revision),
+                None => format!("svn {}", svn.url()),
+            },
+            SourceCheck::Archive(archive) => format!("archive {}", archive.url()),
+            SourceCheck::Local(local) => format!("local {}", local.path().display()),
+            SourceCheck::System(dep) => format!("system {} ({})", dep.name, dep.version),
+        }
+    }
+
+    fn run(&self) -> Result<(), String> {
+        match self {
+            SourceCheck::Git(git) => git.preflight(),
+            SourceCheck::Hg(hg) => hg.preflight(),
+            SourceCheck::Svn(svn) => svn.preflight(),
+            SourceCheck::Archive(archive) => archive.preflight(),
+            SourceCheck::Local(local) => local.preflight(),
+            SourceCheck::System(dep) => system_dep::check(dep).map(|_| ()),
+        }
+    }
+}
+
+/// # 预检失败的源
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreflightFailure {
+    /// 任务的`name_version()`
+    pub task: String,
+    /// 源的描述，例如`git https://...`
+    pub source: String,
+    pub error: String,
+}
+
+/// # 预检报告
+#[derive(Debug, Clone, Default)]
+pub struct PreflightReport {
+    /// 检查了的源的数量
+    pub checked: usize,
+    /// 检查失败的源，按任务的顺序排列
+    pub failures: Vec<PreflightFailure>,
+}
+
+impl PreflightReport {
+    pub fn is_ok(&self) -> bool {
+        return self.failures.is_empty();
+    }
+}
+
+impl Display for PreflightReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_ok() {
+            return write!(f, "Preflight: all {} sources are reachable", self.checked);
+        }
+        write!(
+            f,
+            "Preflight: {} of {} sources are unreachable:",
+            self.failures.len(),
+            self.checked
+        )?;
+        for failure in self.failures.iter() {
+            write!(
+                f,
+                "\n  {}: {}: {}",
+                failure.task, failure.source, failure.error
+            )?;
+        }
+        return Ok(());
+    }
+}
+
+/// # 检查任务的源是否可以访问
+pub fn preflight(tasks: &[DADKTask]) -> PreflightReport {
+    let mut checks: Vec<(String, SourceCheck)> = Vec::new();
+    for task in tasks {
+        let mut sources = Vec::new();
+        match &task.task_type {
+            TaskType::BuildFromSource(cs) | TaskType::KernelModule(cs) => {
+                code_source_checks(cs, &mut sources)
+            }
+            TaskType::InstallFromPrebuilt(ps) => match ps {
+                PrebuiltSource::Git(git) => sources.push(SourceCheck::Git(git)),
+                PrebuiltSource::Archive(archive) => sources.push(SourceCheck::Archive(archive)),
+                PrebuiltSource::Local(local) => sources.push(SourceCheck::Local(local)),
+                PrebuiltSource::Oci(_) | PrebuiltSource::ReleaseAsset(_) => {}
+            },
+            TaskType::NoSource | TaskType::Meta => {}
+        }
+        sources.extend(
+            task.depends
+                .iter()
+                .filter(|d| !d.is_task())
+                .map(SourceCheck::System),
+        );
+        checks.extend(sources.into_iter().map(|s| (task.name_version(), s)));
+    }
+
+    let mut report = PreflightReport {
+        checked: checks.len(),
+        failures: Vec::new(),
+    };
+    for chunk in checks.chunks(PREFLIGHT_CONCURRENCY) {
+        let results: Vec<Result<(), String>> = std::thread::scope(|s| {
+            let handles: Vec<_> = chunk
+                .iter()
+                .map(|(_, check)| s.spawn(move || check.run()))
+                .collect();
+            handles
+                .into_iter()
+                .map(|h| {
+                    h.join()
+                        .unwrap_or_else(|_| Err("preflight check panicked".to_string()))
+                })
+                .collect()
+        });
+        for ((task, check), result) in chunk.iter().zip(results) {
+            if let Err(error) = result {
+                report.failures.push(PreflightFailure {
+                    task: task.clone(),
+                    source: check.describe(),
+                    error,
+                });
+            }
+        }
+    }
+    return report;
+}
+
+fn code_source_checks<'a>(cs: &'a CodeSource, sources: &mut Vec<SourceCheck<'a>>) {
+    match cs {
+        CodeSource::Git(git) => sources.push(SourceCheck::Git(git)),
+        CodeSource::Hg(hg) => sources.push(SourceCheck::Hg(hg)),
+        CodeSource::Svn(svn) => sources.push(SourceCheck::Svn(svn)),
+        CodeSource::Archive(archive) => sources.push(SourceCheck::Archive(archive)),
+        CodeSource::Local(local) => sources.push(SourceCheck::Local(local)),
+        CodeSource::ReleaseAsset(_) => {}
+        CodeSource::Overlay(layers) => {
+            for layer in layers {
+                code_source_checks(layer, sources);
+            }
+        }
+    }
+}
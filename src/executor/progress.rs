@@ -0,0 +1,72 @@
+//! # 进度报告
+//!
+//! 下载、克隆、解压等耗时操作通过`ProgressReporter`报告进度。
+//! 默认的`NoopProgressReporter`不输出任何内容，命令行使用`LogProgressReporter`把进度打印到日志中。
+
+use std::{fmt::Debug, sync::Mutex};
+
+use log::info;
+
+/// 下载文件
+pub const STAGE_DOWNLOAD: &str = "download";
+/// 解压压缩包
+pub const STAGE_EXTRACT: &str = "extract";
+/// 克隆Git仓库
+pub const STAGE_CLONE: &str = "clone";
+/// 检出Git仓库的分支或者提交
+pub const STAGE_CHECKOUT: &str = "checkout";
+/// 拉取Git仓库的更新
+pub const STAGE_PULL: &str = "pull";
+/// 复用已有的克隆，获取远程仓库的更新
+pub const STAGE_FETCH: &str = "fetch";
+
+/// # 进度报告器
+///
+/// 所有方法都有默认的空实现，只需要实现关心的方法
+pub trait ProgressReporter: Debug + Send + Sync {
+    /// 进入一个新的阶段，`name`为`STAGE_*`常量之一
+    fn on_stage(&self, _name: &str) {}
+
+    /// 下载进度
+    ///
+    /// ## 参数
+    ///
+    /// * `bytes` - 已经下载的字节数
+    /// * `total` - 总字节数，服务器没有返回长度时为`None`
+    fn on_download_progress(&self, _bytes: u64, _total: Option<u64>) {}
+}
+
+/// 不报告任何进度
+#[derive(Debug, Default)]
+pub struct NoopProgressReporter;
+
+impl ProgressReporter for NoopProgressReporter {}
+
+/// # 把进度输出到日志
+///
+/// 下载进度每增加10%输出一次
+#[derive(Debug, Default)]
+pub struct LogProgressReporter {
+    /// 上一次输出的下载进度（百分比的十位）
+    last_decile: Mutex<Option<u64>>,
+}
+
+impl ProgressReporter for LogProgressReporter {
+    fn on_stage(&self, name: &str) {
+        *self.last_decile.lock().unwrap() = None;
+        info!("Stage: {}", name);
+    }
+
+    fn on_download_progress(&self, bytes: u64, total: Option<u64>) {
+        let total = match total {
+            Some(total) if total > 0 => total,
+            _ => return,
+        };
+        let decile = bytes.min(total) * 10 / total;
+        let mut last = self.last_decile.lock().unwrap();
+        if *last != Some(decile) {
+            *last = Some(decile);
+            info!("Downloaded {}% ({}/{} bytes)", decile * 10, bytes, total);
+        }
+    }
+}
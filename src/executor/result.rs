@@ -0,0 +1,154 @@
+//! # 任务执行结果
+//!
+//! 执行器在执行任务时记录各阶段的结果，调度器把所有任务的结果汇总返回，
+//! 便于调用者生成报告（可以序列化为JSON）。
+
+use std::{fmt::Write, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+use crate::parser::task::TargetArch;
+
+/// # 一个任务的执行结果
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TaskResult {
+    /// 任务的`name_version()`
+    pub name_version: String,
+    /// 目标架构
+    pub target_arch: TargetArch,
+    /// 构建阶段的结果，没有执行构建时为None
+    pub build: Option<PhaseResult>,
+    /// 安装阶段的结果，没有执行安装时为None
+    pub install: Option<PhaseResult>,
+    /// 清理阶段的结果，没有执行清理时为None
+    pub clean: Option<PhaseResult>,
+    /// 任务是否执行成功
+    pub success: bool,
+    /// 执行失败时的错误信息
+    pub error: Option<String>,
+    /// 构建输出中的警告数和错误数，没有执行构建时为None
+    #[serde(default)]
+    pub diagnostics: Option<DiagnosticCounts>,
+    /// 安装时剥离符号减少的字节数，没有开启`install.strip`时为None
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stripped_bytes: Option<u64>,
+    /// 安装时压缩或删除文档、翻译文件减少的字节数，没有设置`install.docs`、`install.locales`时为None
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub docs_saved_bytes: Option<u64>,
+    /// 清理时删除的字节数，没有执行清理时为None
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub freed_bytes: Option<u64>,
+    /// 任务是否因为`enabled_if`不成立而被跳过
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub disabled: bool,
+}
+
+impl TaskResult {
+    pub fn new(name_version: String, target_arch: TargetArch) -> Self {
+        Self {
+            name_version,
+            target_arch,
+            build: None,
+            install: None,
+            clean: None,
+            success: false,
+            error: None,
+            diagnostics: None,
+            stripped_bytes: None,
+            docs_saved_bytes: None,
+            freed_bytes: None,
+            disabled: false,
+        }
+    }
+
+    /// 因为`enabled_if`不成立而被跳过的任务的结果
+    pub fn disabled(name_version: String, target_arch: TargetArch) -> Self {
+        let mut result = Self::new(name_version, target_arch);
+        result.success = true;
+        result.disabled = true;
+        return result;
+    }
+}
+
+/// # 生成执行结束时的汇总信息
+///
+/// 每个任务一行，包括是否成功以及构建输出中的警告数和错误数
+pub fn summary(results: &[TaskResult]) -> String {
+    let failed = results.iter().filter(|r| !r.success).count();
+    let disabled = results.iter().filter(|r| r.disabled).count();
+    let mut s = format!(
+        "Summary: {} task(s), {} succeeded, {} failed",
+        results.len(),
+        results.len() - failed - disabled,
+        failed
+    );
+    if disabled > 0 {
+        write!(s, ", {} disabled", disabled).unwrap();
+    }
+    for r in results.iter() {
+        let status = if r.disabled {
+            "disabled"
+        } else if r.success {
+            "ok"
+        } else {
+            "failed"
+        };
+        write!(
+            s,
+            "\n  [{}] {} ({:?})",
+            status, r.name_version, r.target_arch
+        )
+        .unwrap();
+        if let Some(d) = &r.diagnostics {
+            write!(s, ", warnings: {}, errors: {}", d.warnings, d.errors).unwrap();
+        }
+        if let Some(bytes) = r.stripped_bytes {
+            write!(s, ", stripped: {} bytes", bytes).unwrap();
+        }
+        if let Some(bytes) = r.docs_saved_bytes {
+            write!(s, ", docs: {} bytes saved", bytes).unwrap();
+        }
+        if let Some(bytes) = r.freed_bytes {
+            write!(s, ", freed: {} bytes", bytes).unwrap();
+        }
+    }
+    return s;
+}
+
+/// # 一个阶段的执行结果
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PhaseResult {
+    /// 是否命中缓存，命中时没有执行任何命令
+    pub cache_hit: bool,
+    /// 执行时长（毫秒），命中缓存时为0
+    pub duration_ms: u64,
+    /// 最后一条执行的命令的退出码，没有执行命令或者被信号终止时为None
+    pub exit_code: Option<i32>,
+}
+
+impl PhaseResult {
+    /// 命中缓存的阶段
+    pub fn cached() -> Self {
+        Self {
+            cache_hit: true,
+            duration_ms: 0,
+            exit_code: None,
+        }
+    }
+
+    /// 实际执行了的阶段
+    pub fn executed(duration: Duration, exit_code: Option<i32>) -> Self {
+        Self {
+            cache_hit: false,
+            duration_ms: duration.as_millis() as u64,
+            exit_code,
+        }
+    }
+}
+
+/// # 构建输出中的警告数和错误数
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DiagnosticCounts {
+    pub warnings: usize,
+    pub errors: usize,
+}
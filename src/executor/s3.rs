@@ -0,0 +1,370 @@
+//! # S3对象存储
+//!
+//! 从S3兼容的对象存储中下载`s3://bucket/key`形式的对象，请求使用AWS Signature Version 4签名。
+//!
+//! 配置按AWS的惯例从环境变量和配置文件中读取：
+//!
+//! - 认证信息：`AWS_ACCESS_KEY_ID`、`AWS_SECRET_ACCESS_KEY`、`AWS_SESSION_TOKEN`，
+//!   没有设置时读取`AWS_SHARED_CREDENTIALS_FILE`（默认为`~/.aws/credentials`）中`AWS_PROFILE`（默认为`default`）的配置。
+//!   都没有时发送匿名请求
+//! - 区域：`AWS_REGION`、`AWS_DEFAULT_REGION`，或者`AWS_CONFIG_FILE`（默认为`~/.aws/config`）中的`region`，默认为`us-east-1`
+//! - 服务地址：`AWS_ENDPOINT_URL_S3`、`AWS_ENDPOINT_URL`，用于MinIO等S3兼容的服务。
+//!   设置了服务地址时使用路径形式（`<endpoint>/<bucket>/<key>`）访问对象
+
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use regex::Regex;
+use reqwest::{
+    blocking::{ClientBuilder, RequestBuilder, Response},
+    Url,
+};
+use sha2::Sha256;
+
+use crate::utils::hash::HashUtils;
+
+/// S3 URI的scheme
+pub const S3_SCHEME: &str = "s3";
+
+/// 没有配置区域时使用的区域
+const DEFAULT_REGION: &str = "us-east-1";
+
+/// 空请求体的sha256值
+const EMPTY_PAYLOAD_SHA256: &str =
+    "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+/// # S3对象的位置
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct S3Uri {
+    pub bucket: String,
+    pub key: String,
+}
+
+impl S3Uri {
+    /// # 解析`s3://bucket/key`形式的URI
+    ///
+    /// 桶名需要符合S3的命名规则，对象的key不能为空，也不能以`/`结尾
+    pub fn parse(uri: &str) -> Result<Self, String> {
+        let rest = uri
+            .strip_prefix("s3://")
+            .ok_or(format!("{:?} is not a s3:// uri", uri))?;
+        if rest.contains(['?', '#']) {
+            return Err(format!(
+                "s3 uri {:?} must not have a query or fragment",
+                uri
+            ));
+        }
+        let (bucket, key) = rest
+            .split_once('/')
+            .ok_or(format!("s3 uri {:?} has no object key", uri))?;
+        let bucket_re = Regex::new(r"^[a-z0-9][a-z0-9.-]{1,61}[a-z0-9]$").unwrap();
+        if !bucket_re.is_match(bucket) || bucket.contains("..") {
+            return Err(format!(
+                "s3 uri {:?} has an invalid bucket name {:?}",
+                uri, bucket
+            ));
+        }
+        if key.is_empty() || key.ends_with('/') {
+            return Err(format!("s3 uri {:?} has no object key", uri));
+        }
+        return Ok(Self {
+            bucket: bucket.to_string(),
+            key: key.to_string(),
+        });
+    }
+
+    /// 对象的文件名（key的最后一段）
+    pub fn file_name(&self) -> &str {
+        return self.key.rsplit('/').next().unwrap();
+    }
+}
+
+/// # S3的认证信息
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct S3Credentials {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    pub session_token: Option<String>,
+}
+
+/// # 访问S3所需的配置
+#[derive(Debug, Clone)]
+pub struct S3Config {
+    pub region: String,
+    /// 自定义的服务地址，为None时访问AWS
+    pub endpoint: Option<String>,
+    /// 为None时发送匿名请求
+    pub credentials: Option<S3Credentials>,
+}
+
+impl S3Config {
+    /// 从环境变量和AWS的配置文件中读取配置
+    pub fn from_env() -> Self {
+        let env = |key: &str| std::env::var(key).ok().filter(|v| !v.trim().is_empty());
+        let profile = env("AWS_PROFILE").unwrap_or("default".to_string());
+        let aws_dir = env("HOME").map(|home| PathBuf::from(home).join(".aws"));
+        let config_file = |env_key: &str, name: &str| {
+            env(env_key)
+                .map(PathBuf::from)
+                .or(aws_dir.as_ref().map(|d| d.join(name)))
+        };
+
+        let credentials = match (env("AWS_ACCESS_KEY_ID"), env("AWS_SECRET_ACCESS_KEY")) {
+            (Some(access_key_id), Some(secret_access_key)) => Some(S3Credentials {
+                access_key_id,
+                secret_access_key,
+                session_token: env("AWS_SESSION_TOKEN"),
+            }),
+            _ => config_file("AWS_SHARED_CREDENTIALS_FILE", "credentials").and_then(|f| {
+                let section = Self::profile_section(&f, &profile);
+                Some(S3Credentials {
+                    access_key_id: section.get("aws_access_key_id")?.clone(),
+                    secret_access_key: section.get("aws_secret_access_key")?.clone(),
+                    session_token: section.get("aws_session_token").cloned(),
+                })
+            }),
+        };
+
+        // config文件中，default以外的配置写作`[profile name]`
+        let config_section = if profile == "default" {
+            profile.clone()
+        } else {
+            format!("profile {}", profile)
+        };
+        let region = env("AWS_REGION")
+            .or(env("AWS_DEFAULT_REGION"))
+            .or(config_file("AWS_CONFIG_FILE", "config")
+                .and_then(|f| Self::profile_section(&f, &config_section).remove("region")))
+            .unwrap_or(DEFAULT_REGION.to_string());
+
+        return Self {
+            region,
+            endpoint: env("AWS_ENDPOINT_URL_S3").or(env("AWS_ENDPOINT_URL")),
+            credentials,
+        };
+    }
+
+    /// 读取ini格式的AWS配置文件中的一节，文件不存在时返回空
+    fn profile_section(file: &Path, section: &str) -> HashMap<String, String> {
+        let mut result = HashMap::new();
+        let content = match std::fs::read_to_string(file) {
+            Ok(content) => content,
+            Err(_) => return result,
+        };
+        let mut in_section = false;
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                in_section = name.trim() == section;
+                continue;
+            }
+            if in_section {
+                if let Some((k, v)) = line.split_once('=') {
+                    result.insert(k.trim().to_string(), v.trim().to_string());
+                }
+            }
+        }
+        return result;
+    }
+
+    /// 对象的URL
+    pub fn object_url(&self, uri: &S3Uri) -> Result<Url, String> {
+        let key = uri_encode(&uri.key, false);
+        let url = match &self.endpoint {
+            Some(endpoint) => format!("{}/{}/{}", endpoint.trim_end_matches('/'), uri.bucket, key),
+            // 桶名中有`.`时，虚拟主机形式的地址无法通过TLS证书校验
+            None if uri.bucket.contains('.') => format!(
+                "https://s3.{}.amazonaws.com/{}/{}",
+                self.region, uri.bucket, key
+            ),
+            None => format!(
+                "https://{}.s3.{}.amazonaws.com/{}",
+                uri.bucket, self.region, key
+            ),
+        };
+        return Url::parse(&url).map_err(|e| format!("invalid s3 url {:?}: {}", url, e));
+    }
+
+    /// # 构造获取对象的请求
+    ///
+    /// `head`为true时构造HEAD请求，只检查对象是否存在
+    pub fn request(&self, uri: &S3Uri, head: bool) -> Result<RequestBuilder, String> {
+        let url = self.object_url(uri)?;
+        let client = ClientBuilder::new()
+            .timeout(std::time::Duration::from_secs(300))
+            .user_agent("dadk")
+            .build()
+            .map_err(|e| e.to_string())?;
+        let method = if head { "HEAD" } else { "GET" };
+        let mut request = if head {
+            client.head(url.clone())
+        } else {
+            client.get(url.clone())
+        };
+        if let Some(credentials) = &self.credentials {
+            let headers = sign_request(method, &url, &self.region, credentials, Utc::now(), &[]);
+            for (key, value) in headers {
+                request = request.header(key, value);
+            }
+        }
+        return Ok(request);
+    }
+
+    /// # 发送请求，把S3返回的错误转换为错误信息
+    pub fn send(&self, uri: &S3Uri, head: bool) -> Result<Response, String> {
+        let response = self.request(uri, head)?.send().map_err(|e| e.to_string())?;
+        if response.status().is_success() {
+            return Ok(response);
+        }
+        let status = response.status();
+        let body = response.text().unwrap_or_default();
+        let tag = |name: &str| {
+            Regex::new(&format!("<{0}>([^<]*)</{0}>", name))
+                .unwrap()
+                .captures(&body)
+                .map(|c| c[1].to_string())
+        };
+        let reason = match (tag("Code"), tag("Message")) {
+            (Some(code), Some(message)) => format!("{}: {}", code, message),
+            (Some(code), None) => code,
+            _ => format!("status: {}", status),
+        };
+        return Err(format!(
+            "Failed to get s3://{}/{}, {}",
+            uri.bucket, uri.key, reason
+        ));
+    }
+}
+
+type HmacSha256 = Hmac<Sha256>;
+
+fn hmac(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = HmacSha256::new_from_slice(key).unwrap();
+    mac.update(data.as_bytes());
+    return mac.finalize().into_bytes().to_vec();
+}
+
+/// # 按RFC 3986编码
+///
+/// 不编码非保留字符，`encode_slash`为false时也不编码`/`（用于编码路径）
+fn uri_encode(s: &str, encode_slash: bool) -> String {
+    let mut result = String::with_capacity(s.len());
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                result.push(b as char)
+            }
+            b'/' if !encode_slash => result.push('/'),
+            _ => write!(result, "%{:02X}", b).unwrap(),
+        }
+    }
+    return result;
+}
+
+/// # 用AWS Signature Version 4为没有请求体的请求签名
+///
+/// `extra_headers`（名字为小写）也会被签名，调用者需要自己把它们添加到请求中
+///
+/// ## 返回值
+///
+/// 还需要添加到请求中的请求头，包括`Authorization`
+pub fn sign_request(
+    method: &str,
+    url: &Url,
+    region: &str,
+    credentials: &S3Credentials,
+    now: DateTime<Utc>,
+    extra_headers: &[(&str, &str)],
+) -> Vec<(String, String)> {
+    let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let date = now.format("%Y%m%d").to_string();
+    let host = match url.port() {
+        Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+        None => url.host_str().unwrap_or_default().to_string(),
+    };
+
+    let mut headers: Vec<(String, String)> = vec![
+        ("host".to_string(), host),
+        (
+            "x-amz-content-sha256".to_string(),
+            EMPTY_PAYLOAD_SHA256.to_string(),
+        ),
+        ("x-amz-date".to_string(), amz_date.clone()),
+    ];
+    if let Some(token) = &credentials.session_token {
+        headers.push(("x-amz-security-token".to_string(), token.clone()));
+    }
+    headers.extend(
+        extra_headers
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.trim().to_string())),
+    );
+    headers.sort();
+
+    let mut query: Vec<(String, String)> = url
+        .query_pairs()
+        .map(|(k, v)| (uri_encode(&k, true), uri_encode(&v, true)))
+        .collect();
+    query.sort();
+    let canonical_query = query
+        .iter()
+        .map(|(k, v)| format!("{}={}", k, v))
+        .collect::<Vec<_>>()
+        .join("&");
+    let mut canonical_headers = String::new();
+    for (k, v) in headers.iter() {
+        writeln!(canonical_headers, "{}:{}", k, v).unwrap();
+    }
+    let signed_headers = headers
+        .iter()
+        .map(|(k, _)| k.as_str())
+        .collect::<Vec<_>>()
+        .join(";");
+    // URL中的路径已经编码过
+    let canonical_request = format!(
+        "{}\n{}\n{}\n{}\n{}\n{}",
+        method,
+        url.path(),
+        canonical_query,
+        canonical_headers,
+        signed_headers,
+        EMPTY_PAYLOAD_SHA256
+    );
+
+    let scope = format!("{}/{}/s3/aws4_request", date, region);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        amz_date,
+        scope,
+        HashUtils::sha256_bytes(canonical_request.as_bytes())
+    );
+    let key = hmac(
+        format!("AWS4{}", credentials.secret_access_key).as_bytes(),
+        &date,
+    );
+    let key = hmac(&key, region);
+    let key = hmac(&key, "s3");
+    let key = hmac(&key, "aws4_request");
+    let signature = HashUtils::hex(&hmac(&key, &string_to_sign));
+
+    let mut result: Vec<(String, String)> = headers
+        .into_iter()
+        .filter(|(k, _)| k != "host" && !extra_headers.iter().any(|(extra, _)| extra == k))
+        .collect();
+    result.push((
+        "authorization".to_string(),
+        format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            credentials.access_key_id, scope, signed_headers, signature
+        ),
+    ));
+    return result;
+}
@@ -0,0 +1,93 @@
+//! # 构建沙箱
+//!
+//! 开启网络隔离后，构建命令以及构建钩子在新的网络命名空间（`unshare -n`）中执行，
+//! 命名空间中只有回环网卡，构建过程中偷偷下载文件的命令会直接失败，从而保证构建可复现。
+//! 拉取源码（git、压缩包等）在沙箱之外进行，不受影响。
+//!
+//! 非root用户通过用户命名空间（`unshare -rn`）获得创建网络命名空间的权限，
+//! 主机不支持时（例如禁用了非特权用户命名空间），沙箱不可用。
+
+use std::{
+    process::{Command, Stdio},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        OnceLock,
+    },
+};
+
+/// 是否默认开启网络隔离（`--sandbox-network`），任务中的`build.sandbox.network`优先
+static NETWORK_DEFAULT: AtomicBool = AtomicBool::new(false);
+
+/// 探测得到的`unshare`参数，沙箱不可用时为错误信息
+static NETWORK_PROBE: OnceLock<Result<Vec<&'static str>, String>> = OnceLock::new();
+
+/// 构建在网络隔离的沙箱中失败时，附加在错误信息后的说明
+pub const NETWORK_SANDBOX_HINT: &str =
+    "the build ran in a network sandbox (sandbox.network) with only a loopback interface, \
+commands that access the network (e.g. curl, git clone, cargo fetching crates) fail inside it, \
+fetch such inputs through the task's source or disable sandbox.network for this task";
+
+pub fn set_network_default(enabled: bool) {
+    NETWORK_DEFAULT.store(enabled, Ordering::Relaxed);
+}
+
+pub fn network_default() -> bool {
+    return NETWORK_DEFAULT.load(Ordering::Relaxed);
+}
+
+/// # 检查网络隔离沙箱是否可用
+///
+/// 只在第一次调用时实际探测，返回传给`unshare`的参数
+pub fn probe_network() -> Result<Vec<&'static str>, String> {
+    return NETWORK_PROBE.get_or_init(probe).clone();
+}
+
+fn probe() -> Result<Vec<&'static str>, String> {
+    let flags = if unsafe { libc::geteuid() } == 0 {
+        vec!["-n"]
+    } else {
+        vec!["-r", "-n"]
+    };
+    let output = Command::new("unshare")
+        .args(&flags)
+        .arg("true")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .output()
+        .map_err(|e| format!("failed to run unshare: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "unshare {} failed: {}",
+            flags.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    return Ok(flags);
+}
+
+/// # 把命令放到新的网络命名空间中执行
+///
+/// 保留命令的工作目录和环境变量，并在执行前启用回环网卡
+pub fn isolate_network(command: &Command) -> Result<Command, String> {
+    let flags = probe_network()?;
+    let mut sandboxed = Command::new("unshare");
+    sandboxed
+        .args(flags)
+        .arg("sh")
+        .arg("-c")
+        .arg("ip link set lo up >/dev/null 2>&1; exec \"$@\"")
+        .arg("sh")
+        .arg(command.get_program())
+        .args(command.get_args());
+    if let Some(dir) = command.get_current_dir() {
+        sandboxed.current_dir(dir);
+    }
+    for (key, value) in command.get_envs() {
+        match value {
+            Some(value) => sandboxed.env(key, value),
+            None => sandboxed.env_remove(key),
+        };
+    }
+    return Ok(sandboxed);
+}
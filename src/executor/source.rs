@@ -1,18 +1,29 @@
-use log::info;
+use flate2::bufread::MultiGzDecoder;
+use log::{debug, info, warn};
 use regex::Regex;
 use reqwest::Url;
 use serde::{Deserialize, Serialize};
 use std::os::unix::fs::PermissionsExt;
 use std::{
     fs::File,
-    path::PathBuf,
+    io::BufReader,
+    path::{Component, Path, PathBuf},
     process::{Command, Stdio},
 };
-use zip::ZipArchive;
 
-use crate::utils::{file::FileUtils, stdio::StdioUtils};
-
-use super::cache::CacheDir;
+use crate::utils::{file::FileUtils, hash::HashUtils, stdio::StdioUtils};
+
+use super::{
+    cache::CacheDir,
+    events, extract,
+    oci::{ImageReference, OciPuller},
+    progress::{
+        ProgressReporter, STAGE_CHECKOUT, STAGE_CLONE, STAGE_DOWNLOAD, STAGE_EXTRACT, STAGE_FETCH,
+        STAGE_PULL,
+    },
+    s3::{S3Config, S3Uri, S3_SCHEME},
+    ExecError,
+};
 
 /// # Git源
 ///
@@ -25,6 +36,38 @@ pub struct GitSource {
     branch: Option<String>,
     /// 特定的提交的hash值（可选，如果为空，则拉取branch的最新提交）
     revision: Option<String>,
+    /// 通过SSH访问私有仓库时使用的私钥文件（可选）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ssh_key: Option<PathBuf>,
+    /// 复用已有的克隆时，工作区中被跟踪的文件有本地修改的处理方式（默认报错，保留修改）
+    #[serde(default, skip_serializing_if = "GitDirtyPolicy::is_default")]
+    on_dirty: GitDirtyPolicy,
+    /// 浅克隆的深度（可选，指定了分支时默认为1），与`shallow_since`只能同时指定一个
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    depth: Option<u32>,
+    /// 只克隆这个时间之后的提交（可选，`git clone --shallow-since`），
+    /// 例如`2024-01-01`、`2024-01-01T08:00:00+08:00`或者`2 weeks ago`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    shallow_since: Option<String>,
+}
+
+/// # Git工作区有本地修改时的处理方式
+///
+/// 只考虑被跟踪的文件，构建产生的未跟踪文件不算作本地修改
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum GitDirtyPolicy {
+    /// 报错，保留本地修改
+    #[default]
+    Error,
+    /// 丢弃本地修改（`git reset --hard`），需要显式指定
+    Reset,
+}
+
+impl GitDirtyPolicy {
+    pub fn is_default(&self) -> bool {
+        return *self == Self::default();
+    }
 }
 
 impl GitSource {
@@ -33,8 +76,34 @@ impl GitSource {
             url,
             branch,
             revision,
+            ssh_key: None,
+            on_dirty: GitDirtyPolicy::default(),
+            depth: None,
+            shallow_since: None,
         }
     }
+
+    /// 设置浅克隆的深度，或者只克隆某个时间之后的提交
+    #[allow(dead_code)]
+    pub fn with_shallow(mut self, depth: Option<u32>, shallow_since: Option<String>) -> Self {
+        self.depth = depth;
+        self.shallow_since = shallow_since;
+        return self;
+    }
+
+    /// 设置复用已有的克隆时，工作区有本地修改的处理方式
+    #[allow(dead_code)]
+    pub fn with_on_dirty(mut self, on_dirty: GitDirtyPolicy) -> Self {
+        self.on_dirty = on_dirty;
+        return self;
+    }
+
+    /// 设置访问仓库时使用的SSH私钥文件
+    #[allow(dead_code)]
+    pub fn with_ssh_key(mut self, ssh_key: Option<PathBuf>) -> Self {
+        self.ssh_key = ssh_key;
+        return self;
+    }
     /// # 验证参数合法性
     ///
     /// 仅进行形式校验，不会检查Git仓库是否存在，以及分支是否存在、是否有权限访问等
@@ -42,6 +111,7 @@ impl GitSource {
         if self.url.is_empty() {
             return Err("url is empty".to_string());
         }
+        self.validate_url()?;
         // branch和revision不能同时为空
         if self.branch.is_none() && self.revision.is_none() {
             self.branch = Some("master".to_string());
@@ -61,9 +131,200 @@ impl GitSource {
                 return Err("revision is empty".to_string());
             }
         }
+        self.validate_shallow()?;
+        if let Some(warning) = self.validate_ssh_key()? {
+            warn!("{}", warning);
+        }
+        return Ok(());
+    }
+
+    /// # 校验`depth`和`shallow_since`
+    ///
+    /// 两者只能同时指定一个，`depth`必须大于0，`shallow_since`必须是ISO 8601格式的日期或时间，
+    /// 或者`<数字> <单位> ago`形式的相对时间（单位为second、minute、hour、day、week、month、year）
+    fn validate_shallow(&self) -> Result<(), String> {
+        if self.depth.is_some() && self.shallow_since.is_some() {
+            return Err("depth and shallow_since are both specified".to_string());
+        }
+        if self.depth == Some(0) {
+            return Err("depth must be greater than 0".to_string());
+        }
+        if let Some(since) = &self.shallow_since {
+            if !Self::is_valid_since(since) {
+                return Err(format!(
+                    "invalid shallow_since '{}': expected an ISO 8601 date such as 2024-01-01 \
+                    or a relative time such as '2 weeks ago'",
+                    since
+                ));
+            }
+        }
         return Ok(());
     }
 
+    fn is_valid_since(since: &str) -> bool {
+        const UNITS: [&str; 7] = ["second", "minute", "hour", "day", "week", "month", "year"];
+
+        let since = since.trim();
+        if chrono::DateTime::parse_from_rfc3339(since).is_ok()
+            || chrono::NaiveDate::parse_from_str(since, "%Y-%m-%d").is_ok()
+            || chrono::NaiveDateTime::parse_from_str(since, "%Y-%m-%dT%H:%M:%S").is_ok()
+            || chrono::NaiveDateTime::parse_from_str(since, "%Y-%m-%d %H:%M:%S").is_ok()
+        {
+            return true;
+        }
+        let words: Vec<&str> = since.split_whitespace().collect();
+        return match words.as_slice() {
+            [number, unit, "ago"] => {
+                let unit = unit.strip_suffix('s').unwrap_or(unit);
+                number.parse::<u64>().is_ok() && UNITS.contains(&unit)
+            }
+            _ => false,
+        };
+    }
+
+    /// # 校验SSH私钥文件
+    ///
+    /// 私钥文件必须存在且是普通文件。其他用户可以访问私钥时（ssh本身也会拒绝使用这样的私钥），
+    /// 返回一条警告信息。只检查文件的元数据，不会读取私钥的内容。
+    pub fn validate_ssh_key(&self) -> Result<Option<String>, String> {
+        let key = match &self.ssh_key {
+            Some(key) => key,
+            None => return Ok(None),
+        };
+        if key.as_os_str().is_empty() {
+            return Err("ssh_key is empty".to_string());
+        }
+        let metadata = std::fs::metadata(key)
+            .map_err(|e| format!("ssh_key {} is not accessible: {}", key.display(), e))?;
+        if !metadata.is_file() {
+            return Err(format!("ssh_key {} is not a file", key.display()));
+        }
+        let mode = metadata.permissions().mode() & 0o777;
+        if mode & 0o077 != 0 {
+            return Ok(Some(format!(
+                "ssh_key {} has permissions {:o} and is accessible by other users, \
+                consider `chmod 600 {}`",
+                key.display(),
+                mode,
+                key.display()
+            )));
+        }
+        return Ok(None);
+    }
+
+    /// 校验仓库地址是否像一个git远程仓库地址
+    ///
+    /// 支持`https://`、`http://`、`git://`、`ssh://`、`file://`形式的URL，以及scp形式的`user@host:path`，
+    /// 用于尽早发现把本地路径等误填为仓库地址的情况
+    fn validate_url(&self) -> Result<(), String> {
+        const SCHEMES: [&str; 5] = ["https://", "http://", "git://", "ssh://", "file://"];
+
+        let url = self.url.as_str();
+        if url.chars().any(char::is_whitespace) {
+            return Err(format!("git url '{}' contains whitespace", url));
+        }
+        if let Some(scheme) = SCHEMES.iter().find(|s| url.starts_with(*s)) {
+            let rest = &url[scheme.len()..];
+            if *scheme == "file://" {
+                if rest.is_empty() {
+                    return Err(format!("git url '{}' has no path", url));
+                }
+                return Ok(());
+            }
+            // 去掉用户信息和端口，只保留主机名
+            let authority = rest.split('/').next().unwrap_or_default();
+            let host = authority.rsplit('@').next().unwrap_or_default();
+            let host = host.split(':').next().unwrap_or_default();
+            if host.is_empty() {
+                return Err(format!("git url '{}' has no host", url));
+            }
+            return Ok(());
+        }
+        // scp形式：user@host:path
+        if let Some((user_host, path)) = url.split_once(':') {
+            if let Some((user, host)) = user_host.split_once('@') {
+                if !user.is_empty() && !host.is_empty() && !host.contains('/') && !path.is_empty() {
+                    return Ok(());
+                }
+            }
+        }
+        return Err(format!(
+            "invalid git url '{}': expected https://, git://, ssh:// url or user@host:path",
+            url
+        ));
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn branch(&self) -> Option<&String> {
+        self.branch.as_ref()
+    }
+
+    pub fn revision(&self) -> Option<&String> {
+        self.revision.as_ref()
+    }
+
+    pub fn ssh_key(&self) -> Option<&PathBuf> {
+        self.ssh_key.as_ref()
+    }
+
+    #[allow(dead_code)]
+    pub fn on_dirty(&self) -> GitDirtyPolicy {
+        self.on_dirty
+    }
+
+    /// # 创建git命令
+    ///
+    /// 指定了SSH私钥时，通过`GIT_SSH_COMMAND`让git使用该私钥。
+    /// 命令中只包含私钥的路径，私钥的内容不会出现在日志中。
+    fn git_command(&self) -> Command {
+        let mut cmd = Command::new("git");
+        if let Some(key) = &self.ssh_key {
+            let key = if key.is_absolute() {
+                key.clone()
+            } else {
+                std::env::current_dir().unwrap_or_default().join(key)
+            };
+            let quoted = key.to_string_lossy().replace('\'', "'\\''");
+            cmd.env(
+                "GIT_SSH_COMMAND",
+                format!("ssh -i '{}' -o IdentitiesOnly=yes", quoted),
+            );
+        }
+        return cmd;
+    }
+
+    /// # 检查仓库是否可以访问
+    ///
+    /// 使用`git ls-remote`，不会下载仓库。指定了分支时同时检查分支是否存在，
+    /// 指定了提交时只检查仓库是否可以访问
+    pub fn preflight(&self) -> Result<(), String> {
+        let mut cmd = self.git_command();
+        // 避免需要认证时等待用户输入
+        cmd.env("GIT_TERMINAL_PROMPT", "0");
+        cmd.arg("ls-remote").arg("--exit-code").arg(&self.url);
+        match &self.branch {
+            Some(branch) => cmd.arg(branch),
+            None => cmd.arg("HEAD"),
+        };
+        let output = cmd
+            .output()
+            .map_err(|e| format!("failed to run git ls-remote: {}", e))?;
+        if output.status.success() {
+            return Ok(());
+        }
+        // `--exit-code`在仓库可以访问但没有匹配的引用时返回2
+        if output.status.code() == Some(2) {
+            return Err(format!(
+                "branch {} not found",
+                self.branch.as_deref().unwrap_or("HEAD")
+            ));
+        }
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+
     pub fn trim(&mut self) {
         self.url = self.url.trim().to_string();
         if let Some(branch) = &mut self.branch {
@@ -73,21 +334,32 @@ impl GitSource {
         if let Some(revision) = &mut self.revision {
             *revision = revision.trim().to_string();
         }
+
+        if let Some(since) = &mut self.shallow_since {
+            *since = since.trim().to_string();
+        }
     }
 
     /// # 确保Git仓库已经克隆到指定目录，并且切换到指定分支/Revision
     ///
-    /// 如果目录不存在，则会自动创建
+    /// 如果目录不存在，则会自动创建。目录（按`name_version`区分）中已经有同一个远程仓库的克隆时，
+    /// 复用该克隆，只执行`git fetch`和检出，不会重新克隆；远程仓库地址不同时清空目录后重新克隆。
+    /// 复用前检查工作区是否有本地修改，按`on_dirty`丢弃修改或者报错。
     ///
     /// ## 参数
     ///
     /// - `target_dir` - 目标目录
+    /// - `progress` - 进度报告器
     ///
     /// ## 返回
     ///
     /// - `Ok(())` - 成功
     /// - `Err(String)` - 失败，错误信息
-    pub fn prepare(&self, target_dir: &CacheDir) -> Result<(), String> {
+    pub fn prepare(
+        &self,
+        target_dir: &CacheDir,
+        progress: &dyn ProgressReporter,
+    ) -> Result<(), String> {
         info!(
             "Preparing git repo: {}, branch: {:?}, revision: {:?}",
             self.url, self.branch, self.revision
@@ -100,26 +372,65 @@ impl GitSource {
             )
         })?;
 
-        if target_dir.is_empty().map_err(|e| {
+        let mut reuse = !target_dir.is_empty().map_err(|e| {
             format!(
                 "Failed to check if target dir is empty: {}, message: {e:?}",
                 target_dir.path.display()
             )
-        })? {
+        })?;
+        if reuse && !self.check_repo(target_dir)? {
+            info!(
+                "Target dir {} is a clone of another repo, cloning {} again",
+                target_dir.path.display(),
+                self.url
+            );
+            target_dir
+                .remove_self_recursive()
+                .and_then(|_| target_dir.create())
+                .map_err(|e| {
+                    format!(
+                        "Failed to clear target dir: {}, message: {e:?}",
+                        target_dir.path.display()
+                    )
+                })?;
+            reuse = false;
+        }
+
+        if reuse {
+            info!(
+                "Reusing existing clone in {}, fetching updates",
+                target_dir.path.display()
+            );
+            progress.on_stage(STAGE_FETCH);
+            self.guard_dirty(target_dir)?;
+            self.fetch(target_dir);
+        } else {
             info!("Target dir is empty, cloning repo");
+            progress.on_stage(STAGE_CLONE);
             self.clone_repo(target_dir)?;
         }
 
+        progress.on_stage(STAGE_CHECKOUT);
         self.checkout(target_dir)?;
 
+        progress.on_stage(STAGE_PULL);
         self.pull(target_dir)?;
 
         return Ok(());
     }
 
+    /// # 检查目录中是否是当前仓库的克隆
+    ///
+    /// 目录不是git仓库时报错，避免覆盖其中的文件
     fn check_repo(&self, target_dir: &CacheDir) -> Result<bool, String> {
         let path: &PathBuf = &target_dir.path;
-        let mut cmd = Command::new("git");
+        if !path.join(".git").exists() {
+            return Err(format!(
+                "Target dir {} is not empty and is not a git clone",
+                path.display()
+            ));
+        }
+        let mut cmd = self.git_command();
         cmd.arg("remote").arg("get-url").arg("origin");
 
         // 设置工作目录
@@ -146,9 +457,85 @@ impl GitSource {
         }
     }
 
+    /// # 检查工作区中被跟踪的文件是否有本地修改
+    ///
+    /// 按`on_dirty`丢弃修改或者报错
+    fn guard_dirty(&self, target_dir: &CacheDir) -> Result<(), String> {
+        let mut cmd = self.git_command();
+        cmd.current_dir(&target_dir.path);
+        cmd.arg("status")
+            .arg("--porcelain")
+            .arg("--untracked-files=no");
+        let output = cmd.output().map_err(|e| e.to_string())?;
+        if !output.status.success() {
+            return Err(format!(
+                "Failed to check local modifications of {}, message: {}",
+                target_dir.path.display(),
+                StdioUtils::tail_n_str(StdioUtils::stderr_to_lines(&output.stderr), 5)
+            ));
+        }
+        let modified: Vec<String> = String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(|line| line.trim().to_string())
+            .filter(|line| !line.is_empty())
+            .collect();
+        if modified.is_empty() {
+            return Ok(());
+        }
+
+        match self.on_dirty {
+            GitDirtyPolicy::Error => {
+                return Err(format!(
+                    "Working tree {} has local modifications: [{}], \
+                    commit or discard them, or set `on_dirty` to \"reset\"",
+                    target_dir.path.display(),
+                    modified.join(", ")
+                ));
+            }
+            GitDirtyPolicy::Reset => {
+                warn!(
+                    "Discarding local modifications in {}: [{}]",
+                    target_dir.path.display(),
+                    modified.join(", ")
+                );
+                let mut cmd = self.git_command();
+                cmd.current_dir(&target_dir.path);
+                cmd.arg("reset").arg("--hard").arg("-q");
+                let output = cmd.output().map_err(|e| e.to_string())?;
+                if !output.status.success() {
+                    return Err(format!(
+                        "Failed to reset {}, message: {}",
+                        target_dir.path.display(),
+                        StdioUtils::tail_n_str(StdioUtils::stderr_to_lines(&output.stderr), 5)
+                    ));
+                }
+                return Ok(());
+            }
+        }
+    }
+
+    /// # 获取远程仓库的更新
+    ///
+    /// 失败时（例如离线构建）只给出警告，之后的检出仍然可以使用本地已有的提交
+    fn fetch(&self, target_dir: &CacheDir) {
+        let mut cmd = self.git_command();
+        cmd.current_dir(&target_dir.path);
+        cmd.env("GIT_TERMINAL_PROMPT", "0");
+        cmd.arg("fetch").arg("-q").arg("origin");
+        match cmd.output() {
+            Ok(output) if output.status.success() => {}
+            Ok(output) => warn!(
+                "Failed to fetch {}, message: {}",
+                target_dir.path.display(),
+                StdioUtils::tail_n_str(StdioUtils::stderr_to_lines(&output.stderr), 5)
+            ),
+            Err(e) => warn!("Failed to fetch {}: {}", target_dir.path.display(), e),
+        }
+    }
+
     fn set_url(&self, target_dir: &CacheDir) -> Result<(), String> {
         let path: &PathBuf = &target_dir.path;
-        let mut cmd = Command::new("git");
+        let mut cmd = self.git_command();
         cmd.arg("remote")
             .arg("set-url")
             .arg("origin")
@@ -160,6 +547,7 @@ impl GitSource {
         // 创建子进程，执行命令
         let proc: std::process::Child = cmd
             .stderr(Stdio::piped())
+            .stdout(events::child_stdout())
             .spawn()
             .map_err(|e| e.to_string())?;
         let output = proc.wait_with_output().map_err(|e| e.to_string())?;
@@ -187,7 +575,7 @@ impl GitSource {
         }
 
         let do_checkout = || -> Result<(), String> {
-            let mut cmd = Command::new("git");
+            let mut cmd = self.git_command();
             cmd.current_dir(&target_dir.path);
             cmd.arg("checkout");
 
@@ -204,6 +592,7 @@ impl GitSource {
             // 创建子进程，执行命令
             let proc: std::process::Child = cmd
                 .stderr(Stdio::piped())
+                .stdout(events::child_stdout())
                 .spawn()
                 .map_err(|e| e.to_string())?;
             let output = proc.wait_with_output().map_err(|e| e.to_string())?;
@@ -216,13 +605,14 @@ impl GitSource {
                 ));
             }
 
-            let mut subcmd = Command::new("git");
+            let mut subcmd = self.git_command();
             subcmd.current_dir(&target_dir.path);
             subcmd.arg("submodule").arg("update").arg("--remote");
 
             //当checkout仓库的子进程结束后，启动checkout子模块的子进程
             let subproc: std::process::Child = subcmd
                 .stderr(Stdio::piped())
+                .stdout(events::child_stdout())
                 .spawn()
                 .map_err(|e| e.to_string())?;
             let suboutput = subproc.wait_with_output().map_err(|e| e.to_string())?;
@@ -253,14 +643,14 @@ impl GitSource {
 
     pub fn clone_repo(&self, cache_dir: &CacheDir) -> Result<(), String> {
         let path: &PathBuf = &cache_dir.path;
-        let mut cmd = Command::new("git");
+        let mut cmd = self.git_command();
         cmd.arg("clone").arg(&self.url).arg(".").arg("--recursive");
 
         if let Some(branch) = &self.branch {
-            cmd.arg("--branch").arg(branch).arg("--depth").arg("1");
+            cmd.arg("--branch").arg(branch);
         }
-
-        // 对于克隆，如果指定了revision，则直接克隆整个仓库，稍后再切换到指定的revision
+        // 对于克隆，如果指定了revision，则默认克隆整个仓库，稍后再切换到指定的revision
+        cmd.args(self.shallow_args());
 
         // 设置工作目录
         cmd.current_dir(path);
@@ -268,7 +658,7 @@ impl GitSource {
         // 创建子进程，执行命令
         let proc: std::process::Child = cmd
             .stderr(Stdio::piped())
-            .stdout(Stdio::inherit())
+            .stdout(events::child_stdout())
             .spawn()
             .map_err(|e| e.to_string())?;
         let output = proc.wait_with_output().map_err(|e| e.to_string())?;
@@ -281,7 +671,7 @@ impl GitSource {
             ));
         }
 
-        let mut subcmd = Command::new("git");
+        let mut subcmd = self.git_command();
         subcmd
             .arg("submodule")
             .arg("update")
@@ -294,7 +684,7 @@ impl GitSource {
         //当克隆仓库的子进程结束后，启动保证克隆子模块的子进程
         let subproc: std::process::Child = subcmd
             .stderr(Stdio::piped())
-            .stdout(Stdio::inherit())
+            .stdout(events::child_stdout())
             .spawn()
             .map_err(|e| e.to_string())?;
         let suboutput = subproc.wait_with_output().map_err(|e| e.to_string())?;
@@ -309,9 +699,24 @@ impl GitSource {
         return Ok(());
     }
 
+    /// # 浅克隆的参数
+    ///
+    /// 指定了`shallow_since`时只克隆该时间之后的提交，否则按`depth`浅克隆。
+    /// 都没有指定时，指定了分支则只克隆最新的提交，指定了revision则克隆整个仓库
+    fn shallow_args(&self) -> Vec<String> {
+        if let Some(since) = &self.shallow_since {
+            return vec![format!("--shallow-since={}", since)];
+        }
+        return match (self.depth, &self.branch) {
+            (Some(depth), _) => vec!["--depth".to_string(), depth.to_string()],
+            (None, Some(_)) => vec!["--depth".to_string(), "1".to_string()],
+            (None, None) => Vec::new(),
+        };
+    }
+
     /// 设置fetch所有分支
     fn set_fetch_config(&self, target_dir: &CacheDir) -> Result<(), String> {
-        let mut cmd = Command::new("git");
+        let mut cmd = self.git_command();
         cmd.current_dir(&target_dir.path);
         cmd.arg("config")
             .arg("remote.origin.fetch")
@@ -320,6 +725,7 @@ impl GitSource {
         // 创建子进程，执行命令
         let proc: std::process::Child = cmd
             .stderr(Stdio::piped())
+            .stdout(events::child_stdout())
             .spawn()
             .map_err(|e| e.to_string())?;
         let output = proc.wait_with_output().map_err(|e| e.to_string())?;
@@ -339,7 +745,7 @@ impl GitSource {
             return Ok(());
         }
 
-        let mut cmd = Command::new("git");
+        let mut cmd = self.git_command();
         cmd.current_dir(&target_dir.path);
         cmd.arg("fetch").arg("--unshallow");
 
@@ -348,6 +754,7 @@ impl GitSource {
         // 创建子进程，执行命令
         let proc: std::process::Child = cmd
             .stderr(Stdio::piped())
+            .stdout(events::child_stdout())
             .spawn()
             .map_err(|e| e.to_string())?;
         let output = proc.wait_with_output().map_err(|e| e.to_string())?;
@@ -364,11 +771,12 @@ impl GitSource {
 
     /// 判断当前仓库是否是浅克隆
     fn is_shallow(&self, target_dir: &CacheDir) -> Result<bool, String> {
-        let mut cmd = Command::new("git");
+        let mut cmd = self.git_command();
         cmd.current_dir(&target_dir.path);
         cmd.arg("rev-parse").arg("--is-shallow-repository");
 
         let proc: std::process::Child = cmd
+            .stdout(Stdio::piped())
             .stderr(Stdio::piped())
             .spawn()
             .map_err(|e| e.to_string())?;
@@ -388,7 +796,7 @@ impl GitSource {
 
     fn fetch_all(&self, target_dir: &CacheDir) -> Result<(), String> {
         self.set_fetch_config(target_dir)?;
-        let mut cmd = Command::new("git");
+        let mut cmd = self.git_command();
         cmd.current_dir(&target_dir.path);
         cmd.arg("fetch").arg("--all");
 
@@ -398,6 +806,7 @@ impl GitSource {
         // 创建子进程，执行命令
         let proc: std::process::Child = cmd
             .stderr(Stdio::piped())
+            .stdout(events::child_stdout())
             .spawn()
             .map_err(|e| e.to_string())?;
         let output = proc.wait_with_output().map_err(|e| e.to_string())?;
@@ -420,7 +829,7 @@ impl GitSource {
         }
         info!("git pulling: {}", target_dir.path.display());
 
-        let mut cmd = Command::new("git");
+        let mut cmd = self.git_command();
         cmd.current_dir(&target_dir.path);
         cmd.arg("pull");
 
@@ -430,6 +839,7 @@ impl GitSource {
         // 创建子进程，执行命令
         let proc: std::process::Child = cmd
             .stderr(Stdio::piped())
+            .stdout(events::child_stdout())
             .spawn()
             .map_err(|e| e.to_string())?;
         let output = proc.wait_with_output().map_err(|e| e.to_string())?;
@@ -445,126 +855,1373 @@ impl GitSource {
 
         return Ok(());
     }
-}
-
-/// # 本地源
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
-pub struct LocalSource {
-    /// 本地目录/文件的路径
-    path: PathBuf,
-}
-
-impl LocalSource {
-    #[allow(dead_code)]
-    pub fn new(path: PathBuf) -> Self {
-        Self { path }
-    }
 
-    pub fn validate(&self, expect_file: Option<bool>) -> Result<(), String> {
-        if !self.path.exists() {
-            return Err(format!("path {:?} not exists", self.path));
-        }
+    /// # 获取仓库当前所在的commit
+    ///
+    /// 无论配置文件中指定的是分支还是revision，都返回解析后的完整commit hash
+    pub fn resolved_commit(&self, target_dir: &CacheDir) -> Result<String, String> {
+        let mut cmd = self.git_command();
+        cmd.current_dir(&target_dir.path);
+        cmd.arg("rev-parse").arg("HEAD");
 
-        if let Some(expect_file) = expect_file {
-            if expect_file && !self.path.is_file() {
-                return Err(format!("path {:?} is not a file", self.path));
-            }
+        let proc: std::process::Child = cmd
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| e.to_string())?;
+        let output = proc.wait_with_output().map_err(|e| e.to_string())?;
 
-            if !expect_file && !self.path.is_dir() {
-                return Err(format!("path {:?} is not a directory", self.path));
-            }
+        if !output.status.success() {
+            return Err(format!(
+                "Failed to resolve commit of {}, message: {}",
+                target_dir.path.display(),
+                StdioUtils::tail_n_str(StdioUtils::stderr_to_lines(&output.stderr), 5)
+            ));
         }
 
-        return Ok(());
-    }
-
-    pub fn trim(&mut self) {}
-
-    pub fn path(&self) -> &PathBuf {
-        &self.path
+        return Ok(String::from_utf8_lossy(&output.stdout).trim().to_string());
     }
 }
 
-/// # 在线压缩包源
+/// # Mercurial源
+///
+/// 从Mercurial（hg）仓库获取源码
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
-pub struct ArchiveSource {
-    /// 压缩包的URL
+pub struct HgSource {
+    /// hg仓库地址
     url: String,
+    /// 分支（可选，如果为空，则使用default分支）branch和revision只能同时指定一个
+    branch: Option<String>,
+    /// 特定的changeset的id或者tag（可选，如果为空，则使用branch的最新changeset）
+    revision: Option<String>,
 }
 
-impl ArchiveSource {
-    #[allow(dead_code)]
-    pub fn new(url: String) -> Self {
-        Self { url }
+impl HgSource {
+    /// 没有指定分支和revision时使用的分支
+    pub const DEFAULT_BRANCH: &'static str = "default";
+
+    pub fn new(url: String, branch: Option<String>, revision: Option<String>) -> Self {
+        Self {
+            url,
+            branch,
+            revision,
+        }
     }
-    pub fn validate(&self) -> Result<(), String> {
+
+    /// # 验证参数合法性
+    ///
+    /// 仅进行形式校验，不会检查仓库是否存在，以及分支是否存在、是否有权限访问等
+    pub fn validate(&mut self) -> Result<(), String> {
         if self.url.is_empty() {
             return Err("url is empty".to_string());
         }
+        self.validate_url()?;
+        // branch和revision不能同时为空
+        if self.branch.is_none() && self.revision.is_none() {
+            self.branch = Some(Self::DEFAULT_BRANCH.to_string());
+        }
+        // branch和revision只能同时指定一个
+        if self.branch.is_some() && self.revision.is_some() {
+            return Err("branch and revision are both specified".to_string());
+        }
+        if self.branch.as_ref().is_some_and(|b| b.is_empty()) {
+            return Err("branch is empty".to_string());
+        }
+        if self.revision.as_ref().is_some_and(|r| r.is_empty()) {
+            return Err("revision is empty".to_string());
+        }
+        return Ok(());
+    }
 
-        // 判断是一个网址
-        if let Ok(url) = Url::parse(&self.url) {
-            if url.scheme() != "http" && url.scheme() != "https" {
-                return Err(format!("url {:?} is not a http/https url", self.url));
+    /// 校验仓库地址是否像一个hg远程仓库地址
+    ///
+    /// 支持`https://`、`http://`、`ssh://`、`file://`形式的URL。hg不支持git的scp形式（`user@host:path`）
+    /// 以及`git://`协议，这两种情况通常是把git仓库误配置成了hg源
+    fn validate_url(&self) -> Result<(), String> {
+        const SCHEMES: [&str; 4] = ["https://", "http://", "ssh://", "file://"];
+
+        let url = self.url.as_str();
+        if url.chars().any(char::is_whitespace) {
+            return Err(format!("hg url '{}' contains whitespace", url));
+        }
+        let scheme = SCHEMES.iter().find(|s| url.starts_with(*s)).ok_or(format!(
+            "invalid hg url '{}': expected https://, http://, ssh:// or file:// url",
+            url
+        ))?;
+        let rest = &url[scheme.len()..];
+        if *scheme == "file://" {
+            if rest.is_empty() {
+                return Err(format!("hg url '{}' has no path", url));
             }
-        } else {
-            return Err(format!("url {:?} is not a valid url", self.url));
+            return Ok(());
+        }
+        // 去掉用户信息和端口，只保留主机名
+        let authority = rest.split('/').next().unwrap_or_default();
+        let host = authority.rsplit('@').next().unwrap_or_default();
+        let host = host.split(':').next().unwrap_or_default();
+        if host.is_empty() {
+            return Err(format!("hg url '{}' has no host", url));
         }
         return Ok(());
     }
 
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn branch(&self) -> Option<&String> {
+        self.branch.as_ref()
+    }
+
+    pub fn revision(&self) -> Option<&String> {
+        self.revision.as_ref()
+    }
+
     pub fn trim(&mut self) {
         self.url = self.url.trim().to_string();
+        if let Some(branch) = &mut self.branch {
+            *branch = branch.trim().to_string();
+        }
+        if let Some(revision) = &mut self.revision {
+            *revision = revision.trim().to_string();
+        }
     }
 
-    /// @brief 下载压缩包并把其中的文件提取至target_dir目录下
+    /// 要更新到的分支或者revision
+    fn target(&self) -> &str {
+        return self
+            .revision
+            .as_deref()
+            .or(self.branch.as_deref())
+            .unwrap_or(Self::DEFAULT_BRANCH);
+    }
+
+    /// # 创建hg命令
     ///
-    ///从URL中下载压缩包到临时文件夹 target_dir/DRAGONOS_ARCHIVE_TEMP 后
-    ///原地解压，提取文件后删除下载的压缩包。如果 target_dir 非空，就直接使用
-    ///其中内容，不进行重复下载和覆盖
+    /// 设置`HGPLAIN`，使输出不受用户配置影响，并且不会等待用户输入
+    fn hg_command(&self) -> Command {
+        let mut cmd = Command::new("hg");
+        cmd.env("HGPLAIN", "1");
+        cmd.arg("--noninteractive");
+        return cmd;
+    }
+
+    /// 执行hg命令，失败时返回包含stderr最后几行的错误信息
+    fn run(&self, mut cmd: Command, what: &str) -> Result<String, String> {
+        let output = cmd
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .output()
+            .map_err(|e| format!("Failed to run hg {}: {}", what, e))?;
+        if !output.status.success() {
+            return Err(format!(
+                "hg {} failed, status: {:?},  stderr: {:?}",
+                what,
+                output.status,
+                StdioUtils::tail_n_str(StdioUtils::stderr_to_lines(&output.stderr), 5)
+            ));
+        }
+        return Ok(String::from_utf8_lossy(&output.stdout).trim().to_string());
+    }
+
+    /// # 检查仓库是否可以访问
     ///
-    /// @param target_dir 文件缓存目录
+    /// 使用`hg identify`，不会下载仓库。指定了分支时同时检查分支是否存在
+    pub fn preflight(&self) -> Result<(), String> {
+        let mut cmd = self.hg_command();
+        cmd.arg("identify").arg(&self.url);
+        if let Some(branch) = &self.branch {
+            cmd.arg("-r").arg(branch);
+        }
+        self.run(cmd, "identify")?;
+        return Ok(());
+    }
+
+    /// # 确保hg仓库已经克隆到指定目录，并且更新到指定分支/revision
     ///
-    /// @return 根据结果返回OK或Err
-    pub fn download_unzip(&self, target_dir: &CacheDir) -> Result<(), String> {
-        let url = Url::parse(&self.url).unwrap();
-        let archive_name = url.path_segments().unwrap().last().unwrap();
-        let path = &(target_dir.path.join("DRAGONOS_ARCHIVE_TEMP"));
-        //如果source目录没有临时文件夹，且不为空，说明之前成功执行过一次，那么就直接使用之前的缓存
-        if !path.exists()
-            && !target_dir.is_empty().map_err(|e| {
+    /// 如果目录不存在，则会自动创建。已经克隆过时，从配置的地址拉取更新
+    pub fn prepare(
+        &self,
+        target_dir: &CacheDir,
+        progress: &dyn ProgressReporter,
+    ) -> Result<(), String> {
+        info!(
+            "Preparing hg repo: {}, branch: {:?}, revision: {:?}",
+            self.url, self.branch, self.revision
+        );
+
+        target_dir.create().map_err(|e| {
+            format!(
+                "Failed to create target dir: {}, message: {e:?}",
+                target_dir.path.display()
+            )
+        })?;
+
+        if target_dir.is_empty().map_err(|e| {
+            format!(
+                "Failed to check if target dir is empty: {}, message: {e:?}",
+                target_dir.path.display()
+            )
+        })? {
+            info!("Target dir is empty, cloning repo");
+            progress.on_stage(STAGE_CLONE);
+            let mut cmd = self.hg_command();
+            cmd.arg("clone")
+                .arg("--noupdate")
+                .arg(&self.url)
+                .arg(".")
+                .current_dir(&target_dir.path);
+            self.run(cmd, "clone")?;
+        } else if self.branch.is_some() {
+            // 分支可能有新的changeset，固定的revision只在本地找不到时才拉取
+            progress.on_stage(STAGE_PULL);
+            self.pull(target_dir)?;
+        }
+
+        progress.on_stage(STAGE_CHECKOUT);
+        if let Err(e) = self.update(target_dir) {
+            if self.branch.is_some() {
+                return Err(e);
+            }
+            info!("revision not found locally, pulling: {}", self.url);
+            progress.on_stage(STAGE_PULL);
+            self.pull(target_dir)?;
+            self.update(target_dir)?;
+        }
+        return Ok(());
+    }
+
+    /// 从配置的地址拉取（而不是仓库中记录的default路径，以便地址变化后也能拉取）
+    fn pull(&self, target_dir: &CacheDir) -> Result<(), String> {
+        let mut cmd = self.hg_command();
+        cmd.arg("pull")
+            .arg(&self.url)
+            .arg("--quiet")
+            .current_dir(&target_dir.path);
+        self.run(cmd, "pull")?;
+        return Ok(());
+    }
+
+    /// 强制更新工作目录到指定的分支/revision，丢弃本地的修改
+    fn update(&self, target_dir: &CacheDir) -> Result<(), String> {
+        let mut cmd = self.hg_command();
+        cmd.arg("update")
+            .arg("--clean")
+            .arg("--quiet")
+            .arg("-r")
+            .arg(self.target())
+            .current_dir(&target_dir.path);
+        self.run(cmd, "update")?;
+        return Ok(());
+    }
+
+    /// # 获取工作目录当前所在的changeset
+    ///
+    /// 无论配置文件中指定的是分支还是revision，都返回完整的changeset id
+    pub fn resolved_changeset(&self, target_dir: &CacheDir) -> Result<String, String> {
+        let mut cmd = self.hg_command();
+        cmd.arg("log")
+            .arg("-r")
+            .arg(".")
+            .arg("--template")
+            .arg("{node}")
+            .current_dir(&target_dir.path);
+        return self.run(cmd, "log");
+    }
+}
+
+/// # Subversion源
+///
+/// 从Subversion（svn）仓库检出源码
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SvnSource {
+    /// svn仓库中要检出的目录的地址，例如`https://svn.example.org/repo/trunk`
+    url: String,
+    /// 要检出的revision号（可选，如果为空，则使用`HEAD`）
+    revision: Option<String>,
+}
+
+impl SvnSource {
+    /// 没有指定revision时检出的revision
+    pub const HEAD: &'static str = "HEAD";
+
+    pub fn new(url: String, revision: Option<String>) -> Self {
+        Self { url, revision }
+    }
+
+    /// # 验证参数合法性
+    ///
+    /// 仅进行形式校验，不会检查仓库和revision是否存在
+    pub fn validate(&mut self) -> Result<(), String> {
+        if self.url.is_empty() {
+            return Err("url is empty".to_string());
+        }
+        self.validate_url()?;
+        if let Some(revision) = &self.revision {
+            Self::validate_revision(revision)?;
+        }
+        return Ok(());
+    }
+
+    /// 校验revision：只能是十进制的revision号或者`HEAD`
+    fn validate_revision(revision: &str) -> Result<(), String> {
+        if revision == Self::HEAD {
+            return Ok(());
+        }
+        if revision.is_empty() || !revision.chars().all(|c| c.is_ascii_digit()) {
+            return Err(format!(
+                "invalid svn revision '{}': expected a revision number or HEAD",
+                revision
+            ));
+        }
+        if revision.parse::<u64>().is_err() {
+            return Err(format!("svn revision '{}' is out of range", revision));
+        }
+        return Ok(());
+    }
+
+    /// 校验仓库地址是否像一个svn仓库地址
+    ///
+    /// 支持`https://`、`http://`、`svn://`、`svn+ssh://`、`file://`形式的URL
+    fn validate_url(&self) -> Result<(), String> {
+        const SCHEMES: [&str; 5] = ["https://", "http://", "svn://", "svn+ssh://", "file://"];
+
+        let url = self.url.as_str();
+        if url.chars().any(char::is_whitespace) {
+            return Err(format!("svn url '{}' contains whitespace", url));
+        }
+        let scheme = SCHEMES.iter().find(|s| url.starts_with(*s)).ok_or(format!(
+            "invalid svn url '{}': expected https://, http://, svn://, svn+ssh:// or file:// url",
+            url
+        ))?;
+        let rest = &url[scheme.len()..];
+        if *scheme == "file://" {
+            if rest.is_empty() {
+                return Err(format!("svn url '{}' has no path", url));
+            }
+            return Ok(());
+        }
+        // 去掉用户信息和端口，只保留主机名
+        let authority = rest.split('/').next().unwrap_or_default();
+        let host = authority.rsplit('@').next().unwrap_or_default();
+        let host = host.split(':').next().unwrap_or_default();
+        if host.is_empty() {
+            return Err(format!("svn url '{}' has no host", url));
+        }
+        return Ok(());
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn revision(&self) -> Option<&String> {
+        self.revision.as_ref()
+    }
+
+    pub fn trim(&mut self) {
+        self.url = self.url.trim().to_string();
+        if let Some(revision) = &mut self.revision {
+            *revision = revision.trim().to_string();
+        }
+    }
+
+    /// 要检出的revision
+    fn target(&self) -> &str {
+        return self.revision.as_deref().unwrap_or(Self::HEAD);
+    }
+
+    /// # 创建svn命令
+    ///
+    /// 设置`LC_ALL=C`，使输出不受用户语言设置影响，并且不会等待用户输入
+    fn svn_command(&self) -> Command {
+        let mut cmd = Command::new("svn");
+        cmd.env("LC_ALL", "C");
+        cmd.arg("--non-interactive");
+        return cmd;
+    }
+
+    /// 执行svn命令，失败时返回包含stderr最后几行的错误信息
+    fn run(&self, mut cmd: Command, what: &str) -> Result<String, String> {
+        let output = cmd
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .output()
+            .map_err(|e| format!("Failed to run svn {}: {}", what, e))?;
+        if !output.status.success() {
+            return Err(format!(
+                "svn {} failed, status: {:?},  stderr: {:?}",
+                what,
+                output.status,
+                StdioUtils::tail_n_str(StdioUtils::stderr_to_lines(&output.stderr), 5)
+            ));
+        }
+        return Ok(String::from_utf8_lossy(&output.stdout).trim().to_string());
+    }
+
+    /// # 检查仓库和revision是否可以访问
+    ///
+    /// 使用`svn info`，不会检出代码
+    pub fn preflight(&self) -> Result<(), String> {
+        let mut cmd = self.svn_command();
+        cmd.arg("info").arg("-r").arg(self.target()).arg(&self.url);
+        self.run(cmd, "info")?;
+        return Ok(());
+    }
+
+    /// # 确保svn仓库已经检出到指定目录，并且更新到指定的revision
+    ///
+    /// 如果目录不存在，则会自动创建。已经检出过时，丢弃本地的修改后更新；
+    /// 配置的地址变化时，切换到新的地址
+    pub fn prepare(
+        &self,
+        target_dir: &CacheDir,
+        progress: &dyn ProgressReporter,
+    ) -> Result<(), String> {
+        info!(
+            "Preparing svn checkout: {}, revision: {}",
+            self.url,
+            self.target()
+        );
+
+        target_dir.create().map_err(|e| {
+            format!(
+                "Failed to create target dir: {}, message: {e:?}",
+                target_dir.path.display()
+            )
+        })?;
+
+        if target_dir.is_empty().map_err(|e| {
+            format!(
+                "Failed to check if target dir is empty: {}, message: {e:?}",
+                target_dir.path.display()
+            )
+        })? {
+            info!("Target dir is empty, checking out");
+            progress.on_stage(STAGE_CLONE);
+            let mut cmd = self.svn_command();
+            cmd.arg("checkout")
+                .arg("--quiet")
+                .arg("-r")
+                .arg(self.target())
+                .arg(&self.url)
+                .arg(".")
+                .current_dir(&target_dir.path);
+            self.run(cmd, "checkout")?;
+            return Ok(());
+        }
+
+        let mut cmd = self.svn_command();
+        cmd.arg("revert")
+            .arg("--recursive")
+            .arg("--quiet")
+            .arg(".")
+            .current_dir(&target_dir.path);
+        self.run(cmd, "revert")?;
+
+        progress.on_stage(STAGE_PULL);
+        let mut cmd = self.svn_command();
+        cmd.arg("info")
+            .arg("--show-item")
+            .arg("url")
+            .current_dir(&target_dir.path);
+        let current_url = self.run(cmd, "info")?;
+        let mut cmd = self.svn_command();
+        if current_url.trim_end_matches('/') == self.url.trim_end_matches('/') {
+            cmd.arg("update");
+        } else {
+            info!("svn url changed from {}, switching", current_url);
+            cmd.arg("switch").arg("--ignore-ancestry").arg(&self.url);
+        }
+        cmd.arg("--quiet")
+            .arg("-r")
+            .arg(self.target())
+            .current_dir(&target_dir.path);
+        self.run(cmd, "update")?;
+        return Ok(());
+    }
+
+    /// # 获取工作副本当前所在的revision号
+    ///
+    /// 配置文件中指定的是`HEAD`时，返回检出时的实际revision号
+    pub fn resolved_revision(&self, target_dir: &CacheDir) -> Result<String, String> {
+        let mut cmd = self.svn_command();
+        cmd.arg("info")
+            .arg("--show-item")
+            .arg("revision")
+            .current_dir(&target_dir.path);
+        return self.run(cmd, "info");
+    }
+}
+
+/// # 本地源期望的路径类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LocalSourceKind {
+    /// 必须是目录（例如代码源）
+    Dir,
+    /// 必须是文件
+    File,
+    /// 目录或文件均可（例如预编译包源）
+    Any,
+}
+
+/// # 本地源
+///
+/// 解析配置文件时，相对路径被解析为相对于配置文件所在目录的绝对路径（见[`LocalSource::resolve`]）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LocalSource {
+    /// 本地目录/文件的路径
+    path: PathBuf,
+    /// 配置文件中填写的路径，解析为绝对路径之前的原始值，用于错误信息和锁文件
+    #[serde(skip)]
+    configured: Option<PathBuf>,
+}
+
+/// 指向同一路径的本地源相等，不比较配置文件中的原始写法
+impl PartialEq for LocalSource {
+    fn eq(&self, other: &Self) -> bool {
+        return self.path == other.path;
+    }
+}
+
+impl Eq for LocalSource {}
+
+impl LocalSource {
+    #[allow(dead_code)]
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            configured: None,
+        }
+    }
+
+    /// # 把路径解析为绝对路径
+    ///
+    /// 相对路径相对于`base_dir`（配置文件所在目录）解析，并按字面规范化，不要求路径存在。
+    /// 为了兼容以前相对于当前工作目录填写的路径，相对于`base_dir`的路径不存在、
+    /// 而相对于当前工作目录的路径存在时，使用后者
+    pub fn resolve(&mut self, base_dir: &Path) {
+        if self.configured.is_some() {
+            return;
+        }
+        let cwd = std::env::current_dir().unwrap_or_default();
+        let resolved = if self.path.is_absolute() {
+            FileUtils::normalize_path(&self.path)
+        } else {
+            let from_base = FileUtils::normalize_path(&cwd.join(base_dir).join(&self.path));
+            let from_cwd = FileUtils::normalize_path(&cwd.join(&self.path));
+            if from_base.symlink_metadata().is_err() && from_cwd.symlink_metadata().is_ok() {
+                debug!(
+                    "Local path {} is resolved against the working directory: {}",
+                    self.path.display(),
+                    from_cwd.display()
+                );
+                from_cwd
+            } else {
+                from_base
+            }
+        };
+        self.configured = Some(std::mem::replace(&mut self.path, resolved));
+    }
+
+    /// 配置文件中填写的路径，没有解析过时与[`LocalSource::path`]相同
+    pub fn configured_path(&self) -> &PathBuf {
+        return self.configured.as_ref().unwrap_or(&self.path);
+    }
+
+    /// 错误信息中显示的路径，同时包含原始路径和解析后的路径
+    fn display_path(&self) -> String {
+        match &self.configured {
+            Some(configured) if *configured != self.path => {
+                return format!(
+                    "{:?} (resolved to {:?})",
+                    configured.display().to_string(),
+                    self.path.display().to_string()
+                );
+            }
+            _ => return format!("{:?}", self.path.display().to_string()),
+        }
+    }
+
+    /// # 校验本地路径
+    ///
+    /// ## 参数
+    ///
+    /// * `kind` - 期望的路径类型
+    pub fn validate(&self, kind: LocalSourceKind) -> Result<(), String> {
+        if !self.path.exists() {
+            return Err(format!("path {} not exists", self.display_path()));
+        }
+
+        match kind {
+            LocalSourceKind::Dir => {
+                if !self.path.is_dir() {
+                    return Err(format!(
+                        "path {}: expected a directory but found a file",
+                        self.display_path()
+                    ));
+                }
+            }
+            LocalSourceKind::File => {
+                if !self.path.is_file() {
+                    return Err(format!(
+                        "path {}: expected a file but found a directory",
+                        self.display_path()
+                    ));
+                }
+            }
+            LocalSourceKind::Any => {}
+        }
+
+        return Ok(());
+    }
+
+    pub fn trim(&mut self) {}
+
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    /// 检查本地路径是否存在
+    pub fn preflight(&self) -> Result<(), String> {
+        if self.path.symlink_metadata().is_err() {
+            return Err(format!("path {} not exists", self.display_path()));
+        }
+        return Ok(());
+    }
+}
+
+/// # 在线压缩包源
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ArchiveSource {
+    /// 压缩包的URL，支持http/https以及S3对象存储（`s3://bucket/key`）
+    url: String,
+    /// 只提取压缩包中的这个子目录（相对于压缩包的顶层目录），并去掉这个前缀（可选）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    subdir: Option<String>,
+    /// 压缩包的sha256值（可选），下载后校验
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    checksum: Option<String>,
+    /// 校验和文件（例如`app.tar.gz.sha256sum`）的URL（可选），下载压缩包之前获取其中与压缩包文件名对应的sha256值，
+    /// 下载后校验。不能与`checksum`同时使用
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    checksum_url: Option<String>,
+}
+
+impl ArchiveSource {
+    #[allow(dead_code)]
+    pub fn new(url: String) -> Self {
+        Self {
+            url,
+            subdir: None,
+            checksum: None,
+            checksum_url: None,
+        }
+    }
+
+    /// 设置只提取的子目录
+    #[allow(dead_code)]
+    pub fn with_subdir(mut self, subdir: Option<String>) -> Self {
+        self.subdir = subdir;
+        return self;
+    }
+
+    /// 设置压缩包的sha256值
+    #[allow(dead_code)]
+    pub fn with_checksum(mut self, checksum: Option<String>) -> Self {
+        self.checksum = checksum;
+        return self;
+    }
+
+    /// 设置校验和文件的URL
+    #[allow(dead_code)]
+    pub fn with_checksum_url(mut self, checksum_url: Option<String>) -> Self {
+        self.checksum_url = checksum_url;
+        return self;
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.url.is_empty() {
+            return Err("url is empty".to_string());
+        }
+        self.validate_subdir()?;
+        Self::validate_url(&self.url)?;
+        match (&self.checksum, &self.checksum_url) {
+            (Some(_), Some(_)) => {
+                return Err("checksum and checksum_url cannot be used together".to_string());
+            }
+            (Some(checksum), None) if !is_sha256_hex(checksum) => {
+                return Err(format!("checksum {:?} is not a sha256 value", checksum));
+            }
+            (None, Some(checksum_url)) => {
+                Self::validate_url(checksum_url).map_err(|e| format!("checksum_url: {}", e))?;
+            }
+            _ => {}
+        }
+        return Ok(());
+    }
+
+    /// 判断是一个http/https或者S3的网址
+    fn validate_url(url: &str) -> Result<(), String> {
+        if let Ok(parsed) = Url::parse(url) {
+            if parsed.scheme() == S3_SCHEME {
+                S3Uri::parse(url)?;
+            } else if parsed.scheme() != "http" && parsed.scheme() != "https" {
+                return Err(format!("url {:?} is not a http/https url", url));
+            }
+        } else {
+            return Err(format!("url {:?} is not a valid url", url));
+        }
+        return Ok(());
+    }
+
+    /// # 校验子目录
+    ///
+    /// 子目录必须是相对路径，不能包含`..`，避免提取到源码目录以外
+    fn validate_subdir(&self) -> Result<(), String> {
+        let subdir = match &self.subdir {
+            Some(subdir) => subdir,
+            None => return Ok(()),
+        };
+        for component in Path::new(subdir).components() {
+            match component {
+                Component::RootDir | Component::Prefix(_) => {
+                    return Err(format!("subdir {:?} must be a relative path", subdir));
+                }
+                Component::ParentDir => {
+                    return Err(format!("subdir {:?} must not contain '..'", subdir));
+                }
+                Component::CurDir | Component::Normal(_) => {}
+            }
+        }
+        if self.subdir_path().is_none() {
+            return Err("subdir is empty".to_string());
+        }
+        return Ok(());
+    }
+
+    pub fn trim(&mut self) {
+        self.url = self.url.trim().to_string();
+        if let Some(subdir) = &mut self.subdir {
+            *subdir = subdir.trim().to_string();
+        }
+        if let Some(checksum) = &mut self.checksum {
+            *checksum = checksum.trim().to_ascii_lowercase();
+        }
+        if let Some(checksum_url) = &mut self.checksum_url {
+            *checksum_url = checksum_url.trim().to_string();
+        }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// # 期望的压缩包sha256值
+    ///
+    /// 指定了`checksum_url`时，下载校验和文件并从中找出`archive_name`对应的值。
+    /// 两者都没有指定时返回None
+    fn expected_checksum(&self, archive_name: &str) -> Result<Option<String>, String> {
+        if let Some(checksum) = &self.checksum {
+            return Ok(Some(checksum.clone()));
+        }
+        let checksum_url = match &self.checksum_url {
+            Some(checksum_url) => checksum_url,
+            None => return Ok(None),
+        };
+        let response = match S3Uri::parse(checksum_url) {
+            Ok(uri) => S3Config::from_env().send(&uri, false)?,
+            Err(_) => {
+                let response = reqwest::blocking::ClientBuilder::new()
+                    .timeout(std::time::Duration::from_secs(30))
+                    .user_agent("dadk")
+                    .build()
+                    .map_err(|e| e.to_string())?
+                    .get(checksum_url)
+                    .send()
+                    .map_err(|e| e.to_string())?;
+                if !response.status().is_success() {
+                    return Err(format!("status: {}", response.status()));
+                }
+                response
+            }
+        };
+        let content = response.text().map_err(|e| e.to_string())?;
+        return parse_checksum_file(&content, archive_name).map(Some);
+    }
+
+    /// 只提取的子目录，去掉了`.`和多余的`/`。没有指定子目录时返回None
+    pub fn subdir_path(&self) -> Option<PathBuf> {
+        let path: PathBuf = Path::new(self.subdir.as_ref()?)
+            .components()
+            .filter(|c| matches!(c, Component::Normal(_)))
+            .collect();
+        if path.as_os_str().is_empty() {
+            return None;
+        }
+        return Some(path);
+    }
+
+    /// 压缩包在S3对象存储中时，返回对象的位置
+    fn s3_uri(&self) -> Option<S3Uri> {
+        return S3Uri::parse(&self.url).ok();
+    }
+
+    /// # 检查压缩包是否可以访问
+    ///
+    /// 发送HEAD请求，不会下载压缩包。服务器不支持HEAD请求时，改为只请求第一个字节
+    pub fn preflight(&self) -> Result<(), String> {
+        if let Some(uri) = self.s3_uri() {
+            S3Config::from_env().send(&uri, true)?;
+            return Ok(());
+        }
+        let client = reqwest::blocking::ClientBuilder::new()
+            .timeout(std::time::Duration::from_secs(15))
+            .user_agent("dadk")
+            .build()
+            .map_err(|e| e.to_string())?;
+        let mut response = client.head(&self.url).send().map_err(|e| e.to_string())?;
+        if response.status() == reqwest::StatusCode::METHOD_NOT_ALLOWED {
+            response = client
+                .get(&self.url)
+                .header(reqwest::header::RANGE, "bytes=0-0")
+                .send()
+                .map_err(|e| e.to_string())?;
+        }
+        if !response.status().is_success() {
+            return Err(format!("status: {}", response.status()));
+        }
+        return Ok(());
+    }
+
+    /// 下载压缩包时使用的临时文件夹
+    const TEMP_DIR_NAME: &'static str = "DRAGONOS_ARCHIVE_TEMP";
+
+    /// 判断target_dir中是否已有之前下载并解压好的内容
+    fn cache_exists(target_dir: &CacheDir) -> Result<bool, String> {
+        let path = target_dir.path.join(Self::TEMP_DIR_NAME);
+        //如果source目录没有临时文件夹，且不为空，说明之前成功执行过一次，那么就直接使用之前的缓存
+        return Ok(!path.exists()
+            && !target_dir.is_empty().map_err(|e| {
                 format!(
                     "Failed to check if target dir is empty: {}, message: {e:?}",
                     target_dir.path.display()
                 )
-            })?
-        {
+            })?);
+    }
+
+    /// @brief 下载压缩包并把其中的文件提取至target_dir目录下
+    ///
+    ///从URL中下载压缩包到临时文件夹 target_dir/DRAGONOS_ARCHIVE_TEMP 后
+    ///原地解压，提取文件后删除下载的压缩包。如果 target_dir 非空，就直接使用
+    ///其中内容，不进行重复下载和覆盖
+    ///
+    /// @param target_dir 文件缓存目录
+    /// @param progress 进度报告器
+    ///
+    /// @return 如果重新下载了压缩包，返回压缩包的sha256值；如果使用了之前的缓存，返回None。
+    /// 下载失败时返回`ExecError::Fetch`，解压失败时返回`ExecError::Extract`
+    pub fn download_unzip(
+        &self,
+        target_dir: &CacheDir,
+        progress: &dyn ProgressReporter,
+    ) -> Result<Option<String>, ExecError> {
+        let url = Url::parse(&self.url).unwrap();
+        let s3_uri = self.s3_uri();
+        let archive_name = match &s3_uri {
+            Some(uri) => uri.file_name(),
+            None => url.path_segments().unwrap().last().unwrap(),
+        };
+        let path = &(target_dir.path.join(Self::TEMP_DIR_NAME));
+        if Self::cache_exists(target_dir).map_err(ExecError::Cache)? {
             //如果source文件夹非空，就直接使用，不再重复下载压缩文件，这里可以考虑加入交互
             info!("Source files already exist. Using previous source file cache. You should clean {:?} before re-download the archive ", target_dir.path);
-            return Ok(());
+            return Ok(None);
         }
 
+        let cache_err = |e: std::io::Error| ExecError::Cache(format!("{}: {}", path.display(), e));
         if path.exists() {
-            std::fs::remove_dir_all(path).map_err(|e| e.to_string())?;
+            std::fs::remove_dir_all(path).map_err(cache_err)?;
         }
         //创建临时目录
-        std::fs::create_dir(path).map_err(|e| e.to_string())?;
+        std::fs::create_dir(path).map_err(cache_err)?;
+        let expected = self.expected_checksum(archive_name).map_err(|e| {
+            ExecError::Fetch(format!(
+                "Failed to fetch checksum file {}: {}",
+                self.checksum_url.as_deref().unwrap_or_default(),
+                e
+            ))
+        })?;
         info!("downloading {:?}", archive_name);
-        FileUtils::download_file(&self.url, path).map_err(|e| e.to_string())?;
+        progress.on_stage(STAGE_DOWNLOAD);
+        let on_progress = |bytes, total| progress.on_download_progress(bytes, total);
+        match &s3_uri {
+            Some(uri) => {
+                let response = S3Config::from_env()
+                    .send(uri, false)
+                    .map_err(ExecError::Fetch)?;
+                FileUtils::write_response(response, &path.join(archive_name), &on_progress)
+            }
+            None => FileUtils::download_file(&self.url, path, &on_progress),
+        }
+        .map_err(|e| ExecError::Fetch(format!("Failed to download {}: {}", self.url, e)))?;
         //下载成功，开始尝试解压
         info!("download {:?} finished, start unzip", archive_name);
-        let archive_file = ArchiveFile::new(&path.join(archive_name));
-        archive_file.unzip()?;
+        let checksum = HashUtils::sha256_file(&path.join(archive_name)).map_err(|e| {
+            ExecError::Fetch(format!(
+                "Failed to calculate checksum of {:?}: {}",
+                archive_name, e
+            ))
+        })?;
+        if let Some(expected) = expected {
+            if checksum != expected {
+                // 删除校验失败的压缩包，下次重新下载
+                std::fs::remove_dir_all(path).map_err(cache_err)?;
+                return Err(ExecError::Fetch(format!(
+                    "checksum mismatch for {}: expected sha256 {}, got {}",
+                    self.url, expected, checksum
+                )));
+            }
+        }
+        progress.on_stage(STAGE_EXTRACT);
+        let archive_file = ArchiveFile::new(&path.join(archive_name))
+            .with_subdir(self.subdir_path())
+            .with_jobs(extract::jobs());
+        archive_file.unzip().map_err(ExecError::Extract)?;
         //删除创建的临时文件夹
-        std::fs::remove_dir_all(path).map_err(|e| e.to_string())?;
+        std::fs::remove_dir_all(path).map_err(cache_err)?;
+        return Ok(Some(checksum));
+    }
+}
+
+/// 是否为64位十六进制的sha256值
+fn is_sha256_hex(s: &str) -> bool {
+    return s.len() == 64 && s.chars().all(|c| c.is_ascii_hexdigit());
+}
+
+/// # 从校验和文件中找出文件对应的sha256值
+///
+/// 支持`sha256sum`输出的格式（每行`<hash>  <文件名>`，文件名前可以有表示二进制模式的`*`，
+/// 可以带有路径，只比较文件名），以及只有一个sha256值的文件。空行和以`#`开头的行被忽略
+pub fn parse_checksum_file(content: &str, file_name: &str) -> Result<String, String> {
+    let mut bare = Vec::new();
+    let mut listed = Vec::new();
+    for line in content.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (hash, name) = match line.split_once(char::is_whitespace) {
+            Some((hash, name)) => (hash, Some(name.trim_start())),
+            None => (line, None),
+        };
+        if !is_sha256_hex(hash) {
+            return Err(format!("invalid line in checksum file: {:?}", line));
+        }
+        let hash = hash.to_ascii_lowercase();
+        let name = match name {
+            Some(name) => name.strip_prefix('*').unwrap_or(name),
+            None => {
+                bare.push(hash);
+                continue;
+            }
+        };
+        if Path::new(name).file_name() == Some(file_name.as_ref()) {
+            return Ok(hash);
+        }
+        listed.push(name.to_string());
+    }
+    if bare.len() == 1 && listed.is_empty() {
+        return Ok(bare.remove(0));
+    }
+    if bare.is_empty() && listed.is_empty() {
+        return Err("checksum file is empty".to_string());
+    }
+    return Err(format!(
+        "no checksum for {} in checksum file, listed files: {:?}",
+        file_name, listed
+    ));
+}
+
+/// # Release附件源
+///
+/// 从GitHub或者Gitea的Release中，下载与`asset_pattern`匹配的附件作为压缩包
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ReleaseAssetSource {
+    /// 仓库，GitHub仓库写作`owner/name`，Gitea仓库写作`https://<host>/owner/name`
+    repo: String,
+    /// Release的tag，`latest`表示最新的Release
+    tag: String,
+    /// 附件名的匹配模式，默认为glob（支持`*`和`?`），以`re:`开头时为正则表达式
+    asset_pattern: String,
+}
+
+/// Release中的一个附件
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+pub struct ReleaseAsset {
+    pub name: String,
+    pub browser_download_url: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Release {
+    tag_name: String,
+    #[serde(default)]
+    assets: Vec<ReleaseAsset>,
+}
+
+impl ReleaseAssetSource {
+    /// 表示最新Release的tag
+    pub const LATEST_TAG: &'static str = "latest";
+
+    #[allow(dead_code)]
+    pub fn new(repo: String, tag: String, asset_pattern: String) -> Self {
+        Self {
+            repo,
+            tag,
+            asset_pattern,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.tag.is_empty() {
+            return Err("tag is empty".to_string());
+        }
+        if self.asset_pattern.is_empty() {
+            return Err("asset_pattern is empty".to_string());
+        }
+        self.releases_api()?;
+        self.pattern_regex()?;
+        return Ok(());
+    }
+
+    pub fn trim(&mut self) {
+        self.repo = self.repo.trim().to_string();
+        self.tag = self.tag.trim().to_string();
+        self.asset_pattern = self.asset_pattern.trim().to_string();
+    }
+
+    pub fn repo(&self) -> &str {
+        &self.repo
+    }
+
+    pub fn tag(&self) -> &str {
+        &self.tag
+    }
+
+    /// # 获取仓库的Release API地址
+    ///
+    /// `owner/name`形式的仓库使用GitHub的API，URL形式的仓库（非github.com）使用Gitea的API
+    fn releases_api(&self) -> Result<String, String> {
+        if self.repo.is_empty() {
+            return Err("repo is empty".to_string());
+        }
+
+        let (api_base, path) = if let Ok(url) = Url::parse(&self.repo) {
+            if url.scheme() != "http" && url.scheme() != "https" {
+                return Err(format!("repo {:?} is not a http/https url", self.repo));
+            }
+            let host = url
+                .host_str()
+                .ok_or(format!("repo {:?} has no host", self.repo))?;
+            let api_base = if host == "github.com" {
+                "https://api.github.com".to_string()
+            } else {
+                // Gitea的API位于/api/v1下
+                let mut base = format!("{}://{}", url.scheme(), host);
+                if let Some(port) = url.port() {
+                    base = format!("{}:{}", base, port);
+                }
+                format!("{}/api/v1", base)
+            };
+            (api_base, url.path().trim_matches('/').to_string())
+        } else {
+            (
+                "https://api.github.com".to_string(),
+                self.repo.trim_matches('/').to_string(),
+            )
+        };
+
+        let path = path.trim_end_matches(".git");
+        let parts: Vec<&str> = path.split('/').collect();
+        if parts.len() != 2 || parts.iter().any(|p| p.is_empty()) {
+            return Err(format!(
+                "repo {:?} should be in the form of 'owner/name' or 'https://<host>/owner/name'",
+                self.repo
+            ));
+        }
+
+        return Ok(format!("{}/repos/{}/releases", api_base, path));
+    }
+
+    /// 把附件名的匹配模式转换为正则表达式
+    fn pattern_regex(&self) -> Result<Regex, String> {
+        let regex = match self.asset_pattern.strip_prefix("re:") {
+            Some(re) => re.to_string(),
+            None => {
+                let mut re = String::from("^");
+                for c in self.asset_pattern.chars() {
+                    match c {
+                        '*' => re.push_str(".*"),
+                        '?' => re.push('.'),
+                        _ => re.push_str(&regex::escape(&c.to_string())),
+                    }
+                }
+                re.push('$');
+                re
+            }
+        };
+        return Regex::new(&regex)
+            .map_err(|e| format!("invalid asset_pattern {:?}: {}", self.asset_pattern, e));
+    }
+
+    /// # 从Release的附件中选出唯一一个与模式匹配的附件
+    ///
+    /// 没有匹配或者有多个匹配时，返回的错误信息中会列出Release中所有的附件
+    pub fn select_asset<'a>(&self, assets: &'a [ReleaseAsset]) -> Result<&'a ReleaseAsset, String> {
+        let re = self.pattern_regex()?;
+        let matched: Vec<&ReleaseAsset> = assets.iter().filter(|a| re.is_match(&a.name)).collect();
+        if matched.len() == 1 {
+            return Ok(matched[0]);
+        }
+
+        let names: Vec<&str> = assets.iter().map(|a| a.name.as_str()).collect();
+        if matched.is_empty() {
+            return Err(format!(
+                "no asset matches {:?}, available assets: {:?}",
+                self.asset_pattern, names
+            ));
+        }
+        let matched: Vec<&str> = matched.iter().map(|a| a.name.as_str()).collect();
+        return Err(format!(
+            "{} assets match {:?}: {:?}, available assets: {:?}",
+            matched.len(),
+            self.asset_pattern,
+            matched,
+            names
+        ));
+    }
+
+    /// # 查询Release
+    fn fetch_release(&self) -> Result<Release, String> {
+        let api = self.releases_api()?;
+        let url = if self.tag == Self::LATEST_TAG {
+            format!("{}/latest", api)
+        } else {
+            format!("{}/tags/{}", api, self.tag)
+        };
+
+        let client = reqwest::blocking::ClientBuilder::new()
+            .timeout(std::time::Duration::from_secs(30))
+            .user_agent("dadk")
+            .build()
+            .map_err(|e| e.to_string())?;
+        let mut request = client.get(&url);
+        // 如果设置了token，则使用token访问API，以免触发访问频率限制或者无法访问私有仓库
+        let token_env = if api.starts_with("https://api.github.com") {
+            "GITHUB_TOKEN"
+        } else {
+            "GITEA_TOKEN"
+        };
+        if let Ok(token) = std::env::var(token_env) {
+            request = request.header("Authorization", format!("token {}", token));
+        }
+
+        let response = request.send().map_err(|e| e.to_string())?;
+        if !response.status().is_success() {
+            return Err(format!(
+                "Failed to query release {}, status: {}",
+                url,
+                response.status()
+            ));
+        }
+        return response
+            .json::<Release>()
+            .map_err(|e| format!("Failed to parse release {}: {}", url, e));
+    }
+
+    /// # 下载与模式匹配的附件并解压到target_dir目录下
+    ///
+    /// 与在线压缩包源一样，如果target_dir中已经有之前下载的内容，则直接使用，不会访问API
+    ///
+    /// @return 如果重新下载了附件，返回附件所属Release的tag和附件的sha256值；如果使用了之前的缓存，返回None
+    pub fn download_unzip(
+        &self,
+        target_dir: &CacheDir,
+        progress: &dyn ProgressReporter,
+    ) -> Result<Option<(String, String)>, ExecError> {
+        if ArchiveSource::cache_exists(target_dir).map_err(ExecError::Cache)? {
+            info!("Source files already exist. Using previous source file cache. You should clean {:?} before re-download the release asset ", target_dir.path);
+            return Ok(None);
+        }
+
+        let release = self.fetch_release().map_err(ExecError::Fetch)?;
+        let asset = self.select_asset(&release.assets).map_err(|e| {
+            ExecError::Fetch(format!(
+                "release {} of {}: {}",
+                release.tag_name, self.repo, e
+            ))
+        })?;
+        info!(
+            "Release asset resolved: {} {} -> {}",
+            self.repo, release.tag_name, asset.name
+        );
+
+        let archive = ArchiveSource::new(asset.browser_download_url.clone());
+        let checksum = archive
+            .download_unzip(target_dir, progress)?
+            .ok_or_else(|| {
+                ExecError::Fetch(format!("release asset {} was not downloaded", asset.name))
+            })?;
+        return Ok(Some((release.tag_name, checksum)));
+    }
+}
+
+/// # OCI/Docker镜像源
+///
+/// 从镜像仓库拉取镜像，把镜像的文件系统（或者其中的部分路径）作为预编译包
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct OciSource {
+    /// 镜像名，例如`alpine`、`ghcr.io/org/app`，
+    /// 也可以是带有tag/digest的完整引用，例如`ghcr.io/org/app:1.0@sha256:<hex>`，此时不需要`reference`
+    image: String,
+    /// tag、digest（`sha256:<hex>`）或者`tag@sha256:<hex>`，指定了digest时，镜像会被完全缓存
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    reference: String,
+    /// 只使用镜像中的这些路径（可选，如果为空，则使用整个文件系统）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    paths: Option<Vec<PathBuf>>,
+}
+
+impl OciSource {
+    #[allow(dead_code)]
+    pub fn new(image: String, reference: String, paths: Option<Vec<PathBuf>>) -> Self {
+        Self {
+            image,
+            reference,
+            paths,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        ImageReference::parse(&self.image, &self.reference)?;
+
+        if let Some(paths) = &self.paths {
+            if paths.is_empty() {
+                return Err("paths is empty".to_string());
+            }
+            for path in paths.iter() {
+                if path.as_os_str().is_empty() {
+                    return Err("path in paths is empty".to_string());
+                }
+                if path
+                    .components()
+                    .any(|c| c == std::path::Component::ParentDir)
+                {
+                    return Err(format!("path {:?} must not contain '..'", path));
+                }
+            }
+        }
         return Ok(());
     }
+
+    pub fn trim(&mut self) {
+        self.image = self.image.trim().to_string();
+        self.reference = self.reference.trim().to_string();
+    }
+
+    pub fn image(&self) -> &str {
+        &self.image
+    }
+
+    /// 配置的tag/digest，`image`中包含tag/digest时从中取出
+    pub fn reference(&self) -> String {
+        if self.reference.is_empty() {
+            return ImageReference::split_image(&self.image)
+                .1
+                .unwrap_or_default();
+        }
+        return self.reference.clone();
+    }
+
+    /// # 拉取镜像，并把镜像的内容放到target_dir目录下
+    ///
+    /// target_dir中原有的内容会被清空
+    ///
+    /// ## 参数
+    ///
+    /// - `target_dir` - 目标目录
+    /// - `target_arch` - 目标架构
+    ///
+    /// ## 返回
+    ///
+    /// - `Ok(String)` - 镜像的digest
+    /// - `Err(String)` - 失败，错误信息
+    pub fn prepare(&self, target_dir: &CacheDir, target_arch: &str) -> Result<String, String> {
+        let image = ImageReference::parse(&self.image, &self.reference)?;
+        let (digest, rootfs) = OciPuller::new(image, target_arch)?.pull()?;
+
+        target_dir
+            .remove_self_recursive()
+            .and_then(|_| target_dir.create())
+            .map_err(|e| {
+                format!(
+                    "Failed to recreate target dir: {}, message: {e:?}",
+                    target_dir.path.display()
+                )
+            })?;
+
+        match &self.paths {
+            None => FileUtils::copy_dir_all(&rootfs, &target_dir.path)?,
+            Some(paths) => {
+                for path in paths.iter() {
+                    // 镜像中的路径都是相对于根目录的
+                    let relative: PathBuf = path
+                        .components()
+                        .filter(|c| *c != std::path::Component::RootDir)
+                        .collect();
+                    let src = rootfs.join(&relative);
+                    if std::fs::symlink_metadata(&src).is_err() {
+                        return Err(format!(
+                            "path {:?} not found in image {}@{}",
+                            path, self.image, digest
+                        ));
+                    }
+                    let dst = target_dir.path.join(&relative);
+                    if let Some(parent) = dst.parent() {
+                        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+                    }
+
+                    // 不跟随符号链接，避免镜像中的绝对路径链接指向主机上的文件
+                    let mut cmd = Command::new("cp");
+                    cmd.arg("-r").arg("-P").arg("-f").arg(&src).arg(&dst);
+                    let proc: std::process::Child = cmd
+                        .stderr(Stdio::piped())
+                        .spawn()
+                        .map_err(|e| e.to_string())?;
+                    let output = proc.wait_with_output().map_err(|e| e.to_string())?;
+                    if !output.status.success() {
+                        return Err(format!(
+                            "Failed to copy {:?} from image, message: {}",
+                            path,
+                            StdioUtils::tail_n_str(StdioUtils::stderr_to_lines(&output.stderr), 5)
+                        ));
+                    }
+                }
+            }
+        }
+
+        return Ok(digest);
+    }
 }
 
 pub struct ArchiveFile {
     archive_path: PathBuf,
     archive_name: String,
     archive_type: ArchiveType,
+    /// 只提取的子目录（相对于压缩包的顶层目录）
+    subdir: Option<PathBuf>,
+    /// 写入文件的线程数
+    jobs: usize,
 }
 
 impl ArchiveFile {
@@ -582,6 +2239,8 @@ impl ArchiveFile {
                     archive_path: archive_path.parent().unwrap().to_path_buf(),
                     archive_name: archive_name.to_string(),
                     archive_type: archivetype,
+                    subdir: None,
+                    jobs: 1,
                 };
             }
         }
@@ -589,83 +2248,86 @@ impl ArchiveFile {
             archive_path: archive_path.parent().unwrap().to_path_buf(),
             archive_name: archive_name.to_string(),
             archive_type: ArchiveType::Undefined,
+            subdir: None,
+            jobs: 1,
         }
     }
 
-    /// @brief 对self.archive_path路径下名为self.archive_name的压缩文件(tar.gz或zip)进行解压缩
-    ///
-    /// 在此函数中进行路径和文件名有效性的判断，如果有效的话就开始解压缩，根据ArchiveType枚举类型来
-    /// 生成不同的命令来对压缩文件进行解压缩，暂时只支持tar.gz和zip格式，并且都是通过调用bash来解压缩
-    /// 没有引入第三方rust库
+    /// 只提取压缩包顶层目录下的`subdir`，并去掉这个前缀
+    pub fn with_subdir(mut self, subdir: Option<PathBuf>) -> Self {
+        self.subdir = subdir;
+        return self;
+    }
+
+    /// 压缩包中的路径是否需要提取。`path`的第一级是压缩包的顶层目录
+    fn selects(&self, path: &Path) -> bool {
+        let subdir = match &self.subdir {
+            Some(subdir) => subdir,
+            None => return true,
+        };
+        let mut components = path.components().skip_while(|c| *c == Component::CurDir);
+        if components.next().is_none() {
+            return false;
+        }
+        let relative: PathBuf = components.collect();
+        // 子目录的上级目录也要创建
+        return relative.starts_with(subdir) || subdir.starts_with(&relative);
+    }
+
+    /// 设置写入文件的线程数
+    pub fn with_jobs(mut self, jobs: usize) -> Self {
+        self.jobs = jobs.max(1);
+        return self;
+    }
+
+    /// @brief 对self.archive_path路径下名为self.archive_name的压缩文件(tar.gz、tar.xz或zip)进行解压缩
     ///
+    /// 在此函数中进行路径和文件名有效性的判断，如果有效的话就开始解压缩。压缩包在进程内解压（见[`extract`]），
+    /// tar.xz通过`xz -dc`解压缩数据流。成员名中包含`..`时解压失败
     ///
     /// @return 根据结果返回OK或Err
-
     pub fn unzip(&self) -> Result<(), String> {
         let path = &self.archive_path;
         if !path.is_dir() {
             return Err(format!("Archive directory {:?} is wrong", path));
         }
-        if !path.join(&self.archive_name).is_file() {
-            return Err(format!(
-                " {:?} is not a file",
-                path.join(&self.archive_name)
-            ));
+        let archive = path.join(&self.archive_name);
+        if !archive.is_file() {
+            return Err(format!(" {:?} is not a file", archive));
         }
-        //根据压缩文件的类型生成cmd指令
+        let selects = |p: &Path| self.selects(p);
+        let failed = |e: String| format!("unzip {} failed: {}", self.archive_name, e);
         match &self.archive_type {
-            ArchiveType::TarGz | ArchiveType::TarXz => {
-                let mut cmd = Command::new("tar");
-                cmd.arg("-xf").arg(&self.archive_name);
-                let proc: std::process::Child = cmd
-                    .current_dir(path)
+            ArchiveType::TarGz => {
+                let file = File::open(&archive).map_err(|e| failed(e.to_string()))?;
+                let reader = MultiGzDecoder::new(BufReader::new(file));
+                extract::extract_tar(reader, path, self.jobs, &selects).map_err(failed)?;
+            }
+            ArchiveType::TarXz => {
+                let mut child = Command::new("xz")
+                    .arg("-dc")
+                    .arg(&archive)
+                    .stdout(Stdio::piped())
                     .stderr(Stdio::piped())
-                    .stdout(Stdio::inherit())
                     .spawn()
-                    .map_err(|e| e.to_string())?;
-                let output = proc.wait_with_output().map_err(|e| e.to_string())?;
+                    .map_err(|e| failed(format!("failed to run xz: {}", e)))?;
+                let r =
+                    extract::extract_tar(child.stdout.take().unwrap(), path, self.jobs, &selects);
+                let output = child
+                    .wait_with_output()
+                    .map_err(|e| failed(e.to_string()))?;
+                // 解压出错时xz可能因为管道关闭而失败，先返回解压的错误
+                r.map_err(failed)?;
                 if !output.status.success() {
-                    return Err(format!(
-                        "unzip failed, status: {:?},  stderr: {:?}",
+                    return Err(failed(format!(
+                        "xz status: {:?},  stderr: {:?}",
                         output.status,
                         StdioUtils::tail_n_str(StdioUtils::stderr_to_lines(&output.stderr), 5)
-                    ));
+                    )));
                 }
             }
-
             ArchiveType::Zip => {
-                let file = File::open(&self.archive_path.join(&self.archive_name))
-                    .map_err(|e| e.to_string())?;
-                let mut archive = ZipArchive::new(file).map_err(|e| e.to_string())?;
-                for i in 0..archive.len() {
-                    let mut file = archive.by_index(i).map_err(|e| e.to_string())?;
-                    let outpath = match file.enclosed_name() {
-                        Some(path) => self.archive_path.join(path),
-                        None => continue,
-                    };
-                    if (*file.name()).ends_with('/') {
-                        std::fs::create_dir_all(&outpath).map_err(|e| e.to_string())?;
-                    } else {
-                        if let Some(p) = outpath.parent() {
-                            if !p.exists() {
-                                std::fs::create_dir_all(&p).map_err(|e| e.to_string())?;
-                            }
-                        }
-                        let mut outfile = File::create(&outpath).map_err(|e| e.to_string())?;
-                        std::io::copy(&mut file, &mut outfile).map_err(|e| e.to_string())?;
-                    }
-                    //设置解压后权限，在Linux中Unzip会丢失权限
-                    #[cfg(unix)]
-                    {
-                        if let Some(mode) = file.unix_mode() {
-                            std::fs::set_permissions(
-                                &outpath,
-                                std::fs::Permissions::from_mode(mode),
-                            )
-                            .map_err(|e| e.to_string())?;
-                        }
-                    }
-                }
+                extract::extract_zip(&archive, path, self.jobs, &selects).map_err(failed)?;
             }
             _ => {
                 return Err("unsupported archive type".to_string());
@@ -675,14 +2337,30 @@ impl ArchiveFile {
         info!("unzip successfully, removing archive ");
         std::fs::remove_file(path.join(&self.archive_name)).map_err(|e| e.to_string())?;
         //从解压的文件夹中提取出文件并删除下载的压缩包等价于指令"cd *;mv ./* ../../"
+        //指定了子目录时，等价于"cd */<subdir>;mv ./* <target_dir>"
+        let mut found = false;
         for entry in path.read_dir().map_err(|e| e.to_string())? {
             let entry = entry.map_err(|e| e.to_string())?;
             let path = entry.path();
-            FileUtils::move_files(&path, &self.archive_path.parent().unwrap())
-                .map_err(|e| e.to_string())?;
+            let src = match &self.subdir {
+                Some(subdir) => path.join(subdir),
+                None => path.clone(),
+            };
+            if self.subdir.is_none() || src.is_dir() {
+                found = true;
+                FileUtils::move_files(&src, self.archive_path.parent().unwrap())
+                    .map_err(|e| e.to_string())?;
+            }
             //删除空的单独文件夹
             std::fs::remove_dir_all(&path).map_err(|e| e.to_string())?;
         }
+        if let (Some(subdir), false) = (&self.subdir, found) {
+            return Err(format!(
+                "subdir {} not found in archive {}",
+                subdir.display(),
+                self.archive_name
+            ));
+        }
         return Ok(());
     }
 }
@@ -0,0 +1,89 @@
+//! # 安装时剥离调试符号
+//!
+//! 任务设置了`install.strip`时，执行器在拷贝构建结果后，对安装的ELF可执行文件和共享库执行`strip`，
+//! 以减小DragonOS镜像的大小。构建缓存中的文件保持不变。
+//! `"debug"`只剥离调试信息（`--strip-debug`），`true`或`"all"`剥离所有符号（`--strip-all`）。
+//!
+//! 剥离单个文件失败时只输出警告，设置了`install.strict_strip`时安装失败。
+//! 减少的字节数记录在任务的执行结果中。
+//!
+//! 通过文件开头的魔数识别ELF文件，其他文件不做修改。
+//! 可重定位文件（`.o`）和静态库中的符号在链接时需要，也不会被剥离。
+
+use std::{
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use crate::parser::task::TargetArch;
+
+/// 指定strip工具的环境变量名
+pub const STRIP_ENV_KEY: &str = "STRIP";
+
+const ELF_MAGIC: [u8; 4] = [0x7f, b'E', b'L', b'F'];
+/// ELF头中的文件类型：可执行文件
+const ET_EXEC: u16 = 2;
+/// ELF头中的文件类型：共享库或位置无关的可执行文件
+const ET_DYN: u16 = 3;
+
+/// # 查找目标架构的strip工具
+///
+/// 按以下顺序查找：
+/// 1. `configured`（任务或全局环境变量中的`STRIP`），没有时使用DADK进程的环境变量`STRIP`
+/// 2. 交叉编译工具链中的strip，例如`x86_64-linux-musl-strip`
+/// 3. `llvm-strip`，支持所有架构
+/// 4. 目标架构与主机相同时，主机的`strip`
+pub fn find_strip_tool(arch: TargetArch, configured: Option<String>) -> Option<PathBuf> {
+    if let Some(tool) = configured.or_else(|| std::env::var(STRIP_ENV_KEY).ok()) {
+        if !tool.trim().is_empty() {
+            return find_executable(tool.trim());
+        }
+    }
+    let mut candidates = vec![
+        format!("{}-strip", arch.cross_prefix()),
+        "llvm-strip".to_string(),
+    ];
+    if TargetArch::try_from(std::env::consts::ARCH) == Ok(arch) {
+        candidates.push("strip".to_string());
+    }
+    return candidates.iter().find_map(|name| find_executable(name));
+}
+
+/// 在`PATH`中查找可执行文件，名称中带有路径分隔符时直接检查该路径
+pub(crate) fn find_executable(name: &str) -> Option<PathBuf> {
+    if name.contains('/') {
+        let path = PathBuf::from(name);
+        return path.is_file().then_some(path);
+    }
+    let paths = std::env::var_os("PATH")?;
+    return std::env::split_paths(&paths)
+        .map(|dir| dir.join(name))
+        .find(|path| path.is_file());
+}
+
+/// # 判断文件是否为可以剥离符号的ELF文件
+///
+/// 只有可执行文件和共享库返回true
+pub fn is_strippable_elf(path: &Path) -> std::io::Result<bool> {
+    let mut header = [0u8; 18];
+    let mut file = File::open(path)?;
+    let mut read = 0;
+    while read < header.len() {
+        let n = file.read(&mut header[read..])?;
+        if n == 0 {
+            return Ok(false);
+        }
+        read += n;
+    }
+    if header[..4] != ELF_MAGIC {
+        return Ok(false);
+    }
+    // EI_DATA：1为小端，2为大端
+    let e_type = match header[5] {
+        1 => u16::from_le_bytes([header[16], header[17]]),
+        2 => u16::from_be_bytes([header[16], header[17]]),
+        _ => return Ok(false),
+    };
+    return Ok(e_type == ET_EXEC || e_type == ET_DYN);
+}
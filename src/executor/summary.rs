@@ -0,0 +1,112 @@
+//! # 构建计划摘要
+//!
+//! 在开始构建之前输出一行摘要：任务数、预计命中缓存的任务数、需要访问网络获取源文件的任务数以及目标架构。
+//!
+//! 摘要在检查依赖之后、修改任何文件之前计算，只读取任务数据目录中的执行记录，不会下载任何内容，
+//! 因此只是一个预测：
+//!
+//! - 设置了`build_once`、上一次构建成功、并且`rerun_if_changed`中的路径和继承的全局环境变量都没有改变的任务视为命中缓存
+//! - Git源的任务即使命中缓存，也需要拉取仓库以确认提交没有改变，因此总是计入下载数
+//! - `meta`任务没有构建步骤，视为命中缓存
+
+use std::{collections::BTreeSet, fmt::Display, sync::Arc};
+
+use crate::{
+    parser::{
+        task::{CodeSource, DADKTask, PrebuiltSource, TargetArch, TaskType},
+        task_log::BuildStatus,
+    },
+    scheduler::SchedEntity,
+};
+
+use super::{cache::TaskDataDir, fetch, Executor};
+
+/// 构建计划摘要
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlanSummary {
+    /// 要执行的任务数
+    pub tasks: usize,
+    /// 预计命中缓存、不需要重新构建的任务数
+    pub cache_hits: usize,
+    /// 预计需要访问网络获取源文件的任务数
+    pub downloads: usize,
+    /// 目标架构（包括任务固定的架构），从小到大排列
+    pub arches: Vec<TargetArch>,
+}
+
+impl PlanSummary {
+    /// 需要构建的任务数
+    pub fn to_build(&self) -> usize {
+        return self.tasks - self.cache_hits;
+    }
+}
+
+impl Display for PlanSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let arches: Vec<&str> = self.arches.iter().map(|a| (*a).into()).collect();
+        write!(
+            f,
+            "Plan: {} tasks ({} cached, {} to build), {} downloads, target arch: {}",
+            self.tasks,
+            self.cache_hits,
+            self.to_build(),
+            self.downloads,
+            arches.join(", ")
+        )
+    }
+}
+
+/// # 计算构建计划摘要
+///
+/// `tasks`是要执行的调度实体，`arches`是命令行指定的目标架构
+pub fn plan_summary(tasks: &[Arc<SchedEntity>], arches: &[TargetArch]) -> PlanSummary {
+    let mut summary = PlanSummary {
+        tasks: tasks.len(),
+        cache_hits: 0,
+        downloads: 0,
+        arches: Vec::new(),
+    };
+    let mut all_arches: BTreeSet<TargetArch> = arches.iter().copied().collect();
+    for entity in tasks {
+        let task = entity.task();
+        let cached = predict_cache_hit(entity, &task);
+        if cached {
+            summary.cache_hits += 1;
+        }
+        if fetch::fetches_remote(&task) && (!cached || has_git_source(&task)) {
+            summary.downloads += 1;
+        }
+        all_arches.extend(task.pinned_arch);
+    }
+    summary.arches = all_arches.into_iter().collect();
+    return summary;
+}
+
+/// 任务是否预计命中缓存，判断方式与`Executor::build`相同，但不检查Git提交
+fn predict_cache_hit(entity: &SchedEntity, task: &DADKTask) -> bool {
+    if task.task_type == TaskType::Meta {
+        return true;
+    }
+    if !task.build_once {
+        return false;
+    }
+    let task_log = TaskDataDir::task_log_of(task);
+    if task_log.build_status() != Some(&BuildStatus::Success)
+        || task_log.global_envs_hash() != task.global_envs_hash().as_ref()
+    {
+        return false;
+    }
+    let recorded = task_log.rerun_if_changed().cloned().unwrap_or_default();
+    return Executor::watched_hashes_of(entity)
+        .iter()
+        .all(|(path, hash)| recorded.get(path) == Some(hash));
+}
+
+fn has_git_source(task: &DADKTask) -> bool {
+    return matches!(
+        task.task_type,
+        TaskType::BuildFromSource(CodeSource::Git(_))
+            | TaskType::KernelModule(CodeSource::Git(_))
+            | TaskType::InstallFromPrebuilt(PrebuiltSource::Git(_))
+    );
+}
@@ -0,0 +1,84 @@
+//! # 系统依赖
+//!
+//! `kind`为`"system"`的依赖是主机上的软件包（例如`pkg-config`、`cmake`），不是DADK任务，不会被调度。
+//! 构建任务前以及预检时检查：
+//!
+//! - 依赖的可执行文件（`name`）在`PATH`中
+//! - 版本要求不是`*`时，执行`version_command`，取输出中第一个形如`3.22.1`的版本号与版本要求比较
+//!
+//! ```json
+//! "depends": [
+//!   { "name": "pkg-config", "version": "*", "kind": "system" },
+//!   { "name": "cmake", "version": ">=3.20", "kind": "system", "version_command": "cmake --version" }
+//! ]
+//! ```
+
+use std::process::Command;
+
+use regex::Regex;
+
+use crate::parser::{resolver::VersionReq, task::Dependency};
+
+use super::strip::find_executable;
+
+/// # 检查系统依赖是否存在、版本是否满足要求
+///
+/// 成功时返回检查到的版本（没有检查版本时为None）
+pub fn check(dep: &Dependency) -> Result<Option<String>, String> {
+    if find_executable(&dep.name).is_none() {
+        return Err(format!(
+            "system dependency {} not found in PATH, install it on the host",
+            dep.name
+        ));
+    }
+    let cmd = match &dep.version_command {
+        Some(cmd) if dep.version != "*" => cmd,
+        _ => return Ok(None),
+    };
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(cmd)
+        .output()
+        .map_err(|e| {
+            format!(
+                "system dependency {}: failed to run `{}`: {}",
+                dep.name, cmd, e
+            )
+        })?;
+    if !output.status.success() {
+        return Err(format!(
+            "system dependency {}: `{}` failed ({})",
+            dep.name, cmd, output.status
+        ));
+    }
+    // 有些工具把版本输出到标准错误
+    let text = format!(
+        "{}\n{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let version = find_version(&text).ok_or_else(|| {
+        format!(
+            "system dependency {}: no version number in the output of `{}`",
+            dep.name, cmd
+        )
+    })?;
+    let req = VersionReq::parse(&dep.version)?;
+    if !req.matches(&version) {
+        return Err(format!(
+            "system dependency {}: version {} does not satisfy {}",
+            dep.name, version, dep.version
+        ));
+    }
+    return Ok(Some(version));
+}
+
+/// 输出中第一个由`.`分隔的数字版本号，没有时取第一个数字
+fn find_version(text: &str) -> Option<String> {
+    let re = Regex::new(r"\d+(\.\d+)+").unwrap();
+    if let Some(m) = re.find(text) {
+        return Some(m.as_str().to_string());
+    }
+    let re = Regex::new(r"\d+").unwrap();
+    return re.find(text).map(|m| m.as_str().to_string());
+}
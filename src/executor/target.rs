@@ -12,7 +12,7 @@ use crate::{
     static_resources::INLINE_TARGETS,
 };
 
-use crate::executor::ExecutorError;
+use crate::executor::ExecError;
 
 /// Target用于管理target文件
 #[derive(Debug, Clone)]
@@ -46,8 +46,8 @@ impl Target {
     /// ## 返回值
     ///
     /// Ok(()) 拷贝成功
-    /// Err(ExecutorError) 拷贝失败
-    pub fn cp_to_tmp(&self, rust_target: &str) -> Result<(), ExecutorError> {
+    /// Err(ExecError) 拷贝失败
+    pub fn cp_to_tmp(&self, rust_target: &str) -> Result<(), ExecError> {
         // 创建临时target文件
         if Self::is_user_target(rust_target) {
             // 如果是用户的target文件，则从源target文件路径from拷贝
@@ -60,23 +60,23 @@ impl Target {
         return Ok(());
     }
 
-    pub fn copy_to_tmp(&self, from: &PathBuf) -> Result<(), ExecutorError> {
+    pub fn copy_to_tmp(&self, from: &PathBuf) -> Result<(), ExecError> {
         //创建临时target文件
         self.create_tmp_target()?;
         if let Err(e) = fs::copy(from, &self.tmp_target_path) {
-            return Err(ExecutorError::PrepareEnvError(format!("{}", e)));
+            return Err(ExecError::Validation(format!("{}", e)));
         }
         return Ok(());
     }
 
-    pub fn write_to_tmp(&self, rust_target: &str) -> Result<(), ExecutorError> {
+    pub fn write_to_tmp(&self, rust_target: &str) -> Result<(), ExecError> {
         // 创建临时target文件
         let file = self.create_tmp_target()?;
         let data = INLINE_TARGETS.lock().unwrap().get(rust_target)?;
         // 将target文件的二进制变量写入临时target文件中
         if file.is_some() {
             if let Err(e) = file.unwrap().write_all(&data) {
-                return Err(ExecutorError::PrepareEnvError(format!("{}", e)));
+                return Err(ExecError::Validation(format!("{}", e)));
             }
         }
         return Ok(());
@@ -91,8 +91,8 @@ impl Target {
     /// ## 返回值
     ///
     /// Ok(PathBuf) 用户target文件路径
-    /// Err(ExecutorError) 用户target文件路径无效
-    pub fn user_target_path(rust_target: &str) -> Result<PathBuf, ExecutorError> {
+    /// Err(ExecError) 用户target文件路径无效
+    pub fn user_target_path(rust_target: &str) -> Result<PathBuf, ExecError> {
         // 如果是个路径，说明是用户自己的编译target文件，就判断文件是否有效
         let path = PathBuf::from(rust_target);
         if path.exists() {
@@ -101,7 +101,7 @@ impl Target {
             let path = path.as_path().to_str().unwrap();
             let errmsg = format!("Can not find the rust_target file: {}", path);
             error!("{errmsg}");
-            return Err(ExecutorError::PrepareEnvError(errmsg));
+            return Err(ExecError::Validation(errmsg));
         }
     }
 
@@ -133,17 +133,13 @@ impl Target {
     ///
     /// Ok(Some(fs::File)) 创建成功后的文件
     /// Ok(None) 临时target文件已经存在，不需要再创建
-    /// Err(ExecutorError) 创建失败
-    pub fn create_tmp_target(&self) -> Result<Option<fs::File>, ExecutorError> {
+    /// Err(ExecError) 创建失败
+    pub fn create_tmp_target(&self) -> Result<Option<fs::File>, ExecError> {
         // 先创建用于存放临时target文件的临时dadk目录
         let dir = Self::dir(&self.tmp_target_path);
         if fs::metadata(dir.clone()).is_err() {
             if let Err(e) = fs::create_dir(dir.clone()) {
-                return Err(ExecutorError::PrepareEnvError(format!(
-                    "{}{}",
-                    dir.display(),
-                    e
-                )));
+                return Err(ExecError::Validation(format!("{}{}", dir.display(), e)));
             }
         }
 
@@ -177,11 +173,11 @@ impl Target {
     }
 
     /// 清理生成的临时dadk目录
-    pub fn clean_tmpdadk(&self) -> Result<(), ExecutorError> {
+    pub fn clean_tmpdadk(&self) -> Result<(), ExecError> {
         if self.tmp_target_path.exists() {
             let dir = Self::dir(&self.tmp_target_path);
             std::fs::remove_dir_all(&dir)
-                .map_err(|e| ExecutorError::CleanError(format!("{}{}", dir.display(), e)))?;
+                .map_err(|e| ExecError::Clean(format!("{}{}", dir.display(), e)))?;
         }
         return Ok(());
     }
@@ -0,0 +1,128 @@
+//! # 校验安装的文件
+//!
+//! 拷贝到sysroot中的文件在拷贝后与构建结果中的源文件比较大小和sha256值，
+//! 以发现被截断的拷贝（例如磁盘已满时）。不一致时删除目标文件并重新拷贝一次，仍然不一致时安装失败，
+//! 错误信息中包括两个文件的路径以及不一致的地方。
+//!
+//! 不跟随符号链接时，符号链接被原样重建，不做校验。跟随指向目录的符号链接时，校验被拷贝的目录中的文件。
+
+use std::path::{Path, PathBuf};
+
+use log::warn;
+
+use crate::utils::{file::FileUtils, hash::HashUtils};
+
+/// 拷贝一个文件
+pub type CopyFn = dyn Fn(&Path, &Path) -> std::io::Result<()>;
+
+/// 默认的拷贝方式
+fn fs_copy(src: &Path, dst: &Path) -> std::io::Result<()> {
+    return std::fs::copy(src, dst).map(|_| ());
+}
+
+/// # 校验拷贝到`dst_dir`中的文件
+///
+/// `files`是文件相对于`src_dir`和`dst_dir`的路径
+pub fn verify_dir(
+    src_dir: &Path,
+    dst_dir: &Path,
+    files: &[PathBuf],
+    follow_symlinks: bool,
+) -> Result<(), String> {
+    return verify_dir_with(src_dir, dst_dir, files, follow_symlinks, &fs_copy);
+}
+
+/// 与[`verify_dir`]相同，重新拷贝时使用`copy`
+pub fn verify_dir_with(
+    src_dir: &Path,
+    dst_dir: &Path,
+    files: &[PathBuf],
+    follow_symlinks: bool,
+    copy: &CopyFn,
+) -> Result<(), String> {
+    for file in files {
+        verify_copy_with(
+            &src_dir.join(file),
+            &dst_dir.join(file),
+            follow_symlinks,
+            copy,
+        )?;
+    }
+    return Ok(());
+}
+
+/// # 校验一个拷贝了的文件
+pub fn verify_copy(src: &Path, dst: &Path, follow_symlinks: bool) -> Result<(), String> {
+    return verify_copy_with(src, dst, follow_symlinks, &fs_copy);
+}
+
+/// 与[`verify_copy`]相同，重新拷贝时使用`copy`
+pub fn verify_copy_with(
+    src: &Path,
+    dst: &Path,
+    follow_symlinks: bool,
+    copy: &CopyFn,
+) -> Result<(), String> {
+    if src.is_symlink() && !follow_symlinks {
+        return Ok(());
+    }
+    if src.is_dir() {
+        let files = FileUtils::list_files_recursive(src)
+            .map_err(|e| format!("Failed to list files in {}: {}", src.display(), e))?;
+        let files: Vec<PathBuf> = files
+            .iter()
+            .map(|f| f.strip_prefix(src).unwrap().to_path_buf())
+            .collect();
+        return verify_dir_with(src, dst, &files, true, copy);
+    }
+    let mismatch = match compare(src, dst)? {
+        Some(mismatch) => mismatch,
+        None => return Ok(()),
+    };
+    warn!(
+        "Installed file {} does not match {} ({}), copying it again",
+        dst.display(),
+        src.display(),
+        mismatch
+    );
+    if dst.symlink_metadata().is_ok() {
+        std::fs::remove_file(dst)
+            .map_err(|e| format!("Failed to remove {}: {}", dst.display(), e))?;
+    }
+    copy(src, dst).map_err(|e| format!("Failed to copy {}: {}", src.display(), e))?;
+    if let Some(mismatch) = compare(src, dst)? {
+        return Err(format!(
+            "installed file {} does not match {} after retrying the copy: {}",
+            dst.display(),
+            src.display(),
+            mismatch
+        ));
+    }
+    return Ok(());
+}
+
+/// 比较两个文件的大小和sha256值，返回不一致的地方
+fn compare(src: &Path, dst: &Path) -> Result<Option<String>, String> {
+    let src_meta =
+        std::fs::metadata(src).map_err(|e| format!("Failed to read {}: {}", src.display(), e))?;
+    let dst_meta = match std::fs::metadata(dst) {
+        Ok(meta) => meta,
+        Err(e) => return Ok(Some(format!("cannot read the installed file: {}", e))),
+    };
+    if src_meta.len() != dst_meta.len() {
+        return Ok(Some(format!(
+            "size {} != {} bytes",
+            dst_meta.len(),
+            src_meta.len()
+        )));
+    }
+    let hash = |path: &Path| {
+        HashUtils::sha256_file(path)
+            .map_err(|e| format!("Failed to hash {}: {}", path.display(), e))
+    };
+    let (src_hash, dst_hash) = (hash(src)?, hash(dst)?);
+    if src_hash != dst_hash {
+        return Ok(Some(format!("sha256 {} != {}", dst_hash, src_hash)));
+    }
+    return Ok(None);
+}
@@ -0,0 +1,102 @@
+//! # 依赖关系图导出
+//!
+//! 把任务之间的依赖关系导出为Graphviz DOT格式的字符串。
+//!
+//! 导出的结果可以使用`dot`命令渲染为图片：
+//!
+//! ```shell
+//! dot -Tsvg deps.dot -o deps.svg
+//! ```
+
+use std::collections::BTreeSet;
+
+use crate::parser::task::{DADKTask, TaskType};
+
+use super::find_task;
+
+/// 元包节点的样式
+const META_NODE_STYLE: &str = "shape=folder";
+
+/// 未找到的依赖项节点的样式
+const UNRESOLVED_NODE_STYLE: &str = "style=dashed, color=red, fontcolor=red";
+
+/// # 把任务列表的依赖关系导出为Graphviz DOT格式
+///
+/// 每个任务是一个节点，节点名为任务的`name_version()`；每个依赖项是一条从任务指向其依赖的边。
+/// 如果某个依赖项在任务列表中不存在，则这条边会指向一个使用特殊样式标记的“unresolved”节点。
+///
+/// ## 参数
+///
+/// - `tasks` : 任务列表
+/// - `color_by_type` : 是否根据任务类型为节点着色
+///
+/// ## 返回值
+///
+/// DOT格式的字符串
+pub fn dependency_graph_dot(tasks: &[DADKTask], color_by_type: bool) -> String {
+    let mut dot = String::new();
+    dot.push_str("digraph dadk {\n");
+    dot.push_str("    rankdir=LR;\n");
+    dot.push_str("    node [shape=box];\n");
+
+    for task in tasks {
+        let node = task.name_version();
+        let mut attrs = format!("label=\"{}\"", escape(&node));
+        if task.task_type == TaskType::Meta {
+            attrs.push_str(&format!(", {}", META_NODE_STYLE));
+        }
+        if color_by_type {
+            attrs.push_str(&format!(
+                ", style=filled, fillcolor=\"{}\"",
+                task_type_color(&task.task_type)
+            ));
+        }
+        dot.push_str(&format!("    \"{}\" [{}];\n", escape(&node), attrs));
+    }
+
+    let mut unresolved: BTreeSet<String> = BTreeSet::new();
+    for task in tasks {
+        for dep in task.depends.iter().filter(|d| d.is_task()) {
+            let target = match find_task(tasks, &dep.name, &dep.version) {
+                Some(t) => t.name_version(),
+                None => {
+                    let name = dep.name_version();
+                    unresolved.insert(name.clone());
+                    name
+                }
+            };
+            dot.push_str(&format!(
+                "    \"{}\" -> \"{}\";\n",
+                escape(&task.name_version()),
+                escape(&target)
+            ));
+        }
+    }
+
+    for node in unresolved.iter() {
+        dot.push_str(&format!(
+            "    \"{}\" [label=\"{} (unresolved)\", {}];\n",
+            escape(node),
+            escape(node),
+            UNRESOLVED_NODE_STYLE
+        ));
+    }
+
+    dot.push_str("}\n");
+    return dot;
+}
+
+fn task_type_color(task_type: &TaskType) -> &'static str {
+    match task_type {
+        TaskType::BuildFromSource(_) => "lightblue",
+        TaskType::InstallFromPrebuilt(_) => "lightyellow",
+        TaskType::NoSource => "lightgrey",
+        TaskType::Meta => "palegreen",
+        TaskType::KernelModule(_) => "lightsalmon",
+    }
+}
+
+/// 转义DOT字符串中的特殊字符
+fn escape(s: &str) -> String {
+    return s.replace('\\', "\\\\").replace('"', "\\\"");
+}
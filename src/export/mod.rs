@@ -0,0 +1,21 @@
+//! # 任务信息导出
+//!
+//! 用于把解析得到的任务列表导出为其他工具能够使用的格式。
+//!
+//! - `dot`：把任务之间的依赖关系导出为Graphviz DOT格式，便于调试大型工作区的依赖关系
+//! - `schema`：导出描述任务配置文件格式的JSON Schema，便于编辑器和CI校验配置文件
+//! - `sbom`：把工作区中的任务导出为CycloneDX格式的软件物料清单（SBOM）
+
+use crate::parser::task::DADKTask;
+
+pub mod dot;
+pub mod sbom;
+pub mod schema;
+#[cfg(test)]
+mod tests;
+
+/// 根据名称和版本查找任务，匹配规则与调度器一致
+fn find_task<'a>(tasks: &'a [DADKTask], name: &str, version: &str) -> Option<&'a DADKTask> {
+    let expected = DADKTask::name_version_uppercase(name, version);
+    return tasks.iter().find(|t| t.name_version_env() == expected);
+}
@@ -0,0 +1,274 @@
+//! # CycloneDX SBOM导出
+//!
+//! 把工作区中的所有任务导出为CycloneDX（JSON格式）的软件物料清单：
+//!
+//! - 每个任务对应一个组件，`bom-ref`为任务的`name_version()`
+//! - 组件的来源坐标取自任务的源配置，如果提供了锁文件，则使用锁文件中记录的、
+//!   构建时实际解析出的源码身份（Git的commit、压缩包的sha256值等）
+//! - 任务之间已解析的依赖关系被导出到`dependencies`中，找不到的依赖会被忽略
+
+use std::path::Path;
+
+use serde_json::{json, Map, Value};
+
+use crate::{
+    executor::{
+        lock::{LockFile, LockedSource},
+        source::{
+            ArchiveSource, GitSource, HgSource, LocalSource, OciSource, ReleaseAssetSource,
+            SvnSource,
+        },
+    },
+    parser::task::{CodeSource, DADKTask, PrebuiltSource, TaskType},
+};
+
+use super::find_task;
+
+/// CycloneDX规范的版本
+const SPEC_VERSION: &str = "1.5";
+
+/// # 生成CycloneDX SBOM
+///
+/// ## 参数
+///
+/// * `tasks` - 工作区中的所有任务
+/// * `lock_path` - 锁文件的路径，如果为`None`或者锁文件不存在，则只使用任务配置中的源信息
+///
+/// ## 返回值
+///
+/// * `Ok(Value)` - CycloneDX JSON文档
+/// * `Err(String)` - 读取锁文件失败
+pub fn cyclonedx_sbom(tasks: &[DADKTask], lock_path: Option<&Path>) -> Result<Value, String> {
+    let lock = match lock_path {
+        Some(path) => {
+            Some(LockFile::load(path.to_path_buf(), false).map_err(|e| format!("{:?}", e))?)
+        }
+        None => None,
+    };
+    return Ok(build_sbom(tasks, lock.as_ref()));
+}
+
+fn build_sbom(tasks: &[DADKTask], lock: Option<&LockFile>) -> Value {
+    let components: Vec<Value> = tasks
+        .iter()
+        .map(|task| component(task, lock.and_then(|l| l.get(&task.name_version()))))
+        .collect();
+
+    let dependencies: Vec<Value> = tasks
+        .iter()
+        .map(|task| {
+            let mut depends_on: Vec<String> = task
+                .depends
+                .iter()
+                .filter(|dep| dep.is_task())
+                .filter_map(|dep| find_task(tasks, &dep.name, &dep.version))
+                .map(|t| t.name_version())
+                .collect();
+            depends_on.sort();
+            depends_on.dedup();
+            json!({ "ref": task.name_version(), "dependsOn": depends_on })
+        })
+        .collect();
+
+    return json!({
+        "bomFormat": "CycloneDX",
+        "specVersion": SPEC_VERSION,
+        "version": 1,
+        "metadata": {
+            "tools": [{ "name": "dadk", "version": env!("CARGO_PKG_VERSION") }]
+        },
+        "components": components,
+        "dependencies": dependencies,
+    });
+}
+
+/// 生成单个任务对应的组件
+fn component(task: &DADKTask, locked: Option<&LockedSource>) -> Value {
+    let mut obj = Map::new();
+    obj.insert("type".to_string(), json!("application"));
+    obj.insert("bom-ref".to_string(), json!(task.name_version()));
+    obj.insert("name".to_string(), json!(task.name));
+    obj.insert("version".to_string(), json!(task.version));
+    if !task.description.is_empty() {
+        obj.insert("description".to_string(), json!(task.description));
+    }
+
+    let mut refs: Vec<Value> = Vec::new();
+    let mut hashes: Vec<Value> = Vec::new();
+    let mut props: Vec<Value> = Vec::new();
+    match &task.task_type {
+        TaskType::BuildFromSource(CodeSource::Git(git))
+        | TaskType::KernelModule(CodeSource::Git(git))
+        | TaskType::InstallFromPrebuilt(PrebuiltSource::Git(git)) => {
+            git_coordinates(git, locked, &mut refs, &mut props);
+        }
+        TaskType::BuildFromSource(CodeSource::Hg(hg))
+        | TaskType::KernelModule(CodeSource::Hg(hg)) => {
+            hg_coordinates(hg, locked, &mut refs, &mut props);
+        }
+        TaskType::BuildFromSource(CodeSource::Svn(svn))
+        | TaskType::KernelModule(CodeSource::Svn(svn)) => {
+            svn_coordinates(svn, locked, &mut refs, &mut props);
+        }
+        TaskType::BuildFromSource(CodeSource::Archive(archive))
+        | TaskType::KernelModule(CodeSource::Archive(archive))
+        | TaskType::InstallFromPrebuilt(PrebuiltSource::Archive(archive)) => {
+            archive_coordinates(archive, locked, &mut refs, &mut hashes);
+        }
+        TaskType::BuildFromSource(CodeSource::Local(local))
+        | TaskType::KernelModule(CodeSource::Local(local))
+        | TaskType::InstallFromPrebuilt(PrebuiltSource::Local(local)) => {
+            local_coordinates(local, locked, &mut props);
+        }
+        TaskType::BuildFromSource(CodeSource::ReleaseAsset(release))
+        | TaskType::KernelModule(CodeSource::ReleaseAsset(release))
+        | TaskType::InstallFromPrebuilt(PrebuiltSource::ReleaseAsset(release)) => {
+            release_coordinates(release, locked, &mut refs, &mut hashes, &mut props);
+        }
+        TaskType::InstallFromPrebuilt(PrebuiltSource::Oci(oci)) => {
+            oci_coordinates(oci, locked, &mut refs, &mut props);
+        }
+        // 叠加源的每一层都作为组件的来源
+        TaskType::BuildFromSource(CodeSource::Overlay(layers))
+        | TaskType::KernelModule(CodeSource::Overlay(layers)) => {
+            let locked_layers = match locked {
+                Some(LockedSource::Overlay { layers }) => Some(layers),
+                _ => None,
+            };
+            for (i, layer) in layers.iter().enumerate() {
+                let locked = locked_layers.and_then(|l| l.get(i));
+                match layer {
+                    CodeSource::Git(git) => git_coordinates(git, locked, &mut refs, &mut props),
+                    CodeSource::Hg(hg) => hg_coordinates(hg, locked, &mut refs, &mut props),
+                    CodeSource::Svn(svn) => svn_coordinates(svn, locked, &mut refs, &mut props),
+                    CodeSource::Archive(archive) => {
+                        archive_coordinates(archive, locked, &mut refs, &mut hashes)
+                    }
+                    CodeSource::Local(local) => local_coordinates(local, locked, &mut props),
+                    CodeSource::ReleaseAsset(release) => {
+                        release_coordinates(release, locked, &mut refs, &mut hashes, &mut props)
+                    }
+                    CodeSource::Overlay(_) => {}
+                }
+            }
+        }
+        TaskType::NoSource | TaskType::Meta => {}
+    }
+
+    if !refs.is_empty() {
+        obj.insert("externalReferences".to_string(), Value::Array(refs));
+    }
+    if !hashes.is_empty() {
+        obj.insert("hashes".to_string(), Value::Array(hashes));
+    }
+    if !props.is_empty() {
+        obj.insert("properties".to_string(), Value::Array(props));
+    }
+    return Value::Object(obj);
+}
+
+fn git_coordinates(
+    git: &GitSource,
+    locked: Option<&LockedSource>,
+    refs: &mut Vec<Value>,
+    props: &mut Vec<Value>,
+) {
+    refs.push(json!({ "type": "vcs", "url": git.url() }));
+    if let Some(LockedSource::Git { commit, .. }) = locked {
+        props.push(property("dadk:git:commit", commit));
+    } else if let Some(revision) = git.revision() {
+        props.push(property("dadk:git:commit", revision));
+    } else if let Some(branch) = git.branch() {
+        props.push(property("dadk:git:branch", branch));
+    }
+}
+
+fn hg_coordinates(
+    hg: &HgSource,
+    locked: Option<&LockedSource>,
+    refs: &mut Vec<Value>,
+    props: &mut Vec<Value>,
+) {
+    refs.push(json!({ "type": "vcs", "url": hg.url() }));
+    if let Some(LockedSource::Hg { changeset, .. }) = locked {
+        props.push(property("dadk:hg:changeset", changeset));
+    } else if let Some(revision) = hg.revision() {
+        props.push(property("dadk:hg:changeset", revision));
+    } else if let Some(branch) = hg.branch() {
+        props.push(property("dadk:hg:branch", branch));
+    }
+}
+
+fn svn_coordinates(
+    svn: &SvnSource,
+    locked: Option<&LockedSource>,
+    refs: &mut Vec<Value>,
+    props: &mut Vec<Value>,
+) {
+    refs.push(json!({ "type": "vcs", "url": svn.url() }));
+    if let Some(LockedSource::Svn { revision, .. }) = locked {
+        props.push(property("dadk:svn:revision", revision));
+    } else if let Some(revision) = svn.revision() {
+        props.push(property("dadk:svn:revision", revision));
+    }
+}
+
+fn archive_coordinates(
+    archive: &ArchiveSource,
+    locked: Option<&LockedSource>,
+    refs: &mut Vec<Value>,
+    hashes: &mut Vec<Value>,
+) {
+    refs.push(json!({ "type": "distribution", "url": archive.url() }));
+    if let Some(LockedSource::Archive { sha256, .. }) = locked {
+        hashes.push(sha256_hash(sha256));
+    }
+}
+
+fn local_coordinates(local: &LocalSource, locked: Option<&LockedSource>, props: &mut Vec<Value>) {
+    props.push(property(
+        "dadk:local:path",
+        &local.path().display().to_string(),
+    ));
+    if let Some(LockedSource::Local { hash, .. }) = locked {
+        props.push(property("dadk:local:hash", hash));
+    }
+}
+
+fn release_coordinates(
+    release: &ReleaseAssetSource,
+    locked: Option<&LockedSource>,
+    refs: &mut Vec<Value>,
+    hashes: &mut Vec<Value>,
+    props: &mut Vec<Value>,
+) {
+    refs.push(json!({ "type": "distribution", "url": release.repo() }));
+    if let Some(LockedSource::ReleaseAsset { tag, sha256, .. }) = locked {
+        props.push(property("dadk:release:tag", tag));
+        hashes.push(sha256_hash(sha256));
+    } else {
+        props.push(property("dadk:release:tag", release.tag()));
+    }
+}
+
+fn oci_coordinates(
+    oci: &OciSource,
+    locked: Option<&LockedSource>,
+    refs: &mut Vec<Value>,
+    props: &mut Vec<Value>,
+) {
+    refs.push(json!({ "type": "distribution", "url": oci.image() }));
+    if let Some(LockedSource::Oci { digest, .. }) = locked {
+        props.push(property("dadk:oci:digest", digest));
+    } else {
+        props.push(property("dadk:oci:reference", &oci.reference()));
+    }
+}
+
+fn property(name: &str, value: &str) -> Value {
+    return json!({ "name": name, "value": value });
+}
+
+fn sha256_hash(content: &str) -> Value {
+    return json!({ "alg": "SHA-256", "content": content });
+}
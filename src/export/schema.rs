@@ -0,0 +1,523 @@
+//! # 配置文件JSON Schema导出
+//!
+//! 生成描述DADK任务配置文件（`.dadk`）格式的JSON Schema（draft-07），
+//! 便于编辑器提供补全、CI对配置文件进行校验。
+//!
+//! Schema是按照`DADKTask`及其字段的类型手工构造的，修改这些类型时需要同步修改这里。
+//! 单元测试会把每一种任务类型、源类型的任务序列化后用Schema校验，以保证二者一致。
+
+use serde_json::{json, Value};
+
+use crate::parser::task::TargetArch;
+
+/// JSON Schema的版本
+const SCHEMA_DRAFT: &str = "http://json-schema.org/draft-07/schema#";
+
+/// # 生成DADK任务配置文件的JSON Schema
+pub fn task_config_schema() -> Value {
+    return json!({
+        "$schema": SCHEMA_DRAFT,
+        "title": "DADK task config",
+        "description": "DADK任务配置文件（.dadk）",
+        "type": "object",
+        "required": [
+            "name", "version", "description", "task_type", "depends", "build", "install", "clean"
+        ],
+        "properties": {
+            "name": { "type": "string", "description": "包名" },
+            "version": { "type": "string", "description": "版本" },
+            "description": { "type": "string", "description": "包的描述" },
+            "rust_target": { "type": ["string", "null"], "description": "编译target" },
+            "task_type": { "$ref": "#/definitions/TaskType" },
+            "depends": {
+                "type": "array",
+                "description": "依赖的包",
+                "items": { "$ref": "#/definitions/Dependency" }
+            },
+            "build": build_config(),
+            "install": {
+                "type": "object",
+                "description": "安装配置",
+                "properties": {
+                    "in_dragonos_path": { "type": ["string", "null"] },
+                    "destinations": {
+                        "type": "array",
+                        "items": {
+                            "type": "object",
+                            "required": ["src_subdir", "in_dragonos_path"],
+                            "properties": {
+                                "src_subdir": { "type": "string" },
+                                "in_dragonos_path": { "type": "string" }
+                            }
+                        }
+                    },
+                    "mode": { "type": ["string", "null"], "pattern": "^(0o)?[0-7]{1,4}$" },
+                    "follow_symlinks": { "type": "boolean" },
+                    "include": { "type": "array", "items": { "type": "string" } },
+                    "exclude": { "type": "array", "items": { "type": "string" } },
+                    "strip": { "type": ["boolean", "string"], "enum": [true, false, "debug", "all"] },
+                    "strict_strip": { "type": "boolean" },
+                    "files": {
+                        "type": "array",
+                        "items": {
+                            "type": "object",
+                            "required": ["src", "dest"],
+                            "properties": {
+                                "src": { "type": "string" },
+                                "dest": { "type": "string" },
+                                "mode": { "type": ["string", "null"], "pattern": "^(0o)?[0-7]{1,4}$" },
+                                "uid": { "type": ["integer", "null"], "minimum": 0 },
+                                "gid": { "type": ["integer", "null"], "minimum": 0 }
+                            }
+                        }
+                    },
+                    "symlinks": {
+                        "type": "array",
+                        "items": {
+                            "type": "object",
+                            "required": ["link", "target"],
+                            "properties": {
+                                "link": { "type": "string" },
+                                "target": { "type": "string" },
+                                "force": { "type": "boolean" }
+                            }
+                        }
+                    },
+                    "post_install": {
+                        "type": ["string", "array", "null"],
+                        "items": { "type": "string" }
+                    },
+                    "docs": { "type": "string", "enum": ["keep", "compress", "strip"] },
+                    "locales": { "type": "array", "items": { "type": "string" } },
+                    "allow_overwrite": {
+                        "type": "array",
+                        "items": { "type": "string", "pattern": "^/" }
+                    },
+                    "modes": {
+                        "type": "object",
+                        "additionalProperties": { "type": "string", "pattern": "^(0o)?[0-7]{1,4}$" }
+                    },
+                    "target_arch": arch_overrides(json!({
+                        "type": "object",
+                        "properties": { "in_dragonos_path": { "type": ["string", "null"] } }
+                    }))
+                }
+            },
+            "clean": {
+                "type": "object",
+                "description": "清理配置",
+                "properties": {
+                    "clean_command": {
+                        "type": ["string", "null"],
+                        "description": "output级别的清理命令"
+                    },
+                    "timeout": {
+                        "type": ["integer", "null"],
+                        "minimum": 1,
+                        "description": "每条清理命令的超时时间（秒）"
+                    },
+                    "on_failure": {
+                        "type": "string",
+                        "enum": ["abort", "continue", "ignore"],
+                        "description": "清理命令失败或者超时时的处理方式"
+                    },
+                    "levels": {
+                        "type": "object",
+                        "description": "各清理级别（output、src、cache、all）的命令和行为",
+                        "properties": {
+                            "output": { "$ref": "#/definitions/CleanLevelConfig" },
+                            "src": { "$ref": "#/definitions/CleanLevelConfig" },
+                            "cache": { "$ref": "#/definitions/CleanLevelConfig" },
+                            "all": { "$ref": "#/definitions/CleanLevelConfig" }
+                        },
+                        "additionalProperties": false
+                    }
+                }
+            },
+            "envs": {
+                "type": ["array", "null"],
+                "description": "环境变量",
+                "items": { "$ref": "#/definitions/TaskEnv" }
+            },
+            "build_once": { "type": "boolean", "description": "是否只构建一次" },
+            "install_once": { "type": "boolean", "description": "是否只安装一次" },
+            "rerun_if_changed": {
+                "type": "array",
+                "description": "源码目录以外的构建输入，改变时重新构建",
+                "items": { "type": "string" }
+            },
+            "enabled_if": {
+                "type": ["string", "null"],
+                "description": "启用条件，例如 arch == \"x86_64\" && env.FEATURE_X == \"1\""
+            },
+            "tags": {
+                "type": "array",
+                "description": "任务的标签，用于在命令行中按标签选择任务",
+                "items": { "type": "string" }
+            },
+            "unset": {
+                "type": "array",
+                "description": "不继承的全局环境变量（工作区配置中的global_envs）的名称",
+                "items": { "type": "string" }
+            },
+            "env_files": {
+                "type": "array",
+                "description": "执行时加载的环境变量文件（dotenv格式），相对于配置文件所在的目录",
+                "items": {
+                    "oneOf": [
+                        { "type": "string" },
+                        {
+                            "type": "object",
+                            "required": ["path"],
+                            "properties": {
+                                "path": { "type": "string" },
+                                "optional": { "type": "boolean" },
+                                "secret": {
+                                    "type": "boolean",
+                                    "description": "文件中的环境变量的值是否保密，输出时替换为***"
+                                }
+                            },
+                            "additionalProperties": false
+                        }
+                    ]
+                }
+            },
+            "target_arch": {
+                "type": "array",
+                "description": "支持的目标架构",
+                "items": { "$ref": "#/definitions/TargetArch" }
+            },
+            "arch_envs": arch_overrides(json!({
+                "type": "array",
+                "items": { "$ref": "#/definitions/TaskEnv" }
+            })),
+            "profile": {
+                "type": "object",
+                "description": "构建配置档的覆盖配置",
+                "additionalProperties": {
+                    "type": "object",
+                    "properties": {
+                        "build_command": {
+                            "type": ["string", "array", "null"],
+                            "items": { "type": "string" }
+                        },
+                        "envs": {
+                            "type": ["array", "null"],
+                            "items": { "$ref": "#/definitions/TaskEnv" }
+                        },
+                        "rust_target": { "type": ["string", "null"] }
+                    }
+                }
+            }
+        },
+        "definitions": definitions()
+    });
+}
+
+/// 构建配置（`BuildConfig`）的Schema
+fn build_config() -> Value {
+    return json!({
+        "type": "object",
+        "description": "构建配置",
+        "properties": {
+            "build_command": {
+                "type": ["string", "array", "null"],
+                "items": { "type": "string" }
+            },
+            "build_dir": { "type": ["string", "null"] },
+            "work_dir": { "type": ["string", "null"] },
+            "create": { "type": "boolean" },
+            "pre_build": {
+                "type": ["string", "array", "null"],
+                "items": { "type": "string" }
+            },
+            "pre_build_always": { "type": "boolean" },
+            "post_build": {
+                "type": ["string", "array", "null"],
+                "items": { "type": "string" }
+            },
+            "inject_jobs": { "type": "boolean" },
+            "max_warnings": { "type": ["integer", "null"], "minimum": 0 },
+            "sandbox": {
+                "type": ["object", "null"],
+                "properties": { "network": { "type": ["boolean", "null"] } }
+            },
+            "max_memory": { "type": ["string", "null"] },
+            "cpu_time_limit": { "type": ["integer", "null"], "minimum": 1 },
+            "nice": { "type": ["integer", "null"], "minimum": -20, "maximum": 19 },
+            "retries": { "type": ["integer", "null"], "minimum": 0 },
+            "cargo": {
+                "type": ["object", "null"],
+                "properties": {
+                    "features": { "type": "array", "items": { "type": "string" } },
+                    "no_default_features": { "type": "boolean" },
+                    "bins": { "type": "array", "items": { "type": "string" } },
+                    "profile": { "type": ["string", "null"] },
+                    "workspace_members": {
+                        "type": "array",
+                        "items": { "type": "string" }
+                    }
+                }
+            },
+            "cmake": {
+                "type": ["object", "null"],
+                "properties": {
+                    "defines": {
+                        "type": "object",
+                        "additionalProperties": { "type": "string" }
+                    },
+                    "generator": { "type": ["string", "null"] },
+                    "build_type": { "type": ["string", "null"] },
+                    "install_prefix": { "type": ["string", "null"] }
+                }
+            },
+            "autotools": {
+                "type": ["object", "null"],
+                "properties": {
+                    "configure_args": { "type": "array", "items": { "type": "string" } },
+                    "make_targets": { "type": "array", "items": { "type": "string" } },
+                    "install_target": { "type": ["string", "null"] }
+                }
+            },
+            "target_arch": arch_overrides(json!({
+                "type": "object",
+                "properties": {
+                    "build_command": {
+                        "type": ["string", "array", "null"],
+                        "items": { "type": "string" }
+                    },
+                    "rust_target": { "type": "string", "description": "该架构下使用的rust target" }
+                }
+            }))
+        }
+    });
+}
+
+fn definitions() -> Value {
+    return json!({
+        "TaskType": {
+            "description": "任务类型",
+            "oneOf": [
+                { "enum": ["NoSource", "script", "Meta", "meta"] },
+                tagged("BuildFromSource", json!({ "$ref": "#/definitions/CodeSource" })),
+                tagged("InstallFromPrebuilt", json!({ "$ref": "#/definitions/PrebuiltSource" })),
+                tagged("KernelModule", json!({ "$ref": "#/definitions/CodeSource" })),
+                tagged("kernel_module", json!({ "$ref": "#/definitions/CodeSource" }))
+            ]
+        },
+        "CodeSource": {
+            "description": "代码源",
+            "oneOf": [
+                tagged("Git", json!({ "$ref": "#/definitions/GitSource" })),
+                tagged("Hg", json!({ "$ref": "#/definitions/HgSource" })),
+                tagged("Svn", json!({ "$ref": "#/definitions/SvnSource" })),
+                tagged("Local", json!({ "$ref": "#/definitions/LocalSource" })),
+                tagged("Archive", json!({ "$ref": "#/definitions/ArchiveSource" })),
+                tagged("ReleaseAsset", json!({ "$ref": "#/definitions/ReleaseAssetSource" })),
+                tagged("Overlay", json!({
+                    "type": "array",
+                    "minItems": 1,
+                    "items": { "$ref": "#/definitions/CodeSource" }
+                }))
+            ]
+        },
+        "PrebuiltSource": {
+            "description": "预编译包源",
+            "oneOf": [
+                tagged("Git", json!({ "$ref": "#/definitions/GitSource" })),
+                tagged("Archive", json!({ "$ref": "#/definitions/ArchiveSource" })),
+                tagged("Local", json!({ "$ref": "#/definitions/LocalSource" })),
+                tagged("Oci", json!({ "$ref": "#/definitions/OciSource" })),
+                tagged("ReleaseAsset", json!({ "$ref": "#/definitions/ReleaseAssetSource" }))
+            ]
+        },
+        "GitSource": {
+            "type": "object",
+            "required": ["url"],
+            "properties": {
+                "url": { "type": "string" },
+                "branch": { "type": ["string", "null"] },
+                "revision": { "type": ["string", "null"] },
+                "ssh_key": { "type": ["string", "null"] },
+                "on_dirty": { "type": "string", "enum": ["reset", "error"] },
+                "depth": { "type": ["integer", "null"], "minimum": 1 },
+                "shallow_since": { "type": ["string", "null"] }
+            }
+        },
+        "HgSource": {
+            "type": "object",
+            "required": ["url"],
+            "properties": {
+                "url": { "type": "string" },
+                "branch": { "type": ["string", "null"] },
+                "revision": { "type": ["string", "null"] }
+            }
+        },
+        "SvnSource": {
+            "type": "object",
+            "required": ["url"],
+            "properties": {
+                "url": { "type": "string" },
+                "revision": { "type": ["string", "null"], "pattern": "^(HEAD|[0-9]+)$" }
+            }
+        },
+        "LocalSource": {
+            "type": "object",
+            "required": ["path"],
+            "properties": { "path": { "type": "string" } }
+        },
+        "ArchiveSource": {
+            "type": "object",
+            "required": ["url"],
+            "properties": {
+                "url": { "type": "string" },
+                "subdir": { "type": ["string", "null"] },
+                "checksum": {
+                    "type": ["string", "null"],
+                    "description": "压缩包的sha256值，下载后校验"
+                },
+                "checksum_url": {
+                    "type": ["string", "null"],
+                    "description": "校验和文件（<hash>  <文件名>或者只有sha256值）的URL，不能与checksum同时使用"
+                }
+            }
+        },
+        "OciSource": {
+            "type": "object",
+            "required": ["image"],
+            "properties": {
+                "image": { "type": "string" },
+                "reference": { "type": "string" },
+                "paths": { "type": ["array", "null"], "items": { "type": "string" } }
+            }
+        },
+        "ReleaseAssetSource": {
+            "type": "object",
+            "required": ["repo", "tag", "asset_pattern"],
+            "properties": {
+                "repo": { "type": "string" },
+                "tag": { "type": "string" },
+                "asset_pattern": { "type": "string" }
+            }
+        },
+        "Dependency": {
+            "type": "object",
+            "required": ["name", "version"],
+            "properties": {
+                "name": { "type": "string" },
+                "version": { "type": "string", "description": "版本要求，系统依赖写作*时不检查版本" },
+                "arch": { "$ref": "#/definitions/TargetArch" },
+                "kind": {
+                    "type": "string",
+                    "enum": ["dadk", "system"],
+                    "description": "依赖的类型：dadk为DADK任务（默认），system为主机上的系统软件包，只检查是否存在"
+                },
+                "version_command": {
+                    "type": "string",
+                    "description": "输出系统依赖的版本的命令，系统依赖指定了版本要求时必须指定"
+                },
+                "env_alias": {
+                    "type": "string",
+                    "description": "导出依赖的构建结果目录时使用的环境变量名，代替DADK_DEP_依赖名"
+                }
+            }
+        },
+        "CleanLevelConfig": {
+            "type": "object",
+            "properties": {
+                "command": { "type": "string", "description": "在源码目录中执行的清理命令" },
+                "remove_dirs": {
+                    "type": "boolean",
+                    "description": "是否由DADK删除属于该级别的目录，默认在没有指定command时删除"
+                }
+            }
+        },
+        "TaskEnv": {
+            "type": "object",
+            "required": ["key", "value"],
+            "properties": {
+                "key": {
+                    "type": "string",
+                    "description": "环境变量名，以DADK_开头的名称需要设置allow_reserved",
+                    "pattern": "^\\s*[A-Za-z_][A-Za-z0-9_]*\\s*$"
+                },
+                "value": { "type": "string" },
+                "target_arch": {
+                    "$ref": "#/definitions/TargetArch",
+                    "description": "只在按该目标架构构建时设置，后面的同名环境变量覆盖前面的"
+                },
+                "secret": {
+                    "type": "boolean",
+                    "description": "值是否保密，DADK输出时替换为***，但仍然传给执行的命令"
+                },
+                "hash_secret": {
+                    "type": "boolean",
+                    "description": "保密的值是否参与判断全局环境变量是否改变，需要同时设置secret"
+                },
+                "allow_reserved": {
+                    "type": "boolean",
+                    "description": "允许使用以DADK_开头的名称，覆盖DADK设置的环境变量"
+                },
+                "op": {
+                    "type": "string",
+                    "enum": ["set", "prepend", "append"],
+                    "description": "设置方式：覆盖（默认）、加在继承的值之前或之后"
+                },
+                "separator": {
+                    "type": "string",
+                    "description": "prepend/append时的分隔符，默认为:"
+                },
+                "phases": {
+                    "type": "array",
+                    "items": { "type": "string", "enum": ["build", "install", "clean"] },
+                    "description": "只在列出的执行阶段设置，默认为所有阶段"
+                }
+            }
+        },
+        "TargetArch": {
+            "description": "目标架构（不区分大小写，允许首尾空白）",
+            "type": "string",
+            "pattern": target_arch_pattern()
+        }
+    });
+}
+
+/// 生成匹配目标架构的正则表达式
+///
+/// 目标架构在解析时会去除首尾空白，并且不区分大小写，因此把每个字母展开为大小写两种形式
+fn target_arch_pattern() -> String {
+    let arches: Vec<String> = TargetArch::EXPECTED
+        .iter()
+        .map(|arch| {
+            arch.chars()
+                .map(|c| {
+                    if c.is_ascii_alphabetic() {
+                        format!("[{}{}]", c.to_ascii_lowercase(), c.to_ascii_uppercase())
+                    } else {
+                        c.to_string()
+                    }
+                })
+                .collect()
+        })
+        .collect();
+    return format!("^\\s*({})\\s*$", arches.join("|"));
+}
+
+/// 生成按目标架构覆盖的配置：键为目标架构，值为`content`
+fn arch_overrides(content: Value) -> Value {
+    return json!({
+        "type": "object",
+        "propertyNames": { "pattern": target_arch_pattern() },
+        "additionalProperties": content
+    });
+}
+
+/// 生成serde外部标记的枚举变体：只有一个名为`tag`的字段的对象
+fn tagged(tag: &str, content: Value) -> Value {
+    return json!({
+        "type": "object",
+        "required": [tag],
+        "additionalProperties": false,
+        "properties": { tag: content }
+    });
+}
@@ -0,0 +1,352 @@
+use std::path::PathBuf;
+
+use regex::Regex;
+use serde_json::Value;
+
+use crate::{
+    executor::source::{
+        ArchiveSource, GitSource, HgSource, LocalSource, OciSource, ReleaseAssetSource, SvnSource,
+    },
+    parser::task::{
+        BuildConfig, CleanConfig, CodeSource, DADKTask, Dependency, InstallConfig, PrebuiltSource,
+        TargetArch, TaskType,
+    },
+};
+
+use super::{dot::dependency_graph_dot, sbom::cyclonedx_sbom, schema::task_config_schema};
+
+fn create_task(
+    name: &str,
+    version: &str,
+    task_type: TaskType,
+    depends: Vec<Dependency>,
+) -> DADKTask {
+    DADKTask::new(
+        name.to_string(),
+        version.to_string(),
+        format!("test task {name}"),
+        None,
+        task_type,
+        depends,
+        BuildConfig::new(Some("true".to_string())),
+        InstallConfig::new(None),
+        CleanConfig::new(None),
+        None,
+        false,
+        false,
+        None,
+    )
+}
+
+fn dep(name: &str, version: &str) -> Dependency {
+    Dependency::new(name.to_string(), version.to_string())
+}
+
+#[test]
+fn dot_contains_dependency_edges() {
+    let src = TaskType::BuildFromSource(CodeSource::Local(LocalSource::new(PathBuf::from(
+        "tests/data/apps/app_normal",
+    ))));
+    let prebuilt = TaskType::InstallFromPrebuilt(PrebuiltSource::Archive(ArchiveSource::new(
+        "https://example.com/libc.tar.gz".to_string(),
+    )));
+    let tasks = vec![
+        create_task("app", "0.1.0", src.clone(), vec![dep("libc", "1.0")]),
+        create_task("libc", "1.0", prebuilt, vec![dep("missing", "2.0")]),
+    ];
+
+    let dot = dependency_graph_dot(&tasks, true);
+
+    assert!(dot.starts_with("digraph dadk {"), "{dot}");
+    assert!(dot.contains("\"app_0_1_0\" -> \"libc_1_0\";"), "{dot}");
+    assert!(dot.contains("\"libc_1_0\" -> \"missing-2.0\";"), "{dot}");
+    assert!(
+        dot.contains("\"missing-2.0\" [label=\"missing-2.0 (unresolved)\", style=dashed"),
+        "{dot}"
+    );
+    assert!(dot.contains("fillcolor=\"lightblue\""), "{dot}");
+    assert!(dot.contains("fillcolor=\"lightyellow\""), "{dot}");
+    assert_eq!(dot.matches("->").count(), 2, "{dot}");
+}
+
+#[test]
+fn dot_meta_package_has_distinct_style() {
+    let tasks = vec![create_task("base", "1.0", TaskType::Meta, vec![])];
+
+    let dot = dependency_graph_dot(&tasks, false);
+
+    assert!(
+        dot.contains("\"base_1_0\" [label=\"base_1_0\", shape=folder];"),
+        "{dot}"
+    );
+}
+
+/// 一个只支持`task_config_schema()`中用到的关键字的JSON Schema校验器
+fn schema_validate(root: &Value, schema: &Value, value: &Value) -> Result<(), String> {
+    if let Some(r) = schema.get("$ref").and_then(|r| r.as_str()) {
+        let name = r.trim_start_matches("#/definitions/");
+        return schema_validate(root, &root["definitions"][name], value);
+    }
+
+    if let Some(ty) = schema.get("type") {
+        let types: Vec<&str> = match ty {
+            Value::String(s) => vec![s.as_str()],
+            Value::Array(a) => a.iter().filter_map(|t| t.as_str()).collect(),
+            _ => vec![],
+        };
+        let matched = types.iter().any(|t| match *t {
+            "object" => value.is_object(),
+            "array" => value.is_array(),
+            "string" => value.is_string(),
+            "boolean" => value.is_boolean(),
+            "integer" => value.is_u64() || value.is_i64(),
+            "null" => value.is_null(),
+            _ => false,
+        });
+        if !matched {
+            return Err(format!("{} is not of type {}", value, ty));
+        }
+    }
+
+    if let Some(e) = schema.get("enum").and_then(|e| e.as_array()) {
+        if !e.contains(value) {
+            return Err(format!("{} is not one of {:?}", value, e));
+        }
+    }
+
+    if let (Some(pattern), Some(s)) = (
+        schema.get("pattern").and_then(|p| p.as_str()),
+        value.as_str(),
+    ) {
+        if !Regex::new(pattern).unwrap().is_match(s) {
+            return Err(format!("{} does not match {}", s, pattern));
+        }
+    }
+
+    if let Some(one_of) = schema.get("oneOf").and_then(|o| o.as_array()) {
+        let count = one_of
+            .iter()
+            .filter(|s| schema_validate(root, s, value).is_ok())
+            .count();
+        if count != 1 {
+            return Err(format!("{} matches {} schemas in oneOf", value, count));
+        }
+    }
+
+    if let Some(obj) = value.as_object() {
+        if let Some(required) = schema.get("required").and_then(|r| r.as_array()) {
+            for key in required.iter().filter_map(|k| k.as_str()) {
+                if !obj.contains_key(key) {
+                    return Err(format!("missing required property {}", key));
+                }
+            }
+        }
+        let properties = schema.get("properties").and_then(|p| p.as_object());
+        for (key, v) in obj.iter() {
+            match properties.and_then(|p| p.get(key)) {
+                Some(s) => schema_validate(root, s, v).map_err(|e| format!("{}: {}", key, e))?,
+                None => {
+                    if schema.get("additionalProperties") == Some(&Value::Bool(false)) {
+                        return Err(format!("unknown property {}", key));
+                    }
+                }
+            }
+        }
+    }
+
+    if let (Some(items), Some(arr)) = (schema.get("items"), value.as_array()) {
+        for v in arr.iter() {
+            schema_validate(root, items, v)?;
+        }
+    }
+
+    return Ok(());
+}
+
+#[test]
+fn schema_accepts_known_good_config() {
+    let schema = task_config_schema();
+    let config: Value = serde_json::from_str(
+        &std::fs::read_to_string("tests/data/dadk_config_v1/app_normal_with_env_0_1_0.dadk")
+            .unwrap(),
+    )
+    .unwrap();
+
+    let r = schema_validate(&schema, &schema, &config);
+    assert!(r.is_ok(), "{:?}", r);
+
+    // 目标架构不区分大小写
+    let config: Value = serde_json::from_str(
+        &std::fs::read_to_string(
+            "tests/data/dadk_config_v1/app_target_arch_x86_64_uppercase_0_1_0.dadk",
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    let r = schema_validate(&schema, &schema, &config);
+    assert!(r.is_ok(), "{:?}", r);
+}
+
+#[test]
+fn schema_rejects_unknown_task_type() {
+    let schema = task_config_schema();
+    let mut config: Value = serde_json::from_str(
+        &std::fs::read_to_string("tests/data/dadk_config_v1/app_normal_0_1_0.dadk").unwrap(),
+    )
+    .unwrap();
+    config["task_type"] = serde_json::json!({ "BuildFromNowhere": { "path": "." } });
+
+    let r = schema_validate(&schema, &schema, &config);
+    assert!(r.is_err(), "unknown task type should be rejected");
+}
+
+/// 每一种任务类型和源类型序列化后都应当符合Schema，保证Schema与Rust类型同步
+#[test]
+fn schema_matches_all_task_types() {
+    let schema = task_config_schema();
+    let task_types = vec![
+        TaskType::NoSource,
+        TaskType::Meta,
+        TaskType::KernelModule(CodeSource::Local(LocalSource::new(PathBuf::from("kmod")))),
+        TaskType::BuildFromSource(CodeSource::Overlay(vec![
+            CodeSource::Archive(ArchiveSource::new(
+                "https://example.com/app.tar.gz".to_string(),
+            )),
+            CodeSource::Local(LocalSource::new(PathBuf::from("patches"))),
+        ])),
+        TaskType::BuildFromSource(CodeSource::Git(GitSource::new(
+            "https://example.com/app.git".to_string(),
+            Some("master".to_string()),
+            None,
+        ))),
+        TaskType::BuildFromSource(CodeSource::Hg(HgSource::new(
+            "https://hg.example.org/app".to_string(),
+            None,
+            Some("1.0".to_string()),
+        ))),
+        TaskType::BuildFromSource(CodeSource::Svn(SvnSource::new(
+            "svn://svn.example.org/repo/trunk".to_string(),
+            Some("1024".to_string()),
+        ))),
+        TaskType::BuildFromSource(CodeSource::Local(LocalSource::new(PathBuf::from("app")))),
+        TaskType::BuildFromSource(CodeSource::Archive(ArchiveSource::new(
+            "https://example.com/app.tar.gz".to_string(),
+        ))),
+        TaskType::BuildFromSource(CodeSource::ReleaseAsset(ReleaseAssetSource::new(
+            "owner/app".to_string(),
+            "latest".to_string(),
+            "*.tar.gz".to_string(),
+        ))),
+        TaskType::InstallFromPrebuilt(PrebuiltSource::Git(GitSource::new(
+            "https://example.com/fonts.git".to_string(),
+            None,
+            Some("abcdef".to_string()),
+        ))),
+        TaskType::InstallFromPrebuilt(PrebuiltSource::Archive(ArchiveSource::new(
+            "https://example.com/app.tar.gz".to_string(),
+        ))),
+        TaskType::InstallFromPrebuilt(PrebuiltSource::Local(LocalSource::new(PathBuf::from(
+            "app",
+        )))),
+        TaskType::InstallFromPrebuilt(PrebuiltSource::Oci(OciSource::new(
+            "alpine".to_string(),
+            "3.19".to_string(),
+            Some(vec![PathBuf::from("/bin/busybox")]),
+        ))),
+        TaskType::InstallFromPrebuilt(PrebuiltSource::ReleaseAsset(ReleaseAssetSource::new(
+            "owner/app".to_string(),
+            "v1.0".to_string(),
+            "re:^app-.*$".to_string(),
+        ))),
+    ];
+
+    for task_type in task_types {
+        let mut task = create_task("app", "0.1.0", task_type, vec![dep("libc", "1.0")]);
+        task.target_arch = vec![TargetArch::X86_64, TargetArch::RiscV64];
+        let value = serde_json::to_value(&task).unwrap();
+        let r = schema_validate(&schema, &schema, &value);
+        assert!(r.is_ok(), "{:?}: {}", r, value);
+    }
+}
+
+#[test]
+fn sbom_has_component_per_task_and_dependency_edges() {
+    let git = TaskType::BuildFromSource(CodeSource::Git(GitSource::new(
+        "https://example.com/app.git".to_string(),
+        Some("main".to_string()),
+        None,
+    )));
+    let archive = TaskType::InstallFromPrebuilt(PrebuiltSource::Archive(ArchiveSource::new(
+        "https://example.com/libc.tar.gz".to_string(),
+    )));
+    let tasks = vec![
+        create_task(
+            "app",
+            "0.1.0",
+            git,
+            vec![
+                dep("libc", "1.0"),
+                dep("base", "1.0"),
+                dep("missing", "2.0"),
+            ],
+        ),
+        create_task("libc", "1.0", archive, vec![dep("base", "1.0")]),
+        create_task("base", "1.0", TaskType::Meta, vec![]),
+    ];
+
+    let lock_path = std::env::temp_dir().join(format!("dadk-sbom-{}.lock", std::process::id()));
+    std::fs::write(
+        &lock_path,
+        r#"
+[tasks.app_0_1_0]
+type = "git"
+url = "https://example.com/app.git"
+commit = "0123456789abcdef"
+
+[tasks.libc_1_0]
+type = "archive"
+url = "https://example.com/libc.tar.gz"
+sha256 = "deadbeef"
+"#,
+    )
+    .unwrap();
+
+    let sbom = cyclonedx_sbom(&tasks, Some(&lock_path)).unwrap();
+    std::fs::remove_file(&lock_path).unwrap();
+
+    assert_eq!(sbom["bomFormat"], "CycloneDX");
+    let components = sbom["components"].as_array().unwrap();
+    assert_eq!(components.len(), tasks.len());
+    for task in tasks.iter() {
+        let n = components
+            .iter()
+            .filter(|c| c["bom-ref"] == task.name_version().as_str())
+            .count();
+        assert_eq!(n, 1, "{}", task.name_version());
+    }
+
+    let app = &components[0];
+    assert_eq!(app["externalReferences"][0]["type"], "vcs");
+    assert_eq!(
+        app["externalReferences"][0]["url"],
+        "https://example.com/app.git"
+    );
+    assert_eq!(app["properties"][0]["name"], "dadk:git:commit");
+    assert_eq!(app["properties"][0]["value"], "0123456789abcdef");
+    let libc = &components[1];
+    assert_eq!(
+        libc["externalReferences"][0]["url"],
+        "https://example.com/libc.tar.gz"
+    );
+    assert_eq!(libc["hashes"][0]["content"], "deadbeef");
+
+    let depends_on = |r: &str| -> Vec<String> {
+        let deps = sbom["dependencies"].as_array().unwrap();
+        let d = deps.iter().find(|d| d["ref"] == r).unwrap();
+        return serde_json::from_value(d["dependsOn"].clone()).unwrap();
+    };
+    // 找不到的依赖不会出现在依赖关系中
+    assert_eq!(depends_on("app_0_1_0"), vec!["base_1_0", "libc_1_0"]);
+    assert_eq!(depends_on("libc_1_0"), vec!["base_1_0"]);
+    assert!(depends_on("base_1_0").is_empty());
+}
@@ -59,7 +59,48 @@
 //! 同时，您也要在构建您的app时，把构建结果放到您的软件库的构建结果缓存目录（通过对应的环境变量获得）中。
 //! - `DADK_SOURCE_CACHE_DIR_任务名_任务版本`：DADK的某个任务的源码目录。当您要引用其他软件库的源码目录时，可以通过该环境变量来获得。
 //!
-//! 同时，DADK会为每个任务设置其自身在配置文件中指定的环境变量。
+//! DADK会为每个任务设置以下描述任务自身的环境变量：
+//!
+//! - `DADK_TARGET_ARCH`：当前目标架构，例如`x86_64`
+//! - `DADK_INSTALL_PREFIX`：任务安装到DragonOS内的目录（`install.in_dragonos_path`），没有设置时不存在
+//! - `DADK_PKG_NAME`、`DADK_PKG_VERSION`：任务名、任务版本
+//! - `DADK_PKG_ID`：任务在全局环境变量名中使用的标识，例如`LIBC_0_1_0`
+//! - `DADK_PHASE`：当前的执行阶段，构建（包括`pre_build`钩子）时为`build`，安装（包括`post_install`钩子）时为`install`，
+//!   清理时为`clean`
+//! - `DADK_SYSROOT`：DragonOS sysroot在主机上的路径，只在安装阶段设置，`install.post_install`钩子可以通过它修改安装的文件
+//! - `DADK_DEP_依赖名`：任务的每个依赖的构建结果目录，依赖名转为大写、字母和数字以外的字符替换为`_`，
+//!   例如依赖`lib-foo`为`DADK_DEP_LIB_FOO`。前缀和命名方式可以在工作区配置的`[dep_envs]`中修改，
+//!   依赖可以通过`env_alias`指定固定的名称（例如`LIBFOO_DIR`）。一个任务的两个依赖得到同一个名称时任务报错
+//!
+//! 同时，DADK会为每个任务设置其自身在配置文件中指定的环境变量，以及工作区配置（配置文件目录下的`dadk-config.toml`）
+//! 中的`global_envs`。任务可以在`unset`中列出不需要继承的`global_envs`。
+//! 任务和工作区还可以通过`env_files`在执行时从dotenv格式的文件中加载环境变量，用于不适合提交的凭据和与机器相关的设置。
+//! `envs`中的环境变量可以指定`target_arch`，只在按该架构构建时设置，后面的同名环境变量覆盖前面的。
+//! 环境变量名只能包含字母、数字和`_`，并且不能以数字开头；同一个任务中同名、`target_arch`也相同的环境变量只能定义一次。
+//! 环境变量可以设置`"op": "prepend"`或`"op": "append"`（默认为`set`），把值以`separator`（默认为`:`）
+//! 连接到继承的同名环境变量之前或之后，例如在`PATH`中加入工具链的目录；同一个环境变量的多个`prepend`/`append`按声明的顺序组合，
+//! `set`仍然只能定义一次。组合出的值由`dadk info`输出，也会写在任务日志的开头。
+//! 以`DADK_`开头的名称由DADK保留，任务需要覆盖DADK设置的环境变量（例如`DADK_PKG_NAME`）时，设置`"allow_reserved": true`。
+//! 以上环境变量在构建、安装、清理阶段（包括钩子和`clean_command`）都同样设置。个别需要在不同阶段取不同值的环境变量
+//! 可以指定`"phases": ["build"]`（可选`build`、`install`、`clean`，默认为所有阶段），只在列出的阶段设置；
+//! 同名的`set`环境变量只要阶段不重叠就可以分别定义。
+//! `dadk info <任务> --arch <架构>`会输出任务按该架构执行时设置的环境变量以及它们的来源。
+//! `envs`、`global_envs`中的环境变量和`env_files`中的文件可以设置`"secret": true`（例如签名用的令牌），
+//! 它们的值照常传给执行的命令，但DADK在日志、`dadk info`的输出、错误信息、`--report`和`--events`中把值替换为`***`，
+//! 全局环境变量改变时也不按保密的值判断是否重新构建，除非同时设置了`hash_secret`。
+//!
+//! 同名的环境变量按以下优先级（从高到低）生效：
+//!
+//! 1. DADK设置的构建目录：`DADK_CURRENT_BUILD_DIR`、`DADK_BUILD_DIR`
+//! 2. 任务在配置文件中指定的环境变量（`envs`）
+//! 3. 任务的`env_files`
+//! 4. 工作区配置中的`global_envs`
+//! 5. 工作区配置中的`env_files`
+//! 6. 上面列出的描述任务自身的环境变量
+//! 7. 全局环境变量
+//!
+//! 没有指定`--target-arch`时，目标架构取自环境变量`ARCH`，任务配置中没有`target_arch`时也是如此。
+//! `ARCH`没有设置时默认为`x86_64`；设置了`DADK_REQUIRE_ARCH`（例如在CI中）时报错，以免遗漏架构配置。
 //!
 //! #### 全局环境变量命名格式
 //!
@@ -76,6 +117,22 @@
 //!
 //! **举例**：对于任务`libc-0.1.0`，其构建结果的全局环境变量名为`DADK_BUILD_CACHE_DIR_LIBC_0_1_0`。
 //!
+//! ### 配置中的变量
+//!
+//! 任务配置中的`build_command`、`clean_command`、环境变量的值以及安装路径（`install.in_dragonos_path`、`install.destinations`的`in_dragonos_path`、`install.files`的`dest`、`install.symlinks`的`link`和`target`）
+//! 可以引用以下变量，DADK在执行任务时替换它们：
+//!
+//! - `${ARCH}`：当前目标架构，例如`x86_64`
+//! - `${NAME}`、`${VERSION}`：任务名、任务版本
+//! - `${NAME_VERSION}`：任务名和版本，特殊字符按上表替换，例如`libc_0_1_0`
+//! - `${DADK_CACHE_ROOT}`：DADK的缓存根目录
+//! - `${DADK_BUILD_DIR}`：执行构建命令的工作目录（源码目录下的`build.build_dir`）
+//! - `${DADK_CURRENT_BUILD_DIR}`：任务的构建结果目录
+//! - `${PROFILE}`：当前生效的构建配置档，没有时为空
+//! - `${env:FOO}`：主机的环境变量`FOO`，没有设置时任务失败
+//!
+//! `$${`表示字面量`${`（例如shell中的`$${HOME}`），引用未知的变量会导致解析配置文件失败。
+//!
 //!
 //! ## TODO
 //!
@@ -105,14 +162,16 @@ use parser::task::DADKTask;
 use simple_logger::SimpleLogger;
 
 use crate::{
-    console::{interactive::InteractiveConsole, CommandLineArgs},
+    console::{info::InfoArg, interactive::InteractiveConsole, CommandLineArgs},
     context::DadkExecuteContextBuilder,
+    executor::{output_log::OutputLogConfig, plan::TaskPlan},
     scheduler::Scheduler,
 };
 
 mod console;
 mod context;
 mod executor;
+pub mod export;
 pub mod parser;
 mod scheduler;
 pub mod static_resources;
@@ -126,12 +185,52 @@ pub fn dadk_main() {
 
     info!("DADK run with args: {:?}", &args);
 
+    // `dadk info --arch`指定了查看的目标架构时，与`--target-arch`相同
+    let target_arch = match &args.action {
+        console::Action::Info(InfoArg {
+            arch: Some(arch), ..
+        }) => Some(*arch),
+        _ => args.target_arch,
+    };
+    // 没有指定`--target-arch`时使用`ARCH`，严格模式（`DADK_REQUIRE_ARCH`）下没有设置`ARCH`是错误
+    let target_arch = match target_arch.map_or_else(DADKTask::try_default_target_arch, Ok) {
+        Ok(arch) => arch,
+        Err(e) => {
+            error!("{}, use --target-arch or set ARCH", e);
+            exit(1);
+        }
+    };
     let context = DadkExecuteContextBuilder::default()
         .sysroot_dir(args.dragonos_dir)
         .config_dir(args.config_dir)
         .action(args.action)
         .thread_num(args.thread)
+        .target_arch(target_arch)
+        .all_arches(args.all_arches)
+        .fail_fast(!args.no_fail_fast)
         .cache_dir(args.cache_dir)
+        .lock(args.lock)
+        .update_lock(args.update_lock)
+        .kernel_src(args.kernel_src)
+        .kernel_build(args.kernel_build)
+        .kernel_arch(args.kernel_arch)
+        .jobs(args.jobs)
+        .profile(args.profile)
+        .dep_resolve(args.dep_resolve.unwrap_or_default())
+        .output_log(OutputLogConfig {
+            timestamped: args.log_timestamp,
+            tail_lines: args.log_tail.unwrap_or(OutputLogConfig::DEFAULT_TAIL_LINES),
+        })
+        .diagnostic_patterns(args.diagnostic_patterns)
+        .why_dirty(args.why_dirty)
+        .events(args.events)
+        .sandbox_network(args.sandbox_network)
+        .parallel_extract(args.parallel_extract)
+        .download_jobs(args.download_jobs)
+        .force_install(args.force_install)
+        .docs(args.docs.unwrap_or_default())
+        .keep_locales(args.keep_locales)
+        .deny_install_paths(args.deny_install_path)
         .build()
         .expect("Failed to build execute context");
     let context = Arc::new(context);
@@ -161,7 +260,7 @@ pub fn dadk_main() {
             let r = InteractiveConsole::new(
                 context.sysroot_dir().cloned(),
                 context.config_dir().cloned(),
-                *context.action(),
+                context.action().clone(),
             )
             .run();
             if r.is_err() {
@@ -170,10 +269,33 @@ pub fn dadk_main() {
             }
             exit(0);
         }
+        console::Action::Owns(arg) => {
+            if let Err(e) = console::owns::print_owners(arg) {
+                error!("{}", e);
+                exit(1);
+            }
+            exit(0);
+        }
+        console::Action::Cache(console::cache::CacheCommand::Stats) => {
+            if let Err(e) = console::cache::print_stats(executor::cache::CACHE_ROOT.get()) {
+                error!("{}", e);
+                exit(1);
+            }
+            exit(0);
+        }
+        console::Action::Uninstall(arg) if !arg.prune_removed => {
+            if let Err(e) = console::uninstall::uninstall_tasks(arg) {
+                error!("{}", e);
+                exit(1);
+            }
+            exit(0);
+        }
         _ => {}
     }
 
     let mut parser = parser::Parser::new(context.config_dir().unwrap().clone());
+    parser.set_profile(context.profile().cloned());
+    parser.set_resolve_mode(context.dep_resolve());
     let r = parser.parse();
     if r.is_err() {
         exit(1);
@@ -181,18 +303,170 @@ pub fn dadk_main() {
     let tasks: Vec<(PathBuf, DADKTask)> = r.unwrap();
     // info!("Parsed tasks: {:?}", tasks);
 
+    if context.action() == &console::Action::List {
+        console::list::list_tasks(&tasks);
+        exit(0);
+    }
+
+    if let console::Action::Info(arg) = context.action() {
+        // 指定了任务时，通过调度器计算任务执行时设置的环境变量和任务的传递依赖
+        let scheduler = match &arg.task {
+            Some(_) => match Scheduler::new(
+                context.clone(),
+                context.sysroot_dir().cloned().unwrap_or_default(),
+                context.action().clone(),
+                tasks.clone(),
+            ) {
+                Ok(scheduler) => Some(scheduler),
+                Err(_) => exit(1),
+            },
+            None => None,
+        };
+        let envs_of = |task: &DADKTask| match &scheduler {
+            Some(scheduler) => scheduler
+                .effective_envs(task)
+                .map_err(|e| format!("Failed to compute the environment: {:?}", e)),
+            None => Ok(Vec::new()),
+        };
+        let deps_of = |task: &DADKTask| match &scheduler {
+            Some(scheduler) => scheduler
+                .transitive_deps(task)
+                .map_err(|e| format!("Failed to resolve dependencies: {:?}", e)),
+            None => Ok(Vec::new()),
+        };
+        if let Err(e) = console::info::print_effective_tasks(
+            &tasks,
+            *context.target_arch(),
+            arg,
+            &envs_of,
+            &deps_of,
+        ) {
+            error!("{}", e);
+            exit(1);
+        }
+        exit(0);
+    }
+
+    if let console::Action::Cache(console::cache::CacheCommand::Prune(arg)) = context.action() {
+        let r = console::cache::prune(arg, executor::cache::CACHE_ROOT.get(), &tasks);
+        if let Err(e) = r {
+            error!("{}", e);
+            exit(1);
+        }
+        exit(0);
+    }
+
+    if let console::Action::Uninstall(arg) = context.action() {
+        let r = console::uninstall::uninstall_tasks(arg).and_then(|_| {
+            console::uninstall::prune_removed(arg, &tasks, context.sysroot_dir().unwrap())
+        });
+        if let Err(e) = r {
+            error!("{}", e);
+            exit(1);
+        }
+        exit(0);
+    }
+
+    if let console::Action::Install(arg) = context.action() {
+        if arg.is_plan_only() {
+            let sysroot = context.sysroot_dir().cloned().unwrap();
+            let removed = match console::uninstall::removed_tasks(&tasks, &sysroot) {
+                Ok(removed) => removed,
+                Err(e) => {
+                    error!("{}", e);
+                    exit(1);
+                }
+            };
+            let r = Scheduler::new(
+                context.clone(),
+                sysroot.clone(),
+                context.action().clone(),
+                tasks,
+            )
+            .and_then(|scheduler| scheduler.install_plan());
+            let mut plans = match r {
+                Ok(plans) => plans,
+                Err(e) => {
+                    error!("Failed to compute the install plan: {:?}", e);
+                    exit(1);
+                }
+            };
+            plans.extend(removed.iter().map(TaskPlan::removal));
+            if let Err(e) = console::install::print_plans(&plans, &sysroot, arg.diff) {
+                error!("{}", e);
+                exit(1);
+            }
+            exit(0);
+        }
+    }
+
+    if let console::Action::Clean(arg) = context.action() {
+        if arg.dry_run {
+            let r = Scheduler::new(
+                context.clone(),
+                context.sysroot_dir().cloned().unwrap(),
+                context.action().clone(),
+                tasks,
+            )
+            .and_then(|scheduler| scheduler.clean_plan(arg));
+            let plans = match r {
+                Ok(plans) => plans,
+                Err(e) => {
+                    error!("Failed to compute the clean plan: {:?}", e);
+                    exit(1);
+                }
+            };
+            if let Err(e) = console::clean::print_plans(arg.level(), &plans) {
+                error!("{}", e);
+                exit(1);
+            }
+            exit(0);
+        }
+    }
+
+    if args.preflight {
+        let arch = *context.target_arch();
+        let arch_tasks: Vec<DADKTask> = tasks
+            .iter()
+            .filter(|(_, task)| context.all_arches() || task.target_arch.contains(&arch))
+            .map(|(_, task)| task.clone())
+            .collect();
+        let report = executor::preflight::preflight(&arch_tasks);
+        if !report.is_ok() {
+            error!("{}", report);
+            exit(1);
+        }
+        info!("{}", report);
+    }
+
     let scheduler = Scheduler::new(
         context.clone(),
         context.sysroot_dir().cloned().unwrap(),
-        *context.action(),
+        context.action().clone(),
         tasks,
     );
     if scheduler.is_err() {
         exit(1);
     }
 
-    let r = scheduler.unwrap().run();
-    if r.is_err() {
+    let results = match scheduler.unwrap().run() {
+        Ok(results) => results,
+        Err(e) => {
+            error!("{:?}", e);
+            exit(1);
+        }
+    };
+    info!("{}", executor::result::summary(&results));
+    if let Some(report) = args.report {
+        let json = serde_json::to_string_pretty(&results).unwrap();
+        if let Err(e) = std::fs::write(&report, json) {
+            error!("Failed to write report {}: {}", report.display(), e);
+            exit(1);
+        }
+        info!("Report written to {}", report.display());
+    }
+    // 清理命令失败、按`on_failure = "continue"`继续清理其他任务时，最终以非0的退出码退出
+    if results.iter().any(|r| !r.success) {
         exit(1);
     }
 }
@@ -0,0 +1,163 @@
+//! # 从Cargo.toml导入任务
+//!
+//! 读取一个Rust项目的`Cargo.toml`，生成一个从本地源码构建的DADK任务骨架，
+//! 便于Rust项目快速接入DragonOS。
+//!
+//! 生成的任务：
+//!
+//! - 包名、版本、描述取自`[package]`
+//! - 任务类型为`BuildFromSource(Local(<Cargo.toml所在目录>))`
+//! - `rust_target`由目标架构决定，构建命令为`cargo build --release --target <rust_target>`，
+//!   构建完成后会把二进制文件拷贝到`$DADK_CURRENT_BUILD_DIR`
+//! - `[dependencies]`中与已有DADK任务同名的依赖，会被转换为任务依赖
+
+use std::path::{Path, PathBuf};
+
+use super::{
+    task::{
+        BuildConfig, CleanConfig, CodeSource, DADKTask, Dependency, InstallConfig, TargetArch,
+        TaskType,
+    },
+    InnerParserError, ParserError,
+};
+use crate::executor::source::LocalSource;
+
+/// 生成的任务默认的安装路径
+const DEFAULT_IN_DRAGONOS_PATH: &str = "/bin";
+
+/// # 从Cargo.toml生成DADK任务
+///
+/// ## 参数
+///
+/// * `manifest_path` - Cargo.toml的路径
+/// * `target_arch` - 目标架构
+/// * `known_tasks` - 已有的DADK任务，用于把Cargo依赖转换为任务依赖
+///
+/// ## 返回值
+///
+/// * `Ok(DADKTask)` - 生成的任务
+/// * `Err(ParserError)` - 读取或解析Cargo.toml失败
+pub fn task_from_cargo_manifest(
+    manifest_path: &Path,
+    target_arch: TargetArch,
+    known_tasks: &[DADKTask],
+) -> Result<DADKTask, ParserError> {
+    let err = |error: InnerParserError| ParserError {
+        config_file: Some(manifest_path.to_path_buf()),
+        error,
+        location: None,
+    };
+
+    let content =
+        std::fs::read_to_string(manifest_path).map_err(|e| err(InnerParserError::IoError(e)))?;
+    let manifest: toml::Table =
+        toml::from_str(&content).map_err(|e| ParserError::toml(manifest_path, &content, e))?;
+
+    let package = manifest
+        .get("package")
+        .and_then(|p| p.as_table())
+        .ok_or(err(InnerParserError::TaskError(
+            "missing [package] section".to_string(),
+        )))?;
+    let package_str = |key: &str| -> Result<Option<String>, ParserError> {
+        match package.get(key) {
+            None => Ok(None),
+            Some(toml::Value::String(s)) => Ok(Some(s.clone())),
+            Some(_) => Err(err(InnerParserError::TaskError(format!(
+                "package.{} must be a string, inheriting from the workspace is not supported",
+                key
+            )))),
+        }
+    };
+    let name = package_str("name")?.ok_or(err(InnerParserError::TaskError(
+        "missing package.name".to_string(),
+    )))?;
+    let version = package_str("version")?.unwrap_or("0.0.0".to_string());
+    let description = package_str("description")?.unwrap_or_default();
+
+    let source_dir = match manifest_path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+
+    let rust_target = target_arch.rust_target();
+    let build_command = build_command(&manifest, &source_dir, &name, rust_target);
+
+    let depends = manifest
+        .get("dependencies")
+        .and_then(|d| d.as_table())
+        .map(|deps| map_dependencies(deps, known_tasks))
+        .unwrap_or_default();
+
+    let task = DADKTask::new(
+        name,
+        version,
+        description,
+        Some(rust_target.to_string()),
+        TaskType::BuildFromSource(CodeSource::Local(LocalSource::new(source_dir))),
+        depends,
+        BuildConfig::new(Some(build_command)),
+        InstallConfig::new(Some(PathBuf::from(DEFAULT_IN_DRAGONOS_PATH))),
+        CleanConfig::new(Some("cargo clean".to_string())),
+        None,
+        false,
+        false,
+        Some(vec![target_arch]),
+    );
+    return Ok(task);
+}
+
+/// 生成构建命令
+///
+/// 如果包中有二进制目标，则在构建完成后把它们拷贝到`$DADK_CURRENT_BUILD_DIR`
+fn build_command(
+    manifest: &toml::Table,
+    source_dir: &Path,
+    package_name: &str,
+    rust_target: &str,
+) -> String {
+    let mut bins: Vec<String> = manifest
+        .get("bin")
+        .and_then(|b| b.as_array())
+        .map(|bins| {
+            bins.iter()
+                .filter_map(|b| b.get("name").and_then(|n| n.as_str()))
+                .map(|n| n.to_string())
+                .collect()
+        })
+        .unwrap_or_default();
+    if bins.is_empty() && source_dir.join("src/main.rs").exists() {
+        bins.push(package_name.to_string());
+    }
+
+    let mut cmd = format!("cargo build --release --target {}", rust_target);
+    for bin in bins.iter() {
+        cmd.push_str(&format!(
+            " && cp target/{}/release/{} $DADK_CURRENT_BUILD_DIR/",
+            rust_target, bin
+        ));
+    }
+    return cmd;
+}
+
+/// 把与已有DADK任务同名的Cargo依赖转换为任务依赖
+///
+/// 比较名称时不区分`-`和`_`，依赖被重命名（`package = "..."`）时使用原始包名
+fn map_dependencies(deps: &toml::Table, known_tasks: &[DADKTask]) -> Vec<Dependency> {
+    let normalize = |s: &str| s.replace('-', "_");
+
+    let mut result: Vec<Dependency> = Vec::new();
+    for (key, value) in deps.iter() {
+        let crate_name = value.get("package").and_then(|p| p.as_str()).unwrap_or(key);
+        if let Some(task) = known_tasks
+            .iter()
+            .find(|t| normalize(&t.name) == normalize(crate_name))
+        {
+            let dep = Dependency::new(task.name.clone(), task.version.clone());
+            if !result.contains(&dep) {
+                result.push(dep);
+            }
+        }
+    }
+    return result;
+}
@@ -0,0 +1,317 @@
+//! # 任务的启用条件
+//!
+//! 任务可以通过`enabled_if`设置一个布尔表达式，调度前按当前的目标架构和环境变量求值，
+//! 结果为false时任务被跳过（不会被删除），并在执行结果中标记为disabled。
+//!
+//! 表达式的语法：
+//!
+//! - 操作数：`arch`（当前目标架构）、`env.NAME`（DADK进程的环境变量，未设置时为空字符串）、
+//!   双引号字符串（支持`\"`和`\\`转义）
+//! - 比较：`==`、`!=`，每个比较的两边都必须是操作数
+//! - 逻辑运算：`!`、`&&`、`||`，优先级从高到低，可以用括号分组
+//! - 字面量：`true`、`false`
+//!
+//! 例如：`arch == "x86_64" && env.FEATURE_X == "1"`
+//!
+//! 与`arch`比较的字符串必须是合法的目标架构，避免拼写错误导致任务被悄悄跳过。
+
+use std::fmt::Display;
+
+use super::task::TargetArch;
+
+/// # 启用条件表达式
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Condition {
+    Literal(bool),
+    Eq(Operand, Operand),
+    Ne(Operand, Operand),
+    Not(Box<Condition>),
+    And(Box<Condition>, Box<Condition>),
+    Or(Box<Condition>, Box<Condition>),
+}
+
+/// # 比较的操作数
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operand {
+    Arch,
+    Env(String),
+    Str(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    EqEq,
+    NotEq,
+    Not,
+    And,
+    Or,
+    LParen,
+    RParen,
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Ident(s) => write!(f, "`{}`", s),
+            Token::Str(s) => write!(f, "{:?}", s),
+            Token::EqEq => write!(f, "`==`"),
+            Token::NotEq => write!(f, "`!=`"),
+            Token::Not => write!(f, "`!`"),
+            Token::And => write!(f, "`&&`"),
+            Token::Or => write!(f, "`||`"),
+            Token::LParen => write!(f, "`(`"),
+            Token::RParen => write!(f, "`)`"),
+        }
+    }
+}
+
+impl Condition {
+    /// # 解析启用条件表达式
+    ///
+    /// 出错时返回的信息中带有出错的位置（从0开始的字符偏移）
+    pub fn parse(expr: &str) -> Result<Self, String> {
+        let tokens = tokenize(expr).map_err(|e| format!("enabled_if {:?}: {}", expr, e))?;
+        if tokens.is_empty() {
+            return Err("enabled_if is empty".to_string());
+        }
+        let mut parser = Parser { tokens, pos: 0 };
+        let cond = parser
+            .parse_or()
+            .map_err(|e| format!("enabled_if {:?}: {}", expr, e))?;
+        if let Some((token, offset)) = parser.peek() {
+            return Err(format!(
+                "enabled_if {:?}: unexpected {} at {}",
+                expr, token, offset
+            ));
+        }
+        return Ok(cond);
+    }
+
+    /// # 求值
+    ///
+    /// ## 参数
+    ///
+    /// * `arch` - 当前目标架构
+    /// * `env` - 查询环境变量的值
+    pub fn eval(&self, arch: TargetArch, env: &dyn Fn(&str) -> Option<String>) -> bool {
+        match self {
+            Condition::Literal(b) => *b,
+            Condition::Eq(a, b) => a.value(arch, env) == b.value(arch, env),
+            Condition::Ne(a, b) => a.value(arch, env) != b.value(arch, env),
+            Condition::Not(c) => !c.eval(arch, env),
+            Condition::And(a, b) => a.eval(arch, env) && b.eval(arch, env),
+            Condition::Or(a, b) => a.eval(arch, env) || b.eval(arch, env),
+        }
+    }
+
+    /// 使用DADK进程的环境变量求值
+    pub fn eval_with_process_env(&self, arch: TargetArch) -> bool {
+        return self.eval(arch, &|key| std::env::var(key).ok());
+    }
+}
+
+impl Operand {
+    fn value(&self, arch: TargetArch, env: &dyn Fn(&str) -> Option<String>) -> String {
+        match self {
+            Operand::Arch => {
+                let s: &str = arch.into();
+                s.to_string()
+            }
+            Operand::Env(key) => env(key).unwrap_or_default(),
+            Operand::Str(s) => s.clone(),
+        }
+    }
+}
+
+struct Parser {
+    /// 词法单元及其在表达式中的位置
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&(Token, usize)> {
+        return self.tokens.get(self.pos);
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        if self.peek().map(|(t, _)| t) == Some(token) {
+            self.pos += 1;
+            return true;
+        }
+        return false;
+    }
+
+    fn parse_or(&mut self) -> Result<Condition, String> {
+        let mut left = self.parse_and()?;
+        while self.eat(&Token::Or) {
+            let right = self.parse_and()?;
+            left = Condition::Or(Box::new(left), Box::new(right));
+        }
+        return Ok(left);
+    }
+
+    fn parse_and(&mut self) -> Result<Condition, String> {
+        let mut left = self.parse_unary()?;
+        while self.eat(&Token::And) {
+            let right = self.parse_unary()?;
+            left = Condition::And(Box::new(left), Box::new(right));
+        }
+        return Ok(left);
+    }
+
+    fn parse_unary(&mut self) -> Result<Condition, String> {
+        if self.eat(&Token::Not) {
+            return Ok(Condition::Not(Box::new(self.parse_unary()?)));
+        }
+        if self.eat(&Token::LParen) {
+            let cond = self.parse_or()?;
+            if !self.eat(&Token::RParen) {
+                return Err(self.expected("`)`"));
+            }
+            return Ok(cond);
+        }
+        if let Some((Token::Ident(ident), _)) = self.peek() {
+            let literal = match ident.as_str() {
+                "true" => Some(true),
+                "false" => Some(false),
+                _ => None,
+            };
+            if let Some(b) = literal {
+                self.pos += 1;
+                return Ok(Condition::Literal(b));
+            }
+        }
+        return self.parse_comparison();
+    }
+
+    fn parse_comparison(&mut self) -> Result<Condition, String> {
+        let left = self.parse_operand()?;
+        let eq = if self.eat(&Token::EqEq) {
+            true
+        } else if self.eat(&Token::NotEq) {
+            false
+        } else {
+            return Err(self.expected("`==` or `!=`"));
+        };
+        let right = self.parse_operand()?;
+        check_arch_literal(&left, &right)?;
+        check_arch_literal(&right, &left)?;
+        if eq {
+            return Ok(Condition::Eq(left, right));
+        }
+        return Ok(Condition::Ne(left, right));
+    }
+
+    fn parse_operand(&mut self) -> Result<Operand, String> {
+        let (token, offset) = match self.peek() {
+            Some(t) => t.clone(),
+            None => return Err(self.expected("`arch`, `env.NAME` or a string")),
+        };
+        let operand = match token {
+            Token::Str(s) => Operand::Str(s),
+            Token::Ident(ident) if ident == "arch" => Operand::Arch,
+            Token::Ident(ident) if ident.starts_with("env.") => {
+                let key = &ident["env.".len()..];
+                if key.is_empty() {
+                    return Err(format!("missing environment variable name at {}", offset));
+                }
+                Operand::Env(key.to_string())
+            }
+            Token::Ident(ident) => {
+                return Err(format!(
+                    "unknown identifier `{}` at {}, expected `arch` or `env.NAME`",
+                    ident, offset
+                ))
+            }
+            _ => return Err(self.expected("`arch`, `env.NAME` or a string")),
+        };
+        self.pos += 1;
+        return Ok(operand);
+    }
+
+    fn expected(&self, what: &str) -> String {
+        match self.peek() {
+            Some((token, offset)) => format!("expected {}, found {} at {}", what, token, offset),
+            None => format!("expected {}, found end of expression", what),
+        }
+    }
+}
+
+/// 与`arch`比较的字符串必须是合法的目标架构
+fn check_arch_literal(a: &Operand, b: &Operand) -> Result<(), String> {
+    if let (Operand::Arch, Operand::Str(s)) = (a, b) {
+        if TargetArch::try_from(s.as_str()).is_err() {
+            return Err(format!(
+                "unknown arch {:?}, expected one of {:?}",
+                s,
+                TargetArch::EXPECTED
+            ));
+        }
+    }
+    return Ok(());
+}
+
+fn tokenize(expr: &str) -> Result<Vec<(Token, usize)>, String> {
+    let chars: Vec<char> = expr.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        let two = |next: char| chars.get(i + 1) == Some(&next);
+        let token = match c {
+            _ if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            '=' if two('=') => Token::EqEq,
+            '!' if two('=') => Token::NotEq,
+            '!' => Token::Not,
+            '&' if two('&') => Token::And,
+            '|' if two('|') => Token::Or,
+            '"' => {
+                let mut s = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        None => return Err(format!("unterminated string at {}", start)),
+                        Some('"') => break,
+                        Some('\\') => match chars.get(i + 1) {
+                            Some(e @ ('"' | '\\')) => {
+                                s.push(*e);
+                                i += 2;
+                            }
+                            _ => return Err(format!("invalid escape in string at {}", i)),
+                        },
+                        Some(ch) => {
+                            s.push(*ch);
+                            i += 1;
+                        }
+                    }
+                }
+                Token::Str(s)
+            }
+            _ if c.is_ascii_alphabetic() || c == '_' => {
+                while i < chars.len()
+                    && (chars[i].is_ascii_alphanumeric() || chars[i] == '_' || chars[i] == '.')
+                {
+                    i += 1;
+                }
+                tokens.push((Token::Ident(chars[start..i].iter().collect()), start));
+                continue;
+            }
+            _ => return Err(format!("unexpected character {:?} at {}", c, i)),
+        };
+        i += match token {
+            Token::EqEq | Token::NotEq | Token::And | Token::Or => 2,
+            _ => 1,
+        };
+        tokens.push((token, start));
+    }
+    return Ok(tokens);
+}
@@ -0,0 +1,101 @@
+//! # 解析dotenv格式的环境变量文件
+//!
+//! 任务和工作区可以通过`env_files`从文件中加载环境变量，以免把凭据、与机器相关的设置写进提交的配置文件。
+//! 文件每行一个`KEY=VALUE`：
+//!
+//! - 空行和以`#`开头的行被忽略，行首可以有`export `
+//! - 没有引号的值去掉两端的空白，` #`之后的内容是注释
+//! - 单引号中的值原样使用
+//! - 双引号中的值支持`\n`、`\t`、`\"`、`\\`转义
+//!
+//! 值中的`${...}`不会被替换。
+
+use std::path::Path;
+
+use super::task::TaskEnv;
+
+/// # 解析dotenv格式的内容
+///
+/// 出错时返回出错的行号（从1开始）和原因
+pub fn parse(content: &str) -> Result<Vec<TaskEnv>, (usize, String)> {
+    let mut envs = Vec::new();
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line).trim_start();
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| (i + 1, format!("expected KEY=VALUE, got {:?}", line)))?;
+        let key = key.trim();
+        if !is_valid_key(key) {
+            return Err((i + 1, format!("invalid variable name {:?}", key)));
+        }
+        let value = parse_value(value.trim()).map_err(|e| (i + 1, e))?;
+        envs.push(TaskEnv::new(key.to_string(), value));
+    }
+    return Ok(envs);
+}
+
+/// # 加载dotenv格式的文件
+///
+/// 错误信息中包括文件路径和行号
+pub fn load(path: &Path) -> Result<Vec<TaskEnv>, String> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("failed to read env file {}: {}", path.display(), e))?;
+    return parse(&content).map_err(|(line, e)| format!("{}:{}: {}", path.display(), line, e));
+}
+
+pub(crate) fn is_valid_key(key: &str) -> bool {
+    let mut chars = key.chars();
+    return match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        _ => false,
+    };
+}
+
+fn parse_value(value: &str) -> Result<String, String> {
+    let quote = match value.chars().next() {
+        Some(c @ ('\'' | '"')) => c,
+        _ => {
+            // 没有引号时，` #`之后是注释
+            let value = match value.find(" #") {
+                Some(i) => &value[..i],
+                None => value,
+            };
+            return Ok(value.trim_end().to_string());
+        }
+    };
+    let mut result = String::new();
+    let mut chars = value[1..].chars();
+    loop {
+        let c = chars
+            .next()
+            .ok_or_else(|| format!("unterminated {} quoted value", quote))?;
+        match c {
+            c if c == quote => break,
+            '\\' if quote == '"' => {
+                let escaped = chars
+                    .next()
+                    .ok_or_else(|| "unterminated \" quoted value".to_string())?;
+                result.push(match escaped {
+                    'n' => '\n',
+                    't' => '\t',
+                    'r' => '\r',
+                    '"' => '"',
+                    '\\' => '\\',
+                    c => return Err(format!("unknown escape sequence \\{}", c)),
+                });
+            }
+            c => result.push(c),
+        }
+    }
+    let rest = chars.as_str().trim_start();
+    if !rest.is_empty() && !rest.starts_with('#') {
+        return Err(format!("unexpected {:?} after the quoted value", rest));
+    }
+    return Ok(result);
+}
@@ -0,0 +1,169 @@
+//! # 配置中的变量插值
+//!
+//! `build_command`、`clean_command`、任务环境变量的值以及安装路径（`in_dragonos_path`、`destinations`、`files`的`dest`、`symlinks`）中的
+//! `${变量}`会在执行任务时被替换，支持的变量见[`VARIABLES`]，此外：
+//!
+//! - `${env:FOO}`被替换为主机的环境变量`FOO`，没有设置时任务失败
+//! - `${env:FOO:-默认值}`在`FOO`没有设置或者为空时使用默认值（默认值中不能再引用变量）
+//! - `${dep:名称}`被替换为`depends`中同名的依赖的构建结果目录
+//!
+//! `$${`表示字面量`${`，其他的`$`（例如shell变量`$FOO`）保持不变。
+//!
+//! 解析配置文件时会检查变量名，未知的变量、不在`depends`中的依赖导致解析失败。
+
+/// 支持的变量
+pub const VARIABLES: [&str; 9] = [
+    // 当前目标架构，例如x86_64
+    "ARCH",
+    // 任务名
+    "NAME",
+    // 任务版本
+    "VERSION",
+    // 任务的`name_version()`，例如app_0_1_0
+    "NAME_VERSION",
+    // DADK的缓存根目录
+    "DADK_CACHE_ROOT",
+    // 执行构建命令的工作目录，与环境变量`DADK_BUILD_DIR`相同
+    "DADK_BUILD_DIR",
+    // 任务的构建结果目录，与环境变量`DADK_CURRENT_BUILD_DIR`相同
+    "DADK_CURRENT_BUILD_DIR",
+    // 当前生效的构建配置档，没有时为空字符串
+    "PROFILE",
+    // DragonOS sysroot在主机上的路径，与环境变量`DADK_SYSROOT`相同
+    "DADK_SYSROOT",
+];
+
+/// 主机环境变量的前缀
+const ENV_PREFIX: &str = "env:";
+
+/// 依赖的构建结果目录的前缀
+pub const DEP_PREFIX: &str = "dep:";
+
+/// 主机环境变量与默认值之间的分隔符
+const DEFAULT_SEPARATOR: &str = ":-";
+
+/// 字符串中的一段
+#[derive(Debug, PartialEq, Eq)]
+enum Segment<'a> {
+    Text(&'a str),
+    /// [`VARIABLES`]中的变量，或者`dep:名称`
+    Var(&'a str),
+    /// 主机环境变量，以及可选的默认值
+    Env(&'a str, Option<&'a str>),
+}
+
+/// # 检查字符串中的变量
+///
+/// 语法错误或者使用了未知的变量时返回错误
+pub fn validate(s: &str) -> Result<(), String> {
+    parse(s)?;
+    return Ok(());
+}
+
+/// # 字符串中通过`${dep:名称}`引用的依赖
+///
+/// 语法错误或者使用了未知的变量时返回错误
+pub fn referenced_dependencies(s: &str) -> Result<Vec<&str>, String> {
+    return Ok(parse(s)?
+        .into_iter()
+        .filter_map(|segment| match segment {
+            Segment::Var(name) => name.strip_prefix(DEP_PREFIX),
+            _ => None,
+        })
+        .collect());
+}
+
+/// # 替换字符串中的变量
+///
+/// ## 参数
+///
+/// * `s` - 要替换的字符串
+/// * `lookup` - 查询[`VARIABLES`]中的变量以及`dep:名称`的值
+pub fn interpolate(
+    s: &str,
+    lookup: &dyn Fn(&str) -> Result<String, String>,
+) -> Result<String, String> {
+    let mut result = String::with_capacity(s.len());
+    for segment in parse(s)? {
+        match segment {
+            Segment::Text(text) => result.push_str(text),
+            Segment::Var(name) => result.push_str(&lookup(name)?),
+            Segment::Env(key, default) => {
+                let value = match (std::env::var(key), default) {
+                    (Ok(value), Some(default)) if value.is_empty() => default.to_string(),
+                    (Ok(value), _) => value,
+                    (Err(_), Some(default)) => default.to_string(),
+                    (Err(_), None) => {
+                        return Err(format!(
+                            "environment variable {} referenced by ${{{}{}}} in {:?} is not set",
+                            key, ENV_PREFIX, key, s
+                        ))
+                    }
+                };
+                result.push_str(&value);
+            }
+        }
+    }
+    return Ok(result);
+}
+
+fn parse(s: &str) -> Result<Vec<Segment>, String> {
+    let mut segments = Vec::new();
+    let mut rest = s;
+    while let Some(pos) = rest.find('$') {
+        let after = &rest[pos + 1..];
+        if let Some(escaped) = after.strip_prefix("${") {
+            // `$${`转义为`${`
+            segments.push(Segment::Text(&rest[..pos + 1]));
+            segments.push(Segment::Text("{"));
+            rest = escaped;
+            continue;
+        }
+        if !after.starts_with('{') {
+            segments.push(Segment::Text(&rest[..pos + 1]));
+            rest = after;
+            continue;
+        }
+        segments.push(Segment::Text(&rest[..pos]));
+        let end = after
+            .find('}')
+            .ok_or_else(|| format!("unterminated ${{ in {:?}", s))?;
+        let name = &after[1..end];
+        if let Some(key) = name.strip_prefix(ENV_PREFIX) {
+            let (key, default) = match key.split_once(DEFAULT_SEPARATOR) {
+                Some((key, default)) => (key, Some(default)),
+                None => (key, None),
+            };
+            if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                return Err(format!(
+                    "invalid environment variable name ${{{}}} in {:?}",
+                    name, s
+                ));
+            }
+            if default.map_or(false, |d| d.contains("${")) {
+                return Err(format!(
+                    "the default value of ${{{}}} cannot reference variables in {:?}",
+                    name, s
+                ));
+            }
+            segments.push(Segment::Env(key, default));
+        } else if let Some(dep) = name.strip_prefix(DEP_PREFIX) {
+            if dep.trim().is_empty() {
+                return Err(format!("empty dependency name ${{{}}} in {:?}", name, s));
+            }
+            segments.push(Segment::Var(name));
+        } else if VARIABLES.contains(&name) {
+            segments.push(Segment::Var(name));
+        } else {
+            return Err(format!(
+                "unknown variable ${{{}}} in {:?}, expected one of {}, ${{env:NAME}}, ${{env:NAME:-DEFAULT}} or ${{dep:NAME}} (use $${{ for a literal ${{)",
+                name,
+                s,
+                VARIABLES.map(|v| format!("${{{}}}", v)).join(", ")
+            ));
+        }
+        rest = &after[end + 1..];
+    }
+    segments.push(Segment::Text(rest));
+    return Ok(segments);
+}
@@ -4,6 +4,7 @@
 //!
 //! 您需要指定一个配置文件目录，解析器会自动解析该目录下的所有配置文件。
 //! 软件包的配置文件必须以`.dadk`作为后缀名，内容格式为json。
+//! 作为库使用时，可以通过[`load_tasks`]只加载并校验一个目录下的所有任务。
 //!
 //! ## 简介
 //!
@@ -20,22 +21,56 @@
 //!     "depends": [{依赖项（该部分详见Dependency的文档）}],
 //!     "build": {构建配置（该部分详见BuildConfig的文档）},
 //!     "install": {安装配置（该部分详见InstallConfig的文档）},
-//!     "envs" : [{ "key": "环境变量名", "value": "环境变量值" }]
+//!     "envs" : [{ "key": "环境变量名", "value": "环境变量值", "target_arch": (可选) 只在按该架构构建时设置 }]
 //!     "build_once": (可选) 是否只构建一次，如果为true，DADK会在构建成功后，将构建结果缓存起来，下次构建时，直接使用缓存的构建结果。
 //! }
+//!
+//! ## 工作区配置
+//!
+//! 配置文件目录下可以有一个`dadk-config.toml`，其中的配置对所有任务生效，详见`workspace`模块的文档。
 use std::{
+    collections::BTreeSet,
     fmt::Debug,
     fs::{DirEntry, ReadDir},
-    path::PathBuf,
+    path::{Path, PathBuf},
 };
 
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
+
+use crate::utils::suggest::SuggestUtils;
 
-use self::task::DADKTask;
+use self::{
+    resolver::{resolve_dependencies, ResolveMode},
+    task::DADKTask,
+    workspace::WorkspaceConfig,
+};
+pub mod cargo_import;
+pub mod condition;
+pub mod dotenv;
+pub mod interpolate;
+pub mod resolver;
 pub mod task;
 pub mod task_log;
 #[cfg(test)]
 mod tests;
+pub mod workspace;
+
+/// # 加载目录下的所有任务
+///
+/// 递归扫描`dir`中的配置文件（`.dadk`后缀，其他文件被忽略），按路径排序后逐个解析、去除空白并校验。
+/// 与[`Parser::parse`]不同，不会应用工作区配置、构建配置档，也不会解析依赖关系。
+///
+/// 返回所有任务，或者第一个出错的配置文件的错误（`config_file`为该文件）
+pub fn load_tasks(dir: &Path) -> Result<Vec<DADKTask>, ParserError> {
+    let mut parser = Parser::new(dir.to_path_buf());
+    parser.scan_config_files()?;
+    parser.config_files.sort();
+    return parser
+        .config_files
+        .iter()
+        .map(|config_file| parser.parse_config_file(config_file))
+        .collect();
+}
 
 /// # 配置解析器
 ///
@@ -46,12 +81,106 @@ pub struct Parser {
     config_dir: PathBuf,
     /// 扫描到的配置文件列表
     config_files: Vec<PathBuf>,
+    /// 生效的构建配置档
+    profile: Option<String>,
+    /// 依赖版本冲突的解析策略
+    resolve_mode: ResolveMode,
 }
 
 pub struct ParserError {
     pub config_file: Option<PathBuf>,
     pub error: InnerParserError,
+    /// 错误在配置文件中的位置（仅语法错误）
+    pub location: Option<ErrorLocation>,
+}
+
+/// # 错误在配置文件中的位置
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorLocation {
+    /// 行号，从1开始
+    pub line: usize,
+    /// 列号，从1开始
+    pub column: usize,
+    /// 出错的那一行的内容
+    pub snippet: String,
+}
+
+impl ErrorLocation {
+    /// 根据行号和列号（均从1开始）生成错误位置
+    pub fn new(content: &str, line: usize, column: usize) -> Self {
+        let snippet = content
+            .lines()
+            .nth(line.saturating_sub(1))
+            .unwrap_or_default()
+            .to_string();
+        return Self {
+            line,
+            column,
+            snippet,
+        };
+    }
+
+    /// 根据字节偏移量生成错误位置
+    pub fn from_offset(content: &str, offset: usize) -> Self {
+        let offset = offset.min(content.len());
+        let before = &content[..offset];
+        let line = before.matches('\n').count() + 1;
+        let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+        let column = before[line_start..].chars().count() + 1;
+        return Self::new(content, line, column);
+    }
+}
+
+impl ParserError {
+    /// 配置文件的json语法错误
+    pub fn json(config_file: &Path, content: &str, e: serde_json::Error) -> Self {
+        let location = if e.line() > 0 {
+            Some(ErrorLocation::new(content, e.line(), e.column()))
+        } else {
+            None
+        };
+        return Self {
+            config_file: Some(config_file.to_path_buf()),
+            error: InnerParserError::JsonError(e),
+            location,
+        };
+    }
+
+    /// toml文件的语法错误
+    pub fn toml(config_file: &Path, content: &str, e: toml::de::Error) -> Self {
+        let location = e
+            .span()
+            .map(|span| ErrorLocation::from_offset(content, span.start));
+        return Self {
+            config_file: Some(config_file.to_path_buf()),
+            error: InnerParserError::TomlError(Box::new(e)),
+            location,
+        };
+    }
+
+    /// 输出出错位置及出错的那一行
+    fn fmt_location(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(loc) = &self.location {
+            let file = self
+                .config_file
+                .as_ref()
+                .map(|p| p.display().to_string())
+                .unwrap_or_default();
+            let gutter = " ".repeat(loc.line.to_string().len());
+            write!(f, "\n{} --> {}:{}:{}", gutter, file, loc.line, loc.column)?;
+            write!(f, "\n{} |", gutter)?;
+            write!(f, "\n{} | {}", loc.line, loc.snippet)?;
+            write!(
+                f,
+                "\n{} | {}^",
+                gutter,
+                " ".repeat(loc.column.saturating_sub(1))
+            )?;
+        }
+        return Ok(());
+    }
 }
+
 impl Debug for ParserError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match &self.error {
@@ -79,6 +208,24 @@ impl Debug for ParserError {
                     write!(f, "Json Error while parsing config file: {}", e)
                 }
             }
+            InnerParserError::TomlError(e) => {
+                // toml的错误信息中已经带有出错的那一行，有位置信息时只输出错误原因，避免重复
+                let msg = if self.location.is_some() {
+                    e.message().to_string()
+                } else {
+                    e.to_string()
+                };
+                if let Some(config_file) = &self.config_file {
+                    write!(
+                        f,
+                        "Toml Error while parsing config file {}: {}",
+                        config_file.display(),
+                        msg
+                    )
+                } else {
+                    write!(f, "Toml Error while parsing config file: {}", msg)
+                }
+            }
             InnerParserError::TaskError(e) => {
                 if let Some(config_file) = &self.config_file {
                     write!(
@@ -91,7 +238,8 @@ impl Debug for ParserError {
                     write!(f, "Error while parsing config file: {}", e)
                 }
             }
-        }
+        }?;
+        return self.fmt_location(f);
     }
 }
 
@@ -99,6 +247,7 @@ impl Debug for ParserError {
 pub enum InnerParserError {
     IoError(std::io::Error),
     JsonError(serde_json::Error),
+    TomlError(Box<toml::de::Error>),
     TaskError(String),
 }
 
@@ -107,9 +256,23 @@ impl Parser {
         Self {
             config_dir,
             config_files: Vec::new(),
+            profile: None,
+            resolve_mode: ResolveMode::default(),
         }
     }
 
+    /// # 设置生效的构建配置档
+    ///
+    /// 解析时会把每个任务中该配置档的覆盖配置合并到基础配置之上
+    pub fn set_profile(&mut self, profile: Option<String>) {
+        self.profile = profile;
+    }
+
+    /// # 设置依赖版本冲突的解析策略
+    pub fn set_resolve_mode(&mut self, mode: ResolveMode) {
+        self.resolve_mode = mode;
+    }
+
     /// # 解析所有配置文件，生成任务列表
     ///
     /// ## 参数
@@ -144,12 +307,14 @@ impl Parser {
             let entries: ReadDir = std::fs::read_dir(&dir).map_err(|e| ParserError {
                 config_file: None,
                 error: InnerParserError::IoError(e),
+                location: None,
             })?;
 
             for entry in entries {
                 let entry: DirEntry = entry.map_err(|e| ParserError {
                     config_file: None,
                     error: InnerParserError::IoError(e),
+                    location: None,
                 })?;
 
                 let path: PathBuf = entry.path();
@@ -182,16 +347,70 @@ impl Parser {
     /// * `Ok(Vec<DADKTask>)` - 任务列表
     /// * `Err(ParserError)` - 解析错误
     fn gen_tasks(&self) -> Result<Vec<(PathBuf, DADKTask)>, ParserError> {
+        let workspace = WorkspaceConfig::load(&self.config_dir)?;
         let mut result_vec = Vec::new();
         for config_file in &self.config_files {
-            let task: DADKTask = self.parse_config_file(config_file)?;
+            let mut task: DADKTask = self.parse_config_file(config_file)?;
             debug!("Parsed config file {}: {:?}", config_file.display(), task);
+            for key in workspace.apply(&mut task) {
+                warn!(
+                    "Config file {}: unset {} is not defined in global_envs",
+                    config_file.display(),
+                    key
+                );
+            }
+            task.dependency_env_keys().map_err(|e| ParserError {
+                config_file: Some(config_file.clone()),
+                error: InnerParserError::TaskError(e),
+                location: None,
+            })?;
             result_vec.push((config_file.clone(), task));
         }
 
+        if let Some(profile) = &self.profile {
+            result_vec = Self::apply_profile(result_vec, profile)?;
+        }
+
+        resolve_dependencies(&mut result_vec, self.resolve_mode).map_err(|e| ParserError {
+            config_file: None,
+            error: InnerParserError::TaskError(e),
+            location: None,
+        })?;
+
         return Ok(result_vec);
     }
 
+    /// # 把构建配置档合并到所有任务中
+    ///
+    /// 如果没有任何任务定义了该配置档，则认为配置档名称有误，返回错误并列出所有已定义的配置档
+    fn apply_profile(
+        tasks: Vec<(PathBuf, DADKTask)>,
+        profile: &str,
+    ) -> Result<Vec<(PathBuf, DADKTask)>, ParserError> {
+        let defined: BTreeSet<&String> = tasks
+            .iter()
+            .flat_map(|(_, task)| task.profile.keys())
+            .collect();
+        if !defined.iter().any(|p| p.as_str() == profile) {
+            let defined: Vec<&str> = defined.iter().map(|p| p.as_str()).collect();
+            return Err(ParserError {
+                config_file: None,
+                error: InnerParserError::TaskError(format!(
+                    "unknown profile '{}', defined profiles: [{}]",
+                    profile,
+                    defined.join(", ")
+                )),
+                location: None,
+            });
+        }
+        info!("Using profile: {}", profile);
+
+        return Ok(tasks
+            .iter()
+            .map(|(path, task)| (path.clone(), task.for_profile(profile)))
+            .collect());
+    }
+
     /// # 解析单个配置文件，生成任务
     ///
     /// ## 参数
@@ -206,25 +425,78 @@ impl Parser {
         let content = std::fs::read_to_string(config_file).map_err(|e| ParserError {
             config_file: Some(config_file.clone()),
             error: InnerParserError::IoError(e),
+            location: None,
         })?;
 
-        // 从json字符串中解析出DADKTask
-        let mut task: DADKTask = serde_json::from_str(&content).map_err(|e| ParserError {
+        // 检查是否有未知的字段（比如拼写错误），否则拼错的字段会被忽略，得到难以理解的错误
+        let value: serde_json::Value = serde_json::from_str(&content)
+            .map_err(|e| ParserError::json(config_file, &content, e))?;
+        Self::check_unknown_keys(&value).map_err(|e| ParserError {
             config_file: Some(config_file.clone()),
-            error: InnerParserError::JsonError(e),
+            error: InnerParserError::TaskError(e),
+            location: None,
         })?;
 
+        // 没有指定`target_arch`时使用默认的目标架构，严格模式下没有设置`ARCH`是错误
+        if value.get("target_arch").is_none() {
+            DADKTask::try_default_target_arch().map_err(|e| ParserError {
+                config_file: Some(config_file.clone()),
+                error: InnerParserError::TaskError(format!("target_arch: {}", e)),
+                location: None,
+            })?;
+        }
+
+        // 从json字符串中解析出DADKTask
+        let mut task: DADKTask = serde_json::from_str(&content)
+            .map_err(|e| ParserError::json(config_file, &content, e))?;
+
         debug!("Parsed config file {}: {:?}", config_file.display(), task);
 
         // 去除字符串中的空白字符
         task.trim();
 
+        // 本地源的相对路径相对于配置文件所在目录
+        if let Some(config_dir) = config_file.parent() {
+            task.resolve_local_paths(config_dir);
+        }
+
         // 校验DADKTask的参数是否合法
-        task.validate().map_err(|e| ParserError {
-            config_file: Some(config_file.clone()),
-            error: InnerParserError::TaskError(e),
-        })?;
+        let report = task.validate_with_warnings();
+        for warning in report.warnings.iter() {
+            warn!("Config file {}: {}", config_file.display(), warning);
+        }
+        if let Some(e) = report.errors.into_iter().next() {
+            return Err(ParserError {
+                config_file: Some(config_file.clone()),
+                error: InnerParserError::TaskError(e),
+                location: None,
+            });
+        }
 
         return Ok(task);
     }
+
+    /// # 检查配置文件中是否有未知的顶层字段
+    ///
+    /// 如果未知字段与某个合法字段足够接近，则在错误信息中给出建议
+    fn check_unknown_keys(value: &serde_json::Value) -> Result<(), String> {
+        let obj = match value.as_object() {
+            Some(obj) => obj,
+            // 不是对象时，交给serde报告类型错误
+            None => return Ok(()),
+        };
+        for key in obj.keys() {
+            if DADKTask::KEYS.contains(&key.as_str()) {
+                continue;
+            }
+            return match SuggestUtils::did_you_mean(key, &DADKTask::KEYS) {
+                Some(suggestion) => Err(format!(
+                    "unknown key '{}', did you mean '{}'?",
+                    key, suggestion
+                )),
+                None => Err(format!("unknown key '{}'", key)),
+            };
+        }
+        return Ok(());
+    }
 }
@@ -0,0 +1,322 @@
+//! # 依赖版本解析
+//!
+//! 任务的依赖中的`version`可以是一个确切的版本（例如`0.1.0`），也可以是一个版本要求：
+//!
+//! - `*`：任意版本
+//! - `^1.2.3`：与1.2.3兼容的版本，即`>=1.2.3, <2.0.0`（与cargo相同，0.x版本的兼容范围更小）
+//! - `~1.2.3`：只允许修订号变化，即`>=1.2.3, <1.3.0`
+//! - `>=`、`>`、`<=`、`<`、`=`：比较，多个比较之间用逗号分隔，例如`>=1.0, <2.0`
+//!
+//! 解析时把所有任务对同一个依赖的要求汇总起来，按照解析策略选出版本，
+//! 然后把每个依赖的`version`改写为选中的版本，之后调度器按确切的版本查找任务。
+
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+    path::PathBuf,
+};
+
+use log::info;
+
+use super::task::{DADKTask, TargetArch};
+
+/// # 依赖版本冲突的解析策略
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ResolveMode {
+    /// 每个依赖方使用满足其版本要求的最高版本，不同的版本可以同时存在
+    #[default]
+    Independent,
+    /// 每个依赖方使用满足其版本要求的最高版本，解析出多个不同的版本时报错
+    Strict,
+    /// 选择满足所有版本要求的最高版本，没有这样的版本时报错
+    Unify,
+}
+
+impl TryFrom<&str> for ResolveMode {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "independent" => Ok(ResolveMode::Independent),
+            "strict" => Ok(ResolveMode::Strict),
+            "unify" => Ok(ResolveMode::Unify),
+            _ => Err(format!(
+                "Unknown dependency resolve mode: {}, expected independent, strict or unify",
+                value
+            )),
+        }
+    }
+}
+
+/// # 版本要求
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionReq {
+    comparators: Vec<Comparator>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Comparator {
+    /// 任意版本
+    Any,
+    /// 与版本字符串完全相同
+    Exact(String),
+    /// 大于等于
+    Ge(Vec<u64>),
+    /// 大于
+    Gt(Vec<u64>),
+    /// 小于等于
+    Le(Vec<u64>),
+    /// 小于
+    Lt(Vec<u64>),
+}
+
+impl VersionReq {
+    pub fn parse(req: &str) -> Result<Self, String> {
+        let mut comparators = Vec::new();
+        for part in req.split(',') {
+            let part = part.trim();
+            if part.is_empty() {
+                return Err(format!("invalid version requirement '{}'", req));
+            }
+            comparators.extend(
+                Self::parse_comparator(part)
+                    .map_err(|e| format!("invalid version requirement '{}': {}", req, e))?,
+            );
+        }
+        return Ok(Self { comparators });
+    }
+
+    fn parse_comparator(part: &str) -> Result<Vec<Comparator>, String> {
+        if part == "*" {
+            return Ok(vec![Comparator::Any]);
+        }
+        // 注意先匹配两个字符的运算符
+        for (op, build) in [
+            (">=", Comparator::Ge as fn(Vec<u64>) -> Comparator),
+            ("<=", Comparator::Le),
+            (">", Comparator::Gt),
+            ("<", Comparator::Lt),
+        ] {
+            if let Some(v) = part.strip_prefix(op) {
+                return Ok(vec![build(parse_numeric(v.trim())?)]);
+            }
+        }
+        if let Some(v) = part.strip_prefix('=') {
+            return Ok(vec![Comparator::Exact(v.trim().to_string())]);
+        }
+        if let Some(v) = part.strip_prefix('^') {
+            let lower = parse_numeric(v.trim())?;
+            // 第一个非0的部分不能变化，全为0时最后一个部分不能变化
+            let fixed = lower
+                .iter()
+                .position(|x| *x != 0)
+                .unwrap_or(lower.len() - 1);
+            return Ok(vec![
+                Comparator::Ge(lower.clone()),
+                Comparator::Lt(bump(&lower, fixed)),
+            ]);
+        }
+        if let Some(v) = part.strip_prefix('~') {
+            let lower = parse_numeric(v.trim())?;
+            let fixed = if lower.len() >= 2 { 1 } else { 0 };
+            return Ok(vec![
+                Comparator::Ge(lower.clone()),
+                Comparator::Lt(bump(&lower, fixed)),
+            ]);
+        }
+        return Ok(vec![Comparator::Exact(part.to_string())]);
+    }
+
+    /// 是否为确切的版本（例如`0.1.0`、`=0.1.0`）
+    pub fn is_exact(&self) -> bool {
+        return matches!(self.comparators.as_slice(), [Comparator::Exact(_)]);
+    }
+
+    /// 版本是否满足要求
+    pub fn matches(&self, version: &str) -> bool {
+        let numeric = parse_numeric(version).ok();
+        return self.comparators.iter().all(|c| match c {
+            Comparator::Any => true,
+            Comparator::Exact(v) => v == version,
+            Comparator::Ge(v) => numeric.as_ref().is_some_and(|n| cmp_numeric(n, v).is_ge()),
+            Comparator::Gt(v) => numeric.as_ref().is_some_and(|n| cmp_numeric(n, v).is_gt()),
+            Comparator::Le(v) => numeric.as_ref().is_some_and(|n| cmp_numeric(n, v).is_le()),
+            Comparator::Lt(v) => numeric.as_ref().is_some_and(|n| cmp_numeric(n, v).is_lt()),
+        });
+    }
+}
+
+/// 解析由`.`分隔的数字版本号
+fn parse_numeric(version: &str) -> Result<Vec<u64>, String> {
+    return version
+        .split('.')
+        .map(|x| {
+            x.parse::<u64>()
+                .map_err(|_| format!("'{}' is not a numeric version", version))
+        })
+        .collect();
+}
+
+/// 第`index`个部分加1，之后的部分清零
+fn bump(version: &[u64], index: usize) -> Vec<u64> {
+    let mut upper: Vec<u64> = version[..=index].to_vec();
+    upper[index] += 1;
+    return upper;
+}
+
+fn cmp_numeric(a: &[u64], b: &[u64]) -> Ordering {
+    let len = a.len().max(b.len());
+    for i in 0..len {
+        let x = a.get(i).copied().unwrap_or(0);
+        let y = b.get(i).copied().unwrap_or(0);
+        match x.cmp(&y) {
+            Ordering::Equal => continue,
+            other => return other,
+        }
+    }
+    return Ordering::Equal;
+}
+
+/// 比较两个版本，数字版本按数字比较，否则按字符串比较
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    return match (parse_numeric(a), parse_numeric(b)) {
+        (Ok(x), Ok(y)) => cmp_numeric(&x, &y).then_with(|| a.cmp(b)),
+        _ => a.cmp(b),
+    };
+}
+
+/// # 解析所有任务的依赖版本
+///
+/// 对每个被依赖的任务（按规范化的名称以及依赖固定的架构区分），汇总所有依赖方的版本要求：
+///
+/// - `Independent`模式下，每个版本要求单独解析为满足它的最高版本，同一个依赖的不同版本可以同时存在。
+///   版本范围没有任何版本满足时报错
+/// - `Strict`模式下，与`Independent`相同，但是不同的版本要求解析出多个版本时报错
+/// - `Unify`模式下，选择满足所有要求的最高版本，没有这样的版本时报错
+///
+/// 版本要求按解析后的结果比较，例如`0.1.0`与`=0.1.0`是相同的要求。报错时会列出每一个依赖方及其版本要求。
+/// 确切的版本没有对应的任务时保留原样，由调度器报告找不到依赖。
+pub fn resolve_dependencies(
+    tasks: &mut [(PathBuf, DADKTask)],
+    mode: ResolveMode,
+) -> Result<(), String> {
+    // (规范化的依赖名称, 固定的架构) -> [(依赖方, 依赖名称, 版本要求)]
+    let mut requests: BTreeMap<DepKey, Vec<(String, String, String)>> = BTreeMap::new();
+    for (_, task) in tasks.iter() {
+        for dep in task.depends.iter().filter(|d| d.is_task()) {
+            requests
+                .entry((normalize_name(&dep.name), dep.arch))
+                .or_default()
+                .push((task.name_version(), dep.name.clone(), dep.version.clone()));
+        }
+    }
+
+    // (依赖, 版本要求) -> 选中的版本
+    let mut resolved: BTreeMap<(DepKey, String), String> = BTreeMap::new();
+    for (key, reqs) in requests.iter() {
+        let name = reqs[0].1.as_str();
+        let mut distinct: Vec<(&str, VersionReq)> = Vec::new();
+        for (_, _, req) in reqs.iter() {
+            let parsed = VersionReq::parse(req)?;
+            if !distinct.iter().any(|(_, r)| r == &parsed) {
+                distinct.push((req, parsed));
+            }
+        }
+        let available: BTreeSet<&str> = tasks
+            .iter()
+            .filter(|(_, t)| normalize_name(&t.name) == key.0)
+            .filter(|(_, t)| key.1.map_or(true, |arch| t.target_arch.contains(&arch)))
+            .map(|(_, t)| t.version.as_str())
+            .collect();
+        let highest = |parsed: &[&VersionReq]| {
+            available
+                .iter()
+                .filter(|v| parsed.iter().all(|r| r.matches(v)))
+                .max_by(|a, b| compare_versions(a, b))
+                .map(|v| v.to_string())
+        };
+        let unsatisfied = |what: String| {
+            let available: Vec<&str> = available.iter().copied().collect();
+            format!(
+                "no version of dependency '{}' satisfies {} (available: [{}]):{}",
+                name,
+                what,
+                available.join(", "),
+                format_requesters(reqs)
+            )
+        };
+
+        match mode {
+            ResolveMode::Independent | ResolveMode::Strict => {
+                // 解析出的版本，没有对应任务的确切版本按原样计入
+                let mut versions = BTreeSet::new();
+                for (req, parsed) in distinct.iter() {
+                    match highest(&[parsed]) {
+                        Some(version) => {
+                            for (_, _, r) in reqs.iter().filter(|(_, _, r)| r == req) {
+                                resolved.insert((key.clone(), r.clone()), version.clone());
+                            }
+                            versions.insert(version);
+                        }
+                        None if parsed.is_exact() => {
+                            versions.insert(req.to_string());
+                        }
+                        None => return Err(unsatisfied(format!("'{}'", req))),
+                    }
+                }
+                if mode == ResolveMode::Strict && versions.len() > 1 {
+                    let versions: Vec<String> = versions.into_iter().collect();
+                    return Err(format!(
+                        "dependency '{}' resolves to multiple versions [{}], \
+                        use --dep-resolve independent to allow them to coexist:{}",
+                        name,
+                        versions.join(", "),
+                        format_requesters(reqs)
+                    ));
+                }
+            }
+            ResolveMode::Unify => {
+                let parsed: Vec<&VersionReq> = distinct.iter().map(|(_, r)| r).collect();
+                match highest(&parsed) {
+                    Some(version) => {
+                        if distinct.len() > 1 {
+                            info!("Dependency {} unified to version {}", name, version);
+                        }
+                        for (_, _, r) in reqs.iter() {
+                            resolved.insert((key.clone(), r.clone()), version.clone());
+                        }
+                    }
+                    None if distinct.len() == 1 && distinct[0].1.is_exact() => {}
+                    None => return Err(unsatisfied("all requirements".to_string())),
+                }
+            }
+        }
+    }
+
+    for (_, task) in tasks.iter_mut() {
+        for dep in task.depends.iter_mut().filter(|d| d.is_task()) {
+            let key = ((normalize_name(&dep.name), dep.arch), dep.version.clone());
+            if let Some(version) = resolved.get(&key) {
+                dep.version.clone_from(version);
+            }
+        }
+    }
+    return Ok(());
+}
+
+/// 规范化的依赖名称与依赖固定的架构
+type DepKey = (String, Option<TargetArch>);
+
+/// 规范化依赖名称，与调度器查找依赖时的规则相同（见[`DADKTask::name_version_uppercase`]）
+fn normalize_name(name: &str) -> String {
+    return DADKTask::name_version_uppercase(name, "");
+}
+
+fn format_requesters(reqs: &[(String, String, String)]) -> String {
+    let mut s = String::new();
+    for (requester, _, req) in reqs.iter() {
+        write!(s, "\n  {} requires {}", requester, req).unwrap();
+    }
+    return s;
+}
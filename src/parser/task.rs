@@ -1,8 +1,27 @@
-use std::path::PathBuf;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Component, Path, PathBuf},
+    sync::RwLock,
+};
 
+use log::warn;
 use serde::{Deserialize, Deserializer, Serialize};
 
-use crate::executor::source::{ArchiveSource, GitSource, LocalSource};
+use crate::{
+    executor::{
+        limits::{parse_size, ResourceLimits},
+        source::{
+            ArchiveSource, GitSource, HgSource, LocalSource, LocalSourceKind, OciSource,
+            ReleaseAssetSource, SvnSource,
+        },
+        strip::find_strip_tool,
+    },
+    parser::{condition::Condition, dotenv, interpolate, resolver::VersionReq},
+    utils::{hash::HashUtils, ignore::IgnoreMatcher},
+};
+
+/// 禁止安装到的DragonOS内的路径，为None时使用[`InstallConfig::DEFAULT_DENIED_PATHS`]
+static DENIED_INSTALL_PATHS: RwLock<Option<Vec<PathBuf>>> = RwLock::new(None);
 
 // 对于生成的包名和版本号，需要进行替换的字符。
 pub static NAME_VERSION_REPLACE_TABLE: [(&str, &str); 6] = [
@@ -14,6 +33,18 @@ pub static NAME_VERSION_REPLACE_TABLE: [(&str, &str); 6] = [
     ("*", "_"),
 ];
 
+/// 严格模式的环境变量，设置后没有设置`ARCH`、也没有指定目标架构时报错，而不是默认为`x86_64`
+pub const REQUIRE_ARCH_ENV_KEY: &str = "DADK_REQUIRE_ARCH";
+
+/// DADK保留的环境变量名前缀，任务的环境变量使用它时需要设置`allow_reserved`
+pub const RESERVED_ENV_PREFIX: &str = "DADK_";
+
+/// 环境变量`prepend`/`append`时默认的分隔符
+pub const DEFAULT_ENV_SEPARATOR: &str = ":";
+
+/// 输出保密的环境变量时代替值的字符串
+pub const SECRET_MASK: &str = "***";
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DADKTask {
     /// 包名
@@ -47,9 +78,84 @@ pub struct DADKTask {
 
     #[serde(default = "DADKTask::default_target_arch_vec")]
     pub target_arch: Vec<TargetArch>,
+
+    /// (可选) 按目标架构追加的环境变量，与`envs`中同名的环境变量会被覆盖
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub arch_envs: BTreeMap<TargetArch, Vec<TaskEnv>>,
+
+    /// (可选) 构建配置档（例如debug、release）的覆盖配置，在解析时合并到基础配置之上
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profile: BTreeMap<String, ProfileOverride>,
+
+    /// (可选) 任务源码目录以外的构建输入（文件或目录，相对于配置文件所在的目录）。
+    /// 它们的内容改变时，即使设置了`build_once`也会重新构建
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rerun_if_changed: Vec<PathBuf>,
+
+    /// (可选) 启用条件，例如`arch == "x86_64" && env.FEATURE_X == "1"`。
+    /// 调度前求值，结果为false时跳过该任务
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enabled_if: Option<String>,
+
+    /// (可选) 任务的标签，用于在命令行中按标签选择任务，例如`dadk clean --tag net`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+
+    /// (可选) 不继承的全局环境变量（工作区配置中的`global_envs`）的名称
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unset: Vec<String>,
+
+    /// (可选) 执行时加载的环境变量文件（dotenv格式），优先级低于`envs`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub env_files: Vec<EnvFile>,
+
+    /// 工作区配置中的环境变量文件，由解析器设置，不在配置文件中出现。优先级低于`global_envs`
+    #[serde(skip)]
+    pub global_env_files: Vec<EnvFile>,
+
+    /// 从工作区配置继承的全局环境变量（已经去掉了`unset`中的），由解析器设置，不在配置文件中出现。
+    /// 任务的`envs`中同名的环境变量优先
+    #[serde(skip)]
+    pub global_envs: Vec<TaskEnv>,
+
+    /// 工作区配置中依赖的环境变量的命名方式，由解析器设置，不在配置文件中出现
+    #[serde(skip)]
+    pub dep_envs: DepEnvConfig,
+
+    /// 当前生效的构建配置档，由解析器设置，不在配置文件中出现
+    #[serde(skip)]
+    pub active_profile: Option<String>,
+
+    /// 作为依赖按固定的目标架构（与当前目标架构不同）构建时的架构，由调度器设置，不在配置文件中出现
+    #[serde(skip)]
+    pub pinned_arch: Option<TargetArch>,
 }
 
 impl DADKTask {
+    /// 配置文件中合法的顶层字段（如果修改了结构体的字段，那一定要修改这里）
+    pub const KEYS: [&'static str; 20] = [
+        "name",
+        "version",
+        "description",
+        "rust_target",
+        "task_type",
+        "depends",
+        "build",
+        "install",
+        "clean",
+        "envs",
+        "build_once",
+        "install_once",
+        "target_arch",
+        "arch_envs",
+        "profile",
+        "rerun_if_changed",
+        "enabled_if",
+        "tags",
+        "unset",
+        "env_files",
+    ];
+
     #[allow(dead_code)]
     pub fn new(
         name: String,
@@ -80,17 +186,61 @@ impl DADKTask {
             build_once,
             install_once,
             target_arch: target_arch.unwrap_or_else(Self::default_target_arch_vec),
+            arch_envs: BTreeMap::new(),
+            profile: BTreeMap::new(),
+            rerun_if_changed: Vec::new(),
+            enabled_if: None,
+            tags: Vec::new(),
+            unset: Vec::new(),
+            global_envs: Vec::new(),
+            env_files: Vec::new(),
+            global_env_files: Vec::new(),
+            dep_envs: DepEnvConfig::default(),
+            active_profile: None,
+            pinned_arch: None,
         }
     }
 
     /// 默认的目标处理器架构
     ///
-    /// 从环境变量`ARCH`中获取，如果没有设置，则默认为`x86_64`
+    /// 从环境变量`ARCH`中获取，如果没有设置，则默认为`x86_64`。
+    /// 严格模式下需要先通过[`Self::try_default_target_arch`]检查
     pub fn default_target_arch() -> TargetArch {
         let s = std::env::var("ARCH").unwrap_or("x86_64".to_string());
         return TargetArch::try_from(s.as_str()).unwrap();
     }
 
+    /// # 获取默认的目标处理器架构
+    ///
+    /// 与[`Self::default_target_arch`]相同，但设置了环境变量`DADK_REQUIRE_ARCH`（严格模式）时，
+    /// 没有设置`ARCH`是错误，而不是默认为`x86_64`，便于在CI中发现遗漏的架构配置
+    pub fn try_default_target_arch() -> Result<TargetArch, String> {
+        return Self::resolve_default_target_arch(
+            std::env::var("ARCH").ok().as_deref(),
+            std::env::var(REQUIRE_ARCH_ENV_KEY).ok().as_deref(),
+        );
+    }
+
+    /// # 按环境变量`ARCH`和`DADK_REQUIRE_ARCH`的值计算默认的目标处理器架构
+    ///
+    /// `require_arch`非空且不为`0`时为严格模式
+    pub fn resolve_default_target_arch(
+        arch: Option<&str>,
+        require_arch: Option<&str>,
+    ) -> Result<TargetArch, String> {
+        if let Some(arch) = arch {
+            return TargetArch::try_from(arch);
+        }
+        let strict = require_arch.is_some_and(|v| !v.is_empty() && v != "0");
+        if strict {
+            return Err(format!(
+                "ARCH is not set and no target arch is specified ({} is set)",
+                REQUIRE_ARCH_ENV_KEY
+            ));
+        }
+        return Ok(TargetArch::X86_64);
+    }
+
     fn default_target_arch_vec() -> Vec<TargetArch> {
         vec![Self::default_target_arch()]
     }
@@ -106,14 +256,42 @@ impl DADKTask {
         self.build.validate()?;
         self.validate_build_type()?;
         self.install.validate()?;
+        self.warn_missing_strip_tool();
         self.clean.validate()?;
         self.validate_depends()?;
         self.validate_envs()?;
         self.validate_target_arch()?;
+        self.validate_arch_overrides()?;
+        self.validate_profiles()?;
+        self.validate_rerun_if_changed()?;
+        self.validate_tags()?;
+        self.validate_unset()?;
+        self.enabled_condition()?;
+        self.validate_interpolation()?;
 
         return Ok(());
     }
 
+    /// # 校验任务，并收集不影响使用的警告
+    ///
+    /// 错误与`validate()`返回的错误相同，警告不会导致解析失败
+    pub fn validate_with_warnings(&mut self) -> ValidationReport {
+        let mut report = ValidationReport::default();
+        if let Err(e) = self.validate() {
+            report.errors.push(e);
+        }
+        if self.description.trim().is_empty() {
+            report.warnings.push("description is empty".to_string());
+        }
+        if self.install_once && !self.install.has_install_target() {
+            report.warnings.push(
+                "install_once is set, but install.in_dragonos_path is empty and nothing will be installed"
+                    .to_string(),
+            );
+        }
+        return report;
+    }
+
     pub fn trim(&mut self) {
         self.name = self.name.trim().to_string();
         self.version = self.version.trim().to_string();
@@ -127,6 +305,172 @@ impl DADKTask {
         self.clean.trim();
         self.trim_depends();
         self.trim_envs();
+        for tag in self.tags.iter_mut() {
+            *tag = tag.trim().to_string();
+        }
+        for key in self.unset.iter_mut() {
+            *key = key.trim().to_string();
+        }
+        for env_file in self.env_files.iter_mut() {
+            env_file.trim();
+        }
+    }
+
+    /// # 规范化任务配置
+    ///
+    /// 对`depends`和各处的环境变量列表排序，使同一配置序列化的结果总是相同，便于比较差异。
+    /// 环境变量只按名称稳定排序，同名环境变量保持原来的先后顺序（后面的覆盖前面的）
+    pub fn canonicalize(&mut self) {
+        self.depends.sort();
+        let sort_envs = |envs: &mut Vec<TaskEnv>| envs.sort_by(|a, b| a.key.cmp(&b.key));
+        if let Some(envs) = &mut self.envs {
+            sort_envs(envs);
+        }
+        for envs in self.arch_envs.values_mut() {
+            sort_envs(envs);
+        }
+        for profile in self.profile.values_mut() {
+            if let Some(envs) = &mut profile.envs {
+                sort_envs(envs);
+            }
+        }
+    }
+
+    /// # 序列化为规范化的JSON配置
+    ///
+    /// 不修改任务本身，见[`DADKTask::canonicalize`]
+    pub fn to_canonical_json(&self) -> serde_json::Result<String> {
+        let mut task = self.clone();
+        task.canonicalize();
+        return serde_json::to_string_pretty(&task);
+    }
+
+    /// # 隐藏保密的环境变量的值
+    ///
+    /// 用于输出任务配置，见[`TaskEnv::masked`]
+    pub fn mask_secrets(&mut self) {
+        let mask = |envs: &mut Vec<TaskEnv>| {
+            for env in envs.iter_mut() {
+                *env = env.masked();
+            }
+        };
+        if let Some(envs) = &mut self.envs {
+            mask(envs);
+        }
+        for envs in self.arch_envs.values_mut() {
+            mask(envs);
+        }
+        for profile in self.profile.values_mut() {
+            if let Some(envs) = &mut profile.envs {
+                mask(envs);
+            }
+        }
+        mask(&mut self.global_envs);
+    }
+
+    /// 解析启用条件，没有设置时为None
+    pub fn enabled_condition(&self) -> Result<Option<Condition>, String> {
+        return self.enabled_if.as_deref().map(Condition::parse).transpose();
+    }
+
+    /// # 检查会在执行时进行变量插值的字段
+    ///
+    /// 包括构建命令、清理命令、环境变量的值和安装路径（以及它们按架构、按配置档的覆盖）
+    fn validate_interpolation(&self) -> Result<(), String> {
+        let mut values: Vec<String> = Vec::new();
+        let commands =
+            |c: &Option<BuildCommand>| c.as_ref().map(|c| c.commands()).unwrap_or_default();
+        values.extend(commands(&self.build.build_command));
+        for build in self.build.target_arch.values() {
+            values.extend(commands(&build.build_command));
+        }
+        values.extend(self.clean.commands());
+        let env_values =
+            |envs: &[TaskEnv]| envs.iter().map(|e| e.value.clone()).collect::<Vec<_>>();
+        values.extend(env_values(self.envs.as_deref().unwrap_or_default()));
+        for envs in self.arch_envs.values() {
+            values.extend(env_values(envs));
+        }
+        for profile in self.profile.values() {
+            values.extend(commands(&profile.build_command));
+            values.extend(env_values(profile.envs.as_deref().unwrap_or_default()));
+        }
+        let paths = std::iter::once(&self.install.in_dragonos_path).chain(
+            self.install
+                .target_arch
+                .values()
+                .map(|i| &i.in_dragonos_path),
+        );
+        for path in paths.flatten() {
+            values.push(path.to_string_lossy().to_string());
+        }
+        for dest in self.install.destinations.iter() {
+            values.push(dest.in_dragonos_path.to_string_lossy().to_string());
+        }
+        for entry in self.install.files.iter() {
+            values.push(entry.dest.to_string_lossy().to_string());
+        }
+        for symlink in self.install.symlinks.iter() {
+            values.push(symlink.link.to_string_lossy().to_string());
+            values.push(symlink.target.to_string_lossy().to_string());
+        }
+        for value in values {
+            for dep in interpolate::referenced_dependencies(&value)? {
+                if !self.depends.iter().any(|d| d.is_task() && d.name == dep) {
+                    return Err(format!(
+                        "${{dep:{}}} in {:?}: {} is not in depends",
+                        dep, value, dep
+                    ));
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    fn validate_rerun_if_changed(&self) -> Result<(), String> {
+        if self
+            .rerun_if_changed
+            .iter()
+            .any(|p| p.as_os_str().is_empty())
+        {
+            return Err("rerun_if_changed contains an empty path".to_string());
+        }
+        return Ok(());
+    }
+
+    fn validate_tags(&self) -> Result<(), String> {
+        if self.tags.iter().any(|t| t.is_empty()) {
+            return Err("tags contains an empty tag".to_string());
+        }
+        return Ok(());
+    }
+
+    fn validate_unset(&self) -> Result<(), String> {
+        if self.unset.iter().any(|k| k.is_empty()) {
+            return Err("unset contains an empty name".to_string());
+        }
+        return Ok(());
+    }
+
+    /// # 继承的全局环境变量的哈希值
+    ///
+    /// 没有继承任何全局环境变量时为None。全局环境变量改变时，设置了`build_once`的任务也会重新构建
+    pub fn global_envs_hash(&self) -> Option<String> {
+        if self.global_envs.is_empty() {
+            return None;
+        }
+        // 保密的值只有设置了`hash_secret`时才参与计算
+        let mut envs: Vec<TaskEnv> = self
+            .global_envs
+            .iter()
+            .map(|env| match env.hash_secret {
+                true => env.clone(),
+                false => env.masked(),
+            })
+            .collect();
+        envs.sort_by(|a, b| a.key.cmp(&b.key));
+        let json = serde_json::to_string(&envs).unwrap();
+        return Some(HashUtils::sha256_bytes(json.as_bytes()));
     }
 
     fn validate_depends(&self) -> Result<(), String> {
@@ -136,21 +480,98 @@ impl DADKTask {
         return Ok(());
     }
 
+    /// # 依赖的构建结果目录导出的环境变量
+    ///
+    /// 返回每个DADK任务依赖的环境变量名，按名称排序。两个依赖得到同一个名称时返回错误
+    pub fn dependency_env_keys(&self) -> Result<Vec<(String, &Dependency)>, String> {
+        let mut keys: BTreeMap<String, &Dependency> = BTreeMap::new();
+        for dep in self.depends.iter().filter(|d| d.is_task()) {
+            let key = self.dep_envs.env_key(dep);
+            if let Some(other) = keys.get(&key) {
+                return Err(format!(
+                    "dependency env {} is exported for both {} ({}) and {} ({}), set env_alias on one of them",
+                    key, other.name, other.version, dep.name, dep.version
+                ));
+            }
+            keys.insert(key, dep);
+        }
+        return Ok(keys.into_iter().collect());
+    }
+
     fn trim_depends(&mut self) {
         for depend in &mut self.depends {
             depend.trim();
         }
     }
 
+    /// # 校验任务的环境变量
+    ///
+    /// 同名、`target_arch`相同并且生效的阶段有重叠的环境变量只能`set`一次（`prepend`/`append`可以有多个），
+    /// 错误信息中包括两处定义的值。
+    /// 错误信息中包括任务名，解析配置文件时还会加上配置文件的路径
     fn validate_envs(&self) -> Result<(), String> {
+        let err = |e: String| format!("task {}: {}", self.name_version(), e);
         if let Some(envs) = &self.envs {
+            let mut defined: BTreeMap<(&str, Option<TargetArch>), Vec<&TaskEnv>> = BTreeMap::new();
             for env in envs {
-                env.validate()?;
+                env.validate().map_err(|e| err(format!("envs: {}", e)))?;
+                if let Some(arch) = env.target_arch {
+                    if !self.target_arch.contains(&arch) {
+                        let arch: &str = arch.into();
+                        return Err(err(format!(
+                            "envs: {} is set for target arch {} which is not in target_arch",
+                            env.key(),
+                            arch
+                        )));
+                    }
+                }
+                if !env.op.is_set() {
+                    continue;
+                }
+                let same_key = defined.entry((env.key(), env.target_arch)).or_default();
+                let previous = same_key.iter().find(|e| e.phases_overlap(env)).copied();
+                same_key.push(env);
+                if let Some(previous) = previous {
+                    let scope = match env.target_arch {
+                        Some(arch) => {
+                            let arch: &str = arch.into();
+                            format!("for target arch {}", arch)
+                        }
+                        None => "without target_arch".to_string(),
+                    };
+                    return Err(err(format!(
+                        "envs: {} is defined more than once {} ({:?} and {:?})",
+                        env.key(),
+                        scope,
+                        previous.display_value(),
+                        env.display_value()
+                    )));
+                }
             }
         }
+        for env_file in &self.env_files {
+            env_file.validate().map_err(err)?;
+        }
         return Ok(());
     }
 
+    /// 设置了`install.strip`但找不到目标架构的strip工具时，输出警告，安装时不会剥离符号
+    fn warn_missing_strip_tool(&self) {
+        if !self.install.strip.is_enabled() {
+            return;
+        }
+        for arch in &self.target_arch {
+            if find_strip_tool(*arch, None).is_none() {
+                warn!(
+                    "Task {}: install.strip is set, but no strip tool for {:?} is found (tried $STRIP, {}-strip, llvm-strip), binaries will be installed unstripped",
+                    self.name_version(),
+                    arch,
+                    arch.cross_prefix()
+                );
+            }
+        }
+    }
+
     fn validate_target_arch(&self) -> Result<(), String> {
         if self.target_arch.is_empty() {
             return Err("target_arch is empty".to_string());
@@ -164,23 +585,227 @@ impl DADKTask {
                 env.trim();
             }
         }
+        for envs in self.arch_envs.values_mut() {
+            for env in envs {
+                env.trim();
+            }
+        }
+        for profile in self.profile.values_mut() {
+            profile.trim();
+        }
+    }
+
+    /// 校验构建配置档，合并后的配置也要合法
+    fn validate_profiles(&self) -> Result<(), String> {
+        for name in self.profile.keys() {
+            if name.trim().is_empty() {
+                return Err("profile name is empty".to_string());
+            }
+            let merged = self.for_profile(name);
+            merged
+                .build
+                .validate()
+                .and_then(|_| merged.validate_build_type())
+                .and_then(|_| merged.validate_envs())
+                .map_err(|e| format!("profile {}: {}", name, e))?;
+        }
+        return Ok(());
+    }
+
+    /// # 获取在指定构建配置档下生效的任务配置
+    ///
+    /// 如果任务定义了该配置档，则把它合并到基础配置之上。返回的任务不再包含任何配置档，
+    /// 并且记录了当前生效的配置档（即使任务没有定义它），以便区分不同配置档的构建缓存
+    pub fn for_profile(&self, name: &str) -> DADKTask {
+        let mut task = self.clone();
+        let profile = std::mem::take(&mut task.profile).remove(name);
+        task.active_profile = Some(name.to_string());
+
+        if let Some(profile) = profile {
+            if profile.build_command.is_some() {
+                // 覆盖的构建命令取代cargo、cmake、autotools构建
+                task.build.build_command = profile.build_command;
+                task.build.cargo = None;
+                task.build.cmake = None;
+                task.build.autotools = None;
+            }
+            if profile.rust_target.is_some() {
+                task.rust_target = profile.rust_target;
+            }
+            if let Some(profile_envs) = profile.envs {
+                let envs = task.envs.get_or_insert_with(Vec::new);
+                for env in profile_envs {
+                    match envs.iter_mut().find(|e| {
+                        e.key == env.key && e.target_arch == env.target_arch && e.replaced_by(&env)
+                    }) {
+                        Some(e) => *e = env,
+                        None => envs.push(env),
+                    }
+                }
+            }
+        }
+        return task;
+    }
+
+    /// # 任务的构建缓存的键
+    ///
+    /// 有生效的构建配置档时，键中包含配置档的名称，切换配置档时不会复用其他配置档的构建结果。
+    /// 按固定架构构建的依赖，键中包含架构，不会与当前目标架构的构建结果混用
+    pub fn cache_key(&self) -> String {
+        let key = match &self.active_profile {
+            Some(profile) => format!("{}@{}", self.name_version(), profile),
+            None => self.name_version(),
+        };
+        match self.pinned_arch {
+            Some(arch) => {
+                let arch: &str = arch.into();
+                format!("{}-{}", key, arch)
+            }
+            None => key,
+        }
+    }
+
+    /// 校验按架构覆盖的配置
+    ///
+    /// 只能覆盖`target_arch`中列出的架构，并且合并后的配置也要合法
+    fn validate_arch_overrides(&self) -> Result<(), String> {
+        let arches = self
+            .build
+            .target_arch
+            .keys()
+            .chain(self.install.target_arch.keys())
+            .chain(self.arch_envs.keys());
+        for arch in arches {
+            if !self.target_arch.contains(arch) {
+                let arch: &str = (*arch).into();
+                return Err(format!(
+                    "override for target arch {} which is not in target_arch",
+                    arch
+                ));
+            }
+            if let Some(env) = self
+                .arch_envs
+                .get(arch)
+                .and_then(|envs| envs.iter().find(|e| e.target_arch.is_some()))
+            {
+                return Err(format!(
+                    "arch_envs: {}: target_arch is not allowed in arch_envs",
+                    env.key()
+                ));
+            }
+            let merged = self.for_arch(*arch);
+            merged.build.validate()?;
+            merged.validate_build_type()?;
+            merged.install.validate()?;
+            merged.validate_envs()?;
+        }
+        return Ok(());
+    }
+
+    /// # 获取在指定目标架构下生效的任务配置
+    ///
+    /// 把该架构的覆盖配置合并到基础配置之上，返回的任务不再包含任何按架构覆盖的配置
+    pub fn for_arch(&self, arch: TargetArch) -> DADKTask {
+        let mut task = self.clone();
+        let build_override = task.build.target_arch.remove(&arch);
+        let install_override = task.install.target_arch.remove(&arch);
+        let envs_override = task.arch_envs.remove(&arch);
+        task.build.target_arch.clear();
+        task.install.target_arch.clear();
+        task.arch_envs.clear();
+
+        // 只保留适用于该架构的环境变量，后面的同名环境变量覆盖前面的
+        if let Some(envs) = task.envs.take() {
+            let mut merged: Vec<TaskEnv> = Vec::new();
+            for mut env in envs.into_iter().filter(|e| e.applies_to(arch)) {
+                env.target_arch = None;
+                match merged
+                    .iter_mut()
+                    .find(|e| e.key == env.key && e.replaced_by(&env))
+                {
+                    Some(e) => *e = env,
+                    None => merged.push(env),
+                }
+            }
+            task.envs = Some(merged);
+        }
+
+        if let Some(build_override) = build_override {
+            if let Some(rust_target) = build_override.rust_target {
+                task.rust_target = Some(rust_target.trim().to_string());
+            }
+            if build_override.build_command.is_some() {
+                // 覆盖的构建命令取代cargo、cmake、autotools构建
+                task.build.build_command = build_override.build_command;
+                task.build.cargo = None;
+                task.build.cmake = None;
+                task.build.autotools = None;
+            }
+        }
+        if let Some(install_override) = install_override {
+            if install_override.in_dragonos_path.is_some() {
+                task.install.in_dragonos_path = install_override.in_dragonos_path;
+            }
+        }
+        if let Some(envs_override) = envs_override {
+            let envs = task.envs.get_or_insert_with(Vec::new);
+            for env in envs_override {
+                match envs
+                    .iter_mut()
+                    .find(|e| e.key == env.key && e.replaced_by(&env))
+                {
+                    Some(e) => *e = env,
+                    None => envs.push(env),
+                }
+            }
+        }
+        return task;
     }
 
     /// 验证任务类型与构建配置是否匹配
     fn validate_build_type(&self) -> Result<(), String> {
+        let methods = self.build.build_methods();
+        if methods.len() > 1 {
+            return Err(format!(
+                "only one of build_command, cargo, cmake and autotools can be set, got: {}",
+                methods.join(", ")
+            ));
+        }
         match &self.task_type {
-            TaskType::BuildFromSource(_) => {
-                if self.build.build_command.is_none() {
+            TaskType::BuildFromSource(_) | TaskType::KernelModule(_) => {
+                if !self.build.has_build() {
                     return Err("build command is empty".to_string());
                 }
             }
             TaskType::InstallFromPrebuilt(_) => {
-                if self.build.build_command.is_some() {
+                if self.build.has_build() {
                     return Err(
                         "build command should be empty when install from prebuilt".to_string()
                     );
                 }
             }
+            TaskType::NoSource => {
+                if self.build.has_build_helper() {
+                    return Err(
+                        "cargo, cmake and autotools build are not supported for script task"
+                            .to_string(),
+                    );
+                }
+                if self.build.build_command.is_none() {
+                    return Err("build command is empty for script task".to_string());
+                }
+            }
+            TaskType::Meta => {
+                if self.build.has_build() {
+                    return Err("build command should be empty for meta package".to_string());
+                }
+                if self.install.in_dragonos_path.is_some() {
+                    return Err("in_dragonos_path should be empty for meta package".to_string());
+                }
+                if !self.clean.commands().is_empty() {
+                    return Err("clean command should be empty for meta package".to_string());
+                }
+            }
         }
         return Ok(());
     }
@@ -205,12 +830,28 @@ impl DADKTask {
         return name_version;
     }
 
+    /// # 把本地源和环境变量文件的路径解析为绝对路径
+    ///
+    /// 相对路径相对于配置文件所在的目录`base_dir`解析，见[`LocalSource::resolve`]
+    pub fn resolve_local_paths(&mut self, base_dir: &Path) {
+        for env_file in self.env_files.iter_mut() {
+            env_file.resolve(base_dir);
+        }
+        match &mut self.task_type {
+            TaskType::BuildFromSource(cs) | TaskType::KernelModule(cs) => {
+                cs.resolve_local_paths(base_dir)
+            }
+            TaskType::InstallFromPrebuilt(ps) => ps.resolve_local_paths(base_dir),
+            TaskType::NoSource | TaskType::Meta => {}
+        }
+    }
+
     /// # 获取源码目录
     ///
-    /// 如果从本地路径构建，则返回本地路径。否则返回None。
+    /// 如果从本地路径构建，则返回本地路径（解析配置文件时已经解析为绝对路径）。否则返回None。
     pub fn source_path(&self) -> Option<PathBuf> {
         match &self.task_type {
-            TaskType::BuildFromSource(cs) => match cs {
+            TaskType::BuildFromSource(cs) | TaskType::KernelModule(cs) => match cs {
                 CodeSource::Local(lc) => {
                     return Some(lc.path().clone());
                 }
@@ -226,113 +867,1522 @@ impl DADKTask {
                     return None;
                 }
             },
+            TaskType::NoSource | TaskType::Meta => None,
         }
     }
 }
 
+/// # 任务校验结果
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ValidationReport {
+    /// 错误，存在错误时任务不能使用
+    pub errors: Vec<String>,
+    /// 警告，不影响任务的使用
+    pub warnings: Vec<String>,
+}
+
 /// @brief 构建配置
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct BuildConfig {
-    /// 构建命令
-    pub build_command: Option<String>,
+    /// 构建命令，可以是一条命令，也可以是按顺序执行的多条命令
+    pub build_command: Option<BuildCommand>,
+    /// 执行构建命令的工作目录，相对于任务的源码目录（不能是绝对路径，也不能跳出源码目录）。
+    /// 在配置文件中也可以写作`work_dir`
+    ///
+    /// 为None时，在源码目录下执行构建命令
+    #[serde(default, alias = "work_dir", skip_serializing_if = "Option::is_none")]
+    pub build_dir: Option<PathBuf>,
+    /// 构建的工作目录不存在时，是否自动创建
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub create: bool,
+    /// 构建前钩子，在构建命令之前执行，默认只在源码发生变化后执行
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pre_build: Option<BuildCommand>,
+    /// 是否每次构建都执行构建前钩子
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pre_build_always: bool,
+    /// 构建后钩子，在构建命令成功之后执行，失败时任务失败
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_build: Option<BuildCommand>,
+    /// 是否根据`DADK_JOBS`设置`MAKEFLAGS`和`CARGO_BUILD_JOBS`环境变量
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub inject_jobs: bool,
+    /// 使用cargo构建，由DADK调用cargo并把生成的二进制文件拷贝到构建结果目录。
+    /// 不能与`build_command`同时设置
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cargo: Option<CargoBuild>,
+    /// 使用cmake构建，由DADK完成配置、构建以及安装到构建结果目录。
+    /// 不能与`build_command`、`cargo`同时设置
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cmake: Option<CmakeBuild>,
+    /// 使用autotools构建，由DADK执行configure、make以及make install。
+    /// 不能与其他构建方式同时设置
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub autotools: Option<AutotoolsBuild>,
+    /// 构建输出中允许的最大警告数，超过时任务失败
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_warnings: Option<usize>,
+    /// 构建沙箱配置
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sandbox: Option<SandboxConfig>,
+    /// 构建进程的最大虚拟内存，例如`512M`、`2G`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_memory: Option<String>,
+    /// 构建进程的最大CPU时间（秒）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu_time_limit: Option<u64>,
+    /// 构建进程的nice值（-20到19），负值需要root权限
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nice: Option<i32>,
+    /// 构建命令退出码不为0时重新执行的次数，用于偶尔失败的构建（例如构建时下载依赖）。
+    /// 只针对构建命令本身，与获取源文件时的重试无关
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retries: Option<u32>,
+    /// 按目标架构覆盖的构建配置
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub target_arch: BTreeMap<TargetArch, BuildOverride>,
 }
 
 impl BuildConfig {
     #[allow(dead_code)]
     pub fn new(build_command: Option<String>) -> Self {
-        Self { build_command }
-    }
-
-    pub fn validate(&self) -> Result<(), String> {
-        return Ok(());
+        Self {
+            build_command: build_command.map(BuildCommand::Single),
+            build_dir: None,
+            create: false,
+            pre_build: None,
+            pre_build_always: false,
+            post_build: None,
+            inject_jobs: false,
+            cargo: None,
+            cmake: None,
+            autotools: None,
+            max_warnings: None,
+            sandbox: None,
+            max_memory: None,
+            cpu_time_limit: None,
+            nice: None,
+            retries: None,
+            target_arch: BTreeMap::new(),
+        }
     }
 
-    pub fn trim(&mut self) {
-        if let Some(build_command) = &mut self.build_command {
-            *build_command = build_command.trim().to_string();
+    /// 使用多条按顺序执行的构建命令创建构建配置
+    #[allow(dead_code)]
+    pub fn with_commands(commands: Vec<String>) -> Self {
+        Self {
+            build_command: Some(BuildCommand::Multiple(commands)),
+            build_dir: None,
+            create: false,
+            pre_build: None,
+            pre_build_always: false,
+            post_build: None,
+            inject_jobs: false,
+            cargo: None,
+            cmake: None,
+            autotools: None,
+            max_warnings: None,
+            sandbox: None,
+            max_memory: None,
+            cpu_time_limit: None,
+            nice: None,
+            retries: None,
+            target_arch: BTreeMap::new(),
         }
     }
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
-pub struct InstallConfig {
-    /// 安装到DragonOS内的目录
-    pub in_dragonos_path: Option<PathBuf>,
-}
 
-impl InstallConfig {
-    #[allow(dead_code)]
-    pub fn new(in_dragonos_path: Option<PathBuf>) -> Self {
-        Self { in_dragonos_path }
+    /// 任务自己设置的网络隔离选项，没有设置时为None
+    pub fn sandbox_network(&self) -> Option<bool> {
+        return self.sandbox.as_ref().and_then(|s| s.network);
     }
 
-    pub fn validate(&self) -> Result<(), String> {
-        if self.in_dragonos_path.is_none() {
-            return Ok(());
+    /// # 构建进程的资源限制
+    ///
+    /// 解析`max_memory`、`cpu_time_limit`和`nice`
+    pub fn resource_limits(&self) -> Result<ResourceLimits, String> {
+        let max_memory = match &self.max_memory {
+            Some(size) => {
+                let bytes =
+                    parse_size(size).map_err(|e| format!("BuildConfig: max_memory: {}", e))?;
+                if bytes == 0 {
+                    return Err("BuildConfig: max_memory should be greater than 0".to_string());
+                }
+                Some(bytes)
+            }
+            None => None,
+        };
+        if self.cpu_time_limit == Some(0) {
+            return Err("BuildConfig: cpu_time_limit should be greater than 0".to_string());
         }
-        if self.in_dragonos_path.as_ref().unwrap().is_relative() {
-            return Err("InstallConfig: in_dragonos_path should be an Absolute path".to_string());
+        if let Some(nice) = self.nice {
+            if !(-20..=19).contains(&nice) {
+                return Err(format!(
+                    "BuildConfig: nice should be between -20 and 19, got {}",
+                    nice
+                ));
+            }
         }
-        return Ok(());
+        return Ok(ResourceLimits {
+            max_memory,
+            cpu_time_limit: self.cpu_time_limit,
+            nice: self.nice,
+        });
     }
 
-    pub fn trim(&mut self) {}
-}
+    /// 是否配置了构建方式（构建命令、cargo、cmake或者autotools构建）
+    pub fn has_build(&self) -> bool {
+        return !self.build_methods().is_empty();
+    }
 
-/// # 清理配置
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
-pub struct CleanConfig {
-    /// 清理命令
-    pub clean_command: Option<String>,
-}
+    /// 配置了的构建方式的名称
+    fn build_methods(&self) -> Vec<&'static str> {
+        let mut methods = Vec::new();
+        if self.build_command.is_some() {
+            methods.push("build_command");
+        }
+        if self.cargo.is_some() {
+            methods.push("cargo");
+        }
+        if self.cmake.is_some() {
+            methods.push("cmake");
+        }
+        if self.autotools.is_some() {
+            methods.push("autotools");
+        }
+        return methods;
+    }
 
-impl CleanConfig {
-    #[allow(dead_code)]
-    pub fn new(clean_command: Option<String>) -> Self {
-        Self { clean_command }
+    /// 是否使用了由DADK执行的构建方式（cargo、cmake或者autotools）
+    pub fn has_build_helper(&self) -> bool {
+        return self.cargo.is_some() || self.cmake.is_some() || self.autotools.is_some();
     }
 
     pub fn validate(&self) -> Result<(), String> {
+        if !self.has_build() && (self.pre_build.is_some() || self.post_build.is_some()) {
+            return Err(
+                "BuildConfig: pre_build and post_build require a build_command or cargo"
+                    .to_string(),
+            );
+        }
+        if let Some(cargo) = &self.cargo {
+            cargo.validate()?;
+        }
+        if let Some(cmake) = &self.cmake {
+            cmake.validate()?;
+        }
+        if let Some(autotools) = &self.autotools {
+            autotools.validate()?;
+        }
+        self.resource_limits()?;
+        if let Some(build_dir) = &self.build_dir {
+            if build_dir.has_root() {
+                return Err(format!(
+                    "BuildConfig: build_dir should be a relative path, got {}",
+                    build_dir.display()
+                ));
+            }
+            // 逐级检查，`..`不能跳出源码目录
+            let mut depth: usize = 0;
+            for component in build_dir.components() {
+                match component {
+                    Component::ParentDir => {
+                        if depth == 0 {
+                            return Err(format!(
+                                "BuildConfig: build_dir {} escapes the task source directory",
+                                build_dir.display()
+                            ));
+                        }
+                        depth -= 1;
+                    }
+                    Component::Normal(_) => depth += 1,
+                    _ => {}
+                }
+            }
+        }
         return Ok(());
     }
 
     pub fn trim(&mut self) {
-        if let Some(clean_command) = &mut self.clean_command {
-            *clean_command = clean_command.trim().to_string();
+        trim_build_command(&mut self.build_command);
+        trim_build_command(&mut self.pre_build);
+        trim_build_command(&mut self.post_build);
+        if let Some(cargo) = &mut self.cargo {
+            cargo.trim();
+        }
+        if let Some(cmake) = &mut self.cmake {
+            cmake.trim();
+        }
+        if let Some(autotools) = &mut self.autotools {
+            autotools.trim();
+        }
+        for build_override in self.target_arch.values_mut() {
+            trim_build_command(&mut build_override.build_command);
         }
     }
 }
 
-/// @brief 依赖项
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Dependency {
-    pub name: String,
-    pub version: String,
+fn trim_build_command(build_command: &mut Option<BuildCommand>) {
+    if let Some(cmd) = build_command {
+        cmd.trim();
+        // 去除空命令后，如果没有剩下任何命令，则视为没有构建命令
+        if let BuildCommand::Multiple(commands) = cmd {
+            if commands.is_empty() {
+                *build_command = None;
+            }
+        }
+    }
 }
 
-impl Dependency {
-    #[allow(dead_code)]
-    pub fn new(name: String, version: String) -> Self {
-        Self { name, version }
-    }
+/// # cargo构建配置
+///
+/// 执行器会在构建工作目录下执行`cargo build`，使用任务的`rust_target`作为`--target`，
+/// 并把cargo的target目录放在任务数据目录下。构建完成后，`bins`中列出的二进制文件
+/// （为空时为输出目录下所有的可执行文件）会被拷贝到构建结果目录，供安装步骤使用。
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CargoBuild {
+    /// 启用的features
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub features: Vec<String>,
+    /// 是否禁用默认的features
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub no_default_features: bool,
+    /// 要构建并安装的二进制目标
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub bins: Vec<String>,
+    /// cargo的构建配置档，默认为`release`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    /// 要构建的工作区成员（`-p`），为空时构建默认成员
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub workspace_members: Vec<String>,
+}
+
+impl CargoBuild {
+    pub const DEFAULT_PROFILE: &'static str = "release";
 
     pub fn validate(&self) -> Result<(), String> {
-        if self.name.is_empty() {
-            return Err("name is empty".to_string());
+        let lists = [
+            ("features", &self.features),
+            ("bins", &self.bins),
+            ("workspace_members", &self.workspace_members),
+        ];
+        for (field, list) in lists {
+            if list.iter().any(|x| x.is_empty()) {
+                return Err(format!("CargoBuild: {} contains an empty item", field));
+            }
         }
-        if self.version.is_empty() {
-            return Err("version is empty".to_string());
+        if self.profile.as_deref() == Some("") {
+            return Err("CargoBuild: profile is empty".to_string());
         }
         return Ok(());
     }
 
     pub fn trim(&mut self) {
-        self.name = self.name.trim().to_string();
-        self.version = self.version.trim().to_string();
+        for list in [
+            &mut self.features,
+            &mut self.bins,
+            &mut self.workspace_members,
+        ] {
+            for item in list.iter_mut() {
+                *item = item.trim().to_string();
+            }
+        }
+        if let Some(profile) = &self.profile {
+            self.profile = Some(profile.trim().to_string());
+        }
     }
 
-    pub fn name_version(&self) -> String {
-        return format!("{}-{}", self.name, self.version);
+    /// 使用的cargo构建配置档
+    pub fn profile(&self) -> &str {
+        return self.profile.as_deref().unwrap_or(Self::DEFAULT_PROFILE);
+    }
+
+    /// 构建配置档在target目录下对应的输出目录名
+    pub fn profile_dir(&self) -> &str {
+        return match self.profile() {
+            "dev" | "test" => "debug",
+            "bench" => "release",
+            profile => profile,
+        };
+    }
+}
+
+/// # cmake构建配置
+///
+/// 执行器根据目标架构生成工具链文件，在任务数据目录下的固定目录中完成配置和构建，
+/// 构建时使用任务的并行任务数，最后以构建结果目录为`DESTDIR`执行安装。
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CmakeBuild {
+    /// 额外的`-D`定义
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub defines: BTreeMap<String, String>,
+    /// cmake的生成器（`-G`），为None时使用cmake的默认生成器
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub generator: Option<String>,
+    /// `CMAKE_BUILD_TYPE`，默认为`Release`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build_type: Option<String>,
+    /// `CMAKE_INSTALL_PREFIX`，默认为`/`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub install_prefix: Option<String>,
+}
+
+impl CmakeBuild {
+    pub const DEFAULT_BUILD_TYPE: &'static str = "Release";
+    pub const DEFAULT_INSTALL_PREFIX: &'static str = "/";
+
+    pub fn validate(&self) -> Result<(), String> {
+        for key in self.defines.keys() {
+            if key.is_empty() || key.contains(char::is_whitespace) || key.contains('=') {
+                return Err(format!("CmakeBuild: invalid define name {:?}", key));
+            }
+        }
+        if self.generator.as_deref() == Some("") {
+            return Err("CmakeBuild: generator is empty".to_string());
+        }
+        if self.build_type.as_deref() == Some("") {
+            return Err("CmakeBuild: build_type is empty".to_string());
+        }
+        if let Some(prefix) = &self.install_prefix {
+            if !prefix.starts_with('/') {
+                return Err(format!(
+                    "CmakeBuild: install_prefix should be an absolute path, got {:?}",
+                    prefix
+                ));
+            }
+        }
+        return Ok(());
+    }
+
+    pub fn trim(&mut self) {
+        self.defines = std::mem::take(&mut self.defines)
+            .into_iter()
+            .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+            .collect();
+        for field in [
+            &mut self.generator,
+            &mut self.build_type,
+            &mut self.install_prefix,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            *field = field.trim().to_string();
+        }
+    }
+
+    pub fn build_type(&self) -> &str {
+        return self
+            .build_type
+            .as_deref()
+            .unwrap_or(Self::DEFAULT_BUILD_TYPE);
+    }
+
+    pub fn install_prefix(&self) -> &str {
+        return self
+            .install_prefix
+            .as_deref()
+            .unwrap_or(Self::DEFAULT_INSTALL_PREFIX);
+    }
+}
+
+/// # autotools构建配置
+///
+/// 执行器在构建工作目录下依次执行：
+///
+/// 1. `configure`不存在但`configure.ac`存在时，执行`autoreconf -fi`
+/// 2. `./configure --host=<目标架构的三元组> --prefix=/usr <configure_args>`
+/// 3. `make -j$DADK_JOBS <make_targets>`
+/// 4. `make DESTDIR=<构建结果目录> <install_target>`
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AutotoolsBuild {
+    /// 传给configure的额外参数
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub configure_args: Vec<String>,
+    /// 构建的make目标，为空时使用默认目标
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub make_targets: Vec<String>,
+    /// 安装的make目标，默认为`install`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub install_target: Option<String>,
+}
+
+impl AutotoolsBuild {
+    pub const DEFAULT_INSTALL_TARGET: &'static str = "install";
+    pub const DEFAULT_PREFIX: &'static str = "/usr";
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.configure_args.iter().any(|x| x.is_empty()) {
+            return Err("AutotoolsBuild: configure_args contains an empty item".to_string());
+        }
+        if self.make_targets.iter().any(|x| x.is_empty()) {
+            return Err("AutotoolsBuild: make_targets contains an empty item".to_string());
+        }
+        if self.install_target.as_deref() == Some("") {
+            return Err("AutotoolsBuild: install_target is empty".to_string());
+        }
+        return Ok(());
+    }
+
+    pub fn trim(&mut self) {
+        for list in [&mut self.configure_args, &mut self.make_targets] {
+            for item in list.iter_mut() {
+                *item = item.trim().to_string();
+            }
+        }
+        if let Some(target) = &self.install_target {
+            self.install_target = Some(target.trim().to_string());
+        }
+    }
+
+    pub fn install_target(&self) -> &str {
+        return self
+            .install_target
+            .as_deref()
+            .unwrap_or(Self::DEFAULT_INSTALL_TARGET);
+    }
+}
+
+/// # 构建沙箱配置
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SandboxConfig {
+    /// 是否在没有网络的沙箱中执行构建命令和构建钩子。
+    /// 不设置时使用全局的默认值（`--sandbox-network`）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub network: Option<bool>,
+}
+
+/// # 某个目标架构下的构建配置覆盖
+///
+/// 未设置的字段使用基础构建配置中的值
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct BuildOverride {
+    /// 构建命令
+    #[serde(default)]
+    pub build_command: Option<BuildCommand>,
+    /// 该架构下使用的rust target，覆盖任务的`rust_target`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rust_target: Option<String>,
+}
+
+/// # 构建命令
+///
+/// 在配置文件中可以写作一个字符串，也可以写作一个字符串数组。
+/// 写作数组时，各条命令在同一个工作目录、同样的环境变量下按顺序执行，遇到第一条失败的命令即停止。
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum BuildCommand {
+    /// 一条命令
+    Single(String),
+    /// 按顺序执行的多条命令
+    Multiple(Vec<String>),
+}
+
+impl BuildCommand {
+    /// 获取要按顺序执行的所有命令
+    pub fn commands(&self) -> Vec<String> {
+        match self {
+            BuildCommand::Single(cmd) => vec![cmd.clone()],
+            BuildCommand::Multiple(cmds) => cmds.clone(),
+        }
+    }
+
+    /// 去除每条命令首尾的空白字符，并丢弃空命令（仅多条命令时）
+    pub fn trim(&mut self) {
+        match self {
+            BuildCommand::Single(cmd) => *cmd = cmd.trim().to_string(),
+            BuildCommand::Multiple(cmds) => {
+                *cmds = cmds
+                    .iter()
+                    .map(|c| c.trim().to_string())
+                    .filter(|c| !c.is_empty())
+                    .collect();
+            }
+        }
+    }
+}
+
+/// 按glob模式设置的文件权限
+pub type FileModeRules = Vec<(IgnoreMatcher, u32)>;
+
+/// # 安装时选择文件的规则
+///
+/// 设置了`include`时只安装被它匹配的文件，再从中去掉被`exclude`匹配的文件
+#[derive(Debug, Clone, Default)]
+pub struct InstallFilter {
+    include: Option<IgnoreMatcher>,
+    exclude: Option<IgnoreMatcher>,
+}
+
+impl InstallFilter {
+    /// 是否没有任何规则（安装所有文件）
+    pub fn is_empty(&self) -> bool {
+        return self.include.is_none() && self.exclude.is_none();
+    }
+
+    /// 只保留`exclude`的规则，用于`install.files`中明确列出的目录
+    pub fn exclude_only(&self) -> Self {
+        return Self {
+            include: None,
+            exclude: self.exclude.clone(),
+        };
+    }
+
+    /// 判断文件（相对于被安装的目录的路径）是否需要安装
+    pub fn selects(&self, relative: &Path) -> bool {
+        if let Some(include) = &self.include {
+            if !include.is_ignored(relative) {
+                return false;
+            }
+        }
+        return !self
+            .exclude
+            .as_ref()
+            .is_some_and(|exclude| exclude.is_ignored(relative));
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct InstallConfig {
+    /// 安装到DragonOS内的目录
+    pub in_dragonos_path: Option<PathBuf>,
+    /// 把构建结果中的子目录分别安装到DragonOS内的不同目录，在拷贝整个构建结果目录（如果设置了`in_dragonos_path`）之后按顺序安装
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub destinations: Vec<InstallDestination>,
+    /// 安装的文件的权限，八进制字符串，例如`"0755"`。不设置时保留构建结果中的权限
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<String>,
+    /// 按glob模式（相对于构建结果目录）设置安装的文件的权限，优先于`mode`。
+    /// 多个模式匹配同一个文件时，使用最长的模式
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub modes: BTreeMap<String, String>,
+    /// 安装时是否跟随符号链接：false（默认）时原样重建符号链接，true时复制链接指向的内容
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub follow_symlinks: bool,
+    /// 只安装匹配这些glob模式（相对于被安装的目录）的文件和目录，为空时安装所有文件
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+    /// 不安装匹配这些glob模式的文件和目录，与`include`同时设置时在`include`选中的文件中排除
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<String>,
+    /// 安装后是否剥离ELF可执行文件和共享库中的符号，使用目标架构的strip工具。
+    /// 可以是`true`/`false`，也可以是`"debug"`（只剥离调试信息）或`"all"`（与`true`相同）
+    #[serde(default, skip_serializing_if = "StripMode::is_none")]
+    pub strip: StripMode,
+    /// 剥离符号失败（或者找不到strip工具）时是否报错，默认只输出警告
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub strict_strip: bool,
+    /// 逐个安装的文件，在拷贝整个构建结果目录（如果设置了`in_dragonos_path`）之后按顺序安装
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<InstallEntry>,
+    /// 在DragonOS内创建的符号链接，在安装完文件之后按顺序创建
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub symlinks: Vec<InstallSymlink>,
+    /// 安装后钩子，在拷贝文件和创建符号链接之后在主机上执行，失败时安装失败
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_install: Option<BuildCommand>,
+    /// 允许覆盖其他任务安装的内容不同的文件的glob模式（DragonOS内的绝对路径），匹配目录时包括其中所有文件。
+    /// 覆盖的任务需要（直接或间接）依赖先安装该文件的任务，否则安装顺序不确定，安装失败
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allow_overwrite: Vec<String>,
+    /// 安装的文档（`share/doc`、`share/man`、`share/info`下的文件）的处理方式：
+    /// `"keep"`保留，`"compress"`用gzip压缩，`"strip"`删除。不设置时使用命令行中的`--docs`（默认为`"keep"`）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub docs: Option<DocsPolicy>,
+    /// 保留的语言（`share/locale`下的目录，例如`"zh_CN"`，`"zh"`同时匹配`zh_CN`、`zh_TW`等），
+    /// 其他语言的翻译文件不会被安装。不设置时使用命令行中的`--keep-locales`（默认保留所有语言）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locales: Option<Vec<String>>,
+    /// 按目标架构覆盖的安装配置
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub target_arch: BTreeMap<TargetArch, InstallOverride>,
+}
+
+impl InstallConfig {
+    #[allow(dead_code)]
+    pub fn new(in_dragonos_path: Option<PathBuf>) -> Self {
+        Self {
+            in_dragonos_path,
+            destinations: Vec::new(),
+            mode: None,
+            modes: BTreeMap::new(),
+            follow_symlinks: false,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            strip: StripMode::None,
+            strict_strip: false,
+            files: Vec::new(),
+            symlinks: Vec::new(),
+            post_install: None,
+            allow_overwrite: Vec::new(),
+            docs: None,
+            locales: None,
+            target_arch: BTreeMap::new(),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        self.file_modes()?;
+        self.install_filter()?;
+        for (i, entry) in self.files.iter().enumerate() {
+            entry
+                .validate()
+                .map_err(|e| format!("InstallConfig: files[{}]: {}", i, e))?;
+        }
+        for (i, symlink) in self.symlinks.iter().enumerate() {
+            symlink
+                .validate()
+                .map_err(|e| format!("InstallConfig: symlinks[{}]: {}", i, e))?;
+        }
+        self.validate_destinations()?;
+        self.overwrite_matcher()?;
+        if let Some(locales) = &self.locales {
+            for locale in locales.iter() {
+                if locale.trim().is_empty() || locale.contains('/') {
+                    return Err(format!(
+                        "InstallConfig: locales: invalid locale {:?}",
+                        locale
+                    ));
+                }
+            }
+        }
+        if self.in_dragonos_path.is_none() {
+            return Ok(());
+        }
+        if self.in_dragonos_path.as_ref().unwrap().is_relative() {
+            return Err("InstallConfig: in_dragonos_path should be an Absolute path".to_string());
+        }
+        Self::validate_dragonos_path(self.in_dragonos_path.as_ref().unwrap(), true)
+            .map_err(|e| format!("InstallConfig: in_dragonos_path: {}", e))?;
+        return Ok(());
+    }
+
+    /// 默认禁止安装到的DragonOS内的路径
+    pub const DEFAULT_DENIED_PATHS: [&'static str; 4] = ["/", "/proc", "/sys", "/dev"];
+
+    /// # 设置禁止安装到的DragonOS内的路径（命令行中的`--deny-install-path`）
+    ///
+    /// 替换默认的[`Self::DEFAULT_DENIED_PATHS`]
+    pub fn set_denied_paths(paths: Vec<PathBuf>) {
+        *DENIED_INSTALL_PATHS.write().unwrap() = Some(paths);
+    }
+
+    /// 禁止安装到的DragonOS内的路径
+    pub fn denied_paths() -> Vec<PathBuf> {
+        return DENIED_INSTALL_PATHS
+            .read()
+            .unwrap()
+            .clone()
+            .unwrap_or_else(|| {
+                Self::DEFAULT_DENIED_PATHS
+                    .iter()
+                    .map(PathBuf::from)
+                    .collect()
+            });
+    }
+
+    /// # 校验安装到DragonOS内的路径
+    ///
+    /// 路径必须是绝对路径，不能包含`.`、`..`和空的部分（`//`），也不能是禁止安装到的路径或者其下的路径。
+    /// `/`只禁止替换根目录本身：`into_dir`为true时（把构建结果目录合并到这个目录中）允许安装到`/`
+    pub fn validate_dragonos_path(path: &Path, into_dir: bool) -> Result<(), String> {
+        let s = path.to_string_lossy();
+        if !path.has_root() {
+            return Err(format!("{:?} should be an absolute path", s));
+        }
+        let parts: Vec<&str> = s[1..].split('/').collect();
+        for (i, part) in parts.iter().enumerate() {
+            match *part {
+                // 允许结尾的`/`
+                "" if i + 1 == parts.len() => {}
+                "" => return Err(format!("{:?} contains an empty component", s)),
+                "." | ".." => return Err(format!("{:?} contains '{}'", s, part)),
+                _ => {}
+            }
+        }
+        let normalized: PathBuf = path.components().collect();
+        for denied in Self::denied_paths() {
+            let denied: PathBuf = denied.components().collect();
+            let is_root = denied.parent().is_none();
+            if (normalized == denied && !(is_root && into_dir))
+                || (!is_root && normalized.starts_with(&denied))
+            {
+                return Err(format!(
+                    "{:?} is a reserved path ({}), refusing to install there",
+                    s,
+                    denied.display()
+                ));
+            }
+        }
+        return Ok(());
+    }
+
+    /// # 校验`destinations`
+    ///
+    /// 同一个任务的安装目录（包括`in_dragonos_path`）不能相同，也不能互相包含
+    fn validate_destinations(&self) -> Result<(), String> {
+        let mut dests: Vec<(String, &Path)> = Vec::new();
+        if let Some(path) = &self.in_dragonos_path {
+            dests.push((format!("in_dragonos_path ({})", path.display()), path));
+        }
+        for (i, dest) in self.destinations.iter().enumerate() {
+            dest.validate()
+                .map_err(|e| format!("InstallConfig: destinations[{}]: {}", i, e))?;
+            dests.push((
+                format!(
+                    "destinations[{}] ({} -> {})",
+                    i,
+                    dest.src_subdir.display(),
+                    dest.in_dragonos_path.display()
+                ),
+                &dest.in_dragonos_path,
+            ));
+        }
+        for (i, (a_name, a)) in dests.iter().enumerate() {
+            for (b_name, b) in dests.iter().skip(i + 1) {
+                if a.starts_with(b) || b.starts_with(a) {
+                    return Err(format!(
+                        "InstallConfig: {} and {} install to overlapping paths",
+                        a_name, b_name
+                    ));
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    /// # 解析安装的文件的权限配置
+    ///
+    /// 返回默认权限，以及按模式长度从长到短排列的`(glob匹配器, 权限)`列表
+    pub fn file_modes(&self) -> Result<(Option<u32>, FileModeRules), String> {
+        let mode = match &self.mode {
+            Some(mode) => {
+                Some(Self::parse_mode(mode).map_err(|e| format!("InstallConfig: mode: {}", e))?)
+            }
+            None => None,
+        };
+        let mut patterns: Vec<(&String, &String)> = self.modes.iter().collect();
+        patterns.sort_by_key(|(pattern, _)| std::cmp::Reverse(pattern.len()));
+        let mut rules = Vec::new();
+        for (pattern, mode) in patterns {
+            let matcher = IgnoreMatcher::new(&[pattern])
+                .map_err(|e| format!("InstallConfig: modes: {}", e))?;
+            let mode = Self::parse_mode(mode)
+                .map_err(|e| format!("InstallConfig: modes[{:?}]: {}", pattern, e))?;
+            rules.push((matcher, mode));
+        }
+        return Ok((mode, rules));
+    }
+
+    /// # 解析安装时选择文件的规则
+    ///
+    /// 应用于拷贝整个构建结果目录。`files`中明确列出的目录只应用`exclude`
+    pub fn install_filter(&self) -> Result<InstallFilter, String> {
+        let matcher = |patterns: &[String], field: &str| {
+            if patterns.is_empty() {
+                return Ok(None);
+            }
+            return IgnoreMatcher::new(patterns)
+                .map(Some)
+                .map_err(|e| format!("InstallConfig: {}: {}", field, e));
+        };
+        return Ok(InstallFilter {
+            include: matcher(&self.include, "include")?,
+            exclude: matcher(&self.exclude, "exclude")?,
+        });
+    }
+
+    /// # 解析`allow_overwrite`
+    ///
+    /// 返回的匹配器匹配去掉开头的`/`之后的DragonOS内的路径
+    pub fn overwrite_matcher(&self) -> Result<IgnoreMatcher, String> {
+        for pattern in self.allow_overwrite.iter() {
+            if !pattern.trim().starts_with('/') {
+                return Err(format!(
+                    "InstallConfig: allow_overwrite: {:?} should be an absolute path",
+                    pattern
+                ));
+            }
+        }
+        return IgnoreMatcher::new(&self.allow_overwrite)
+            .map_err(|e| format!("InstallConfig: allow_overwrite: {}", e));
+    }
+
+    /// 解析八进制的文件权限，例如`0755`、`755`或`0o755`
+    pub fn parse_mode(mode: &str) -> Result<u32, String> {
+        let digits = mode.trim();
+        let digits = digits.strip_prefix("0o").unwrap_or(digits);
+        if digits.is_empty() || !digits.chars().all(|c| ('0'..='7').contains(&c)) {
+            return Err(format!("invalid octal file mode {:?}", mode));
+        }
+        let value = u32::from_str_radix(digits, 8)
+            .map_err(|_| format!("invalid octal file mode {:?}", mode))?;
+        if value > 0o7777 {
+            return Err(format!("file mode {:?} is out of range", mode));
+        }
+        return Ok(value);
+    }
+
+    pub fn trim(&mut self) {
+        trim_build_command(&mut self.post_install);
+    }
+
+    /// 是否配置了安装位置（`in_dragonos_path`、按架构覆盖的`in_dragonos_path`、`destinations`、`files`或`symlinks`）
+    pub fn has_install_target(&self) -> bool {
+        return self.in_dragonos_path.is_some()
+            || self
+                .target_arch
+                .values()
+                .any(|o| o.in_dragonos_path.is_some())
+            || !self.destinations.is_empty()
+            || !self.files.is_empty()
+            || !self.symlinks.is_empty();
+    }
+}
+
+/// # 安装时剥离符号的方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StripMode {
+    /// 不剥离
+    #[default]
+    None,
+    /// 只剥离调试信息（`--strip-debug`）
+    Debug,
+    /// 剥离所有符号（`--strip-all`）
+    All,
+}
+
+impl StripMode {
+    pub fn is_none(&self) -> bool {
+        return *self == StripMode::None;
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        return !self.is_none();
+    }
+
+    /// 传给strip工具的参数
+    pub fn args(&self) -> &'static [&'static str] {
+        match self {
+            StripMode::None => &[],
+            StripMode::Debug => &["--strip-debug"],
+            StripMode::All => &["--strip-all"],
+        }
+    }
+}
+
+impl Serialize for StripMode {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            StripMode::None => serializer.serialize_bool(false),
+            StripMode::Debug => serializer.serialize_str("debug"),
+            StripMode::All => serializer.serialize_str("all"),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for StripMode {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Bool(bool),
+            Str(String),
+        }
+        return match Raw::deserialize(deserializer)? {
+            Raw::Bool(false) => Ok(StripMode::None),
+            Raw::Bool(true) => Ok(StripMode::All),
+            Raw::Str(s) => match s.trim() {
+                "debug" => Ok(StripMode::Debug),
+                "all" => Ok(StripMode::All),
+                _ => Err(serde::de::Error::custom(format!(
+                    "invalid strip mode {:?}, expected true, false, \"debug\" or \"all\"",
+                    s
+                ))),
+            },
+        };
+    }
+}
+
+/// # 安装时文档的处理方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DocsPolicy {
+    /// 原样安装
+    #[default]
+    Keep,
+    /// 用gzip压缩
+    Compress,
+    /// 不安装
+    Strip,
+}
+
+impl TryFrom<&str> for DocsPolicy {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "keep" => Ok(DocsPolicy::Keep),
+            "compress" => Ok(DocsPolicy::Compress),
+            "strip" => Ok(DocsPolicy::Strip),
+            _ => Err(format!(
+                "Unknown docs policy: {}, expected keep, compress or strip",
+                value
+            )),
+        }
+    }
+}
+
+/// # 安装目录
+///
+/// 把构建结果中的一个子目录安装到DragonOS内的一个目录
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct InstallDestination {
+    /// 构建结果中的子目录，相对于任务的构建结果目录
+    pub src_subdir: PathBuf,
+    /// 安装到DragonOS内的目录（绝对路径），子目录中的内容会被拷贝到这个目录下
+    pub in_dragonos_path: PathBuf,
+}
+
+impl InstallDestination {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.src_subdir.as_os_str().is_empty() || self.src_subdir.has_root() {
+            return Err(format!(
+                "src_subdir should be a relative path in the build output, got {:?}",
+                self.src_subdir
+            ));
+        }
+        if self
+            .src_subdir
+            .components()
+            .any(|c| matches!(c, Component::ParentDir))
+        {
+            return Err(format!(
+                "src_subdir {} escapes the build output",
+                self.src_subdir.display()
+            ));
+        }
+        if !self.in_dragonos_path.has_root() {
+            return Err(format!(
+                "in_dragonos_path should be an absolute path, got {:?}",
+                self.in_dragonos_path
+            ));
+        }
+        InstallConfig::validate_dragonos_path(&self.in_dragonos_path, true)
+            .map_err(|e| format!("in_dragonos_path: {}", e))?;
+        return Ok(());
+    }
+}
+
+/// # 逐个安装的文件
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct InstallEntry {
+    /// 源文件或目录，相对于任务的构建结果目录
+    pub src: PathBuf,
+    /// 安装到DragonOS内的路径（绝对路径），源文件会被拷贝为这个路径
+    pub dest: PathBuf,
+    /// 安装的文件的权限，八进制字符串，例如`"0755"`，可以包含setuid/setgid/sticky位，例如`"4755"`。
+    /// 不设置时保留构建结果中的权限
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<String>,
+    /// 安装的文件在DragonOS内的属主uid。不设置时不修改
+    ///
+    /// DADK不是以root身份运行时，属主记录在属主数据库中，由构建根文件系统镜像的工具应用
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uid: Option<u32>,
+    /// 安装的文件在DragonOS内的属组gid。不设置时不修改
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gid: Option<u32>,
+}
+
+impl InstallEntry {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.src.as_os_str().is_empty() || self.src.has_root() {
+            return Err(format!(
+                "src should be a relative path in the build output, got {:?}",
+                self.src
+            ));
+        }
+        if self
+            .src
+            .components()
+            .any(|c| matches!(c, Component::ParentDir))
+        {
+            return Err(format!(
+                "src {} escapes the build output",
+                self.src.display()
+            ));
+        }
+        if !self.dest.has_root() {
+            return Err(format!(
+                "dest should be an absolute path, got {:?}",
+                self.dest
+            ));
+        }
+        InstallConfig::validate_dragonos_path(&self.dest, false)
+            .map_err(|e| format!("dest: {}", e))?;
+        self.mode()?;
+        return Ok(());
+    }
+
+    /// 解析文件权限
+    pub fn mode(&self) -> Result<Option<u32>, String> {
+        return self
+            .mode
+            .as_deref()
+            .map(|mode| InstallConfig::parse_mode(mode).map_err(|e| format!("mode: {}", e)))
+            .transpose();
+    }
+}
+
+/// # 安装时创建的符号链接
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct InstallSymlink {
+    /// 符号链接在DragonOS内的路径（绝对路径）
+    pub link: PathBuf,
+    /// 符号链接指向的路径，可以是绝对路径（DragonOS内的路径）或相对于链接所在目录的路径
+    pub target: PathBuf,
+    /// `link`已经存在且不是指向`target`的符号链接时，是否替换它。默认安装失败
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub force: bool,
+}
+
+impl InstallSymlink {
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.link.has_root() {
+            return Err(format!(
+                "link should be an absolute path, got {:?}",
+                self.link
+            ));
+        }
+        if self.link.parent().is_none() {
+            return Err("link should not be the root directory".to_string());
+        }
+        InstallConfig::validate_dragonos_path(&self.link, false)
+            .map_err(|e| format!("link: {}", e))?;
+        if self.target.as_os_str().is_empty() {
+            return Err(format!("target of {} is empty", self.link.display()));
+        }
+        return Ok(());
+    }
+}
+
+/// # 构建配置档的覆盖配置
+///
+/// 未设置的字段使用基础配置中的值，`envs`按名称覆盖或追加到基础配置的环境变量中
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProfileOverride {
+    /// 构建命令
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build_command: Option<BuildCommand>,
+    /// 环境变量
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub envs: Option<Vec<TaskEnv>>,
+    /// 编译target
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rust_target: Option<String>,
+}
+
+impl ProfileOverride {
+    pub fn trim(&mut self) {
+        trim_build_command(&mut self.build_command);
+        if let Some(envs) = &mut self.envs {
+            for env in envs {
+                env.trim();
+            }
+        }
+        if let Some(target) = &self.rust_target {
+            self.rust_target = Some(target.trim().to_string());
+        }
+    }
+}
+
+/// # 某个目标架构下的安装配置覆盖
+///
+/// 未设置的字段使用基础安装配置中的值
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct InstallOverride {
+    /// 安装到DragonOS内的目录
+    #[serde(default)]
+    pub in_dragonos_path: Option<PathBuf>,
+}
+
+/// # 清理配置
+///
+/// 清理分为`output`、`src`、`cache`、`all`四个级别，每个级别包含之前的级别。
+/// `clean_command`是`output`级别的清理命令，与`levels.output.command`相同
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CleanConfig {
+    /// 清理命令
+    pub clean_command: Option<String>,
+    /// 各清理级别的命令和行为
+    #[serde(default, skip_serializing_if = "CleanLevels::is_empty")]
+    pub levels: CleanLevels,
+    /// 每条清理命令的超时时间（秒），超时后结束命令所在的进程组
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+    /// 清理命令失败或者超时时的处理方式
+    #[serde(default, skip_serializing_if = "CleanFailurePolicy::is_default")]
+    pub on_failure: CleanFailurePolicy,
+}
+
+impl CleanConfig {
+    #[allow(dead_code)]
+    pub fn new(clean_command: Option<String>) -> Self {
+        Self {
+            clean_command,
+            levels: CleanLevels::default(),
+            timeout: None,
+            on_failure: CleanFailurePolicy::default(),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.timeout == Some(0) {
+            return Err("CleanConfig: timeout should be greater than 0".to_string());
+        }
+        if self.clean_command.is_some()
+            && self
+                .levels
+                .output
+                .as_ref()
+                .is_some_and(|c| c.command.is_some())
+        {
+            return Err(
+                "clean_command and clean.levels.output.command cannot be both set".to_string(),
+            );
+        }
+        for (name, config) in self.levels.iter() {
+            if config
+                .and_then(|c| c.command.as_ref())
+                .is_some_and(|c| c.is_empty())
+            {
+                return Err(format!("clean command of level {} is empty", name));
+            }
+        }
+        return Ok(());
+    }
+
+    pub fn trim(&mut self) {
+        if let Some(clean_command) = &mut self.clean_command {
+            *clean_command = clean_command.trim().to_string();
+        }
+        for config in self.levels.iter_mut().into_iter().flatten() {
+            if let Some(command) = &mut config.command {
+                *command = command.trim().to_string();
+            }
+        }
+    }
+
+    /// # 某个清理级别（`output`、`src`、`cache`、`all`）的清理命令
+    ///
+    /// `output`级别没有在`levels`中指定命令时，使用`clean_command`
+    pub fn command(&self, level: &str) -> Option<&String> {
+        let command = self.levels.get(level).and_then(|c| c.command.as_ref());
+        if level == "output" {
+            return command.or(self.clean_command.as_ref());
+        }
+        return command;
+    }
+
+    /// # 某个清理级别下是否由DADK删除属于该级别的目录
+    ///
+    /// 没有在`levels`中指定时，该级别没有清理命令才删除
+    pub fn remove_dirs(&self, level: &str) -> bool {
+        return self
+            .levels
+            .get(level)
+            .and_then(|c| c.remove_dirs)
+            .unwrap_or_else(|| self.command(level).is_none());
+    }
+
+    /// 所有清理命令（包括`clean_command`）
+    pub fn commands(&self) -> Vec<String> {
+        let mut commands: Vec<String> = self.clean_command.clone().into_iter().collect();
+        commands.extend(
+            self.levels
+                .iter()
+                .into_iter()
+                .filter_map(|(_, c)| c.and_then(|c| c.command.clone())),
+        );
+        return commands;
+    }
+}
+
+/// # 清理命令失败或者超时时的处理方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CleanFailurePolicy {
+    /// 停止整个清理过程
+    Abort,
+    /// 记录任务失败，继续清理其他任务，最终以非0的退出码退出
+    #[default]
+    Continue,
+    /// 只输出警告，任务视为清理成功，继续删除属于各级别的目录
+    Ignore,
+}
+
+impl CleanFailurePolicy {
+    pub fn is_default(&self) -> bool {
+        return *self == Self::default();
+    }
+}
+
+/// # 各清理级别的配置
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CleanLevels {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<CleanLevelConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub src: Option<CleanLevelConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache: Option<CleanLevelConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub all: Option<CleanLevelConfig>,
+}
+
+impl CleanLevels {
+    pub fn is_empty(&self) -> bool {
+        return self.iter().iter().all(|(_, c)| c.is_none());
+    }
+
+    pub fn get(&self, level: &str) -> Option<&CleanLevelConfig> {
+        return self
+            .iter()
+            .into_iter()
+            .find(|(name, _)| *name == level)
+            .and_then(|(_, c)| c);
+    }
+
+    /// 按从低到高的顺序列出各级别的配置
+    fn iter(&self) -> [(&'static str, Option<&CleanLevelConfig>); 4] {
+        return [
+            ("output", self.output.as_ref()),
+            ("src", self.src.as_ref()),
+            ("cache", self.cache.as_ref()),
+            ("all", self.all.as_ref()),
+        ];
+    }
+
+    fn iter_mut(&mut self) -> [Option<&mut CleanLevelConfig>; 4] {
+        return [
+            self.output.as_mut(),
+            self.src.as_mut(),
+            self.cache.as_mut(),
+            self.all.as_mut(),
+        ];
+    }
+}
+
+/// # 一个清理级别的配置
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CleanLevelConfig {
+    /// 在源码目录中执行的清理命令
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    /// 是否由DADK删除属于该级别的目录，默认在没有指定`command`时删除
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remove_dirs: Option<bool>,
+}
+
+/// # 依赖的类型
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum DependencyKind {
+    /// 另一个DADK任务，在依赖它的任务之前被调度执行
+    #[default]
+    Dadk,
+    /// 主机上的系统软件包（例如`pkg-config`、`cmake`），`name`是它的可执行文件。
+    /// 不会被调度，只在执行任务前检查是否存在以及版本是否满足要求
+    System,
+}
+
+impl DependencyKind {
+    fn is_default(&self) -> bool {
+        return *self == Self::default();
+    }
+}
+
+/// @brief 依赖项
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Dependency {
+    pub name: String,
+    /// 版本要求，系统依赖写作`*`时不检查版本
+    pub version: String,
+    /// 依赖需要按哪个目标架构构建（例如在主机上运行的构建工具），默认与依赖它的任务相同
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub arch: Option<TargetArch>,
+    /// 依赖的类型，默认为DADK任务
+    #[serde(default, skip_serializing_if = "DependencyKind::is_default")]
+    pub kind: DependencyKind,
+    /// 输出系统依赖的版本的命令，例如`cmake --version`，检查版本要求时使用输出中的第一个版本号
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version_command: Option<String>,
+    /// 导出依赖的构建结果目录时使用的环境变量名（例如`LIBFOO_DIR`），代替工作区`dep_envs`生成的名称
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub env_alias: Option<String>,
+}
+
+impl Dependency {
+    #[allow(dead_code)]
+    pub fn new(name: String, version: String) -> Self {
+        Self {
+            name,
+            version,
+            arch: None,
+            kind: DependencyKind::Dadk,
+            version_command: None,
+            env_alias: None,
+        }
+    }
+
+    /// 创建系统依赖
+    #[allow(dead_code)]
+    pub fn system(name: String, version: String, version_command: Option<String>) -> Self {
+        Self {
+            kind: DependencyKind::System,
+            version_command,
+            ..Self::new(name, version)
+        }
+    }
+
+    /// 是否为需要调度的DADK任务
+    pub fn is_task(&self) -> bool {
+        return self.kind == DependencyKind::Dadk;
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.is_empty() {
+            return Err("name is empty".to_string());
+        }
+        if self.version.is_empty() {
+            return Err("version is empty".to_string());
+        }
+        VersionReq::parse(&self.version)?;
+        if let Some(alias) = &self.env_alias {
+            if !dotenv::is_valid_key(alias) {
+                return Err(format!("{}: invalid env_alias {:?}", self.name, alias));
+            }
+        }
+        match self.kind {
+            DependencyKind::Dadk => {
+                if self.version_command.is_some() {
+                    return Err(format!(
+                        "{}: version_command is only allowed for system dependencies",
+                        self.name
+                    ));
+                }
+            }
+            DependencyKind::System => {
+                if self.arch.is_some() {
+                    return Err(format!(
+                        "{}: arch is not allowed for system dependencies",
+                        self.name
+                    ));
+                }
+                if self.env_alias.is_some() {
+                    return Err(format!(
+                        "{}: env_alias is not allowed for system dependencies",
+                        self.name
+                    ));
+                }
+                match &self.version_command {
+                    Some(cmd) if cmd.is_empty() => {
+                        return Err(format!("{}: version_command is empty", self.name));
+                    }
+                    None if self.version != "*" => {
+                        return Err(format!(
+                            "{}: version_command is required to check the version requirement {} of a system dependency",
+                            self.name, self.version
+                        ));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    pub fn trim(&mut self) {
+        self.name = self.name.trim().to_string();
+        self.version = self.version.trim().to_string();
+        if let Some(cmd) = &mut self.version_command {
+            *cmd = cmd.trim().to_string();
+        }
+        if let Some(alias) = &mut self.env_alias {
+            *alias = alias.trim().to_string();
+        }
+    }
+
+    pub fn name_version(&self) -> String {
+        return format!("{}-{}", self.name, self.version);
+    }
+}
+
+/// # 依赖的环境变量名的格式
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DepEnvNaming {
+    /// 依赖名转为大写，字母、数字以外的字符替换为`_`，例如`lib-foo.bar`为`LIB_FOO_BAR`
+    #[default]
+    Sanitized,
+    /// 原样使用依赖名
+    Verbatim,
+}
+
+/// # 依赖的构建结果目录的环境变量
+///
+/// 执行任务时，任务的每个DADK任务依赖的构建结果目录被导出为`前缀+依赖名`，
+/// 依赖设置了`env_alias`时使用别名。在工作区配置的`[dep_envs]`中设置
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DepEnvConfig {
+    /// 环境变量名的前缀，默认为`DADK_DEP_`
+    #[serde(default = "DepEnvConfig::default_prefix")]
+    pub prefix: String,
+    /// 依赖名的格式
+    #[serde(default)]
+    pub naming: DepEnvNaming,
+}
+
+impl Default for DepEnvConfig {
+    fn default() -> Self {
+        Self {
+            prefix: Self::default_prefix(),
+            naming: DepEnvNaming::default(),
+        }
+    }
+}
+
+impl DepEnvConfig {
+    fn default_prefix() -> String {
+        return "DADK_DEP_".to_string();
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.prefix.is_empty() && !dotenv::is_valid_key(&self.prefix) {
+            return Err(format!("dep_envs: invalid prefix {:?}", self.prefix));
+        }
+        return Ok(());
+    }
+
+    /// 导出依赖的构建结果目录时使用的环境变量名
+    pub fn env_key(&self, dep: &Dependency) -> String {
+        if let Some(alias) = &dep.env_alias {
+            return alias.clone();
+        }
+        let name = match self.naming {
+            DepEnvNaming::Sanitized => dep
+                .name
+                .chars()
+                .map(|c| match c {
+                    c if c.is_ascii_alphanumeric() => c.to_ascii_uppercase(),
+                    _ => '_',
+                })
+                .collect(),
+            DepEnvNaming::Verbatim => dep.name.clone(),
+        };
+        return format!("{}{}", self.prefix, name);
     }
 }
 
@@ -343,20 +2393,38 @@ pub enum TaskType {
     BuildFromSource(CodeSource),
     /// 从预编译包安装
     InstallFromPrebuilt(PrebuiltSource),
+    /// 没有源码，仅执行脚本（配置文件中可写作`"script"`）
+    ///
+    /// 构建命令会在一个空的任务工作目录中执行
+    #[serde(alias = "script")]
+    NoSource,
+    /// 元包（配置文件中可写作`"meta"`）
+    ///
+    /// 没有构建、安装和清理操作，总是被视为最新的，仅用于聚合一组依赖
+    #[serde(alias = "meta")]
+    Meta,
+    /// DragonOS内核模块（配置文件中可写作`"kernel_module"`）
+    ///
+    /// 从源码构建，构建时会导出内核源码目录、内核构建目录和内核的目标架构等环境变量，
+    /// 构建结果中的`.ko`文件会被安装到`/lib/modules`（可以通过`in_dragonos_path`指定）
+    #[serde(alias = "kernel_module")]
+    KernelModule(CodeSource),
 }
 
 impl TaskType {
     pub fn validate(&mut self) -> Result<(), String> {
         match self {
-            TaskType::BuildFromSource(source) => source.validate(),
+            TaskType::BuildFromSource(source) | TaskType::KernelModule(source) => source.validate(),
             TaskType::InstallFromPrebuilt(source) => source.validate(),
+            TaskType::NoSource | TaskType::Meta => Ok(()),
         }
     }
 
     pub fn trim(&mut self) {
         match self {
-            TaskType::BuildFromSource(source) => source.trim(),
+            TaskType::BuildFromSource(source) | TaskType::KernelModule(source) => source.trim(),
             TaskType::InstallFromPrebuilt(source) => source.trim(),
+            TaskType::NoSource | TaskType::Meta => {}
         }
     }
 }
@@ -366,25 +2434,71 @@ impl TaskType {
 pub enum CodeSource {
     /// 从Git仓库获取
     Git(GitSource),
+    /// 从Mercurial仓库获取
+    Hg(HgSource),
+    /// 从Subversion仓库获取
+    Svn(SvnSource),
     /// 从本地目录获取
     Local(LocalSource),
     /// 从在线压缩包获取
     Archive(ArchiveSource),
+    /// 从GitHub/Gitea的Release附件获取
+    ReleaseAsset(ReleaseAssetSource),
+    /// 叠加多个源
+    ///
+    /// 每一层独立获取，然后按照声明的顺序叠加到任务的源码目录中，后面的层会覆盖前面的层中的同名文件
+    Overlay(Vec<CodeSource>),
 }
 
 impl CodeSource {
     pub fn validate(&mut self) -> Result<(), String> {
         match self {
             CodeSource::Git(source) => source.validate(),
-            CodeSource::Local(source) => source.validate(Some(false)),
+            CodeSource::Hg(source) => source.validate(),
+            CodeSource::Svn(source) => source.validate(),
+            CodeSource::Local(source) => source.validate(LocalSourceKind::Dir),
             CodeSource::Archive(source) => source.validate(),
+            CodeSource::ReleaseAsset(source) => source.validate(),
+            CodeSource::Overlay(layers) => {
+                if layers.is_empty() {
+                    return Err("overlay source should have at least one layer".to_string());
+                }
+                for layer in layers.iter_mut() {
+                    if let CodeSource::Overlay(_) = layer {
+                        return Err("nested overlay source is not supported".to_string());
+                    }
+                    layer.validate()?;
+                }
+                return Ok(());
+            }
         }
     }
     pub fn trim(&mut self) {
         match self {
             CodeSource::Git(source) => source.trim(),
+            CodeSource::Hg(source) => source.trim(),
+            CodeSource::Svn(source) => source.trim(),
             CodeSource::Local(source) => source.trim(),
             CodeSource::Archive(source) => source.trim(),
+            CodeSource::ReleaseAsset(source) => source.trim(),
+            CodeSource::Overlay(layers) => {
+                for layer in layers.iter_mut() {
+                    layer.trim();
+                }
+            }
+        }
+    }
+
+    /// 把本地源（包括叠加源中的本地层）的路径解析为相对于`base_dir`的绝对路径
+    pub fn resolve_local_paths(&mut self, base_dir: &Path) {
+        match self {
+            CodeSource::Local(source) => source.resolve(base_dir),
+            CodeSource::Overlay(layers) => {
+                for layer in layers.iter_mut() {
+                    layer.resolve_local_paths(base_dir);
+                }
+            }
+            _ => {}
         }
     }
 }
@@ -392,24 +2506,43 @@ impl CodeSource {
 /// # 预编译包源
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum PrebuiltSource {
+    /// 从Git仓库获取（仓库中的文件直接作为预编译包，没有构建步骤）
+    Git(GitSource),
     /// 从在线压缩包获取
     Archive(ArchiveSource),
     /// 从本地目录/文件获取
     Local(LocalSource),
+    /// 从OCI/Docker镜像获取
+    Oci(OciSource),
+    /// 从GitHub/Gitea的Release附件获取
+    ReleaseAsset(ReleaseAssetSource),
 }
 
 impl PrebuiltSource {
-    pub fn validate(&self) -> Result<(), String> {
+    pub fn validate(&mut self) -> Result<(), String> {
         match self {
+            PrebuiltSource::Git(source) => source.validate(),
             PrebuiltSource::Archive(source) => source.validate(),
-            PrebuiltSource::Local(source) => source.validate(None),
+            PrebuiltSource::Local(source) => source.validate(LocalSourceKind::Any),
+            PrebuiltSource::Oci(source) => source.validate(),
+            PrebuiltSource::ReleaseAsset(source) => source.validate(),
         }
     }
 
     pub fn trim(&mut self) {
         match self {
+            PrebuiltSource::Git(source) => source.trim(),
             PrebuiltSource::Archive(source) => source.trim(),
             PrebuiltSource::Local(source) => source.trim(),
+            PrebuiltSource::Oci(source) => source.trim(),
+            PrebuiltSource::ReleaseAsset(source) => source.trim(),
+        }
+    }
+
+    /// 把本地源的路径解析为相对于`base_dir`的绝对路径
+    pub fn resolve_local_paths(&mut self, base_dir: &Path) {
+        if let PrebuiltSource::Local(source) = self {
+            source.resolve(base_dir);
         }
     }
 }
@@ -417,16 +2550,151 @@ impl PrebuiltSource {
 /// # 任务环境变量
 ///
 /// 任务执行时的环境变量.这个环境变量是在当前任务执行时设置的，不会影响到其他任务
-#[derive(Debug, Clone, Serialize, Deserialize)]
+///
+/// `op`为`prepend`或`append`时，值以`separator`（默认为`:`）连接到继承的同名环境变量之前或之后，
+/// 例如在`PATH`前面加入工具链的目录，而不是整个替换它。同一个环境变量的多个`prepend`/`append`按声明的顺序组合。
+///
+/// `secret`为true的环境变量（例如签名用的令牌）仍然传给执行的命令，但DADK输出环境变量、命令和错误信息时
+/// 把它的值替换为`***`，并且计算全局环境变量的哈希值时不使用它的值，除非同时设置了`hash_secret`
+#[derive(Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
 pub struct TaskEnv {
     pub key: String,
     pub value: String,
+    /// (可选) 只在按这个目标架构构建时设置，与依赖的`arch`相同
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_arch: Option<TargetArch>,
+    /// 值是否保密
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub secret: bool,
+    /// 保密的值是否参与计算缓存的键（全局环境变量的哈希值）
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub hash_secret: bool,
+    /// 是否允许使用DADK保留的名称（以`DADK_`开头），用于覆盖DADK设置的环境变量
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub allow_reserved: bool,
+    /// 设置方式，默认为`set`
+    #[serde(default, skip_serializing_if = "EnvOp::is_set")]
+    pub op: EnvOp,
+    /// (可选) `prepend`/`append`时的分隔符，默认为`:`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub separator: Option<String>,
+    /// (可选) 只在这些阶段（`build`、`install`、`clean`）设置，为空时在所有阶段设置
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub phases: Vec<String>,
+}
+
+/// # 环境变量的设置方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EnvOp {
+    /// 设置为配置的值，覆盖继承的值
+    #[default]
+    Set,
+    /// 加在继承的值之前
+    Prepend,
+    /// 加在继承的值之后
+    Append,
+}
+
+impl EnvOp {
+    pub fn is_set(&self) -> bool {
+        return *self == EnvOp::Set;
+    }
 }
 
 impl TaskEnv {
+    /// 执行阶段，与`DADK_PHASE`环境变量的值相同
+    pub const PHASES: [&'static str; 3] = ["build", "install", "clean"];
+
     #[allow(dead_code)]
     pub fn new(key: String, value: String) -> Self {
-        Self { key, value }
+        Self {
+            key,
+            value,
+            target_arch: None,
+            secret: false,
+            hash_secret: false,
+            allow_reserved: false,
+            op: EnvOp::Set,
+            separator: None,
+            phases: Vec::new(),
+        }
+    }
+
+    /// 以`op`方式设置的环境变量
+    #[allow(dead_code)]
+    pub fn with_op(key: String, value: String, op: EnvOp) -> Self {
+        Self {
+            op,
+            ..Self::new(key, value)
+        }
+    }
+
+    /// `prepend`/`append`时使用的分隔符
+    pub fn separator(&self) -> &str {
+        return self.separator.as_deref().unwrap_or(DEFAULT_ENV_SEPARATOR);
+    }
+
+    /// # 按`op`组合出最终的值
+    ///
+    /// `value`是（替换了变量的）配置的值，`inherited`是继承的同名环境变量的值。
+    /// 没有继承的值或者继承的值为空时，`prepend`/`append`的结果就是`value`
+    pub fn compose(&self, value: String, inherited: Option<&str>) -> String {
+        let inherited = match inherited {
+            Some(inherited) if !inherited.is_empty() && !self.op.is_set() => inherited,
+            _ => return value,
+        };
+        return match self.op {
+            EnvOp::Prepend => format!("{}{}{}", value, self.separator(), inherited),
+            EnvOp::Append => format!("{}{}{}", inherited, self.separator(), value),
+            EnvOp::Set => value,
+        };
+    }
+
+    /// 值保密的环境变量
+    #[allow(dead_code)]
+    pub fn secret(key: String, value: String) -> Self {
+        Self {
+            secret: true,
+            ..Self::new(key, value)
+        }
+    }
+
+    /// 只在按`arch`构建时设置的环境变量
+    #[allow(dead_code)]
+    pub fn for_arch(key: String, value: String, arch: TargetArch) -> Self {
+        Self {
+            target_arch: Some(arch),
+            ..Self::new(key, value)
+        }
+    }
+
+    /// # 合并覆盖配置时，是否被同名的`other`取代
+    ///
+    /// 只有`set`取代阶段相同的`set`，`prepend`/`append`总是追加在后面，按声明的顺序组合
+    pub fn replaced_by(&self, other: &TaskEnv) -> bool {
+        return self.op.is_set() && other.op.is_set() && self.phase_set() == other.phase_set();
+    }
+
+    /// 在执行阶段`phase`中是否设置
+    pub fn applies_in_phase(&self, phase: &str) -> bool {
+        return self.phases.is_empty() || self.phases.iter().any(|p| p == phase);
+    }
+
+    /// 是否有同时生效的阶段
+    pub fn phases_overlap(&self, other: &TaskEnv) -> bool {
+        return TaskEnv::PHASES
+            .iter()
+            .any(|p| self.applies_in_phase(p) && other.applies_in_phase(p));
+    }
+
+    fn phase_set(&self) -> BTreeSet<&str> {
+        return self.phases.iter().map(String::as_str).collect();
+    }
+
+    /// 按`arch`构建时是否设置
+    pub fn applies_to(&self, arch: TargetArch) -> bool {
+        return self.target_arch.map_or(true, |a| a == arch);
     }
 
     pub fn key(&self) -> &str {
@@ -440,18 +2708,165 @@ impl TaskEnv {
     pub fn trim(&mut self) {
         self.key = self.key.trim().to_string();
         self.value = self.value.trim().to_string();
+        for phase in self.phases.iter_mut() {
+            *phase = phase.trim().to_string();
+        }
     }
 
     pub fn validate(&self) -> Result<(), String> {
         if self.key.is_empty() {
             return Err("Env: key is empty".to_string());
         }
+        if !dotenv::is_valid_key(&self.key) {
+            return Err(format!(
+                "Env: invalid key {:?}, expected [A-Za-z_][A-Za-z0-9_]*",
+                self.key
+            ));
+        }
+        if self.key.starts_with(RESERVED_ENV_PREFIX) && !self.allow_reserved {
+            return Err(format!(
+                "Env: {} is reserved for DADK (names starting with {}), set allow_reserved to override it",
+                self.key, RESERVED_ENV_PREFIX
+            ));
+        }
+        match &self.separator {
+            Some(_) if self.op.is_set() => {
+                return Err(format!(
+                    "Env: {}: separator is only used with op prepend or append",
+                    self.key
+                ));
+            }
+            Some(separator) if separator.is_empty() => {
+                return Err(format!("Env: {}: separator is empty", self.key));
+            }
+            _ => {}
+        }
+        if let Some(phase) = self
+            .phases
+            .iter()
+            .find(|p| !TaskEnv::PHASES.contains(&p.as_str()))
+        {
+            return Err(format!(
+                "Env: {}: unknown phase {:?}, expected one of {:?}",
+                self.key,
+                phase,
+                TaskEnv::PHASES
+            ));
+        }
+        if self.hash_secret && !self.secret {
+            return Err(format!("Env: {}: hash_secret requires secret", self.key));
+        }
+        return Ok(());
+    }
+
+    /// 输出时使用的值，保密的值被替换为[`SECRET_MASK`]
+    pub fn display_value(&self) -> &str {
+        if self.secret {
+            return SECRET_MASK;
+        }
+        return &self.value;
+    }
+
+    /// # 值被隐藏的副本
+    ///
+    /// 用于输出任务配置，保密的值被替换为[`SECRET_MASK`]
+    pub fn masked(&self) -> Self {
+        let mut env = self.clone();
+        env.value = self.display_value().to_string();
+        return env;
+    }
+}
+
+impl std::fmt::Debug for TaskEnv {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TaskEnv")
+            .field("key", &self.key)
+            .field("value", &self.display_value())
+            .field("target_arch", &self.target_arch)
+            .field("secret", &self.secret)
+            .field("hash_secret", &self.hash_secret)
+            .field("allow_reserved", &self.allow_reserved)
+            .field("op", &self.op)
+            .field("separator", &self.separator)
+            .field("phases", &self.phases)
+            .finish()
+    }
+}
+
+/// # 环境变量文件
+///
+/// dotenv格式的文件（见`parser::dotenv`），执行任务时加载。在配置文件中可以只写路径，
+/// 也可以写作`{ "path": 路径, "optional": true, "secret": true }`，文件不存在时，`optional`为false则任务失败。
+/// `secret`为true时，文件中的所有环境变量都是保密的（见[`TaskEnv`]）
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct EnvFile {
+    /// 文件路径，相对路径相对于配置文件所在的目录
+    pub path: PathBuf,
+    /// 文件不存在时是否忽略
+    pub optional: bool,
+    /// 文件中的环境变量的值是否保密
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub secret: bool,
+}
+
+impl EnvFile {
+    #[allow(dead_code)]
+    pub fn new(path: PathBuf, optional: bool) -> Self {
+        Self {
+            path,
+            optional,
+            secret: false,
+        }
+    }
+
+    pub fn trim(&mut self) {
+        self.path = PathBuf::from(self.path.to_string_lossy().trim());
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.path.as_os_str().is_empty() {
+            return Err("env_files: path is empty".to_string());
+        }
         return Ok(());
     }
+
+    /// 把相对路径解析为相对于`base_dir`的路径
+    pub fn resolve(&mut self, base_dir: &Path) {
+        if self.path.is_relative() {
+            self.path = base_dir.join(&self.path);
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for EnvFile {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct Detailed {
+            path: PathBuf,
+            #[serde(default)]
+            optional: bool,
+            #[serde(default)]
+            secret: bool,
+        }
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Path(PathBuf),
+            Detailed(Detailed),
+        }
+        return match Raw::deserialize(deserializer)? {
+            Raw::Path(path) => Ok(EnvFile::new(path, false)),
+            Raw::Detailed(d) => Ok(EnvFile {
+                secret: d.secret,
+                ..EnvFile::new(d.path, d.optional)
+            }),
+        };
+    }
 }
 
 /// 目标处理器架构
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum TargetArch {
     Aarch64,
     X86_64,
@@ -462,6 +2877,26 @@ pub enum TargetArch {
 impl TargetArch {
     /// 期望的目标处理器架构（如果修改了枚举，那一定要修改这里）
     pub const EXPECTED: [&'static str; 4] = ["aarch64", "x86_64", "riscv64", "riscv32"];
+
+    /// 该架构下交叉编译C/C++程序的工具链前缀，例如`x86_64-linux-musl`
+    pub fn cross_prefix(&self) -> &'static str {
+        match self {
+            TargetArch::Aarch64 => "aarch64-linux-musl",
+            TargetArch::X86_64 => "x86_64-linux-musl",
+            TargetArch::RiscV64 => "riscv64-linux-musl",
+            TargetArch::RiscV32 => "riscv32-linux-musl",
+        }
+    }
+
+    /// 该架构下DragonOS用户程序的rust target
+    pub fn rust_target(&self) -> &'static str {
+        match self {
+            TargetArch::Aarch64 => "aarch64-unknown-dragonos",
+            TargetArch::X86_64 => "x86_64-unknown-dragonos",
+            TargetArch::RiscV64 => "riscv64gc-unknown-dragonos",
+            TargetArch::RiscV32 => "riscv32imac-unknown-dragonos",
+        }
+    }
 }
 
 impl Default for TargetArch {
@@ -2,15 +2,20 @@
 //!
 //! DADK在执行任务时，会把一些日志记录到任务的文件夹下。
 
+use std::collections::BTreeMap;
+
 use chrono::{DateTime, Utc};
 use log::warn;
 use serde::{Deserialize, Deserializer, Serialize};
 
+use crate::executor::lock::LockedSource;
+
 /// 任务日志（输出到任务构建日志目录下的）
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TaskLog {
     /// 任务执行完成时间
     #[serde(
+        default,
         deserialize_with = "ok_or_default",
         skip_serializing_if = "Option::is_none"
     )]
@@ -19,6 +24,34 @@ pub struct TaskLog {
     build_status: Option<BuildStatus>,
     /// 任务安装状态
     install_status: Option<InstallStatus>,
+    /// 最近一次成功安装时的安装标记（仅设置了`install_once`的任务）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    install_stamp: Option<InstallStamp>,
+    /// 最近一次下载的压缩包的sha256值（仅在线压缩包源）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    archive_checksum: Option<String>,
+    /// 最近一次下载的Release附件所属的tag（仅Release附件源）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    release_tag: Option<String>,
+    /// 最近一次拉取的镜像的digest（仅OCI镜像源）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    oci_digest: Option<String>,
+    /// 最近一次准备叠加源时，每一层的源码身份（仅叠加源）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    overlay_layers: Option<Vec<OverlayLayer>>,
+    /// 最近一次成功执行构建前钩子时的源码身份
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pre_build_source: Option<LockedSource>,
+    /// 最近一次构建成功时，`rerun_if_changed`中每个路径的哈希值（路径不存在时为空字符串，
+    /// 因为toml无法表示None）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    rerun_if_changed: Option<BTreeMap<String, String>>,
+    /// 最近一次构建成功时检出的Git提交（仅Git源）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    built_commit: Option<String>,
+    /// 最近一次构建成功时继承的全局环境变量的哈希值（没有继承全局环境变量时为None）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    global_envs_hash: Option<String>,
 }
 
 fn ok_or_default<'a, T, D>(deserializer: D) -> Result<T, D::Error>
@@ -37,6 +70,15 @@ impl TaskLog {
             build_timestamp: None,
             build_status: None,
             install_status: None,
+            install_stamp: None,
+            archive_checksum: None,
+            release_tag: None,
+            oci_digest: None,
+            overlay_layers: None,
+            pre_build_source: None,
+            rerun_if_changed: None,
+            built_commit: None,
+            global_envs_hash: None,
         }
     }
 
@@ -77,6 +119,79 @@ impl TaskLog {
 
     pub fn clean_install_status(&mut self) {
         self.install_status = None;
+        self.install_stamp = None;
+    }
+
+    pub fn install_stamp(&self) -> Option<&InstallStamp> {
+        self.install_stamp.as_ref()
+    }
+
+    pub fn set_install_stamp(&mut self, stamp: Option<InstallStamp>) {
+        self.install_stamp = stamp;
+    }
+
+    pub fn set_archive_checksum(&mut self, checksum: String) {
+        self.archive_checksum = Some(checksum);
+    }
+
+    pub fn archive_checksum(&self) -> Option<&String> {
+        self.archive_checksum.as_ref()
+    }
+
+    pub fn set_release_tag(&mut self, tag: String) {
+        self.release_tag = Some(tag);
+    }
+
+    pub fn release_tag(&self) -> Option<&String> {
+        self.release_tag.as_ref()
+    }
+
+    pub fn set_oci_digest(&mut self, digest: String) {
+        self.oci_digest = Some(digest);
+    }
+
+    pub fn oci_digest(&self) -> Option<&String> {
+        self.oci_digest.as_ref()
+    }
+
+    pub fn set_overlay_layers(&mut self, layers: Option<Vec<OverlayLayer>>) {
+        self.overlay_layers = layers;
+    }
+
+    pub fn overlay_layers(&self) -> Option<&Vec<OverlayLayer>> {
+        self.overlay_layers.as_ref()
+    }
+
+    pub fn set_pre_build_source(&mut self, source: Option<LockedSource>) {
+        self.pre_build_source = source;
+    }
+
+    pub fn pre_build_source(&self) -> Option<&LockedSource> {
+        self.pre_build_source.as_ref()
+    }
+
+    pub fn set_rerun_if_changed(&mut self, hashes: Option<BTreeMap<String, String>>) {
+        self.rerun_if_changed = hashes;
+    }
+
+    pub fn rerun_if_changed(&self) -> Option<&BTreeMap<String, String>> {
+        self.rerun_if_changed.as_ref()
+    }
+
+    pub fn set_built_commit(&mut self, commit: Option<String>) {
+        self.built_commit = commit;
+    }
+
+    pub fn built_commit(&self) -> Option<&String> {
+        self.built_commit.as_ref()
+    }
+
+    pub fn set_global_envs_hash(&mut self, hash: Option<String>) {
+        self.global_envs_hash = hash;
+    }
+
+    pub fn global_envs_hash(&self) -> Option<&String> {
+        self.global_envs_hash.as_ref()
     }
 }
 
@@ -106,6 +221,44 @@ impl<'de> Deserialize<'de> for BuildStatus {
     }
 }
 
+/// # 叠加源中一层的源码身份
+///
+/// 无法确定身份的层（例如复用缓存时没有记录sha256值的压缩包）记录为空表，不影响其他层
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct OverlayLayer {
+    #[serde(flatten)]
+    pub source: Option<LockedSource>,
+}
+
+/// # 安装标记
+///
+/// 设置了`install_once`的任务，只有标记中的各项都与当前一致时才跳过安装
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct InstallStamp {
+    /// 任务的`name_version()`
+    pub name_version: String,
+    /// 构建结果目录的哈希值
+    pub output_hash: String,
+    /// 安装配置（`install`）的哈希值
+    pub install_config_hash: String,
+}
+
+impl InstallStamp {
+    /// # 与上一次的标记比较，返回第一项不一致的原因
+    pub fn diff(&self, previous: &InstallStamp) -> Option<&'static str> {
+        if self.name_version != previous.name_version {
+            return Some("task version changed");
+        }
+        if self.output_hash != previous.output_hash {
+            return Some("build output changed");
+        }
+        if self.install_config_hash != previous.install_config_hash {
+            return Some("install config changed");
+        }
+        return None;
+    }
+}
+
 /// 任务安装状态
 #[derive(Debug, Clone, Serialize, PartialEq)]
 pub enum InstallStatus {
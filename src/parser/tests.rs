@@ -2,9 +2,12 @@ use test_base::{
     test_context::{self as test_context, test_context},
     BaseTestContext,
 };
-use tests::task::{BuildConfig, TargetArch, TaskType};
+use tests::task::{BuildCommand, BuildConfig, CodeSource, PrebuiltSource, TargetArch, TaskType};
 
-use crate::executor::source::LocalSource;
+use crate::executor::source::{
+    ArchiveSource, GitDirtyPolicy, GitSource, HgSource, LocalSource, OciSource, ReleaseAssetSource,
+    SvnSource,
+};
 
 use super::*;
 
@@ -23,8 +26,9 @@ fn parse_normal_v1(ctx: &mut BaseTestContext) {
     assert_eq!(result.version, "0.1.0");
     assert_eq!(result.description, "A normal app");
 
+    // 相对路径在解析时被解析为绝对路径
     let expected_task_type = TaskType::BuildFromSource(task::CodeSource::Local(LocalSource::new(
-        PathBuf::from("tests/data/apps/app_normal"),
+        ctx.abs_path("tests/data/apps/app_normal"),
     )));
 
     assert_eq!(result.task_type, expected_task_type,);
@@ -102,3 +106,2215 @@ fn target_arch_field_empty_should_failed_v1(ctx: &mut BaseTestContext) {
         "parse_config_file should return error when target_arch field in config file is empty"
     );
 }
+
+#[test_context(BaseTestContext)]
+#[test]
+fn parse_no_source_v1(ctx: &mut BaseTestContext) {
+    let parser = Parser::new(ctx.config_v1_dir());
+    let config_file = ctx.config_v1_dir().join("app_no_source_0_1_0.dadk");
+    let result = parser.parse_config_file(&config_file);
+
+    assert!(result.is_ok(), "Error: {:?}", result);
+
+    let result = result.unwrap();
+
+    assert_eq!(result.name, "app_no_source");
+    assert_eq!(result.task_type, TaskType::NoSource);
+    assert!(result.source_path().is_none());
+}
+
+#[test_context(BaseTestContext)]
+#[test]
+fn no_source_without_build_command_should_failed_v1(ctx: &mut BaseTestContext) {
+    let parser = Parser::new(ctx.config_v1_dir());
+    let config_file = ctx
+        .config_v1_dir()
+        .join("app_no_source_without_build_command_should_fail_0_1_0.dadk");
+    let result = parser.parse_config_file(&config_file);
+
+    assert!(
+        result.is_err(),
+        "parse_config_file should return error when script task has no build command"
+    );
+}
+
+#[test_context(BaseTestContext)]
+#[test]
+fn parse_prebuilt_git_v1(ctx: &mut BaseTestContext) {
+    let parser = Parser::new(ctx.config_v1_dir());
+    let config_file = ctx.config_v1_dir().join("app_prebuilt_git_0_1_0.dadk");
+    let result = parser.parse_config_file(&config_file);
+
+    assert!(result.is_ok(), "Error: {:?}", result);
+
+    let result = result.unwrap();
+
+    assert_eq!(
+        result.task_type,
+        TaskType::InstallFromPrebuilt(PrebuiltSource::Git(GitSource::new(
+            "https://git.mirrors.dragonos.org.cn/DragonOS-Community/fonts.git".to_string(),
+            Some("main".to_string()),
+            None,
+        )))
+    );
+    assert!(result.source_path().is_none());
+}
+
+#[test_context(BaseTestContext)]
+#[test]
+fn parse_kernel_module_v1(ctx: &mut BaseTestContext) {
+    let parser = Parser::new(ctx.config_v1_dir());
+    let config_file = ctx.config_v1_dir().join("app_kernel_module_0_1_0.dadk");
+    let result = parser.parse_config_file(&config_file);
+
+    assert!(result.is_ok(), "Error: {:?}", result);
+
+    let result = result.unwrap();
+
+    assert_eq!(
+        result.task_type,
+        TaskType::KernelModule(CodeSource::Local(LocalSource::new(
+            ctx.abs_path("tests/data/apps/app_kernel_module")
+        )))
+    );
+    assert_eq!(
+        result.source_path(),
+        Some(ctx.abs_path("tests/data/apps/app_kernel_module"))
+    );
+    assert_eq!(result.target_arch, vec![TargetArch::X86_64]);
+}
+
+#[test_context(BaseTestContext)]
+#[test]
+fn parse_hg_v1(ctx: &mut BaseTestContext) {
+    let parser = Parser::new(ctx.config_v1_dir());
+    let config_file = ctx.config_v1_dir().join("app_hg_0_1_0.dadk");
+    let result = parser.parse_config_file(&config_file);
+
+    assert!(result.is_ok(), "Error: {:?}", result);
+
+    // 没有指定分支和revision时，使用default分支
+    assert_eq!(
+        result.unwrap().task_type,
+        TaskType::BuildFromSource(CodeSource::Hg(HgSource::new(
+            "https://hg.example.org/app".to_string(),
+            Some("default".to_string()),
+            None,
+        )))
+    );
+}
+
+#[test_context(BaseTestContext)]
+#[test]
+fn hg_branch_and_revision_should_failed_v1(ctx: &mut BaseTestContext) {
+    let parser = Parser::new(ctx.config_v1_dir());
+    let config_file = ctx
+        .config_v1_dir()
+        .join("app_hg_branch_and_revision_should_fail_0_1_0.dadk");
+    let result = parser.parse_config_file(&config_file);
+
+    assert!(
+        result.is_err(),
+        "parse_config_file should return error when hg source has both branch and revision"
+    );
+}
+
+/// 测试hg源与JSON配置之间的转换
+#[test]
+fn hg_source_round_trip() {
+    let source = CodeSource::Hg(HgSource::new(
+        "ssh://hg@example.org/app".to_string(),
+        None,
+        Some("1.0".to_string()),
+    ));
+    let json = serde_json::to_value(&source).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({
+            "Hg": { "url": "ssh://hg@example.org/app", "branch": null, "revision": "1.0" }
+        })
+    );
+    assert_eq!(serde_json::from_value::<CodeSource>(json).unwrap(), source);
+}
+
+#[test_context(BaseTestContext)]
+#[test]
+fn parse_svn_v1(ctx: &mut BaseTestContext) {
+    let parser = Parser::new(ctx.config_v1_dir());
+    let config_file = ctx.config_v1_dir().join("app_svn_0_1_0.dadk");
+    let result = parser.parse_config_file(&config_file);
+
+    assert!(result.is_ok(), "Error: {:?}", result);
+    assert_eq!(
+        result.unwrap().task_type,
+        TaskType::BuildFromSource(CodeSource::Svn(SvnSource::new(
+            "svn://svn.example.org/repo/trunk".to_string(),
+            Some("1024".to_string()),
+        )))
+    );
+}
+
+#[test_context(BaseTestContext)]
+#[test]
+fn svn_invalid_revision_should_failed_v1(ctx: &mut BaseTestContext) {
+    let parser = Parser::new(ctx.config_v1_dir());
+    let config_file = ctx
+        .config_v1_dir()
+        .join("app_svn_invalid_revision_should_fail_0_1_0.dadk");
+    let result = parser.parse_config_file(&config_file);
+
+    assert!(
+        result.is_err(),
+        "parse_config_file should return error when svn revision is not a number or HEAD"
+    );
+}
+
+/// 测试svn源的revision校验：只能是revision号或者HEAD
+#[test]
+fn svn_revision_validate() {
+    let source = |revision: Option<&str>| {
+        SvnSource::new(
+            "https://svn.example.org/repo/trunk".to_string(),
+            revision.map(|r| r.to_string()),
+        )
+    };
+    assert!(source(None).validate().is_ok());
+    assert!(source(Some("HEAD")).validate().is_ok());
+    assert!(source(Some("0")).validate().is_ok());
+    assert!(source(Some("1024")).validate().is_ok());
+
+    for revision in ["", "head", "r1024", "-1", "1.0", "BASE", "{2024-01-01}"] {
+        let err = source(Some(revision)).validate().unwrap_err();
+        assert!(
+            err.contains("invalid svn revision"),
+            "{}: {}",
+            revision,
+            err
+        );
+    }
+    let err = source(Some("99999999999999999999")).validate().unwrap_err();
+    assert!(err.contains("out of range"), "{}", err);
+
+    // 解析时会去除首尾空白
+    let mut cs = CodeSource::Svn(source(Some(" 42 ")));
+    cs.trim();
+    assert!(cs.validate().is_ok());
+
+    assert!(
+        SvnSource::new("svn+ssh://user@svn.example.org/repo".to_string(), None)
+            .validate()
+            .is_ok()
+    );
+    assert!(SvnSource::new("file:///srv/svn/repo".to_string(), None)
+        .validate()
+        .is_ok());
+    assert!(SvnSource::new(
+        "git@github.com:DragonOS-Community/DADK.git".to_string(),
+        None
+    )
+    .validate()
+    .is_err());
+    assert!(SvnSource::new("svn:///repo".to_string(), None)
+        .validate()
+        .is_err());
+}
+
+/// 测试svn源与JSON配置之间的转换
+#[test]
+fn svn_source_round_trip() {
+    let source = CodeSource::Svn(SvnSource::new(
+        "https://svn.example.org/repo/trunk".to_string(),
+        Some("HEAD".to_string()),
+    ));
+    let json = serde_json::to_value(&source).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({
+            "Svn": { "url": "https://svn.example.org/repo/trunk", "revision": "HEAD" }
+        })
+    );
+    assert_eq!(serde_json::from_value::<CodeSource>(json).unwrap(), source);
+}
+
+/// 测试压缩包源的`subdir`校验：只能是不包含`..`的相对路径
+#[test]
+fn archive_subdir_validate() {
+    let source = |subdir: &str| {
+        ArchiveSource::new("https://example.org/mono.tar.gz".to_string())
+            .with_subdir(Some(subdir.to_string()))
+    };
+    assert!(source("pkg/foo").validate().is_ok());
+    assert_eq!(
+        source("./pkg//foo/").subdir_path(),
+        Some(PathBuf::from("pkg/foo"))
+    );
+
+    for (subdir, expected) in [
+        ("/pkg/foo", "must be a relative path"),
+        ("../pkg", "must not contain '..'"),
+        ("pkg/../../foo", "must not contain '..'"),
+        ("", "subdir is empty"),
+        ("./", "subdir is empty"),
+    ] {
+        let err = source(subdir).validate().unwrap_err();
+        assert!(err.contains(expected), "{}: {}", subdir, err);
+    }
+
+    // 解析时会去除首尾空白
+    let mut cs = CodeSource::Archive(source(" pkg/foo "));
+    cs.trim();
+    assert!(cs.validate().is_ok());
+
+    // 没有指定子目录时不写入配置
+    let json =
+        serde_json::to_value(ArchiveSource::new("https://example.org/a.zip".to_string())).unwrap();
+    assert!(json.get("subdir").is_none(), "{}", json);
+}
+
+/// 测试规范化序列化：`depends`和`envs`的顺序不影响输出，同名环境变量保持先后顺序
+#[test_context(BaseTestContext)]
+#[test]
+fn canonical_json_is_deterministic(ctx: &mut BaseTestContext) {
+    let parser = Parser::new(ctx.config_v1_dir());
+    let config_file = ctx.config_v1_dir().join("app_normal_0_1_0.dadk");
+    let mut task = parser.parse_config_file(&config_file).unwrap();
+    let dep = |name: &str| task::Dependency::new(name.to_string(), "0.1.0".to_string());
+    let env = |key: &str, value: &str| task::TaskEnv::new(key.to_string(), value.to_string());
+    task.depends = vec![dep("zlib"), dep("app_b"), dep("app_a")];
+    task.envs = Some(vec![
+        env("PATH", "/bin"),
+        env("CC", "gcc"),
+        env("CC", "clang"),
+    ]);
+    let json = task.to_canonical_json().unwrap();
+
+    let mut reordered = task.clone();
+    reordered.depends.reverse();
+    reordered.envs = Some(vec![
+        env("CC", "gcc"),
+        env("PATH", "/bin"),
+        env("CC", "clang"),
+    ]);
+    assert_eq!(reordered.to_canonical_json().unwrap(), json);
+    assert_eq!(task.to_canonical_json().unwrap(), json);
+
+    task.canonicalize();
+    let names: Vec<&str> = task.depends.iter().map(|d| d.name.as_str()).collect();
+    assert_eq!(names, vec!["app_a", "app_b", "zlib"]);
+    assert_eq!(
+        task.envs.unwrap(),
+        vec![env("CC", "gcc"), env("CC", "clang"), env("PATH", "/bin")]
+    );
+}
+
+/// 测试本地源的相对路径相对于配置文件所在目录解析
+#[test_context(BaseTestContext)]
+#[test]
+fn local_source_relative_to_config_file(ctx: &mut BaseTestContext) {
+    let parser = Parser::new(ctx.config_v1_dir());
+    let config_file = ctx.config_v1_dir().join("app_local_relative_0_1_0.dadk");
+    let result = parser.parse_config_file(&config_file);
+    assert!(result.is_ok(), "Error: {:?}", result);
+    let result = result.unwrap();
+
+    let expected = ctx.abs_path("tests/data/apps/app_normal");
+    assert_eq!(result.source_path(), Some(expected.clone()));
+    match &result.task_type {
+        TaskType::BuildFromSource(CodeSource::Local(local)) => {
+            assert_eq!(local.path(), &expected);
+            assert_eq!(
+                local.configured_path(),
+                &PathBuf::from("../apps/./app_normal")
+            );
+        }
+        t => panic!("unexpected task type {:?}", t),
+    }
+
+    // 不存在的路径按字面解析，错误信息中同时包含原始路径和解析后的路径
+    let mut local = LocalSource::new(PathBuf::from("../apps/not_built_yet/../app"));
+    local.resolve(&ctx.config_v1_dir());
+    assert_eq!(local.path(), &ctx.abs_path("tests/data/apps/app"));
+    let e = local
+        .validate(crate::executor::source::LocalSourceKind::Any)
+        .unwrap_err();
+    assert!(e.contains("../apps/not_built_yet/../app"), "{}", e);
+    assert!(
+        e.contains(&format!(
+            "resolved to {:?}",
+            ctx.abs_path("tests/data/apps/app")
+        )),
+        "{}",
+        e
+    );
+
+    // 绝对路径只做规范化
+    let mut local = LocalSource::new(PathBuf::from("/opt/./app/../src"));
+    local.resolve(&ctx.config_v1_dir());
+    assert_eq!(local.path(), &PathBuf::from("/opt/src"));
+}
+
+/// 测试git源的`on_dirty`：默认值不写入配置，缺省时为`error`
+#[test]
+fn git_on_dirty_round_trip() {
+    let url = "https://github.com/DragonOS-Community/DADK.git";
+    let source = GitSource::new(url.to_string(), Some("master".to_string()), None);
+    let json = serde_json::to_value(&source).unwrap();
+    assert!(json.get("on_dirty").is_none(), "{}", json);
+    assert_eq!(
+        serde_json::from_value::<GitSource>(json)
+            .unwrap()
+            .on_dirty(),
+        GitDirtyPolicy::Error
+    );
+
+    let source = source.with_on_dirty(GitDirtyPolicy::Reset);
+    let json = serde_json::to_value(&source).unwrap();
+    assert_eq!(json["on_dirty"], "reset");
+    assert_eq!(serde_json::from_value::<GitSource>(json).unwrap(), source);
+}
+
+#[test_context(BaseTestContext)]
+#[test]
+fn parse_overlay_v1(ctx: &mut BaseTestContext) {
+    let parser = Parser::new(ctx.config_v1_dir());
+    let config_file = ctx.config_v1_dir().join("app_overlay_0_1_0.dadk");
+    let result = parser.parse_config_file(&config_file);
+
+    assert!(result.is_ok(), "Error: {:?}", result);
+
+    let result = result.unwrap();
+
+    assert_eq!(
+        result.task_type,
+        TaskType::BuildFromSource(CodeSource::Overlay(vec![
+            CodeSource::Local(LocalSource::new(ctx.abs_path("tests/data/apps/app_normal"))),
+            CodeSource::Local(LocalSource::new(
+                ctx.abs_path("tests/data/apps/app_overlay_patch")
+            )),
+        ]))
+    );
+    assert!(result.source_path().is_none());
+}
+
+#[test_context(BaseTestContext)]
+#[test]
+fn overlay_empty_should_failed_v1(ctx: &mut BaseTestContext) {
+    let parser = Parser::new(ctx.config_v1_dir());
+    let config_file = ctx
+        .config_v1_dir()
+        .join("app_overlay_empty_should_fail_0_1_0.dadk");
+    let result = parser.parse_config_file(&config_file);
+
+    assert!(
+        result.is_err(),
+        "parse_config_file should return error when overlay source has no layer"
+    );
+}
+
+#[test_context(BaseTestContext)]
+#[test]
+fn parse_prebuilt_oci_v1(ctx: &mut BaseTestContext) {
+    let parser = Parser::new(ctx.config_v1_dir());
+    let config_file = ctx.config_v1_dir().join("app_prebuilt_oci_0_1_0.dadk");
+    let result = parser.parse_config_file(&config_file);
+
+    assert!(result.is_ok(), "Error: {:?}", result);
+
+    let result = result.unwrap();
+
+    assert_eq!(
+        result.task_type,
+        TaskType::InstallFromPrebuilt(PrebuiltSource::Oci(OciSource::new(
+            "ghcr.io/dragonos-community/app".to_string(),
+            "sha256:6c3c624b58dbbcd3c0dd82b4c53f04194d1247c6eebdaab7c610cf7d66709b3b".to_string(),
+            Some(vec![
+                PathBuf::from("/usr/bin/app"),
+                PathBuf::from("/etc/app")
+            ]),
+        )))
+    );
+}
+
+#[test_context(BaseTestContext)]
+#[test]
+fn prebuilt_oci_invalid_reference_should_failed_v1(ctx: &mut BaseTestContext) {
+    let parser = Parser::new(ctx.config_v1_dir());
+    let config_file = ctx
+        .config_v1_dir()
+        .join("app_prebuilt_oci_invalid_reference_should_fail_0_1_0.dadk");
+    let result = parser.parse_config_file(&config_file);
+
+    assert!(
+        result.is_err(),
+        "parse_config_file should return error when the image reference is invalid"
+    );
+}
+
+#[test_context(BaseTestContext)]
+#[test]
+fn parse_release_asset_v1(ctx: &mut BaseTestContext) {
+    let parser = Parser::new(ctx.config_v1_dir());
+    let config_file = ctx.config_v1_dir().join("app_release_asset_0_1_0.dadk");
+    let result = parser.parse_config_file(&config_file);
+
+    assert!(result.is_ok(), "Error: {:?}", result);
+
+    let result = result.unwrap();
+
+    assert_eq!(
+        result.task_type,
+        TaskType::BuildFromSource(CodeSource::ReleaseAsset(ReleaseAssetSource::new(
+            "DragonOS-Community/app".to_string(),
+            "latest".to_string(),
+            "app-*-src.tar.gz".to_string(),
+        )))
+    );
+}
+
+#[test_context(BaseTestContext)]
+#[test]
+fn parse_meta_v1(ctx: &mut BaseTestContext) {
+    let parser = Parser::new(ctx.config_v1_dir());
+    let config_file = ctx.config_v1_dir().join("app_meta_0_1_0.dadk");
+    let result = parser.parse_config_file(&config_file);
+
+    assert!(result.is_ok(), "Error: {:?}", result);
+
+    let result = result.unwrap();
+
+    assert_eq!(result.task_type, TaskType::Meta);
+    assert_eq!(result.depends.len(), 2);
+}
+
+#[test_context(BaseTestContext)]
+#[test]
+fn meta_with_build_command_should_failed_v1(ctx: &mut BaseTestContext) {
+    let parser = Parser::new(ctx.config_v1_dir());
+    let config_file = ctx
+        .config_v1_dir()
+        .join("app_meta_with_build_command_should_fail_0_1_0.dadk");
+    let result = parser.parse_config_file(&config_file);
+
+    assert!(
+        result.is_err(),
+        "parse_config_file should return error when meta package has build command"
+    );
+}
+
+#[test_context(BaseTestContext)]
+#[test]
+fn parse_cargo_build_v1(ctx: &mut BaseTestContext) {
+    let parser = Parser::new(ctx.config_v1_dir());
+    let config_file = ctx.config_v1_dir().join("app_cargo_0_1_0.dadk");
+    let result = parser.parse_config_file(&config_file);
+    assert!(result.is_ok(), "Error: {:?}", result);
+
+    let task = result.unwrap();
+    assert!(task.build.build_command.is_none());
+    let cargo = task
+        .build
+        .cargo
+        .expect("cargo build config should be parsed");
+    assert_eq!(cargo.features, vec!["greet".to_string()]);
+    assert_eq!(cargo.bins, vec!["app_cargo".to_string()]);
+    assert!(!cargo.no_default_features);
+    assert_eq!(cargo.profile(), "dev");
+    assert_eq!(cargo.profile_dir(), "debug");
+}
+
+#[test_context(BaseTestContext)]
+#[test]
+fn cargo_with_build_command_should_failed_v1(ctx: &mut BaseTestContext) {
+    let parser = Parser::new(ctx.config_v1_dir());
+    let config_file = ctx
+        .config_v1_dir()
+        .join("app_cargo_with_build_command_should_fail_0_1_0.dadk");
+    let result = parser.parse_config_file(&config_file);
+
+    assert!(
+        result.is_err(),
+        "parse_config_file should return error when both build_command and cargo are set"
+    );
+}
+
+#[test_context(BaseTestContext)]
+#[test]
+fn parse_cmake_build_v1(ctx: &mut BaseTestContext) {
+    let parser = Parser::new(ctx.config_v1_dir());
+    let config_file = ctx.config_v1_dir().join("app_cmake_0_1_0.dadk");
+    let result = parser.parse_config_file(&config_file);
+    assert!(result.is_ok(), "Error: {:?}", result);
+
+    let cmake = result
+        .unwrap()
+        .build
+        .cmake
+        .expect("cmake build config should be parsed");
+    assert_eq!(
+        cmake.defines.get("ENABLE_TESTS").map(|v| v.as_str()),
+        Some("OFF")
+    );
+    assert_eq!(cmake.generator.as_deref(), Some("Unix Makefiles"));
+    assert_eq!(cmake.build_type(), "Release");
+    assert_eq!(cmake.install_prefix(), "/usr");
+}
+
+#[test_context(BaseTestContext)]
+#[test]
+fn cmake_with_build_command_should_failed_v1(ctx: &mut BaseTestContext) {
+    let parser = Parser::new(ctx.config_v1_dir());
+    let config_file = ctx
+        .config_v1_dir()
+        .join("app_cmake_with_build_command_should_fail_0_1_0.dadk");
+    let result = parser.parse_config_file(&config_file);
+
+    assert!(
+        result.is_err(),
+        "parse_config_file should return error when both build_command and cmake are set"
+    );
+}
+
+#[test_context(BaseTestContext)]
+#[test]
+fn parse_autotools_build_v1(ctx: &mut BaseTestContext) {
+    let parser = Parser::new(ctx.config_v1_dir());
+    let config_file = ctx.config_v1_dir().join("app_autotools_0_1_0.dadk");
+    let result = parser.parse_config_file(&config_file);
+    assert!(result.is_ok(), "Error: {:?}", result);
+
+    let autotools = result
+        .unwrap()
+        .build
+        .autotools
+        .expect("autotools build config should be parsed");
+    assert_eq!(autotools.configure_args, vec!["--disable-nls".to_string()]);
+    assert_eq!(autotools.make_targets, vec!["all".to_string()]);
+    assert_eq!(autotools.install_target(), "install");
+}
+
+#[test_context(BaseTestContext)]
+#[test]
+fn import_task_from_cargo_manifest(ctx: &mut BaseTestContext) {
+    let known_tasks: Vec<DADKTask> = ["app_normal_0_1_0.dadk", "app_no_source_0_1_0.dadk"]
+        .iter()
+        .map(|f| {
+            Parser::new(ctx.config_v1_dir())
+                .parse_config_file(&ctx.config_v1_dir().join(f))
+                .unwrap()
+        })
+        .collect();
+    let manifest = ctx.abs_path("tests/data/cargo_import/app_cargo/Cargo.toml");
+
+    let result =
+        cargo_import::task_from_cargo_manifest(&manifest, TargetArch::RiscV64, &known_tasks);
+    assert!(result.is_ok(), "Error: {:?}", result);
+    let mut task = result.unwrap();
+
+    assert_eq!(task.name, "app-cargo");
+    assert_eq!(task.version, "0.2.1");
+    assert_eq!(task.description, "A minimal rust app");
+    assert_eq!(
+        task.rust_target,
+        Some("riscv64gc-unknown-dragonos".to_string())
+    );
+    assert_eq!(task.target_arch, vec![TargetArch::RiscV64]);
+    assert_eq!(
+        task.task_type,
+        TaskType::BuildFromSource(CodeSource::Local(LocalSource::new(
+            ctx.abs_path("tests/data/cargo_import/app_cargo")
+        )))
+    );
+    assert_eq!(
+        task.build.build_command,
+        Some(BuildCommand::Single(
+            "cargo build --release --target riscv64gc-unknown-dragonos && cp target/riscv64gc-unknown-dragonos/release/app-cargo $DADK_CURRENT_BUILD_DIR/"
+                .to_string()
+        ))
+    );
+    // 只有与已有任务同名的依赖会被转换
+    assert_eq!(
+        task.depends,
+        vec![
+            task::Dependency::new("app_normal".to_string(), "0.1.0".to_string()),
+            task::Dependency::new("app_no_source".to_string(), "0.1.0".to_string()),
+        ]
+    );
+    assert!(task.validate().is_ok(), "{:?}", task.validate());
+}
+
+#[test_context(BaseTestContext)]
+#[test]
+fn malformed_json_error_has_location_v1(ctx: &mut BaseTestContext) {
+    let parser = Parser::new(ctx.config_v1_dir());
+    let config_file = ctx
+        .config_v1_dir()
+        .join("app_malformed_json_should_fail_0_1_0.dadk");
+    let result = parser.parse_config_file(&config_file);
+
+    assert!(result.is_err(), "malformed config file should fail");
+    let err = result.unwrap_err();
+    let location = err.location.as_ref().expect("location is missing");
+    assert_eq!(location.line, 7);
+    assert_eq!(location.snippet, "  \"build\": {");
+
+    let msg = format!("{:?}", err);
+    assert!(msg.contains(":7:"), "{msg}");
+    assert!(msg.contains("7 |   \"build\": {"), "{msg}");
+}
+
+#[test_context(BaseTestContext)]
+#[test]
+fn malformed_toml_error_has_location(ctx: &mut BaseTestContext) {
+    let manifest = ctx.abs_path("tests/data/cargo_import/app_cargo_malformed/Cargo.toml");
+    let result = cargo_import::task_from_cargo_manifest(&manifest, TargetArch::X86_64, &[]);
+
+    assert!(result.is_err(), "malformed Cargo.toml should fail");
+    let err = result.unwrap_err();
+    let location = err.location.as_ref().expect("location is missing");
+    assert_eq!(location.line, 5);
+    assert!(location.snippet.starts_with("description = "));
+
+    let msg = format!("{:?}", err);
+    assert!(msg.contains("Cargo.toml:5:"), "{msg}");
+}
+
+#[test_context(BaseTestContext)]
+#[test]
+fn parse_build_command_list_v1(ctx: &mut BaseTestContext) {
+    let parser = Parser::new(ctx.config_v1_dir());
+    let config_file = ctx
+        .config_v1_dir()
+        .join("app_build_command_list_0_1_0.dadk");
+    let result = parser.parse_config_file(&config_file);
+
+    assert!(result.is_ok(), "Error: {:?}", result);
+
+    let result = result.unwrap();
+
+    // 每条命令都被去除了首尾空白，空命令被丢弃
+    assert_eq!(
+        result.build,
+        BuildConfig::with_commands(vec![
+            "echo step1".to_string(),
+            "echo step2 > $DADK_CURRENT_BUILD_DIR/step2.txt".to_string(),
+        ])
+    );
+}
+
+#[test]
+fn build_command_round_trip() {
+    let single = BuildConfig::new(Some("make".to_string()));
+    let json = serde_json::to_string(&single).unwrap();
+    assert_eq!(json, r#"{"build_command":"make"}"#);
+    assert_eq!(serde_json::from_str::<BuildConfig>(&json).unwrap(), single);
+
+    let multiple = BuildConfig::with_commands(vec!["make".to_string(), "make install".to_string()]);
+    let json = serde_json::to_string(&multiple).unwrap();
+    assert_eq!(json, r#"{"build_command":["make","make install"]}"#);
+    assert_eq!(
+        serde_json::from_str::<BuildConfig>(&json).unwrap(),
+        multiple
+    );
+
+    // 只有空命令的列表被视为没有构建命令
+    let mut empty = BuildConfig::with_commands(vec!["  ".to_string(), "".to_string()]);
+    empty.trim();
+    assert_eq!(empty.build_command, None);
+}
+
+#[test_context(BaseTestContext)]
+#[test]
+fn unknown_key_typo_suggests_v1(ctx: &mut BaseTestContext) {
+    let parser = Parser::new(ctx.config_v1_dir());
+    let config_file = ctx
+        .config_v1_dir()
+        .join("app_unknown_key_typo_should_fail_0_1_0.dadk");
+    let result = parser.parse_config_file(&config_file);
+
+    assert!(result.is_err(), "unknown key should be rejected");
+    let msg = format!("{:?}", result.unwrap_err());
+    assert!(
+        msg.contains("unknown key 'biuld', did you mean 'build'?"),
+        "{msg}"
+    );
+}
+
+#[test_context(BaseTestContext)]
+#[test]
+fn unknown_key_without_suggestion_v1(ctx: &mut BaseTestContext) {
+    let parser = Parser::new(ctx.config_v1_dir());
+    let config_file = ctx
+        .config_v1_dir()
+        .join("app_unknown_key_should_fail_0_1_0.dadk");
+    let result = parser.parse_config_file(&config_file);
+
+    assert!(result.is_err(), "unknown key should be rejected");
+    let msg = format!("{:?}", result.unwrap_err());
+    assert!(
+        msg.contains("unknown key 'maintainer_contact_email'"),
+        "{msg}"
+    );
+    assert!(!msg.contains("did you mean"), "{msg}");
+}
+
+/// `DADKTask::KEYS`需要与结构体的字段保持一致
+#[test_context(BaseTestContext)]
+#[test]
+fn task_keys_match_fields(ctx: &mut BaseTestContext) {
+    let mut task = Parser::new(ctx.config_v1_dir())
+        .parse_config_file(&ctx.config_v1_dir().join("app_normal_0_1_0.dadk"))
+        .unwrap();
+    // 为空时不序列化的字段
+    task.arch_envs.insert(TargetArch::RiscV64, Vec::new());
+    task.profile
+        .insert("release".to_string(), task::ProfileOverride::default());
+    task.rerun_if_changed.push(PathBuf::from("include"));
+    task.enabled_if = Some("arch == \"x86_64\"".to_string());
+    task.tags.push("net".to_string());
+    task.unset.push("CFLAGS".to_string());
+    task.env_files.push(task::EnvFile {
+        path: PathBuf::from(".env"),
+        optional: true,
+        secret: false,
+    });
+    let value = serde_json::to_value(&task).unwrap();
+    let mut keys: Vec<&str> = value
+        .as_object()
+        .unwrap()
+        .keys()
+        .map(|k| k.as_str())
+        .collect();
+    let mut expected = task::DADKTask::KEYS.to_vec();
+    keys.sort();
+    expected.sort();
+    assert_eq!(keys, expected);
+}
+
+#[test]
+fn build_dir_validate() {
+    let with_dir = |dir: &str| {
+        let mut config = BuildConfig::new(Some("make".to_string()));
+        config.build_dir = Some(PathBuf::from(dir));
+        config
+    };
+
+    assert!(with_dir("build").validate().is_ok());
+    assert!(with_dir("out/../build/./x").validate().is_ok());
+    assert!(with_dir("/tmp/build").validate().is_err());
+    assert!(with_dir("..").validate().is_err());
+    assert!(with_dir("build/../../other").validate().is_err());
+}
+
+/// `work_dir`是`build_dir`的别名，同样不能是绝对路径或者跳出源码目录
+#[test]
+fn work_dir_alias() {
+    let parse = |dir: &str| {
+        serde_json::from_value::<BuildConfig>(serde_json::json!({
+            "build_command": "make",
+            "work_dir": dir,
+        }))
+        .unwrap()
+    };
+    let config = parse("sub/dir");
+    assert_eq!(config.build_dir, Some(PathBuf::from("sub/dir")));
+    assert!(config.validate().is_ok());
+    assert!(parse("/abs").validate().is_err());
+    assert!(parse("sub/../..").validate().is_err());
+
+    let both = serde_json::from_value::<BuildConfig>(serde_json::json!({
+        "build_command": "make",
+        "build_dir": "a",
+        "work_dir": "b",
+    }));
+    assert!(both.is_err());
+}
+
+#[test]
+fn empty_description_warns() {
+    let mut task = task::DADKTask::new(
+        "app".to_string(),
+        "0.1.0".to_string(),
+        "  ".to_string(),
+        None,
+        TaskType::NoSource,
+        vec![],
+        BuildConfig::new(Some("true".to_string())),
+        task::InstallConfig::new(None),
+        task::CleanConfig::new(None),
+        None,
+        false,
+        false,
+        None,
+    );
+    task.trim();
+
+    let report = task.validate_with_warnings();
+    assert!(report.errors.is_empty(), "{:?}", report);
+    assert_eq!(report.warnings.len(), 1, "{:?}", report);
+    assert!(task.validate().is_ok());
+}
+
+/// 测试设置了`install_once`但没有安装路径时给出警告，仍然可以通过校验
+#[test]
+fn install_once_without_install_path_warns() {
+    let new_task = |in_dragonos_path: Option<PathBuf>| {
+        task::DADKTask::new(
+            "app".to_string(),
+            "0.1.0".to_string(),
+            "An app".to_string(),
+            None,
+            TaskType::NoSource,
+            vec![],
+            BuildConfig::new(Some("true".to_string())),
+            task::InstallConfig::new(in_dragonos_path),
+            task::CleanConfig::new(None),
+            None,
+            false,
+            true,
+            None,
+        )
+    };
+
+    let report = new_task(None).validate_with_warnings();
+    assert!(report.errors.is_empty(), "{:?}", report);
+    assert_eq!(report.warnings.len(), 1, "{:?}", report);
+    assert!(report.warnings[0].contains("install_once"), "{:?}", report);
+
+    let report = new_task(Some(PathBuf::from("/bin"))).validate_with_warnings();
+    assert_eq!(report, task::ValidationReport::default());
+}
+
+#[test_context(BaseTestContext)]
+#[test]
+fn arch_override_merged_for_arch_v1(ctx: &mut BaseTestContext) {
+    let parser = Parser::new(ctx.config_v1_dir());
+    let config_file = ctx.config_v1_dir().join("app_arch_override_0_1_0.dadk");
+    let task = parser.parse_config_file(&config_file);
+    assert!(task.is_ok(), "Error: {:?}", task);
+    let task = task.unwrap();
+
+    let env_value = |task: &task::DADKTask, key: &str| {
+        task.envs
+            .as_ref()
+            .unwrap()
+            .iter()
+            .find(|e| e.key() == key)
+            .map(|e| e.value().to_string())
+    };
+
+    // x86_64没有覆盖配置，使用基础配置
+    let x86_64 = task.for_arch(TargetArch::X86_64);
+    assert_eq!(
+        x86_64.build.build_command,
+        Some(BuildCommand::Single("./configure && make".to_string()))
+    );
+    assert_eq!(x86_64.install.in_dragonos_path, Some(PathBuf::from("/bin")));
+    assert_eq!(env_value(&x86_64, "CC"), Some("gcc".to_string()));
+
+    let riscv64 = task.for_arch(TargetArch::RiscV64);
+    assert_eq!(
+        riscv64.build.build_command,
+        Some(BuildCommand::Single(
+            "./configure --host=riscv64 && make".to_string()
+        ))
+    );
+    assert_eq!(
+        riscv64.install.in_dragonos_path,
+        Some(PathBuf::from("/usr/bin"))
+    );
+    assert_eq!(
+        env_value(&riscv64, "CC"),
+        Some("riscv64-linux-musl-gcc".to_string())
+    );
+    assert_eq!(env_value(&riscv64, "OPT"), Some("-O2".to_string()));
+    assert_eq!(riscv64.envs.as_ref().unwrap().len(), 2);
+    assert!(riscv64.build.target_arch.is_empty());
+    assert!(riscv64.install.target_arch.is_empty());
+    assert!(riscv64.arch_envs.is_empty());
+}
+
+#[test_context(BaseTestContext)]
+#[test]
+fn arch_override_for_unlisted_arch_should_fail_v1(ctx: &mut BaseTestContext) {
+    let parser = Parser::new(ctx.config_v1_dir());
+    let config_file = ctx
+        .config_v1_dir()
+        .join("app_arch_override_unlisted_should_fail_0_1_0.dadk");
+    let result = parser.parse_config_file(&config_file);
+
+    assert!(result.is_err());
+    let msg = format!("{:?}", result.unwrap_err());
+    assert!(msg.contains("riscv64"), "{}", msg);
+}
+
+#[test_context(BaseTestContext)]
+#[test]
+fn arch_envs_filtered_for_arch_v1(ctx: &mut BaseTestContext) {
+    let parser = Parser::new(ctx.config_v1_dir());
+    let config_file = ctx.config_v1_dir().join("app_arch_envs_0_1_0.dadk");
+    let task = parser.parse_config_file(&config_file);
+    assert!(task.is_ok(), "Error: {:?}", task);
+    let task = task.unwrap();
+    let envs = |arch: TargetArch| {
+        task.for_arch(arch)
+            .envs
+            .unwrap()
+            .into_iter()
+            .map(|e| (e.key, e.value, e.target_arch))
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        envs(TargetArch::X86_64),
+        vec![
+            ("CFLAGS".to_string(), "-O2".to_string(), None),
+            ("CC".to_string(), "gcc".to_string(), None),
+            ("LDFLAGS".to_string(), "-static".to_string(), None),
+        ]
+    );
+    // 后面适用于该架构的同名环境变量覆盖前面的
+    assert_eq!(
+        envs(TargetArch::RiscV64),
+        vec![
+            (
+                "CFLAGS".to_string(),
+                "-O2 -march=rv64gc -mabi=lp64d".to_string(),
+                None
+            ),
+            ("CC".to_string(), "riscv64-linux-musl-gcc".to_string(), None),
+        ]
+    );
+
+    // 可选的字段为空时序列化不输出这一项，反序列化后仍然为空
+    let json = serde_json::to_value(&task).unwrap();
+    for key in [
+        "arch_envs",
+        "profile",
+        "rerun_if_changed",
+        "enabled_if",
+        "tags",
+        "unset",
+        "env_files",
+    ] {
+        assert!(json.get(key).is_none(), "{} in {}", key, json);
+    }
+    let parsed: DADKTask = serde_json::from_value(json).unwrap();
+    assert!(parsed.profile.is_empty() && parsed.enabled_if.is_none());
+    assert!(parsed.tags.is_empty() && parsed.env_files.is_empty());
+    let mut with_arch_envs = task.clone();
+    with_arch_envs.arch_envs.insert(
+        TargetArch::RiscV64,
+        vec![task::TaskEnv::new("CC".to_string(), "clang".to_string())],
+    );
+    let json = serde_json::to_value(&with_arch_envs).unwrap();
+    assert_eq!(json["arch_envs"]["riscv64"][0]["value"], "clang");
+    let parsed: DADKTask = serde_json::from_value(json).unwrap();
+    assert_eq!(parsed.arch_envs, with_arch_envs.arch_envs);
+
+    let config_file = ctx
+        .config_v1_dir()
+        .join("app_arch_envs_duplicate_should_fail_0_1_0.dadk");
+    let msg = format!("{:?}", parser.parse_config_file(&config_file).unwrap_err());
+    assert!(
+        msg.contains(
+            "task app_arch_envs_duplicate_should_fail_0_1_0: envs: CFLAGS is defined more than once without target_arch (\"-O2\" and \"-O3\")"
+        ),
+        "{}",
+        msg
+    );
+    assert!(msg.contains(&config_file.display().to_string()), "{}", msg);
+
+    // 只能按target_arch中列出的架构设置
+    let mut unlisted = task.clone();
+    unlisted
+        .envs
+        .as_mut()
+        .unwrap()
+        .push(task::TaskEnv::for_arch(
+            "CC".to_string(),
+            "aarch64-gcc".to_string(),
+            TargetArch::Aarch64,
+        ));
+    let msg = unlisted.validate().unwrap_err();
+    assert!(msg.contains("not in target_arch"), "{}", msg);
+}
+
+/// 测试环境变量名的校验：只允许字母、数字和`_`，`DADK_`开头的名称需要`allow_reserved`，
+/// 同一架构下重复定义时错误信息中包括两个值
+#[test_context(BaseTestContext)]
+#[test]
+fn env_keys_validated_v1(ctx: &mut BaseTestContext) {
+    let config_file = ctx.config_v1_dir().join("app_arch_envs_0_1_0.dadk");
+    let task = Parser::new(ctx.config_v1_dir())
+        .parse_config_file(&config_file)
+        .unwrap();
+    let with_env = |env: task::TaskEnv| {
+        let mut t = task.clone();
+        t.envs.as_mut().unwrap().push(env);
+        return t.validate();
+    };
+    let env = |key: &str| task::TaskEnv::new(key.to_string(), "1".to_string());
+
+    for key in ["_PRIVATE", "cc", "LD_LIBRARY_PATH2"] {
+        assert!(with_env(env(key)).is_ok(), "{}", key);
+    }
+    for key in ["MY VAR", "A=B", "1ST", "CC-X", "PATH.EXT"] {
+        let e = with_env(env(key)).unwrap_err();
+        assert!(e.contains(&format!("invalid key {:?}", key)), "{}", e);
+        assert!(e.starts_with("task app_arch_envs_0_1_0: envs:"), "{}", e);
+    }
+
+    let e = with_env(env("DADK_PKG_NAME")).unwrap_err();
+    assert!(e.contains("DADK_PKG_NAME is reserved"), "{}", e);
+    let reserved = task::TaskEnv {
+        allow_reserved: true,
+        ..env("DADK_PKG_NAME")
+    };
+    assert!(with_env(reserved).is_ok());
+
+    // 同名的环境变量只有在`target_arch`相同时才是重复定义
+    let cc = task
+        .envs
+        .as_ref()
+        .unwrap()
+        .iter()
+        .find(|e| e.key() == "CC" && e.target_arch.is_none())
+        .unwrap()
+        .clone();
+    let e = with_env(task::TaskEnv::new("CC".to_string(), "clang".to_string())).unwrap_err();
+    assert!(
+        e.contains(&format!(
+            "CC is defined more than once without target_arch ({:?} and \"clang\")",
+            cc.value()
+        )),
+        "{}",
+        e
+    );
+    let riscv = |value: &str| {
+        task::TaskEnv::for_arch(
+            "LDFLAGS".to_string(),
+            value.to_string(),
+            TargetArch::RiscV64,
+        )
+    };
+    let mut t = task.clone();
+    t.envs.as_mut().unwrap().push(riscv("-static"));
+    assert!(t.validate().is_ok());
+    t.envs.as_mut().unwrap().push(riscv("-pie"));
+    let e = t.validate().unwrap_err();
+    assert!(
+        e.contains(
+            "LDFLAGS is defined more than once for target arch riscv64 (\"-static\" and \"-pie\")"
+        ),
+        "{}",
+        e
+    );
+
+    // 保密的值不出现在错误信息中
+    let mut t = task.clone();
+    for value in ["s3cr3t-a", "s3cr3t-b"] {
+        t.envs.as_mut().unwrap().push(task::TaskEnv::secret(
+            "TOKEN".to_string(),
+            value.to_string(),
+        ));
+    }
+    let e = t.validate().unwrap_err();
+    assert!(e.contains("(\"***\" and \"***\")"), "{}", e);
+}
+
+/// 测试`prepend`/`append`：可以重复声明，合并覆盖配置时不被取代，按声明的顺序组合
+#[test_context(BaseTestContext)]
+#[test]
+fn env_op_prepend_append_v1(ctx: &mut BaseTestContext) {
+    use task::{EnvOp, TaskEnv};
+    let config_file = ctx.config_v1_dir().join("app_env_op_0_1_0.dadk");
+    let task = Parser::new(ctx.config_v1_dir())
+        .parse_config_file(&config_file)
+        .unwrap();
+    let ops: Vec<(&str, EnvOp)> = task
+        .envs
+        .as_ref()
+        .unwrap()
+        .iter()
+        .map(|e| (e.key(), e.op))
+        .collect();
+    assert_eq!(ops[0], ("PATH", EnvOp::Prepend));
+    assert_eq!(ops[3], ("APP_ENV_OP_FLAGS", EnvOp::Append));
+    assert_eq!(task.envs.as_ref().unwrap()[3].separator(), " ");
+    assert_eq!(task.envs.as_ref().unwrap()[0].separator(), ":");
+
+    let prepend = TaskEnv::with_op("PATH".to_string(), "/a".to_string(), EnvOp::Prepend);
+    let append = TaskEnv::with_op("PATH".to_string(), "/b".to_string(), EnvOp::Append);
+    assert_eq!(
+        prepend.compose("/a".to_string(), Some("/usr/bin")),
+        "/a:/usr/bin"
+    );
+    assert_eq!(
+        append.compose("/b".to_string(), Some("/usr/bin")),
+        "/usr/bin:/b"
+    );
+    assert_eq!(append.compose("/b".to_string(), Some("")), "/b");
+    assert_eq!(append.compose("/b".to_string(), None), "/b");
+    let set = TaskEnv::new("PATH".to_string(), "/c".to_string());
+    assert_eq!(set.compose("/c".to_string(), Some("/usr/bin")), "/c");
+
+    // 按架构覆盖时，`set`取代`set`，`prepend`/`append`追加在后面
+    let mut t = task.clone();
+    t.arch_envs.insert(
+        TargetArch::X86_64,
+        vec![
+            TaskEnv::new("APP_ENV_OP_FLAGS".to_string(), "-x".to_string()),
+            TaskEnv::with_op(
+                "PATH".to_string(),
+                "/opt/x86/bin".to_string(),
+                EnvOp::Prepend,
+            ),
+        ],
+    );
+    assert!(t.validate().is_ok(), "{:?}", t.validate());
+    let merged = t.for_arch(TargetArch::X86_64);
+    let envs: Vec<(&str, &str, EnvOp)> = merged
+        .envs
+        .as_ref()
+        .unwrap()
+        .iter()
+        .map(|e| (e.key(), e.value(), e.op))
+        .collect();
+    assert_eq!(
+        envs,
+        vec![
+            ("PATH", "/opt/tool-a/bin", EnvOp::Prepend),
+            ("APP_ENV_OP_FLAGS", "-x", EnvOp::Set),
+            ("PATH", "/opt/tool-b/bin", EnvOp::Prepend),
+            ("APP_ENV_OP_FLAGS", "-b", EnvOp::Append),
+            ("APP_ENV_OP_FLAGS", "-c", EnvOp::Append),
+            ("APP_ENV_OP_ONLY", "only", EnvOp::Append),
+            ("PATH", "/opt/x86/bin", EnvOp::Prepend),
+        ]
+    );
+
+    // 分隔符只用于`prepend`/`append`，并且不能为空
+    let mut t = task.clone();
+    t.envs.as_mut().unwrap()[1].separator = Some(",".to_string());
+    let e = t.validate().unwrap_err();
+    assert!(
+        e.contains("separator is only used with op prepend or append"),
+        "{}",
+        e
+    );
+    let mut t = task.clone();
+    t.envs.as_mut().unwrap()[0].separator = Some("".to_string());
+    let e = t.validate().unwrap_err();
+    assert!(e.contains("PATH: separator is empty"), "{}", e);
+}
+
+/// 测试环境变量的`phases`：阶段不重叠的同名环境变量可以分别定义，未知的阶段和重叠的阶段报错
+#[test_context(BaseTestContext)]
+#[test]
+fn env_phases_v1(ctx: &mut BaseTestContext) {
+    let config_file = ctx.config_v1_dir().join("app_env_phases_0_1_0.dadk");
+    let task = Parser::new(ctx.config_v1_dir())
+        .parse_config_file(&config_file)
+        .unwrap();
+    let envs = task.envs.as_ref().unwrap();
+    assert!(envs[0].applies_in_phase("clean"));
+    assert!(envs[1].applies_in_phase("build"));
+    assert!(!envs[1].applies_in_phase("install"));
+    assert!(!envs[1].phases_overlap(&envs[2]));
+    assert!(envs[0].phases_overlap(&envs[3]));
+
+    let mut t = task.clone();
+    t.envs.as_mut().unwrap()[3].phases = vec!["deploy".to_string()];
+    let e = t.validate().unwrap_err();
+    assert!(
+        e.contains("APP_ENV_PHASES_STAGE: unknown phase \"deploy\""),
+        "{}",
+        e
+    );
+
+    // `clean`阶段同时有两个值
+    let mut t = task.clone();
+    t.envs.as_mut().unwrap()[1].phases.push("clean".to_string());
+    let e = t.validate().unwrap_err();
+    assert!(
+        e.contains("envs: APP_ENV_PHASES_MODE is defined more than once"),
+        "{}",
+        e
+    );
+}
+
+#[test_context(BaseTestContext)]
+#[test]
+fn profile_merged_at_parse_time_v1(ctx: &mut BaseTestContext) {
+    let config_file = ctx.config_v1_dir().join("app_profile_0_1_0.dadk");
+    let task = Parser::new(ctx.config_v1_dir()).parse_config_file(&config_file);
+    assert!(task.is_ok(), "Error: {:?}", task);
+    let tasks = vec![(config_file, task.unwrap())];
+
+    let env_value = |task: &task::DADKTask, key: &str| {
+        task.envs
+            .as_ref()
+            .unwrap()
+            .iter()
+            .find(|e| e.key() == key)
+            .map(|e| e.value().to_string())
+    };
+
+    let release = Parser::apply_profile(tasks.clone(), "release").unwrap();
+    let release = &release[0].1;
+    assert_eq!(
+        release.build.build_command,
+        Some(BuildCommand::Single("make release".to_string()))
+    );
+    assert_eq!(
+        release.rust_target,
+        Some("x86_64-unknown-dragonos".to_string())
+    );
+    assert_eq!(env_value(release, "CFLAGS"), Some("-O2".to_string()));
+    assert_eq!(env_value(release, "STRIP"), Some("1".to_string()));
+    assert!(release.profile.is_empty());
+
+    let debug = Parser::apply_profile(tasks.clone(), "debug").unwrap();
+    let debug = &debug[0].1;
+    assert_eq!(
+        debug.build.build_command,
+        Some(BuildCommand::Single("make".to_string()))
+    );
+    assert_eq!(env_value(debug, "CFLAGS"), Some("-O0 -g".to_string()));
+
+    // 不同配置档的构建缓存互不相同
+    assert_eq!(tasks[0].1.cache_key(), "app_profile_0_1_0");
+    assert_eq!(release.cache_key(), "app_profile_0_1_0@release");
+    assert_ne!(release.cache_key(), debug.cache_key());
+
+    let err = Parser::apply_profile(tasks, "nightly").unwrap_err();
+    let msg = format!("{:?}", err);
+    assert!(
+        msg.contains("unknown profile 'nightly', defined profiles: [debug, release]"),
+        "{}",
+        msg
+    );
+}
+
+/// 基于app_normal生成指定名称、版本和依赖的任务
+fn task_with_depends(
+    ctx: &BaseTestContext,
+    name: &str,
+    version: &str,
+    depends: &[(&str, &str)],
+) -> (PathBuf, DADKTask) {
+    let config_file = ctx.config_v1_dir().join("app_normal_0_1_0.dadk");
+    let mut task = Parser::new(ctx.config_v1_dir())
+        .parse_config_file(&config_file)
+        .unwrap();
+    task.name = name.to_string();
+    task.version = version.to_string();
+    task.depends = depends
+        .iter()
+        .map(|(n, v)| task::Dependency::new(n.to_string(), v.to_string()))
+        .collect();
+    return (config_file, task);
+}
+
+#[test_context(BaseTestContext)]
+#[test]
+fn resolve_dependency_versions_unify(ctx: &mut BaseTestContext) {
+    let tasks = vec![
+        task_with_depends(ctx, "libfoo", "1.2.0", &[]),
+        task_with_depends(ctx, "libfoo", "1.4.0", &[]),
+        task_with_depends(ctx, "libfoo", "2.0.0", &[]),
+        task_with_depends(ctx, "app_a", "0.1.0", &[("libfoo", "^1.2")]),
+        task_with_depends(ctx, "app_b", "0.1.0", &[("libfoo", ">=1.3, <3")]),
+    ];
+
+    // 独立模式下，每个依赖方使用满足其要求的最高版本，不同的版本同时存在
+    let mut independent = tasks.clone();
+    let r = resolver::resolve_dependencies(&mut independent, ResolveMode::Independent);
+    assert!(r.is_ok(), "{:?}", r);
+    assert_eq!(independent[3].1.depends[0].version, "1.4.0");
+    assert_eq!(independent[4].1.depends[0].version, "2.0.0");
+
+    // 严格模式下，解析出多个版本时报错
+    let err = resolver::resolve_dependencies(&mut tasks.clone(), ResolveMode::Strict)
+        .expect_err("requirements resolve to 1.4.0 and 2.0.0");
+    assert!(err.contains("multiple versions [1.4.0, 2.0.0]"), "{}", err);
+
+    // 统一模式下，选择满足所有要求的最高版本
+    let mut unified = tasks.clone();
+    let r = resolver::resolve_dependencies(&mut unified, ResolveMode::Unify);
+    assert!(r.is_ok(), "{:?}", r);
+    for (_, task) in unified.iter().filter(|(_, t)| t.name.starts_with("app_")) {
+        assert_eq!(task.depends[0].version, "1.4.0");
+    }
+}
+
+#[test_context(BaseTestContext)]
+#[test]
+fn resolve_dependency_versions_conflict(ctx: &mut BaseTestContext) {
+    let mut tasks = vec![
+        task_with_depends(ctx, "libfoo", "0.1.0", &[]),
+        task_with_depends(ctx, "libfoo", "1.0.0", &[]),
+        task_with_depends(ctx, "app_a", "0.1.0", &[("libfoo", "0.1.0")]),
+        task_with_depends(ctx, "app_b", "0.1.0", &[("libfoo", "^1.0")]),
+    ];
+    let err = resolver::resolve_dependencies(&mut tasks, ResolveMode::Unify)
+        .expect_err("no version satisfies both requirements");
+    assert!(err.contains("available: [0.1.0, 1.0.0]"), "{}", err);
+    assert!(err.contains("app_a_0_1_0 requires 0.1.0"), "{}", err);
+    assert!(err.contains("app_b_0_1_0 requires ^1.0"), "{}", err);
+
+    // 独立模式下，两个版本可以同时存在
+    let r = resolver::resolve_dependencies(&mut tasks, ResolveMode::Independent);
+    assert!(r.is_ok(), "{:?}", r);
+    assert_eq!(tasks[3].1.depends[0].version, "1.0.0");
+
+    // 严格模式下，两个依赖方固定了不同的版本时报错，列出所有依赖方
+    let mut tasks = vec![
+        task_with_depends(ctx, "libfoo", "0.1.0", &[]),
+        task_with_depends(ctx, "libfoo", "0.2.0", &[]),
+        task_with_depends(ctx, "app_a", "0.1.0", &[("libfoo", "0.1.0")]),
+        task_with_depends(ctx, "app_b", "0.1.0", &[("libfoo", "0.2.0")]),
+    ];
+    let err = resolver::resolve_dependencies(&mut tasks, ResolveMode::Strict)
+        .expect_err("0.1.0 and 0.2.0 are both required");
+    assert!(err.contains("multiple versions [0.1.0, 0.2.0]"), "{}", err);
+    assert!(err.contains("app_a_0_1_0 requires 0.1.0"), "{}", err);
+    assert!(err.contains("app_b_0_1_0 requires 0.2.0"), "{}", err);
+
+    // 没有版本满足的版本范围报错
+    let mut tasks = vec![
+        task_with_depends(ctx, "libfoo", "0.1.0", &[]),
+        task_with_depends(ctx, "app_a", "0.1.0", &[("libfoo", "^2")]),
+    ];
+    let err = resolver::resolve_dependencies(&mut tasks, ResolveMode::Strict)
+        .expect_err("no version satisfies ^2");
+    assert!(err.contains("satisfies '^2'"), "{}", err);
+    assert!(err.contains("app_a_0_1_0 requires ^2"), "{}", err);
+}
+
+/// 相同的版本要求的不同写法不冲突；名称按规范化的形式比较；固定了架构的依赖单独解析
+#[test_context(BaseTestContext)]
+#[test]
+fn resolve_dependency_versions_equivalent_and_pinned(ctx: &mut BaseTestContext) {
+    let mut riscv = task_with_depends(ctx, "libfoo", "2.0.0", &[]);
+    riscv.1.target_arch.push(TargetArch::RiscV64);
+    let mut tasks = vec![
+        task_with_depends(ctx, "libfoo", "0.1.0", &[]),
+        riscv,
+        task_with_depends(ctx, "app_a", "0.1.0", &[("libfoo", "0.1.0")]),
+        task_with_depends(ctx, "app_b", "0.1.0", &[("LibFoo", "=0.1.0")]),
+        task_with_depends(ctx, "app_c", "0.1.0", &[("libfoo", "*")]),
+    ];
+    tasks[4].1.depends[0].arch = Some(TargetArch::RiscV64);
+
+    let r = resolver::resolve_dependencies(&mut tasks, ResolveMode::Unify);
+    assert!(r.is_ok(), "{:?}", r);
+    assert_eq!(tasks[2].1.depends[0].version, "0.1.0");
+    assert_eq!(tasks[3].1.depends[0].version, "0.1.0");
+    assert_eq!(tasks[4].1.depends[0].version, "2.0.0");
+}
+
+#[test]
+fn version_requirements() {
+    let req = |r: &str| resolver::VersionReq::parse(r).unwrap();
+    assert!(req("^0.2.3").matches("0.2.9"));
+    assert!(!req("^0.2.3").matches("0.3.0"));
+    assert!(req("~1.2").matches("1.2.7"));
+    assert!(!req("~1.2").matches("1.3.0"));
+    assert!(req("*").matches("anything"));
+    assert!(req("0.1.0").matches("0.1.0"));
+    assert!(!req("0.1.0").matches("0.1"));
+    assert!(!req(">=1.0").matches("nightly"));
+    assert!(resolver::VersionReq::parse("^abc").is_err());
+}
+
+/// 测试安装权限的校验：只接受八进制的权限
+#[test]
+fn install_mode_validate() {
+    assert_eq!(task::InstallConfig::parse_mode("0755"), Ok(0o755));
+    assert_eq!(task::InstallConfig::parse_mode("644"), Ok(0o644));
+    assert_eq!(task::InstallConfig::parse_mode("0o4755"), Ok(0o4755));
+    for bad in ["", "0x755", "0789", "rwxr-xr-x", "17777"] {
+        assert!(task::InstallConfig::parse_mode(bad).is_err(), "{:?}", bad);
+    }
+
+    let mut install = task::InstallConfig::new(Some(PathBuf::from("/bin")));
+    install.mode = Some("0755".to_string());
+    assert!(install.validate().is_ok());
+    install.mode = Some("0999".to_string());
+    let err = install.validate().unwrap_err();
+    assert!(err.contains("mode"), "{}", err);
+
+    install.mode = None;
+    install
+        .modes
+        .insert("*.conf".to_string(), "u+x".to_string());
+    let err = install.validate().unwrap_err();
+    assert!(err.contains("*.conf"), "{}", err);
+}
+
+/// 测试`install.files`的校验
+#[test]
+fn install_files_validate() {
+    let entry = |src: &str, dest: &str, mode: Option<&str>| task::InstallEntry {
+        src: PathBuf::from(src),
+        dest: PathBuf::from(dest),
+        mode: mode.map(|m| m.to_string()),
+        uid: None,
+        gid: None,
+    };
+    let mut install = task::InstallConfig::new(None);
+    install.files = vec![
+        entry("bin/foo", "/usr/bin/foo", Some("0755")),
+        entry("etc/foo.conf", "/etc/foo.conf", Some("0o644")),
+        entry("share", "/usr/share/foo", None),
+    ];
+    assert!(install.validate().is_ok());
+
+    for (bad, expected) in [
+        (
+            entry("bin/foo", "usr/bin/foo", None),
+            "files[3]: dest should be an absolute path",
+        ),
+        (
+            entry("bin/foo", "/usr/bin/foo", Some("0o17777")),
+            "out of range",
+        ),
+        (
+            entry("bin/foo", "/usr/bin/foo", Some("rwx")),
+            "invalid octal file mode",
+        ),
+        (
+            entry("/bin/foo", "/usr/bin/foo", None),
+            "src should be a relative path",
+        ),
+        (
+            entry("../foo", "/usr/bin/foo", None),
+            "escapes the build output",
+        ),
+    ] {
+        let mut install = install.clone();
+        install.files.push(bad);
+        let err = install.validate().unwrap_err();
+        assert!(err.contains(expected), "{}", err);
+    }
+}
+
+/// 测试安装到DragonOS内的路径的校验：不能包含`.`、`..`和空的部分，不能安装到禁止的路径
+#[test]
+fn install_dragonos_path_validate() {
+    let install = |path: &str| task::InstallConfig::new(Some(PathBuf::from(path)));
+    for path in ["/", "/usr", "/usr/bin/", "/opt/${NAME}", "/device"] {
+        let r = install(path).validate();
+        assert!(r.is_ok(), "{}: {:?}", path, r);
+    }
+    for (path, expected) in [
+        ("/usr/../etc", "contains '..'"),
+        ("/usr/./bin", "contains '.'"),
+        ("/usr//bin", "contains an empty component"),
+        ("/proc", "is a reserved path (/proc)"),
+        ("/sys/kernel", "is a reserved path (/sys)"),
+        ("/dev/", "is a reserved path (/dev)"),
+    ] {
+        let err = install(path).validate().unwrap_err();
+        assert!(
+            err.contains("in_dragonos_path") && err.contains(expected),
+            "{}: {}",
+            path,
+            err
+        );
+    }
+
+    // 构建结果目录可以合并到`/`中，但是文件不能替换根目录
+    let mut files = install("/usr");
+    files.files.push(task::InstallEntry {
+        src: PathBuf::from("bin/foo"),
+        dest: PathBuf::from("/"),
+        mode: None,
+        uid: None,
+        gid: None,
+    });
+    let err = files.validate().unwrap_err();
+    assert!(
+        err.contains("files[0]: dest:") && err.contains("reserved"),
+        "{}",
+        err
+    );
+
+    let mut destinations = install("/usr");
+    destinations.destinations.push(task::InstallDestination {
+        src_subdir: PathBuf::from("lib"),
+        in_dragonos_path: PathBuf::from("/lib/../../etc"),
+    });
+    let err = destinations.validate().unwrap_err();
+    assert!(
+        err.contains("destinations[0]") && err.contains("'..'"),
+        "{}",
+        err
+    );
+
+    // 禁止的路径可以配置（这里在默认的路径之外增加，避免影响并行执行的其他测试）
+    let mut denied = task::InstallConfig::denied_paths();
+    denied.push(PathBuf::from("/opt/reserved"));
+    task::InstallConfig::set_denied_paths(denied);
+    let err = install("/opt/reserved/app").validate().unwrap_err();
+    assert!(err.contains("reserved path (/opt/reserved)"), "{}", err);
+    task::InstallConfig::set_denied_paths(
+        task::InstallConfig::DEFAULT_DENIED_PATHS
+            .iter()
+            .map(PathBuf::from)
+            .collect(),
+    );
+    assert!(install("/opt/reserved/app").validate().is_ok());
+}
+
+/// 测试资源限制的解析和校验
+#[test]
+fn resource_limits_validate() {
+    let mut build = task::BuildConfig::new(Some("make".to_string()));
+    assert!(build.resource_limits().unwrap().is_empty());
+
+    build.max_memory = Some("512M".to_string());
+    build.cpu_time_limit = Some(60);
+    build.nice = Some(10);
+    let limits = build.resource_limits().unwrap();
+    assert_eq!(limits.max_memory, Some(512 << 20));
+    assert_eq!(
+        limits.to_string(),
+        "max_memory=512M, cpu_time_limit=60s, nice=10"
+    );
+    assert!(build.validate().is_ok());
+
+    build.max_memory = Some("2 GiB".to_string());
+    assert_eq!(build.resource_limits().unwrap().max_memory, Some(2 << 30));
+
+    for bad in ["", "0", "12X", "-1M", "99999999999T"] {
+        build.max_memory = Some(bad.to_string());
+        let err = build.validate().unwrap_err();
+        assert!(err.contains("max_memory"), "{:?}: {}", bad, err);
+    }
+    build.max_memory = None;
+
+    build.nice = Some(20);
+    let err = build.validate().unwrap_err();
+    assert!(err.contains("nice"), "{}", err);
+    build.nice = None;
+
+    build.cpu_time_limit = Some(0);
+    let err = build.validate().unwrap_err();
+    assert!(err.contains("cpu_time_limit"), "{}", err);
+}
+
+/// 测试启用条件的求值
+#[test]
+fn enabled_if_eval() {
+    use condition::Condition;
+
+    let env = |key: &str| match key {
+        "FEATURE_X" => Some("1".to_string()),
+        _ => None,
+    };
+    let eval = |expr: &str, arch: TargetArch| Condition::parse(expr).unwrap().eval(arch, &env);
+
+    let expr = r#"arch == "x86_64" && env.FEATURE_X == "1""#;
+    assert!(eval(expr, TargetArch::X86_64));
+    assert!(!eval(expr, TargetArch::RiscV64));
+
+    assert!(eval(r#"env.FEATURE_Y == """#, TargetArch::X86_64));
+    assert!(eval(
+        r#"!(arch == "riscv64" || env.FEATURE_Y != "") && true"#,
+        TargetArch::X86_64
+    ));
+    assert!(!eval(r#"false || arch != "x86_64""#, TargetArch::X86_64));
+    // `&&`的优先级高于`||`
+    assert!(eval(
+        r#"arch == "x86_64" || arch == "riscv64" && false"#,
+        TargetArch::X86_64
+    ));
+    assert!(eval(r#""a\"b" == "a\"b""#, TargetArch::X86_64));
+}
+
+/// 测试格式错误的启用条件
+#[test]
+fn enabled_if_malformed() {
+    use condition::Condition;
+
+    for (expr, expected) in [
+        ("", "empty"),
+        (r#"arch == "x86""#, "unknown arch \"x86\""),
+        (r#"arch = "x86_64""#, "unexpected character '='"),
+        (r#"arch == "x86_64" &&"#, "found end of expression"),
+        (r#"arch"#, "expected `==` or `!=`"),
+        (r#"os == "linux""#, "unknown identifier `os`"),
+        (r#"env. == "1""#, "missing environment variable name"),
+        (r#"(arch == "x86_64""#, "expected `)`"),
+        (r#"arch == "x86_64")"#, "unexpected `)` at 16"),
+        (r#"env.A == "1"#, "unterminated string"),
+        (r#"env.A == env.B env.C"#, "unexpected `env.C`"),
+    ] {
+        let err = Condition::parse(expr).unwrap_err();
+        assert!(err.contains(expected), "{:?}: {}", expr, err);
+    }
+
+    let mut task = DADKTask::new(
+        "app".to_string(),
+        "0.1.0".to_string(),
+        "app".to_string(),
+        None,
+        TaskType::NoSource,
+        vec![],
+        BuildConfig::new(Some("make".to_string())),
+        task::InstallConfig::new(None),
+        task::CleanConfig::new(None),
+        None,
+        false,
+        false,
+        None,
+    );
+    task.enabled_if = Some("arch ==".to_string());
+    let err = task.validate().unwrap_err();
+    assert!(err.contains("enabled_if"), "{}", err);
+}
+
+/// 测试`${...}`变量的替换和转义
+#[test]
+fn interpolate_variables() {
+    let lookup = |name: &str| Ok(format!("<{}>", name));
+    let s = "cp ${NAME_VERSION}.bin ${DADK_BUILD_DIR}/${ARCH}/ && echo $HOME $${HOME} $$";
+    assert_eq!(
+        interpolate::interpolate(s, &lookup).unwrap(),
+        "cp <NAME_VERSION>.bin <DADK_BUILD_DIR>/<ARCH>/ && echo $HOME ${HOME} $$"
+    );
+
+    std::env::set_var("DADK_TEST_INTERPOLATE_HOST_ENV", "host");
+    assert_eq!(
+        interpolate::interpolate("${env:DADK_TEST_INTERPOLATE_HOST_ENV}-${VERSION}", &lookup)
+            .unwrap(),
+        "host-<VERSION>"
+    );
+    let err = interpolate::interpolate("${env:DADK_TEST_INTERPOLATE_UNSET}", &lookup).unwrap_err();
+    assert!(err.contains("is not set"), "{}", err);
+
+    // 默认值在主机环境变量没有设置或者为空时使用
+    std::env::set_var("DADK_TEST_INTERPOLATE_EMPTY", "");
+    assert_eq!(
+        interpolate::interpolate(
+            "${env:DADK_TEST_INTERPOLATE_UNSET:-/usr/lib}:${env:DADK_TEST_INTERPOLATE_EMPTY:-x}:${env:DADK_TEST_INTERPOLATE_HOST_ENV:-y}:${env:DADK_TEST_INTERPOLATE_UNSET:-}",
+            &lookup
+        )
+        .unwrap(),
+        "/usr/lib:x:host:"
+    );
+
+    // 依赖的构建结果目录
+    assert_eq!(
+        interpolate::interpolate("${dep:libc}/lib", &lookup).unwrap(),
+        "<dep:libc>/lib"
+    );
+    assert_eq!(
+        interpolate::referenced_dependencies("${dep:libc} ${dep:zlib} ${NAME}").unwrap(),
+        vec!["libc", "zlib"]
+    );
+    let failing = |_: &str| Err("lookup failed".to_string());
+    assert_eq!(
+        interpolate::interpolate("${dep:libc}", &failing).unwrap_err(),
+        "lookup failed"
+    );
+
+    for (s, expected) in [
+        (
+            "make ${SRC_DIR}",
+            "unknown variable ${SRC_DIR} in \"make ${SRC_DIR}\"",
+        ),
+        ("echo ${ARCH", "unterminated ${"),
+        ("${env:}", "invalid environment variable name"),
+        ("${env:A-B}", "invalid environment variable name"),
+        ("${env::-x}", "invalid environment variable name"),
+        ("${dep:}", "empty dependency name"),
+        ("${env:PATH:-${NAME}", "cannot reference variables"),
+    ] {
+        let err = interpolate::validate(s).unwrap_err();
+        assert!(err.contains(expected), "{:?}: {}", s, err);
+    }
+}
+
+/// 引用未知变量的配置文件解析失败，错误信息中带有出错的字符串和配置文件
+#[test_context(BaseTestContext)]
+#[test]
+fn interpolate_unknown_variable_should_fail(ctx: &mut BaseTestContext) {
+    let parser = Parser::new(ctx.config_v1_dir());
+    let config_file = ctx
+        .config_v1_dir()
+        .join("app_interpolate_unknown_0_1_0.dadk");
+    let err = format!("{:?}", parser.parse_config_file(&config_file).unwrap_err());
+    assert!(err.contains("unknown variable ${SRC_DIR}"), "{}", err);
+    assert!(err.contains("make -C ${SRC_DIR}"), "{}", err);
+    assert!(
+        err.contains("app_interpolate_unknown_0_1_0.dadk"),
+        "{}",
+        err
+    );
+}
+
+/// `${dep:名称}`只能引用`depends`中的依赖
+#[test_context(BaseTestContext)]
+#[test]
+fn interpolate_dependency_must_be_in_depends(ctx: &mut BaseTestContext) {
+    let parser = Parser::new(ctx.config_v1_dir());
+    let config_file = ctx.config_v1_dir().join("app_env_expand_dep_0_1_0.dadk");
+    let mut task = parser.parse_config_file(&config_file).unwrap();
+    assert!(task.validate().is_ok());
+    task.depends.clear();
+    let err = task.validate().unwrap_err();
+    assert!(
+        err.contains("${dep:app_env_expand_missing}") && err.contains("is not in depends"),
+        "{}",
+        err
+    );
+}
+
+/// 测试`install.strip`接受布尔值和"debug"/"all"
+#[test]
+fn install_strip_mode() {
+    let parse = |strip: &str| {
+        serde_json::from_str::<task::InstallConfig>(&format!(
+            r#"{{ "in_dragonos_path": "/bin", "strip": {} }}"#,
+            strip
+        ))
+        .map(|c| c.strip)
+    };
+    assert_eq!(parse("false").unwrap(), task::StripMode::None);
+    assert_eq!(parse("true").unwrap(), task::StripMode::All);
+    assert_eq!(parse(r#""all""#).unwrap(), task::StripMode::All);
+    assert_eq!(parse(r#""debug""#).unwrap(), task::StripMode::Debug);
+    let e = parse(r#""symbols""#).unwrap_err().to_string();
+    assert!(e.contains("invalid strip mode"), "{}", e);
+
+    let install: task::InstallConfig =
+        serde_json::from_str(r#"{ "in_dragonos_path": "/bin" }"#).unwrap();
+    assert!(!install.strip.is_enabled());
+    assert!(!install.strict_strip);
+    let json = serde_json::to_value(&install).unwrap();
+    assert!(json.get("strip").is_none() && json.get("strict_strip").is_none());
+
+    let mut install = install;
+    install.strip = task::StripMode::Debug;
+    let json = serde_json::to_value(&install).unwrap();
+    assert_eq!(json["strip"], "debug");
+    assert_eq!(task::StripMode::Debug.args(), ["--strip-debug"]);
+}
+
+/// 测试`install.include`优先，`exclude`在其选中的文件中排除
+#[test]
+fn install_filter_include_and_exclude() {
+    let install: task::InstallConfig = serde_json::from_str(
+        r#"{ "in_dragonos_path": "/bin", "include": ["bin", "lib/**"], "exclude": ["**/*.o"] }"#,
+    )
+    .unwrap();
+    let filter = install.install_filter().unwrap();
+    assert!(filter.selects(Path::new("bin/app")));
+    assert!(filter.selects(Path::new("lib/x86_64/libapp.so")));
+    assert!(!filter.selects(Path::new("lib/libapp.o")));
+    assert!(!filter.selects(Path::new("obj/app.c")));
+    // `files`中的目录只应用`exclude`
+    assert!(filter.exclude_only().selects(Path::new("obj/app.c")));
+    assert!(!filter.exclude_only().selects(Path::new("app.o")));
+
+    let mut install = install;
+    install.exclude = vec![" ".to_string()];
+    let e = install.validate().unwrap_err();
+    assert!(e.contains("exclude"), "{}", e);
+}
+
+/// 测试`install.destinations`的校验：重复或互相包含的安装目录在错误信息中给出两项
+#[test]
+fn install_destinations_validate() {
+    let dest = |src: &str, path: &str| task::InstallDestination {
+        src_subdir: PathBuf::from(src),
+        in_dragonos_path: PathBuf::from(path),
+    };
+    let mut install = task::InstallConfig::new(None);
+    install.destinations = vec![dest("bin", "/usr/bin"), dest("share", "/usr/share/app")];
+    assert!(install.validate().is_ok());
+
+    install.destinations = vec![dest("bin", "/usr/bin"), dest("sbin", "/usr/bin")];
+    let e = install.validate().unwrap_err();
+    assert!(e.contains("destinations[0] (bin -> /usr/bin)"), "{}", e);
+    assert!(e.contains("destinations[1] (sbin -> /usr/bin)"), "{}", e);
+
+    install.destinations = vec![dest("share", "/usr/share"), dest("doc", "/usr/share/doc")];
+    let e = install.validate().unwrap_err();
+    assert!(e.contains("destinations[0] (share -> /usr/share)"), "{}", e);
+    assert!(
+        e.contains("destinations[1] (doc -> /usr/share/doc)"),
+        "{}",
+        e
+    );
+
+    // 与`in_dragonos_path`重叠
+    install.in_dragonos_path = Some(PathBuf::from("/usr"));
+    install.destinations = vec![dest("bin", "/usr/local/bin")];
+    let e = install.validate().unwrap_err();
+    assert!(e.contains("in_dragonos_path (/usr)"), "{}", e);
+    assert!(
+        e.contains("destinations[0] (bin -> /usr/local/bin)"),
+        "{}",
+        e
+    );
+
+    // 前缀相同但不是父目录的路径不算重叠
+    install.destinations = vec![dest("bin", "/usrlocal/bin")];
+    assert!(install.validate().is_ok());
+
+    install.in_dragonos_path = None;
+    install.destinations = vec![dest("../bin", "/usr/bin")];
+    assert!(install.validate().unwrap_err().contains("destinations[0]"));
+    install.destinations = vec![dest("bin", "usr/bin")];
+    assert!(install.validate().unwrap_err().contains("absolute"));
+}
+
+/// 测试`install.allow_overwrite`的校验和匹配
+#[test]
+fn install_allow_overwrite_validate() {
+    let mut install = task::InstallConfig::new(None);
+    install.allow_overwrite = vec!["/usr/lib/libfoo.so*".to_string(), "/etc/app".to_string()];
+    assert!(install.validate().is_ok());
+    let matcher = install.overwrite_matcher().unwrap();
+    assert!(matcher.is_ignored(Path::new("usr/lib/libfoo.so.1")));
+    assert!(matcher.is_ignored(Path::new("etc/app/app.conf")));
+    assert!(!matcher.is_ignored(Path::new("usr/lib/libbar.so")));
+
+    install.allow_overwrite = vec!["usr/lib/libfoo.so".to_string()];
+    let e = install.validate().unwrap_err();
+    assert!(e.contains("allow_overwrite"), "{}", e);
+    assert!(e.contains("absolute"), "{}", e);
+}
+
+/// 测试`install.symlinks`的校验
+#[test]
+fn install_symlinks_validate() {
+    let symlink = |link: &str, target: &str| task::InstallSymlink {
+        link: PathBuf::from(link),
+        target: PathBuf::from(target),
+        force: false,
+    };
+    let mut install = task::InstallConfig::new(None);
+    install.symlinks = vec![
+        symlink("/usr/bin/sh", "bash"),
+        symlink("/bin/ls", "/bin/busybox"),
+    ];
+    assert!(install.validate().is_ok());
+
+    for (bad, expected) in [
+        (
+            symlink("usr/bin/sh", "bash"),
+            "link should be an absolute path",
+        ),
+        (symlink("/", "bash"), "should not be the root directory"),
+        (symlink("/usr/bin/sh", ""), "target of /usr/bin/sh is empty"),
+    ] {
+        let mut install = install.clone();
+        install.symlinks.push(bad);
+        let e = install.validate().unwrap_err();
+        assert!(e.contains("symlinks[2]") && e.contains(expected), "{}", e);
+    }
+}
+
+/// 测试解析各清理级别的配置，以及`clean_command`与`levels.output.command`不能同时设置
+#[test_context(BaseTestContext)]
+#[test]
+fn parse_clean_levels_v1(ctx: &mut BaseTestContext) {
+    let parser = Parser::new(ctx.config_v1_dir());
+    let config_file = ctx.config_v1_dir().join("app_clean_levels_0_1_0.dadk");
+    let result = parser.parse_config_file(&config_file);
+    assert!(result.is_ok(), "Error: {:?}", result);
+    let clean = result.unwrap().clean;
+
+    assert_eq!(clean.command("output"), None);
+    assert_eq!(clean.command("src"), Some(&"rm -f src.txt".to_string()));
+    assert!(clean.remove_dirs("output"));
+    assert!(!clean.remove_dirs("src"));
+    assert!(!clean.remove_dirs("cache"));
+    assert!(clean.remove_dirs("all"));
+    assert_eq!(clean.commands(), vec!["rm -f src.txt".to_string()]);
+
+    // `clean_command`是`output`级别的命令
+    let legacy = task::CleanConfig::new(Some("make clean".to_string()));
+    assert_eq!(legacy.command("output"), Some(&"make clean".to_string()));
+    assert!(!legacy.remove_dirs("output"));
+    assert!(legacy.remove_dirs("src"));
+
+    let config_file = ctx
+        .config_v1_dir()
+        .join("app_clean_levels_both_commands_should_fail_0_1_0.dadk");
+    let e = parser.parse_config_file(&config_file).unwrap_err();
+    assert!(format!("{:?}", e).contains("cannot be both set"), "{:?}", e);
+}
+
+/// 测试解析清理命令的超时时间和失败时的处理方式
+#[test_context(BaseTestContext)]
+#[test]
+fn parse_clean_timeout_v1(ctx: &mut BaseTestContext) {
+    let parser = Parser::new(ctx.config_v1_dir());
+    let parse = |file: &str| {
+        let result = parser.parse_config_file(&ctx.config_v1_dir().join(file));
+        assert!(result.is_ok(), "Error: {:?}", result);
+        return result.unwrap().clean;
+    };
+
+    let clean = parse("app_clean_timeout_0_1_0.dadk");
+    assert_eq!(clean.timeout, Some(1));
+    assert_eq!(clean.on_failure, task::CleanFailurePolicy::Continue);
+    assert_eq!(
+        parse("app_clean_fail_abort_0_1_0.dadk").on_failure,
+        task::CleanFailurePolicy::Abort
+    );
+    assert_eq!(
+        parse("app_clean_fail_ignore_0_1_0.dadk").on_failure,
+        task::CleanFailurePolicy::Ignore
+    );
+
+    let mut clean = task::CleanConfig::new(Some("make clean".to_string()));
+    clean.timeout = Some(0);
+    let e = clean.validate().unwrap_err();
+    assert!(e.contains("timeout should be greater than 0"), "{}", e);
+}
+
+/// 测试解析任务的标签
+#[test_context(BaseTestContext)]
+#[test]
+fn parse_tags_v1(ctx: &mut BaseTestContext) {
+    let parser = Parser::new(ctx.config_v1_dir());
+    let result = parser.parse_config_file(&ctx.config_v1_dir().join("app_tags_0_1_0.dadk"));
+    assert!(result.is_ok(), "Error: {:?}", result);
+    let mut task = result.unwrap();
+    assert_eq!(task.tags, vec!["net".to_string(), "host".to_string()]);
+
+    task.tags.push(" ".to_string());
+    task.trim();
+    let e = task.validate().unwrap_err();
+    assert!(e.contains("tags contains an empty tag"), "{}", e);
+}
+
+/// 测试工作区配置中的全局环境变量：任务继承去掉`unset`之后的全局环境变量
+#[test_context(BaseTestContext)]
+#[test]
+fn parse_workspace_global_envs(ctx: &mut BaseTestContext) {
+    let mut parser = Parser::new(ctx.abs_path("tests/data/dadk_workspace_v1"));
+    let tasks = parser.parse();
+    assert!(tasks.is_ok(), "Error: {:?}", tasks);
+    let tasks = tasks.unwrap();
+    assert_eq!(tasks.len(), 1);
+    let task = &tasks[0].1;
+    assert_eq!(
+        task.unset,
+        vec!["DROP_ME".to_string(), "NOT_GLOBAL".to_string()]
+    );
+    let keys: Vec<&str> = task.global_envs.iter().map(|e| e.key()).collect();
+    assert_eq!(keys, vec!["RUSTFLAGS", "CC", "GLOBAL_PKG"]);
+
+    // 全局环境变量的哈希值只与内容有关，与顺序无关
+    let hash = task.global_envs_hash();
+    assert!(hash.is_some());
+    let mut reordered = task.clone();
+    reordered.global_envs.reverse();
+    assert_eq!(reordered.global_envs_hash(), hash);
+    let mut changed = task.clone();
+    changed.global_envs[0].value = "-C opt-level=3".to_string();
+    assert_ne!(changed.global_envs_hash(), hash);
+    changed.global_envs.clear();
+    assert_eq!(changed.global_envs_hash(), None);
+
+    // 没有工作区配置时，任务不继承任何环境变量
+    let workspace = workspace::WorkspaceConfig::load(&ctx.config_v1_dir()).unwrap();
+    assert_eq!(workspace, workspace::WorkspaceConfig::default());
+}
+
+/// 测试工作区配置的校验
+#[test]
+fn workspace_config_validate() {
+    let config = |envs: &[(&str, &str)]| workspace::WorkspaceConfig {
+        global_envs: envs
+            .iter()
+            .map(|(k, v)| task::TaskEnv::new(k.to_string(), v.to_string()))
+            .collect(),
+        env_files: vec![],
+        ..Default::default()
+    };
+    assert!(config(&[("CC", "gcc"), ("PATH", "${env:PATH}")])
+        .validate()
+        .is_ok());
+
+    let e = config(&[("CC", "gcc"), ("CC", "clang")])
+        .validate()
+        .unwrap_err();
+    assert_eq!(e, "global_envs: CC is defined more than once");
+    let e = config(&[("", "gcc")]).validate().unwrap_err();
+    assert!(e.contains("key is empty"), "{}", e);
+    let e = config(&[("LIBC", "${dep:libc}")]).validate().unwrap_err();
+    assert!(
+        e.contains("cannot reference dependency ${dep:libc}"),
+        "{}",
+        e
+    );
+    let e = config(&[("X", "${UNKNOWN}")]).validate().unwrap_err();
+    assert!(e.starts_with("global_envs: X:"), "{}", e);
+
+    let e = toml::from_str::<workspace::WorkspaceConfig>(
+        "[[global_env]]\nkey = \"A\"\nvalue = \"1\"\n",
+    )
+    .unwrap_err();
+    assert!(e.to_string().contains("unknown field"), "{}", e);
+}
+
+/// 测试工作区配置中的`dep_envs`以及依赖的`env_alias`的校验
+#[test]
+fn dep_envs_config() {
+    let config: workspace::WorkspaceConfig = toml::from_str("").unwrap();
+    assert_eq!(config.dep_envs.prefix, "DADK_DEP_");
+    assert_eq!(config.dep_envs.naming, task::DepEnvNaming::Sanitized);
+
+    let config: workspace::WorkspaceConfig =
+        toml::from_str("[dep_envs]\nprefix = \"DEP_\"\nnaming = \"verbatim\"\n").unwrap();
+    assert!(config.validate().is_ok());
+    let dep = task::Dependency::new("lib-foo.bar".to_string(), "0.1.0".to_string());
+    assert_eq!(config.dep_envs.env_key(&dep), "DEP_lib-foo.bar");
+    assert_eq!(
+        task::DepEnvConfig::default().env_key(&dep),
+        "DADK_DEP_LIB_FOO_BAR"
+    );
+
+    let config: workspace::WorkspaceConfig =
+        toml::from_str("[dep_envs]\nprefix = \"1-DEP\"\n").unwrap();
+    let e = config.validate().unwrap_err();
+    assert!(e.starts_with("dep_envs: invalid prefix"), "{}", e);
+    let e = toml::from_str::<workspace::WorkspaceConfig>("[dep_envs]\nnaming = \"lower\"\n")
+        .unwrap_err();
+    assert!(e.to_string().contains("unknown variant"), "{}", e);
+
+    let mut dep = task::Dependency::new("libfoo".to_string(), "0.1.0".to_string());
+    dep.env_alias = Some("LIBFOO_DIR".to_string());
+    assert!(dep.validate().is_ok());
+    dep.env_alias = Some("LIBFOO-DIR".to_string());
+    let e = dep.validate().unwrap_err();
+    assert!(e.contains("invalid env_alias"), "{}", e);
+    let mut dep = task::Dependency::system("cmake".to_string(), "*".to_string(), None);
+    dep.env_alias = Some("CMAKE_DIR".to_string());
+    let e = dep.validate().unwrap_err();
+    assert!(e.contains("env_alias is not allowed"), "{}", e);
+}
+
+/// 测试默认的目标架构：宽松模式下没有设置`ARCH`时为x86_64，严格模式（`DADK_REQUIRE_ARCH`）下报错
+#[test]
+fn default_target_arch_strict_and_lenient() {
+    let resolve = task::DADKTask::resolve_default_target_arch;
+    // 宽松模式
+    assert_eq!(resolve(None, None), Ok(TargetArch::X86_64));
+    assert_eq!(resolve(None, Some("")), Ok(TargetArch::X86_64));
+    assert_eq!(resolve(None, Some("0")), Ok(TargetArch::X86_64));
+    assert_eq!(resolve(Some("riscv64"), None), Ok(TargetArch::RiscV64));
+    assert!(resolve(Some("mips"), None).is_err());
+
+    // 严格模式
+    assert_eq!(resolve(Some("riscv64"), Some("1")), Ok(TargetArch::RiscV64));
+    let e = resolve(None, Some("1")).unwrap_err();
+    assert!(e.contains("ARCH is not set"), "{}", e);
+    assert!(e.contains(task::REQUIRE_ARCH_ENV_KEY), "{}", e);
+}
+
+/// 测试解析dotenv格式的环境变量文件
+#[test]
+fn dotenv_parse() {
+    let content = "# comment\n\nexport A=1\nB = plain value # comment\nC=\"x \\\"y\\\"\\n\" # trailing\nD='${NAME} \\n'\nE=\nF=a#b\n";
+    let envs: Vec<(String, String)> = dotenv::parse(content)
+        .unwrap()
+        .into_iter()
+        .map(|e| (e.key, e.value))
+        .collect();
+    let expected = [
+        ("A", "1"),
+        ("B", "plain value"),
+        ("C", "x \"y\"\n"),
+        ("D", "${NAME} \\n"),
+        ("E", ""),
+        ("F", "a#b"),
+    ];
+    assert_eq!(
+        envs,
+        expected
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect::<Vec<_>>()
+    );
+
+    let err = |content: &str| dotenv::parse(content).unwrap_err();
+    assert_eq!(err("A=1\nnot a pair\n").0, 2);
+    assert!(err("1A=x").1.contains("invalid variable name"));
+    assert!(err("A=\"open").1.contains("unterminated"));
+    assert!(err("A='x' y").1.contains("after the quoted value"));
+    assert!(err("A=\"\\q\"").1.contains("unknown escape"));
+}
+
+/// 测试加载环境变量文件出错时，错误信息中包括文件路径和行号
+#[test_context(BaseTestContext)]
+#[test]
+fn dotenv_load_reports_location(ctx: &mut BaseTestContext) {
+    let path = ctx
+        .fake_dadk_cache_root()
+        .join("dotenv_load_reports_location.env");
+    std::fs::write(&path, "A=1\n\nB='unterminated\n").unwrap();
+    let e = dotenv::load(&path).unwrap_err();
+    assert_eq!(
+        e,
+        format!("{}:3: unterminated ' quoted value", path.display())
+    );
+    std::fs::remove_file(&path).unwrap();
+}
+
+/// 测试任务的`env_files`：可以写作路径或者带`optional`的对象，相对路径相对于配置文件所在的目录
+#[test_context(BaseTestContext)]
+#[test]
+fn parse_env_files_v1(ctx: &mut BaseTestContext) {
+    let parser = Parser::new(ctx.config_v1_dir());
+    let task = parser
+        .parse_config_file(&ctx.config_v1_dir().join("app_env_files_0_1_0.dadk"))
+        .unwrap();
+    assert_eq!(
+        task.env_files,
+        vec![
+            task::EnvFile::new(ctx.config_v1_dir().join("env_files/app.env"), false),
+            task::EnvFile::new(ctx.config_v1_dir().join("env_files/missing.env"), true),
+        ]
+    );
+
+    let e = serde_json::from_str::<task::EnvFile>(r#"{"path": "a.env", "optinal": true}"#);
+    assert!(e.is_err());
+    assert!(task::EnvFile::new(PathBuf::new(), false)
+        .validate()
+        .is_err());
+}
+
+/// 测试加载目录下的所有任务：递归扫描、忽略其他文件、去除空白；有非法的配置文件时返回它的错误
+#[test_context(BaseTestContext)]
+#[test]
+fn load_tasks_from_dir(ctx: &mut BaseTestContext) {
+    let tasks = load_tasks(&ctx.abs_path("tests/data/load_tasks_v1/valid")).unwrap();
+    let names: Vec<String> = tasks.iter().map(|t| t.name_version()).collect();
+    assert_eq!(names, vec!["app_load_a_0_1_0", "app_load_b_0_2_0"]);
+    assert_eq!(tasks[1].depends[0].name, "app_load_a");
+
+    let invalid = ctx.abs_path("tests/data/load_tasks_v1/invalid");
+    let e = load_tasks(&invalid).unwrap_err();
+    assert_eq!(
+        e.config_file,
+        Some(invalid.join("app_load_bad_env_0_1_0.dadk"))
+    );
+    assert!(
+        matches!(&e.error, InnerParserError::TaskError(msg) if msg.contains("invalid key \"MY VAR\"")),
+        "{:?}",
+        e
+    );
+
+    let e = load_tasks(&ctx.abs_path("tests/data/load_tasks_v1/missing")).unwrap_err();
+    assert!(matches!(e.error, InnerParserError::IoError(_)), "{:?}", e);
+}
@@ -0,0 +1,166 @@
+//! # 工作区配置
+//!
+//! 配置文件目录下的`dadk-config.toml`（可选）是所有任务共用的配置。目前支持：
+//!
+//! - `global_envs`：所有任务继承的环境变量。任务的`envs`（包括按架构、按配置档覆盖的）中同名的环境变量优先，
+//!   任务也可以在`unset`中列出不需要继承的全局环境变量
+//! - `env_files`：所有任务在执行时加载的环境变量文件（dotenv格式，相对于配置文件目录），
+//!   优先级低于`global_envs`，同样可以被任务的`unset`去掉
+//! - `dep_envs`：导出依赖的构建结果目录的环境变量的命名方式。执行任务时，任务的每个依赖的构建结果目录被导出为
+//!   `prefix`（默认为`DADK_DEP_`）加上依赖名，`naming`为`sanitized`（默认）时依赖名转为大写、
+//!   字母和数字以外的字符替换为`_`，为`verbatim`时原样使用。依赖可以用`env_alias`指定其他名称。
+//!   一个任务的两个依赖得到同一个名称时报错
+//!
+//! ```toml
+//! env_files = ["local.env", { path = "secrets.env", optional = true, secret = true }]
+//!
+//! [dep_envs]
+//! prefix = "DADK_DEP_"
+//! naming = "sanitized"
+//!
+//! [[global_envs]]
+//! key = "RUSTFLAGS"
+//! value = "-C opt-level=2"
+//!
+//! [[global_envs]]
+//! key = "CC"
+//! value = "${env:CC:-x86_64-linux-musl-gcc}"
+//! ```
+//!
+//! 全局环境变量的值与任务的环境变量一样可以使用`${...}`变量，但不能引用依赖（`${dep:名称}`）。
+//! 全局环境变量改变后，设置了`build_once`的任务也会重新构建。
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    interpolate,
+    task::{DADKTask, DepEnvConfig, EnvFile, TaskEnv},
+    InnerParserError, ParserError,
+};
+
+/// 工作区配置文件的文件名，位于配置文件目录下
+pub const WORKSPACE_CONFIG_FILE_NAME: &str = "dadk-config.toml";
+
+/// # 工作区配置
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WorkspaceConfig {
+    /// 所有任务继承的环境变量
+    #[serde(default)]
+    pub global_envs: Vec<TaskEnv>,
+    /// 所有任务在执行时加载的环境变量文件
+    #[serde(default)]
+    pub env_files: Vec<EnvFile>,
+    /// 依赖的构建结果目录的环境变量的命名方式
+    #[serde(default)]
+    pub dep_envs: DepEnvConfig,
+}
+
+impl WorkspaceConfig {
+    /// 工作区配置文件的路径
+    pub fn path(config_dir: &Path) -> PathBuf {
+        return config_dir.join(WORKSPACE_CONFIG_FILE_NAME);
+    }
+
+    /// # 加载配置文件目录下的工作区配置
+    ///
+    /// 配置文件不存在时返回默认配置
+    pub fn load(config_dir: &Path) -> Result<Self, ParserError> {
+        let path = Self::path(config_dir);
+        if !path.is_file() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(&path).map_err(|e| ParserError {
+            config_file: Some(path.clone()),
+            error: InnerParserError::IoError(e),
+            location: None,
+        })?;
+        let mut config: WorkspaceConfig =
+            toml::from_str(&content).map_err(|e| ParserError::toml(&path, &content, e))?;
+        config.trim();
+        for env_file in config.env_files.iter_mut() {
+            env_file.resolve(config_dir);
+        }
+        config.validate().map_err(|e| ParserError {
+            config_file: Some(path.clone()),
+            error: InnerParserError::TaskError(e),
+            location: None,
+        })?;
+        return Ok(config);
+    }
+
+    pub fn trim(&mut self) {
+        for env in self.global_envs.iter_mut() {
+            env.trim();
+        }
+        for env_file in self.env_files.iter_mut() {
+            env_file.trim();
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let mut set_envs: Vec<&TaskEnv> = Vec::new();
+        for env in self.global_envs.iter() {
+            env.validate().map_err(|e| format!("global_envs: {}", e))?;
+            if env.target_arch.is_some() {
+                return Err(format!(
+                    "global_envs: {}: target_arch is not supported",
+                    env.key()
+                ));
+            }
+            if env.op.is_set() {
+                if set_envs
+                    .iter()
+                    .any(|e| e.key() == env.key() && e.phases_overlap(env))
+                {
+                    return Err(format!(
+                        "global_envs: {} is defined more than once",
+                        env.key()
+                    ));
+                }
+                set_envs.push(env);
+            }
+            if let Some(dep) = interpolate::referenced_dependencies(env.value())
+                .map_err(|e| format!("global_envs: {}: {}", env.key(), e))?
+                .first()
+            {
+                return Err(format!(
+                    "global_envs: {}: cannot reference dependency ${{dep:{}}}",
+                    env.key(),
+                    dep
+                ));
+            }
+        }
+        for env_file in self.env_files.iter() {
+            env_file.validate()?;
+        }
+        self.dep_envs.validate()?;
+        return Ok(());
+    }
+
+    /// # 把全局环境变量设置到任务中
+    ///
+    /// 去掉任务`unset`中列出的环境变量，返回`unset`中没有对应全局环境变量的名称。
+    /// 环境变量文件在执行时才加载，因此其中的环境变量在加载时按`unset`去掉，不在这里检查
+    pub fn apply(&self, task: &mut DADKTask) -> Vec<String> {
+        task.global_env_files.clone_from(&self.env_files);
+        task.dep_envs.clone_from(&self.dep_envs);
+        task.global_envs = self
+            .global_envs
+            .iter()
+            .filter(|env| !task.unset.iter().any(|k| k == env.key()))
+            .cloned()
+            .collect();
+        if !self.env_files.is_empty() {
+            return Vec::new();
+        }
+        return task
+            .unset
+            .iter()
+            .filter(|k| !self.global_envs.iter().any(|env| env.key() == k.as_str()))
+            .cloned()
+            .collect();
+    }
+}
@@ -1,5 +1,5 @@
 use std::{
-    collections::{BTreeMap, HashMap},
+    collections::{BTreeMap, BTreeSet, HashMap},
     fmt::Debug,
     path::PathBuf,
     process::exit,
@@ -13,10 +13,22 @@ use std::{
 use log::{error, info};
 
 use crate::{
-    console::Action,
+    console::{
+        clean::{print_selected, CleanArg},
+        Action,
+    },
     context::DadkExecuteContext,
-    executor::{target::Target, Executor},
-    parser::task::DADKTask,
+    executor::{
+        clean_plan::CleanPlan,
+        events, fetch,
+        plan::{PlannedPaths, TaskPlan},
+        progress::LogProgressReporter,
+        result::TaskResult,
+        summary::plan_summary,
+        target::Target,
+        EnvVar, Executor,
+    },
+    parser::task::{DADKTask, TargetArch},
 };
 
 use self::task_deque::TASK_DEQUE;
@@ -28,6 +40,10 @@ mod tests;
 lazy_static! {
     // 线程id与任务实体id映射表
     pub static ref TID_EID: Mutex<HashMap<ThreadId,i32>> = Mutex::new(HashMap::new());
+    // 已经执行完成的任务的执行结果
+    static ref TASK_RESULTS: Mutex<Vec<TaskResult>> = Mutex::new(Vec::new());
+    // 执行失败、或者因为依赖的任务失败而不执行的任务（任务实体id到最先失败的任务）
+    static ref FAILED: Mutex<BTreeMap<i32, String>> = Mutex::new(BTreeMap::new());
 }
 
 /// # 调度实体内部结构
@@ -54,7 +70,8 @@ pub struct SchedEntity {
 
 impl PartialEq for SchedEntity {
     fn eq(&self, other: &Self) -> bool {
-        self.inner.lock().unwrap().id == other.inner.lock().unwrap().id
+        // 不能同时持有两个锁，与自身比较时会死锁
+        self.id() == other.id()
     }
 }
 
@@ -90,6 +107,26 @@ impl SchedEntity {
         self.inner.lock().unwrap().children.push(entity);
     }
 
+    /// # 当前任务是否（直接或间接）依赖`other`
+    ///
+    /// 沿`other`的子节点查找，只有拓扑排序记录了依赖关系之后才能找到
+    pub fn depends_on(&self, other: &Arc<SchedEntity>) -> bool {
+        let mut visited = BTreeSet::new();
+        let mut stack = vec![other.clone()];
+        while let Some(entity) = stack.pop() {
+            let children = entity.inner.lock().unwrap().children.clone();
+            for child in children {
+                if std::ptr::eq(child.as_ref(), self) {
+                    return true;
+                }
+                if visited.insert(child.id()) {
+                    stack.push(child);
+                }
+            }
+        }
+        return false;
+    }
+
     /// 获取入度
     pub fn indegree(&self) -> usize {
         self.inner.lock().unwrap().indegree
@@ -149,9 +186,25 @@ impl SchedEntities {
         self.id2entity.read().unwrap().get(&id).cloned()
     }
 
+    /// 按名称和版本查找按当前目标架构构建的任务
     pub fn get_by_name_version(&self, name: &str, version: &str) -> Option<Arc<SchedEntity>> {
+        return self.get_by_name_version_arch(name, version, None);
+    }
+
+    /// # 按名称、版本和固定的目标架构查找任务
+    ///
+    /// `pinned_arch`为None时查找按当前目标架构构建的任务
+    pub fn get_by_name_version_arch(
+        &self,
+        name: &str,
+        version: &str,
+        pinned_arch: Option<TargetArch>,
+    ) -> Option<Arc<SchedEntity>> {
         for e in self.id2entity.read().unwrap().iter() {
-            if e.1.task().name_version_env() == DADKTask::name_version_uppercase(name, version) {
+            let task = e.1.task();
+            if task.name_version_env() == DADKTask::name_version_uppercase(name, version)
+                && task.pinned_arch == pinned_arch
+            {
                 return Some(e.1.clone());
             }
         }
@@ -191,7 +244,7 @@ impl SchedEntities {
         let btree = self.id2entity.write().unwrap().clone();
         for entity in btree.iter() {
             if !visited.contains_key(entity.0) {
-                let r = self.dfs(entity.1, &mut visited, &mut result);
+                let r = self.dfs(entity.1, &mut visited, &mut result, true);
                 if r.is_err() {
                     let err = r.unwrap_err();
                     error!("{}", err.display());
@@ -203,20 +256,42 @@ impl SchedEntities {
         return result;
     }
 
+    /// # 计算任务的传递依赖
+    ///
+    /// 与[`Self::topo_sort`]使用同一个深度优先遍历（包括环检测），但不修改调度实体的入度和子节点。
+    /// 返回的顺序与拓扑排序相同：被依赖的任务排在依赖它的任务之前，同一层的依赖按声明顺序排列。
+    /// 每个任务只出现一次，不包含任务本身
+    pub fn transitive_deps(
+        &self,
+        entity: &Arc<SchedEntity>,
+    ) -> Result<Vec<Arc<SchedEntity>>, DependencyCycleError> {
+        let mut visited = BTreeMap::new();
+        let mut result = Vec::new();
+        self.dfs(entity, &mut visited, &mut result, false)?;
+        result.pop();
+        return Ok(result);
+    }
+
+    /// `link`为true时，在遍历的同时记录依赖关系（入度和子节点）
     fn dfs(
         &self,
         entity: &Arc<SchedEntity>,
         visited: &mut BTreeMap<i32, bool>,
         result: &mut Vec<Arc<SchedEntity>>,
+        link: bool,
     ) -> Result<(), DependencyCycleError> {
         visited.insert(entity.id(), false);
-        for dep in entity.task().depends.iter() {
-            if let Some(dep_entity) = self.get_by_name_version(&dep.name, &dep.version) {
-                let guard = self.id2entity.write().unwrap();
-                let e = guard.get(&entity.id()).unwrap();
-                let d = guard.get(&dep_entity.id()).unwrap();
-                e.add_indegree();
-                d.add_child(e.clone());
+        for dep in entity.task().depends.iter().filter(|d| d.is_task()) {
+            if let Some(dep_entity) =
+                self.get_by_name_version_arch(&dep.name, &dep.version, dep.arch)
+            {
+                if link {
+                    let guard = self.id2entity.read().unwrap();
+                    let e = guard.get(&entity.id()).unwrap();
+                    let d = guard.get(&dep_entity.id()).unwrap();
+                    e.add_indegree();
+                    d.add_child(e.clone());
+                }
                 if let Some(&false) = visited.get(&dep_entity.id()) {
                     // 输出完整环形依赖
                     let mut err = DependencyCycleError::new(dep_entity.clone());
@@ -225,8 +300,7 @@ impl SchedEntities {
                     return Err(err);
                 }
                 if !visited.contains_key(&dep_entity.id()) {
-                    drop(guard);
-                    let r = self.dfs(&dep_entity, visited, result);
+                    let r = self.dfs(&dep_entity, visited, result, link);
                     if r.is_err() {
                         let mut err: DependencyCycleError = r.unwrap_err();
                         // 如果错误已经停止传播，则直接返回
@@ -267,12 +341,16 @@ pub struct Scheduler {
     target: SchedEntities,
     /// dadk执行的上下文
     context: Arc<DadkExecuteContext>,
+    /// 因为`enabled_if`不成立而被跳过的任务
+    disabled: Vec<DADKTask>,
 }
 
 pub enum SchedulerError {
     TaskError(String),
     /// 不是当前正在编译的目标架构
     InvalidTargetArch(String),
+    /// 任务的启用条件不成立
+    TaskDisabled(String),
     DependencyNotFound(Arc<SchedEntity>, String),
     RunError(String),
 }
@@ -298,6 +376,9 @@ impl Debug for SchedulerError {
             SchedulerError::InvalidTargetArch(msg) => {
                 write!(f, "InvalidTargetArch: {}", msg)
             }
+            SchedulerError::TaskDisabled(msg) => {
+                write!(f, "TaskDisabled: {}", msg)
+            }
         }
     }
 }
@@ -316,6 +397,7 @@ impl Scheduler {
             action,
             target: entities,
             context,
+            disabled: Vec::new(),
         };
 
         let r = scheduler.add_tasks(tasks);
@@ -331,16 +413,71 @@ impl Scheduler {
     ///
     /// 添加任务到调度器中，如果任务已经存在，则返回错误
     pub fn add_tasks(&mut self, tasks: Vec<(PathBuf, DADKTask)>) -> Result<(), SchedulerError> {
-        for task in tasks {
-            let e = self.add_task(task.0, task.1);
+        for task in tasks.iter().cloned() {
+            let e = match self.context.all_arches() {
+                true => self.add_task_all_arches(task.0, task.1),
+                false => self.add_task(task.0, task.1).map(|_| ()),
+            };
             if e.is_err() {
-                if let Err(SchedulerError::InvalidTargetArch(_)) = &e {
+                if let Err(SchedulerError::InvalidTargetArch(_) | SchedulerError::TaskDisabled(_)) =
+                    &e
+                {
                     continue;
                 }
                 e?;
             }
         }
+        self.add_pinned_dependencies(&tasks)?;
+
+        return Ok(());
+    }
 
+    /// # 添加按固定目标架构构建的依赖
+    ///
+    /// 依赖的`arch`与当前目标架构不同时（例如在主机上运行的构建工具），
+    /// 从所有任务中找到被依赖的任务，按该架构再添加一个调度实体。它的依赖默认也按该架构构建
+    fn add_pinned_dependencies(
+        &mut self,
+        tasks: &[(PathBuf, DADKTask)],
+    ) -> Result<(), SchedulerError> {
+        let mut queue = self.target.entities();
+        while let Some(entity) = queue.pop() {
+            for dep in entity.task().depends.iter().filter(|d| d.is_task()) {
+                let arch = match dep.arch {
+                    Some(arch) => arch,
+                    None => continue,
+                };
+                if self
+                    .target
+                    .get_by_name_version_arch(&dep.name, &dep.version, Some(arch))
+                    .is_some()
+                {
+                    continue;
+                }
+                let key = DADKTask::name_version_uppercase(&dep.name, &dep.version);
+                // 找不到的依赖由check_not_exists_dependency报错
+                let (path, task) = match tasks.iter().find(|(_, t)| t.name_version_env() == key) {
+                    Some(found) => found.clone(),
+                    None => continue,
+                };
+                if !task.target_arch.contains(&arch) {
+                    return Err(SchedulerError::DependencyNotFound(
+                        entity.clone(),
+                        format!(
+                            "{} is pinned to arch {:?}, which is not in its target arch list {:?}",
+                            dep.name_version(),
+                            arch,
+                            task.target_arch
+                        ),
+                    ));
+                }
+                match self.add_task_for_arch(path, task, arch) {
+                    Ok(e) => queue.push(e),
+                    Err(SchedulerError::TaskDisabled(_)) => continue,
+                    Err(e) => return Err(e),
+                }
+            }
+        }
         return Ok(());
     }
 
@@ -364,6 +501,55 @@ impl Scheduler {
                 self.context.target_arch()
             )));
         }
+        return self.add_task_for_arch(path, task, *self.context.target_arch());
+    }
+
+    /// # 按任务的`target_arch`中的每个架构分别添加一个任务
+    ///
+    /// 与当前目标架构不同的架构按固定架构添加（见[`Self::add_task_for_arch`]），
+    /// 各架构使用各自的覆盖配置、rust target、构建缓存和安装路径，执行结果也分别记录
+    fn add_task_all_arches(&mut self, path: PathBuf, task: DADKTask) -> Result<(), SchedulerError> {
+        for arch in task.target_arch.iter() {
+            match self.add_task_for_arch(path.clone(), task.clone(), *arch) {
+                Ok(_) | Err(SchedulerError::TaskDisabled(_)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        return Ok(());
+    }
+
+    /// # 按指定的目标架构添加一个任务
+    ///
+    /// 架构与当前目标架构不同时，任务作为按固定架构构建的依赖添加，与当前目标架构的同名任务互不影响
+    fn add_task_for_arch(
+        &mut self,
+        path: PathBuf,
+        task: DADKTask,
+        arch: TargetArch,
+    ) -> Result<Arc<SchedEntity>, SchedulerError> {
+        // 合并目标架构的覆盖配置
+        let mut task = task.for_arch(arch);
+        let current = *self.context.target_arch();
+        if arch != current {
+            task.pinned_arch = Some(arch);
+        }
+        // 依赖的架构默认与任务相同。与当前目标架构相同时记为None，
+        // 使依赖的`arch`与被依赖的任务的`pinned_arch`一致
+        for dep in task.depends.iter_mut().filter(|d| d.is_task()) {
+            let dep_arch = dep.arch.unwrap_or(arch);
+            dep.arch = (dep_arch != current).then_some(dep_arch);
+        }
+
+        if !self.task_enabled(&task, arch)? {
+            let msg = format!(
+                "Task {} is disabled by enabled_if: {}",
+                task.name_version(),
+                task.enabled_if.as_deref().unwrap_or_default()
+            );
+            info!("{}", msg);
+            self.disabled.push(task);
+            return Err(SchedulerError::TaskDisabled(msg));
+        }
 
         let id: i32 = self.generate_task_id();
         let indegree: usize = 0;
@@ -383,7 +569,7 @@ impl Scheduler {
 
         if self
             .target
-            .get_by_name_version(&name_version.0, &name_version.1)
+            .get_by_name_version_arch(&name_version.0, &name_version.1, entity.task().pinned_arch)
             .is_some()
         {
             return Err(SchedulerError::TaskError(format!(
@@ -396,10 +582,27 @@ impl Scheduler {
 
         self.target.add(entity.clone());
 
-        info!("Task added: {}", entity.task().name_version());
+        match entity.task().pinned_arch {
+            Some(arch) => info!(
+                "Task added: {} (pinned to {:?})",
+                entity.task().name_version(),
+                arch
+            ),
+            None => info!("Task added: {}", entity.task().name_version()),
+        }
         return Ok(entity);
     }
 
+    /// # 按任务的目标架构和环境变量对任务的启用条件求值
+    ///
+    /// 没有设置启用条件时总是启用
+    fn task_enabled(&self, task: &DADKTask, arch: TargetArch) -> Result<bool, SchedulerError> {
+        let cond = task
+            .enabled_condition()
+            .map_err(|e| SchedulerError::TaskError(format!("{}: {}", task.name_version(), e)))?;
+        return Ok(cond.map_or(true, |c| c.eval_with_process_env(arch)));
+    }
+
     fn generate_task_id(&self) -> i32 {
         static TASK_ID: AtomicI32 = AtomicI32::new(0);
         return TASK_ID.fetch_add(1, Ordering::SeqCst);
@@ -440,25 +643,151 @@ impl Scheduler {
     }
 
     /// # 执行调度器中的所有任务
-    pub fn run(&self) -> Result<(), SchedulerError> {
+    ///
+    /// 返回所有执行了的任务的执行结果
+    pub fn run(&self) -> Result<Vec<TaskResult>, SchedulerError> {
         // 准备全局环境变量
         crate::executor::prepare_env(&self.target, &self.context)
             .map_err(|e| SchedulerError::RunError(format!("{:?}", e)))?;
 
-        match self.action {
-            Action::Build | Action::Install => {
+        match &self.action {
+            Action::Build => {
+                self.init_lock_file()?;
+                self.run_with_topo_sort()?;
+                if self.write_lock_file() {
+                    crate::executor::lock::lock_file_save()
+                        .map_err(|e| SchedulerError::RunError(format!("{:?}", e)))?;
+                }
+            }
+            Action::Install(_) => {
                 self.run_with_topo_sort()?;
             }
-            Action::Clean(_) => self.run_without_topo_sort()?,
+            Action::Clean(arg) => {
+                let entities = self.clean_targets(arg)?;
+                self.run_without_topo_sort(entities)?;
+            }
             _ => unimplemented!(),
         }
 
+        let arch = *self.context.target_arch();
+        let mut results: Vec<TaskResult> = self
+            .disabled
+            .iter()
+            .map(|task| TaskResult::disabled(task.name_version(), task.pinned_arch.unwrap_or(arch)))
+            .collect();
+        results.append(&mut TASK_RESULTS.lock().unwrap());
+        return Ok(results);
+    }
+
+    /// # 计算所有任务的安装计划
+    ///
+    /// 按拓扑序计算每个任务的计划并检查任务之间的冲突，不修改sysroot（`dadk install --dry-run`）
+    pub fn install_plan(&self) -> Result<Vec<TaskPlan>, SchedulerError> {
+        crate::executor::prepare_env(&self.target, &self.context)
+            .map_err(|e| SchedulerError::RunError(format!("{:?}", e)))?;
+        self.check_not_exists_dependency()?;
+
+        let mut planned = PlannedPaths::default();
+        let mut plans = Vec::new();
+        for entity in self.target.topo_sort() {
+            let mut executor = Executor::new(
+                entity.clone(),
+                self.action.clone(),
+                self.dragonos_dir.clone(),
+            )
+            .map_err(|e| SchedulerError::RunError(format!("{:?}", e)))?;
+            let mut plan = executor.install_plan();
+            match entity.task().install.overwrite_matcher() {
+                Ok(allow_overwrite) => planned.check(&mut plan, &entity, &allow_overwrite),
+                Err(e) => plan.errors.push(e),
+            }
+            plans.push(plan);
+        }
+        return Ok(plans);
+    }
+
+    /// # 计算所选任务的清理计划
+    ///
+    /// 不执行清理命令，也不删除任何文件（`dadk clean --dry-run`）
+    pub fn clean_plan(&self, arg: &CleanArg) -> Result<Vec<CleanPlan>, SchedulerError> {
+        crate::executor::prepare_env(&self.target, &self.context)
+            .map_err(|e| SchedulerError::RunError(format!("{:?}", e)))?;
+
+        let mut plans = Vec::new();
+        for entity in self.clean_targets(arg)? {
+            let executor = Executor::new(
+                entity.clone(),
+                self.action.clone(),
+                self.dragonos_dir.clone(),
+            )
+            .map_err(|e| SchedulerError::RunError(format!("{:?}", e)))?;
+            plans.push(executor.clean_plan());
+        }
+        return Ok(plans);
+    }
+
+    /// # 计算任务执行时设置的环境变量
+    ///
+    /// 包括DADK设置的、从工作区继承的以及任务自己的环境变量（同名时只保留生效的那个），
+    /// 不包括执行DADK时的环境变量（`dadk info <任务>`）
+    pub fn effective_envs(&self, task: &DADKTask) -> Result<Vec<EnvVar>, SchedulerError> {
+        crate::executor::prepare_env(&self.target, &self.context)
+            .map_err(|e| SchedulerError::RunError(format!("{:?}", e)))?;
+        let entity = self
+            .target
+            .get_by_name_version(&task.name, &task.version)
+            .ok_or_else(|| {
+                SchedulerError::RunError(format!("task {} is not scheduled", task.name_version()))
+            })?;
+        let mut executor = Executor::new(entity, self.action.clone(), self.dragonos_dir.clone())
+            .map_err(|e| SchedulerError::RunError(format!("{:?}", e)))?;
+        return executor
+            .effective_envs()
+            .map_err(|e| SchedulerError::RunError(format!("{:?}", e)));
+    }
+
+    /// # 计算任务的传递依赖
+    ///
+    /// 见[`SchedEntities::transitive_deps`]，依赖不存在或者有环形依赖时返回错误（`dadk info <任务>`）
+    pub fn transitive_deps(&self, task: &DADKTask) -> Result<Vec<DADKTask>, SchedulerError> {
+        self.check_not_exists_dependency()?;
+        let entity = self
+            .target
+            .get_by_name_version(&task.name, &task.version)
+            .ok_or_else(|| {
+                SchedulerError::RunError(format!("task {} is not scheduled", task.name_version()))
+            })?;
+        return self
+            .target
+            .transitive_deps(&entity)
+            .map(|deps| deps.iter().map(|e| e.task()).collect())
+            .map_err(|e| SchedulerError::TaskError(e.display()));
+    }
+
+    /// # 初始化锁文件
+    ///
+    /// 锁文件位于DADK任务配置文件所在目录下。没有指定`--lock`、`--update-lock`，
+    /// 且锁文件不存在时，不检查源码身份
+    fn init_lock_file(&self) -> Result<(), SchedulerError> {
+        if let Some(config_dir) = self.context.config_dir() {
+            let path = config_dir.join(crate::executor::lock::LOCK_FILE_NAME);
+            if !self.write_lock_file() && !path.exists() {
+                return Ok(());
+            }
+            crate::executor::lock::lock_file_init(path, self.context.update_lock())
+                .map_err(|e| SchedulerError::RunError(format!("{:?}", e)))?;
+        }
         return Ok(());
     }
 
+    /// 构建结束后是否写入锁文件
+    fn write_lock_file(&self) -> bool {
+        return self.context.lock() || self.context.update_lock();
+    }
+
     /// Action需要按照拓扑序执行
     ///
-    /// Action::Build | Action::Install
+    /// Action::Build | Action::Install(_)
     fn run_with_topo_sort(&self) -> Result<(), SchedulerError> {
         // 检查是否有不存在的依赖
         let r = self.check_not_exists_dependency();
@@ -470,14 +799,20 @@ impl Scheduler {
         // 对调度实体进行拓扑排序
         let r: Vec<Arc<SchedEntity>> = self.target.topo_sort();
 
+        // 开始构建之前输出计划摘要
+        if self.action == Action::Build {
+            info!("{}", plan_summary(&r, &[*self.context.target_arch()]));
+        }
+
         let action = self.action.clone();
         let dragonos_dir = self.dragonos_dir.clone();
         let id2entity = self.target.id2entity();
         let count = r.len();
+        let fail_fast = self.context.fail_fast();
 
         // 启动守护线程
         let handler = std::thread::spawn(move || {
-            Self::build_install_daemon(action, dragonos_dir, id2entity, count, &r)
+            Self::build_install_daemon(action, dragonos_dir, id2entity, count, &r, fail_fast)
         });
 
         handler.join().expect("Could not join deamon");
@@ -485,43 +820,114 @@ impl Scheduler {
         return Ok(());
     }
 
+    /// # 按`--task`、`--tag`选择要清理的任务
+    ///
+    /// 在清理任何任务之前输出所选的任务，返回它们的调度实体
+    pub fn clean_targets(&self, arg: &CleanArg) -> Result<Vec<Arc<SchedEntity>>, SchedulerError> {
+        let entities = self.target.entities();
+        let tasks: Vec<DADKTask> = entities.iter().map(|e| e.task()).collect();
+        let selected = arg
+            .select_tasks(&tasks)
+            .map_err(SchedulerError::TaskError)?;
+        print_selected(arg.level(), &selected);
+        return Ok(entities
+            .into_iter()
+            .filter(|e| selected.contains(&e.task().name_version()))
+            .collect());
+    }
+
     /// Action不需要按照拓扑序执行
-    fn run_without_topo_sort(&self) -> Result<(), SchedulerError> {
+    fn run_without_topo_sort(&self, mut r: Vec<Arc<SchedEntity>>) -> Result<(), SchedulerError> {
         // 启动守护线程
         let action = self.action.clone();
         let dragonos_dir = self.dragonos_dir.clone();
-        let mut r = self.target.entities();
+        let fail_fast = self.context.fail_fast();
         let handler = std::thread::spawn(move || {
-            Self::clean_daemon(action, dragonos_dir, &mut r);
+            Self::clean_daemon(action, dragonos_dir, &mut r, fail_fast);
         });
 
         handler.join().expect("Could not join deamon");
         return Ok(());
     }
 
-    pub fn execute(action: Action, dragonos_dir: PathBuf, entity: Arc<SchedEntity>) {
-        let mut executor = Executor::new(entity.clone(), action.clone(), dragonos_dir.clone())
-            .map_err(|e| {
+    /// # 执行一个任务
+    ///
+    /// 任务失败时，`fail_fast`为true则退出，否则记录失败的任务，依赖它的任务不再执行
+    pub fn execute(
+        action: Action,
+        dragonos_dir: PathBuf,
+        entity: Arc<SchedEntity>,
+        fail_fast: bool,
+    ) {
+        let task = entity.task();
+        let arch = Executor::task_arch(&task);
+        let arch_str: &str = arch.into();
+        let label = format!("{} ({})", task.name_version(), arch_str);
+
+        let failed_dependency = FAILED.lock().unwrap().get(&entity.id()).cloned();
+        if let Some(failed) = failed_dependency {
+            error!("Task {} is skipped because {} failed", label, failed);
+            let mut result = TaskResult::new(task.name_version(), arch);
+            result.error = Some(format!("skipped because {} failed", failed));
+            TASK_RESULTS.lock().unwrap().push(result);
+            return;
+        }
+
+        let mut executor = match Executor::new(entity.clone(), action, dragonos_dir) {
+            Ok(executor) => executor,
+            Err(e) => {
                 error!(
                     "Error while creating executor for task {} : {:?}",
-                    entity.task().name_version(),
+                    task.name_version(),
                     e
                 );
-                exit(-1);
-            })
-            .unwrap();
+                let mut result = TaskResult::new(task.name_version(), arch);
+                result.error = Some(e.to_string());
+                TASK_RESULTS.lock().unwrap().push(result);
+                return Self::task_failed(&entity, label, fail_fast);
+            }
+        };
+        executor.set_progress_reporter(Arc::new(LogProgressReporter::default()));
+        if let Some(emitter) = events::emitter() {
+            executor.set_event_emitter(emitter);
+        }
 
-        executor
-            .execute()
-            .map_err(|e| {
-                error!(
-                    "Error while executing task {} : {:?}",
-                    entity.task().name_version(),
-                    e
-                );
-                exit(-1);
-            })
-            .unwrap();
+        let r = executor.execute();
+        TASK_RESULTS.lock().unwrap().push(executor.result().clone());
+        if let Err(e) = r {
+            error!(
+                "Error while executing task {} : {:?}",
+                task.name_version(),
+                e
+            );
+            Self::task_failed(&entity, label, fail_fast);
+        }
+    }
+
+    /// # 处理失败的任务
+    ///
+    /// `fail_fast`为true时等待其他任务正在进行的下载完成后退出，否则把任务记为失败
+    fn task_failed(entity: &SchedEntity, label: String, fail_fast: bool) {
+        if fail_fast {
+            // 等待其他任务正在进行的下载完成，以免中断它们
+            fetch::drain();
+            exit(-1);
+        }
+        FAILED.lock().unwrap().insert(entity.id(), label);
+    }
+
+    /// # 任务失败时，把依赖它的任务也记为失败
+    ///
+    /// 在任务完成、依赖它的任务的入度减1之前调用，使这些任务在执行时被跳过
+    fn propagate_failure(entity: &SchedEntity) {
+        let mut failed = FAILED.lock().unwrap();
+        let cause = match failed.get(&entity.id()) {
+            Some(cause) => cause.clone(),
+            None => return,
+        };
+        for child in entity.inner.lock().unwrap().children.iter() {
+            failed.entry(child.id()).or_insert_with(|| cause.clone());
+        }
     }
 
     /// 构建和安装DADK任务的守护线程
@@ -533,6 +939,7 @@ impl Scheduler {
     /// - `id2entity` : DADK任务id与实体映射表
     /// - `count` : 当前剩余任务数
     /// - `r` : 总任务实体表
+    /// - `fail_fast` : 任务失败时是否退出
     ///
     /// ## 返回值
     ///
@@ -543,6 +950,7 @@ impl Scheduler {
         id2entity: BTreeMap<i32, Arc<SchedEntity>>,
         mut count: usize,
         r: &Vec<Arc<SchedEntity>>,
+        fail_fast: bool,
     ) {
         let mut guard = TASK_DEQUE.lock().unwrap();
         // 初始化0入度的任务实体
@@ -560,6 +968,7 @@ impl Scheduler {
                     action.clone(),
                     dragonos_dir.clone(),
                     zero_entity.last().unwrap().clone(),
+                    fail_fast,
                 )
             {
                 zero_entity.pop();
@@ -573,6 +982,7 @@ impl Scheduler {
                     let tid = x.thread().id();
                     let eid = *TID_EID.lock().unwrap().get(&tid).unwrap();
                     let entity = id2entity.get(&eid).unwrap();
+                    Self::propagate_failure(entity);
                     let zero = entity.sub_children_indegree();
                     for e in zero.iter() {
                         zero_entity.push(e.clone());
@@ -591,14 +1001,25 @@ impl Scheduler {
     /// - `action` : 要执行的操作
     /// - `dragonos_dir` : DragonOS sysroot在主机上的路径
     /// - `r` : 总任务实体表
+    /// - `fail_fast` : 任务失败时是否退出
     ///
     /// ## 返回值
     ///
     /// 无
-    pub fn clean_daemon(action: Action, dragonos_dir: PathBuf, r: &mut Vec<Arc<SchedEntity>>) {
+    pub fn clean_daemon(
+        action: Action,
+        dragonos_dir: PathBuf,
+        r: &mut Vec<Arc<SchedEntity>>,
+        fail_fast: bool,
+    ) {
         let mut guard = TASK_DEQUE.lock().unwrap();
         while !guard.queue().is_empty() && !r.is_empty() {
-            guard.clean_task(action, dragonos_dir.clone(), r.pop().unwrap().clone());
+            guard.clean_task(
+                action.clone(),
+                dragonos_dir.clone(),
+                r.pop().unwrap().clone(),
+                fail_fast,
+            );
         }
     }
 
@@ -607,17 +1028,21 @@ impl Scheduler {
     /// 如果某个任务的dependency中的任务不存在，则返回错误
     fn check_not_exists_dependency(&self) -> Result<(), SchedulerError> {
         for entity in self.target.entities().iter() {
-            for dependency in entity.task().depends.iter() {
+            for dependency in entity.task().depends.iter().filter(|d| d.is_task()) {
                 let name_version = (dependency.name.clone(), dependency.version.clone());
                 if !self
                     .target
-                    .get_by_name_version(&name_version.0, &name_version.1)
+                    .get_by_name_version_arch(&name_version.0, &name_version.1, dependency.arch)
                     .is_some()
                 {
-                    return Err(SchedulerError::DependencyNotFound(
-                        entity.clone(),
-                        format!("name:{}, version:{}", name_version.0, name_version.1,),
-                    ));
+                    let mut msg = format!("name:{}, version:{}", name_version.0, name_version.1,);
+                    if let Some(arch) = dependency.arch {
+                        msg = format!("{}, arch:{:?}", msg, arch);
+                    }
+                    if self.disabled.iter().any(|t| t.name == name_version.0) {
+                        msg = format!("{} (the task is disabled by enabled_if)", msg);
+                    }
+                    return Err(SchedulerError::DependencyNotFound(entity.clone(), msg));
                 }
             }
         }
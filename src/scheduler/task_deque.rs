@@ -35,6 +35,7 @@ impl TaskDeque {
     /// - `action` : 要执行的操作
     /// - `dragonos_dir` : DragonOS sysroot在主机上的路径
     /// - `entity` : 任务实体
+    /// - `fail_fast` : 任务失败时是否退出
     ///
     /// ## 返回值
     ///
@@ -45,12 +46,13 @@ impl TaskDeque {
         action: Action,
         dragonos_dir: PathBuf,
         entity: Arc<SchedEntity>,
+        fail_fast: bool,
     ) -> bool {
         // log::warn!("push stack: task:{} {entity:?}", entity.id());
         if self.queue.len() < self.max_num {
             let id = entity.id();
             let handler = std::thread::spawn(move || {
-                Scheduler::execute(action, dragonos_dir.clone(), entity)
+                Scheduler::execute(action, dragonos_dir.clone(), entity, fail_fast)
             });
             TID_EID.lock().unwrap().insert(handler.thread().id(), id);
             self.queue.push(handler);
@@ -66,16 +68,24 @@ impl TaskDeque {
     /// - `action` : 要执行的操作
     /// - `dragonos_dir` : DragonOS sysroot在主机上的路径
     /// - `entity` : 任务实体
+    /// - `fail_fast` : 任务失败时是否退出
     ///
     /// ## 返回值
     ///
     /// 无
-    pub fn clean_task(&mut self, action: Action, dragonos_dir: PathBuf, entity: Arc<SchedEntity>) {
+    pub fn clean_task(
+        &mut self,
+        action: Action,
+        dragonos_dir: PathBuf,
+        entity: Arc<SchedEntity>,
+        fail_fast: bool,
+    ) {
         while self.queue.len() >= self.max_num {
             self.queue.retain(|x| !x.is_finished());
         }
-        let handler =
-            std::thread::spawn(move || Scheduler::execute(action, dragonos_dir.clone(), entity));
+        let handler = std::thread::spawn(move || {
+            Scheduler::execute(action, dragonos_dir.clone(), entity, fail_fast)
+        });
         self.queue.push(handler);
     }
 
@@ -4,10 +4,18 @@ use test_base::{
 };
 
 use crate::{
+    console::{clean::CleanLevel, install::InstallArg},
     context::{
-        DadkExecuteContextTestBuildRiscV64V1, DadkExecuteContextTestBuildX86_64V1, TestContextExt,
+        DadkExecuteContextTestBuildAllArchesX86_64V1, DadkExecuteContextTestBuildRiscV64V1,
+        DadkExecuteContextTestBuildX86_64V1, TestContextExt,
+    },
+    parser::{
+        task::{
+            BuildConfig, BuildOverride, CleanConfig, DADKTask, Dependency, InstallConfig,
+            InstallOverride, TargetArch, TaskType,
+        },
+        Parser,
     },
-    parser::{task::TargetArch, Parser},
 };
 
 use super::*;
@@ -37,7 +45,7 @@ fn should_not_run_task_only_riscv64_on_x86_64(ctx: &DadkExecuteContextTestBuildX
     let scheduler = Scheduler::new(
         ctx.execute_context().self_ref().unwrap(),
         ctx.base_context().fake_dragonos_sysroot(),
-        *ctx.execute_context().action(),
+        ctx.execute_context().action().clone(),
         vec![],
     );
 
@@ -78,7 +86,7 @@ fn should_not_run_task_only_x86_64_on_riscv64(ctx: &DadkExecuteContextTestBuildR
     let scheduler = Scheduler::new(
         ctx.execute_context().self_ref().unwrap(),
         ctx.base_context().fake_dragonos_sysroot(),
-        *ctx.execute_context().action(),
+        ctx.execute_context().action().clone(),
         vec![],
     );
 
@@ -115,7 +123,7 @@ fn should_run_task_include_x86_64_on_x86_64(ctx: &DadkExecuteContextTestBuildX86
     let scheduler = Scheduler::new(
         ctx.execute_context().self_ref().unwrap(),
         ctx.base_context().fake_dragonos_sysroot(),
-        *ctx.execute_context().action(),
+        ctx.execute_context().action().clone(),
         vec![],
     );
 
@@ -148,7 +156,7 @@ fn should_run_task_include_riscv64_on_riscv64(ctx: &DadkExecuteContextTestBuildR
     let scheduler = Scheduler::new(
         ctx.execute_context().self_ref().unwrap(),
         ctx.base_context().fake_dragonos_sysroot(),
-        *ctx.execute_context().action(),
+        ctx.execute_context().action().clone(),
         vec![],
     );
 
@@ -179,3 +187,440 @@ fn ensure_all_target_arch_testcase_v1(ctx: &BaseTestContext) {
         );
     }
 }
+
+fn task_with_depends(ctx: &BaseTestContext, name: &str, depends: &[&str]) -> DADKTask {
+    let config_file = ctx.config_v1_dir().join("app_normal_0_1_0.dadk");
+    let mut task = Parser::new(ctx.config_v1_dir())
+        .parse_config_file(&config_file)
+        .unwrap();
+    task.name = name.to_string();
+    task.version = "0.1.0".to_string();
+    task.depends = depends
+        .iter()
+        .map(|n| Dependency::new(n.to_string(), "0.1.0".to_string()))
+        .collect();
+    return task;
+}
+
+/// 用给定的任务创建调度器
+fn scheduler_with_tasks(
+    ctx: &DadkExecuteContextTestBuildX86_64V1,
+    tasks: &[DADKTask],
+) -> Scheduler {
+    let config_file = ctx
+        .base_context()
+        .config_v1_dir()
+        .join("app_normal_0_1_0.dadk");
+    return Scheduler::new(
+        ctx.execute_context().self_ref().unwrap(),
+        ctx.base_context().fake_dragonos_sysroot(),
+        ctx.execute_context().action().clone(),
+        tasks
+            .iter()
+            .map(|t| (config_file.clone(), t.clone()))
+            .collect(),
+    )
+    .unwrap();
+}
+
+/// 多层依赖图中，菱形依赖的公共依赖只出现一次，被依赖的任务排在前面
+#[test_context(DadkExecuteContextTestBuildX86_64V1)]
+#[test]
+fn transitive_deps_dedup_diamond(ctx: &DadkExecuteContextTestBuildX86_64V1) {
+    // app -> {liba, libb}, liba -> libcore, libb -> {libcore, libc}, libcore -> libbase
+    let base = ctx.base_context();
+    let all = vec![
+        task_with_depends(base, "app", &["liba", "libb"]),
+        task_with_depends(base, "liba", &["libcore"]),
+        task_with_depends(base, "libb", &["libcore", "libc"]),
+        task_with_depends(base, "libcore", &["libbase"]),
+        task_with_depends(base, "libc", &[]),
+        task_with_depends(base, "libbase", &[]),
+    ];
+    let scheduler = scheduler_with_tasks(ctx, &all);
+
+    let deps = scheduler.transitive_deps(&all[0]).unwrap();
+    let names: Vec<&str> = deps.iter().map(|d| d.name.as_str()).collect();
+    assert_eq!(names, vec!["libbase", "libcore", "liba", "libc", "libb"]);
+
+    // 结果是稳定的，并且不影响之后的拓扑排序
+    assert_eq!(
+        names,
+        scheduler
+            .transitive_deps(&all[0])
+            .unwrap()
+            .iter()
+            .map(|d| d.name.as_str())
+            .collect::<Vec<_>>()
+    );
+    assert!(scheduler.transitive_deps(&all[5]).unwrap().is_empty());
+    let sorted = scheduler.target.topo_sort();
+    let app = sorted.iter().find(|e| e.task().name == "app").unwrap();
+    assert_eq!(app.indegree(), 2);
+}
+
+#[test_context(DadkExecuteContextTestBuildX86_64V1)]
+#[test]
+fn transitive_deps_detects_cycle(ctx: &DadkExecuteContextTestBuildX86_64V1) {
+    let base = ctx.base_context();
+    let all = vec![
+        task_with_depends(base, "app", &["liba"]),
+        task_with_depends(base, "liba", &["libb"]),
+        task_with_depends(base, "libb", &["liba"]),
+    ];
+    let err = scheduler_with_tasks(ctx, &all)
+        .transitive_deps(&all[0])
+        .unwrap_err();
+    assert!(
+        matches!(&err, SchedulerError::TaskError(msg)
+            if msg.contains("Dependency cycle detected")
+                && msg.contains("liba_0_1_0")
+                && msg.contains("libb_0_1_0")),
+        "{:?}",
+        err
+    );
+
+    let missing = vec![task_with_depends(base, "app", &["libnone"])];
+    let err = scheduler_with_tasks(ctx, &missing)
+        .transitive_deps(&missing[0])
+        .unwrap_err();
+    assert!(
+        matches!(err, SchedulerError::DependencyNotFound(..)),
+        "{:?}",
+        err
+    );
+}
+
+/// 启用条件成立的任务被添加，不成立的任务被跳过并记录为disabled
+#[test_context(DadkExecuteContextTestBuildX86_64V1)]
+#[test]
+fn enabled_if_skips_disabled_task(ctx: &DadkExecuteContextTestBuildX86_64V1) {
+    let parser = Parser::new(ctx.base_context().config_v1_dir());
+    let config_dir = ctx.base_context().config_v1_dir();
+    let enabled = config_dir.join("app_enabled_if_0_1_0.dadk");
+    let disabled = config_dir.join("app_enabled_if_feature_0_1_0.dadk");
+
+    let mut scheduler = Scheduler::new(
+        ctx.execute_context().self_ref().unwrap(),
+        ctx.base_context().fake_dragonos_sysroot(),
+        ctx.execute_context().action().clone(),
+        vec![],
+    )
+    .unwrap();
+
+    let task = parser.parse_config_file(&enabled).unwrap();
+    let entity = scheduler.add_task(enabled, task);
+    assert!(entity.is_ok(), "Add task error: {:?}", entity);
+
+    let task = parser.parse_config_file(&disabled).unwrap();
+    let r = scheduler.add_task(disabled, task);
+    assert!(
+        matches!(&r, Err(SchedulerError::TaskDisabled(msg)) if msg.contains("app_enabled_if_feature_0_1_0")),
+        "{:?}",
+        r
+    );
+    assert_eq!(scheduler.target.len(), 1);
+    assert_eq!(scheduler.disabled.len(), 1);
+    assert_eq!(scheduler.disabled[0].name, "app_enabled_if_feature");
+}
+
+/// 依赖固定为x86_64的主机构建工具，依赖它的任务按riscv64构建
+#[test_context(DadkExecuteContextTestBuildRiscV64V1)]
+#[test]
+fn dependency_pinned_to_host_arch(ctx: &DadkExecuteContextTestBuildRiscV64V1) {
+    let parser = Parser::new(ctx.base_context().config_v1_dir());
+    let tasks: Vec<(PathBuf, DADKTask)> = ["app_host_tool_dep", "app_host_tool", "app_host_lib"]
+        .iter()
+        .map(|name| {
+            let path = ctx
+                .base_context()
+                .config_v1_dir()
+                .join(format!("{}_0_1_0.dadk", name));
+            let task = parser.parse_config_file(&path).unwrap();
+            (path, task)
+        })
+        .collect();
+
+    let scheduler = Scheduler::new(
+        ctx.execute_context().self_ref().unwrap(),
+        ctx.base_context().fake_dragonos_sysroot(),
+        ctx.execute_context().action().clone(),
+        tasks,
+    )
+    .unwrap();
+    let entities = &scheduler.target;
+
+    // 主机构建工具不支持riscv64，只按固定的x86_64构建
+    assert!(entities
+        .get_by_name_version("app_host_tool", "0.1.0")
+        .is_none());
+    let tool = entities
+        .get_by_name_version_arch("app_host_tool", "0.1.0", Some(TargetArch::X86_64))
+        .expect("host tool should be added for x86_64");
+    assert_eq!(tool.task().cache_key(), "app_host_tool_0_1_0-x86_64");
+    // 主机构建工具的依赖默认也按x86_64构建，与riscv64的同名任务互不影响
+    assert_eq!(tool.task().depends[0].arch, Some(TargetArch::X86_64));
+    let lib = entities
+        .get_by_name_version_arch("app_host_lib", "0.1.0", Some(TargetArch::X86_64))
+        .expect("host lib should be added for x86_64");
+    let lib_riscv64 = entities
+        .get_by_name_version("app_host_lib", "0.1.0")
+        .expect("host lib should also be added for riscv64");
+    assert_eq!(lib_riscv64.task().pinned_arch, None);
+    assert_eq!(entities.len(), 4);
+
+    let app = entities
+        .get_by_name_version("app_host_tool_dep", "0.1.0")
+        .unwrap();
+    assert!(scheduler.check_not_exists_dependency().is_ok());
+    let order: Vec<i32> = entities.topo_sort().iter().map(|e| e.id()).collect();
+    let pos = |e: &Arc<SchedEntity>| order.iter().position(|id| *id == e.id()).unwrap();
+    assert!(pos(&lib) < pos(&tool));
+    assert!(pos(&tool) < pos(&app));
+}
+
+/// 按所有架构执行时，任务按`target_arch`中的每个架构分别执行并得到各自的执行结果。
+/// 不立即停止时，一个架构失败不影响其他架构，依赖失败任务的任务被跳过
+#[test_context(DadkExecuteContextTestBuildAllArchesX86_64V1)]
+#[test]
+fn all_arches_fan_out(ctx: &DadkExecuteContextTestBuildAllArchesX86_64V1) {
+    let parser = Parser::new(ctx.base_context().config_v1_dir());
+    let mut tasks: Vec<(PathBuf, DADKTask)> = ["app_all_arches", "app_all_arches_user"]
+        .iter()
+        .map(|name| {
+            let path = ctx
+                .base_context()
+                .config_v1_dir()
+                .join(format!("{}_0_1_0.dadk", name));
+            let task = parser.parse_config_file(&path).unwrap();
+            (path, task)
+        })
+        .collect();
+    let sysroot = ctx.base_context().fake_dragonos_sysroot();
+    let install_root = sysroot.join("opt/app_all_arches");
+    let _ = std::fs::remove_dir_all(&install_root);
+    let scheduler = |tasks: Vec<(PathBuf, DADKTask)>, action: Action| {
+        Scheduler::new(
+            ctx.execute_context().self_ref().unwrap(),
+            sysroot.clone(),
+            action,
+            tasks,
+        )
+        .unwrap()
+    };
+
+    let build = scheduler(tasks.clone(), Action::Build);
+    assert_eq!(build.target.len(), 4);
+    let app_riscv64 = build
+        .target
+        .get_by_name_version_arch("app_all_arches", "0.1.0", Some(TargetArch::RiscV64))
+        .expect("app_all_arches should be added for riscv64");
+    assert_eq!(
+        app_riscv64.task().cache_key(),
+        "app_all_arches_0_1_0-riscv64"
+    );
+    let user_riscv64 = build
+        .target
+        .get_by_name_version_arch("app_all_arches_user", "0.1.0", Some(TargetArch::RiscV64))
+        .expect("app_all_arches_user should be added for riscv64");
+    assert_eq!(
+        user_riscv64.task().depends[0].arch,
+        Some(TargetArch::RiscV64)
+    );
+    assert!(build.check_not_exists_dependency().is_ok());
+
+    // 直接执行每个调度实体的构建，不初始化锁文件
+    crate::executor::prepare_env(&build.target, &ctx.execute_context().self_ref().unwrap())
+        .unwrap();
+    for entity in build.target.topo_sort() {
+        let r = Executor::new(entity, Action::Build, sysroot.clone())
+            .unwrap()
+            .execute();
+        assert!(r.is_ok(), "Execute error: {:?}", r);
+    }
+
+    let install = Action::Install(InstallArg::default());
+    let arches_of = |results: &[TaskResult], name_version: &str| {
+        let mut arches: Vec<(TargetArch, bool)> = results
+            .iter()
+            .filter(|r| r.name_version == name_version)
+            .map(|r| (r.target_arch, r.success))
+            .collect();
+        arches.sort();
+        return arches;
+    };
+    let results = scheduler(tasks.clone(), install.clone()).run().unwrap();
+    assert_eq!(results.len(), 4, "{:?}", results);
+    assert_eq!(
+        arches_of(&results, "app_all_arches_0_1_0"),
+        vec![(TargetArch::X86_64, true), (TargetArch::RiscV64, true)]
+    );
+    for arch in ["x86_64", "riscv64"] {
+        let installed = std::fs::read_to_string(install_root.join(arch).join("arch.txt")).unwrap();
+        assert_eq!(installed.trim(), arch);
+    }
+
+    // riscv64的安装失败，x86_64不受影响，依赖它的riscv64任务被跳过
+    tasks[0].1.install.target_arch.insert(
+        TargetArch::RiscV64,
+        InstallOverride {
+            in_dragonos_path: Some(PathBuf::from("/proc/app_all_arches")),
+        },
+    );
+    let results = scheduler(tasks, install).run().unwrap();
+    assert_eq!(
+        arches_of(&results, "app_all_arches_0_1_0"),
+        vec![(TargetArch::X86_64, true), (TargetArch::RiscV64, false)]
+    );
+    assert_eq!(
+        arches_of(&results, "app_all_arches_user_0_1_0"),
+        vec![(TargetArch::X86_64, true), (TargetArch::RiscV64, false)]
+    );
+    let skipped = results
+        .iter()
+        .find(|r| r.name_version == "app_all_arches_user_0_1_0" && !r.success)
+        .unwrap();
+    assert_eq!(
+        skipped.error.as_deref(),
+        Some("skipped because app_all_arches_0_1_0 (riscv64) failed")
+    );
+    std::fs::remove_dir_all(&install_root).unwrap();
+}
+
+/// 按架构覆盖的构建配置可以指定该架构下的rust target
+#[test]
+fn arch_override_rust_target() {
+    let mut task = DADKTask::new(
+        "app_rust_target".to_string(),
+        "0.1.0".to_string(),
+        "".to_string(),
+        Some("x86_64-unknown-dragonos".to_string()),
+        TaskType::NoSource,
+        vec![],
+        BuildConfig::new(Some("true".to_string())),
+        InstallConfig::new(None),
+        CleanConfig::new(None),
+        None,
+        false,
+        false,
+        Some(vec![TargetArch::X86_64, TargetArch::RiscV64]),
+    );
+    task.build.target_arch.insert(
+        TargetArch::RiscV64,
+        BuildOverride {
+            build_command: None,
+            rust_target: Some(" riscv64gc-unknown-dragonos ".to_string()),
+        },
+    );
+    assert!(task.validate().is_ok(), "{:?}", task.validate());
+    assert_eq!(
+        task.for_arch(TargetArch::RiscV64).rust_target.as_deref(),
+        Some("riscv64gc-unknown-dragonos")
+    );
+    assert_eq!(
+        task.for_arch(TargetArch::X86_64).rust_target.as_deref(),
+        Some("x86_64-unknown-dragonos")
+    );
+}
+
+/// 固定的架构不在被依赖的任务支持的架构中时报错
+#[test_context(DadkExecuteContextTestBuildRiscV64V1)]
+#[test]
+fn dependency_pinned_to_unsupported_arch(ctx: &DadkExecuteContextTestBuildRiscV64V1) {
+    let parser = Parser::new(ctx.base_context().config_v1_dir());
+    let path = ctx
+        .base_context()
+        .config_v1_dir()
+        .join("app_host_tool_dep_0_1_0.dadk");
+    let mut task = parser.parse_config_file(&path).unwrap();
+    task.depends[0].arch = Some(TargetArch::Aarch64);
+    let tool_path = ctx
+        .base_context()
+        .config_v1_dir()
+        .join("app_host_tool_0_1_0.dadk");
+    let tool = parser.parse_config_file(&tool_path).unwrap();
+
+    let r = Scheduler::new(
+        ctx.execute_context().self_ref().unwrap(),
+        ctx.base_context().fake_dragonos_sysroot(),
+        ctx.execute_context().action().clone(),
+        vec![(path, task), (tool_path, tool)],
+    );
+    assert!(
+        matches!(&r, Err(SchedulerError::DependencyNotFound(_, msg)) if msg.contains("pinned to arch Aarch64")),
+        "{:?}",
+        r
+    );
+}
+
+/// 按`--task`、`--tag`选择要清理的任务，`--with-dependents`沿反向依赖图扩展
+#[test_context(DadkExecuteContextTestBuildX86_64V1)]
+#[test]
+fn clean_targets_filter_tasks(ctx: &DadkExecuteContextTestBuildX86_64V1) {
+    let base = ctx.base_context();
+    let mut libnet = task_with_depends(base, "libnet", &[]);
+    libnet.tags = vec!["net".to_string()];
+    let tasks = vec![
+        task_with_depends(base, "libbase", &[]),
+        task_with_depends(base, "libcore", &["libbase"]),
+        task_with_depends(base, "app", &["libcore"]),
+        task_with_depends(base, "tool", &[]),
+        libnet,
+    ];
+    let scheduler = Scheduler::new(
+        ctx.execute_context().self_ref().unwrap(),
+        base.fake_dragonos_sysroot(),
+        Action::Clean(CleanArg::new(CleanLevel::Output)),
+        tasks
+            .into_iter()
+            .map(|t| (PathBuf::from(format!("{}.dadk", t.name)), t))
+            .collect(),
+    )
+    .unwrap();
+
+    let select = |tasks: &[&str], tags: &[&str], with_dependents: bool| {
+        let mut arg = CleanArg::new(CleanLevel::Output);
+        arg.tasks = tasks.iter().map(|t| t.to_string()).collect();
+        arg.tags = tags.iter().map(|t| t.to_string()).collect();
+        arg.with_dependents = with_dependents;
+        return scheduler.clean_targets(&arg).map(|entities| {
+            let mut names: Vec<String> = entities.iter().map(|e| e.task().name).collect();
+            names.sort();
+            names
+        });
+    };
+
+    assert_eq!(select(&[], &[], false).unwrap().len(), 5);
+    assert_eq!(select(&["libcore"], &[], false).unwrap(), vec!["libcore"]);
+    assert_eq!(
+        select(&["libcore"], &[], true).unwrap(),
+        vec!["app", "libcore"]
+    );
+    assert_eq!(
+        select(&["libbase-0.1.0"], &[], true).unwrap(),
+        vec!["app", "libbase", "libcore"]
+    );
+    assert_eq!(
+        select(&["tool"], &["net"], false).unwrap(),
+        vec!["libnet", "tool"]
+    );
+
+    let err = select(&["libcroe"], &[], false).unwrap_err();
+    assert!(
+        format!("{:?}", err).contains("unknown task 'libcroe', close matches: libcore"),
+        "{:?}",
+        err
+    );
+    let err = select(&["nothing_like_it"], &[], false).unwrap_err();
+    assert!(
+        format!("{:?}", err).ends_with("unknown task 'nothing_like_it'"),
+        "{:?}",
+        err
+    );
+    let err = select(&[], &["nett"], false).unwrap_err();
+    assert!(
+        format!("{:?}", err).contains("no task has tag 'nett', close matches: net"),
+        "{:?}",
+        err
+    );
+}
@@ -2,7 +2,7 @@ use std::sync::Mutex;
 
 use log::error;
 
-use crate::executor::ExecutorError;
+use crate::executor::ExecError;
 
 lazy_static! {
     // 全局内置target
@@ -34,7 +34,7 @@ impl InlineTargets {
         ));
     }
 
-    pub fn get(&self, rust_target: &str) -> Result<&'static [u8], ExecutorError> {
+    pub fn get(&self, rust_target: &str) -> Result<&'static [u8], ExecError> {
         // 通过rust_target找到对应的binary数据
         for (name, data) in &self.inline_list {
             if name == rust_target {
@@ -44,6 +44,6 @@ impl InlineTargets {
 
         let errmsg = format!("无效的内置target文件: {}", rust_target);
         error!("{errmsg}");
-        return Err(ExecutorError::PrepareEnvError(errmsg));
+        return Err(ExecError::Validation(errmsg));
     }
 }
@@ -1,10 +1,16 @@
 use std::{
+    collections::BTreeMap,
     fs::File,
-    path::Path,
+    io::{Read, Write},
+    path::{Component, Path, PathBuf},
     process::{Command, Stdio},
 };
 
-use reqwest::{blocking::ClientBuilder, Url};
+use log::debug;
+use reqwest::{
+    blocking::{ClientBuilder, Response},
+    Url,
+};
 
 use super::stdio::StdioUtils;
 
@@ -12,7 +18,13 @@ pub struct FileUtils;
 
 impl FileUtils {
     ///从指定url下载文件到指定路径
-    pub fn download_file(url: &str, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    ///
+    /// 每写入一块数据，调用一次`progress(已下载的字节数, 总字节数)`
+    pub fn download_file(
+        url: &str,
+        path: &Path,
+        progress: &dyn Fn(u64, Option<u64>),
+    ) -> Result<(), Box<dyn std::error::Error>> {
         let tempurl = Url::parse(url).expect("failed to parse the url");
         let file_name = tempurl
             .path_segments()
@@ -22,12 +34,55 @@ impl FileUtils {
         let client = ClientBuilder::new()
             .timeout(std::time::Duration::from_secs(10))
             .build()?;
-        let mut response = client.get(url).send()?;
-        let mut file = File::create(path.join(file_name))?;
-        response.copy_to(&mut file)?;
+        let response = client.get(url).send()?;
+        return Self::write_response(response, &path.join(file_name), progress);
+    }
+
+    /// 把HTTP响应的内容写入文件，每写入一块数据报告一次进度
+    pub fn write_response(
+        mut response: Response,
+        file_path: &Path,
+        progress: &dyn Fn(u64, Option<u64>),
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let total = response.content_length();
+        let mut file = File::create(file_path)?;
+        let mut buf = vec![0u8; 64 * 1024];
+        let mut downloaded: u64 = 0;
+        progress(downloaded, total);
+        loop {
+            let n = response.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            file.write_all(&buf[..n])?;
+            downloaded += n as u64;
+            progress(downloaded, total);
+        }
         Ok(())
     }
 
+    /// # 按字面规范化路径
+    ///
+    /// 去掉`.`，并用`..`抵消前一级目录，不访问文件系统（路径可以不存在，也不会解析符号链接）。
+    /// 绝对路径中跳出根目录的`..`会被忽略
+    pub fn normalize_path(path: &Path) -> PathBuf {
+        let mut normalized = PathBuf::new();
+        for component in path.components() {
+            match component {
+                Component::CurDir => {}
+                Component::ParentDir => match normalized.components().next_back() {
+                    Some(Component::Normal(_)) => {
+                        normalized.pop();
+                    }
+                    Some(Component::RootDir) | Some(Component::Prefix(_)) => {}
+                    _ => normalized.push(".."),
+                },
+                c => normalized.push(c),
+            }
+        }
+        return normalized;
+    }
+
     /// 把指定路径下所有文件和文件夹递归地移动到另一个文件中
     pub fn move_files(src: &Path, dst: &Path) -> std::io::Result<()> {
         for entry in src.read_dir()? {
@@ -46,8 +101,41 @@ impl FileUtils {
 
     /// 递归地复制给定目录下所有文件到另一个文件夹中
     pub fn copy_dir_all(src: &Path, dst: &Path) -> Result<(), String> {
+        return Self::copy_dir_with_symlinks(src, dst, false);
+    }
+
+    /// # 递归地复制目录，并指定如何处理符号链接
+    ///
+    /// - `follow_symlinks`为false时，符号链接被原样重建（包括失效的符号链接）
+    /// - `follow_symlinks`为true时，复制符号链接指向的内容；存在失效的符号链接时报错，并列出它们
+    pub fn copy_dir_with_symlinks(
+        src: &Path,
+        dst: &Path,
+        follow_symlinks: bool,
+    ) -> Result<(), String> {
+        if follow_symlinks {
+            let dangling = Self::dangling_symlinks(src)
+                .map_err(|e| format!("Failed to check symlinks in {}: {}", src.display(), e))?;
+            if !dangling.is_empty() {
+                let list: Vec<String> = dangling
+                    .iter()
+                    .map(|(link, target)| format!("{} -> {}", link.display(), target.display()))
+                    .collect();
+                return Err(format!(
+                    "Cannot follow dangling symlinks: {}",
+                    list.join(", ")
+                ));
+            }
+        }
+
         let mut cmd = Command::new("cp");
-        cmd.arg("-r").arg("-f").arg("./").arg(dst);
+        cmd.arg("-r").arg("-f");
+        if follow_symlinks {
+            cmd.arg("-L");
+        } else {
+            cmd.arg("-P");
+        }
+        cmd.arg("./").arg(dst);
 
         cmd.current_dir(src);
 
@@ -67,4 +155,146 @@ impl FileUtils {
         }
         Ok(())
     }
+
+    /// # 递归地复制目录中被选中的文件
+    ///
+    /// `selected`的参数是文件相对于`src`的路径。符号链接的处理与[`Self::copy_dir_with_symlinks`]相同，
+    /// 跟随符号链接时，指向目录的符号链接被选中后会复制整个目录。
+    ///
+    /// 返回复制了的文件（相对路径），按路径排序
+    pub fn copy_dir_filtered(
+        src: &Path,
+        dst: &Path,
+        follow_symlinks: bool,
+        selected: &dyn Fn(&Path) -> bool,
+    ) -> Result<Vec<PathBuf>, String> {
+        let files: Vec<PathBuf> = Self::list_files_recursive(src)
+            .map_err(|e| format!("Failed to list files in {}: {}", src.display(), e))?
+            .iter()
+            .map(|f| f.strip_prefix(src).unwrap().to_path_buf())
+            .filter(|f| selected(f))
+            .collect();
+        for relative in files.iter() {
+            let file = src.join(relative);
+            let target = dst.join(relative);
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+            }
+            if target.symlink_metadata().is_ok() && !target.is_dir() {
+                std::fs::remove_file(&target).map_err(|e| e.to_string())?;
+            }
+            if file.is_symlink() && !follow_symlinks {
+                let link = std::fs::read_link(&file).map_err(|e| e.to_string())?;
+                std::os::unix::fs::symlink(link, &target).map_err(|e| e.to_string())?;
+            } else if file.is_dir() {
+                std::fs::create_dir_all(&target).map_err(|e| e.to_string())?;
+                Self::copy_dir_with_symlinks(&file, &target, true)?;
+            } else {
+                std::fs::copy(&file, &target)
+                    .map_err(|e| format!("Failed to copy {}: {}", file.display(), e))?;
+            }
+        }
+        return Ok(files);
+    }
+
+    /// # 列出目录下所有失效的符号链接
+    ///
+    /// 返回`(相对路径, 链接指向的目标)`，按路径排序
+    pub fn dangling_symlinks(dir: &Path) -> std::io::Result<Vec<(PathBuf, PathBuf)>> {
+        let mut dangling = Vec::new();
+        for file in Self::list_files_recursive(dir)? {
+            if file.symlink_metadata()?.file_type().is_symlink() && file.metadata().is_err() {
+                let target = std::fs::read_link(&file)?;
+                dangling.push((file.strip_prefix(dir).unwrap().to_path_buf(), target));
+            }
+        }
+        return Ok(dangling);
+    }
+
+    /// # 计算文件或目录占用的字节数
+    ///
+    /// 目录递归地累加其中所有文件的大小，不跟随符号链接，路径不存在时为0
+    pub fn disk_usage(path: &Path) -> std::io::Result<u64> {
+        let meta = match path.symlink_metadata() {
+            Ok(meta) => meta,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e),
+        };
+        if !meta.is_dir() {
+            return Ok(meta.len());
+        }
+        let mut total = 0;
+        for file in Self::list_files_recursive(path)? {
+            total += file.symlink_metadata()?.len();
+        }
+        return Ok(total);
+    }
+
+    /// # 递归地列出给定目录下的所有文件，结果按路径排序
+    ///
+    /// 不包括目录本身。符号链接不会被跟随，而是作为一项列出（即使它指向目录），
+    /// 由调用者决定是保留链接还是跟随它
+    pub fn list_files_recursive(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        Self::collect_files(dir, &mut files)?;
+        files.sort();
+        Ok(files)
+    }
+
+    /// # 把多个目录按顺序叠加到目标目录中
+    ///
+    /// 后面的目录中的文件会覆盖前面的目录中的同名文件，覆盖时会输出debug日志。
+    /// 各目录顶层的`.git`目录不会被拷贝，符号链接会被原样保留。
+    pub fn overlay_dirs(layers: &[PathBuf], target: &Path) -> Result<(), String> {
+        // 每个文件来自哪一层
+        let mut owners: BTreeMap<PathBuf, usize> = BTreeMap::new();
+        for (i, layer) in layers.iter().enumerate() {
+            let files = Self::list_files_recursive(layer)
+                .map_err(|e| format!("Failed to list files in layer {}: {}", layer.display(), e))?;
+            for file in files {
+                let relative = file.strip_prefix(layer).unwrap().to_path_buf();
+                if relative.components().next() == Some(Component::Normal(".git".as_ref())) {
+                    continue;
+                }
+                if let Some(prev) = owners.insert(relative.clone(), i) {
+                    debug!(
+                        "Overlay conflict: {} from layer {} overrides the one from layer {}",
+                        relative.display(),
+                        i,
+                        prev
+                    );
+                }
+
+                let dst = target.join(&relative);
+                if let Some(parent) = dst.parent() {
+                    std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+                }
+                if dst.symlink_metadata().is_ok() {
+                    std::fs::remove_file(&dst).map_err(|e| e.to_string())?;
+                }
+                if file.is_symlink() {
+                    let link = std::fs::read_link(&file).map_err(|e| e.to_string())?;
+                    std::os::unix::fs::symlink(link, &dst).map_err(|e| e.to_string())?;
+                } else {
+                    std::fs::copy(&file, &dst)
+                        .map_err(|e| format!("Failed to copy {}: {}", file.display(), e))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+        for entry in dir.read_dir()? {
+            let entry = entry?;
+            let path = entry.path();
+            // `DirEntry::file_type`不跟随符号链接：指向目录的符号链接作为一项列出，不会递归进去
+            if entry.file_type()?.is_dir() {
+                Self::collect_files(&path, files)?;
+            } else {
+                files.push(path);
+            }
+        }
+        Ok(())
+    }
 }
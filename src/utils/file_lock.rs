@@ -0,0 +1,96 @@
+use std::{
+    fs::{File, OpenOptions},
+    os::fd::AsRawFd,
+    path::{Path, PathBuf},
+};
+
+use log::info;
+
+/// # 基于文件的建议锁
+///
+/// 使用`flock`对锁文件加排他锁，可以在多个DADK进程（以及同一进程的多个线程）之间互斥。
+/// 锁在`FileLock`被drop时释放，因此即使持有锁的线程panic，锁也会在栈展开时被释放；
+/// 进程意外退出时，操作系统会在关闭文件时释放锁。
+#[derive(Debug)]
+pub struct FileLock {
+    file: File,
+    path: PathBuf,
+}
+
+impl FileLock {
+    /// # 获取锁
+    ///
+    /// 如果锁已经被其他进程持有，则输出日志并阻塞等待
+    pub fn acquire(path: &Path) -> std::io::Result<Self> {
+        if let Some(lock) = Self::try_acquire(path)? {
+            return Ok(lock);
+        }
+        info!(
+            "Waiting for lock {} held by another process",
+            path.display()
+        );
+        let file = Self::open(path)?;
+        Self::flock(&file, libc::LOCK_EX)?;
+        return Ok(Self {
+            file,
+            path: path.to_path_buf(),
+        });
+    }
+
+    /// # 尝试获取锁
+    ///
+    /// 锁已经被其他进程持有时，返回`Ok(None)`，不会阻塞
+    pub fn try_acquire(path: &Path) -> std::io::Result<Option<Self>> {
+        let file = Self::open(path)?;
+        match Self::flock(&file, libc::LOCK_EX | libc::LOCK_NB) {
+            Ok(()) => {
+                return Ok(Some(Self {
+                    file,
+                    path: path.to_path_buf(),
+                }))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => return Ok(None),
+            Err(e) => return Err(e),
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn path(&self) -> &Path {
+        return &self.path;
+    }
+
+    fn open(path: &Path) -> std::io::Result<File> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        return OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path);
+    }
+
+    fn flock(file: &File, operation: libc::c_int) -> std::io::Result<()> {
+        loop {
+            let r = unsafe { libc::flock(file.as_raw_fd(), operation) };
+            if r == 0 {
+                return Ok(());
+            }
+            let err = std::io::Error::last_os_error();
+            // 被信号打断时重试
+            if err.kind() != std::io::ErrorKind::Interrupted {
+                return Err(err);
+            }
+        }
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        // 关闭文件也会释放锁，这里显式释放，便于尽早唤醒等待者
+        unsafe {
+            libc::flock(self.file.as_raw_fd(), libc::LOCK_UN);
+        }
+    }
+}
@@ -0,0 +1,127 @@
+use std::{
+    fmt::Write,
+    fs::File,
+    io::Read,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+};
+
+use sha2::{Digest, Sha256};
+
+use super::ignore::IgnoreMatcher;
+
+pub struct HashUtils;
+
+impl HashUtils {
+    /// # 计算文件的sha256值
+    ///
+    /// 返回小写的十六进制字符串
+    pub fn sha256_file(path: &Path) -> std::io::Result<String> {
+        let mut hasher = Sha256::new();
+        Self::update_with_file(&mut hasher, path)?;
+        return Ok(Self::hex(&hasher.finalize()));
+    }
+
+    /// # 计算字节数组的sha256值
+    pub fn sha256_bytes(bytes: &[u8]) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(bytes);
+        return Self::hex(&hasher.finalize());
+    }
+
+    /// # 计算目录的哈希值
+    ///
+    /// 按照相对路径排序后，依次对每一项的类型、相对路径、权限位以及内容计算哈希值，
+    /// 因此结果与遍历顺序无关，文件内容改变或者被chmod后哈希值都会改变。
+    ///
+    /// - 被`ignore`忽略的项（以及被忽略的目录下的所有内容）不参与计算
+    /// - 符号链接不会被跟随，只对链接指向的目标字符串计算哈希值
+    /// - 如果`path`是文件，则只对这个文件计算（相对路径为空）
+    ///
+    /// 使用BLAKE3计算，返回小写的十六进制字符串。
+    pub fn hash_dir(path: &Path, ignore: &IgnoreMatcher) -> std::io::Result<String> {
+        let mut entries = Vec::new();
+        if path.symlink_metadata()?.is_dir() {
+            Self::collect_entries(path, Path::new(""), ignore, &mut entries)?;
+        } else {
+            entries.push(PathBuf::new());
+        }
+        entries.sort();
+
+        let mut hasher = blake3::Hasher::new();
+        for relative in entries.iter() {
+            let full = path.join(relative);
+            let metadata = full.symlink_metadata()?;
+            let file_type = metadata.file_type();
+            let tag: &[u8] = if file_type.is_symlink() {
+                b"l"
+            } else if file_type.is_dir() {
+                b"d"
+            } else {
+                b"f"
+            };
+            hasher.update(tag);
+            hasher.update(&[0u8]);
+            hasher.update(relative.to_string_lossy().as_bytes());
+            hasher.update(&[0u8]);
+
+            if file_type.is_symlink() {
+                // 不跟随符号链接，只记录它指向哪里
+                let target = std::fs::read_link(&full)?;
+                hasher.update(target.to_string_lossy().as_bytes());
+            } else {
+                hasher.update(format!("{:o}", metadata.permissions().mode() & 0o7777).as_bytes());
+                if file_type.is_file() {
+                    hasher.update(&[0u8]);
+                    hasher.update(&metadata.len().to_le_bytes());
+                    std::io::copy(&mut File::open(&full)?, &mut hasher)?;
+                }
+            }
+            hasher.update(&[0u8]);
+        }
+        return Ok(hasher.finalize().to_hex().to_string());
+    }
+
+    /// 收集目录下所有未被忽略的项的相对路径（不跟随符号链接）
+    fn collect_entries(
+        root: &Path,
+        relative: &Path,
+        ignore: &IgnoreMatcher,
+        entries: &mut Vec<PathBuf>,
+    ) -> std::io::Result<()> {
+        for entry in root.join(relative).read_dir()? {
+            let entry = entry?;
+            let child = relative.join(entry.file_name());
+            if ignore.is_ignored(&child) {
+                continue;
+            }
+            let is_dir = entry.file_type()?.is_dir();
+            entries.push(child.clone());
+            if is_dir {
+                Self::collect_entries(root, &child, ignore, entries)?;
+            }
+        }
+        return Ok(());
+    }
+
+    fn update_with_file(hasher: &mut Sha256, path: &Path) -> std::io::Result<()> {
+        let mut file = File::open(path)?;
+        let mut buf = [0u8; 8192];
+        loop {
+            let n = file.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buf[..n]);
+        }
+        return Ok(());
+    }
+
+    pub fn hex(bytes: &[u8]) -> String {
+        let mut s = String::with_capacity(bytes.len() * 2);
+        for b in bytes {
+            write!(s, "{:02x}", b).unwrap();
+        }
+        return s;
+    }
+}
@@ -0,0 +1,107 @@
+use std::path::{Component, Path};
+
+use regex::Regex;
+
+/// # 忽略规则匹配器
+///
+/// 用glob模式描述需要忽略的路径，路径都是相对于被遍历的根目录的：
+///
+/// - `*`匹配除`/`以外的任意字符，`?`匹配除`/`以外的单个字符，`**`匹配任意字符（包括`/`），`**/`还可以匹配零级目录
+/// - 不含`/`的模式与路径中的任意一级名称匹配，例如`target`会忽略所有名为`target`的文件或目录
+/// - 含有`/`的模式与从根目录开始的完整相对路径匹配，例如`docs/*.md`
+///
+/// 目录被忽略时，其下的所有内容也会被忽略。
+#[derive(Debug, Clone, Default)]
+pub struct IgnoreMatcher {
+    /// 与任意一级名称匹配的规则
+    names: Vec<Regex>,
+    /// 与完整相对路径匹配的规则
+    paths: Vec<Regex>,
+}
+
+impl IgnoreMatcher {
+    /// 不忽略任何路径的匹配器
+    pub fn empty() -> Self {
+        return Self::default();
+    }
+
+    pub fn new<S: AsRef<str>>(patterns: &[S]) -> Result<Self, String> {
+        let mut matcher = Self::default();
+        for pattern in patterns {
+            let pattern = pattern.as_ref().trim().trim_start_matches('/');
+            if pattern.is_empty() {
+                return Err("ignore pattern is empty".to_string());
+            }
+            let pattern = pattern.trim_end_matches('/');
+            let re = Self::glob_regex(pattern)
+                .map_err(|e| format!("invalid ignore pattern {:?}: {}", pattern, e))?;
+            if pattern.contains('/') {
+                matcher.paths.push(re);
+            } else {
+                matcher.names.push(re);
+            }
+        }
+        return Ok(matcher);
+    }
+
+    /// # 忽略一个确定的相对路径
+    ///
+    /// 路径按字面匹配（不解释通配符），并且总是从根目录开始匹配，即使它只有一级
+    pub fn add_path(&mut self, relative: &Path) {
+        let path = relative.to_string_lossy();
+        let path = path.trim_start_matches("./").trim_matches('/');
+        if path.is_empty() {
+            return;
+        }
+        let re = Regex::new(&format!("^{}$", regex::escape(path))).unwrap();
+        self.paths.push(re);
+    }
+
+    /// # 判断相对路径是否被忽略
+    ///
+    /// 路径本身或者它的任意一级父目录匹配规则时，都认为被忽略
+    pub fn is_ignored(&self, relative: &Path) -> bool {
+        let mut prefix = String::new();
+        for component in relative.components() {
+            let name = match component {
+                Component::Normal(name) => name.to_string_lossy(),
+                _ => continue,
+            };
+            if self.names.iter().any(|re| re.is_match(&name)) {
+                return true;
+            }
+            if !prefix.is_empty() {
+                prefix.push('/');
+            }
+            prefix.push_str(&name);
+            if self.paths.iter().any(|re| re.is_match(&prefix)) {
+                return true;
+            }
+        }
+        return false;
+    }
+
+    fn glob_regex(pattern: &str) -> Result<Regex, regex::Error> {
+        let mut re = String::from("^");
+        let mut chars = pattern.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '*' if chars.peek() == Some(&'*') => {
+                    chars.next();
+                    if chars.peek() == Some(&'/') {
+                        // `**/`也匹配零级目录，例如`**/*.o`匹配`a.o`
+                        chars.next();
+                        re.push_str("(?:.*/)?");
+                    } else {
+                        re.push_str(".*");
+                    }
+                }
+                '*' => re.push_str("[^/]*"),
+                '?' => re.push_str("[^/]"),
+                _ => re.push_str(&regex::escape(&c.to_string())),
+            }
+        }
+        re.push('$');
+        return Regex::new(&re);
+    }
+}
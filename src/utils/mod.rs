@@ -1,3 +1,9 @@
 pub mod file;
+pub mod file_lock;
+pub mod hash;
+pub mod ignore;
 pub mod lazy_init;
 pub mod stdio;
+pub mod suggest;
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,63 @@
+/// # 拼写建议
+///
+/// 用于在用户输入了未知的名称时，从合法的名称中找出最接近的一个作为建议
+pub struct SuggestUtils;
+
+impl SuggestUtils {
+    /// # 计算两个字符串之间的编辑距离
+    ///
+    /// 在Levenshtein距离的基础上，把相邻两个字符的交换也算作一次编辑（如`biuld`与`build`的距离为1），
+    /// 这是最常见的拼写错误
+    pub fn edit_distance(a: &str, b: &str) -> usize {
+        let a: Vec<char> = a.chars().collect();
+        let b: Vec<char> = b.chars().collect();
+        let mut d = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+        for (i, row) in d.iter_mut().enumerate() {
+            row[0] = i;
+        }
+        for j in 0..=b.len() {
+            d[0][j] = j;
+        }
+        for i in 1..=a.len() {
+            for j in 1..=b.len() {
+                let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+                d[i][j] = (d[i - 1][j] + 1)
+                    .min(d[i][j - 1] + 1)
+                    .min(d[i - 1][j - 1] + cost);
+                if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                    d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+                }
+            }
+        }
+        return d[a.len()][b.len()];
+    }
+
+    /// # 从候选项中找出与输入最接近的一个
+    ///
+    /// 只有编辑距离足够小（不超过输入长度的三分之一，且至少允许1）时才返回建议，
+    /// 以免给出毫不相关的建议
+    pub fn did_you_mean<'a>(input: &str, candidates: &[&'a str]) -> Option<&'a str> {
+        return Self::close_matches(input, candidates, 1).into_iter().next();
+    }
+
+    /// # 从候选项中找出与输入接近的若干个
+    ///
+    /// 与[`SuggestUtils::did_you_mean`]使用相同的距离限制，按编辑距离从小到大排列
+    /// （距离相同时保持候选项的顺序），去掉重复的候选项，最多返回`limit`个
+    pub fn close_matches<'a>(input: &str, candidates: &[&'a str], limit: usize) -> Vec<&'a str> {
+        let max_distance = (input.chars().count() / 3).max(1);
+        let mut matches: Vec<(usize, &str)> = candidates
+            .iter()
+            .map(|c| (Self::edit_distance(input, c), *c))
+            .filter(|(d, _)| *d <= max_distance)
+            .collect();
+        matches.sort_by_key(|(d, _)| *d);
+        let mut result: Vec<&str> = Vec::new();
+        for (_, c) in matches {
+            if result.len() < limit && !result.contains(&c) {
+                result.push(c);
+            }
+        }
+        return result;
+    }
+}
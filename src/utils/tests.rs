@@ -0,0 +1,173 @@
+use std::{
+    fs,
+    io::Write,
+    os::unix::fs::PermissionsExt,
+    path::Path,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use test_base::{
+    test_context::{self as test_context, test_context},
+    BaseTestContext,
+};
+
+use super::{file::FileUtils, file_lock::FileLock, hash::HashUtils, ignore::IgnoreMatcher};
+
+fn prepare_hash_dir(dir: &Path) {
+    if dir.exists() {
+        fs::remove_dir_all(dir).unwrap();
+    }
+    fs::create_dir_all(dir.join("src")).unwrap();
+    fs::create_dir_all(dir.join("target/debug")).unwrap();
+    fs::write(dir.join("build.sh"), "echo build").unwrap();
+    fs::write(dir.join("src/main.c"), "int main() { return 0; }").unwrap();
+    fs::write(dir.join("target/debug/app"), "binary").unwrap();
+}
+
+#[test_context(BaseTestContext)]
+#[test]
+fn hash_dir_is_stable(ctx: &mut BaseTestContext) {
+    let dir = ctx.fake_dadk_cache_root().join("hash_dir_stable");
+    prepare_hash_dir(&dir);
+    let ignore = IgnoreMatcher::new(&["target"]).unwrap();
+
+    let first = HashUtils::hash_dir(&dir, &ignore).unwrap();
+    let second = HashUtils::hash_dir(&dir, &ignore).unwrap();
+    assert_eq!(first, second);
+
+    // 被忽略的内容改变时，哈希值不变
+    fs::write(dir.join("target/debug/app"), "another binary").unwrap();
+    assert_eq!(first, HashUtils::hash_dir(&dir, &ignore).unwrap());
+
+    // 重新创建相同的目录，哈希值不变
+    prepare_hash_dir(&dir);
+    assert_eq!(first, HashUtils::hash_dir(&dir, &ignore).unwrap());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test_context(BaseTestContext)]
+#[test]
+fn hash_dir_detects_content_and_mode(ctx: &mut BaseTestContext) {
+    let dir = ctx.fake_dadk_cache_root().join("hash_dir_changes");
+    prepare_hash_dir(&dir);
+    let ignore = IgnoreMatcher::empty();
+    let origin = HashUtils::hash_dir(&dir, &ignore).unwrap();
+
+    fs::write(dir.join("src/main.c"), "int main() { return 1; }").unwrap();
+    let changed = HashUtils::hash_dir(&dir, &ignore).unwrap();
+    assert_ne!(origin, changed);
+
+    fs::set_permissions(dir.join("build.sh"), fs::Permissions::from_mode(0o755)).unwrap();
+    let chmoded = HashUtils::hash_dir(&dir, &ignore).unwrap();
+    assert_ne!(changed, chmoded);
+
+    // 符号链接只按照目标字符串计算，不跟随
+    std::os::unix::fs::symlink("src/main.c", dir.join("link")).unwrap();
+    let linked = HashUtils::hash_dir(&dir, &ignore).unwrap();
+    assert_ne!(chmoded, linked);
+    fs::write(dir.join("src/main.c"), "int main() { return 2; }").unwrap();
+    let target_changed = HashUtils::hash_dir(&dir, &ignore).unwrap();
+    fs::remove_file(dir.join("link")).unwrap();
+    std::os::unix::fs::symlink("src/other.c", dir.join("link")).unwrap();
+    assert_ne!(target_changed, HashUtils::hash_dir(&dir, &ignore).unwrap());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn ignore_matcher_patterns() {
+    let ignore = IgnoreMatcher::new(&["target", "*.o", "docs/**/*.md"]).unwrap();
+    assert!(ignore.is_ignored(Path::new("target")));
+    assert!(ignore.is_ignored(Path::new("sub/target/debug/app")));
+    assert!(ignore.is_ignored(Path::new("src/main.o")));
+    assert!(ignore.is_ignored(Path::new("docs/a/b/readme.md")));
+    assert!(!ignore.is_ignored(Path::new("readme.md")));
+    assert!(!ignore.is_ignored(Path::new("src/main.c")));
+    assert!(IgnoreMatcher::new(&[" "]).is_err());
+
+    // `**/`也匹配零级目录
+    let ignore = IgnoreMatcher::new(&["**/*.a", "docs/**/index.md"]).unwrap();
+    assert!(ignore.is_ignored(Path::new("libfoo.a")));
+    assert!(ignore.is_ignored(Path::new("lib/x86_64/libfoo.a")));
+    assert!(ignore.is_ignored(Path::new("docs/index.md")));
+    assert!(ignore.is_ignored(Path::new("docs/a/index.md")));
+    assert!(!ignore.is_ignored(Path::new("docsindex.md")));
+
+    // 按字面添加的路径只从根目录开始匹配
+    let mut ignore = IgnoreMatcher::empty();
+    ignore.add_path(Path::new("./out/"));
+    assert!(ignore.is_ignored(Path::new("out/app")));
+    assert!(!ignore.is_ignored(Path::new("src/out")));
+}
+
+/// 指向目录的符号链接作为一项列出，不会递归进去
+#[test_context(BaseTestContext)]
+#[test]
+fn list_files_keeps_dir_symlinks(ctx: &mut BaseTestContext) {
+    let dir = ctx.fake_dadk_cache_root().join("list_files_symlinks");
+    prepare_hash_dir(&dir);
+    std::os::unix::fs::symlink("src", dir.join("src_link")).unwrap();
+
+    let files = FileUtils::list_files_recursive(&dir).unwrap();
+    assert!(files.contains(&dir.join("src_link")));
+    assert!(!files.contains(&dir.join("src_link/main.c")));
+    assert!(files.contains(&dir.join("src/main.c")));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+/// 两个线程争用同一个锁，持有锁期间写入并读回缓存文件，内容不应该被另一个线程破坏
+#[test_context(BaseTestContext)]
+#[test]
+fn file_lock_serializes_cache_access(ctx: &mut BaseTestContext) {
+    let dir = ctx.fake_dadk_cache_root().join("file_lock_contention");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let lock_path = dir.join("locks/app-0.1.0-x86_64.lock");
+    let cache_file = dir.join("cache.txt");
+    let holders = Arc::new(AtomicUsize::new(0));
+
+    let workers: Vec<_> = (0..2)
+        .map(|id| {
+            let lock_path = lock_path.clone();
+            let cache_file = cache_file.clone();
+            let holders = holders.clone();
+            std::thread::spawn(move || {
+                for round in 0..20 {
+                    let _lock = FileLock::acquire(&lock_path).unwrap();
+                    assert_eq!(holders.fetch_add(1, Ordering::SeqCst), 0);
+                    let content = format!("worker {} round {}", id, round);
+                    let mut file = fs::File::create(&cache_file).unwrap();
+                    for b in content.as_bytes() {
+                        file.write_all(&[*b]).unwrap();
+                        std::thread::yield_now();
+                    }
+                    drop(file);
+                    assert_eq!(fs::read_to_string(&cache_file).unwrap(), content);
+                    holders.fetch_sub(1, Ordering::SeqCst);
+                }
+            })
+        })
+        .collect();
+    for worker in workers {
+        worker.join().unwrap();
+    }
+
+    // 持有锁的线程panic时，锁也会被释放
+    let panicked = {
+        let lock_path = lock_path.clone();
+        std::thread::spawn(move || {
+            let _lock = FileLock::acquire(&lock_path).unwrap();
+            panic!("panic while holding the cache lock");
+        })
+        .join()
+    };
+    assert!(panicked.is_err());
+    assert!(FileLock::try_acquire(&lock_path).unwrap().is_some());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,5 @@
+fn main() {
+    if cfg!(feature = "greet") {
+        println!("hello from app_cargo");
+    }
+}
@@ -0,0 +1,7 @@
+#include <stdio.h>
+
+int main(void)
+{
+    printf("hello from app_cmake\n");
+    return 0;
+}
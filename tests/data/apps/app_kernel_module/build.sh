@@ -0,0 +1,2 @@
+echo "app_kernel_module: build against $DADK_KERNEL_SRC ($DADK_KERNEL_ARCH)"
+touch $DADK_CURRENT_BUILD_DIR/hello.ko
@@ -0,0 +1,2 @@
+echo "app_overlay_patch: build"
+cp extra.txt $DADK_CURRENT_BUILD_DIR/
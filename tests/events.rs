@@ -0,0 +1,117 @@
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// 创建一个只有一个提交的git仓库，作为任务的本地源码
+fn init_git_repo(dir: &Path) {
+    std::fs::create_dir_all(dir).unwrap();
+    std::fs::write(
+        dir.join("build.sh"),
+        "echo build-stdout\necho build-stderr >&2\n",
+    )
+    .unwrap();
+    for args in [
+        vec!["init", "-q"],
+        vec!["checkout", "-q", "-b", "main"],
+        vec!["add", "build.sh"],
+        vec![
+            "-c",
+            "user.name=dadk",
+            "-c",
+            "user.email=dadk@localhost",
+            "commit",
+            "-q",
+            "-m",
+            "init",
+        ],
+    ] {
+        let status = Command::new("git")
+            .args(&args)
+            .current_dir(dir)
+            .status()
+            .unwrap();
+        assert!(status.success(), "git {:?} failed", args);
+    }
+}
+
+fn write_config(config_dir: &Path, repo: &Path) {
+    std::fs::create_dir_all(config_dir).unwrap();
+    let config = serde_json::json!({
+        "name": "app_events_stdout",
+        "version": "0.1.0",
+        "description": "Prints to stdout while building",
+        "rust_target": null,
+        "task_type": {
+            "BuildFromSource": {
+                "Local": {
+                    "path": repo.to_string_lossy()
+                }
+            }
+        },
+        "depends": [],
+        "build": {
+            "build_command": "bash build.sh && git log --oneline"
+        },
+        "install": {
+            "in_dragonos_path": "/"
+        },
+        "clean": {
+            "clean_command": null
+        },
+        "envs": [],
+        "build_once": false,
+        "install_once": false
+    });
+    std::fs::write(
+        config_dir.join("app_events_stdout_0_1_0.dadk"),
+        serde_json::to_string_pretty(&config).unwrap(),
+    )
+    .unwrap();
+}
+
+/// 测试`--events -`：标准输出中只有事件，构建命令（包括其中的git命令）的输出都在标准错误中
+#[test]
+fn events_on_stdout_are_ndjson() {
+    let work = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("events_on_stdout_are_ndjson");
+    let _ = std::fs::remove_dir_all(&work);
+    let repo = work.join("repo");
+    let config_dir = work.join("config");
+    let cache_dir = work.join("cache");
+    let sysroot = work.join("sysroot");
+    init_git_repo(&repo);
+    write_config(&config_dir, &repo);
+    std::fs::create_dir_all(&cache_dir).unwrap();
+    std::fs::create_dir_all(&sysroot).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_dadk"))
+        .arg("--config-dir")
+        .arg(&config_dir)
+        .arg("--cache-dir")
+        .arg(&cache_dir)
+        .arg("--dragonos-dir")
+        .arg(&sysroot)
+        .arg("--events")
+        .arg("-")
+        .arg("build")
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "stderr: {}", stderr);
+
+    let events: Vec<serde_json::Value> = stdout
+        .lines()
+        .map(|line| {
+            serde_json::from_str(line)
+                .unwrap_or_else(|e| panic!("stdout line {:?} is not JSON: {}", line, e))
+        })
+        .collect();
+    assert_eq!(events.first().unwrap()["event"], "task_started");
+    assert_eq!(events.last().unwrap()["event"], "task_completed");
+    assert!(stderr.contains("build-stdout"), "stderr: {}", stderr);
+    assert!(stderr.contains("build-stderr"), "stderr: {}", stderr);
+    assert!(stderr.contains("init"), "stderr: {}", stderr);
+
+    std::fs::remove_dir_all(&work).unwrap();
+}